@@ -0,0 +1,36 @@
+use tonic_build::manual::{Builder, Method, Service};
+
+/// Generate the gRPC server stubs for `proto/dubdub.proto`
+///
+/// The service is described here rather than compiled from the .proto so
+/// the build doesn't depend on protoc; messages live in `src/grpc.rs`.
+fn main() {
+    let method = |name: &str, route: &str, input: &str, output: &str| {
+        Method::builder()
+            .name(name)
+            .route_name(route)
+            .input_type(format!("crate::grpc::pb::{}", input))
+            .output_type(format!("crate::grpc::pb::{}", output))
+            .codec_path("tonic_prost::ProstCodec")
+    };
+
+    let service = Service::builder()
+        .name("Dubdub")
+        .package("dubdub.v1")
+        .method(method("tokenize", "Tokenize", "TokenizeRequest", "TokenizeResponse").build())
+        .method(method("batch_tokenize", "BatchTokenize", "BatchTokenizeRequest", "BatchTokenizeResponse").build())
+        .method(method("align", "Align", "AlignRequest", "AlignResponse").build())
+        .method(
+            method("batch_align", "BatchAlign", "AlignRequest", "AlignResponse")
+                .client_streaming()
+                .server_streaming()
+                .build(),
+        )
+        .build();
+
+    Builder::new()
+        .build_client(false)
+        .compile(&[service]);
+
+    println!("cargo:rerun-if-changed=build.rs");
+}
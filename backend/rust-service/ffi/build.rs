@@ -0,0 +1,25 @@
+use std::env;
+use std::path::PathBuf;
+
+/// Generate the C header from the `extern "C"` functions into `OUT_DIR`
+///
+/// `include/dubdub.h` is checked in so app builds can use it without Rust
+/// tooling; it's only rewritten with `--features regenerate-header`, and a
+/// test checks it matches the generated one.
+fn main() {
+    let crate_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+
+    match cbindgen::generate(&crate_dir) {
+        Ok(bindings) => {
+            bindings.write_to_file(out_dir.join("dubdub.h"));
+            if env::var_os("CARGO_FEATURE_REGENERATE_HEADER").is_some() {
+                bindings.write_to_file(crate_dir.join("include/dubdub.h"));
+            }
+        }
+        Err(e) => println!("cargo:warning=Can't generate dubdub.h: {}", e),
+    }
+
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+}
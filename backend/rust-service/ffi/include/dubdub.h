@@ -0,0 +1,74 @@
+#ifndef DUBDUB_H
+#define DUBDUB_H
+
+/* Generated by cbindgen from ffi/src/lib.rs; don't edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Outcome of a call
+typedef enum DubdubStatus {
+  // `out` holds the result JSON
+  DUBDUB_STATUS_OK = 0,
+  // `out` holds `{"error": {"code", "message", ...}}`, as the HTTP API returns
+  DUBDUB_STATUS_ERROR = 1,
+  // A pointer was null or a string wasn't UTF-8; `out` holds the error
+  // JSON unless `out` itself was null
+  DUBDUB_STATUS_INVALID_ARGUMENT = 2,
+} DubdubStatus;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Library version, e.g. `1.0.0`; static, don't free it
+const char *dubdub_version(void);
+
+// Load duration models and frequency lists from `data_dir` (its `duration/`
+// and `frequency/` directories)
+//
+// Call once before aligning; later calls are ignored. Until then,
+// durations come from character counts. A panic while loading returns
+// `DUBDUB_STATUS_ERROR`.
+//
+// # Safety
+// `data_dir` must be null or a NUL-terminated string.
+enum DubdubStatus dubdub_load_data(const char *data_dir);
+
+// Tokenize `text`, like `POST /api/v1/tokenize`
+//
+// # Safety
+// `text` and `language` must be null or NUL-terminated strings, and `out`
+// a valid pointer; free what's written to it with `dubdub_string_free`.
+enum DubdubStatus dubdub_tokenize(const char *text, const char *language, char **out);
+
+// Estimate word timings for one subtitle, like `POST /api/v1/align`
+//
+// `mode: "tts"` predicts from the duration model; the TTS engine and
+// `audio_url` are only available through the service.
+//
+// # Safety
+// `request` must be null or a NUL-terminated string, and `out` a valid
+// pointer; free what's written to it with `dubdub_string_free`.
+enum DubdubStatus dubdub_align(const char *request, char **out);
+
+// Align every cue of a file, like `POST /api/v1/align/file`
+//
+// # Safety
+// `request` must be null or a NUL-terminated string, and `out` a valid
+// pointer; free what's written to it with `dubdub_string_free`.
+enum DubdubStatus dubdub_align_file(const char *request, char **out);
+
+// Free a string written to `out`; null is ignored
+//
+// # Safety
+// `s` must be null or a string from this library, freed only once.
+void dubdub_string_free(char *s);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* DUBDUB_H */
@@ -0,0 +1,219 @@
+//! C API to the dubdub tokenizer and aligner
+//!
+//! Lets the iOS and Android apps link the core directly and work offline.
+//! Requests and results are the HTTP API's JSON, passed as UTF-8 C strings;
+//! see `include/dubdub.h` (generated from this file by `build.rs` with
+//! `--features regenerate-header`).
+
+use std::ffi::{CStr, CString, c_char};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+
+use dubdub::error::{ApiError, ErrorResponse};
+use dubdub::models::{AlignmentRequest, FileAlignmentRequest, TokenizeRequest};
+use dubdub::validation::Validate;
+use dubdub::{aligner, duration, frequency, tokenizer};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// Outcome of a call
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DubdubStatus {
+    /// `out` holds the result JSON
+    Ok = 0,
+    /// `out` holds `{"error": {"code", "message", ...}}`, as the HTTP API returns
+    Error = 1,
+    /// A pointer was null or a string wasn't UTF-8; `out` holds the error
+    /// JSON unless `out` itself was null
+    InvalidArgument = 2,
+}
+
+const VERSION: &CStr = match CStr::from_bytes_with_nul(concat!(env!("CARGO_PKG_VERSION"), "\0").as_bytes()) {
+    Ok(version) => version,
+    Err(_) => panic!("version contains a NUL"),
+};
+
+/// Library version, e.g. `1.0.0`; static, don't free it
+#[unsafe(no_mangle)]
+pub extern "C" fn dubdub_version() -> *const c_char {
+    VERSION.as_ptr()
+}
+
+/// Load duration models and frequency lists from `data_dir` (its `duration/`
+/// and `frequency/` directories)
+///
+/// Call once before aligning; later calls are ignored. Until then,
+/// durations come from character counts. A panic while loading returns
+/// `DUBDUB_STATUS_ERROR`.
+///
+/// # Safety
+/// `data_dir` must be null or a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn dubdub_load_data(data_dir: *const c_char) -> DubdubStatus {
+    match unsafe { str_arg(data_dir, "data_dir") } {
+        Ok(data_dir) => {
+            let loaded = panic::catch_unwind(|| {
+                duration::init(&Path::new(data_dir).join("duration"));
+                frequency::init(&Path::new(data_dir).join("frequency"));
+            });
+            match loaded {
+                Ok(()) => DubdubStatus::Ok,
+                Err(_) => DubdubStatus::Error,
+            }
+        }
+        Err(_) => DubdubStatus::InvalidArgument,
+    }
+}
+
+/// Tokenize `text`, like `POST /api/v1/tokenize`
+///
+/// # Safety
+/// `text` and `language` must be null or NUL-terminated strings, and `out`
+/// a valid pointer; free what's written to it with `dubdub_string_free`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn dubdub_tokenize(text: *const c_char, language: *const c_char, out: *mut *mut c_char) -> DubdubStatus {
+    call(out, || {
+        let req = TokenizeRequest {
+            text: unsafe { str_arg(text, "text") }?.to_string(),
+            language: unsafe { str_arg(language, "language") }?.to_string(),
+            known: None,
+        };
+        req.validate().map_err(failed)?;
+        to_json(&tokenizer::tokenize_text(&req.text, &req.language).map_err(failed)?)
+    })
+}
+
+/// Estimate word timings for one subtitle, like `POST /api/v1/align`
+///
+/// `mode: "tts"` predicts from the duration model; the TTS engine and
+/// `audio_url` are only available through the service.
+///
+/// # Safety
+/// `request` must be null or a NUL-terminated string, and `out` a valid
+/// pointer; free what's written to it with `dubdub_string_free`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn dubdub_align(request: *const c_char, out: *mut *mut c_char) -> DubdubStatus {
+    call(out, || {
+        let req: AlignmentRequest = from_json(unsafe { str_arg(request, "request") }?)?;
+        req.validate().map_err(failed)?;
+        to_json(&aligner::align_in_process(&req).map_err(failed)?)
+    })
+}
+
+/// Align every cue of a file, like `POST /api/v1/align/file`
+///
+/// # Safety
+/// `request` must be null or a NUL-terminated string, and `out` a valid
+/// pointer; free what's written to it with `dubdub_string_free`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn dubdub_align_file(request: *const c_char, out: *mut *mut c_char) -> DubdubStatus {
+    call(out, || {
+        let req: FileAlignmentRequest = from_json(unsafe { str_arg(request, "request") }?)?;
+        req.validate().map_err(failed)?;
+        to_json(&aligner::align_file(&req).map_err(failed)?)
+    })
+}
+
+/// Free a string written to `out`; null is ignored
+///
+/// # Safety
+/// `s` must be null or a string from this library, freed only once.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn dubdub_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(unsafe { CString::from_raw(s) });
+    }
+}
+
+/// How a call went wrong, and the error to report
+type Failure = (DubdubStatus, ApiError);
+
+fn failed(error: ApiError) -> Failure {
+    (DubdubStatus::Error, error)
+}
+
+/// Run `f` and write its JSON, or the error's, to `out`
+///
+/// A panic becomes an `internal` error rather than unwinding into C.
+fn call(out: *mut *mut c_char, f: impl FnOnce() -> Result<String, Failure>) -> DubdubStatus {
+    if out.is_null() {
+        return DubdubStatus::InvalidArgument;
+    }
+
+    let result = panic::catch_unwind(AssertUnwindSafe(f))
+        .unwrap_or_else(|_| Err(failed(ApiError::internal("Panicked while handling the call"))));
+    let (status, json) = match result {
+        Ok(json) => (DubdubStatus::Ok, json),
+        Err((status, error)) => (status, serde_json::to_string(&ErrorResponse { error }).unwrap_or_default()),
+    };
+
+    // serde_json escapes NUL, so this can't fail
+    let json = CString::new(json).unwrap_or_default();
+    unsafe { *out = json.into_raw() };
+    status
+}
+
+/// # Safety
+/// `ptr` must be null or a NUL-terminated string that outlives the result.
+unsafe fn str_arg<'a>(ptr: *const c_char, name: &str) -> Result<&'a str, Failure> {
+    if ptr.is_null() {
+        return Err((DubdubStatus::InvalidArgument, ApiError::invalid_input("must not be null").with_field(name)));
+    }
+    unsafe { CStr::from_ptr(ptr) }.to_str()
+        .map_err(|_| (DubdubStatus::InvalidArgument, ApiError::invalid_input("must be UTF-8").with_field(name)))
+}
+
+fn from_json<T: DeserializeOwned>(json: &str) -> Result<T, Failure> {
+    serde_json::from_str(json).map_err(|e| failed(ApiError::invalid_input(format!("Invalid request: {}", e))))
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String, Failure> {
+    serde_json::to_string(value).map_err(|e| failed(ApiError::internal(format!("Failed to serialize result: {}", e))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+
+    fn output(call: impl FnOnce(*mut *mut c_char) -> DubdubStatus) -> (DubdubStatus, serde_json::Value) {
+        let mut out = ptr::null_mut();
+        let status = call(&mut out);
+        let json = serde_json::from_str(unsafe { CStr::from_ptr(out) }.to_str().unwrap()).unwrap();
+        unsafe { dubdub_string_free(out) };
+        (status, json)
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let (status, json) = output(|out| unsafe { dubdub_tokenize(c"Hello, world".as_ptr(), c"en".as_ptr(), out) });
+        assert_eq!(status, DubdubStatus::Ok);
+        assert_eq!(json["tokens"], serde_json::json!(["Hello", "world"]));
+
+        let (status, json) = output(|out| unsafe { dubdub_tokenize(ptr::null(), c"en".as_ptr(), out) });
+        assert_eq!(status, DubdubStatus::InvalidArgument);
+        assert_eq!(json["error"]["field"], "text");
+    }
+
+    #[test]
+    fn test_align() {
+        let request = cr#"{"text": "Hello there", "language": "en", "subtitle_start": 1.0, "subtitle_end": 2.0}"#;
+        let (status, json) = output(|out| unsafe { dubdub_align(request.as_ptr(), out) });
+        assert_eq!(status, DubdubStatus::Ok);
+        assert_eq!(json["timings"].as_array().unwrap().len(), 2);
+
+        let (status, json) = output(|out| unsafe { dubdub_align(c"{}".as_ptr(), out) });
+        assert_eq!(status, DubdubStatus::Error);
+        assert_eq!(json["error"]["code"], "invalid_input");
+
+        assert_eq!(unsafe { dubdub_align(request.as_ptr(), ptr::null_mut()) }, DubdubStatus::InvalidArgument);
+    }
+
+    #[test]
+    fn test_checked_in_header_is_current() {
+        let generated = include_str!(concat!(env!("OUT_DIR"), "/dubdub.h"));
+        let checked_in = include_str!("../include/dubdub.h");
+        assert!(generated == checked_in, "include/dubdub.h is stale, rebuild with --features regenerate-header");
+    }
+}
@@ -0,0 +1,75 @@
+//! Node.js bindings to the dubdub tokenizer and aligner
+//!
+//! For the Node backend's hot path: tokenizing and estimating word timings
+//! in-process instead of a round trip to the HTTP service, which stays the
+//! place for audio and TTS engine work. Requests and results are the same
+//! JSON shapes as the HTTP API, so callers can switch between the two.
+//!
+//! A panic in any export is caught and thrown as a JS `Error` rather than
+//! unwinding into Node, which would abort the process.
+
+use std::path::Path;
+
+use dubdub::error::{ApiError, ErrorCode};
+use dubdub::models::{AlignmentRequest, FileAlignmentRequest, TokenizeRequest};
+use dubdub::validation::Validate;
+use dubdub::{aligner, duration, frequency, tokenizer};
+use napi::{Error, Result, Status};
+use napi_derive::napi;
+use serde_json::Value;
+
+/// Load duration models and frequency lists from `dataDir` (its `duration/`
+/// and `frequency/` directories), as `DATA_DIR` does for the service
+///
+/// Call once at startup; until then, durations come from character counts.
+#[napi(catch_unwind)]
+pub fn load_data(data_dir: String) {
+    let dir = Path::new(&data_dir);
+    duration::init(&dir.join("duration"));
+    frequency::init(&dir.join("frequency"));
+}
+
+/// Tokenize `text`, like `POST /api/v1/tokenize`
+#[napi(catch_unwind)]
+pub fn tokenize(text: String, language: String) -> Result<Value> {
+    let req = TokenizeRequest { text, language, known: None };
+    req.validate().map_err(to_js)?;
+    to_value(tokenizer::tokenize_text(&req.text, &req.language).map_err(to_js)?)
+}
+
+/// Estimate word timings for one subtitle, like `POST /api/v1/align`
+///
+/// `mode: "tts"` predicts from the duration model; the TTS engine and
+/// `audio_url` are only available through the service.
+#[napi(catch_unwind)]
+pub fn align(request: Value) -> Result<Value> {
+    let req: AlignmentRequest = from_value(request)?;
+    req.validate().map_err(to_js)?;
+    to_value(aligner::align_in_process(&req).map_err(to_js)?)
+}
+
+/// Align every cue of a file, like `POST /api/v1/align/file`
+#[napi(catch_unwind)]
+pub fn align_file(request: Value) -> Result<Value> {
+    let req: FileAlignmentRequest = from_value(request)?;
+    req.validate().map_err(to_js)?;
+    to_value(aligner::align_file(&req).map_err(to_js)?)
+}
+
+/// Throw with the API's error code in front, e.g. `invalid_input: text: must not be empty`
+fn to_js(error: ApiError) -> Error {
+    let status = match error.code {
+        ErrorCode::InvalidInput | ErrorCode::InvalidSubtitles | ErrorCode::NoWords | ErrorCode::PayloadTooLarge => Status::InvalidArg,
+        _ => Status::GenericFailure,
+    };
+    let code = serde_json::to_value(error.code).ok().and_then(|code| code.as_str().map(str::to_string)).unwrap_or_default();
+    Error::new(status, format!("{}: {}", code, error))
+}
+
+fn from_value<T: serde::de::DeserializeOwned>(value: Value) -> Result<T> {
+    serde_json::from_value(value).map_err(|e| Error::new(Status::InvalidArg, format!("invalid_input: {}", e)))
+}
+
+fn to_value<T: serde::Serialize>(value: T) -> Result<Value> {
+    serde_json::to_value(value).map_err(|e| Error::from_reason(format!("internal: Failed to serialize result: {}", e)))
+}
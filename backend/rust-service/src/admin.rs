@@ -0,0 +1,69 @@
+use std::sync::OnceLock;
+
+use actix_web::dev::ServiceRequest;
+
+use crate::auth::{ApiKeys, Client};
+use crate::error::{ApiError, ErrorCode};
+use crate::features::{self, Feature};
+use crate::models::{AdminSettings, AdminSettingsUpdate};
+use crate::telemetry;
+
+/// Keys for `/admin`, separate from the `/api` keys; `None` disables it
+static KEYS: OnceLock<Option<ApiKeys>> = OnceLock::new();
+
+pub fn init(keys: Option<ApiKeys>) {
+    match &keys {
+        Some(keys) => log::info!("Admin endpoints enabled ({} keys)", keys.len()),
+        None => log::info!("No ADMIN_KEYS configured, /admin is disabled"),
+    }
+    if KEYS.set(keys).is_err() {
+        log::warn!("Admin keys already initialised");
+    }
+}
+
+/// Whether `/admin` is served at all
+pub fn is_enabled() -> bool {
+    KEYS.get().is_some_and(Option::is_some)
+}
+
+/// The admin presenting an admin key with `req`
+pub fn authorize(req: &ServiceRequest) -> Result<Client, ApiError> {
+    let keys = KEYS.get()
+        .and_then(Option::as_ref)
+        .ok_or_else(|| ApiError::not_found("Admin endpoints are disabled"))?;
+    keys.authorize(req).map_err(|message| ApiError::new(ErrorCode::Unauthorized, message))
+}
+
+pub fn settings() -> AdminSettings {
+    AdminSettings {
+        log_filter: telemetry::log_filter(),
+        verbose_requests: telemetry::verbose_requests(),
+        features: features::snapshot(),
+    }
+}
+
+/// Apply `update`, all or nothing
+///
+/// Everything is validated before anything changes, so a bad flag name
+/// doesn't leave the log filter half-applied.
+pub fn update(update: AdminSettingsUpdate, admin: &Client) -> Result<AdminSettings, ApiError> {
+    let flags = update.features.iter()
+        .map(|(name, enabled)| Feature::parse(name).map(|feature| (feature, *enabled)))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| ApiError::invalid_input(e).with_field("features"))?;
+
+    if let Some(filter) = &update.log_filter {
+        telemetry::set_log_filter(filter).map_err(|e| ApiError::invalid_input(e).with_field("log_filter"))?;
+        log::warn!("{} set the log filter to '{}'", admin, filter);
+    }
+    if let Some(verbose) = update.verbose_requests {
+        telemetry::set_verbose_requests(verbose);
+        log::warn!("{} turned verbose request logging {}", admin, if verbose { "on" } else { "off" });
+    }
+    for (feature, enabled) in flags {
+        feature.set(enabled);
+        log::warn!("{} turned feature {} {}", admin, feature.name(), if enabled { "on" } else { "off" });
+    }
+
+    Ok(settings())
+}
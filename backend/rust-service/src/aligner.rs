@@ -1,99 +1,167 @@
-use crate::models::{AlignmentRequest, AlignmentResponse, WordTiming, AlignmentMethod};
-use crate::tokenizer::tokenize_text;
+use std::ops::ControlFlow;
+
+use crate::calibration;
+use crate::cues::resolve_overlaps;
+use crate::duration::{self, DurationModel};
+use crate::error::{ApiError, ErrorCode};
+use crate::normalize;
+use crate::models::{
+    AlignmentMode, AlignmentRequest, AlignmentResponse, WordTiming, AlignmentMethod, Gap, GapKind,
+    FileAlignmentRequest, FileAlignmentResponse, CueAlignment, CueWarning, CueWarningKind, Warning, WarningCode,
+};
+use crate::stages::{self, Stage};
+use crate::tokenizer;
+use crate::tts;
 
 /// Align words using weighted distribution
 /// 
 /// # How it works:
 /// 1. Tokenize the text into words
-/// 2. Calculate each word's character count
-/// 3. Distribute time proportionally to length
+/// 2. Calculate each word's weight from the language's duration model
+///    (plain character count when no model is loaded for the language)
+/// 3. Weigh the pause at any punctuation between words
+/// 4. Distribute time proportionally to weight, leaving pauses as gaps
 /// 
-/// Example:
+/// Example (character count):
 /// Text: "Hi wonderful" (2 seconds total)
 /// - "Hi" = 2 chars → 2/11 = 18% → 0.36 seconds
 /// - "wonderful" = 9 chars → 9/11 = 82% → 1.64 seconds
-pub fn align_weighted(req: &AlignmentRequest) -> Result<AlignmentResponse, String> {
+pub fn align_weighted(req: &AlignmentRequest) -> Result<AlignmentResponse, ApiError> {
+    align_weighted_with(req, duration::models().get(&req.language))
+}
+
+/// Weighted alignment using an explicit duration model
+#[tracing::instrument(level = "debug", skip_all, fields(language = %req.language, bytes = req.text.len()))]
+pub fn align_weighted_with(req: &AlignmentRequest, model: &DurationModel) -> Result<AlignmentResponse, ApiError> {
+    let timings = weighted_timings(&req.text, &req.language, req.subtitle_start, req.subtitle_end, model)?;
+    
+    Ok(AlignmentResponse {
+        text: req.text.clone(),
+        language: req.language.clone(),
+        duration: req.subtitle_end - req.subtitle_start,
+        mean_confidence: mean_confidence(&timings),
+        gaps: find_gaps(&req.text, &timings, req.subtitle_start, req.subtitle_end),
+        timings,
+        method: AlignmentMethod::Weighted,
+        n_flagged: 0,
+        warnings: language_warnings(&req.language, true),
+        debug_timings: None,
+    })
+}
+
+/// What makes timings for `language` text a best guess from the start: a
+/// tokenizer fallback, and no duration model when `weighs_words` by one
+pub(crate) fn language_warnings(language: &str, weighs_words: bool) -> Vec<Warning> {
+    let no_model = weighs_words.then(|| duration::models().warning(language)).flatten();
+    tokenizer::warning(language).into_iter().chain(no_model).collect()
+}
+
+/// The weighted aligner's word timings for `text` between `start` and `end`
+///
+/// Works on borrowed tokens, so each word is copied once, into its timing.
+fn weighted_timings(text: &str, language: &str, start: f64, end: f64, model: &DurationModel) -> Result<Vec<WordTiming>, ApiError> {
     // Step 1: Tokenize to get words and their positions
-    let tokenized = tokenize_text(&req.text, &req.language)?;
+    let tokens = tokenizer::tokens(text, language);
     
-    if tokenized.tokens.is_empty() {
-        return Err("No words found to align".to_string());
+    if tokens.is_empty() {
+        return Err(ApiError::new(ErrorCode::NoWords, "No words found to align").with_field("text"));
     }
     
     // Step 2: Calculate total duration
-    let total_duration = req.subtitle_end - req.subtitle_start;
+    let total_duration = end - start;
     
     if total_duration <= 0.0 {
-        return Err("Invalid subtitle timing: end must be after start".to_string());
+        return Err(ApiError::invalid_input("Invalid subtitle timing: end must be after start").with_field("subtitle_end"));
     }
     
-    // Step 3: Count total characters (for weight calculation)
-    let total_chars: usize = tokenized.tokens.iter()
-        .map(|word| word.chars().count())
-        .sum();
+    // Step 3: Weigh every word and every pause after it (for weight calculation)
+    let word_weights: Vec<f64> = tokens.iter()
+        .map(|token| model.word_weight(token.text))
+        .collect();
+    let pause_weights: Vec<f64> = tokens.windows(2)
+        .map(|pair| model.pause_weight(&text[pair[0].end..pair[1].start]))
+        .collect();
     
-    if total_chars == 0 {
-        return Err("No characters found".to_string());
+    let total_word_weight: f64 = word_weights.iter().sum();
+    if total_word_weight <= 0.0 {
+        return Err(ApiError::new(ErrorCode::NoWords, "No characters found").with_field("text"));
     }
+    let total_weight = total_word_weight + pause_weights.iter().sum::<f64>();
     
-    // Step 4: Assign timing to each word
-    let mut timings = Vec::new();
-    let mut current_time = req.subtitle_start;
+    // Step 4: Assign timing to each word, as sure of it as the calibration
+    // says, and as the linear aligner agrees
+    let calibration = calibration::get();
+    let confidence = calibration.confidence(&AlignmentMethod::Weighted, language, tokens.len());
+    let linear_duration = total_duration / tokens.len() as f64;
+    let mut timings = Vec::with_capacity(tokens.len());
+    let mut current_time = start;
     
-    for (i, word) in tokenized.tokens.iter().enumerate() {
-        let word_chars = word.chars().count();
-        
+    for (i, token) in tokens.iter().enumerate() {
         // Calculate this word's proportion of total time
-        let weight = word_chars as f64 / total_chars as f64;
+        let weight = word_weights[i] / total_weight;
         let word_duration = total_duration * weight;
+        let linear_start = start + i as f64 * linear_duration;
+        let agreement = calibration::agreement((current_time, current_time + word_duration), (linear_start, linear_start + linear_duration));
         
         let timing = WordTiming {
-            word: word.clone(),
+            word: token.text.to_string(),
             start: current_time,
             end: current_time + word_duration,
-            confidence: 0.75, // Weighted method is decent but not perfect
-            char_start: tokenized.positions[i].start,
-            char_end: tokenized.positions[i].end,
+            confidence: calibration.with_agreement(confidence, agreement),
+            char_start: token.start,
+            char_end: token.end,
+            flagged: false,
         };
         
         timings.push(timing);
         current_time += word_duration;
+        
+        if let Some(pause) = pause_weights.get(i) {
+            current_time += total_duration * pause / total_weight;
+        }
     }
     
-    Ok(AlignmentResponse {
-        text: req.text.clone(),
-        language: req.language.clone(),
-        duration: total_duration,
-        timings,
-        method: AlignmentMethod::Weighted,
-    })
+    Ok(timings)
 }
 
 /// Align words using simple linear distribution
 /// 
 /// Each word gets exactly equal time.
 /// Fast but less accurate than weighted.
-pub fn align_linear(req: &AlignmentRequest) -> Result<AlignmentResponse, String> {
-    let tokenized = tokenize_text(&req.text, &req.language)?;
+pub fn align_linear(req: &AlignmentRequest) -> Result<AlignmentResponse, ApiError> {
+    let tokens = tokenizer::tokens(&req.text, &req.language);
     
-    if tokenized.tokens.is_empty() {
-        return Err("No words found to align".to_string());
+    if tokens.is_empty() {
+        return Err(ApiError::new(ErrorCode::NoWords, "No words found to align").with_field("text"));
     }
     
     let total_duration = req.subtitle_end - req.subtitle_start;
-    let time_per_word = total_duration / tokenized.tokens.len() as f64;
+    let time_per_word = total_duration / tokens.len() as f64;
     
-    let mut timings = Vec::new();
+    // Agreement with the weighted aligner, only worked out when it counts
+    let calibration = calibration::get();
+    let confidence = calibration.confidence(&AlignmentMethod::Linear, &req.language, tokens.len());
+    let weighted = if calibration.uses_agreement() {
+        weighted_timings(&req.text, &req.language, req.subtitle_start, req.subtitle_end, duration::models().get(&req.language)).ok()
+    } else {
+        None
+    };
+    
+    let mut timings = Vec::with_capacity(tokens.len());
     let mut current_time = req.subtitle_start;
     
-    for (i, word) in tokenized.tokens.iter().enumerate() {
+    for (i, token) in tokens.iter().enumerate() {
+        let agreement = weighted.as_ref()
+            .and_then(|weighted| weighted.get(i))
+            .map_or(1.0, |other| calibration::agreement((current_time, current_time + time_per_word), (other.start, other.end)));
         timings.push(WordTiming {
-            word: word.clone(),
+            word: token.text.to_string(),
             start: current_time,
             end: current_time + time_per_word,
-            confidence: 0.5, // Linear is just a guess
-            char_start: tokenized.positions[i].start,
-            char_end: tokenized.positions[i].end,
+            confidence: calibration.with_agreement(confidence, agreement),
+            char_start: token.start,
+            char_end: token.end,
+            flagged: false,
         });
         
         current_time += time_per_word;
@@ -103,26 +171,319 @@ pub fn align_linear(req: &AlignmentRequest) -> Result<AlignmentResponse, String>
         text: req.text.clone(),
         language: req.language.clone(),
         duration: total_duration,
+        mean_confidence: mean_confidence(&timings),
+        gaps: find_gaps(&req.text, &timings, req.subtitle_start, req.subtitle_end),
         timings,
         method: AlignmentMethod::Linear,
+        n_flagged: 0,
+        warnings: language_warnings(&req.language, false),
+        debug_timings: None,
     })
 }
 
 // Smart selector: choose best method based on request
-pub fn align_smart(req: &AlignmentRequest) -> Result<AlignmentResponse, String> {
-    // If audio URL is provided, we'll use forced alignment (future)
-    if req.audio_url.is_some() {
-        // TODO: Implement forced alignment
-        return Err("Forced alignment not yet implemented".to_string());
+#[tracing::instrument(skip_all, fields(language = %req.language))]
+pub fn align_smart(req: &AlignmentRequest) -> Result<AlignmentResponse, ApiError> {
+    stages::time(Stage::Alignment, || align_smart_untimed(req))
+}
+
+fn align_smart_untimed(req: &AlignmentRequest) -> Result<AlignmentResponse, ApiError> {
+    single_cue(req)?;
+    
+    // With an audio URL, align against the audio; otherwise (or if that
+    // fails and the request allows it) weighted is the best available
+    let mut response = match req.audio_url.as_ref().map(|_| align_forced(req)) {
+        None => align_weighted(req)?,
+        Some(Ok(response)) => response,
+        Some(Err(e)) if req.allow_fallback => fall_back(req, &e.message)?,
+        Some(Err(e)) => return Err(e),
+    };
+    
+    if let Some(min_confidence) = req.min_confidence {
+        apply_confidence_threshold(&mut response, min_confidence, req.exclude_flagged);
+    }
+    
+    Ok(response)
+}
+
+/// Align against the audio at `audio_url`
+fn align_forced(_req: &AlignmentRequest) -> Result<AlignmentResponse, ApiError> {
+    // TODO: Implement forced alignment
+    Err(forced_alignment_unavailable())
+}
+
+/// Why `audio_url` can't be aligned against
+pub fn forced_alignment_unavailable() -> ApiError {
+    ApiError::unsupported("Forced alignment not yet implemented").with_field("audio_url")
+}
+
+/// Weighted alignment in place of forced alignment that failed because of
+/// `reason`, with a warning saying so
+fn fall_back(req: &AlignmentRequest, reason: &str) -> Result<AlignmentResponse, ApiError> {
+    log::warn!("Forced alignment failed, falling back to weighted: {}", reason);
+    let mut response = align_weighted(req)?;
+    response.warnings.insert(0, Warning::new(WarningCode::ForcedAlignmentFallback,
+        format!("Forced alignment wasn't used ({}), so timings were estimated from the subtitle window", reason)));
+    Ok(response)
+}
+
+/// `align_smart`, or for `mode=tts` the duration model's prediction, but
+/// never the TTS engine
+///
+/// For callers that link the aligner in-process (Node and C bindings) and
+/// can't wait on the network.
+pub fn align_in_process(req: &AlignmentRequest) -> Result<AlignmentResponse, ApiError> {
+    match req.mode {
+        AlignmentMode::Tts => {
+            let req = normalize::alignment_request(req);
+            let mut response = tts::predict_alignment(&req, duration::models().get(&req.language))?;
+            if let Some(min_confidence) = req.min_confidence {
+                apply_confidence_threshold(&mut response, min_confidence, req.exclude_flagged);
+            }
+            Ok(response)
+        }
+        AlignmentMode::Subtitle => align_smart(&normalize::alignment_request(req)),
+    }
+}
+
+/// Gap (seconds) after a cue from which its subtitle may stay up after the
+/// speech ends
+const LINGER_GAP: f64 = 0.5;
+
+/// How much longer than predicted a cue's speech may run before its window
+/// is taken to be longer than the speech
+const LINGER_SLACK: f64 = 1.3;
+
+/// Reject requests carrying several cues, for the callers that align one
+pub fn single_cue(req: &AlignmentRequest) -> Result<(), ApiError> {
+    if req.cues.is_empty() {
+        Ok(())
+    } else {
+        Err(ApiError::invalid_input("Multi-cue requests are only accepted by POST /align").with_field("cues"))
+    }
+}
+
+/// Each cue of a multi-cue request as a request of its own, with its index
+/// (its position from 1 unless given)
+pub fn cue_requests(req: &AlignmentRequest) -> Vec<(usize, AlignmentRequest)> {
+    req.cues.iter()
+        .enumerate()
+        .map(|(i, cue)| {
+            let cue_req = AlignmentRequest {
+                text: cue.text.clone(),
+                subtitle_start: cue.subtitle_start,
+                subtitle_end: cue.subtitle_end,
+                cues: Vec::new(),
+                ..req.clone()
+            };
+            (cue.index.unwrap_or(i + 1), cue_req)
+        })
+        .collect()
+}
+
+/// Align every cue of a multi-cue request, using the gaps between cues as
+/// hints to where each cue's speech ends
+///
+/// # How it works:
+/// 1. Find the gap after each cue, to the next cue to start
+/// 2. Predict how long each cue takes to say from the duration model
+/// 3. A cue followed by a pause of at least `LINGER_GAP` (or by nothing)
+///    whose window is well over its predicted speech is taken to linger on
+///    screen: its words are spread over the predicted speech instead, and
+///    the rest of the window is a trailing silence gap
+/// 4. Cues chained closely to the next are aligned to their whole window
+///
+/// Returns `(index, speech end if hinted, alignment)` in request order.
+pub fn align_cues_hinted(req: &AlignmentRequest) -> Result<Vec<(usize, Option<f64>, AlignmentResponse)>, ApiError> {
+    let cues = cue_requests(req);
+    let model = duration::models().get(&req.language);
+
+    // Step 1: Gap to the next cue to start, by position in the request
+    let mut order: Vec<usize> = (0..cues.len()).collect();
+    order.sort_by(|&a, &b| cues[a].1.subtitle_start.total_cmp(&cues[b].1.subtitle_start));
+    let mut gaps_after = vec![None; cues.len()];
+    for pair in order.windows(2) {
+        gaps_after[pair[0]] = Some(cues[pair[1]].1.subtitle_start - cues[pair[0]].1.subtitle_end);
+    }
+
+    cues.into_iter()
+        .zip(gaps_after)
+        .map(|((index, cue), gap_after)| {
+            let cue = normalize::alignment_request(&cue);
+            let window = cue.subtitle_end - cue.subtitle_start;
+
+            // Steps 2 and 3: Predicted speech against the window
+            let lingers = gap_after.is_none_or(|gap| gap >= LINGER_GAP);
+            let speech_end = lingers
+                .then(|| tts::predict_alignment(&cue, model))
+                .and_then(Result::ok)
+                .map(|predicted| predicted.duration * LINGER_SLACK)
+                .filter(|&speech| speech < window)
+                .map(|speech| cue.subtitle_start + speech);
+
+            let response = match speech_end {
+                Some(speech_end) => align_smart(&AlignmentRequest { subtitle_end: speech_end, ..cue.clone().into_owned() }).map(|mut response| {
+                    response.duration = window;
+                    response.gaps = find_gaps(&response.text, &response.timings, cue.subtitle_start, cue.subtitle_end);
+                    response
+                }),
+                None => align_smart(&cue),
+            };
+            response
+                .map(|response| (index, speech_end, response))
+                .map_err(|e| e.context(&format!("Cue {}", index)))
+        })
+        .collect()
+}
+
+/// Flag words below `min_confidence`, optionally removing them
+///
+/// `n_flagged` and `mean_confidence` always describe the full alignment,
+/// even when flagged words are excluded from `timings`.
+pub fn apply_confidence_threshold(response: &mut AlignmentResponse, min_confidence: f64, exclude: bool) {
+    for timing in response.timings.iter_mut() {
+        timing.flagged = timing.confidence < min_confidence;
+    }
+    
+    response.n_flagged = response.timings.iter().filter(|t| t.flagged).count();
+    response.mean_confidence = mean_confidence(&response.timings);
+    
+    if exclude {
+        response.timings.retain(|t| !t.flagged);
+    }
+}
+
+/// Shortest stretch worth reporting as a gap (seconds)
+const MIN_GAP: f64 = 0.001;
+
+/// Find every part of the window not covered by a word
+///
+/// Gaps before the first word or after the last are silence; gaps between
+/// words are punctuation breaks when the text between them has punctuation,
+/// otherwise plain pauses.
+pub fn find_gaps(text: &str, timings: &[WordTiming], window_start: f64, window_end: f64) -> Vec<Gap> {
+    let mut gaps = Vec::new();
+    
+    let (Some(first), Some(last)) = (timings.first(), timings.last()) else {
+        return gaps;
+    };
+    
+    if first.start - window_start > MIN_GAP {
+        gaps.push(Gap { start: window_start, end: first.start, kind: GapKind::Silence });
+    }
+    
+    for pair in timings.windows(2) {
+        if pair[1].start - pair[0].end <= MIN_GAP {
+            continue;
+        }
+        
+        let separator = text.get(pair[0].char_end..pair[1].char_start).unwrap_or("");
+        let kind = if separator.chars().any(|c| c.is_ascii_punctuation() || is_unicode_punctuation(c)) {
+            GapKind::Punctuation
+        } else {
+            GapKind::Pause
+        };
+        
+        gaps.push(Gap { start: pair[0].end, end: pair[1].start, kind });
+    }
+    
+    if window_end - last.end > MIN_GAP {
+        gaps.push(Gap { start: last.end, end: window_end, kind: GapKind::Silence });
+    }
+    
+    gaps
+}
+
+fn is_unicode_punctuation(c: char) -> bool {
+    matches!(c, '…' | '—' | '–' | '。' | '、' | '，' | '！' | '？' | '；' | '：' | '¿' | '¡')
+}
+
+pub fn mean_confidence(timings: &[WordTiming]) -> f64 {
+    if timings.is_empty() {
+        return 0.0;
+    }
+    timings.iter().map(|t| t.confidence).sum::<f64>() / timings.len() as f64
+}
+
+/// Align every cue of a subtitle file
+///
+/// Overlapping and out-of-order cues are resolved first (see `cues::resolve_overlaps`)
+/// so the resulting word timings never overlap across cues unless the caller asked
+/// to keep them. Cues that fail to align are returned with no timings and a warning.
+pub fn align_file(req: &FileAlignmentRequest) -> Result<FileAlignmentResponse, ApiError> {
+    align_file_with_progress(req, |_, _, _| ControlFlow::Continue(()))
+}
+
+/// `align_file`, calling `on_cue(cue, processed, total)` as each cue is done
+///
+/// `total` counts cues left after overlap resolution, so it can be lower
+/// than the number of cues in the request. Returning `ControlFlow::Break`
+/// from `on_cue` stops alignment with a "Cancelled" error.
+#[tracing::instrument(skip_all, fields(language = %req.language, cues = req.cues.len()))]
+pub fn align_file_with_progress(
+    req: &FileAlignmentRequest,
+    on_cue: impl FnMut(&CueAlignment, usize, usize) -> ControlFlow<()>,
+) -> Result<FileAlignmentResponse, ApiError> {
+    stages::time(Stage::Alignment, || align_cues(req, on_cue))
+}
+
+fn align_cues(
+    req: &FileAlignmentRequest,
+    mut on_cue: impl FnMut(&CueAlignment, usize, usize) -> ControlFlow<()>,
+) -> Result<FileAlignmentResponse, ApiError> {
+    if req.cues.is_empty() {
+        return Err(ApiError::invalid_input("No cues to align").with_field("cues"));
+    }
+    
+    let (cues, mut warnings) = resolve_overlaps(&req.cues, req.overlap_policy);
+    
+    let total = cues.len();
+    let mut aligned = Vec::with_capacity(total);
+    
+    let model = duration::models().get(&req.language);
+    for cue in cues {
+        let (timings, gaps) = match weighted_timings(&cue.text, &req.language, cue.start, cue.end, model) {
+            Ok(timings) => {
+                let gaps = find_gaps(&cue.text, &timings, cue.start, cue.end);
+                (timings, gaps)
+            }
+            Err(e) => {
+                warnings.push(CueWarning {
+                    cue_index: cue.index,
+                    kind: CueWarningKind::AlignmentFailed,
+                    message: e.message,
+                });
+                (Vec::new(), Vec::new())
+            }
+        };
+        
+        aligned.push(CueAlignment {
+            index: cue.index,
+            start: cue.start,
+            end: cue.end,
+            text: cue.text,
+            style: cue.style,
+            actor: cue.actor,
+            timings,
+            gaps,
+        });
+        if on_cue(&aligned[aligned.len() - 1], aligned.len(), total).is_break() {
+            return Err(ApiError::new(ErrorCode::Cancelled, "Cancelled"));
+        }
     }
     
-    // Otherwise, use weighted (best available)
-    align_weighted(req)
+    Ok(FileAlignmentResponse {
+        language: req.language.clone(),
+        cues: aligned,
+        warnings,
+        debug_timings: None,
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::AlignmentCue;
     
     #[test]
     fn test_weighted_alignment_basic() {
@@ -132,6 +493,7 @@ mod tests {
             subtitle_start: 0.0,
             subtitle_end: 2.0,
             audio_url: None,
+            ..Default::default()
         };
         
         let result = align_weighted(&req).unwrap();
@@ -155,6 +517,7 @@ mod tests {
             subtitle_start: 0.0,
             subtitle_end: 3.0,
             audio_url: None,
+            ..Default::default()
         };
         
         let result = align_weighted(&req).unwrap();
@@ -177,6 +540,7 @@ mod tests {
             subtitle_start: 0.0,
             subtitle_end: 3.0,
             audio_url: None,
+            ..Default::default()
         };
         
         let result = align_linear(&req).unwrap();
@@ -196,6 +560,7 @@ mod tests {
             subtitle_start: 0.0,
             subtitle_end: 2.0,
             audio_url: None,
+            ..Default::default()
         };
         
         let weighted = align_weighted(&req).unwrap();
@@ -204,4 +569,186 @@ mod tests {
         // Weighted should have higher confidence
         assert!(weighted.timings[0].confidence > linear.timings[0].confidence);
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_weighted_alignment_with_duration_model() {
+        let req = AlignmentRequest {
+            text: "aa bb".to_string(),
+            language: "xx".to_string(),
+            subtitle_start: 0.0,
+            subtitle_end: 3.0,
+            audio_url: None,
+            ..Default::default()
+        };
+        
+        // Vowels twice as long as consonants
+        let model = DurationModel::new(1.0, [("a".to_string(), 2.0)].into());
+        let result = align_weighted_with(&req, &model).unwrap();
+        
+        let aa_duration = result.timings[0].end - result.timings[0].start;
+        let bb_duration = result.timings[1].end - result.timings[1].start;
+        
+        assert!((aa_duration - 2.0).abs() < 0.01);
+        assert!((bb_duration - 1.0).abs() < 0.01);
+    }
+    
+    #[test]
+    fn test_file_alignment_has_no_overlaps() {
+        use crate::models::{Cue, OverlapPolicy};
+        
+        let req = FileAlignmentRequest {
+            language: "en".to_string(),
+            cues: vec![
+                Cue { index: 1, start: 0.0, end: 2.0, text: "Hello there".to_string(), ..Default::default() },
+                Cue { index: 2, start: 1.5, end: 3.0, text: "General Kenobi".to_string(), ..Default::default() },
+                Cue { index: 3, start: 3.0, end: 4.0, text: "...".to_string(), ..Default::default() },
+            ],
+            overlap_policy: OverlapPolicy::Clamp,
+        };
+        
+        let result = align_file(&req).unwrap();
+        
+        let all_timings: Vec<&WordTiming> = result.cues.iter()
+            .flat_map(|cue| cue.timings.iter())
+            .collect();
+        for pair in all_timings.windows(2) {
+            assert!(pair[0].end <= pair[1].start + 1e-9);
+        }
+        
+        assert!(result.warnings.iter().any(|w| w.kind == CueWarningKind::Clamped));
+        assert!(result.warnings.iter().any(|w| w.kind == CueWarningKind::AlignmentFailed && w.cue_index == 3));
+    }
+    
+    #[test]
+    fn test_confidence_threshold_flags_words() {
+        let req = AlignmentRequest {
+            text: "Hello world".to_string(),
+            language: "en".to_string(),
+            subtitle_start: 0.0,
+            subtitle_end: 2.0,
+            min_confidence: Some(0.6),
+            ..Default::default()
+        };
+        
+        let mut linear = align_linear(&req).unwrap();
+        apply_confidence_threshold(&mut linear, 0.6, false);
+        assert_eq!(linear.n_flagged, 2);
+        assert!(linear.timings.iter().all(|t| t.flagged));
+        assert!((linear.mean_confidence - 0.5).abs() < 0.01);
+        
+        apply_confidence_threshold(&mut linear, 0.6, true);
+        assert!(linear.timings.is_empty());
+        assert_eq!(linear.n_flagged, 2);
+        
+        let weighted = align_smart(&req).unwrap();
+        assert_eq!(weighted.n_flagged, 0);
+        assert!(weighted.timings.iter().all(|t| !t.flagged));
+    }
+    
+    #[test]
+    fn test_punctuation_leaves_gap() {
+        let req = AlignmentRequest {
+            text: "Well, hello there.".to_string(),
+            language: "en".to_string(),
+            subtitle_start: 0.0,
+            subtitle_end: 2.0,
+            ..Default::default()
+        };
+        
+        let result = align_weighted_with(&req, &DurationModel::default()).unwrap();
+        
+        // Words 4 + 5 + 5 = 14, comma pause 2 → 16 units
+        assert_eq!(result.gaps.len(), 1);
+        assert_eq!(result.gaps[0].kind, GapKind::Punctuation);
+        assert!((result.gaps[0].start - 0.5).abs() < 0.01);
+        assert!((result.gaps[0].end - 0.75).abs() < 0.01);
+        assert!((result.timings[2].end - 2.0).abs() < 0.01);
+    }
+    
+    #[test]
+    fn test_find_gaps_classifies_silence_and_pause() {
+        let timing = |word: &str, start: f64, end: f64, char_start: usize| WordTiming {
+            word: word.to_string(),
+            start,
+            end,
+            confidence: 0.9,
+            char_start,
+            char_end: char_start + word.len(),
+            flagged: false,
+        };
+        let timings = vec![timing("a", 0.5, 1.0, 0), timing("b", 1.5, 2.0, 2)];
+        
+        let gaps = find_gaps("a b", &timings, 0.0, 3.0);
+        
+        let kinds: Vec<GapKind> = gaps.iter().map(|g| g.kind).collect();
+        assert_eq!(kinds, vec![GapKind::Silence, GapKind::Pause, GapKind::Silence]);
+    }
+    
+    #[test]
+    fn test_cue_gaps_hint_speech_end() {
+        let cue = |text: &str, subtitle_start: f64, subtitle_end: f64| AlignmentCue {
+            index: None,
+            text: text.to_string(),
+            subtitle_start,
+            subtitle_end,
+        };
+        let req = AlignmentRequest {
+            language: "en".to_string(),
+            // Chained to the next cue, then left on screen for 4 s
+            cues: vec![cue("Hi there", 0.0, 5.0), cue("Hi there", 5.1, 10.0)],
+            ..Default::default()
+        };
+        
+        let results = align_cues_hinted(&req).unwrap();
+        
+        let (index, speech_end, chained) = &results[0];
+        assert_eq!((*index, *speech_end), (1, None));
+        assert!((chained.timings[1].end - 5.0).abs() < 1e-9);
+        
+        let (index, speech_end, lingering) = &results[1];
+        assert_eq!(*index, 2);
+        let speech_end = speech_end.unwrap();
+        assert!(speech_end < 10.0);
+        assert!((lingering.timings[1].end - speech_end).abs() < 1e-9);
+        assert!((lingering.duration - 4.9).abs() < 1e-9);
+        let trailing = lingering.gaps.last().unwrap();
+        assert_eq!(trailing.kind, GapKind::Silence);
+        assert!((trailing.end - 10.0).abs() < 1e-9);
+    }
+    
+    #[test]
+    fn test_single_cue_aligners_reject_cues() {
+        let req = AlignmentRequest {
+            language: "en".to_string(),
+            cues: vec![AlignmentCue { index: Some(7), text: "Hi".to_string(), subtitle_start: 0.0, subtitle_end: 1.0 }],
+            ..Default::default()
+        };
+        assert_eq!(align_smart(&req).unwrap_err().field.as_deref(), Some("cues"));
+        assert_eq!(cue_requests(&req)[0].0, 7);
+    }
+    
+    #[test]
+    fn test_language_warnings() {
+        let codes = |weighs_words| language_warnings("xx", weighs_words).into_iter().map(|warning| warning.code).collect::<Vec<_>>();
+        assert_eq!(codes(false), vec![WarningCode::DefaultTokenizer]);
+        assert_eq!(codes(true), vec![WarningCode::DefaultTokenizer, WarningCode::NoDurationModel]);
+    }
+
+    #[test]
+    fn test_forced_alignment_falls_back_when_allowed() {
+        let req = AlignmentRequest {
+            text: "Hello world".to_string(),
+            language: "en".to_string(),
+            subtitle_end: 2.0,
+            audio_url: Some("https://example.com/episode.wav".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(align_smart(&req).unwrap_err().field.as_deref(), Some("audio_url"));
+        
+        let response = align_smart(&AlignmentRequest { allow_fallback: true, ..req }).unwrap();
+        assert!(matches!(response.method, AlignmentMethod::Weighted));
+        assert_eq!(response.timings.len(), 2);
+        assert_eq!(response.warnings[0].code, WarningCode::ForcedAlignmentFallback);
+        assert!(response.warnings[0].message.starts_with("Forced alignment wasn't used (Forced alignment not yet implemented)"));
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::BTreeMap;
+
+use crate::dictionary;
+use crate::models::{FileAlignmentResponse, SpeakerStats, TimingStats, WordDurationStats};
+
+/// Part of speech for words the dictionary lacks
+const UNKNOWN_PART_OF_SPEECH: &str = "unknown";
+
+/// Running total behind a `WordDurationStats`
+#[derive(Debug, Default)]
+struct Durations {
+    n_words: usize,
+    total: f64,
+}
+
+impl Durations {
+    fn add(&mut self, duration: f64) {
+        self.n_words += 1;
+        self.total += duration;
+    }
+
+    fn stats(&self) -> WordDurationStats {
+        WordDurationStats { n_words: self.n_words, mean_duration: mean(self.total, self.n_words) }
+    }
+}
+
+/// Summarise an aligned file for dashboards, without its raw timings
+///
+/// # How it works:
+/// 1. Group word durations overall, by length and by part of speech
+/// 2. Merge word intervals across cues, so overlapping cues don't count
+///    the same stretch of speech twice
+/// 3. Add up talk time per speaker (the cue's actor)
+pub fn timing_stats(alignment: &FileAlignmentResponse) -> TimingStats {
+    let dictionaries = dictionary::dictionaries();
+    let dictionary = dictionaries.get(&alignment.language);
+
+    let mut words = Durations::default();
+    let mut by_length: BTreeMap<usize, Durations> = BTreeMap::new();
+    let mut by_part_of_speech: BTreeMap<String, Durations> = BTreeMap::new();
+    let mut speakers: Vec<(Option<String>, usize, Durations)> = Vec::new();
+    let mut intervals = Vec::new();
+
+    for cue in &alignment.cues {
+        let at = match speakers.iter().position(|(speaker, _, _)| *speaker == cue.actor) {
+            Some(at) => at,
+            None => {
+                speakers.push((cue.actor.clone(), 0, Durations::default()));
+                speakers.len() - 1
+            }
+        };
+        speakers[at].1 += 1;
+
+        for timing in &cue.timings {
+            // Step 1: Word durations
+            let duration = (timing.end - timing.start).max(0.0);
+            words.add(duration);
+            by_length.entry(timing.word.chars().count()).or_default().add(duration);
+            if let Some(dictionary) = dictionary {
+                let part_of_speech = dictionary.lookup(&timing.word).into_iter()
+                    .find_map(|entry| entry.part_of_speech.as_deref())
+                    .and_then(|part_of_speech| part_of_speech.split(',').next())
+                    .map_or(UNKNOWN_PART_OF_SPEECH.to_string(), |part_of_speech| part_of_speech.trim().to_lowercase());
+                by_part_of_speech.entry(part_of_speech).or_default().add(duration);
+            }
+
+            intervals.push((timing.start, timing.end));
+            speakers[at].2.add(duration);
+        }
+    }
+
+    // Step 2: Speech and silence
+    let start = alignment.cues.iter().map(|cue| cue.start).fold(f64::INFINITY, f64::min);
+    let end = alignment.cues.iter().map(|cue| cue.end).fold(f64::NEG_INFINITY, f64::max);
+    let duration = if alignment.cues.is_empty() { 0.0 } else { (end - start).max(0.0) };
+    let speech = merged_length(&mut intervals).min(duration);
+
+    // Step 3: Talk time
+    let total_talk: f64 = speakers.iter().map(|(_, _, durations)| durations.total).sum();
+    let mut speakers: Vec<SpeakerStats> = speakers.into_iter()
+        .map(|(speaker, n_cues, durations)| SpeakerStats {
+            speaker,
+            n_cues,
+            n_words: durations.n_words,
+            talk_time: durations.total,
+            share: if total_talk > 0.0 { durations.total / total_talk } else { 0.0 },
+        })
+        .collect();
+    speakers.sort_by(|a, b| b.talk_time.total_cmp(&a.talk_time));
+
+    TimingStats {
+        language: alignment.language.clone(),
+        n_cues: alignment.cues.len(),
+        n_words: words.n_words,
+        duration,
+        speech,
+        silence: duration - speech,
+        speech_ratio: if duration > 0.0 { speech / duration } else { 0.0 },
+        words: words.stats(),
+        by_length: by_length.into_iter().map(|(length, durations)| (length, durations.stats())).collect(),
+        by_part_of_speech: by_part_of_speech.into_iter().map(|(part_of_speech, durations)| (part_of_speech, durations.stats())).collect(),
+        speakers,
+        n_warnings: alignment.warnings.len(),
+    }
+}
+
+/// Total length of the union of `intervals`
+fn merged_length(intervals: &mut [(f64, f64)]) -> f64 {
+    intervals.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut total = 0.0;
+    let mut current: Option<(f64, f64)> = None;
+    for &(start, end) in intervals.iter() {
+        current = match current {
+            Some((from, to)) if start <= to => Some((from, to.max(end))),
+            Some((from, to)) => {
+                total += to - from;
+                Some((start, end))
+            }
+            None => Some((start, end)),
+        };
+    }
+    total + current.map_or(0.0, |(from, to)| to - from)
+}
+
+fn mean(total: f64, n: usize) -> f64 {
+    if n == 0 { 0.0 } else { total / n as f64 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aligner::align_file;
+    use crate::models::{Cue, FileAlignmentRequest};
+
+    #[test]
+    fn test_merged_length() {
+        assert_eq!(merged_length(&mut [(2.0, 3.0), (0.0, 1.0), (0.5, 1.5)]), 2.5);
+        assert_eq!(merged_length(&mut []), 0.0);
+    }
+
+    #[test]
+    fn test_timing_stats() {
+        let cue = |index: usize, start: f64, end: f64, text: &str, actor: Option<&str>| Cue {
+            index, start, end, text: text.to_string(), actor: actor.map(str::to_string), ..Default::default()
+        };
+        let request = FileAlignmentRequest {
+            language: "en".to_string(),
+            cues: vec![
+                cue(1, 0.0, 2.0, "Hello there", Some("Ann")),
+                cue(2, 4.0, 5.0, "Hi", Some("Bob")),
+                cue(3, 6.0, 8.0, "How are you", Some("Ann")),
+            ],
+            overlap_policy: Default::default(),
+        };
+        let stats = timing_stats(&align_file(&request).unwrap());
+
+        assert_eq!(stats.n_words, 6);
+        assert_eq!(stats.duration, 8.0);
+        assert!(stats.speech > 0.0 && stats.speech <= 5.0);
+        assert!((stats.speech + stats.silence - 8.0).abs() < 1e-9);
+        assert_eq!(stats.by_length.get(&3).map(|group| group.n_words), Some(3));
+        assert_eq!(stats.speakers[0].speaker.as_deref(), Some("Ann"));
+        assert_eq!(stats.speakers[0].n_cues, 2);
+        assert_eq!(stats.speakers[1].n_words, 1);
+        assert!((stats.speakers.iter().map(|speaker| speaker.share).sum::<f64>() - 1.0).abs() < 1e-9);
+    }
+}
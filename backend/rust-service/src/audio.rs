@@ -0,0 +1,673 @@
+use std::fs::File;
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use symphonia::core::audio::{AudioBufferRef, SampleBuffer};
+use symphonia::core::codecs::{Decoder, DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, FormatReader};
+use symphonia::core::io::{MediaSource, MediaSourceStream};
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+use crate::downloads;
+use crate::error::ApiError;
+use crate::spool::{Spool, Spooled};
+use crate::storage;
+
+/// Sample rate every downstream stage (alignment, VAD, energy) works at
+pub const TARGET_SAMPLE_RATE: u32 = 16_000;
+
+/// Decoded mono audio
+#[derive(Debug, Clone)]
+pub struct AudioBuffer {
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+}
+
+impl AudioBuffer {
+    /// Length in seconds
+    pub fn duration(&self) -> f64 {
+        self.samples.len() as f64 / self.sample_rate as f64
+    }
+
+    /// Copy out the samples between `start` and `end` seconds
+    ///
+    /// The range is clamped to the buffer, so slicing past the end
+    /// returns whatever audio is available (possibly none).
+    pub fn slice(&self, start: f64, end: f64) -> AudioBuffer {
+        let to_index = |seconds: f64| {
+            ((seconds.max(0.0) * self.sample_rate as f64).round() as usize).min(self.samples.len())
+        };
+
+        let start_index = to_index(start);
+        let end_index = to_index(end).max(start_index);
+
+        AudioBuffer {
+            samples: self.samples[start_index..end_index].to_vec(),
+            sample_rate: self.sample_rate,
+        }
+    }
+
+    /// Resample to `target_rate`
+    ///
+    /// Downsampling averages the input samples covered by each output sample
+    /// (a cheap anti-aliasing filter); upsampling interpolates linearly.
+    pub fn resample(&self, target_rate: u32) -> AudioBuffer {
+        if target_rate == self.sample_rate || self.samples.is_empty() {
+            return AudioBuffer { samples: self.samples.clone(), sample_rate: target_rate };
+        }
+
+        let ratio = self.sample_rate as f64 / target_rate as f64;
+        let out_len = (self.samples.len() as f64 / ratio).round() as usize;
+        let last = self.samples.len() - 1;
+
+        let samples = (0..out_len)
+            .map(|i| {
+                let pos = i as f64 * ratio;
+                if ratio > 1.0 {
+                    let from = (pos as usize).min(last);
+                    let to = ((pos + ratio) as usize).clamp(from + 1, last + 1);
+                    self.samples[from..to].iter().sum::<f32>() / (to - from) as f32
+                } else {
+                    let index = (pos as usize).min(last);
+                    let next = (index + 1).min(last);
+                    let frac = (pos - index as f64) as f32;
+                    self.samples[index] * (1.0 - frac) + self.samples[next] * frac
+                }
+            })
+            .collect();
+
+        AudioBuffer { samples, sample_rate: target_rate }
+    }
+
+    /// Encode as a 16-bit PCM mono WAV file
+    ///
+    /// A WAV file's sizes are 32-bit, so samples past about 4 GB of data
+    /// (over a day at 16 kHz) are left out rather than wrapping the header.
+    pub fn to_wav(&self) -> Vec<u8> {
+        let samples = &self.samples[..self.samples.len().min(MAX_WAV_SAMPLES)];
+        let data_len = (samples.len() * 2) as u32;
+        let mut bytes = Vec::with_capacity(44 + data_len as usize);
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        // PCM, one channel
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&self.sample_rate.to_le_bytes());
+        bytes.extend_from_slice(&self.sample_rate.saturating_mul(2).to_le_bytes());
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_len.to_le_bytes());
+        for sample in samples {
+            let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+            bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+        bytes
+    }
+}
+
+/// Most 16-bit samples whose size, with the rest of the header, fits the
+/// RIFF chunk's 32-bit length
+const MAX_WAV_SAMPLES: usize = (u32::MAX as usize - 36) / 2;
+
+/// Length of one envelope frame (seconds)
+pub const ENVELOPE_FRAME_SECONDS: f64 = 0.02;
+
+/// Quietest level that can count as speech (dBFS)
+const MIN_SPEECH_LEVEL: f32 = -50.0;
+
+/// How far above the noise floor speech must be (dB)
+const SPEECH_MARGIN: f32 = 10.0;
+
+/// Loudness of successive 20 ms frames, for telling speech from silence
+///
+/// Fifty numbers per second, so even a feature-length file's envelope is
+/// small enough to keep while its samples are not.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EnergyEnvelope {
+    /// RMS level of each frame (dBFS)
+    pub levels: Vec<f32>,
+}
+
+impl EnergyEnvelope {
+    /// Build from mono samples
+    pub fn from_samples(samples: &[f32], sample_rate: u32) -> Self {
+        let mut builder = EnvelopeBuilder::new(sample_rate);
+        for &sample in samples {
+            builder.push(sample);
+        }
+        builder.finish()
+    }
+
+    /// Length in seconds
+    pub fn duration(&self) -> f64 {
+        self.levels.len() as f64 * ENVELOPE_FRAME_SECONDS
+    }
+
+    /// Level above which a frame counts as speech: `SPEECH_MARGIN` over
+    /// the noise floor (the quietest tenth of frames), and never below
+    /// `MIN_SPEECH_LEVEL`
+    pub fn speech_threshold(&self) -> f32 {
+        let mut levels = self.levels.clone();
+        levels.sort_by(f32::total_cmp);
+        let floor = levels.get(levels.len() / 10).copied().unwrap_or(f32::NEG_INFINITY);
+        (floor + SPEECH_MARGIN).max(MIN_SPEECH_LEVEL)
+    }
+
+    /// Seconds of frames louder than `threshold` between `start` and `end`
+    pub fn speech_seconds(&self, start: f64, end: f64, threshold: f32) -> f64 {
+        let to_frame = |seconds: f64| ((seconds.max(0.0) / ENVELOPE_FRAME_SECONDS).round() as usize).min(self.levels.len());
+        let (from, to) = (to_frame(start), to_frame(end));
+        let voiced = self.levels[from..to.max(from)].iter().filter(|&&level| level > threshold).count();
+        voiced as f64 * ENVELOPE_FRAME_SECONDS
+    }
+}
+
+/// Accumulates samples into envelope frames
+struct EnvelopeBuilder {
+    frame_len: usize,
+    sum_squares: f64,
+    n: usize,
+    levels: Vec<f32>,
+}
+
+impl EnvelopeBuilder {
+    fn new(sample_rate: u32) -> Self {
+        let frame_len = ((sample_rate as f64 * ENVELOPE_FRAME_SECONDS).round() as usize).max(1);
+        EnvelopeBuilder { frame_len, sum_squares: 0.0, n: 0, levels: Vec::new() }
+    }
+
+    fn push(&mut self, sample: f32) {
+        self.sum_squares += (sample as f64).powi(2);
+        self.n += 1;
+        if self.n == self.frame_len {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        let rms = (self.sum_squares / self.n as f64).sqrt();
+        self.levels.push((20.0 * rms.max(1e-10).log10()) as f32);
+        self.sum_squares = 0.0;
+        self.n = 0;
+    }
+
+    fn finish(mut self) -> EnergyEnvelope {
+        // A final partial frame shorter than half a frame is dropped
+        if self.n * 2 >= self.frame_len {
+            self.flush();
+        }
+        EnergyEnvelope { levels: self.levels }
+    }
+}
+
+/// Anything audio can be decoded from
+///
+/// Implementations decode the whole source and return it as 16 kHz mono,
+/// so callers never deal with codecs, channel layouts or sample rates.
+pub trait AudioSource {
+    fn decode(&self) -> Result<AudioBuffer, ApiError>;
+
+    /// Length in seconds
+    ///
+    /// Sources override this to measure without holding the decoded samples,
+    /// which for a feature-length file would be far larger than the file.
+    fn duration(&self) -> Result<f64, ApiError> {
+        Ok(self.decode()?.duration())
+    }
+
+    /// Decode only the `start`..`end` range (seconds)
+    fn decode_range(&self, start: f64, end: f64) -> Result<AudioBuffer, ApiError> {
+        Ok(self.decode()?.slice(start, end))
+    }
+
+    /// Loudness envelope, overridden like `duration` to avoid holding samples
+    fn envelope(&self) -> Result<EnergyEnvelope, ApiError> {
+        let buffer = self.decode()?;
+        Ok(EnergyEnvelope::from_samples(&buffer.samples, buffer.sample_rate))
+    }
+
+    /// Mono audio of each `start`..`end` range (seconds), in order
+    ///
+    /// Sources override this to keep only the clips while decoding, at the
+    /// track's own sample rate rather than 16 kHz.
+    fn clips(&self, ranges: &[(f64, f64)]) -> Result<Vec<AudioBuffer>, ApiError> {
+        let buffer = self.decode()?;
+        Ok(ranges.iter().map(|&(start, end)| buffer.slice(start, end)).collect())
+    }
+}
+
+/// Audio file on local disk
+pub struct FileSource {
+    pub path: PathBuf,
+}
+
+impl FileSource {
+    fn open(&self) -> Result<Box<dyn MediaSource>, ApiError> {
+        let file = File::open(&self.path)
+            .map_err(|e| ApiError::internal(format!("Failed to open {}: {}", self.path.display(), e)))?;
+        Ok(Box::new(file))
+    }
+
+    fn extension(&self) -> Option<&str> {
+        self.path.extension().and_then(|ext| ext.to_str())
+    }
+}
+
+impl AudioSource for FileSource {
+    fn decode(&self) -> Result<AudioBuffer, ApiError> {
+        decode_stream(self.open()?, self.extension())
+    }
+
+    fn duration(&self) -> Result<f64, ApiError> {
+        measure_stream(self.open()?, self.extension())
+    }
+
+    fn envelope(&self) -> Result<EnergyEnvelope, ApiError> {
+        envelope_stream(self.open()?, self.extension())
+    }
+
+    fn clips(&self, ranges: &[(f64, f64)]) -> Result<Vec<AudioBuffer>, ApiError> {
+        clips_stream(self.open()?, self.extension(), ranges)
+    }
+}
+
+/// Encoded audio already in memory (uploads, downloads)
+pub struct MemorySource {
+    pub bytes: Vec<u8>,
+    /// File extension or format name ("mp3", "wav", ...) to speed up probing
+    pub format_hint: Option<String>,
+}
+
+impl AudioSource for MemorySource {
+    fn decode(&self) -> Result<AudioBuffer, ApiError> {
+        decode_stream(Box::new(Cursor::new(self.bytes.clone())), self.format_hint.as_deref())
+    }
+
+    fn duration(&self) -> Result<f64, ApiError> {
+        measure_stream(Box::new(Cursor::new(self.bytes.clone())), self.format_hint.as_deref())
+    }
+
+    fn envelope(&self) -> Result<EnergyEnvelope, ApiError> {
+        envelope_stream(Box::new(Cursor::new(self.bytes.clone())), self.format_hint.as_deref())
+    }
+
+    fn clips(&self, ranges: &[(f64, f64)]) -> Result<Vec<AudioBuffer>, ApiError> {
+        clips_stream(Box::new(Cursor::new(self.bytes.clone())), self.format_hint.as_deref(), ranges)
+    }
+}
+
+/// Encoded audio received into a `Spool`: in memory if small, else in a
+/// temporary file
+pub struct SpooledSource {
+    pub content: Spooled,
+    /// File extension or format name ("mp3", "wav", ...) to speed up probing
+    pub format_hint: Option<String>,
+}
+
+impl SpooledSource {
+    fn open(&self) -> Result<Box<dyn MediaSource>, ApiError> {
+        match &self.content {
+            Spooled::Memory(bytes) => Ok(Box::new(Cursor::new(bytes.clone()))),
+            Spooled::File(file) => file.reopen()
+                .map(|file| Box::new(file) as Box<dyn MediaSource>)
+                .map_err(|e| ApiError::internal(format!("Failed to reopen spooled audio: {}", e))),
+        }
+    }
+}
+
+impl AudioSource for SpooledSource {
+    fn decode(&self) -> Result<AudioBuffer, ApiError> {
+        decode_stream(self.open()?, self.format_hint.as_deref())
+    }
+
+    fn duration(&self) -> Result<f64, ApiError> {
+        measure_stream(self.open()?, self.format_hint.as_deref())
+    }
+
+    fn envelope(&self) -> Result<EnergyEnvelope, ApiError> {
+        envelope_stream(self.open()?, self.format_hint.as_deref())
+    }
+
+    fn clips(&self, ranges: &[(f64, f64)]) -> Result<Vec<AudioBuffer>, ApiError> {
+        clips_stream(self.open()?, self.format_hint.as_deref(), ranges)
+    }
+}
+
+/// Download up to `limit` bytes of audio, spooling past the memory budget
+///
+/// `s3://bucket/key` URLs are read from the configured object storage,
+/// anything else with the shared download client.
+pub async fn fetch(url: &str, limit: usize) -> Result<SpooledSource, ApiError> {
+    let format_hint = format_hint(url);
+    let mut spool = Spool::new(limit);
+    if url.starts_with("s3://") {
+        storage::get(url, &mut spool).await?;
+    } else {
+        downloads::get(url, &mut spool).await?;
+    }
+
+    Ok(SpooledSource { content: spool.finish().await?, format_hint })
+}
+
+/// Check that the audio `fetch` would download is there, without
+/// downloading it
+pub async fn probe(url: &str) -> Result<(), ApiError> {
+    if url.starts_with("s3://") {
+        storage::head(url).await
+    } else {
+        downloads::probe(url).await
+    }
+}
+
+/// "https://cdn/episode.mp3?token=..." → "mp3"
+fn format_hint(url: &str) -> Option<String> {
+    url.split(['?', '#']).next()
+        .and_then(|path| path.rsplit_once('.'))
+        .map(|(_, ext)| ext.to_lowercase())
+        .filter(|ext| ext.len() <= 4)
+}
+
+/// The first audio track of a container, ready to decode
+struct Track {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    id: u32,
+    sample_rate: u32,
+    /// Frames in the track, if the container says
+    n_frames: Option<u64>,
+}
+
+/// Probe the container (mp3, aac/mp4, ogg, wav) and set up a decoder for
+/// its first audio track
+fn open_track(source: Box<dyn MediaSource>, extension: Option<&str>) -> Result<Track, ApiError> {
+    let stream = MediaSourceStream::new(source, Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(extension) = extension {
+        hint.with_extension(extension);
+    }
+
+    let probed = symphonia::default::get_probe()
+        .format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default())
+        .map_err(|e| ApiError::unsupported(format!("Unsupported audio format: {}", e)))?;
+    let format = probed.format;
+
+    let track = format.tracks().iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| ApiError::invalid_input("No audio track found"))?;
+    let sample_rate = track.codec_params.sample_rate
+        .ok_or_else(|| ApiError::invalid_input("Audio track has no sample rate"))?;
+
+    let decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|e| ApiError::unsupported(format!("Unsupported audio codec: {}", e)))?;
+
+    Ok(Track { id: track.id, sample_rate, n_frames: track.codec_params.n_frames, format, decoder })
+}
+
+impl Track {
+    /// Decode packets one at a time, handing each decoded buffer to `f`
+    fn for_each_buffer(&mut self, mut f: impl FnMut(AudioBufferRef<'_>)) -> Result<(), ApiError> {
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(()),
+                Err(e) => return Err(ApiError::invalid_input(format!("Failed to read audio: {}", e))),
+            };
+
+            if packet.track_id() != self.id {
+                continue;
+            }
+
+            match self.decoder.decode(&packet) {
+                Ok(decoded) => f(decoded),
+                // Corrupt frames are skipped rather than failing the whole file
+                Err(SymphoniaError::DecodeError(e)) => log::debug!("Skipping undecodable audio frame: {}", e),
+                Err(e) => return Err(ApiError::invalid_input(format!("Failed to decode audio: {}", e))),
+            }
+        }
+    }
+}
+
+/// Decode any supported container/codec to 16 kHz mono
+///
+/// # How it works:
+/// 1. Probe the container (mp3, aac/mp4, ogg, wav)
+/// 2. Decode every packet of the first audio track
+/// 3. Average all channels down to mono
+/// 4. Resample to `TARGET_SAMPLE_RATE`
+fn decode_stream(source: Box<dyn MediaSource>, extension: Option<&str>) -> Result<AudioBuffer, ApiError> {
+    // Step 1: Probe the container
+    let mut track = open_track(source, extension)?;
+
+    // Step 2 + 3: Decode and downmix
+    let mut mono = Vec::new();
+    track.for_each_buffer(|decoded| {
+        let spec = *decoded.spec();
+        let channels = spec.channels.count().max(1);
+        let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buffer.copy_interleaved_ref(decoded);
+
+        mono.extend(
+            buffer.samples()
+                .chunks(channels)
+                .map(|frame| frame.iter().sum::<f32>() / channels as f32),
+        );
+    })?;
+
+    // Step 4: Resample
+    let buffer = AudioBuffer { samples: mono, sample_rate: track.sample_rate };
+    Ok(buffer.resample(TARGET_SAMPLE_RATE))
+}
+
+/// Length of any supported audio in seconds, in constant memory
+///
+/// Taken from the container when it records the frame count (WAV, MP4);
+/// otherwise every packet is decoded and counted, one at a time.
+fn measure_stream(source: Box<dyn MediaSource>, extension: Option<&str>) -> Result<f64, ApiError> {
+    let mut track = open_track(source, extension)?;
+    if let Some(n_frames) = track.n_frames {
+        return Ok(n_frames as f64 / track.sample_rate as f64);
+    }
+
+    let mut frames = 0;
+    track.for_each_buffer(|decoded| frames += decoded.frames())?;
+    Ok(frames as f64 / track.sample_rate as f64)
+}
+
+/// Loudness envelope of any supported audio, in constant memory
+///
+/// Channels are averaged to mono and frames cut at the track's own sample
+/// rate, one decoded packet at a time.
+fn envelope_stream(source: Box<dyn MediaSource>, extension: Option<&str>) -> Result<EnergyEnvelope, ApiError> {
+    let mut track = open_track(source, extension)?;
+    let mut builder = EnvelopeBuilder::new(track.sample_rate);
+    track.for_each_buffer(|decoded| {
+        let spec = *decoded.spec();
+        let channels = spec.channels.count().max(1);
+        let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buffer.copy_interleaved_ref(decoded);
+        for frame in buffer.samples().chunks(channels) {
+            builder.push(frame.iter().sum::<f32>() / channels as f32);
+        }
+    })?;
+    Ok(builder.finish())
+}
+
+/// Mono clips of any supported audio at the track's sample rate, holding
+/// only the clips
+///
+/// Ranges are filled as decoding passes them, so they may overlap and come
+/// in any order.
+fn clips_stream(source: Box<dyn MediaSource>, extension: Option<&str>, ranges: &[(f64, f64)]) -> Result<Vec<AudioBuffer>, ApiError> {
+    let mut track = open_track(source, extension)?;
+    let sample_rate = track.sample_rate;
+    let to_index = |seconds: f64| (seconds.max(0.0) * sample_rate as f64).round() as usize;
+    let spans: Vec<(usize, usize)> = ranges.iter().map(|&(start, end)| (to_index(start), to_index(end).max(to_index(start)))).collect();
+
+    // Ranges by start, and those the decoded samples have reached
+    let mut order: Vec<usize> = (0..spans.len()).collect();
+    order.sort_by_key(|&i| spans[i].0);
+    let mut next = 0;
+    let mut active: Vec<usize> = Vec::new();
+
+    let mut clips: Vec<Vec<f32>> = vec![Vec::new(); spans.len()];
+    let mut position = 0;
+    track.for_each_buffer(|decoded| {
+        let spec = *decoded.spec();
+        let channels = spec.channels.count().max(1);
+        let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buffer.copy_interleaved_ref(decoded);
+        let mono: Vec<f32> = buffer.samples()
+            .chunks(channels)
+            .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+            .collect();
+        let end = position + mono.len();
+
+        while next < order.len() && spans[order[next]].0 < end {
+            active.push(order[next]);
+            next += 1;
+        }
+        for &i in &active {
+            let (from, to) = (spans[i].0.max(position), spans[i].1.min(end));
+            if from < to {
+                clips[i].extend_from_slice(&mono[from - position..to - position]);
+            }
+        }
+        active.retain(|&i| spans[i].1 > end);
+        position = end;
+    })?;
+
+    Ok(clips.into_iter().map(|samples| AudioBuffer { samples, sample_rate }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Minimal 16-bit PCM WAV file
+    fn wav_bytes(sample_rate: u32, channels: u16, frames: &[i16]) -> Vec<u8> {
+        let data_len = (frames.len() * 2) as u32;
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&channels.to_le_bytes());
+        bytes.extend_from_slice(&sample_rate.to_le_bytes());
+        bytes.extend_from_slice(&(sample_rate * channels as u32 * 2).to_le_bytes());
+        bytes.extend_from_slice(&(channels * 2).to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_len.to_le_bytes());
+        for sample in frames {
+            bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_decode_wav_resamples_to_16k_mono() {
+        // One second of stereo 44.1 kHz audio
+        let frames = vec![1000i16; 44_100 * 2];
+        let source = MemorySource { bytes: wav_bytes(44_100, 2, &frames), format_hint: Some("wav".to_string()) };
+
+        let buffer = source.decode().unwrap();
+
+        assert_eq!(buffer.sample_rate, TARGET_SAMPLE_RATE);
+        assert!((buffer.duration() - 1.0).abs() < 0.01);
+        assert!((buffer.samples[100] - 1000.0 / 32768.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_decode_range() {
+        let frames = vec![0i16; 16_000 * 2];
+        let source = MemorySource { bytes: wav_bytes(16_000, 1, &frames), format_hint: None };
+
+        let slice = source.decode_range(0.5, 1.25).unwrap();
+        assert!((slice.duration() - 0.75).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_slice_clamps_to_buffer() {
+        let buffer = AudioBuffer { samples: vec![0.0; 16_000], sample_rate: 16_000 };
+
+        assert!((buffer.slice(0.5, 5.0).duration() - 0.5).abs() < 0.001);
+        assert_eq!(buffer.slice(3.0, 4.0).samples.len(), 0);
+        assert_eq!(buffer.slice(0.8, 0.2).samples.len(), 0);
+    }
+
+    #[test]
+    fn test_upsample() {
+        let buffer = AudioBuffer { samples: vec![0.0, 1.0], sample_rate: 8_000 };
+        let upsampled = buffer.resample(16_000);
+
+        assert_eq!(upsampled.samples.len(), 4);
+        assert!((upsampled.samples[1] - 0.5).abs() < 0.001);
+    }
+
+    #[tokio::test]
+    async fn test_spooled_duration() {
+        let frames = vec![0i16; 8_000 * 3];
+        let mut spool = Spool::with_budget(1024, usize::MAX);
+        spool.write(&wav_bytes(8_000, 1, &frames)).await.unwrap();
+        let content = spool.finish().await.unwrap();
+        assert!(matches!(content, Spooled::File(_)));
+
+        let source = SpooledSource { content, format_hint: Some("wav".to_string()) };
+        assert!((source.duration().unwrap() - 3.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_envelope_finds_speech() {
+        // Half a second of silence, then half a second of tone
+        let frames: Vec<i16> = (0..16_000)
+            .map(|i| if i < 8_000 { 0 } else { ((i as f32 * 0.1).sin() * 8000.0) as i16 })
+            .collect();
+        let source = MemorySource { bytes: wav_bytes(16_000, 1, &frames), format_hint: Some("wav".to_string()) };
+
+        let envelope = source.envelope().unwrap();
+        assert_eq!(envelope.levels.len(), 50);
+        let threshold = envelope.speech_threshold();
+        assert!((envelope.speech_seconds(0.0, 1.0, threshold) - 0.5).abs() < 0.03);
+        assert_eq!(envelope.speech_seconds(0.0, 0.4, threshold), 0.0);
+        assert_eq!(envelope, EnergyEnvelope::from_samples(&source.decode().unwrap().samples, TARGET_SAMPLE_RATE));
+    }
+
+    #[test]
+    fn test_clips_keep_sample_rate() {
+        let frames: Vec<i16> = (0..22_050).map(|i| (i / 2205) as i16 * 1000).collect();
+        let source = MemorySource { bytes: wav_bytes(22_050, 1, &frames), format_hint: Some("wav".to_string()) };
+
+        let clips = source.clips(&[(0.5, 0.6), (0.1, 0.2), (0.9, 2.0)]).unwrap();
+        assert_eq!(clips[0].sample_rate, 22_050);
+        assert_eq!(clips[0].samples.len(), 2205);
+        assert!((clips[0].samples[0] - 5000.0 / 32768.0).abs() < 0.001);
+        assert!((clips[1].samples[0] - 1000.0 / 32768.0).abs() < 0.001);
+        // Clamped to the end of the audio
+        assert_eq!(clips[2].samples.len(), 2205);
+    }
+
+    #[test]
+    fn test_wav_round_trip() {
+        let buffer = AudioBuffer { samples: vec![0.0, 0.5, -0.5, 1.0], sample_rate: 16_000 };
+        let source = MemorySource { bytes: buffer.to_wav(), format_hint: Some("wav".to_string()) };
+
+        let decoded = source.decode().unwrap();
+        assert_eq!(decoded.samples.len(), 4);
+        assert!((decoded.samples[1] - 0.5).abs() < 0.001);
+        assert!((decoded.samples[2] + 0.5).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_garbage_is_rejected() {
+        let source = MemorySource { bytes: vec![1, 2, 3, 4], format_hint: None };
+        assert!(source.decode().is_err());
+    }
+}
@@ -0,0 +1,430 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::OnceLock;
+use std::task::{Context, Poll};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use actix_web::body::{BodySize, MessageBody};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::error::PayloadError;
+use actix_web::http::Method;
+use actix_web::web::Bytes;
+use actix_web::HttpMessage;
+use futures::Stream;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use sqlx::any::AnyPoolOptions;
+use sqlx::AnyPool;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+
+use crate::auth::Client;
+
+/// Records waiting to be written before new ones are dropped
+const QUEUE_SIZE: usize = 4096;
+
+/// Plain SQL that SQLite and Postgres both accept
+const SCHEMA: [&str; 2] = [
+    "CREATE TABLE IF NOT EXISTS audit_log (
+        at BIGINT NOT NULL,
+        request_id TEXT,
+        client TEXT,
+        tenant TEXT,
+        method TEXT NOT NULL,
+        endpoint TEXT NOT NULL,
+        status INTEGER NOT NULL,
+        language TEXT,
+        content_sha256 TEXT,
+        request_bytes BIGINT NOT NULL,
+        response_bytes BIGINT,
+        duration_ms DOUBLE PRECISION NOT NULL
+    )",
+    "CREATE INDEX IF NOT EXISTS audit_log_tenant_at ON audit_log (tenant, at)",
+];
+
+static QUEUE: OnceLock<mpsc::Sender<AuditRecord>> = OnceLock::new();
+
+/// Key of the content hashes (`AUDIT_KEY`)
+static KEY: OnceLock<Vec<u8>> = OnceLock::new();
+
+tokio::task_local! {
+    static LANGUAGE: RefCell<Option<String>>;
+}
+
+/// Who processed what: one line of the audit log
+///
+/// The content is only hashed, so reviewers can tell which requests sent
+/// the same text without the log holding any of it. The hash is keyed, so
+/// a short line can't be confirmed by hashing guesses without the key.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AuditRecord {
+    /// Unix seconds
+    pub at: u64,
+    pub request_id: Option<String>,
+    /// API key name or token subject; `None` with authentication off
+    pub client: Option<String>,
+    pub tenant: Option<String>,
+    pub method: String,
+    /// Route pattern, e.g. `/api/v1/jobs/{id}`
+    pub endpoint: String,
+    pub status: u16,
+    /// The request's `language`, once validated
+    pub language: Option<String>,
+    /// HMAC-SHA256 of the request body under `AUDIT_KEY`, hex; `None`
+    /// without a key
+    pub content_sha256: Option<String>,
+    pub request_bytes: u64,
+    /// `None` for streamed responses
+    pub response_bytes: Option<u64>,
+    pub duration_ms: f64,
+}
+
+/// Where records end up (`AUDIT_LOG`)
+enum Sink {
+    /// One JSON record per line, appended
+    File(tokio::fs::File),
+    Db(AnyPool),
+}
+
+impl Sink {
+    /// `sqlite://` and `postgres://` URLs are databases (the `audit_log`
+    /// table is created if missing); anything else is a file path
+    async fn open(target: &str) -> Result<Self, String> {
+        if ["sqlite:", "postgres:", "postgresql:"].iter().any(|scheme| target.starts_with(scheme)) {
+            sqlx::any::install_default_drivers();
+            let pool = AnyPoolOptions::new()
+                .max_connections(2)
+                .connect(target)
+                .await
+                .map_err(|e| format!("Can't open audit database: {}", e))?;
+            for statement in SCHEMA {
+                sqlx::query(statement).execute(&pool).await
+                    .map_err(|e| format!("Can't create the audit_log table: {}", e))?;
+            }
+            return Ok(Sink::Db(pool));
+        }
+
+        tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(target)
+            .await
+            .map(Sink::File)
+            .map_err(|e| format!("Can't open audit log {}: {}", target, e))
+    }
+
+    async fn write(&mut self, record: &AuditRecord) -> Result<(), String> {
+        match self {
+            Sink::File(file) => {
+                let mut line = serde_json::to_vec(record).map_err(|e| e.to_string())?;
+                line.push(b'\n');
+                file.write_all(&line).await.map_err(|e| e.to_string())?;
+                file.flush().await.map_err(|e| e.to_string())
+            }
+            Sink::Db(pool) => {
+                sqlx::query("INSERT INTO audit_log (at, request_id, client, tenant, method, endpoint, status, language,
+                    content_sha256, request_bytes, response_bytes, duration_ms)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)")
+                    .bind(record.at as i64)
+                    .bind(record.request_id.clone())
+                    .bind(record.client.clone())
+                    .bind(record.tenant.clone())
+                    .bind(&record.method)
+                    .bind(&record.endpoint)
+                    .bind(record.status as i32)
+                    .bind(record.language.clone())
+                    .bind(record.content_sha256.clone())
+                    .bind(record.request_bytes as i64)
+                    .bind(record.response_bytes.map(|bytes| bytes as i64))
+                    .bind(record.duration_ms)
+                    .execute(&*pool)
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+            }
+        }
+    }
+}
+
+/// Start writing audit records to `target`, if configured (`AUDIT_LOG`),
+/// hashing content with `key` (`AUDIT_KEY`)
+///
+/// Records are written in the background, so a slow disk or database
+/// doesn't hold up responses; if it falls too far behind, records are
+/// dropped and logged as errors.
+pub async fn init(target: Option<&str>, key: Option<&str>) -> Result<(), String> {
+    let Some(target) = target else {
+        return Ok(());
+    };
+    match key.filter(|key| !key.is_empty()) {
+        Some(key) => {
+            let _ = KEY.set(key.as_bytes().to_vec());
+        }
+        None => log::warn!("AUDIT_KEY is not set, so the audit log records no content hashes"),
+    }
+
+    let mut sink = Sink::open(target).await?;
+    let (sender, mut receiver) = mpsc::channel::<AuditRecord>(QUEUE_SIZE);
+    if QUEUE.set(sender).is_err() {
+        log::warn!("Audit log already initialised");
+        return Ok(());
+    }
+    log::info!("Audit log: {}", if matches!(sink, Sink::Db(_)) { "database" } else { target });
+
+    tokio::spawn(async move {
+        while let Some(record) = receiver.recv().await {
+            if let Err(e) = sink.write(&record).await {
+                log::error!("Audit record for request {} not written: {}", record.request_id.as_deref().unwrap_or("-"), e);
+            }
+        }
+    });
+    Ok(())
+}
+
+pub fn is_enabled() -> bool {
+    QUEUE.get().is_some()
+}
+
+/// Note the current request's language for its audit record
+///
+/// The first call wins, so a request's own `language` comes before any
+/// its items repeat. Does nothing outside a request's task.
+pub fn language(language: &str) {
+    let _ = LANGUAGE.try_with(|noted| {
+        noted.borrow_mut().get_or_insert_with(|| language.to_string());
+    });
+}
+
+/// Run a request's future, returning the language it noted
+pub async fn scope<F: Future>(fut: F) -> (F::Output, Option<String>) {
+    LANGUAGE.scope(RefCell::new(None), async {
+        let output = fut.await;
+        (output, LANGUAGE.with(RefCell::take))
+    }).await
+}
+
+/// A request being audited: what's known before it's handled, and its
+/// body as the handler reads it
+pub struct Audit {
+    started: Instant,
+    method: Method,
+    path: String,
+    body: Rc<RefCell<BodyDigest>>,
+    /// Who made the request, once authenticated (see `identify`)
+    caller: Caller,
+}
+
+/// Where `identify` notes an audited request's client, in the request's
+/// extensions, for when it fails without a response to find it on
+#[derive(Clone, Default)]
+struct Caller(Rc<RefCell<Option<Client>>>);
+
+#[derive(Default)]
+struct BodyDigest {
+    /// `None` without a key
+    hasher: Option<Hmac<Sha256>>,
+    bytes: u64,
+}
+
+impl BodyDigest {
+    fn new(key: Option<&[u8]>) -> Self {
+        let hasher = key.and_then(|key| Hmac::<Sha256>::new_from_slice(key).ok());
+        BodyDigest { hasher, bytes: 0 }
+    }
+
+    /// Hex hash of the body, if there was one to hash
+    fn finish(self) -> Option<String> {
+        let hasher = self.hasher.filter(|_| self.bytes > 0)?;
+        Some(hex::encode(hasher.finalize().into_bytes()))
+    }
+}
+
+/// The request body, hashed and counted as it streams past
+struct Tap {
+    payload: Payload,
+    digest: Rc<RefCell<BodyDigest>>,
+}
+
+impl Stream for Tap {
+    type Item = Result<Bytes, PayloadError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = Pin::new(&mut self.payload).poll_next(cx);
+        if let Poll::Ready(Some(Ok(chunk))) = &poll {
+            let mut digest = self.digest.borrow_mut();
+            if let Some(hasher) = &mut digest.hasher {
+                hasher.update(chunk);
+            }
+            digest.bytes += chunk.len() as u64;
+        }
+        poll
+    }
+}
+
+/// Start auditing `req`, if the audit log is on and it's an API request
+/// that does work (not a GET)
+pub fn start(req: &mut ServiceRequest, started: Instant) -> Option<Audit> {
+    if !is_enabled() || !req.path().starts_with("/api/") || matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+        return None;
+    }
+
+    let body = Rc::new(RefCell::new(BodyDigest::new(KEY.get().map(Vec::as_slice))));
+    let tap = Tap { payload: req.take_payload(), digest: body.clone() };
+    req.set_payload(Payload::from(Box::pin(tap) as Pin<Box<dyn Stream<Item = Result<Bytes, PayloadError>>>>));
+    let caller = Caller::default();
+    req.extensions_mut().insert(caller.clone());
+    Some(Audit { started, method: req.method().clone(), path: req.path().to_string(), body, caller })
+}
+
+/// Note who an audited request was authenticated as
+pub fn identify(req: &ServiceRequest, client: &Client) {
+    if let Some(caller) = req.extensions().get::<Caller>() {
+        *caller.0.borrow_mut() = Some(client.clone());
+    }
+}
+
+impl Audit {
+    /// Queue the record of a handled request
+    pub fn finish<B: MessageBody>(self, res: &ServiceResponse<B>, request_id: Option<String>, language: Option<String>) {
+        let client = res.request().extensions().get::<Client>().cloned();
+        let response_bytes = match res.response().body().size() {
+            BodySize::Sized(bytes) => Some(bytes),
+            BodySize::None => Some(0),
+            BodySize::Stream => None,
+        };
+        let endpoint = res.request().match_pattern().unwrap_or_else(|| self.path.clone());
+        self.send(endpoint, res.status().as_u16(), client, request_id, language, response_bytes);
+    }
+
+    /// Queue the record of a request that failed without a response (e.g.
+    /// timed out), by whoever it had been authenticated as
+    pub fn fail(self, error: &actix_web::Error) {
+        let endpoint = self.path.clone();
+        let client = self.caller.0.take();
+        self.send(endpoint, error.as_response_error().status_code().as_u16(), client, None, None, None);
+    }
+
+    fn send(self, endpoint: String, status: u16, client: Option<Client>, request_id: Option<String>, language: Option<String>, response_bytes: Option<u64>) {
+        let body = self.body.take();
+        let record = AuditRecord {
+            at: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            request_id,
+            tenant: client.as_ref().and_then(|client| client.tenant.clone()),
+            client: client.map(|client| client.name),
+            method: self.method.to_string(),
+            endpoint,
+            status,
+            language,
+            request_bytes: body.bytes,
+            content_sha256: body.finish(),
+            response_bytes,
+            duration_ms: self.started.elapsed().as_secs_f64() * 1000.0,
+        };
+
+        if let Some(queue) = QUEUE.get()
+            && queue.try_send(record).is_err()
+        {
+            log::error!("Audit log is falling behind; dropped the record of a {} {}", self.method, self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+    use futures::StreamExt;
+
+    fn record() -> AuditRecord {
+        AuditRecord {
+            at: 1700000000,
+            request_id: Some("r1".to_string()),
+            client: Some("node".to_string()),
+            tenant: Some("acme".to_string()),
+            method: "POST".to_string(),
+            endpoint: "/api/v1/align".to_string(),
+            status: 200,
+            language: Some("ja".to_string()),
+            content_sha256: Some("0".repeat(64)),
+            request_bytes: 2,
+            response_bytes: Some(120),
+            duration_ms: 3.5,
+        }
+    }
+
+    async fn tap(body: &'static str, key: Option<&[u8]>) -> BodyDigest {
+        let digest = Rc::new(RefCell::new(BodyDigest::new(key)));
+        let (_, payload) = TestRequest::post().set_payload(body).to_http_parts();
+        let mut tap = Tap { payload, digest: digest.clone() };
+
+        let mut read = Vec::new();
+        while let Some(chunk) = tap.next().await {
+            read.extend_from_slice(&chunk.unwrap());
+        }
+        assert_eq!(read, body.as_bytes());
+        digest.take()
+    }
+
+    #[actix_web::test]
+    async fn test_tap_hashes_the_body_with_the_key() {
+        let body = "{\"text\":\"secret\"}";
+        let digest = tap(body, Some(b"key")).await;
+        assert_eq!(digest.bytes, 17);
+        let mut expected = Hmac::<Sha256>::new_from_slice(b"key").unwrap();
+        expected.update(body.as_bytes());
+        assert_eq!(digest.finish(), Some(hex::encode(expected.finalize().into_bytes())));
+
+        // Another key, another hash; no key, no hash
+        assert_ne!(tap(body, Some(b"other")).await.finish(), tap(body, Some(b"key")).await.finish());
+        let unkeyed = tap(body, None).await;
+        assert_eq!(unkeyed.bytes, 17);
+        assert_eq!(unkeyed.finish(), None);
+    }
+
+    #[test]
+    fn test_identify() {
+        let req = TestRequest::post().uri("/api/v1/align").to_srv_request();
+        let caller = Caller::default();
+        req.extensions_mut().insert(caller.clone());
+        let client = Client { name: "node".to_string(), tenant: Some("acme".to_string()) };
+        identify(&req, &client);
+        assert_eq!(caller.0.take().map(|client| client.name).as_deref(), Some("node"));
+    }
+
+    #[tokio::test]
+    async fn test_language_first_wins() {
+        let ((), noted) = scope(async {
+            language("ja");
+            language("en");
+        }).await;
+        assert_eq!(noted.as_deref(), Some("ja"));
+
+        // Outside a request, nothing to note it in
+        language("es");
+    }
+
+    #[tokio::test]
+    async fn test_file_and_database_sinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let mut file = Sink::open(path.to_str().unwrap()).await.unwrap();
+        file.write(&record()).await.unwrap();
+        file.write(&record()).await.unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(text.lines().count(), 2);
+        let line: serde_json::Value = serde_json::from_str(text.lines().next().unwrap()).unwrap();
+        assert_eq!(line["tenant"], "acme");
+        assert_eq!(line["endpoint"], "/api/v1/align");
+
+        let url = format!("sqlite://{}?mode=rwc", dir.path().join("audit.db").display());
+        let mut db = Sink::open(&url).await.unwrap();
+        db.write(&record()).await.unwrap();
+        let Sink::Db(pool) = db else { panic!("expected a database") };
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM audit_log WHERE language = 'ja'").fetch_one(&pool).await.unwrap();
+        assert_eq!(count, 1);
+    }
+}
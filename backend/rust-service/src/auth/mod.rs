@@ -0,0 +1,329 @@
+pub mod jwt;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use actix_web::dev::ServiceRequest;
+use actix_web::http::Uri;
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::web;
+use sha2::{Digest, Sha256};
+
+use crate::error::{ApiError, ErrorCode};
+use jwt::JwtValidator;
+
+/// Header clients send their API key in
+pub const API_KEY_HEADER: &str = "X-API-Key";
+
+/// Query parameter fallback for clients that can't set headers
+/// (browser WebSocket and EventSource)
+pub const API_KEY_PARAM: &str = "api_key";
+
+/// Routes reachable without a key, so load balancers can probe the service
+const PUBLIC_PATHS: &[&str] = &["/api/health", "/api/v1/health"];
+
+/// Credentials accepted at startup; `None` means authentication is off
+static AUTHENTICATOR: OnceLock<Option<Authenticator>> = OnceLock::new();
+
+/// Who made a request, stored in the request extensions once authenticated
+#[derive(Debug, Clone, PartialEq)]
+pub struct Client {
+    /// API key name, or the token's subject
+    pub name: String,
+    /// Tenant from the bearer token; API keys have none
+    pub tenant: Option<String>,
+}
+
+impl fmt::Display for Client {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.tenant {
+            Some(tenant) => write!(f, "{}/{}", tenant, self.name),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// API keys and/or bearer tokens, whichever are configured
+pub struct Authenticator {
+    pub api_keys: Option<ApiKeys>,
+    pub jwt: Option<JwtValidator>,
+}
+
+impl Authenticator {
+    /// Check the credentials presented with a request
+    ///
+    /// An `Authorization: Bearer` token is validated as a JWT; otherwise the
+    /// API key is checked. Public paths and paths outside `/api` pass
+    /// without a client.
+    pub fn authorize(&self, req: &ServiceRequest) -> Result<Option<Client>, ApiError> {
+        let path = req.path();
+        if !path.starts_with("/api/") || PUBLIC_PATHS.contains(&path) {
+            return Ok(None);
+        }
+
+        self.authenticate(bearer_token(req), presented_key(req).as_deref()).map(Some)
+    }
+
+    /// Check a bearer token or API key, however the call carried them
+    /// (HTTP headers or gRPC metadata)
+    pub fn authenticate(&self, token: Option<&str>, key: Option<&str>) -> Result<Client, ApiError> {
+        let client = match (&self.jwt, token, &self.api_keys) {
+            (Some(jwt), Some(token), _) => jwt.validate(token),
+            (_, _, Some(keys)) => keys.check(key),
+            (_, _, None) => Err("Missing bearer token".to_string()),
+        };
+        client.map_err(|message| ApiError::new(ErrorCode::Unauthorized, message))
+    }
+}
+
+/// API keys by SHA-256 hash, mapped to the client name they identify
+///
+/// Only hashes are kept, so a memory dump or a timing side channel on the
+/// map lookup doesn't reveal the keys themselves.
+#[derive(Debug, Default)]
+pub struct ApiKeys {
+    names: HashMap<[u8; 32], String>,
+}
+
+impl ApiKeys {
+    /// Parse `name:key` entries separated by commas or newlines
+    ///
+    /// Blank lines and `#` comments are skipped. A bare `key` is named
+    /// after its position (`key1`, `key2`, ...).
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut keys = ApiKeys::default();
+
+        let entries = text.lines()
+            .map(str::trim)
+            .filter(|line| !line.starts_with('#'))
+            .flat_map(|line| line.split(','))
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty());
+
+        for (i, entry) in entries.enumerate() {
+            let (name, key) = match entry.split_once(':') {
+                Some((name, key)) => (name.trim().to_string(), key.trim()),
+                None => (format!("key{}", i + 1), entry),
+            };
+            if key.is_empty() {
+                return Err(format!("API key for '{}' is empty", name));
+            }
+            if keys.names.insert(hash(key), name.clone()).is_some() {
+                return Err(format!("API key for '{}' is configured twice", name));
+            }
+        }
+
+        Ok(keys)
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Name of the client owning `key`
+    pub fn identify(&self, key: &str) -> Option<&str> {
+        self.names.get(&hash(key)).map(String::as_str)
+    }
+
+    /// Check the key presented with a request
+    pub fn authorize(&self, req: &ServiceRequest) -> Result<Client, String> {
+        self.check(presented_key(req).as_deref())
+    }
+
+    /// Check a presented key, if any
+    pub fn check(&self, key: Option<&str>) -> Result<Client, String> {
+        let key = key.ok_or_else(|| format!("Missing API key, send it in the {} header", API_KEY_HEADER))?;
+        let name = self.identify(key).ok_or_else(|| "Invalid API key".to_string())?;
+
+        Ok(Client { name: name.to_string(), tenant: None })
+    }
+}
+
+fn hash(key: &str) -> [u8; 32] {
+    Sha256::digest(key.as_bytes()).into()
+}
+
+/// The token from an `Authorization: Bearer <token>` header
+fn bearer_token(req: &ServiceRequest) -> Option<&str> {
+    req.headers()
+        .get(actix_web::http::header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+/// The key from the `X-API-Key` header (where `hoist_query_key` puts an
+/// `api_key` query parameter)
+fn presented_key(req: &ServiceRequest) -> Option<String> {
+    req.headers().get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(|key| key.trim().to_string())
+}
+
+/// Move an `api_key` query parameter into the `X-API-Key` header and out of
+/// the URL, before the access log, traces or audit log see the URL
+///
+/// A key already sent in the header wins; the parameter is dropped either way.
+pub fn hoist_query_key(req: &mut ServiceRequest) {
+    let query = req.query_string();
+    let is_key = |pair: &&str| pair.split('=').next() == Some(API_KEY_PARAM);
+    if !query.split('&').any(|pair| is_key(&pair)) {
+        return;
+    }
+
+    let key = web::Query::<HashMap<String, String>>::from_query(query)
+        .ok()
+        .and_then(|query| query.get(API_KEY_PARAM).cloned());
+    let rest: Vec<&str> = query.split('&').filter(|pair| !is_key(pair)).collect();
+    let path_and_query = match rest.is_empty() {
+        true => req.path().to_string(),
+        false => format!("{}?{}", req.path(), rest.join("&")),
+    };
+    let Ok(uri) = path_and_query.parse::<Uri>() else {
+        return;
+    };
+
+    if let Some(key) = key
+        && !req.headers().contains_key(API_KEY_HEADER)
+        && let Ok(value) = HeaderValue::from_str(&key)
+    {
+        req.headers_mut().insert(HeaderName::from_static("x-api-key"), value);
+    }
+    req.match_info_mut().get_mut().update(&uri);
+    req.head_mut().uri = uri;
+}
+
+impl ApiKeys {
+    /// Load keys from `API_KEYS` and/or an `API_KEYS_FILE`
+    ///
+    /// Returns `None` when neither holds any key. A keys file that can't be
+    /// read is an error rather than silently leaving the service open.
+    pub fn load(env_keys: Option<&str>, keys_file: Option<&Path>) -> Result<Option<Self>, String> {
+        let mut text = env_keys.unwrap_or_default().to_string();
+
+        if let Some(path) = keys_file {
+            let content = fs::read_to_string(path)
+                .map_err(|e| format!("Can't read API keys from {}: {}", path.display(), e))?;
+            text.push('\n');
+            text.push_str(&content);
+        }
+
+        Ok(Some(ApiKeys::parse(&text)?).filter(|keys| !keys.is_empty()))
+    }
+}
+
+/// Enable authentication with whichever credentials are configured
+pub fn init(api_keys: Option<ApiKeys>, jwt: Option<JwtValidator>) {
+    if let Some(keys) = &api_keys {
+        log::info!("API key authentication enabled ({} keys)", keys.len());
+    }
+    if jwt.is_some() {
+        log::info!("JWT bearer authentication enabled");
+    }
+
+    let authenticator = match (api_keys, jwt) {
+        (None, None) => {
+            log::warn!("No API keys or JWT issuer configured, /api routes are open");
+            None
+        }
+        (api_keys, jwt) => Some(Authenticator { api_keys, jwt }),
+    };
+    if AUTHENTICATOR.set(authenticator).is_err() {
+        log::warn!("Authentication already initialised");
+    }
+}
+
+/// Authenticator set up by `init`, or `None` if authentication is off
+pub fn authenticator() -> Option<&'static Authenticator> {
+    AUTHENTICATOR.get().and_then(Option::as_ref)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn test_parse_named_and_bare_keys() {
+        let keys = ApiKeys::parse("# staging\nnode-backend:abc123, worker:def456\nghi789\n").unwrap();
+
+        assert_eq!(keys.len(), 3);
+        assert_eq!(keys.identify("abc123"), Some("node-backend"));
+        assert_eq!(keys.identify("ghi789"), Some("key3"));
+        assert_eq!(keys.identify("nope"), None);
+    }
+
+    #[test]
+    fn test_parse_rejects_duplicates_and_empty_keys() {
+        assert!(ApiKeys::parse("a:same,b:same").is_err());
+        assert!(ApiKeys::parse("a:").is_err());
+        assert!(ApiKeys::parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_authorize() {
+        let keys = Authenticator { api_keys: Some(ApiKeys::parse("node:secret").unwrap()), jwt: None };
+
+        let ok = TestRequest::post().uri("/api/tokenize").insert_header((API_KEY_HEADER, "secret")).to_srv_request();
+        assert_eq!(keys.authorize(&ok).unwrap(), Some(Client { name: "node".to_string(), tenant: None }));
+
+        let mut query = TestRequest::get().uri("/api/ws?api_key=secret").to_srv_request();
+        hoist_query_key(&mut query);
+        assert!(keys.authorize(&query).unwrap().is_some());
+
+        let wrong = TestRequest::post().uri("/api/tokenize").insert_header((API_KEY_HEADER, "guess")).to_srv_request();
+        assert!(keys.authorize(&wrong).is_err());
+
+        let missing = TestRequest::post().uri("/api/tokenize").to_srv_request();
+        assert!(keys.authorize(&missing).is_err());
+
+        let health = TestRequest::get().uri("/api/health").to_srv_request();
+        assert_eq!(keys.authorize(&health).unwrap(), None);
+    }
+
+    #[test]
+    fn test_query_key_leaves_the_url() {
+        let mut req = TestRequest::get().uri("/api/ws?lang=en&api_key=se%20cret&x=1").to_srv_request();
+        hoist_query_key(&mut req);
+        assert_eq!(req.uri().to_string(), "/api/ws?lang=en&x=1");
+        assert_eq!(req.headers().get(API_KEY_HEADER).unwrap(), "se cret");
+
+        let mut req = TestRequest::get().uri("/api/ws?api_key=secret").insert_header((API_KEY_HEADER, "header")).to_srv_request();
+        hoist_query_key(&mut req);
+        assert_eq!(req.uri().to_string(), "/api/ws");
+        assert_eq!(req.headers().get(API_KEY_HEADER).unwrap(), "header");
+    }
+
+    #[test]
+    fn test_bearer_without_jwt_falls_back_to_key() {
+        let keys = Authenticator { api_keys: Some(ApiKeys::parse("node:secret").unwrap()), jwt: None };
+
+        let bearer = TestRequest::post().uri("/api/tokenize")
+            .insert_header(("Authorization", "Bearer abc.def.ghi"))
+            .to_srv_request();
+        assert!(keys.authorize(&bearer).is_err());
+    }
+
+    #[test]
+    fn test_authenticate_without_a_request() {
+        let keys = Authenticator { api_keys: Some(ApiKeys::parse("node:secret").unwrap()), jwt: None };
+
+        assert_eq!(keys.authenticate(None, Some("secret")).unwrap().name, "node");
+        assert_eq!(keys.authenticate(None, None).unwrap_err().code, ErrorCode::Unauthorized);
+        assert!(keys.authenticate(Some("abc.def.ghi"), Some("guess")).is_err());
+    }
+
+    #[test]
+    fn test_client_display() {
+        let client = Client { name: "user-42".to_string(), tenant: Some("acme".to_string()) };
+        assert_eq!(client.to_string(), "acme/user-42");
+    }
+}
@@ -0,0 +1,240 @@
+use std::io::{Cursor, Read, Write};
+use std::sync::OnceLock;
+use std::thread;
+
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::aligner::align_file;
+use crate::error::{ApiError, ErrorCode};
+use crate::models::{BatchFileResult, BatchOperation, FileAlignmentRequest, OverlapPolicy, SubtitleFormat};
+use crate::subtitles::{self, validate};
+use crate::usage::{self, Meter};
+
+/// Most files processed from one archive
+const MAX_FILES: usize = 500;
+
+/// Largest single subtitle file inside an archive (uncompressed)
+const MAX_FILE_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Items of one batch request worked on at once, configured at startup
+static PARALLELISM: OnceLock<usize> = OnceLock::new();
+
+/// Cap how many items of one batch run at once (`BATCH_PARALLELISM`);
+/// defaults to one per CPU core
+pub fn init(parallelism: Option<usize>) {
+    let parallelism = parallelism.unwrap_or_else(default_parallelism).max(1);
+    log::info!("Batch items run {} at a time", parallelism);
+    if PARALLELISM.set(parallelism).is_err() {
+        log::warn!("Batch parallelism already initialised");
+    }
+}
+
+pub fn parallelism() -> usize {
+    *PARALLELISM.get_or_init(default_parallelism)
+}
+
+fn default_parallelism() -> usize {
+    thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+/// Run CPU-bound work for one batch item on the blocking pool, as a
+/// request of its own against `meter`'s account (see `usage::item`)
+///
+/// Combine with `StreamExt::buffered(parallelism())` to spread a batch over
+/// the cores while keeping results in input order.
+pub async fn blocking<R: Send + 'static>(meter: Option<Meter>, f: impl FnOnce() -> Result<R, ApiError> + Send + 'static) -> Result<R, ApiError> {
+    let span = tracing::Span::current();
+    tokio::task::spawn_blocking(move || span.in_scope(|| usage::item(meter.as_ref(), f))).await
+        .map_err(|e| ApiError::internal(format!("Worker error: {}", e)))?
+}
+
+/// Run one operation over every subtitle file in a ZIP archive
+///
+/// Files that aren't subtitles (by extension), directories and macOS
+/// resource forks are skipped. A file that fails doesn't stop the batch:
+/// its result carries the error instead. Each file counts as a request of
+/// its own against `meter`'s account.
+///
+/// CPU-bound: call from a blocking context.
+#[tracing::instrument(skip_all, fields(bytes = bytes.len(), ?operation))]
+pub fn process_zip(bytes: &[u8], operation: BatchOperation, language: Option<&str>, meter: Option<&Meter>) -> Result<Vec<BatchFileResult>, ApiError> {
+    if operation == BatchOperation::Align && language.is_none() {
+        return Err(ApiError::invalid_input("'language' is required for alignment").with_field("language"));
+    }
+
+    let mut archive = ZipArchive::new(Cursor::new(bytes))
+        .map_err(|e| ApiError::invalid_input(format!("Invalid ZIP archive: {}", e)))?;
+
+    let mut results = Vec::new();
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)
+            .map_err(|e| ApiError::invalid_input(format!("Failed to read archive entry {}: {}", i, e)))?;
+        let name = file.name().to_string();
+
+        let Some(format) = subtitles::format_from_filename(&name) else {
+            continue;
+        };
+        if file.is_dir() || name.starts_with("__MACOSX/") || name.rsplit('/').next().is_some_and(|base| base.starts_with("._")) {
+            continue;
+        }
+        if results.len() == MAX_FILES {
+            return Err(ApiError::new(ErrorCode::PayloadTooLarge, format!("Archive has more than {} subtitle files", MAX_FILES)));
+        }
+
+        // The entry's declared size is the archive's say-so: read at most
+        // one byte past the limit to tell whether it's really over
+        let mut content = Vec::new();
+        let outcome = file.by_ref().take(MAX_FILE_BYTES + 1).read_to_end(&mut content)
+            .map_err(|e| ApiError::invalid_input(format!("Failed to extract: {}", e)))
+            .and_then(|read| match read as u64 > MAX_FILE_BYTES {
+                true => Err(ApiError::new(ErrorCode::PayloadTooLarge, format!("File exceeds {} bytes", MAX_FILE_BYTES))),
+                false => usage::item(meter, || process_file(&String::from_utf8_lossy(&content), format, operation, language)),
+            });
+
+        results.push(match outcome {
+            Ok(result) => BatchFileResult { name, result: Some(result), error: None },
+            Err(e) => BatchFileResult { name, result: None, error: Some(e) },
+        });
+    }
+
+    results.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(results)
+}
+
+#[tracing::instrument(skip(content, language), fields(bytes = content.len()))]
+fn process_file(content: &str, format: SubtitleFormat, operation: BatchOperation, language: Option<&str>) -> Result<serde_json::Value, ApiError> {
+    let parsed = subtitles::parse(content, Some(format))?;
+
+    let result = match operation {
+        BatchOperation::Parse => serde_json::to_value(parsed),
+        BatchOperation::Validate => {
+            let report = validate::validate(&parsed.cues, &parsed.warnings, &validate::Limits::default());
+            serde_json::to_value(report)
+        }
+        BatchOperation::Align => {
+            let request = FileAlignmentRequest {
+                language: language.unwrap_or_default().to_string(),
+                cues: parsed.cues,
+                overlap_policy: OverlapPolicy::default(),
+            };
+            serde_json::to_value(align_file(&request)?)
+        }
+    };
+
+    result.map_err(|e| ApiError::internal(format!("Failed to serialize result: {}", e)))
+}
+
+/// Pack results as a ZIP with one `<file>.json` per input file
+///
+/// Failed files get `<file>.error.txt` instead.
+pub fn bundle_zip(results: &[BatchFileResult]) -> Result<Vec<u8>, ApiError> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    for file in results {
+        let (name, body) = match (&file.result, &file.error) {
+            (Some(result), _) => (format!("{}.json", file.name), serde_json::to_vec_pretty(result).unwrap_or_default()),
+            (None, error) => (format!("{}.error.txt", file.name), error.as_ref().map(ApiError::to_string).unwrap_or_default().into_bytes()),
+        };
+
+        writer.start_file(name, options)
+            .and_then(|_| writer.write_all(&body).map_err(Into::into))
+            .map_err(|e| ApiError::internal(format!("Failed to write bundle: {}", e)))?;
+    }
+
+    let cursor = writer.finish().map_err(|e| ApiError::internal(format!("Failed to write bundle: {}", e)))?;
+    Ok(cursor.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    fn archive(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in files {
+            writer.start_file(*name, SimpleFileOptions::default()).unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    const SRT: &str = "1\n00:00:01,000 --> 00:00:02,000\nHello there\n";
+
+    #[test]
+    fn test_validates_every_subtitle_file() {
+        let bytes = archive(&[
+            ("season1/ep02.srt", SRT),
+            ("season1/ep01.srt", SRT),
+            ("season1/notes.txt", "not a subtitle"),
+            ("__MACOSX/season1/._ep01.srt", "junk"),
+        ]);
+        let results = process_zip(&bytes, BatchOperation::Validate, None, None).unwrap();
+
+        let names: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["season1/ep01.srt", "season1/ep02.srt"]);
+        assert_eq!(results[0].result.as_ref().unwrap()["valid"], true);
+    }
+
+    #[test]
+    fn test_align_requires_language() {
+        let bytes = archive(&[("ep01.srt", SRT)]);
+        assert!(process_zip(&bytes, BatchOperation::Align, None, None).is_err());
+
+        let results = process_zip(&bytes, BatchOperation::Align, Some("en"), None).unwrap();
+        assert_eq!(results[0].result.as_ref().unwrap()["cues"][0]["timings"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_bundle_roundtrip() {
+        let results = vec![
+            BatchFileResult { name: "ep01.srt".to_string(), result: Some(serde_json::json!({"ok": true})), error: None },
+            BatchFileResult { name: "ep02.srt".to_string(), result: None, error: Some(ApiError::invalid_input("bad file")) },
+        ];
+        let bytes = bundle_zip(&results).unwrap();
+
+        let mut bundle = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        let mut error = String::new();
+        bundle.by_name("ep02.srt.error.txt").unwrap().read_to_string(&mut error).unwrap();
+        assert_eq!(error, "bad file");
+        assert!(bundle.by_name("ep01.srt.json").is_ok());
+    }
+
+    #[test]
+    fn test_oversized_file_fails_alone() {
+        let huge = " ".repeat(MAX_FILE_BYTES as usize + 1);
+        let bytes = archive(&[("ep01.srt", SRT), ("ep02.srt", &huge)]);
+        let results = process_zip(&bytes, BatchOperation::Parse, None, None).unwrap();
+
+        assert!(results[0].error.is_none());
+        assert_eq!(results[1].error.as_ref().unwrap().code, ErrorCode::PayloadTooLarge);
+    }
+
+    #[test]
+    fn test_garbage_rejected() {
+        assert!(process_zip(b"not a zip", BatchOperation::Parse, None, None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_blocking_keeps_input_order() {
+        let results: Vec<_> = futures::stream::iter(0..20u64)
+            .map(|i| blocking(None, move || {
+                std::thread::sleep(std::time::Duration::from_millis(20 - i));
+                if i == 7 { Err(ApiError::invalid_input("seven")) } else { Ok(i) }
+            }))
+            .buffered(4)
+            .collect()
+            .await;
+
+        assert_eq!(results.len(), 20);
+        for (i, result) in results.iter().enumerate() {
+            match result {
+                Ok(n) => assert_eq!(*n, i as u64),
+                Err(e) => assert_eq!((i, e.code), (7, ErrorCode::InvalidInput)),
+            }
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use lru::LruCache;
+use sha2::{Digest, Sha256};
+
+use crate::error::ApiError;
+use crate::features;
+use crate::models::{AlignmentRequest, AlignmentResponse, CacheStats};
+
+/// Alignments kept, unless configured
+pub const DEFAULT_ALIGNMENT_CACHE_SIZE: usize = 10_000;
+
+/// Bytes of cached alignments (as JSON) kept, unless configured
+pub const DEFAULT_ALIGNMENT_CACHE_BYTES: usize = 64 * 1024 * 1024;
+
+/// Recent alignment responses, shared across requests
+///
+/// Re-watching an episode sends the same cues again; this answers them
+/// without running the aligner (or calling the TTS engine) a second time.
+/// Keyed by a hash of the whole request and the feature flags, so any
+/// option or flag that changes the result also changes the key. Bounded by
+/// entry count and by the size of the responses as JSON, whichever is hit
+/// first.
+pub struct AlignmentCache {
+    entries: Mutex<Entries>,
+    max_bytes: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+struct Entries {
+    /// Each response with its size in bytes
+    lru: LruCache<[u8; 32], (AlignmentResponse, usize)>,
+    bytes: usize,
+}
+
+impl Entries {
+    fn pop(&mut self, key: &[u8; 32]) {
+        if let Some((_, size)) = self.lru.pop(key) {
+            self.bytes -= size;
+        }
+    }
+}
+
+impl AlignmentCache {
+    pub fn new(capacity: NonZeroUsize, max_bytes: usize) -> Self {
+        AlignmentCache {
+            entries: Mutex::new(Entries { lru: LruCache::new(capacity), bytes: 0 }),
+            max_bytes,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// The cached response for `req`, counting a hit or a miss
+    pub fn get(&self, req: &AlignmentRequest) -> Option<AlignmentResponse> {
+        let response = self.entries.lock().unwrap().lru.get(&key(req)).map(|(response, _)| response.clone());
+        let counter = if response.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        response
+    }
+
+    /// Remember `response` for `req`, evicting the least recently used until
+    /// it fits; a response bigger than the whole budget isn't kept
+    pub fn insert(&self, req: &AlignmentRequest, response: &AlignmentResponse) {
+        let size = serde_json::to_vec(response).map(|json| json.len()).unwrap_or(usize::MAX);
+        if size > self.max_bytes {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        let key = key(req);
+        entries.pop(&key);
+        while entries.bytes + size > self.max_bytes
+            && let Some((_, (_, evicted))) = entries.lru.pop_lru()
+        {
+            entries.bytes -= evicted;
+        }
+        if let Some((_, (_, evicted))) = entries.lru.push(key, (response.clone(), size)) {
+            entries.bytes -= evicted;
+        }
+        entries.bytes += size;
+    }
+
+    /// Drop every entry; returns how many there were
+    ///
+    /// Hit and miss counts keep running, like any other counter.
+    pub fn flush(&self) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        let flushed = entries.lru.len();
+        entries.lru.clear();
+        entries.bytes = 0;
+        flushed
+    }
+
+    /// Entries by the lowercase language of their text
+    pub fn languages(&self) -> HashMap<String, usize> {
+        let mut languages: HashMap<String, usize> = HashMap::new();
+        for (_, (response, _)) in self.entries.lock().unwrap().lru.iter() {
+            *languages.entry(response.language.to_lowercase()).or_default() += 1;
+        }
+        languages
+    }
+
+    /// Drop the entries whose language `matches`; returns how many there were
+    pub fn forget(&self, matches: impl Fn(&str) -> bool) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        let keys: Vec<[u8; 32]> = entries.lru.iter().filter(|(_, (response, _))| matches(&response.language)).map(|(key, _)| *key).collect();
+        for key in &keys {
+            entries.pop(key);
+        }
+        keys.len()
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let entries = self.entries.lock().unwrap();
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        CacheStats {
+            enabled: true,
+            entries: entries.lru.len(),
+            capacity: entries.lru.cap().get(),
+            bytes: entries.bytes,
+            max_bytes: self.max_bytes,
+            hits,
+            misses,
+            hit_rate: if hits + misses > 0 { hits as f64 / (hits + misses) as f64 } else { 0.0 },
+        }
+    }
+}
+
+static CACHE: OnceLock<Option<AlignmentCache>> = OnceLock::new();
+
+/// Keep up to `capacity` alignments (`ALIGNMENT_CACHE_SIZE`) taking up to
+/// `max_bytes` (`ALIGNMENT_CACHE_BYTES`); either at 0 turns the cache off
+pub fn init(capacity: usize, max_bytes: usize) {
+    let capacity = NonZeroUsize::new(capacity).filter(|_| max_bytes > 0);
+    match capacity {
+        None => log::info!("Alignment cache disabled"),
+        Some(capacity) => log::info!("Caching up to {} alignments, {} bytes", capacity, max_bytes),
+    }
+    if CACHE.set(capacity.map(|capacity| AlignmentCache::new(capacity, max_bytes))).is_err() {
+        log::warn!("Alignment cache already initialised");
+    }
+}
+
+fn cache() -> Option<&'static AlignmentCache> {
+    CACHE.get_or_init(|| NonZeroUsize::new(DEFAULT_ALIGNMENT_CACHE_SIZE)
+        .map(|capacity| AlignmentCache::new(capacity, DEFAULT_ALIGNMENT_CACHE_BYTES)))
+        .as_ref()
+}
+
+/// The cached alignment for `req`, if the cache is on and has one
+pub fn get(req: &AlignmentRequest) -> Option<AlignmentResponse> {
+    cache().and_then(|cache| cache.get(req))
+}
+
+/// Remember a successful alignment; failures are never cached
+pub fn insert(req: &AlignmentRequest, response: &AlignmentResponse) {
+    if let Some(cache) = cache() {
+        cache.insert(req, response);
+    }
+}
+
+/// The cached alignment for `req`, or `align`'s (then cached)
+pub fn get_or_align(
+    req: &AlignmentRequest,
+    align: impl FnOnce(&AlignmentRequest) -> Result<AlignmentResponse, ApiError>,
+) -> Result<AlignmentResponse, ApiError> {
+    if let Some(response) = get(req) {
+        return Ok(response);
+    }
+    let response = align(req)?;
+    insert(req, &response);
+    Ok(response)
+}
+
+/// Empty the cache; returns how many alignments were dropped
+pub fn flush() -> usize {
+    cache().map(AlignmentCache::flush).unwrap_or(0)
+}
+
+/// Cached alignments by language, for `/admin/languages`
+pub fn languages() -> HashMap<String, usize> {
+    cache().map(AlignmentCache::languages).unwrap_or_default()
+}
+
+/// Drop the alignments whose language `matches`; returns how many were dropped
+pub fn forget(matches: impl Fn(&str) -> bool) -> usize {
+    cache().map(|cache| cache.forget(matches)).unwrap_or(0)
+}
+
+pub fn stats() -> CacheStats {
+    cache().map(AlignmentCache::stats).unwrap_or_default()
+}
+
+/// The request and the feature flags it was aligned under, hashed: a flag
+/// switched at runtime (see `/admin/settings`) leaves earlier results behind
+fn key(req: &AlignmentRequest) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(serde_json::to_vec(req).unwrap_or_default());
+    hasher.update(serde_json::to_vec(&features::snapshot()).unwrap_or_default());
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aligner::align_smart;
+
+    fn request(text: &str, end: f64) -> AlignmentRequest {
+        AlignmentRequest {
+            text: text.to_string(),
+            language: "en".to_string(),
+            subtitle_end: end,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_hits_misses_and_eviction() {
+        let cache = AlignmentCache::new(NonZeroUsize::new(2).unwrap(), DEFAULT_ALIGNMENT_CACHE_BYTES);
+        let (a, b, c) = (request("Hello there", 1.0), request("Hello there", 2.0), request("Goodbye", 1.0));
+
+        assert!(cache.get(&a).is_none());
+        cache.insert(&a, &align_smart(&a).unwrap());
+        cache.insert(&b, &align_smart(&b).unwrap());
+        assert_eq!(cache.get(&a).unwrap().duration, 1.0);
+        assert_eq!(cache.get(&b).unwrap().duration, 2.0);
+
+        // `a` was used before `b`, so it goes first
+        cache.insert(&c, &align_smart(&c).unwrap());
+        assert!(cache.get(&a).is_none());
+        assert!(cache.get(&c).is_some());
+
+        let stats = cache.stats();
+        assert_eq!((stats.entries, stats.capacity, stats.hits, stats.misses), (2, 2, 3, 2));
+        assert!((stats.hit_rate - 0.6).abs() < 1e-9);
+
+        assert_eq!(cache.flush(), 2);
+        assert_eq!(cache.stats().entries, 0);
+        assert!(cache.get(&b).is_none());
+    }
+
+    #[test]
+    fn test_forget_by_language() {
+        let cache = AlignmentCache::new(NonZeroUsize::new(4).unwrap(), DEFAULT_ALIGNMENT_CACHE_BYTES);
+        let spanish = AlignmentRequest { language: "es-MX".to_string(), ..request("Hola", 1.0) };
+        cache.insert(&spanish, &align_smart(&spanish).unwrap());
+        cache.insert(&request("Hello", 1.0), &align_smart(&request("Hello", 1.0)).unwrap());
+
+        assert_eq!(cache.languages(), HashMap::from([("es-mx".to_string(), 1), ("en".to_string(), 1)]));
+        assert_eq!(cache.forget(|language| language.starts_with("es")), 1);
+        assert!(cache.get(&spanish).is_none());
+        assert_eq!(cache.stats().entries, 1);
+    }
+
+    #[test]
+    fn test_byte_budget() {
+        let (a, b) = (request("Hello there", 1.0), request("Goodbye", 1.0));
+        let size = serde_json::to_vec(&align_smart(&a).unwrap()).unwrap().len();
+
+        // Room for `a` alone: `b` pushes it out
+        let cache = AlignmentCache::new(NonZeroUsize::new(10).unwrap(), size);
+        cache.insert(&a, &align_smart(&a).unwrap());
+        assert_eq!(cache.stats().bytes, size);
+        cache.insert(&b, &align_smart(&b).unwrap());
+        assert!(cache.get(&a).is_none());
+        assert!(cache.get(&b).is_some());
+        assert!(cache.stats().bytes <= size);
+
+        // Too big to keep at all
+        let cache = AlignmentCache::new(NonZeroUsize::new(10).unwrap(), size - 1);
+        cache.insert(&a, &align_smart(&a).unwrap());
+        assert_eq!(cache.stats().entries, 0);
+    }
+
+    #[test]
+    fn test_options_are_part_of_the_key() {
+        let flagged = AlignmentRequest { min_confidence: Some(0.9), ..request("Hello there", 1.0) };
+        assert_ne!(key(&flagged), key(&request("Hello there", 1.0)));
+        assert_eq!(key(&request("Hello there", 1.0)), key(&request("Hello there", 1.0)));
+    }
+}
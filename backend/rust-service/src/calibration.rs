@@ -0,0 +1,241 @@
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+use crate::models::AlignmentMethod;
+
+static CALIBRATION: OnceLock<Calibration> = OnceLock::new();
+
+/// How sure each aligner says it is of its word timings
+///
+/// The `[calibration]` section of the config file; every setting is
+/// optional and the defaults are the service's long-standing confidences,
+/// unadjusted. Review tooling flags words by these numbers
+/// (`min_confidence`, `review_threshold`), so tune them against it.
+///
+/// ```toml
+/// [calibration]
+/// # Share of a weighted or linear word's confidence that rests on the two
+/// # aligners agreeing where it is
+/// agreement_weight = 0.3
+///
+/// [calibration.methods]
+/// weighted = 0.75
+/// linear = 0.5
+/// tts_prediction = 0.6
+/// tts_engine = 0.95
+///
+/// # Per language, over the methods above
+/// [calibration.languages.ja]
+/// weighted = 0.65
+///
+/// # Cues of at most 2 words gain 0.05; past 12 words, each word costs 0.01
+/// [calibration.length]
+/// short_words = 2
+/// short_bonus = 0.05
+/// long_words = 12
+/// long_penalty = 0.01
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Calibration {
+    pub methods: MethodConfidence,
+    pub languages: BTreeMap<String, MethodOverrides>,
+    pub length: LengthAdjustment,
+    pub agreement_weight: f64,
+}
+
+/// Confidence of every word each method aligns
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MethodConfidence {
+    /// Decent but not perfect
+    pub weighted: f64,
+    /// Just a guess
+    pub linear: f64,
+    /// A model of a voice, not the voice itself
+    pub tts_prediction: f64,
+    /// Straight from the synthesizer
+    pub tts_engine: f64,
+}
+
+impl Default for MethodConfidence {
+    fn default() -> Self {
+        MethodConfidence { weighted: 0.75, linear: 0.5, tts_prediction: 0.6, tts_engine: 0.95 }
+    }
+}
+
+/// `MethodConfidence` for one language, where it differs
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MethodOverrides {
+    pub weighted: Option<f64>,
+    pub linear: Option<f64>,
+    pub tts_prediction: Option<f64>,
+    pub tts_engine: Option<f64>,
+}
+
+/// Confidence by how many words a cue has
+///
+/// Spread over a long cue, small errors in each word's share add up.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LengthAdjustment {
+    /// Cues of at most this many words gain `short_bonus`
+    pub short_words: Option<usize>,
+    pub short_bonus: f64,
+    /// Each word past this many costs `long_penalty`
+    pub long_words: Option<usize>,
+    pub long_penalty: f64,
+}
+
+impl Calibration {
+    /// Every confidence and weight must be between 0 and 1
+    pub fn check(&self) -> Result<(), String> {
+        let mut values = vec![
+            ("methods.weighted".to_string(), self.methods.weighted),
+            ("methods.linear".to_string(), self.methods.linear),
+            ("methods.tts_prediction".to_string(), self.methods.tts_prediction),
+            ("methods.tts_engine".to_string(), self.methods.tts_engine),
+            ("length.short_bonus".to_string(), self.length.short_bonus),
+            ("length.long_penalty".to_string(), self.length.long_penalty),
+            ("agreement_weight".to_string(), self.agreement_weight),
+        ];
+        for (language, overrides) in &self.languages {
+            let methods = [
+                ("weighted", overrides.weighted),
+                ("linear", overrides.linear),
+                ("tts_prediction", overrides.tts_prediction),
+                ("tts_engine", overrides.tts_engine),
+            ];
+            values.extend(methods.into_iter().filter_map(|(method, value)| value.map(|value| (format!("languages.{}.{}", language, method), value))));
+        }
+
+        match values.into_iter().find(|(_, value)| !(0.0..=1.0).contains(value)) {
+            Some((name, value)) => Err(format!("Calibration {} must be between 0 and 1, got {}", name, value)),
+            None => Ok(()),
+        }
+    }
+
+    /// Confidence of each word `method` aligns in an `n_words`-word cue
+    ///
+    /// A language's own settings win over a base language's ("pt" for
+    /// "pt-BR"), which win over `methods`.
+    pub fn confidence(&self, method: &AlignmentMethod, language: &str, n_words: usize) -> f64 {
+        let base_language = language.split(['-', '_']).next().unwrap_or_default();
+        let find = |language: &str| self.languages.iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(language))
+            .map(|(_, overrides)| overrides);
+        let overrides = [find(language), find(base_language)];
+        let pick = |base: f64, field: fn(&MethodOverrides) -> Option<f64>| {
+            overrides.iter().flatten().find_map(|overrides| field(overrides)).unwrap_or(base)
+        };
+        let base = match method {
+            AlignmentMethod::Weighted => pick(self.methods.weighted, |overrides| overrides.weighted),
+            AlignmentMethod::Linear => pick(self.methods.linear, |overrides| overrides.linear),
+            AlignmentMethod::TtsPrediction => pick(self.methods.tts_prediction, |overrides| overrides.tts_prediction),
+            AlignmentMethod::TtsEngine => pick(self.methods.tts_engine, |overrides| overrides.tts_engine),
+            AlignmentMethod::ForcedAligner => 1.0,
+        };
+
+        let length = &self.length;
+        let mut adjustment = 0.0;
+        if length.short_words.is_some_and(|short| n_words <= short) {
+            adjustment += length.short_bonus;
+        }
+        if let Some(long) = length.long_words {
+            adjustment -= n_words.saturating_sub(long) as f64 * length.long_penalty;
+        }
+        (base + adjustment).clamp(0.0, 1.0)
+    }
+
+    /// `confidence` for a word the other estimate (weighted or linear)
+    /// places with `agreement`, from 0 (nowhere near) to 1 (the same)
+    pub fn with_agreement(&self, confidence: f64, agreement: f64) -> f64 {
+        confidence * (1.0 - self.agreement_weight + self.agreement_weight * agreement)
+    }
+
+    /// Whether `with_agreement` changes anything, so the other estimate is
+    /// worth working out
+    pub fn uses_agreement(&self) -> bool {
+        self.agreement_weight > 0.0
+    }
+}
+
+/// Overlap of two placements of a word, over the time either covers
+pub fn agreement((start, end): (f64, f64), (other_start, other_end): (f64, f64)) -> f64 {
+    let union = end.max(other_end) - start.min(other_start);
+    if union <= 0.0 {
+        return 1.0;
+    }
+    let overlap = (end.min(other_end) - start.max(other_start)).max(0.0);
+    overlap / union
+}
+
+/// Use `calibration` from now on
+pub fn init(calibration: Calibration) {
+    if CALIBRATION.set(calibration).is_err() {
+        log::warn!("Confidence calibration already initialised");
+    }
+}
+
+/// The configured calibration, or the defaults
+pub fn get() -> &'static Calibration {
+    CALIBRATION.get_or_init(Calibration::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_are_the_old_constants() {
+        let calibration = Calibration::default();
+        assert_eq!(calibration.confidence(&AlignmentMethod::Weighted, "en", 40), 0.75);
+        assert_eq!(calibration.confidence(&AlignmentMethod::Linear, "en", 1), 0.5);
+        assert_eq!(calibration.with_agreement(0.75, 0.0), 0.75);
+        assert!(calibration.check().is_ok());
+    }
+
+    #[test]
+    fn test_languages_and_length() {
+        let calibration: Calibration = toml::from_str(r#"
+            agreement_weight = 0.5
+
+            [languages.pt]
+            weighted = 0.7
+
+            [languages.pt-BR]
+            linear = 0.4
+
+            [length]
+            short_words = 2
+            short_bonus = 0.05
+            long_words = 10
+            long_penalty = 0.01
+        "#).unwrap();
+
+        assert!((calibration.confidence(&AlignmentMethod::Weighted, "pt-BR", 5) - 0.7).abs() < 1e-9);
+        assert!((calibration.confidence(&AlignmentMethod::Linear, "pt-BR", 5) - 0.4).abs() < 1e-9);
+        assert!((calibration.confidence(&AlignmentMethod::Weighted, "en", 2) - 0.8).abs() < 1e-9);
+        assert!((calibration.confidence(&AlignmentMethod::Weighted, "en", 15) - 0.7).abs() < 1e-9);
+        assert!((calibration.with_agreement(0.8, 0.5) - 0.6).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_check() {
+        let calibration = Calibration { agreement_weight: 1.5, ..Default::default() };
+        assert!(calibration.check().is_err());
+
+        let calibration: Calibration = toml::from_str("[languages.ja]\ntts_engine = -0.1\n").unwrap();
+        assert_eq!(calibration.check().unwrap_err(), "Calibration languages.ja.tts_engine must be between 0 and 1, got -0.1");
+    }
+
+    #[test]
+    fn test_agreement() {
+        assert_eq!(agreement((0.0, 1.0), (0.0, 1.0)), 1.0);
+        assert_eq!(agreement((0.0, 1.0), (0.5, 1.5)), 0.5 / 1.5);
+        assert_eq!(agreement((0.0, 1.0), (2.0, 3.0)), 0.0);
+    }
+}
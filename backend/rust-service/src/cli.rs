@@ -0,0 +1,239 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::{Subcommand, ValueEnum};
+use serde::Serialize;
+
+use crate::aligner::align_file;
+use crate::models::{Cue, CueAlignment, FileAlignmentRequest, OverlapPolicy, SubtitleFormat, TokenizeResponse};
+use crate::queue::{self, QueueOptions};
+use crate::subtitles::{self, writer};
+use crate::tokenizer::tokenize_text;
+use crate::validation;
+
+/// Process local subtitle files or queued tasks instead of starting the server
+///
+/// For batch and event-driven pipelines where calling the HTTP service is
+/// overkill; uses the same duration models and frequency lists (`DATA_DIR`
+/// etc.) as the server.
+#[derive(Debug, Clone, PartialEq, Subcommand)]
+pub enum Command {
+    /// Tokenize every cue into `<name>.tokens.json`
+    Tokenize {
+        #[command(flatten)]
+        files: Files,
+    },
+    /// Estimate word timings into `<name>.aligned.<format>`
+    Align {
+        #[command(flatten)]
+        files: Files,
+        /// `srt` and `vtt` write one cue per word
+        #[arg(long, value_enum, default_value_t = Output::Json)]
+        output_format: Output,
+    },
+    /// Take tokenize and align tasks from a message queue and publish the results
+    Consume {
+        #[command(flatten)]
+        queue: QueueOptions,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, clap::Args)]
+pub struct Files {
+    /// Language of the subtitles, e.g. `ja`
+    #[arg(long, short)]
+    pub language: String,
+    /// Directory for the results, created if missing (default: next to each input)
+    #[arg(long, short)]
+    pub output_dir: Option<PathBuf>,
+    /// SRT, WebVTT or ASS files
+    #[arg(required = true)]
+    pub inputs: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Output {
+    Json,
+    Srt,
+    Vtt,
+}
+
+/// One cue's tokens, as written by `tokenize`
+#[derive(Debug, Serialize)]
+struct CueTokens {
+    index: usize,
+    start: f64,
+    end: f64,
+    #[serde(flatten)]
+    tokenized: TokenizeResponse,
+}
+
+/// Run `command` over every input file, or consume the queue until shut down
+///
+/// A file that fails doesn't stop the rest; the error lists how many did.
+pub async fn run(command: &Command) -> Result<(), String> {
+    let files = match command {
+        Command::Tokenize { files } | Command::Align { files, .. } => files,
+        Command::Consume { queue } => return queue::run(queue).await,
+    };
+
+    if !validation::is_known_language(&files.language) {
+        return Err(format!("Unknown language '{}', expected an ISO 639-1 code such as 'en' or 'pt-BR'", files.language));
+    }
+    if let Some(dir) = &files.output_dir {
+        fs::create_dir_all(dir).map_err(|e| format!("Can't create {}: {}", dir.display(), e))?;
+    }
+
+    let mut failed = 0;
+    for input in &files.inputs {
+        match process(command, files, input) {
+            Ok(output) => println!("{} -> {}", input.display(), output.display()),
+            Err(e) => {
+                log::error!("{}: {}", input.display(), e);
+                failed += 1;
+            }
+        }
+    }
+
+    match failed {
+        0 => Ok(()),
+        _ => Err(format!("{} of {} files failed", failed, files.inputs.len())),
+    }
+}
+
+fn process(command: &Command, files: &Files, input: &Path) -> Result<PathBuf, String> {
+    let content = fs::read(input).map_err(|e| format!("Can't read file: {}", e))?;
+    let format = subtitles::format_from_filename(&input.to_string_lossy());
+    let parsed = subtitles::parse(&String::from_utf8_lossy(&content), format).map_err(|e| e.to_string())?;
+
+    let (suffix, body) = match command {
+        Command::Consume { .. } => return Err("Consuming a queue doesn't process files".to_string()),
+        Command::Tokenize { .. } => ("tokens.json", tokenize(&parsed.cues, &files.language)?),
+        Command::Align { output_format, .. } => {
+            let request = FileAlignmentRequest {
+                language: files.language.clone(),
+                cues: parsed.cues,
+                overlap_policy: OverlapPolicy::default(),
+            };
+            let response = align_file(&request).map_err(|e| e.to_string())?;
+            for warning in &response.warnings {
+                log::warn!("{}: cue {}: {}", input.display(), warning.cue_index, warning.message);
+            }
+
+            match output_format {
+                Output::Json => ("aligned.json", to_json(&response)?),
+                Output::Srt => ("aligned.srt", word_subtitles(&response.cues, SubtitleFormat::Srt)),
+                Output::Vtt => ("aligned.vtt", word_subtitles(&response.cues, SubtitleFormat::Vtt)),
+            }
+        }
+    };
+
+    let output = output_path(input, files.output_dir.as_deref(), suffix);
+    fs::write(&output, body).map_err(|e| format!("Can't write {}: {}", output.display(), e))?;
+    Ok(output)
+}
+
+fn tokenize(cues: &[Cue], language: &str) -> Result<String, String> {
+    let tokens = cues.iter()
+        .map(|cue| tokenize_text(&subtitles::strip_tags(&cue.text), language).map(|tokenized| CueTokens {
+            index: cue.index,
+            start: cue.start,
+            end: cue.end,
+            tokenized,
+        }))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    to_json(&tokens)
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String, String> {
+    serde_json::to_string_pretty(value).map_err(|e| format!("Failed to serialize result: {}", e))
+}
+
+/// One cue per word, e.g. for karaoke-style highlighting
+fn word_subtitles(cues: &[CueAlignment], format: SubtitleFormat) -> String {
+    let words: Vec<Cue> = cues.iter()
+        .flat_map(|cue| &cue.timings)
+        .enumerate()
+        .map(|(i, timing)| Cue {
+            index: i + 1,
+            start: timing.start,
+            end: timing.end,
+            text: timing.word.clone(),
+            ..Default::default()
+        })
+        .collect();
+
+    // Keep the words exactly as timed: no re-wrapping or reading-speed stretching
+    let options = writer::WriteOptions { max_line_length: None, max_lines: 1, max_cps: None };
+    writer::write(&words, format, &options).0
+}
+
+/// `<dir>/<stem>.<suffix>`, where `dir` defaults to the input's directory
+fn output_path(input: &Path, output_dir: Option<&Path>, suffix: &str) -> PathBuf {
+    let stem = input.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    let dir = output_dir.or_else(|| input.parent()).unwrap_or(Path::new(""));
+    dir.join(format!("{}.{}", stem, suffix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRT: &str = "1\n00:00:01,000 --> 00:00:02,000\nHello there\n\n2\n00:00:03,000 --> 00:00:04,000\n<i>General</i> Kenobi\n";
+
+    fn input(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dubdub-cli-test-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("episode.srt");
+        fs::write(&path, SRT).unwrap();
+        path
+    }
+
+    fn files(input: &Path) -> Files {
+        Files { language: "en".to_string(), output_dir: None, inputs: vec![input.to_path_buf()] }
+    }
+
+    #[test]
+    fn test_consume_is_not_a_file_command() {
+        let input = input("consume");
+        let queue = QueueOptions { url: String::new(), tasks: String::new(), results: String::new(), concurrency: 1 };
+        let command = Command::Consume { queue };
+        assert!(process(&command, &files(&input), &input).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tokenize_writes_json_per_cue() {
+        let input = input("tokenize");
+        run(&Command::Tokenize { files: files(&input) }).await.unwrap();
+
+        let written = fs::read_to_string(input.with_file_name("episode.tokens.json")).unwrap();
+        let cues: serde_json::Value = serde_json::from_str(&written).unwrap();
+        assert_eq!(cues[1]["tokens"], serde_json::json!(["General", "Kenobi"]));
+        assert_eq!(cues[1]["start"], 3.0);
+    }
+
+    #[tokio::test]
+    async fn test_align_writes_one_cue_per_word() {
+        let input = input("align");
+        let out = input.with_file_name("out");
+        let command = Command::Align {
+            files: Files { output_dir: Some(out.clone()), ..files(&input) },
+            output_format: Output::Vtt,
+        };
+        run(&command).await.unwrap();
+
+        let written = fs::read_to_string(out.join("episode.aligned.vtt")).unwrap();
+        assert!(written.starts_with("WEBVTT"));
+        assert_eq!(written.matches(" --> ").count(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_failures_are_counted() {
+        let missing = std::env::temp_dir().join("dubdub-cli-test-missing.srt");
+        let err = run(&Command::Tokenize { files: files(&missing) }).await.unwrap_err();
+        assert_eq!(err, "1 of 1 files failed");
+
+        assert!(run(&Command::Tokenize { files: Files { language: "klingon".to_string(), ..files(&missing) } }).await.is_err());
+    }
+}
@@ -0,0 +1,208 @@
+use std::future::{ready, Ready};
+use std::ops::Deref;
+use std::sync::OnceLock;
+
+use actix_web::dev::{Decompress, Payload};
+use actix_web::http::header;
+use actix_web::web::{self, BytesMut};
+use actix_web::{FromRequest, HttpRequest, HttpResponse};
+use futures::future::LocalBoxFuture;
+use futures::StreamExt;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::error::{ApiError, ErrorCode};
+
+/// Largest binary request body, unless configured
+const DEFAULT_MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+static MAX_BODY_BYTES: OnceLock<usize> = OnceLock::new();
+
+/// Configure the binary body limit (the same as `MAX_JSON_BYTES`)
+pub fn init(max_body_bytes: usize) {
+    if MAX_BODY_BYTES.set(max_body_bytes).is_err() {
+        log::warn!("Body limit already initialised");
+    }
+}
+
+fn max_body_bytes() -> usize {
+    MAX_BODY_BYTES.get().copied().unwrap_or(DEFAULT_MAX_BODY_BYTES)
+}
+
+/// Serialization for the high-volume endpoints' bodies
+///
+/// Binary formats keep JSON's field names, so they decode to the same
+/// shape; word timings for a full film are a fraction of the JSON size.
+/// Errors are always JSON.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    MessagePack,
+    Cbor,
+}
+
+impl Format {
+    fn from_media_type(media_type: &str) -> Option<Self> {
+        let media_type = media_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+        match media_type.as_str() {
+            "application/json" => Some(Format::Json),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => Some(Format::MessagePack),
+            "application/cbor" => Some(Format::Cbor),
+            _ => None,
+        }
+    }
+
+    /// The first format listed in `Accept` that we can write, or JSON
+    pub fn accepted(req: &HttpRequest) -> Self {
+        req.headers().get(header::ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .and_then(|accept| accept.split(',').find_map(Format::from_media_type))
+            .unwrap_or(Format::Json)
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::MessagePack => "application/msgpack",
+            Format::Cbor => "application/cbor",
+        }
+    }
+
+    pub fn encode(self, value: &impl Serialize) -> Result<Vec<u8>, ApiError> {
+        let encoded = match self {
+            Format::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+            Format::MessagePack => rmp_serde::to_vec_named(value).map_err(|e| e.to_string()),
+            Format::Cbor => cbor4ii::serde::to_vec(Vec::new(), value).map_err(|e| e.to_string()),
+        };
+        encoded.map_err(|e| ApiError::internal(format!("Can't encode response: {}", e)))
+    }
+
+    pub fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, ApiError> {
+        let decoded = match self {
+            Format::Json => serde_json::from_slice(bytes).map_err(|e| e.to_string()),
+            Format::MessagePack => rmp_serde::from_slice(bytes).map_err(|e| e.to_string()),
+            Format::Cbor => cbor4ii::serde::from_slice(bytes).map_err(|e| e.to_string()),
+        };
+        decoded.map_err(|e| ApiError::invalid_input(format!("Invalid {} body: {}", self.content_type(), e)))
+    }
+
+    /// 200 with `value` in this format
+    pub fn respond(self, value: &impl Serialize) -> Result<HttpResponse, ApiError> {
+        Ok(HttpResponse::Ok().content_type(self.content_type()).body(self.encode(value)?))
+    }
+}
+
+/// The response format, from the request's `Accept` header
+impl FromRequest for Format {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Ok(Format::accepted(req)))
+    }
+}
+
+/// A request body decoded according to its `Content-Type`
+///
+/// JSON (or no content type) goes through `web::Json`, keeping its limit
+/// and error handling; MessagePack and CBOR are decompressed and decoded here.
+pub struct Body<T>(pub T);
+
+impl<T> Body<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Body<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: DeserializeOwned + 'static> FromRequest for Body<T> {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let format = req.headers().get(header::CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .and_then(Format::from_media_type);
+
+        match format {
+            None | Some(Format::Json) => {
+                let json = web::Json::<T>::from_request(req, payload);
+                Box::pin(async move { json.await.map(|json| Body(json.into_inner())) })
+            }
+            Some(format) => {
+                let mut stream = Decompress::from_headers(payload.take(), req.headers());
+                let limit = max_body_bytes();
+                Box::pin(async move {
+                    let mut body = BytesMut::new();
+                    while let Some(chunk) = stream.next().await {
+                        let chunk = chunk.map_err(|e| ApiError::invalid_input(format!("Can't read body: {}", e)))?;
+                        if body.len() + chunk.len() > limit {
+                            return Err(ApiError::new(ErrorCode::PayloadTooLarge, format!("Body is larger than allowed (limit: {} bytes)", limit)).into());
+                        }
+                        body.extend_from_slice(&chunk);
+                    }
+                    Ok(Body(format.decode(&body)?))
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AlignmentRequest, TokenizeRequest};
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn test_accepted() {
+        let accept = |value: &'static str| Format::accepted(&TestRequest::default().insert_header(("accept", value)).to_http_request());
+
+        assert_eq!(accept("application/msgpack"), Format::MessagePack);
+        assert_eq!(accept("text/html, application/cbor;q=0.9"), Format::Cbor);
+        assert_eq!(accept("application/json, application/cbor"), Format::Json);
+        assert_eq!(accept("*/*"), Format::Json);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let request = AlignmentRequest {
+            text: "Hello world".to_string(),
+            language: "en".to_string(),
+            subtitle_start: 1.0,
+            subtitle_end: 2.5,
+            ..Default::default()
+        };
+
+        for format in [Format::Json, Format::MessagePack, Format::Cbor] {
+            let decoded: AlignmentRequest = format.decode(&format.encode(&request).unwrap()).unwrap();
+            assert_eq!(decoded.text, "Hello world");
+            assert_eq!(decoded.subtitle_end, 2.5);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_body_by_content_type() {
+        let request = TokenizeRequest { text: "Hola".to_string(), language: "es".to_string(), known: None };
+        let (req, mut payload) = TestRequest::post()
+            .insert_header(("content-type", "application/msgpack"))
+            .set_payload(Format::MessagePack.encode(&request).unwrap())
+            .to_http_parts();
+
+        let body = Body::<TokenizeRequest>::from_request(&req, &mut payload).await.unwrap();
+        assert_eq!(body.text, "Hola");
+
+        let (req, mut payload) = TestRequest::post()
+            .insert_header(("content-type", "application/cbor"))
+            .set_payload(vec![0xff, 0x00])
+            .to_http_parts();
+        assert!(Body::<TokenizeRequest>::from_request(&req, &mut payload).await.is_err());
+    }
+}
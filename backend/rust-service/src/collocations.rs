@@ -0,0 +1,226 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::frequency::{self, WordSet};
+use crate::models::{AssociationMeasure, Collocation, Cue};
+use crate::tokenizer;
+
+/// What `find` looks for
+#[derive(Debug, Clone, Copy)]
+pub struct CollocationOptions {
+    /// Longest phrase, in words
+    pub max_words: usize,
+    /// Fewest uses for a phrase to count
+    pub min_count: usize,
+    pub measure: AssociationMeasure,
+    /// Most phrases returned
+    pub limit: usize,
+}
+
+/// Uses of one word sequence, and where it first appears
+#[derive(Debug)]
+struct Ngram {
+    count: usize,
+    /// Index of the first cue it appears in
+    cue: usize,
+    /// Its text there
+    surface: String,
+}
+
+/// Phrases a file uses more often than its words alone would explain,
+/// best first, and the number of words in the file
+///
+/// # How it works:
+/// 1. Split each cue into runs of words, breaking at punctuation so a
+///    phrase never spans a comma or sentence end
+/// 2. Count every sequence of one to `max_words` words in the runs
+/// 3. Score sequences used at least `min_count` times by how much more
+///    often their words appear together than apart; longer phrases are
+///    scored as their first words followed by the last
+/// 4. Drop phrases made only of stopwords ("of the"), and phrases that only
+///    ever appear inside a longer one ("take care" within "take care of")
+pub fn find(cues: &[Cue], language: &str, options: &CollocationOptions) -> (Vec<Collocation>, usize) {
+    find_with(cues, language, options, frequency::lists().stopwords(language))
+}
+
+fn find_with(cues: &[Cue], language: &str, options: &CollocationOptions, stopwords: &WordSet) -> (Vec<Collocation>, usize) {
+    // Steps 1 and 2: Runs and counts
+    let mut ngrams: HashMap<Vec<String>, Ngram> = HashMap::new();
+    let mut n_tokens = 0;
+
+    for (at, cue) in cues.iter().enumerate() {
+        for run in runs(&cue.text, language) {
+            n_tokens += run.len();
+            for n in 1..=options.max_words {
+                for window in run.windows(n) {
+                    let words: Vec<String> = window.iter().map(|(word, _, _)| word.clone()).collect();
+                    let ngram = ngrams.entry(words).or_insert_with(|| Ngram {
+                        count: 0,
+                        cue: at,
+                        surface: cue.text[window[0].1..window[n - 1].2].split_whitespace().collect::<Vec<_>>().join(" "),
+                    });
+                    ngram.count += 1;
+                }
+            }
+        }
+    }
+
+    // Step 3: Association scores
+    let count = |words: &[String]| ngrams.get(words).map_or(0, |ngram| ngram.count);
+    let mut scored: Vec<(&Vec<String>, &Ngram, f64)> = ngrams.iter()
+        .filter(|(words, ngram)| words.len() >= 2 && ngram.count >= options.min_count)
+        .filter(|(words, _)| !words.iter().all(|word| stopwords.contains(word)))
+        .filter_map(|(words, ngram)| {
+            let (head, last) = words.split_at(words.len() - 1);
+            let score = match options.measure {
+                AssociationMeasure::LogLikelihood => log_likelihood(ngram.count, count(head), count(last), n_tokens)?,
+                AssociationMeasure::Pmi => Some(pmi(ngram.count, words.iter().map(|word| count(std::slice::from_ref(word))), n_tokens))
+                    .filter(|score| *score > 0.0)?,
+            };
+            Some((words, ngram, score))
+        })
+        .collect();
+
+    // Step 4: Phrases only seen inside longer ones
+    let subsumed: HashSet<&[String]> = scored.iter()
+        .flat_map(|(words, ngram, _)| {
+            [&words[..words.len() - 1], &words[1..]].into_iter()
+                .filter(move |part| part.len() >= 2 && count(part) == ngram.count)
+        })
+        .collect();
+    scored.retain(|(words, _, _)| !subsumed.contains(words.as_slice()));
+
+    scored.sort_by(|a, b| b.2.total_cmp(&a.2).then(b.1.count.cmp(&a.1.count)).then(a.0.cmp(b.0)));
+    let collocations = scored.into_iter()
+        .take(options.limit)
+        .map(|(words, ngram, score)| {
+            let cue = &cues[ngram.cue];
+            Collocation {
+                phrase: ngram.surface.clone(),
+                words: words.clone(),
+                count: ngram.count,
+                score,
+                first_start: cue.start,
+                first_cue_index: cue.index,
+                context: cue.text.split_whitespace().collect::<Vec<_>>().join(" "),
+            }
+        })
+        .collect();
+
+    (collocations, n_tokens)
+}
+
+/// Runs of lowercased words with their byte spans, split at anything
+/// between words other than whitespace, and at numbers and symbols
+fn runs(text: &str, language: &str) -> Vec<Vec<(String, usize, usize)>> {
+    let mut runs = Vec::new();
+    let mut run: Vec<(String, usize, usize)> = Vec::new();
+    let mut previous_end = 0;
+
+    for token in tokenizer::tokens(text, language) {
+        let between = &text[previous_end..token.start];
+        previous_end = token.end;
+        if !between.trim().is_empty() || !token.text.chars().any(char::is_alphabetic) {
+            runs.push(std::mem::take(&mut run));
+        }
+        if token.text.chars().any(char::is_alphabetic) {
+            run.push((token.text.to_lowercase().replace('’', "'"), token.start, token.end));
+        }
+    }
+    runs.push(run);
+    runs.retain(|run| !run.is_empty());
+    runs
+}
+
+/// Dunning's log-likelihood ratio (G²) for `head` followed by `last`, or
+/// `None` if they appear together less often than chance
+fn log_likelihood(together: usize, head: usize, last: usize, total: usize) -> Option<f64> {
+    let total = total as f64;
+    let (together, head, last) = (together as f64, head as f64, last as f64);
+    if together * total <= head * last {
+        return None;
+    }
+
+    // Observed counts of the 2×2 table: with and without each part
+    let observed = [
+        together,
+        (head - together).max(0.0),
+        (last - together).max(0.0),
+        (total - head - last + together).max(0.0),
+    ];
+    let expected = [
+        head * last / total,
+        head * (total - last) / total,
+        (total - head) * last / total,
+        (total - head) * (total - last) / total,
+    ];
+    let g2: f64 = observed.iter().zip(expected)
+        .filter(|(observed, expected)| **observed > 0.0 && *expected > 0.0)
+        .map(|(observed, expected)| observed * (observed / expected).ln())
+        .sum();
+    Some(2.0 * g2)
+}
+
+/// Pointwise mutual information (bits): how much likelier the words are
+/// together than if they were independent
+fn pmi(together: usize, words: impl Iterator<Item = usize>, total: usize) -> f64 {
+    let total = total as f64;
+    let independent: f64 = words.map(|count| count as f64 / total).product();
+    (together as f64 / total / independent).log2()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::cue;
+
+    fn options(measure: AssociationMeasure) -> CollocationOptions {
+        CollocationOptions { max_words: 3, min_count: 2, measure, limit: 10 }
+    }
+
+    fn cues() -> Vec<Cue> {
+        vec![
+            cue(1, 1.0, 2.0, "Take care of it, please."),
+            cue(2, 2.0, 3.0, "I will take care of him."),
+            cue(3, 3.0, 4.0, "You take care of yourself. Of it I know nothing."),
+            cue(4, 4.0, 5.0, "Him? I know him."),
+        ]
+    }
+
+    #[test]
+    fn test_runs_break_at_punctuation() {
+        let runs = runs("Take care, of 42 it!", "en");
+        let words: Vec<Vec<&str>> = runs.iter().map(|run| run.iter().map(|(word, _, _)| word.as_str()).collect()).collect();
+        assert_eq!(words, vec![vec!["take", "care"], vec!["of"], vec!["it"]]);
+    }
+
+    #[test]
+    fn test_finds_longest_phrase() {
+        let (collocations, n_tokens) = find_with(&cues(), "xx", &options(AssociationMeasure::LogLikelihood), &WordSet::default());
+        assert_eq!(n_tokens, 25);
+
+        let top = &collocations[0];
+        assert_eq!(top.words, vec!["take", "care", "of"]);
+        assert_eq!(top.phrase, "Take care of");
+        assert_eq!(top.count, 3);
+        assert_eq!(top.first_cue_index, 1);
+        // Only ever inside "take care of"
+        assert!(collocations.iter().all(|collocation| collocation.words != ["take", "care"]));
+        assert!(collocations.iter().any(|collocation| collocation.words == ["i", "know"]));
+    }
+
+    #[test]
+    fn test_stopword_phrases_dropped() {
+        let stopwords = WordSet::parse("i\nknow\n");
+        let (collocations, _) = find_with(&cues(), "xx", &options(AssociationMeasure::Pmi), &stopwords);
+        assert!(collocations.iter().all(|collocation| collocation.words != ["i", "know"]));
+        assert!(collocations.iter().all(|collocation| collocation.score > 0.0));
+    }
+
+    #[test]
+    fn test_log_likelihood() {
+        // Always together: strongly associated
+        assert!(log_likelihood(10, 10, 10, 100).unwrap() > 50.0);
+        // Exactly as often as chance
+        assert_eq!(log_likelihood(1, 10, 10, 100), None);
+    }
+}
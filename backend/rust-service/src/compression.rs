@@ -0,0 +1,81 @@
+use actix_web::body::{BodySize, MessageBody};
+use actix_web::dev::ServiceResponse;
+use actix_web::http::header::{self, ContentEncoding};
+
+/// Responses smaller than this go out as they are; compressing them saves
+/// less than the encoding costs
+pub const MIN_COMPRESSED_BYTES: u64 = 1024;
+
+/// Content types that are compressed already (actix-web skips images and
+/// video itself)
+const COMPRESSED_TYPES: &[&str] = &["application/zip", "application/gzip", "application/zstd", "audio/"];
+
+/// Keep `Compress` off small and already compressed responses, by marking
+/// them `Content-Encoding: identity`
+pub fn exempt<B: MessageBody>(mut res: ServiceResponse<B>) -> ServiceResponse<B> {
+    let small = matches!(res.response().body().size(), BodySize::Sized(size) if size < MIN_COMPRESSED_BYTES);
+    let compressed = res.headers().get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|content_type| COMPRESSED_TYPES.iter().any(|compressed| content_type.starts_with(compressed)));
+
+    if (small || compressed) && !res.headers().contains_key(header::CONTENT_ENCODING) {
+        res.headers_mut().insert(header::CONTENT_ENCODING, ContentEncoding::Identity.to_header_value());
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+    use actix_web::dev::Service;
+    use actix_web::middleware::Compress;
+    use actix_web::{test, web, App, HttpResponse};
+
+    const JSON: &str = r#"{"tokens": ["Hello", "world"]}"#;
+
+    async fn call(path: &str, accept_encoding: &str) -> (Option<String>, Vec<u8>) {
+        let app = test::init_service(App::new()
+            .wrap_fn(|req, srv| {
+                let fut = srv.call(req);
+                async { fut.await.map(exempt) }
+            })
+            .wrap(Compress::default())
+            .route("/large", web::get().to(|| async { HttpResponse::Ok().content_type("application/json").body(JSON.repeat(100)) }))
+            .route("/small", web::get().to(|| async { HttpResponse::Ok().content_type("application/json").body(JSON) }))
+            .route("/zip", web::get().to(|| async { HttpResponse::Ok().content_type("application/zip").body(vec![0u8; 4096]) })))
+            .await;
+
+        let req = test::TestRequest::get().uri(path).insert_header((header::ACCEPT_ENCODING, accept_encoding)).to_request();
+        let res = test::call_service(&app, req).await;
+        let encoding = res.headers().get(header::CONTENT_ENCODING).map(|value| value.to_str().unwrap().to_string());
+        (encoding, test::read_body(res).await.to_vec())
+    }
+
+    #[actix_web::test]
+    async fn test_large_responses_round_trip() {
+        let (encoding, body) = call("/large", "gzip").await;
+        assert_eq!(encoding.as_deref(), Some("gzip"));
+        let mut decoded = String::new();
+        flate2::read::GzDecoder::new(body.as_slice()).read_to_string(&mut decoded).unwrap();
+        assert_eq!(decoded, JSON.repeat(100));
+
+        let (encoding, body) = call("/large", "br").await;
+        assert_eq!(encoding.as_deref(), Some("br"));
+        let mut decoded = String::new();
+        brotli::Decompressor::new(body.as_slice(), 4096).read_to_string(&mut decoded).unwrap();
+        assert_eq!(decoded, JSON.repeat(100));
+    }
+
+    #[actix_web::test]
+    async fn test_small_and_compressed_responses_left_alone() {
+        let (encoding, body) = call("/small", "gzip, br").await;
+        assert_eq!(encoding.as_deref(), Some("identity"));
+        assert_eq!(body, JSON.as_bytes());
+
+        let (encoding, body) = call("/zip", "gzip, br").await;
+        assert_eq!(encoding.as_deref(), Some("identity"));
+        assert_eq!(body, vec![0u8; 4096]);
+    }
+}
@@ -0,0 +1,263 @@
+use std::error::Error as StdError;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, OnceLock};
+use std::task::{Context, Poll};
+
+use actix_web::body::{BodySize, BoxBody, MessageBody};
+use actix_web::dev::ServiceResponse;
+use actix_web::http::{header, StatusCode};
+use actix_web::web::Bytes;
+use actix_web::HttpResponse;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::{Instant, Sleep};
+
+use crate::error::{ApiError, ErrorCode, ErrorResponse};
+use crate::versioning;
+
+/// Requests in flight across all `/api` routes, unless configured
+pub const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 512;
+
+/// Seconds clients are told to wait after a 503
+const RETRY_AFTER_SECS: u64 = 1;
+
+/// `/api` paths that are never shed, so health checks keep answering
+const EXEMPT_PATHS: &[&str] = &["health", "health/deep"];
+
+/// Caps on requests in flight, overall and for individual routes
+#[derive(Debug)]
+pub struct ConcurrencyLimits {
+    global: Option<Arc<Semaphore>>,
+    /// Path below `/api` (e.g. `align/file`) and its own cap
+    routes: Vec<(String, Arc<Semaphore>)>,
+}
+
+/// Slots held until the response is sent (see `hold`)
+pub type Permits = Vec<OwnedSemaphorePermit>;
+
+/// One connection's cap on requests in flight (`MAX_CONCURRENT_STREAMS`),
+/// kept in the connection's data from when it's accepted
+///
+/// Only HTTP/2 connections carry more than one request at a time, so in
+/// practice this caps the streams a client multiplexes over a connection.
+/// actix doesn't let the limit be announced in the HTTP/2 settings, so
+/// streams past it are answered 503 like any other shed request.
+#[derive(Debug, Clone)]
+pub struct ConnectionStreams(Arc<Semaphore>);
+
+impl ConnectionStreams {
+    pub fn new(max: usize) -> Self {
+        ConnectionStreams(Arc::new(Semaphore::new(max)))
+    }
+}
+
+impl ConcurrencyLimits {
+    /// Build the limits from `MAX_CONCURRENT_REQUESTS` (0 for no global cap)
+    /// and `ROUTE_CONCURRENCY`, e.g. `align/file=8,batch/zip=2`
+    pub fn parse(global: usize, routes: Option<&str>) -> Result<Self, String> {
+        let routes = routes.unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (route, limit) = entry.split_once('=')
+                    .ok_or_else(|| format!("Invalid route concurrency '{}', expected route=limit", entry))?;
+                let limit: usize = limit.trim().parse()
+                    .ok()
+                    .filter(|limit| *limit > 0)
+                    .ok_or_else(|| format!("Invalid concurrency limit for '{}'", route.trim()))?;
+                let route = route.trim();
+                let route = versioning::api_path(route).unwrap_or(route.trim_start_matches('/'));
+                Ok((route.to_string(), Arc::new(Semaphore::new(limit))))
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(ConcurrencyLimits {
+            global: (global > 0).then(|| Arc::new(Semaphore::new(global))),
+            routes,
+        })
+    }
+
+    /// Take a slot for the request, or fail at once if the service, the
+    /// route or the request's connection is saturated
+    ///
+    /// Requests never queue for a slot: waiting would only move the queue
+    /// from actix into here, with latency growing without bound.
+    pub fn acquire(&self, path: &str, connection: Option<&ConnectionStreams>) -> Result<Permits, ApiError> {
+        let Some(route) = versioning::api_path(path).filter(|route| !EXEMPT_PATHS.contains(route)) else {
+            return Ok(Vec::new());
+        };
+        let busy = |scope: &str| ApiError::new(ErrorCode::Unavailable, format!("Too many concurrent requests{}, try again shortly", scope));
+
+        let mut permits = Vec::with_capacity(3);
+        if let Some(ConnectionStreams(streams)) = connection {
+            permits.push(streams.clone().try_acquire_owned().map_err(|_| busy(" on this connection"))?);
+        }
+        if let Some(global) = &self.global {
+            permits.push(global.clone().try_acquire_owned().map_err(|_| busy(""))?);
+        }
+        if let Some((_, limit)) = self.routes.iter().find(|(limited, _)| limited == route) {
+            permits.push(limit.clone().try_acquire_owned().map_err(|_| busy(&format!(" to /api/{}", route)))?);
+        }
+        Ok(permits)
+    }
+}
+
+static LIMITS: OnceLock<ConcurrencyLimits> = OnceLock::new();
+
+pub fn init(limits: ConcurrencyLimits) {
+    match &limits.global {
+        Some(global) => log::info!("Serving up to {} concurrent requests", global.available_permits()),
+        None => log::info!("No global concurrency limit"),
+    }
+    for (route, limit) in &limits.routes {
+        log::info!("Serving up to {} concurrent requests to /api/{}", limit.available_permits(), route);
+    }
+
+    if LIMITS.set(limits).is_err() {
+        log::warn!("Concurrency limits already initialised");
+    }
+}
+
+/// Slots for a request to `path` over `connection`, or why it's shed
+pub fn acquire(path: &str, connection: Option<&ConnectionStreams>) -> Result<Permits, ApiError> {
+    match LIMITS.get() {
+        Some(limits) => limits.acquire(path, connection),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Keep a request's slots, and its deadline, until a streamed response body
+/// is fully sent or the client goes away
+///
+/// Without this, both would end when the handler returns the response head
+/// and a long stream would run unbounded and uncounted. Bodies already in
+/// memory go out as they are; WebSocket sessions keep their slots but not the
+/// deadline.
+pub fn hold<B: MessageBody + 'static>(res: ServiceResponse<B>, permits: Permits, deadline: Option<Instant>) -> ServiceResponse<BoxBody> {
+    let deadline = deadline.filter(|_| res.status() != StatusCode::SWITCHING_PROTOCOLS);
+    if !matches!(res.response().body().size(), BodySize::Stream) || (permits.is_empty() && deadline.is_none()) {
+        return res.map_into_boxed_body();
+    }
+    res.map_body(|_, body| BoxBody::new(Held {
+        body: BoxBody::new(body),
+        _permits: permits,
+        deadline: deadline.map(|deadline| Box::pin(tokio::time::sleep_until(deadline))),
+    }))
+}
+
+/// A streamed body holding its request's slots, cut off at its deadline
+struct Held {
+    body: BoxBody,
+    _permits: Permits,
+    deadline: Option<Pin<Box<Sleep>>>,
+}
+
+impl MessageBody for Held {
+    type Error = Box<dyn StdError>;
+
+    fn size(&self) -> BodySize {
+        self.body.size()
+    }
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        if let Some(deadline) = &mut self.deadline
+            && deadline.as_mut().poll(cx).is_ready()
+        {
+            return Poll::Ready(Some(Err("Response took longer than the request timeout".into())));
+        }
+        Pin::new(&mut self.body).poll_next(cx)
+    }
+}
+
+/// 503 with `Retry-After` for a shed request
+///
+/// Logged at debug level only: under load there are many, and the access
+/// log already records them.
+pub fn shed_response(error: ApiError) -> HttpResponse {
+    log::debug!("Shedding request: {}", error);
+    HttpResponse::build(error.code.status())
+        .insert_header((header::RETRY_AFTER, RETRY_AFTER_SECS.to_string()))
+        .json(ErrorResponse { error })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    #[test]
+    fn test_parse_routes() {
+        let limits = ConcurrencyLimits::parse(0, Some("align/file=8, /api/v1/batch/zip=2")).unwrap();
+
+        assert!(limits.global.is_none());
+        let routes: Vec<&str> = limits.routes.iter().map(|(route, _)| route.as_str()).collect();
+        assert_eq!(routes, vec!["align/file", "batch/zip"]);
+
+        assert!(ConcurrencyLimits::parse(0, Some("align/file")).is_err());
+        assert!(ConcurrencyLimits::parse(0, Some("align/file=0")).is_err());
+    }
+
+    #[test]
+    fn test_sheds_when_saturated() {
+        let limits = ConcurrencyLimits::parse(3, Some("align/file=1")).unwrap();
+
+        let first = limits.acquire("/api/v1/align/file", None).unwrap();
+        let error = limits.acquire("/api/align/file", None).unwrap_err();
+        assert_eq!(error.code, ErrorCode::Unavailable);
+        assert!(limits.acquire("/api/tokenize", None).is_ok());
+
+        drop(first);
+        assert!(limits.acquire("/api/align/file", None).is_ok());
+    }
+
+    #[test]
+    fn test_global_cap_and_exemptions() {
+        let limits = ConcurrencyLimits::parse(1, None).unwrap();
+
+        let _held = limits.acquire("/api/tokenize", None).unwrap();
+        assert!(limits.acquire("/api/align", None).is_err());
+        assert!(limits.acquire("/api/health", None).is_ok());
+        assert!(limits.acquire("/readyz", None).is_ok());
+    }
+
+    fn streamed(chunks: Vec<&'static str>) -> ServiceResponse<BoxBody> {
+        let body = futures::stream::iter(chunks).map(|chunk| Ok::<_, std::convert::Infallible>(Bytes::from(chunk)));
+        ServiceResponse::new(actix_web::test::TestRequest::default().to_http_request(), HttpResponse::Ok().streaming(body))
+    }
+
+    #[actix_web::test]
+    async fn test_streamed_body_holds_slots_until_sent() {
+        let limits = ConcurrencyLimits::parse(1, None).unwrap();
+
+        let res = hold(streamed(vec!["a", "b"]), limits.acquire("/api/align", None).unwrap(), None);
+        assert!(limits.acquire("/api/align", None).is_err());
+
+        let body = actix_web::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(body, "ab");
+        assert!(limits.acquire("/api/align", None).is_ok());
+    }
+
+    #[actix_web::test]
+    async fn test_streamed_body_cut_off_at_deadline() {
+        let body = futures::stream::pending::<Result<Bytes, std::convert::Infallible>>();
+        let res = ServiceResponse::new(actix_web::test::TestRequest::default().to_http_request(), HttpResponse::Ok().streaming(body));
+
+        let res = hold(res, Vec::new(), Some(Instant::now() + std::time::Duration::from_millis(10)));
+        assert!(actix_web::body::to_bytes(res.into_body()).await.is_err());
+    }
+
+    #[test]
+    fn test_connection_streams() {
+        let limits = ConcurrencyLimits::parse(0, None).unwrap();
+        let connection = ConnectionStreams::new(1);
+
+        let held = limits.acquire("/api/align", Some(&connection)).unwrap();
+        assert!(limits.acquire("/api/align", Some(&connection)).is_err());
+        assert!(limits.acquire("/api/align", Some(&ConnectionStreams::new(1))).is_ok());
+        assert!(limits.acquire("/api/health", Some(&connection)).is_ok());
+
+        drop(held);
+        assert!(limits.acquire("/api/align", Some(&connection)).is_ok());
+    }
+}
@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::models::{ContentCategory, DataFile, FailedDataFile, ResidentData, TokenPosition};
+use crate::reload::{self, Reloadable};
+use crate::resources::HeapSize;
+use crate::terms::TermList;
+
+/// Lists loaded at startup, shared by every request until reloaded
+static LISTS: Reloadable<ContentLists> = Reloadable::new();
+
+/// Flagged words and phrases of one language, each with its categories
+///
+/// A phrase flags every token it covers ("son of a ..." flags all four).
+pub struct ContentList {
+    terms: TermList,
+    /// By term index
+    categories: Vec<Vec<ContentCategory>>,
+}
+
+impl ContentList {
+    /// Build from `(term, category)` pairs; a term in several lists gets
+    /// every category
+    pub fn new(entries: Vec<(String, ContentCategory)>, language: &str) -> Result<Self, String> {
+        let mut index: HashMap<String, usize> = HashMap::new();
+        let mut terms = Vec::new();
+        let mut categories: Vec<Vec<ContentCategory>> = Vec::new();
+
+        for (term, category) in entries {
+            let term = term.trim().to_lowercase();
+            if term.is_empty() {
+                continue;
+            }
+            let at = *index.entry(term.clone()).or_insert_with(|| {
+                terms.push(term);
+                categories.push(Vec::new());
+                categories.len() - 1
+            });
+            if !categories[at].contains(&category) {
+                categories[at].push(category);
+                categories[at].sort();
+            }
+        }
+
+        Ok(ContentList { terms: TermList::new(terms, language)?, categories })
+    }
+
+    /// Categories of each token at `positions` in `text`; empty for clean tokens
+    pub fn flags(&self, text: &str, positions: &[TokenPosition]) -> Vec<Vec<ContentCategory>> {
+        let mut flags = vec![Vec::new(); positions.len()];
+        for found in self.terms.find(text) {
+            for (token, token_flags) in positions.iter().zip(flags.iter_mut()) {
+                if token.start < found.end && found.start < token.end {
+                    for category in &self.categories[found.term] {
+                        if !token_flags.contains(category) {
+                            token_flags.push(*category);
+                            token_flags.sort();
+                        }
+                    }
+                }
+            }
+        }
+        flags
+    }
+
+    pub fn len(&self) -> usize {
+        self.terms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+}
+
+impl HeapSize for ContentList {
+    fn heap_size(&self) -> usize {
+        let categories = self.categories.iter().map(|categories| categories.capacity() * size_of::<ContentCategory>()).sum::<usize>();
+        self.terms.heap_size() + self.categories.capacity() * size_of::<Vec<ContentCategory>>() + categories
+    }
+}
+
+/// All loaded lists, keyed by lowercase language code
+#[derive(Default)]
+pub struct ContentLists {
+    /// Shared, so evicting one language doesn't copy the others
+    lists: HashMap<String, Arc<ContentList>>,
+    /// Files loaded, by language
+    files: Vec<DataFile>,
+    /// Files that couldn't be read or built into a list
+    failed: Vec<FailedDataFile>,
+}
+
+impl ContentLists {
+    /// Load every `<language>.<category>.txt` in `dir`, e.g. `en.profanity.txt`:
+    /// one word or phrase per line, `#` comments
+    pub fn load_dir(dir: &Path) -> Self {
+        Self::load(dir, |_| true)
+    }
+
+    /// `load_dir`, reading only the files of languages `wanted` accepts
+    /// (by lowercase code)
+    pub fn load(dir: &Path, wanted: impl Fn(&str) -> bool) -> Self {
+        let mut entries: HashMap<String, Vec<(String, ContentCategory)>> = HashMap::new();
+        let mut files = Vec::new();
+        let mut failed = Vec::new();
+
+        let dir_entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::info!("No content lists loaded from {}: {}", dir.display(), e);
+                return ContentLists::default();
+            }
+        };
+
+        for entry in dir_entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("txt") {
+                continue;
+            }
+            let Some((language, category)) = path.file_stem().and_then(|stem| stem.to_str()).and_then(|stem| stem.split_once('.')) else {
+                continue;
+            };
+            if !wanted(&language.to_lowercase()) {
+                continue;
+            }
+            let Some(category) = ContentCategory::parse(category) else {
+                let error = format!("unknown category '{}'", category);
+                log::warn!("Skipping content list {}: {}", path.display(), error);
+                failed.push(FailedDataFile::new(language, &path, error));
+                continue;
+            };
+
+            match fs::read_to_string(&path) {
+                Ok(content) => {
+                    let terms: Vec<(String, ContentCategory)> = content.lines()
+                        .map(str::trim)
+                        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                        .map(|line| (line.to_string(), category))
+                        .collect();
+                    log::info!("Loaded {} {} terms for '{}'", terms.len(), category.name(), language);
+                    files.push(DataFile::new(language, &path, &content, terms.len()));
+                    entries.entry(language.to_lowercase()).or_default().extend(terms);
+                }
+                Err(e) => {
+                    log::warn!("Skipping content list {}: {}", path.display(), e);
+                    failed.push(FailedDataFile::new(language, &path, e.to_string()));
+                }
+            }
+        }
+
+        let mut lists = HashMap::new();
+        for (language, terms) in entries {
+            match ContentList::new(terms, &language) {
+                Ok(list) => {
+                    lists.insert(language, Arc::new(list));
+                }
+                Err(e) => {
+                    log::warn!("Skipping content lists for '{}': {}", language, e);
+                    let (unbuilt, built): (Vec<_>, Vec<_>) = files.into_iter().partition(|file: &DataFile| file.language == language);
+                    failed.extend(unbuilt.into_iter().map(|file| FailedDataFile { language: file.language, file: file.file, error: e.clone() }));
+                    files = built;
+                }
+            }
+        }
+
+        files.sort_by(|a, b| a.language.cmp(&b.language));
+        ContentLists { lists, files, failed }
+    }
+
+    /// Number of languages with lists
+    pub fn len(&self) -> usize {
+        self.lists.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lists.is_empty()
+    }
+
+    /// The files the lists came from
+    pub fn files(&self) -> &[DataFile] {
+        &self.files
+    }
+
+    /// The files that failed to load
+    pub fn failed(&self) -> &[FailedDataFile] {
+        &self.failed
+    }
+
+    /// These lists with every language that had a file fail to load back on
+    /// its lists from `previous`
+    pub fn keeping(mut self, previous: &Self) -> Self {
+        for language in reload::failed_languages(&self.failed) {
+            reload::restore(&mut self.lists, &previous.lists, &language);
+            reload::restore_files(&mut self.files, &previous.files, &language);
+        }
+        self
+    }
+
+    /// Lists for a language (`pt-BR` falls back to `pt`), if any were loaded
+    pub fn get(&self, language: &str) -> Option<&ContentList> {
+        let language = language.to_lowercase();
+        self.lists.get(&language)
+            .or_else(|| self.lists.get(language.split(['-', '_']).next().unwrap_or_default()))
+            .map(Arc::as_ref)
+    }
+
+    /// Each language's lists with their sizes, for `/admin/languages`
+    pub fn resident(&self) -> Vec<(String, ResidentData)> {
+        self.lists.iter()
+            .map(|(language, list)| (language.clone(), ResidentData {
+                name: "content_list".to_string(),
+                entries: list.len(),
+                approx_bytes: list.heap_size(),
+            }))
+            .collect()
+    }
+
+    /// These lists with `language`'s taken from `loaded`
+    pub fn with(&self, language: &str, loaded: &Self) -> Self {
+        let mut lists = self.without(language);
+        reload::restore(&mut lists.lists, &loaded.lists, language);
+        reload::restore_files(&mut lists.files, &loaded.files, language);
+        lists
+    }
+
+    /// These lists without `language`'s
+    pub fn without(&self, language: &str) -> Self {
+        ContentLists {
+            lists: self.lists.iter()
+                .filter(|(code, _)| *code != language)
+                .map(|(code, list)| (code.clone(), list.clone()))
+                .collect(),
+            files: self.files.iter().filter(|file| file.language != language).cloned().collect(),
+            failed: self.failed.clone(),
+        }
+    }
+}
+
+/// Load lists at startup
+pub fn init(dir: &Path) {
+    if !LISTS.init(dir, ContentLists::load_dir) {
+        log::warn!("Content lists already initialised");
+    }
+}
+
+/// Load the lists of the languages `wanted` accepts again from the `init`
+/// directory (see `reload::reload`)
+pub fn reload(wanted: impl Fn(&str) -> bool) -> Option<Arc<ContentLists>> {
+    LISTS.reload(|dir, previous| ContentLists::load(dir, wanted).keeping(previous))
+}
+
+/// Load `language`'s lists back after an eviction (see `resources::restore`)
+pub fn restore(language: &str) -> bool {
+    LISTS.reload(|dir, current| current.with(language, &ContentLists::load(dir, |code| code == language))).is_some()
+}
+
+/// Drop `language`'s lists until they're next used (see `resources::evict`)
+pub fn evict(language: &str) -> bool {
+    LISTS.get().lists.contains_key(language) && LISTS.update(|lists| lists.without(language)).is_some()
+}
+
+/// Lists loaded by `init` or the latest reload, or none if it was never called
+pub fn lists() -> Arc<ContentLists> {
+    LISTS.get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenizer::tokenize_text;
+
+    #[test]
+    fn test_flags_per_token() {
+        let list = ContentList::new(vec![
+            ("damn".to_string(), ContentCategory::Profanity),
+            ("Strip Club".to_string(), ContentCategory::Adult),
+            ("damn".to_string(), ContentCategory::Profanity),
+        ], "en").unwrap();
+        assert_eq!(list.len(), 2);
+
+        let text = "Damn, the strip club was damned";
+        let flags = list.flags(text, &tokenize_text(text, "en").unwrap().positions);
+        assert_eq!(flags, vec![
+            vec![ContentCategory::Profanity],
+            vec![],
+            vec![ContentCategory::Adult],
+            vec![ContentCategory::Adult],
+            vec![],
+            vec![],
+        ]);
+    }
+
+    #[test]
+    fn test_load_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("es.profanity.txt"), "# comment\nmierda\n").unwrap();
+        fs::write(dir.path().join("es.slur.txt"), "mierda\n").unwrap();
+        fs::write(dir.path().join("es.unknown.txt"), "x\n").unwrap();
+
+        let lists = ContentLists::load_dir(dir.path());
+        assert_eq!(lists.files().len(), 2);
+        assert_eq!(lists.failed().len(), 1);
+        assert_eq!(lists.failed()[0].error, "unknown category 'unknown'");
+        let flags = lists.get("es-MX").unwrap().flags("mierda", &tokenize_text("mierda", "es").unwrap().positions);
+        assert_eq!(flags, vec![vec![ContentCategory::Profanity, ContentCategory::Slur]]);
+
+        let resident = lists.resident();
+        assert_eq!(resident[0].0, "es");
+        assert_eq!(resident[0].1.entries, 1);
+        assert!(lists.without("es").get("es").is_none());
+    }
+}
@@ -0,0 +1,177 @@
+use actix_cors::Cors;
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::http::Method;
+
+/// Response header granting Private Network Access (public page -> localhost)
+pub const PRIVATE_NETWORK_HEADER: &str = "access-control-allow-private-network";
+
+const DEFAULT_METHODS: &str = "GET,POST,DELETE";
+const DEFAULT_HEADERS: &str = "content-type,authorization,x-api-key,idempotency-key";
+const MAX_AGE_SECS: usize = 3600;
+
+/// Which browser origins may call the API
+#[derive(Debug, Clone, PartialEq)]
+pub enum CorsMode {
+    /// Any origin, method and header: for local development only
+    Dev,
+    /// Only the configured origins, methods and headers
+    Strict,
+}
+
+#[derive(Debug, Clone)]
+pub struct CorsConfig {
+    pub mode: CorsMode,
+    /// Exact origins, or `https://*.example.com` to allow every subdomain
+    pub origins: Vec<String>,
+    pub methods: Vec<Method>,
+    pub headers: Vec<HeaderName>,
+    /// Answer Private Network Access preflights, so pages on the public
+    /// internet (e.g. the extension's content scripts) can reach a local service
+    pub private_network: bool,
+}
+
+impl CorsConfig {
+    /// Build the policy from `CORS_MODE`, `CORS_ALLOWED_ORIGINS`,
+    /// `CORS_ALLOWED_METHODS`, `CORS_ALLOWED_HEADERS` and `CORS_PRIVATE_NETWORK`
+    ///
+    /// Lists are comma-separated. Private Network Access defaults to on in
+    /// dev mode and off otherwise.
+    pub fn parse(
+        mode: Option<&str>,
+        origins: Option<&str>,
+        methods: Option<&str>,
+        headers: Option<&str>,
+        private_network: Option<bool>,
+    ) -> Result<Self, String> {
+        let mode = match mode.map(str::trim).unwrap_or("strict") {
+            "dev" => CorsMode::Dev,
+            "strict" => CorsMode::Strict,
+            other => return Err(format!("Unknown CORS_MODE '{}', expected dev or strict", other)),
+        };
+
+        let origins: Vec<String> = list(origins.unwrap_or_default())
+            .map(|origin| origin.trim_end_matches('/').to_string())
+            .collect();
+        if origins.iter().any(|origin| origin == "*") {
+            return Err("Wildcard origin '*' is only allowed with CORS_MODE=dev".to_string());
+        }
+
+        let methods = list(methods.unwrap_or(DEFAULT_METHODS))
+            .map(|method| method.to_uppercase().parse::<Method>().map_err(|_| format!("Invalid CORS method '{}'", method)))
+            .collect::<Result<_, _>>()?;
+        let headers = list(headers.unwrap_or(DEFAULT_HEADERS))
+            .map(|header| header.parse::<HeaderName>().map_err(|_| format!("Invalid CORS header '{}'", header)))
+            .collect::<Result<_, _>>()?;
+
+        let private_network = private_network.unwrap_or(mode == CorsMode::Dev);
+
+        Ok(CorsConfig { mode, origins, methods, headers, private_network })
+    }
+
+    /// Whether requests from `origin` are allowed
+    pub fn allows_origin(&self, origin: &str) -> bool {
+        self.mode == CorsMode::Dev || self.origins.iter().any(|allowed| origin_matches(allowed, origin))
+    }
+
+    /// The actix middleware enforcing this policy
+    pub fn build(&self) -> Cors {
+        let cors = match self.mode {
+            CorsMode::Dev => Cors::default()
+                .allow_any_origin()
+                .allow_any_method()
+                .allow_any_header()
+                .expose_any_header(),
+            CorsMode::Strict => {
+                let config = self.clone();
+                Cors::default()
+                    .allowed_origin_fn(move |origin, _| {
+                        origin.to_str().is_ok_and(|origin| config.allows_origin(origin))
+                    })
+                    .allowed_methods(self.methods.clone())
+                    .allowed_headers(self.headers.clone())
+            }
+        };
+        cors.max_age(MAX_AGE_SECS)
+    }
+
+    /// `access-control-allow-private-network: true` for allowed origins, if enabled
+    pub fn private_network_header(&self, origin: Option<&HeaderValue>) -> Option<(HeaderName, HeaderValue)> {
+        let allowed = match origin.and_then(|origin| origin.to_str().ok()) {
+            Some(origin) => self.allows_origin(origin),
+            None => self.mode == CorsMode::Dev,
+        };
+        (self.private_network && allowed)
+            .then(|| (HeaderName::from_static(PRIVATE_NETWORK_HEADER), HeaderValue::from_static("true")))
+    }
+}
+
+fn list(text: &str) -> impl Iterator<Item = &str> {
+    text.split(',').map(str::trim).filter(|item| !item.is_empty())
+}
+
+/// `https://*.example.com` matches `https://www.example.com` but not
+/// `https://example.com` or `https://evil-example.com`
+fn origin_matches(allowed: &str, origin: &str) -> bool {
+    match allowed.split_once("://*.") {
+        Some((scheme, domain)) => origin
+            .strip_prefix(scheme)
+            .and_then(|rest| rest.strip_prefix("://"))
+            .and_then(|host| host.strip_suffix(domain))
+            .is_some_and(|subdomain| subdomain.len() > 1 && subdomain.ends_with('.')),
+        None => allowed == origin,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strict(origins: &str) -> CorsConfig {
+        CorsConfig::parse(None, Some(origins), None, None, None).unwrap()
+    }
+
+    #[test]
+    fn test_defaults_to_strict() {
+        let config = CorsConfig::parse(None, None, None, None, None).unwrap();
+
+        assert_eq!(config.mode, CorsMode::Strict);
+        assert!(!config.allows_origin("https://example.com"));
+        assert!(!config.private_network);
+        assert_eq!(config.methods, vec![Method::GET, Method::POST, Method::DELETE]);
+    }
+
+    #[test]
+    fn test_dev_allows_everything() {
+        let config = CorsConfig::parse(Some("dev"), None, None, None, None).unwrap();
+
+        assert!(config.allows_origin("https://anything.example"));
+        assert!(config.private_network_header(None).is_some());
+    }
+
+    #[test]
+    fn test_origin_patterns() {
+        let config = strict("http://localhost:3000, https://*.netflix.com/");
+
+        assert!(config.allows_origin("http://localhost:3000"));
+        assert!(config.allows_origin("https://www.netflix.com"));
+        assert!(!config.allows_origin("https://netflix.com"));
+        assert!(!config.allows_origin("https://evilnetflix.com"));
+        assert!(!config.allows_origin("http://www.netflix.com"));
+        assert!(!config.allows_origin("http://localhost:3001"));
+    }
+
+    #[test]
+    fn test_private_network_only_for_allowed_origins() {
+        let config = CorsConfig::parse(None, Some("https://*.youtube.com"), None, None, Some(true)).unwrap();
+
+        assert!(config.private_network_header(Some(&HeaderValue::from_static("https://www.youtube.com"))).is_some());
+        assert!(config.private_network_header(Some(&HeaderValue::from_static("https://example.com"))).is_none());
+    }
+
+    #[test]
+    fn test_rejects_bad_settings() {
+        assert!(CorsConfig::parse(Some("open"), None, None, None, None).is_err());
+        assert!(CorsConfig::parse(None, Some("*"), None, None, None).is_err());
+        assert!(CorsConfig::parse(None, None, Some("GE T"), None, None).is_err());
+    }
+}
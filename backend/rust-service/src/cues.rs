@@ -0,0 +1,199 @@
+use crate::models::{Cue, CueWarning, CueWarningKind, OverlapPolicy};
+
+/// Tolerance when comparing cue boundaries (floating point noise)
+const EPSILON: f64 = 1e-6;
+
+/// Clean up a file's cues before alignment
+///
+/// # How it works:
+/// 1. Drop cues with missing or negative durations
+/// 2. Flag cues that start before their predecessor, then sort by start time
+/// 3. Resolve overlaps according to `policy`
+///
+/// Every change (and every overlap left in place) is reported as a warning
+/// against the affected cue, so callers can see exactly what was adjusted.
+pub fn resolve_overlaps(cues: &[Cue], policy: OverlapPolicy) -> (Vec<Cue>, Vec<CueWarning>) {
+    let mut warnings = Vec::new();
+
+    // Step 1: Drop cues that can't be aligned at all
+    let mut valid: Vec<Cue> = Vec::with_capacity(cues.len());
+    for cue in cues {
+        if !cue.start.is_finite() || !cue.end.is_finite() || cue.end <= cue.start {
+            warnings.push(CueWarning {
+                cue_index: cue.index,
+                kind: CueWarningKind::InvalidTiming,
+                message: format!("Cue ends at {} but starts at {}, skipped", cue.end, cue.start),
+            });
+            continue;
+        }
+        valid.push(cue.clone());
+    }
+
+    // Step 2: Detect and fix out-of-order cues
+    for pair in valid.windows(2) {
+        if pair[1].start < pair[0].start {
+            warnings.push(CueWarning {
+                cue_index: pair[1].index,
+                kind: CueWarningKind::OutOfOrder,
+                message: format!("Cue starts before previous cue {}, reordered", pair[0].index),
+            });
+        }
+    }
+    valid.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+    // Step 3: Resolve overlaps
+    let mut resolved: Vec<Cue> = Vec::with_capacity(valid.len());
+    let mut latest_end = f64::NEG_INFINITY;
+
+    for cue in valid {
+        let Some(last) = resolved.last_mut() else {
+            latest_end = cue.end;
+            resolved.push(cue);
+            continue;
+        };
+
+        if cue.start + EPSILON >= latest_end {
+            latest_end = cue.end;
+            resolved.push(cue);
+            continue;
+        }
+
+        match policy {
+            OverlapPolicy::Keep => {
+                warnings.push(CueWarning {
+                    cue_index: cue.index,
+                    kind: CueWarningKind::Overlap,
+                    message: format!("Cue starts at {} before previous cue ends at {}", cue.start, latest_end),
+                });
+                latest_end = latest_end.max(cue.end);
+                resolved.push(cue);
+            }
+            // Clamping needs a positive gap between the two starts; cues starting
+            // together can only be merged
+            OverlapPolicy::Clamp if cue.start > last.start + EPSILON => {
+                warnings.push(CueWarning {
+                    cue_index: last.index,
+                    kind: CueWarningKind::Clamped,
+                    message: format!("End trimmed from {} to {} to avoid overlapping cue {}",
+                        last.end, cue.start, cue.index),
+                });
+                last.end = cue.start;
+                latest_end = cue.end;
+                resolved.push(cue);
+            }
+            OverlapPolicy::Clamp | OverlapPolicy::Merge => {
+                warnings.push(CueWarning {
+                    cue_index: cue.index,
+                    kind: CueWarningKind::Merged,
+                    message: format!("Overlapping cue merged into cue {}", last.index),
+                });
+                last.end = last.end.max(cue.end);
+                last.text = format!("{}\n{}", last.text, cue.text);
+                latest_end = last.end;
+            }
+        }
+    }
+
+    (resolved, warnings)
+}
+
+/// Report every cue that starts before an earlier cue has ended
+///
+/// Unlike `resolve_overlaps` this never changes the cues.
+pub fn find_overlaps(cues: &[Cue]) -> Vec<CueWarning> {
+    let mut warnings = Vec::new();
+    let mut latest: Option<&Cue> = None;
+
+    for cue in cues {
+        if let Some(previous) = latest
+            && cue.start + EPSILON < previous.end
+            && cue.start + EPSILON >= previous.start
+        {
+            warnings.push(CueWarning {
+                cue_index: cue.index,
+                kind: CueWarningKind::Overlap,
+                message: format!("Cue starts at {} before cue {} ends at {}",
+                    cue.start, previous.index, previous.end),
+            });
+        }
+
+        if latest.is_none_or(|previous| cue.end > previous.end) {
+            latest = Some(cue);
+        }
+    }
+
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::cue;
+
+    #[test]
+    fn test_clean_cues_untouched() {
+        let cues = vec![cue(1, 0.0, 1.0, "a"), cue(2, 1.0, 2.0, "b")];
+        let (resolved, warnings) = resolve_overlaps(&cues, OverlapPolicy::Clamp);
+
+        assert_eq!(resolved.len(), 2);
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_clamp_trims_previous_cue() {
+        let cues = vec![cue(1, 0.0, 2.0, "a"), cue(2, 1.5, 3.0, "b")];
+        let (resolved, warnings) = resolve_overlaps(&cues, OverlapPolicy::Clamp);
+
+        assert_eq!(resolved[0].end, 1.5);
+        assert_eq!(resolved[1].start, 1.5);
+        assert_eq!(warnings[0].kind, CueWarningKind::Clamped);
+        assert_eq!(warnings[0].cue_index, 1);
+    }
+
+    #[test]
+    fn test_clamp_merges_identical_starts() {
+        let cues = vec![cue(1, 0.0, 2.0, "a"), cue(2, 0.0, 1.0, "b")];
+        let (resolved, warnings) = resolve_overlaps(&cues, OverlapPolicy::Clamp);
+
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].text, "a\nb");
+        assert_eq!(warnings[0].kind, CueWarningKind::Merged);
+    }
+
+    #[test]
+    fn test_merge_policy() {
+        let cues = vec![cue(1, 0.0, 2.0, "a"), cue(2, 1.0, 3.0, "b"), cue(3, 4.0, 5.0, "c")];
+        let (resolved, _) = resolve_overlaps(&cues, OverlapPolicy::Merge);
+
+        assert_eq!(resolved.len(), 2);
+        assert_eq!(resolved[0].end, 3.0);
+    }
+
+    #[test]
+    fn test_keep_reports_overlap() {
+        let cues = vec![cue(1, 0.0, 2.0, "a"), cue(2, 1.0, 3.0, "b")];
+        let (resolved, warnings) = resolve_overlaps(&cues, OverlapPolicy::Keep);
+
+        assert_eq!(resolved.len(), 2);
+        assert_eq!(warnings[0].kind, CueWarningKind::Overlap);
+    }
+
+    #[test]
+    fn test_out_of_order_and_invalid() {
+        let cues = vec![cue(1, 5.0, 6.0, "a"), cue(2, 0.0, 1.0, "b"), cue(3, 2.0, 2.0, "c")];
+        let (resolved, warnings) = resolve_overlaps(&cues, OverlapPolicy::Clamp);
+
+        assert_eq!(resolved.iter().map(|c| c.index).collect::<Vec<_>>(), vec![2, 1]);
+        assert!(warnings.iter().any(|w| w.kind == CueWarningKind::OutOfOrder && w.cue_index == 2));
+        assert!(warnings.iter().any(|w| w.kind == CueWarningKind::InvalidTiming && w.cue_index == 3));
+    }
+
+    #[test]
+    fn test_find_overlaps_leaves_cues_alone() {
+        let cues = vec![cue(1, 0.0, 3.0, "a"), cue(2, 1.0, 2.0, "b"), cue(3, 2.5, 4.0, "c"), cue(4, 4.0, 5.0, "d")];
+        let warnings = find_overlaps(&cues);
+
+        let overlapping: Vec<usize> = warnings.iter().map(|w| w.cue_index).collect();
+        assert_eq!(overlapping, vec![2, 3]);
+    }
+}
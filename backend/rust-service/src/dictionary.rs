@@ -0,0 +1,589 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::models::{DataFile, DictionaryEntry, FailedDataFile, ResidentData};
+use crate::reload::{self, Reloadable};
+use crate::resources::HeapSize;
+
+/// Dictionaries loaded at startup, shared by every request until reloaded
+static DICTIONARIES: Reloadable<Dictionaries> = Reloadable::new();
+
+/// Open dictionary formats, told apart by file extension
+///
+/// Files are named `<language>.<ext>`, optionally with the source in
+/// between (`ja.jmdict.json`, `en.wiktionary.jsonl`); several files for one
+/// language are merged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    /// CC-CEDICT (`zh.u8`): `傳統 传统 [chuan2 tong3] /tradition/traditional/`
+    Cedict,
+    /// jmdict-simplified JSON (`ja.json`), a `words` array
+    Jmdict,
+    /// Wiktionary extract from kaikki.org (`es.jsonl`), one word per line
+    Wiktionary,
+}
+
+impl Format {
+    fn from_extension(extension: &str) -> Option<Format> {
+        match extension {
+            "u8" | "cedict" => Some(Format::Cedict),
+            "json" => Some(Format::Jmdict),
+            "jsonl" => Some(Format::Wiktionary),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct JmdictFile {
+    words: Vec<JmdictWord>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JmdictWord {
+    #[serde(default)]
+    kanji: Vec<JmdictText>,
+    #[serde(default)]
+    kana: Vec<JmdictText>,
+    #[serde(default)]
+    sense: Vec<JmdictSense>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JmdictText {
+    text: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JmdictSense {
+    #[serde(default)]
+    part_of_speech: Vec<String>,
+    #[serde(default)]
+    gloss: Vec<JmdictText>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WiktionaryWord {
+    word: String,
+    pos: Option<String>,
+    #[serde(default)]
+    senses: Vec<WiktionarySense>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WiktionarySense {
+    /// Broadest first; the last is this sense's own gloss
+    #[serde(default)]
+    glosses: Vec<String>,
+    /// Set on inflections ("ran": form of "run")
+    #[serde(default)]
+    form_of: Vec<WiktionaryForm>,
+    /// Grammatical features, e.g. `["second-person", "singular", "present"]`
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WiktionaryForm {
+    word: String,
+}
+
+/// Headwords and glosses for one language
+#[derive(Debug, Clone, Default)]
+pub struct Dictionary {
+    entries: Vec<DictionaryEntry>,
+    /// Normalized headwords, variants and readings → entries
+    index: HashMap<String, Vec<usize>>,
+    /// Normalized inflected forms → the lemmas they're forms of
+    lemmas: HashMap<String, Vec<String>>,
+    /// Normalized inflected forms → how they inflect each lemma
+    inflections: HashMap<String, Vec<Inflection>>,
+}
+
+/// One reading of an inflected form, from a Wiktionary "form of" sense
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inflection {
+    pub lemma: String,
+    pub part_of_speech: Option<String>,
+    /// Wiktionary's tags for the form, e.g. `["second-person", "singular", "present", "indicative"]`
+    pub tags: Vec<String>,
+}
+
+impl Dictionary {
+    /// Parse CC-CEDICT lines; comments and malformed lines are skipped
+    pub fn parse_cedict(content: &str, source: &str) -> Self {
+        let mut dictionary = Dictionary::default();
+
+        for line in content.lines() {
+            if line.starts_with('#') {
+                continue;
+            }
+            let Some((traditional, rest)) = line.split_once(' ') else {
+                continue;
+            };
+            let Some((simplified, rest)) = rest.split_once(' ') else {
+                continue;
+            };
+            let Some((reading, glosses)) = rest.strip_prefix('[').and_then(|rest| rest.split_once(']')) else {
+                continue;
+            };
+
+            let glosses: Vec<String> = glosses.split('/')
+                .map(str::trim)
+                .filter(|gloss| !gloss.is_empty())
+                .map(String::from)
+                .collect();
+            dictionary.add(DictionaryEntry {
+                headword: simplified.to_string(),
+                reading: Some(reading.to_string()),
+                part_of_speech: None,
+                glosses,
+                source: source.to_string(),
+            }, &[traditional]);
+        }
+
+        dictionary
+    }
+
+    /// Parse a jmdict-simplified file, indexing every kanji and kana spelling
+    pub fn parse_jmdict(content: &str, source: &str) -> Result<Self, String> {
+        let file: JmdictFile = serde_json::from_str(content).map_err(|e| e.to_string())?;
+        let mut dictionary = Dictionary::default();
+
+        for word in file.words {
+            let spellings: Vec<&str> = word.kanji.iter().chain(&word.kana).map(|text| text.text.as_str()).collect();
+            let Some((&headword, variants)) = spellings.split_first() else {
+                continue;
+            };
+
+            let glosses = word.sense.iter()
+                .map(|sense| sense.gloss.iter().map(|gloss| gloss.text.as_str()).collect::<Vec<_>>().join("; "))
+                .filter(|gloss| !gloss.is_empty())
+                .collect();
+            dictionary.add(DictionaryEntry {
+                headword: headword.to_string(),
+                reading: (!word.kanji.is_empty()).then(|| word.kana.first().map(|kana| kana.text.clone())).flatten(),
+                part_of_speech: word.sense.first()
+                    .filter(|sense| !sense.part_of_speech.is_empty())
+                    .map(|sense| sense.part_of_speech.join(", ")),
+                glosses,
+                source: source.to_string(),
+            }, variants);
+        }
+
+        Ok(dictionary)
+    }
+
+    /// Parse a kaikki.org Wiktionary extract, remembering which words are
+    /// inflections of which lemmas; unparseable lines are skipped
+    pub fn parse_wiktionary(content: &str, source: &str) -> Self {
+        let mut dictionary = Dictionary::default();
+        let mut skipped = 0;
+
+        for line in content.lines().filter(|line| !line.trim().is_empty()) {
+            let word: WiktionaryWord = match serde_json::from_str(line) {
+                Ok(word) => word,
+                Err(_) => {
+                    skipped += 1;
+                    continue;
+                }
+            };
+
+            for sense in &word.senses {
+                for lemma in &sense.form_of {
+                    let lemmas = dictionary.lemmas.entry(normalize(&word.word)).or_default();
+                    if !lemmas.contains(&lemma.word) {
+                        lemmas.push(lemma.word.clone());
+                    }
+                    let inflection = Inflection {
+                        lemma: lemma.word.clone(),
+                        part_of_speech: word.pos.clone(),
+                        tags: sense.tags.iter().filter(|tag| *tag != "form-of").cloned().collect(),
+                    };
+                    let inflections = dictionary.inflections.entry(normalize(&word.word)).or_default();
+                    if !inflections.contains(&inflection) {
+                        inflections.push(inflection);
+                    }
+                }
+            }
+
+            let glosses: Vec<String> = word.senses.iter().filter_map(|sense| sense.glosses.last().cloned()).collect();
+            if !glosses.is_empty() {
+                dictionary.add(DictionaryEntry {
+                    headword: word.word,
+                    reading: None,
+                    part_of_speech: word.pos,
+                    glosses,
+                    source: source.to_string(),
+                }, &[]);
+            }
+        }
+
+        if skipped > 0 {
+            log::warn!("Skipped {} unparseable lines of {}", skipped, source);
+        }
+        dictionary
+    }
+
+    fn add(&mut self, entry: DictionaryEntry, variants: &[&str]) {
+        let id = self.entries.len();
+        let mut keys: Vec<String> = std::iter::once(entry.headword.as_str()).chain(variants.iter().copied()).map(normalize).collect();
+        keys.sort();
+        keys.dedup();
+        for key in keys {
+            self.index.entry(key).or_default().push(id);
+        }
+        self.entries.push(entry);
+    }
+
+    /// Add another file's entries for the same language
+    pub fn merge(&mut self, other: Dictionary) {
+        let offset = self.entries.len();
+        self.entries.extend(other.entries);
+        for (key, ids) in other.index {
+            self.index.entry(key).or_default().extend(ids.into_iter().map(|id| id + offset));
+        }
+        for (form, lemmas) in other.lemmas {
+            let known = self.lemmas.entry(form).or_default();
+            for lemma in lemmas {
+                if !known.contains(&lemma) {
+                    known.push(lemma);
+                }
+            }
+        }
+        for (form, inflections) in other.inflections {
+            let known = self.inflections.entry(form).or_default();
+            for inflection in inflections {
+                if !known.contains(&inflection) {
+                    known.push(inflection);
+                }
+            }
+        }
+    }
+
+    /// Lemmas `word` is an inflection of, if the dictionary says
+    pub fn lemmas(&self, word: &str) -> &[String] {
+        self.lemmas.get(&normalize(word)).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// How `word` inflects its lemmas, if the dictionary says
+    pub fn inflections(&self, word: &str) -> &[Inflection] {
+        self.inflections.get(&normalize(word)).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Entries for `word` itself, then for each of its lemmas
+    pub fn lookup(&self, word: &str) -> Vec<&DictionaryEntry> {
+        let mut ids: Vec<usize> = Vec::new();
+        let keys = std::iter::once(normalize(word)).chain(self.lemmas(word).iter().map(|lemma| normalize(lemma)));
+        for key in keys {
+            for &id in self.index.get(&key).into_iter().flatten() {
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
+            }
+        }
+        ids.into_iter().map(|id| &self.entries[id]).collect()
+    }
+
+    /// Headwords
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl HeapSize for Dictionary {
+    fn heap_size(&self) -> usize {
+        self.entries.heap_size() + self.index.heap_size() + self.lemmas.heap_size() + self.inflections.heap_size()
+    }
+}
+
+impl HeapSize for DictionaryEntry {
+    fn heap_size(&self) -> usize {
+        self.headword.heap_size() + self.reading.heap_size() + self.part_of_speech.heap_size()
+            + self.glosses.heap_size() + self.source.heap_size()
+    }
+}
+
+impl HeapSize for Inflection {
+    fn heap_size(&self) -> usize {
+        self.lemma.heap_size() + self.part_of_speech.heap_size() + self.tags.heap_size()
+    }
+}
+
+/// Lowercase and use a plain apostrophe so "Don’t" finds "don't"
+fn normalize(word: &str) -> String {
+    word.trim().to_lowercase().replace('’', "'")
+}
+
+/// All loaded dictionaries, keyed by lowercase language code
+#[derive(Debug, Default)]
+pub struct Dictionaries {
+    /// Shared, so evicting one language doesn't copy the others
+    dictionaries: HashMap<String, Arc<Dictionary>>,
+    /// Files loaded, by language
+    files: Vec<DataFile>,
+    /// Files that couldn't be read or parsed
+    failed: Vec<FailedDataFile>,
+}
+
+impl Dictionaries {
+    /// Load every dictionary file in `dir`
+    ///
+    /// Unknown extensions are ignored and malformed files are logged and
+    /// skipped, like the other data directories.
+    pub fn load_dir(dir: &Path) -> Self {
+        Self::load(dir, |_| true)
+    }
+
+    /// `load_dir`, reading only the files of languages `wanted` accepts
+    /// (by lowercase code)
+    pub fn load(dir: &Path, wanted: impl Fn(&str) -> bool) -> Self {
+        let mut loaded = Dictionaries::default();
+
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::info!("No dictionaries loaded from {}: {}", dir.display(), e);
+                return loaded;
+            }
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Some(format) = path.extension().and_then(|ext| ext.to_str()).and_then(Format::from_extension) else {
+                continue;
+            };
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let language = name.split('.').next().unwrap_or_default().to_lowercase();
+            if !wanted(&language) {
+                continue;
+            }
+
+            let parsed = fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|content| {
+                    let dictionary = match format {
+                        Format::Cedict => Dictionary::parse_cedict(&content, name),
+                        Format::Jmdict => Dictionary::parse_jmdict(&content, name)?,
+                        Format::Wiktionary => Dictionary::parse_wiktionary(&content, name),
+                    };
+                    Ok((DataFile::new(&language, &path, &content, dictionary.len()), dictionary))
+                });
+
+            match parsed {
+                Ok((file, dictionary)) => {
+                    log::info!("Loaded {} dictionary entries for '{}' from {}", dictionary.len(), language, name);
+                    loaded.files.push(file);
+                    Arc::make_mut(loaded.dictionaries.entry(language).or_default()).merge(dictionary);
+                }
+                Err(e) => {
+                    log::warn!("Skipping dictionary {}: {}", path.display(), e);
+                    loaded.failed.push(FailedDataFile::new(&language, &path, e));
+                }
+            }
+        }
+
+        loaded.files.sort_by(|a, b| a.language.cmp(&b.language).then_with(|| a.file.cmp(&b.file)));
+        loaded
+    }
+
+    /// Number of languages with a dictionary
+    pub fn len(&self) -> usize {
+        self.dictionaries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dictionaries.is_empty()
+    }
+
+    /// The files the dictionaries came from
+    pub fn files(&self) -> &[DataFile] {
+        &self.files
+    }
+
+    /// The files that failed to load
+    pub fn failed(&self) -> &[FailedDataFile] {
+        &self.failed
+    }
+
+    /// These dictionaries with every language that had a file fail to load
+    /// back on its dictionary from `previous`
+    pub fn keeping(mut self, previous: &Self) -> Self {
+        for language in reload::failed_languages(&self.failed) {
+            reload::restore(&mut self.dictionaries, &previous.dictionaries, &language);
+            reload::restore_files(&mut self.files, &previous.files, &language);
+        }
+        self
+    }
+
+    /// Dictionary for a language, if one was loaded
+    pub fn get(&self, language: &str) -> Option<&Dictionary> {
+        self.dictionaries.get(&language.to_lowercase()).map(Arc::as_ref)
+    }
+
+    /// Each language's dictionary with its size, for `/admin/languages`
+    pub fn resident(&self) -> Vec<(String, ResidentData)> {
+        self.dictionaries.iter()
+            .map(|(language, dictionary)| (language.clone(), ResidentData {
+                name: "dictionary".to_string(),
+                entries: dictionary.len(),
+                approx_bytes: dictionary.heap_size(),
+            }))
+            .collect()
+    }
+
+    /// These dictionaries with `language`'s taken from `loaded`
+    pub fn with(&self, language: &str, loaded: &Self) -> Self {
+        let mut dictionaries = self.without(language);
+        reload::restore(&mut dictionaries.dictionaries, &loaded.dictionaries, language);
+        reload::restore_files(&mut dictionaries.files, &loaded.files, language);
+        dictionaries
+    }
+
+    /// These dictionaries without `language`'s
+    pub fn without(&self, language: &str) -> Self {
+        Dictionaries {
+            dictionaries: self.dictionaries.iter()
+                .filter(|(code, _)| *code != language)
+                .map(|(code, dictionary)| (code.clone(), dictionary.clone()))
+                .collect(),
+            files: self.files.iter().filter(|file| file.language != language).cloned().collect(),
+            failed: self.failed.clone(),
+        }
+    }
+}
+
+/// Load dictionaries at startup
+pub fn init(dir: &Path) {
+    if !DICTIONARIES.init(dir, Dictionaries::load_dir) {
+        log::warn!("Dictionaries already initialised");
+    }
+}
+
+/// Load the dictionaries of the languages `wanted` accepts again from the
+/// `init` directory (see `reload::reload`)
+pub fn reload(wanted: impl Fn(&str) -> bool) -> Option<Arc<Dictionaries>> {
+    DICTIONARIES.reload(|dir, previous| Dictionaries::load(dir, wanted).keeping(previous))
+}
+
+/// Load `language`'s dictionary back after an eviction (see `resources::restore`)
+pub fn restore(language: &str) -> bool {
+    DICTIONARIES.reload(|dir, current| current.with(language, &Dictionaries::load(dir, |code| code == language))).is_some()
+}
+
+/// Drop `language`'s dictionary until it's next used (see `resources::evict`)
+pub fn evict(language: &str) -> bool {
+    DICTIONARIES.get().dictionaries.contains_key(language) && DICTIONARIES.update(|dictionaries| dictionaries.without(language)).is_some()
+}
+
+/// Whether `init` has run
+pub fn is_loaded() -> bool {
+    DICTIONARIES.is_loaded()
+}
+
+/// Dictionaries loaded by `init` or the latest reload, or none if it was never called
+pub fn dictionaries() -> Arc<Dictionaries> {
+    DICTIONARIES.get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CEDICT: &str = "# CC-CEDICT\n中國 中国 [Zhong1 guo2] /China/Middle Kingdom/\n學習 学习 [xue2 xi2] /to learn/to study/\nnot an entry\n";
+
+    const JMDICT: &str = r#"{"words": [
+        {"kanji": [{"text": "食べる"}], "kana": [{"text": "たべる"}],
+         "sense": [{"partOfSpeech": ["v1", "vt"], "gloss": [{"text": "to eat"}]}, {"gloss": [{"text": "to live on"}]}]},
+        {"kanji": [], "kana": [{"text": "ありがとう"}], "sense": [{"gloss": [{"text": "thank you"}]}]}
+    ]}"#;
+
+    const WIKTIONARY: &str = concat!(
+        r#"{"word": "run", "pos": "verb", "senses": [{"glosses": ["To move swiftly on foot."]}]}"#, "\n",
+        r#"{"word": "run", "pos": "noun", "senses": [{"glosses": ["Act of running."]}]}"#, "\n",
+        r#"{"word": "ran", "pos": "verb", "senses": [{"glosses": ["simple past of run"], "form_of": [{"word": "run"}], "tags": ["form-of", "past"]}]}"#, "\n",
+        "not json\n",
+    );
+
+    #[test]
+    fn test_cedict_indexes_both_scripts() {
+        let dictionary = Dictionary::parse_cedict(CEDICT, "zh.u8");
+        assert_eq!(dictionary.len(), 2);
+
+        let entries = dictionary.lookup("中國");
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].headword, "中国");
+        assert_eq!(entries[0].reading.as_deref(), Some("Zhong1 guo2"));
+        assert_eq!(entries[0].glosses, vec!["China", "Middle Kingdom"]);
+        assert_eq!(dictionary.lookup("中国").len(), 1);
+    }
+
+    #[test]
+    fn test_jmdict_by_kanji_or_kana() {
+        let dictionary = Dictionary::parse_jmdict(JMDICT, "ja.json").unwrap();
+
+        let eat = dictionary.lookup("たべる");
+        assert_eq!(eat[0].headword, "食べる");
+        assert_eq!(eat[0].reading.as_deref(), Some("たべる"));
+        assert_eq!(eat[0].part_of_speech.as_deref(), Some("v1, vt"));
+        assert_eq!(eat[0].glosses, vec!["to eat", "to live on"]);
+
+        let thanks = dictionary.lookup("ありがとう");
+        assert_eq!(thanks[0].reading, None);
+        assert!(Dictionary::parse_jmdict("[]", "ja.json").is_err());
+    }
+
+    #[test]
+    fn test_wiktionary_inflections_find_their_lemma() {
+        let dictionary = Dictionary::parse_wiktionary(WIKTIONARY, "en.jsonl");
+        assert_eq!(dictionary.lemmas("Ran"), ["run"]);
+        assert_eq!(dictionary.inflections("ran"), [Inflection {
+            lemma: "run".to_string(),
+            part_of_speech: Some("verb".to_string()),
+            tags: vec!["past".to_string()],
+        }]);
+
+        let headwords: Vec<(&str, Option<&str>)> = dictionary.lookup("Ran").iter()
+            .map(|entry| (entry.headword.as_str(), entry.part_of_speech.as_deref()))
+            .collect();
+        assert_eq!(headwords, vec![("ran", Some("verb")), ("run", Some("verb")), ("run", Some("noun"))]);
+        assert!(dictionary.lookup("walk").is_empty());
+    }
+
+    #[test]
+    fn test_load_dir_merges_files_per_language() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("zh.u8"), CEDICT).unwrap();
+        fs::write(dir.path().join("en.wiktionary.jsonl"), WIKTIONARY).unwrap();
+        fs::write(dir.path().join("en.extra.jsonl"), r#"{"word": "walk", "senses": [{"glosses": ["To move on foot."]}]}"#).unwrap();
+        fs::write(dir.path().join("ja.json"), "{ broken").unwrap();
+        fs::write(dir.path().join("README.md"), "ignored").unwrap();
+
+        let dictionaries = Dictionaries::load_dir(dir.path());
+        assert_eq!(dictionaries.len(), 2);
+        assert_eq!(dictionaries.files().len(), 3);
+        assert_eq!(dictionaries.get("EN").unwrap().lookup("walk").len(), 1);
+        assert_eq!(dictionaries.get("en").unwrap().lookup("ran").len(), 3);
+        assert!(dictionaries.get("ja").is_none());
+
+        let resident = dictionaries.resident();
+        assert_eq!(resident.len(), 2);
+        assert!(resident.iter().all(|(_, data)| data.approx_bytes > 0));
+
+        // Evicting one language leaves the other's dictionary as it was
+        let trimmed = dictionaries.without("en");
+        assert!(trimmed.get("en").is_none());
+        assert_eq!(trimmed.files().len(), 1);
+        assert!(std::ptr::eq(trimmed.get("zh").unwrap(), dictionaries.get("zh").unwrap()));
+    }
+}
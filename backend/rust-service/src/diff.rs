@@ -0,0 +1,173 @@
+use similar::{capture_diff_slices, Algorithm, DiffOp};
+
+use crate::aligner::align_weighted;
+use crate::models::{AlignmentRequest, Cue, DiffKind, DiffSegment, TranscriptDiff, TranscriptWord};
+
+/// Cues with fewer matching words than this are flagged for review
+const FLAG_BELOW_MATCH_RATIO: f64 = 0.5;
+
+/// A subtitle word with its estimated timing and cue
+struct SubtitleWord {
+    word: String,
+    start: f64,
+    end: f64,
+    cue_index: usize,
+}
+
+/// Compare what was said (ASR transcript) with what the subtitles show
+///
+/// # How it works:
+/// 1. Give every subtitle word a timestamp with the weighted aligner
+/// 2. Normalize both sides (lowercase, no punctuation) and diff the word
+///    sequences
+/// 3. Report runs of matches, substitutions, dialogue missing from the
+///    subtitles and captions with no matching speech, each with times
+///    (transcript times where there was speech, subtitle times otherwise)
+/// 4. Flag cues where less than half the words match the transcript
+pub fn diff_transcript(transcript: &[TranscriptWord], cues: &[Cue], language: &str) -> TranscriptDiff {
+    // Step 1: Timed subtitle words
+    let mut subtitle: Vec<SubtitleWord> = Vec::new();
+    for cue in cues {
+        let request = AlignmentRequest {
+            text: cue.text.clone(),
+            language: language.to_string(),
+            subtitle_start: cue.start,
+            subtitle_end: cue.end,
+            ..Default::default()
+        };
+        match align_weighted(&request) {
+            Ok(response) => subtitle.extend(response.timings.into_iter()
+                .filter(|t| !normalize(&t.word).is_empty())
+                .map(|t| SubtitleWord { word: t.word, start: t.start, end: t.end, cue_index: cue.index })),
+            Err(e) => log::debug!("Skipping cue {} in transcript diff: {}", cue.index, e),
+        }
+    }
+    let transcript: Vec<&TranscriptWord> = transcript.iter()
+        .filter(|t| !normalize(&t.word).is_empty())
+        .collect();
+
+    // Step 2: Diff the normalized sequences
+    let old: Vec<String> = transcript.iter().map(|t| normalize(&t.word)).collect();
+    let new: Vec<String> = subtitle.iter().map(|s| normalize(&s.word)).collect();
+    let ops = capture_diff_slices(Algorithm::Myers, &old, &new);
+
+    // Step 3: One segment per diff operation
+    let mut diff = TranscriptDiff::default();
+    let mut cue_matches: Vec<(usize, usize, usize)> = Vec::new(); // (cue, matched, total)
+
+    for op in ops {
+        let (kind, old_range, new_range) = match op {
+            DiffOp::Equal { old_index, new_index, len } => (DiffKind::Match, old_index..old_index + len, new_index..new_index + len),
+            DiffOp::Delete { old_index, old_len, new_index } => (DiffKind::Missing, old_index..old_index + old_len, new_index..new_index),
+            DiffOp::Insert { old_index, new_index, new_len } => (DiffKind::Extra, old_index..old_index, new_index..new_index + new_len),
+            DiffOp::Replace { old_index, old_len, new_index, new_len } => (DiffKind::Substitution, old_index..old_index + old_len, new_index..new_index + new_len),
+        };
+
+        let spoken = &transcript[old_range];
+        let shown = &subtitle[new_range];
+
+        let (start, end) = match (spoken.first(), spoken.last()) {
+            (Some(first), Some(last)) => (first.start, last.end),
+            _ => (shown.first().map_or(0.0, |s| s.start), shown.last().map_or(0.0, |s| s.end)),
+        };
+
+        let mut cue_indices: Vec<usize> = shown.iter().map(|s| s.cue_index).collect();
+        cue_indices.dedup();
+
+        for word in shown {
+            match cue_matches.last_mut() {
+                Some((cue, matched, total)) if *cue == word.cue_index => {
+                    *total += 1;
+                    *matched += usize::from(kind == DiffKind::Match);
+                }
+                _ => cue_matches.push((word.cue_index, usize::from(kind == DiffKind::Match), 1)),
+            }
+        }
+
+        match kind {
+            DiffKind::Match => diff.n_matched += spoken.len(),
+            DiffKind::Substitution => diff.n_substituted += spoken.len().max(shown.len()),
+            DiffKind::Missing => diff.n_missing += spoken.len(),
+            DiffKind::Extra => diff.n_extra += shown.len(),
+        }
+
+        diff.segments.push(DiffSegment {
+            kind,
+            start,
+            end,
+            transcript_words: spoken.iter().map(|t| t.word.clone()).collect(),
+            subtitle_words: shown.iter().map(|s| s.word.clone()).collect(),
+            cue_indices,
+        });
+    }
+
+    // Step 4: Cues that mostly don't match what was said
+    diff.flagged_cues = cue_matches.into_iter()
+        .filter(|(_, matched, total)| (*matched as f64) < *total as f64 * FLAG_BELOW_MATCH_RATIO)
+        .map(|(cue, _, _)| cue)
+        .collect();
+    diff.flagged_cues.dedup();
+
+    diff
+}
+
+/// Compare words, not spellings: "Don't," matches "don't"
+fn normalize(word: &str) -> String {
+    word.chars()
+        .filter(|c| c.is_alphanumeric() || *c == '\'' || *c == '’')
+        .flat_map(char::to_lowercase)
+        .map(|c| if c == '’' { '\'' } else { c })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::cue;
+
+    fn transcript(words: &[(&str, f64)]) -> Vec<TranscriptWord> {
+        words.iter()
+            .map(|(word, start)| TranscriptWord { word: word.to_string(), start: *start, end: start + 0.3 })
+            .collect()
+    }
+
+    #[test]
+    fn test_identical_text_matches() {
+        let spoken = transcript(&[("hello", 0.0), ("world", 0.5)]);
+        let diff = diff_transcript(&spoken, &[cue(1, 0.0, 1.0, "Hello, world!")], "en");
+
+        assert_eq!(diff.segments.len(), 1);
+        assert_eq!(diff.segments[0].kind, DiffKind::Match);
+        assert_eq!(diff.n_matched, 2);
+        assert!(diff.flagged_cues.is_empty());
+    }
+
+    #[test]
+    fn test_missing_substituted_and_extra() {
+        let spoken = transcript(&[("i", 0.0), ("really", 0.3), ("like", 0.6), ("cats", 0.9), ("okay", 3.0)]);
+        let cues = vec![cue(1, 0.0, 1.2, "I like dogs"), cue(2, 5.0, 6.0, "[music]")];
+        let diff = diff_transcript(&spoken, &cues, "en");
+
+        let kinds: Vec<DiffKind> = diff.segments.iter().map(|s| s.kind).collect();
+        assert_eq!(kinds, vec![DiffKind::Match, DiffKind::Missing, DiffKind::Match, DiffKind::Substitution]);
+
+        let missing = &diff.segments[1];
+        assert_eq!(missing.transcript_words, vec!["really"]);
+        assert_eq!(missing.start, 0.3);
+
+        let substitution = &diff.segments[3];
+        assert_eq!(substitution.transcript_words, vec!["cats", "okay"]);
+        assert_eq!(substitution.subtitle_words, vec!["dogs", "music"]);
+        assert_eq!(substitution.cue_indices, vec![1, 2]);
+        assert_eq!(diff.flagged_cues, vec![2]);
+    }
+
+    #[test]
+    fn test_caption_without_speech() {
+        let diff = diff_transcript(&[], &[cue(1, 2.0, 3.0, "Hello")], "en");
+
+        assert_eq!(diff.segments[0].kind, DiffKind::Extra);
+        assert_eq!(diff.segments[0].start, 2.0);
+        assert_eq!(diff.n_extra, 1);
+    }
+}
@@ -0,0 +1,192 @@
+use crate::dictionary::{self, Dictionary};
+use crate::frequency::{self, FrequencyList, FrequencyLists};
+use crate::models::{CefrLevel, Cue, CueDifficulty, DifficultyScore, FrequencyBand, FrequencyProfile};
+use crate::tokenizer::{self, Token};
+
+/// Share of words a learner must know to follow a text comfortably
+const COVERAGE: f64 = 0.95;
+
+/// Levels from easiest to hardest
+const LEVELS: [CefrLevel; 6] = [CefrLevel::A1, CefrLevel::A2, CefrLevel::B1, CefrLevel::B2, CefrLevel::C1, CefrLevel::C2];
+
+/// Largest `vocabulary_needed` for each level from A1 to C1; anything
+/// beyond is C2
+///
+/// Rough vocabulary sizes learners reach at each level, in word families.
+const VOCABULARY_LIMITS: [usize; 5] = [1000, 2000, 3000, 5000, 8000];
+
+/// Longest mean sentence (in words) each level from A1 to C1 handles;
+/// longer ones move the estimate up one level
+const SENTENCE_LIMITS: [f64; 5] = [8.0, 12.0, 16.0, 22.0, 30.0];
+
+/// Punctuation that ends a sentence
+const SENTENCE_ENDS: &[char] = &['.', '!', '?', '…', '。', '！', '？'];
+
+/// Score a text for learners of `language`
+///
+/// # How it works:
+/// 1. Rank each word in the language's frequency list, falling back to
+///    its lemmas' ranks from the dictionary ("ran" ranks as "run"), and
+///    split sentences at periods other than the language's abbreviations
+/// 2. Build the lexical frequency profile and find the vocabulary size
+///    that covers 95% of the words
+/// 3. Map that size to a CEFR level, one level up if sentences run long
+///
+/// For a whole file, pass the cue texts joined by newlines: a sentence
+/// carried over into the next cue counts once.
+pub fn score(text: &str, language: &str) -> DifficultyScore {
+    score_with(text, language, &frequency::lists())
+}
+
+fn score_with(text: &str, language: &str, lists: &FrequencyLists) -> DifficultyScore {
+    let list = lists.get(language);
+    let abbreviations = lists.abbreviations(language);
+    let dictionaries = dictionary::dictionaries();
+    let dictionary = dictionaries.get(language);
+
+    // Step 1: Ranks and sentence lengths
+    let mut ranks = Vec::new();
+    let mut sentences: Vec<usize> = Vec::new();
+    let mut in_sentence = 0;
+    let mut previous: Option<Token> = None;
+
+    for token in tokenizer::tokens(text, language) {
+        let between = &text[previous.map_or(0, |previous| previous.end)..token.start];
+        // "Mr. Smith": the period belongs to the abbreviation
+        let abbreviated = between.trim() == "." && previous.is_some_and(|previous| abbreviations.contains(previous.text));
+        if between.contains(SENTENCE_ENDS) && !abbreviated && in_sentence > 0 {
+            sentences.push(in_sentence);
+            in_sentence = 0;
+        }
+        previous = Some(token);
+        if !token.text.chars().any(char::is_alphabetic) {
+            continue;
+        }
+        in_sentence += 1;
+
+        let rank = rank(token.text, list, dictionary);
+        let mid_sentence = in_sentence > 1;
+        if rank.is_none() && mid_sentence && token.text.chars().next().is_some_and(char::is_uppercase) {
+            continue;
+        }
+        ranks.push(rank);
+    }
+    if in_sentence > 0 {
+        sentences.push(in_sentence);
+    }
+
+    // Step 2: Profile and coverage
+    let profile = profile(&ranks);
+    let vocabulary_needed = vocabulary_needed(&mut ranks);
+    let mean_sentence_length = match sentences.len() {
+        0 => 0.0,
+        n => sentences.iter().sum::<usize>() as f64 / n as f64,
+    };
+
+    // Step 3: Level
+    let cefr = (!ranks.is_empty()).then(|| cefr(vocabulary_needed, mean_sentence_length));
+
+    DifficultyScore {
+        n_words: ranks.len(),
+        n_sentences: sentences.len(),
+        mean_sentence_length,
+        profile,
+        vocabulary_needed,
+        cefr,
+    }
+}
+
+/// Score a whole file, and each cue alone
+pub fn score_cues(cues: &[Cue], language: &str) -> (DifficultyScore, Vec<CueDifficulty>) {
+    let text = cues.iter().map(|cue| cue.text.as_str()).collect::<Vec<_>>().join("\n");
+    let per_cue = cues.iter()
+        .map(|cue| CueDifficulty { index: cue.index, score: score(&cue.text, language) })
+        .collect();
+    (score(&text, language), per_cue)
+}
+
+fn rank(word: &str, list: &FrequencyList, dictionary: Option<&Dictionary>) -> Option<usize> {
+    list.rank(word).or_else(|| {
+        dictionary?.lemmas(word).iter().filter_map(|lemma| list.rank(lemma)).min()
+    })
+}
+
+fn profile(ranks: &[Option<usize>]) -> FrequencyProfile {
+    let mut profile = FrequencyProfile::default();
+    if ranks.is_empty() {
+        return profile;
+    }
+
+    let share = 1.0 / ranks.len() as f64;
+    for rank in ranks {
+        let band = match FrequencyBand::of(*rank) {
+            FrequencyBand::K1 => &mut profile.k1,
+            FrequencyBand::K2 => &mut profile.k2,
+            FrequencyBand::K3To5 => &mut profile.k3_5,
+            FrequencyBand::Rare => &mut profile.rare,
+            FrequencyBand::Unlisted => &mut profile.unlisted,
+        };
+        *band += share;
+    }
+    profile
+}
+
+/// The rank reached by the `COVERAGE` share of words, most frequent first
+fn vocabulary_needed(ranks: &mut [Option<usize>]) -> Option<usize> {
+    if ranks.is_empty() {
+        return Some(0);
+    }
+    // Unlisted words sort last
+    ranks.sort_by_key(|rank| rank.unwrap_or(usize::MAX));
+    let covered = (ranks.len() as f64 * COVERAGE).ceil() as usize;
+    ranks[covered.max(1) - 1]
+}
+
+fn cefr(vocabulary_needed: Option<usize>, mean_sentence_length: f64) -> CefrLevel {
+    let level = VOCABULARY_LIMITS.iter()
+        .position(|&limit| vocabulary_needed.is_some_and(|needed| needed <= limit))
+        .unwrap_or(LEVELS.len() - 1);
+    let long_sentences = SENTENCE_LIMITS.get(level).is_some_and(|&limit| mean_sentence_length > limit);
+    LEVELS[if long_sentences { level + 1 } else { level }]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vocabulary_needed() {
+        let mut ranks: Vec<Option<usize>> = (1..=19).map(|i| Some(i * 100)).chain([None]).collect();
+        assert_eq!(vocabulary_needed(&mut ranks), Some(1900));
+
+        ranks.push(None);
+        assert_eq!(vocabulary_needed(&mut ranks), None);
+        assert_eq!(vocabulary_needed(&mut []), Some(0));
+    }
+
+    #[test]
+    fn test_cefr() {
+        assert_eq!(cefr(Some(800), 6.0), CefrLevel::A1);
+        assert_eq!(cefr(Some(800), 9.0), CefrLevel::A2);
+        assert_eq!(cefr(Some(4500), 10.0), CefrLevel::B2);
+        assert_eq!(cefr(None, 5.0), CefrLevel::C2);
+    }
+
+    #[test]
+    fn test_score_without_list() {
+        let score = score("Hello there, Alice. How are you?", "xx");
+        assert_eq!(score.n_sentences, 2);
+        // "Alice" is taken for a name
+        assert_eq!(score.n_words, 5);
+        assert_eq!(score.profile.unlisted, 1.0);
+        assert_eq!(score.vocabulary_needed, None);
+        assert_eq!(score.cefr, Some(CefrLevel::C2));
+    }
+
+    #[test]
+    fn test_abbreviations_dont_end_sentences() {
+        let lists = FrequencyLists::load_dir(&std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("data/frequency"));
+        let score = score_with("Mr. Smith is here. Go!", "en", &lists);
+        assert_eq!(score.n_sentences, 2);
+    }
+}
@@ -0,0 +1,331 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::egress;
+use crate::error::{ApiError, ErrorCode};
+use crate::spool::Spool;
+use crate::upstream::{self, Breaker, Failure, RetryPolicy, DEFAULT_BREAKER_COOLDOWN_SECS, DEFAULT_BREAKER_THRESHOLD, DEFAULT_RETRIES};
+
+pub const DEFAULT_USER_AGENT: &str = concat!("dubdub/", env!("CARGO_PKG_VERSION"));
+
+/// Downloads from one host at once, unless configured
+pub const DEFAULT_DOWNLOADS_PER_HOST: usize = 4;
+
+/// Seconds one try at a download may take, body included, unless configured
+pub const DEFAULT_DOWNLOAD_TIMEOUT_SECS: u64 = 600;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a download may go without receiving anything
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long `probe` waits for a host to start answering
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long an idle connection is kept for the next download from its host
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Hosts tracked before those with nothing in flight are forgotten
+const MAX_TRACKED_HOSTS: usize = 1024;
+
+static DOWNLOADER: OnceLock<Downloader> = OnceLock::new();
+
+/// `AUDIO_FETCH_*` settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct DownloadConfig {
+    /// Every download goes through this proxy; without it the usual
+    /// `HTTPS_PROXY`/`NO_PROXY` variables apply
+    pub proxy: Option<String>,
+    pub user_agent: String,
+    /// Downloads from one host at once; 0 for no limit
+    pub per_host: usize,
+    /// Tries after a download fails on the network or with a 5xx, 408 or 429
+    pub retries: u32,
+    /// Failed downloads in a row from one host before it's left alone for
+    /// `breaker_cooldown`; 0 to keep trying
+    pub breaker_threshold: u32,
+    pub breaker_cooldown: Duration,
+    /// How long one try may take, so a host trickling bytes can't hold a
+    /// request (and its slot) indefinitely
+    pub timeout: Duration,
+    /// Where downloads and their redirects may lead (`EGRESS_ALLOWED_HOSTS`)
+    pub egress: egress::Policy,
+}
+
+impl Default for DownloadConfig {
+    fn default() -> Self {
+        DownloadConfig {
+            proxy: None,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            per_host: DEFAULT_DOWNLOADS_PER_HOST,
+            retries: DEFAULT_RETRIES,
+            breaker_threshold: DEFAULT_BREAKER_THRESHOLD,
+            breaker_cooldown: Duration::from_secs(DEFAULT_BREAKER_COOLDOWN_SECS),
+            timeout: Duration::from_secs(DEFAULT_DOWNLOAD_TIMEOUT_SECS),
+            egress: egress::Policy::default(),
+        }
+    }
+}
+
+/// One HTTP client for every `audio_url` download
+///
+/// Sharing it keeps connections to a CDN open between requests instead of
+/// a TLS handshake per episode, and the per-host limit keeps a batch of
+/// uploads from hammering (or getting throttled by) a single origin. A
+/// host that keeps failing gets a circuit breaker, so requests for its
+/// audio fail fast instead of each waiting out its retries. Only public
+/// addresses are downloaded from, redirects included (see `egress::Policy`).
+pub struct Downloader {
+    client: reqwest::Client,
+    egress: egress::Policy,
+    per_host: usize,
+    hosts: Mutex<HashMap<String, Arc<Semaphore>>>,
+    policy: RetryPolicy,
+    breaker_threshold: u32,
+    breaker_cooldown: Duration,
+    breakers: Mutex<HashMap<String, Arc<Breaker>>>,
+}
+
+impl Downloader {
+    pub fn new(config: &DownloadConfig) -> Result<Self, String> {
+        let mut builder = config.egress.apply(reqwest::Client::builder())
+            .user_agent(&config.user_agent)
+            .connect_timeout(CONNECT_TIMEOUT)
+            .read_timeout(READ_TIMEOUT)
+            .timeout(config.timeout)
+            .pool_idle_timeout(POOL_IDLE_TIMEOUT);
+        if config.per_host > 0 {
+            builder = builder.pool_max_idle_per_host(config.per_host);
+        }
+        if let Some(proxy) = &config.proxy {
+            let proxy = reqwest::Proxy::all(proxy).map_err(|e| format!("Invalid audio fetch proxy: {}", e))?;
+            builder = builder.proxy(proxy);
+        }
+
+        let client = builder.build().map_err(|e| format!("Can't build the download client: {}", e))?;
+        Ok(Downloader {
+            client,
+            egress: config.egress.clone(),
+            per_host: config.per_host,
+            hosts: Mutex::new(HashMap::new()),
+            policy: RetryPolicy { retries: config.retries, ..Default::default() },
+            breaker_threshold: config.breaker_threshold,
+            breaker_cooldown: config.breaker_cooldown,
+            breakers: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Stream `url` into `spool`, waiting for a free slot on its host first
+    ///
+    /// Transient failures start the download over, after a backoff, for
+    /// which the slot is given up.
+    pub async fn get(&self, url: &str, spool: &mut Spool) -> Result<(), ApiError> {
+        let parsed = self.parse(url)?;
+        let host = parsed.host_str().unwrap_or_default().to_string();
+        let breaker = self.breaker(&host);
+        breaker.check()?;
+
+        // A slot per attempt, so a host's other downloads go ahead during
+        // the backoff between them
+        let host = host.as_str();
+        *spool = upstream::call(&breaker, self.policy, || {
+            let (url, spool) = (parsed.clone(), spool.fresh());
+            async move {
+                let _slot = self.slot(host).await;
+                self.try_get(url, spool).await
+            }
+        }).await?;
+        Ok(())
+    }
+
+    async fn try_get(&self, url: reqwest::Url, mut spool: Spool) -> Result<Spool, Failure> {
+        let mut response = self.client.get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| Failure::from_reqwest(e, "Failed to fetch audio"))?;
+
+        while let Some(chunk) = response.chunk()
+            .await
+            .map_err(|e| Failure::from_reqwest(e, "Failed to read audio"))? {
+            spool.write(&chunk).await?;
+        }
+        Ok(spool)
+    }
+
+    /// Check that `url` answers, fetching only its first byte
+    ///
+    /// A ranged GET rather than HEAD, because presigned URLs are signed for
+    /// GET only. No retries: a dry run should report what it finds. Why the
+    /// URL didn't answer is only logged, so a probe can't be used to read
+    /// what other hosts say.
+    pub async fn probe(&self, url: &str) -> Result<(), ApiError> {
+        let parsed = self.parse(url)?;
+        let host = parsed.host_str().unwrap_or_default().to_string();
+        self.breaker(&host).check()?;
+        let _slot = self.slot(&host).await;
+
+        self.client.get(parsed)
+            .header(reqwest::header::RANGE, "bytes=0-0")
+            .timeout(PROBE_TIMEOUT)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| {
+                log::info!("Audio probe to {} failed: {}", host, e);
+                ApiError::new(ErrorCode::Upstream, "Audio URL is unreachable")
+            })?;
+        Ok(())
+    }
+
+    /// `url`, if it's one the egress policy lets downloads go to
+    fn parse(&self, url: &str) -> Result<reqwest::Url, ApiError> {
+        let parsed = reqwest::Url::parse(url).map_err(|e| ApiError::invalid_input(format!("Invalid audio URL: {}", e)))?;
+        self.egress.check_url(&parsed)
+            .map_err(|e| ApiError::invalid_input(format!("Invalid audio URL: {}", e)).with_field("audio_url"))?;
+        Ok(parsed)
+    }
+
+    /// The circuit breaker for `host`
+    fn breaker(&self, host: &str) -> Arc<Breaker> {
+        let mut breakers = self.breakers.lock().unwrap();
+        if breakers.len() >= MAX_TRACKED_HOSTS && !breakers.contains_key(host) {
+            breakers.retain(|_, breaker| breaker.is_failing());
+        }
+        breakers.entry(host.to_string())
+            .or_insert_with(|| Arc::new(Breaker::new(format!("Audio host {}", host), self.breaker_threshold, self.breaker_cooldown)))
+            .clone()
+    }
+
+    /// Wait until fewer than `per_host` downloads from `host` are in flight;
+    /// the slot is freed when dropped
+    async fn slot(&self, host: &str) -> Option<OwnedSemaphorePermit> {
+        if self.per_host == 0 {
+            return None;
+        }
+
+        let semaphore = {
+            let mut hosts = self.hosts.lock().unwrap();
+            if hosts.len() >= MAX_TRACKED_HOSTS && !hosts.contains_key(host) {
+                hosts.retain(|_, semaphore| semaphore.available_permits() < self.per_host);
+            }
+            hosts.entry(host.to_string()).or_insert_with(|| Arc::new(Semaphore::new(self.per_host))).clone()
+        };
+        semaphore.acquire_owned().await.ok()
+    }
+}
+
+/// Set up the download client (`AUDIO_FETCH_*`)
+pub fn init(config: DownloadConfig) -> Result<(), String> {
+    let downloader = Downloader::new(&config)?;
+    match config.per_host {
+        0 => log::info!("Audio downloads as '{}', no per-host limit", config.user_agent),
+        limit => log::info!("Audio downloads as '{}', {} at once per host", config.user_agent, limit),
+    }
+    if config.proxy.is_some() {
+        log::info!("Audio downloads go through AUDIO_FETCH_PROXY");
+    }
+    if config.breaker_threshold > 0 {
+        log::info!("Audio downloads retry {} times; a host failing {} in a row is left alone for {}s",
+            config.retries, config.breaker_threshold, config.breaker_cooldown.as_secs());
+    }
+
+    if DOWNLOADER.set(downloader).is_err() {
+        log::warn!("Download client already initialised");
+    }
+    Ok(())
+}
+
+fn downloader() -> &'static Downloader {
+    DOWNLOADER.get_or_init(|| Downloader::new(&DownloadConfig::default()).expect("Default download client"))
+}
+
+/// Stream `url` into `spool` with the shared client
+pub async fn get(url: &str, spool: &mut Spool) -> Result<(), ApiError> {
+    downloader().get(url, spool).await
+}
+
+/// Check that `url` answers with the shared client
+pub async fn probe(url: &str) -> Result<(), ApiError> {
+    downloader().probe(url).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::FutureExt;
+
+    #[tokio::test]
+    async fn test_per_host_limit() {
+        let downloader = Downloader::new(&DownloadConfig { per_host: 1, ..Default::default() }).unwrap();
+
+        let held = downloader.slot("cdn.example.com").await;
+        assert!(held.is_some());
+        assert!(downloader.slot("cdn.example.com").now_or_never().is_none());
+        assert!(downloader.slot("other.example.com").now_or_never().is_some());
+
+        drop(held);
+        assert!(downloader.slot("cdn.example.com").now_or_never().is_some());
+
+        let unlimited = Downloader::new(&DownloadConfig { per_host: 0, ..Default::default() }).unwrap();
+        assert!(unlimited.slot("cdn.example.com").await.is_none());
+    }
+
+    #[test]
+    fn test_invalid_proxy() {
+        let config = DownloadConfig { proxy: Some("not a url".to_string()), ..Default::default() };
+        assert!(Downloader::new(&config).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_url() {
+        let mut spool = Spool::with_budget(8, 100);
+        let error = Downloader::new(&DownloadConfig::default()).unwrap().get("episode.mp3", &mut spool).await.unwrap_err();
+        assert_eq!(error.code, ErrorCode::InvalidInput);
+    }
+
+    /// Lets tests reach the closed port 9 on localhost
+    fn local() -> egress::Policy {
+        egress::Policy::new(["127.0.0.1".to_string()])
+    }
+
+    #[tokio::test]
+    async fn test_private_addresses_refused() {
+        let downloader = Downloader::new(&DownloadConfig { retries: 0, ..Default::default() }).unwrap();
+        let mut spool = Spool::with_budget(8, 100);
+        for url in ["http://127.0.0.1:9/episode.mp3", "http://169.254.169.254/latest/meta-data", "file:///etc/passwd"] {
+            assert_eq!(downloader.get(url, &mut spool).await.unwrap_err().code, ErrorCode::InvalidInput, "{}", url);
+            assert_eq!(downloader.probe(url).await.unwrap_err().code, ErrorCode::InvalidInput, "{}", url);
+        }
+
+        // Names are checked once resolved
+        let error = downloader.get("http://localhost:9/episode.mp3", &mut spool).await.unwrap_err();
+        assert_eq!(error.code, ErrorCode::Upstream);
+    }
+
+    #[tokio::test]
+    async fn test_failing_host_is_left_alone() {
+        let config = DownloadConfig { retries: 0, breaker_threshold: 1, egress: local(), ..Default::default() };
+        let downloader = Downloader::new(&config).unwrap();
+        let mut spool = Spool::with_budget(8, 100);
+
+        // Nothing listens on port 9 of localhost
+        let error = downloader.get("http://127.0.0.1:9/episode.mp3", &mut spool).await.unwrap_err();
+        assert_eq!(error.code, ErrorCode::Upstream);
+        let error = downloader.get("http://127.0.0.1:9/other.mp3", &mut spool).await.unwrap_err();
+        assert_eq!(error.code, ErrorCode::UpstreamUnavailable);
+        assert!(downloader.breaker("cdn.example.com").check().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_probe_unreachable_host() {
+        let downloader = Downloader::new(&DownloadConfig { egress: local(), ..Default::default() }).unwrap();
+        assert_eq!(downloader.probe("episode.mp3").await.unwrap_err().code, ErrorCode::InvalidInput);
+        let error = downloader.probe("http://127.0.0.1:9/episode.mp3").await.unwrap_err();
+        assert_eq!(error.code, ErrorCode::Upstream);
+        assert_eq!(error.message, "Audio URL is unreachable");
+    }
+}
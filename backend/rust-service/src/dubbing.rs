@@ -0,0 +1,129 @@
+use crate::duration;
+use crate::error::ApiError;
+use crate::models::{AlignmentRequest, Cue, DubFit, DubFitStatus};
+use crate::tokenizer;
+use crate::tts::predict_alignment;
+
+/// How far a translated line may run over or under its original slot
+#[derive(Debug, Clone, Copy)]
+pub struct FitTolerance {
+    /// Longest acceptable estimate as a multiple of the slot (voice can speed up a bit)
+    pub max_stretch: f64,
+    /// Shortest acceptable estimate as a multiple of the slot (below this the lips keep moving)
+    pub min_fill: f64,
+}
+
+/// Check translated lines against the original cue timing
+///
+/// Each cue carries the original start/end and the translated text.
+///
+/// # How it works:
+/// 1. Estimate how long the translation takes to say with the language's
+///    duration model (same prediction as TTS alignment)
+/// 2. Compare it to the original slot: too long, too short, or fits
+/// 3. Suggest a time budget: lines that run long may borrow the silence
+///    before the next cue, lines that run short shrink to their estimate
+#[tracing::instrument(skip_all, fields(language = %language, cues = cues.len()))]
+pub fn fit_script(cues: &[Cue], language: &str, speaking_rate: Option<f64>, tolerance: &FitTolerance) -> Result<Vec<DubFit>, ApiError> {
+    let model = duration::models().get(language);
+    let rate = speaking_rate.unwrap_or_else(|| model.speaking_rate());
+    let mut fits = Vec::with_capacity(cues.len());
+
+    for (i, cue) in cues.iter().enumerate() {
+        let available = cue.end - cue.start;
+        if available <= 0.0 {
+            return Err(ApiError::invalid_input(format!("Cue {} ends at {} but starts at {}", cue.index, cue.end, cue.start))
+                .with_field(format!("cues[{}].end", i)));
+        }
+
+        // Step 1: Estimate the spoken duration
+        let request = AlignmentRequest {
+            text: cue.text.clone(),
+            language: language.to_string(),
+            speaking_rate: Some(rate),
+            ..Default::default()
+        };
+        let has_words = !tokenizer::tokens(&cue.text, language).is_empty();
+        // Nothing to say (e.g. a music cue)
+        let estimated = if has_words {
+            predict_alignment(&request, model)
+                .map_err(|e| e.context(&format!("Cue {}", cue.index)))?
+                .duration
+        } else {
+            0.0
+        };
+
+        // Step 2: Compare with the slot
+        let ratio = estimated / available;
+        let status = if ratio > tolerance.max_stretch {
+            DubFitStatus::TooLong
+        } else if ratio < tolerance.min_fill {
+            DubFitStatus::TooShort
+        } else {
+            DubFitStatus::Fits
+        };
+
+        // Step 3: Suggest a budget
+        let next_start = cues.get(i + 1).map(|next| next.start).unwrap_or(f64::INFINITY);
+        let budget_end = match status {
+            DubFitStatus::TooLong => (cue.start + estimated).min(next_start.max(cue.end)),
+            DubFitStatus::TooShort => cue.start + estimated,
+            DubFitStatus::Fits => cue.end,
+        };
+
+        fits.push(DubFit {
+            index: cue.index,
+            text: cue.text.clone(),
+            available,
+            estimated,
+            ratio,
+            status,
+            budget_start: cue.start,
+            budget_end,
+            required_rate: rate * ratio,
+        });
+    }
+
+    Ok(fits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::cue;
+
+    const TOLERANCE: FitTolerance = FitTolerance { max_stretch: 1.1, min_fill: 0.6 };
+
+    #[test]
+    fn test_classifies_lines() {
+        // At 10 chars/second "Hola amigo" takes about 0.9s
+        let cues = vec![
+            cue(1, 0.0, 1.0, "Hola amigo"),
+            cue(2, 2.0, 2.3, "Hola amigo"),
+            cue(3, 5.0, 9.0, "Hola amigo"),
+        ];
+        let fits = fit_script(&cues, "xx", Some(10.0), &TOLERANCE).unwrap();
+
+        assert_eq!(fits[0].status, DubFitStatus::Fits);
+        assert_eq!(fits[1].status, DubFitStatus::TooLong);
+        assert_eq!(fits[2].status, DubFitStatus::TooShort);
+        assert!(fits[1].required_rate > 10.0);
+    }
+
+    #[test]
+    fn test_budget_borrows_following_gap() {
+        let cues = vec![cue(1, 0.0, 0.5, "Hola amigo"), cue(2, 0.7, 2.0, "Sí")];
+        let fits = fit_script(&cues, "xx", Some(10.0), &TOLERANCE).unwrap();
+
+        assert_eq!(fits[0].status, DubFitStatus::TooLong);
+        assert_eq!(fits[0].budget_end, 0.7);
+    }
+
+    #[test]
+    fn test_empty_line_and_invalid_timing() {
+        let fits = fit_script(&[cue(1, 0.0, 1.0, "♪")], "xx", None, &TOLERANCE).unwrap();
+        assert_eq!(fits[0].estimated, 0.0);
+
+        assert!(fit_script(&[cue(1, 1.0, 1.0, "Hola")], "xx", None, &TOLERANCE).is_err());
+    }
+}
@@ -0,0 +1,397 @@
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::models::{DataFile, Warning, WarningCode};
+
+/// Models loaded at startup, shared by every request
+static MODELS: OnceLock<DurationModels> = OnceLock::new();
+
+/// On-disk format of a per-language duration table
+///
+/// Example (`data/duration/es.json`):
+/// ```json
+/// {
+///   "language": "es",
+///   "aliases": ["spanish"],
+///   "default_weight": 1.0,
+///   "weights": { "a": 1.4, "e": 1.4, "ch": 1.0, "rr": 1.2 },
+///   "pause_weights": { ",": 2.0, ".": 4.0 },
+///   "speaking_rate": 14.0,
+///   "voices": { "lucia": 15.5, "mateo": 12.5 }
+/// }
+/// ```
+/// Multi-letter keys (digraphs) are matched greedily and counted once.
+/// `pause_weights` is optional and replaces the default punctuation pauses.
+/// `speaking_rate` is weight units per second of synthesized speech, used
+/// when predicting TTS durations. `voices` is optional: the speaking rates
+/// of TTS voices that are faster or slower than that, by voice name.
+#[derive(Debug, Deserialize)]
+struct DurationFile {
+    language: String,
+    #[serde(default)]
+    aliases: Vec<String>,
+    #[serde(default = "default_weight")]
+    default_weight: f64,
+    #[serde(default)]
+    weights: HashMap<String, f64>,
+    #[serde(default)]
+    pause_weights: Option<HashMap<char, f64>>,
+    #[serde(default = "default_speaking_rate")]
+    speaking_rate: f64,
+    #[serde(default)]
+    voices: HashMap<String, f64>,
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+/// Typical neural TTS voice: roughly 14 characters per second
+fn default_speaking_rate() -> f64 {
+    14.0
+}
+
+/// Pauses speakers typically make at punctuation, in the same units as letters
+fn default_pause_weights() -> HashMap<char, f64> {
+    let clause = [',', ';', ':', '—', '–', '，', '、', '；', '：'];
+    let sentence = ['.', '!', '?', '…', '。', '！', '？'];
+
+    clause.iter().map(|c| (*c, 2.0))
+        .chain(sentence.iter().map(|c| (*c, 4.0)))
+        .collect()
+}
+
+/// Relative duration weights for the letters (and digraphs) of one language
+#[derive(Debug, Clone)]
+pub struct DurationModel {
+    default_weight: f64,
+    weights: HashMap<String, f64>,
+    longest_key: usize,
+    pause_weights: HashMap<char, f64>,
+    speaking_rate: f64,
+    voices: HashMap<String, f64>,
+}
+
+impl Default for DurationModel {
+    /// Every character weighs the same, i.e. plain char-count weighting
+    fn default() -> Self {
+        DurationModel {
+            default_weight: 1.0,
+            weights: HashMap::new(),
+            longest_key: 1,
+            pause_weights: default_pause_weights(),
+            speaking_rate: default_speaking_rate(),
+            voices: HashMap::new(),
+        }
+    }
+}
+
+impl DurationModel {
+    pub fn new(default_weight: f64, weights: HashMap<String, f64>) -> Self {
+        let weights: HashMap<String, f64> = weights.into_iter()
+            .map(|(key, weight)| (key.to_lowercase(), weight))
+            .collect();
+
+        let longest_key = weights.keys()
+            .map(|key| key.chars().count())
+            .max()
+            .unwrap_or(1);
+
+        DurationModel {
+            default_weight,
+            weights,
+            longest_key,
+            pause_weights: default_pause_weights(),
+            speaking_rate: default_speaking_rate(),
+            voices: HashMap::new(),
+        }
+    }
+
+    /// Replace the default punctuation pause weights
+    pub fn with_pause_weights(mut self, pause_weights: HashMap<char, f64>) -> Self {
+        self.pause_weights = pause_weights;
+        self
+    }
+
+    /// Replace the default TTS speaking rate (weight units per second)
+    pub fn with_speaking_rate(mut self, speaking_rate: f64) -> Self {
+        self.speaking_rate = speaking_rate;
+        self
+    }
+
+    /// Replace the speaking rates of particular voices
+    pub fn with_voices(mut self, voices: HashMap<String, f64>) -> Self {
+        self.voices = voices.into_iter()
+            .map(|(voice, rate)| (voice.to_lowercase(), rate))
+            .collect();
+        self
+    }
+
+    pub fn speaking_rate(&self) -> f64 {
+        self.speaking_rate
+    }
+
+    /// Speaking rate of a voice with its own profile
+    pub fn voice_rate(&self, voice: &str) -> Option<f64> {
+        self.voices.get(&voice.to_lowercase()).copied()
+    }
+
+    /// Names of the voices with their own profile, sorted
+    pub fn voices(&self) -> Vec<&str> {
+        let mut voices: Vec<&str> = self.voices.keys().map(String::as_str).collect();
+        voices.sort();
+        voices
+    }
+
+    /// Relative duration of the pause between two words
+    ///
+    /// `separator` is the raw text between them; the strongest punctuation
+    /// mark wins, so "!?" pauses no longer than "!".
+    pub fn pause_weight(&self, separator: &str) -> f64 {
+        separator.chars()
+            .filter_map(|c| self.pause_weights.get(&c))
+            .fold(0.0, |max, weight| f64::max(max, *weight))
+    }
+
+    /// Relative spoken duration of a word
+    ///
+    /// Walks the word left to right, always consuming the longest matching
+    /// key so "ch" in "chico" is counted once rather than as "c" + "h".
+    ///
+    /// Keys are looked up as slices of the (lowercased) word, so only a
+    /// word with capitals needs a copy.
+    pub fn word_weight(&self, word: &str) -> f64 {
+        let word = if word.chars().any(char::is_uppercase) { Cow::Owned(word.to_lowercase()) } else { Cow::Borrowed(word) };
+        // Byte offset of every char, and of the end
+        let offsets: Vec<usize> = word.char_indices().map(|(offset, _)| offset).chain([word.len()]).collect();
+        let n_chars = offsets.len() - 1;
+        let mut total = 0.0;
+        let mut i = 0;
+
+        while i < n_chars {
+            let max_len = self.longest_key.min(n_chars - i);
+
+            let matched = (1..=max_len).rev().find_map(|len| {
+                let key = &word[offsets[i]..offsets[i + len]];
+                self.weights.get(key).map(|weight| (len, *weight))
+            });
+
+            match matched {
+                Some((len, weight)) => {
+                    total += weight;
+                    i += len;
+                }
+                None => {
+                    total += self.default_weight;
+                    i += 1;
+                }
+            }
+        }
+
+        total
+    }
+}
+
+/// All loaded duration models, keyed by lowercase language code or alias
+#[derive(Debug, Default)]
+pub struct DurationModels {
+    models: HashMap<String, DurationModel>,
+    fallback: DurationModel,
+    /// Tables loaded, by language
+    files: Vec<DataFile>,
+}
+
+impl DurationModels {
+    /// Load every `*.json` table in `dir`
+    ///
+    /// Malformed files are logged and skipped so one bad table can't take
+    /// the service down.
+    pub fn load_dir(dir: &Path) -> Self {
+        let mut models = HashMap::new();
+        let mut files = Vec::new();
+
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::warn!("No duration models loaded from {}: {}", dir.display(), e);
+                return DurationModels::default();
+            }
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+
+            let parsed = fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|content| {
+                    let file = serde_json::from_str::<DurationFile>(&content).map_err(|e| e.to_string())?;
+                    Ok((DataFile::new(&file.language, &path, &content, file.weights.len()), file))
+                });
+
+            match parsed {
+                Ok((data_file, file)) => {
+                    files.push(data_file);
+                    let mut model = DurationModel::new(file.default_weight, file.weights)
+                        .with_speaking_rate(file.speaking_rate)
+                        .with_voices(file.voices);
+                    if let Some(pause_weights) = file.pause_weights {
+                        model = model.with_pause_weights(pause_weights);
+                    }
+                    for alias in file.aliases {
+                        models.insert(alias.to_lowercase(), model.clone());
+                    }
+                    log::info!("Loaded duration model for '{}'", file.language);
+                    models.insert(file.language.to_lowercase(), model);
+                }
+                Err(e) => log::warn!("Skipping duration model {}: {}", path.display(), e),
+            }
+        }
+
+        files.sort_by(|a, b| a.language.cmp(&b.language));
+        DurationModels { models, fallback: DurationModel::default(), files }
+    }
+
+    /// Number of languages (and aliases) with a model
+    pub fn len(&self) -> usize {
+        self.models.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.models.is_empty()
+    }
+
+    /// The tables the models came from
+    pub fn files(&self) -> &[DataFile] {
+        &self.files
+    }
+
+    /// Whether a model was loaded for a language, rather than falling back
+    pub fn contains(&self, language: &str) -> bool {
+        self.models.contains_key(&language.to_lowercase())
+    }
+
+    /// Warning for timings of `language` weighed without a model of its own
+    pub fn warning(&self, language: &str) -> Option<Warning> {
+        (!self.contains(language)).then(|| Warning::new(WarningCode::NoDurationModel,
+            format!("No duration model for '{}', so words were weighted by their length", language)))
+    }
+
+    /// Model for a language, or plain char counting if none was loaded
+    pub fn get(&self, language: &str) -> &DurationModel {
+        self.models.get(language)
+            .or_else(|| self.models.get(&language.to_lowercase()))
+            .unwrap_or(&self.fallback)
+    }
+}
+
+/// Load models once at startup
+pub fn init(dir: &Path) {
+    let loaded = DurationModels::load_dir(dir);
+    if MODELS.set(loaded).is_err() {
+        log::warn!("Duration models already initialised");
+    }
+}
+
+/// Whether `init` has run
+pub fn is_loaded() -> bool {
+    MODELS.get().is_some()
+}
+
+/// Models loaded by `init`, or char-count defaults if it was never called
+pub fn models() -> &'static DurationModels {
+    MODELS.get_or_init(DurationModels::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spanish() -> DurationModel {
+        let weights = HashMap::from([
+            ("a".to_string(), 1.5),
+            ("o".to_string(), 1.5),
+            ("ch".to_string(), 1.0),
+        ]);
+        DurationModel::new(1.0, weights)
+    }
+
+    #[test]
+    fn test_default_model_counts_chars() {
+        let model = DurationModel::default();
+        assert_eq!(model.word_weight("hello"), 5.0);
+    }
+
+    #[test]
+    fn test_vowels_weigh_more() {
+        let model = spanish();
+        // m(1) + a(1.5) + l(1) + o(1.5)
+        assert_eq!(model.word_weight("malo"), 5.0);
+    }
+
+    #[test]
+    fn test_digraph_counted_once() {
+        let model = spanish();
+        // ch(1) + i(1) + c(1) + o(1.5)
+        assert_eq!(model.word_weight("chico"), 4.5);
+        assert_eq!(model.word_weight("Chico"), 4.5);
+    }
+
+    #[test]
+    fn test_multibyte_keys() {
+        let model = DurationModel::new(1.0, HashMap::from([("ñ".to_string(), 2.0), ("ll".to_string(), 0.5)]));
+        // ñ(2) + a(1) + ll(0.5) + é(1)
+        assert_eq!(model.word_weight("ÑaLLé"), 4.5);
+        assert_eq!(model.word_weight("日本"), 2.0);
+    }
+
+    #[test]
+    fn test_pause_weights() {
+        let model = DurationModel::default();
+        assert_eq!(model.pause_weight(" "), 0.0);
+        assert_eq!(model.pause_weight(", "), 2.0);
+        assert_eq!(model.pause_weight("?! "), 4.0);
+
+        let custom = model.with_pause_weights(HashMap::from([(',', 0.5)]));
+        assert_eq!(custom.pause_weight(", "), 0.5);
+        assert_eq!(custom.pause_weight(". "), 0.0);
+    }
+
+    #[test]
+    fn test_voice_rates() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("es.json"), r#"{"language": "es", "voices": {"Lucia": 16.0}}"#).unwrap();
+        let model = DurationModels::load_dir(dir.path()).get("es").clone();
+
+        assert_eq!(model.voice_rate("lucia"), Some(16.0));
+        assert_eq!(model.voice_rate("mateo"), None);
+        assert_eq!(model.voices(), vec!["lucia"]);
+        assert_eq!(model.speaking_rate(), 14.0);
+    }
+
+    #[test]
+    fn test_unknown_language_falls_back() {
+        let models = DurationModels::default();
+        assert_eq!(models.get("xx").word_weight("abc"), 3.0);
+        assert_eq!(models.warning("xx").unwrap().code, WarningCode::NoDurationModel);
+    }
+
+    #[test]
+    fn test_bundled_tables_parse() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("data/duration");
+        let models = DurationModels::load_dir(&dir);
+        assert!(models.get("en").word_weight("the") < 3.0);
+        assert!(models.get("spanish").word_weight("casa") > 4.0);
+        assert!(models.warning("EN").is_none());
+
+        let files: Vec<&str> = models.files().iter().map(|file| file.file.as_str()).collect();
+        assert_eq!(files, vec!["de.json", "en.json", "es.json", "fr.json"]);
+        assert_eq!(models.files()[1].version.len(), 12);
+    }
+}
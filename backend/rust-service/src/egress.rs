@@ -0,0 +1,184 @@
+use std::error::Error;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::redirect;
+use reqwest::{ClientBuilder, Url};
+
+/// Redirects followed before a request is given up on
+const MAX_REDIRECTS: usize = 10;
+
+/// Where outgoing requests to client-given URLs (audio downloads, job
+/// callbacks) may go
+///
+/// Only public addresses: a URL can't point the service at itself, the
+/// cloud metadata endpoint or anything else on the private network, not
+/// even through a DNS name or a redirect. Hosts in `allowed_hosts`
+/// (`EGRESS_ALLOWED_HOSTS`) are exempt, for callbacks to a backend on the
+/// same network.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Policy {
+    allowed_hosts: Arc<Vec<String>>,
+}
+
+impl Policy {
+    pub fn new(allowed_hosts: impl IntoIterator<Item = String>) -> Self {
+        let allowed_hosts = allowed_hosts.into_iter()
+            .map(|host| host.trim().trim_start_matches('[').trim_end_matches(']').to_lowercase())
+            .filter(|host| !host.is_empty())
+            .collect();
+        Policy { allowed_hosts: Arc::new(allowed_hosts) }
+    }
+
+    fn allows_host(&self, host: &str) -> bool {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        self.allowed_hosts.iter().any(|allowed| allowed.eq_ignore_ascii_case(host))
+    }
+
+    /// An http(s) URL whose host, if it's an address, is a public one
+    ///
+    /// Names are checked when they're resolved (see `apply`).
+    pub fn check_url(&self, url: &Url) -> Result<(), String> {
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(format!("Must be http or https, got {}", url.scheme()));
+        }
+        let host = url.host_str().ok_or("Missing host")?;
+        match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+            Ok(address) if !is_public(address) && !self.allows_host(host) => Err(format!("{} is not a public address", host)),
+            _ => Ok(()),
+        }
+    }
+
+    /// `check_url`, then resolve the host and check every address it has,
+    /// for URLs stored now and requested later
+    pub async fn check_resolved(&self, url: &Url) -> Result<(), String> {
+        self.check_url(url)?;
+        let host = url.host_str().unwrap_or_default();
+        if self.allows_host(host) {
+            return Ok(());
+        }
+
+        let port = url.port_or_known_default().unwrap_or(443);
+        let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host.trim_start_matches('[').trim_end_matches(']'), port)).await
+            .map_err(|_| format!("Can't resolve {}", host))?
+            .collect();
+        match addresses.iter().find(|address| !is_public(address.ip())) {
+            Some(address) => Err(format!("{} resolves to {}, which is not a public address", host, address.ip())),
+            None => Ok(()),
+        }
+    }
+
+    /// Hold a client to the policy: only public addresses are connected to
+    /// and every redirect is checked
+    ///
+    /// Without a proxy, the policy applies to the addresses names resolve
+    /// to, so a name can't be re-pointed at a private address between a
+    /// check and the request. Through a proxy, the proxy resolves names and
+    /// is responsible for where they lead.
+    pub fn apply(&self, builder: ClientBuilder) -> ClientBuilder {
+        let policy = self.clone();
+        builder
+            .dns_resolver(Arc::new(PublicResolver { policy: self.clone() }))
+            .redirect(redirect::Policy::custom(move |attempt| {
+                if attempt.previous().len() >= MAX_REDIRECTS {
+                    return attempt.error("Too many redirects");
+                }
+                match policy.check_url(attempt.url()) {
+                    Ok(()) => attempt.follow(),
+                    Err(e) => attempt.error(e),
+                }
+            }))
+    }
+}
+
+/// Resolves names to their public addresses only
+struct PublicResolver {
+    policy: Policy,
+}
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        let allowed = self.policy.allows_host(&host);
+        Box::pin(async move {
+            let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0)).await?
+                .filter(|address| allowed || is_public(address.ip()))
+                .collect();
+            if addresses.is_empty() {
+                let error: Box<dyn Error + Send + Sync> = format!("{} has no public address", host).into();
+                return Err(error);
+            }
+            Ok(Box::new(addresses.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Whether `address` is reachable on the public internet: not loopback,
+/// private, link-local, shared (CGNAT), unspecified, broadcast or multicast
+pub fn is_public(address: IpAddr) -> bool {
+    match address {
+        IpAddr::V4(address) => {
+            let shared = address.octets()[0] == 100 && (address.octets()[1] & 0xc0) == 64;
+            !(address.is_loopback() || address.is_private() || address.is_link_local() || address.is_unspecified()
+                || address.is_broadcast() || address.is_multicast() || shared || address.octets()[0] == 0)
+        }
+        IpAddr::V6(address) => match address.to_ipv4_mapped() {
+            Some(mapped) => is_public(IpAddr::V4(mapped)),
+            None => {
+                let first = address.segments()[0];
+                let unique_local = (first & 0xfe00) == 0xfc00;
+                let link_local = (first & 0xffc0) == 0xfe80;
+                !(address.is_loopback() || address.is_unspecified() || address.is_multicast() || unique_local || link_local
+                    || address.to_ipv4().is_some_and(|compatible| compatible == Ipv4Addr::UNSPECIFIED))
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    #[test]
+    fn test_is_public() {
+        for private in ["127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "0.0.0.0", "100.64.0.1", "::1", "::", "fd00::1", "fe80::1", "::ffff:127.0.0.1"] {
+            assert!(!is_public(private.parse().unwrap()), "{}", private);
+        }
+        for public in ["93.184.216.34", "8.8.8.8", "2606:4700::1111", "::ffff:8.8.8.8"] {
+            assert!(is_public(public.parse().unwrap()), "{}", public);
+        }
+    }
+
+    #[test]
+    fn test_check_url() {
+        let policy = Policy::default();
+        assert!(policy.check_url(&url("https://example.com/hook")).is_ok());
+        assert!(policy.check_url(&url("http://93.184.216.34/hook")).is_ok());
+        assert!(policy.check_url(&url("http://169.254.169.254/latest/meta-data")).is_err());
+        assert!(policy.check_url(&url("http://[::1]:8080/")).is_err());
+        assert!(policy.check_url(&url("ftp://example.com")).is_err());
+
+        let policy = Policy::new(["127.0.0.1".to_string(), "[::1]".to_string()]);
+        assert!(policy.check_url(&url("http://127.0.0.1:9/hook")).is_ok());
+        assert!(policy.check_url(&url("http://[::1]:9/hook")).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_check_resolved() {
+        let policy = Policy::default();
+        assert!(policy.check_resolved(&url("http://localhost:8080/hook")).await.is_err());
+        assert!(Policy::new(["localhost".to_string()]).check_resolved(&url("http://localhost:8080/hook")).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_client_refuses_private_names() {
+        let client = Policy::default().apply(reqwest::Client::builder()).build().unwrap();
+        let error = client.get("http://localhost:9/").send().await.unwrap_err();
+        assert!(error.is_connect());
+    }
+}
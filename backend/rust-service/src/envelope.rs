@@ -0,0 +1,148 @@
+use std::time::Instant;
+
+use actix_web::body::{self, BoxBody, MessageBody};
+use actix_web::dev::ServiceResponse;
+use actix_web::http::header::{self, HeaderName, HeaderValue};
+use actix_web::web::Bytes;
+
+use serde::Deserialize;
+
+use crate::models::{ResponseMeta, Warning};
+
+/// Response header with the request's ID, on every response
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+/// Response header with `processing_time_ms`, on every response
+pub const PROCESSING_TIME_HEADER: &str = "x-processing-time-ms";
+
+/// Whether the query string asks for array bodies to be wrapped
+/// (`envelope=true`)
+pub fn requested(query: &str) -> bool {
+    query.split('&').any(|pair| matches!(pair, "envelope=true" | "envelope=1" | "envelope"))
+}
+
+/// What a response's `meta` says, once the handler is done
+///
+/// Requests that never reached the tracing layer get an ID of their own.
+/// `warnings` are filled in from the body by `wrap`.
+pub fn meta(request_id: Option<String>, started: Instant, api_version: Option<&str>) -> ResponseMeta {
+    ResponseMeta {
+        request_id: request_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+        processing_time_ms: started.elapsed().as_secs_f64() * 1000.0,
+        api_version: api_version.unwrap_or(crate::versioning::CURRENT).to_string(),
+        warnings: Vec::new(),
+    }
+}
+
+/// Label a response with its request ID and processing time
+///
+/// Every response gets the headers. On versioned requests, JSON objects
+/// also get a `meta` field, its `warnings` the messages of the body's own
+/// coded `warnings`, and with `wrap_arrays` JSON arrays become
+/// `{"meta": ..., "results": [...]}`; the unversioned aliases keep their
+/// original bodies, as for `api_version`.
+pub async fn wrap<B: MessageBody + 'static>(
+    res: ServiceResponse<B>,
+    mut meta: ResponseMeta,
+    versioned: bool,
+    wrap_arrays: bool,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let mut res = res.map_into_boxed_body();
+    insert_headers(res.headers_mut(), &meta);
+
+    let is_json = res.headers().get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("application/json"));
+    if !(versioned && is_json) {
+        return Ok(res);
+    }
+
+    let (req, res) = res.into_parts();
+    let (res, body) = res.into_parts();
+    let body = body::to_bytes(body).await.map_err(actix_web::error::ErrorInternalServerError)?;
+    meta.warnings = warnings(&body);
+    let body = with_meta(&body, &meta, wrap_arrays).unwrap_or(body);
+    Ok(ServiceResponse::new(req, res.set_body(BoxBody::new(body))))
+}
+
+fn insert_headers(headers: &mut header::HeaderMap, meta: &ResponseMeta) {
+    if let Ok(value) = HeaderValue::from_str(&meta.request_id) {
+        headers.insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+    }
+    if let Ok(value) = HeaderValue::from_str(&format!("{:.3}", meta.processing_time_ms)) {
+        headers.insert(HeaderName::from_static(PROCESSING_TIME_HEADER), value);
+    }
+}
+
+/// Messages of the coded `warnings` a JSON object carries, if any
+///
+/// Warnings travel in the results they're about, so they survive caching,
+/// blocking threads and batches; `meta` repeats them where it's added.
+fn warnings(body: &[u8]) -> Vec<String> {
+    #[derive(Deserialize)]
+    struct Warned {
+        #[serde(default)]
+        warnings: Vec<Warning>,
+    }
+
+    serde_json::from_slice::<Warned>(body)
+        .map(|warned| warned.warnings.into_iter().map(|warning| warning.message).collect())
+        .unwrap_or_default()
+}
+
+/// Splice `"meta"` in as the first key of an object, or wrap an array as
+/// `results` when `wrap_arrays`; other bodies are left alone
+fn with_meta(body: &[u8], meta: &ResponseMeta, wrap_arrays: bool) -> Option<Bytes> {
+    let meta = serde_json::to_vec(meta).ok()?;
+    let trimmed = body.trim_ascii_start();
+    let mut tagged = b"{\"meta\":".to_vec();
+    tagged.extend_from_slice(&meta);
+
+    if let Some(rest) = trimmed.strip_prefix(b"{") {
+        if !rest.trim_ascii_start().starts_with(b"}") {
+            tagged.push(b',');
+        }
+        tagged.extend_from_slice(rest);
+    } else if wrap_arrays && trimmed.starts_with(b"[") {
+        tagged.extend_from_slice(b",\"results\":");
+        tagged.extend_from_slice(trimmed);
+        tagged.push(b'}');
+    } else {
+        return None;
+    }
+    Some(Bytes::from(tagged))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> ResponseMeta {
+        ResponseMeta { request_id: "r1".to_string(), processing_time_ms: 1.5, api_version: "v1".to_string(), warnings: Vec::new() }
+    }
+
+    #[test]
+    fn test_with_meta() {
+        let meta = r#"{"request_id":"r1","processing_time_ms":1.5,"api_version":"v1","warnings":[]}"#;
+        assert_eq!(with_meta(br#"{"tokens":[]}"#, &sample(), false).unwrap(), Bytes::from(format!(r#"{{"meta":{},"tokens":[]}}"#, meta)));
+        assert_eq!(with_meta(b"{}", &sample(), false).unwrap(), Bytes::from(format!(r#"{{"meta":{}}}"#, meta)));
+        assert_eq!(with_meta(b"[1,2]", &sample(), true).unwrap(), Bytes::from(format!(r#"{{"meta":{},"results":[1,2]}}"#, meta)));
+        assert!(with_meta(b"[1,2]", &sample(), false).is_none());
+    }
+
+    #[test]
+    fn test_warnings() {
+        let body = br#"{"tokens":[],"warnings":[{"code":"default_tokenizer","message":"Split on spaces"}]}"#;
+        assert_eq!(warnings(body), vec!["Split on spaces"]);
+        assert!(warnings(br#"{"tokens":[]}"#).is_empty());
+        // Cue warnings of file alignments aren't coded ones
+        assert!(warnings(br#"{"warnings":[{"cue_index":1,"kind":"overlap","message":"Overlaps"}]}"#).is_empty());
+        assert!(warnings(b"[1,2]").is_empty());
+    }
+
+    #[test]
+    fn test_requested() {
+        assert!(requested("format=json&envelope=true"));
+        assert!(!requested("envelope=false"));
+        assert!(!requested(""));
+    }
+}
@@ -0,0 +1,187 @@
+use std::fmt;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Stable, machine-readable error codes
+///
+/// Codes are part of the API: add new ones, but never rename or reuse them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// Malformed or inconsistent request
+    InvalidInput,
+    /// Subtitle content that can't be parsed
+    InvalidSubtitles,
+    /// Text with nothing to tokenize or align
+    NoWords,
+    /// A valid request for something the service doesn't do (yet)
+    Unsupported,
+    NotFound,
+    Unauthorized,
+    /// The requested API version isn't served
+    UnsupportedVersion,
+    PayloadTooLarge,
+    /// The client has used up a monthly quota
+    QuotaExceeded,
+    /// Too busy to accept the work right now
+    Unavailable,
+    Cancelled,
+    /// Clashes with a request still in progress
+    Conflict,
+    /// A service we depend on (TTS engine, audio host) failed
+    Upstream,
+    /// A service we depend on keeps failing, so it isn't being called for a while
+    UpstreamUnavailable,
+    /// The request took longer than the configured timeout
+    Timeout,
+    Internal,
+}
+
+impl ErrorCode {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ErrorCode::InvalidInput | ErrorCode::InvalidSubtitles | ErrorCode::NoWords => StatusCode::BAD_REQUEST,
+            ErrorCode::Unsupported => StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+            ErrorCode::UnsupportedVersion => StatusCode::NOT_ACCEPTABLE,
+            ErrorCode::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::QuotaExceeded => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::Unavailable | ErrorCode::UpstreamUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::Cancelled | ErrorCode::Conflict => StatusCode::CONFLICT,
+            ErrorCode::Upstream => StatusCode::BAD_GATEWAY,
+            ErrorCode::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// Error returned by the API, as `{"error": {"code", "message", ...}}`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub message: String,
+    /// Request field the error is about, e.g. `subtitle_end` or `cues[3].start`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub details: Option<serde_json::Value>,
+}
+
+/// Response body of every failed request
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorResponse {
+    pub error: ApiError,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        ApiError { code, message: message.into(), field: None, details: None }
+    }
+
+    pub fn invalid_input(message: impl Into<String>) -> Self {
+        ApiError::new(ErrorCode::InvalidInput, message)
+    }
+
+    pub fn unsupported(message: impl Into<String>) -> Self {
+        ApiError::new(ErrorCode::Unsupported, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        ApiError::new(ErrorCode::NotFound, message)
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        ApiError::new(ErrorCode::Internal, message)
+    }
+
+    pub fn with_field(mut self, field: impl Into<String>) -> Self {
+        self.field = Some(field.into());
+        self
+    }
+
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    /// Prefix the message, keeping the code (e.g. which of two files failed)
+    pub fn context(mut self, context: &str) -> Self {
+        self.message = format!("{}: {}", context, self.message);
+        self
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.field {
+            Some(field) => write!(f, "{}: {}", field, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.code.status()
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        if self.code.status().is_server_error() {
+            log::error!("{:?}: {}", self.code, self);
+        } else {
+            log::warn!("{:?}: {}", self.code, self);
+        }
+
+        HttpResponse::build(self.status_code()).json(ErrorResponse { error: self.clone() })
+    }
+}
+
+/// Report malformed JSON bodies and query strings in the same shape as other errors
+pub fn json_error_handler(err: actix_web::error::JsonPayloadError, _req: &actix_web::HttpRequest) -> actix_web::Error {
+    let code = match err {
+        actix_web::error::JsonPayloadError::OverflowKnownLength { .. }
+        | actix_web::error::JsonPayloadError::Overflow { .. } => ErrorCode::PayloadTooLarge,
+        _ => ErrorCode::InvalidInput,
+    };
+    ApiError::new(code, err.to_string()).into()
+}
+
+pub fn query_error_handler(err: actix_web::error::QueryPayloadError, _req: &actix_web::HttpRequest) -> actix_web::Error {
+    ApiError::invalid_input(err.to_string()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::body::MessageBody;
+
+    #[test]
+    fn test_codes_map_to_statuses() {
+        assert_eq!(ErrorCode::NoWords.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(ErrorCode::Unsupported.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(ErrorCode::Internal.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(ErrorCode::Timeout.status(), StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(ErrorCode::UpstreamUnavailable.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(ErrorCode::QuotaExceeded.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[test]
+    fn test_response_body() {
+        let error = ApiError::invalid_input("must be after subtitle_start").with_field("subtitle_end");
+        let response = error.error_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = response.into_body().try_into_bytes().unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json, serde_json::json!({
+            "error": { "code": "invalid_input", "message": "must be after subtitle_start", "field": "subtitle_end" }
+        }));
+    }
+}
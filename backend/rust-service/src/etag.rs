@@ -0,0 +1,106 @@
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::{HttpRequest, HttpResponse};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::{reload, versioning};
+
+/// Validator for a response that depends only on the request
+///
+/// Hashes the body with what else shapes the response: the path and query
+/// string, the `Accept` and `API-Version` headers that pick its format and
+/// version, and the service version and data generation, so a deploy or a
+/// data reload retires old tags. Weak, because versioned JSON carries a
+/// fresh `meta` each time: the body is equivalent, not byte for byte equal.
+pub fn tag(req: &HttpRequest, body: &impl Serialize) -> String {
+    let mut hasher = Sha256::new();
+    for part in [req.path(), req.query_string(), env!("CARGO_PKG_VERSION")] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    for name in [header::ACCEPT.as_str(), versioning::VERSION_HEADER] {
+        hasher.update(req.headers().get(name).map(HeaderValue::as_bytes).unwrap_or_default());
+        hasher.update([0]);
+    }
+    hasher.update(reload::generation().to_be_bytes());
+    hasher.update(serde_json::to_vec(body).unwrap_or_default());
+    format!("W/\"{}\"", hex::encode(&hasher.finalize()[..16]))
+}
+
+/// Whether `If-None-Match` on `req` already names `etag`, so the client's
+/// copy is current
+///
+/// Weak comparison, as RFC 9110 asks for `If-None-Match`: `W/` prefixes are
+/// ignored on both sides. `*` never matches: the tagged endpoints are POSTs
+/// answered before their body is validated, so only a tag computed from
+/// the same body can stand in for the response.
+pub fn is_fresh(req: &HttpRequest, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    req.headers().get_all(header::IF_NONE_MATCH)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|candidate| candidate.trim() != "*" && opaque(candidate) == opaque(etag))
+}
+
+/// 304 for a client whose copy is current
+pub fn not_modified(etag: &str) -> HttpResponse {
+    HttpResponse::NotModified().insert_header((header::ETAG, etag)).finish()
+}
+
+/// Attach `etag` to `response` if it succeeded; errors aren't cacheable
+pub fn attach(mut response: HttpResponse, etag: Option<&str>) -> HttpResponse {
+    if let Some(value) = etag.filter(|_| response.status().is_success()).and_then(|etag| HeaderValue::from_str(etag).ok()) {
+        response.headers_mut().insert(header::ETAG, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use serde_json::json;
+
+    #[test]
+    fn test_tag() {
+        let req = TestRequest::post().uri("/api/v1/tokenize").to_http_request();
+        let etag = tag(&req, &json!({"text": "Hola", "language": "es"}));
+
+        assert!(etag.starts_with("W/\"") && etag.ends_with('"'));
+        assert_eq!(etag, tag(&req, &json!({"text": "Hola", "language": "es"})));
+        assert_ne!(etag, tag(&req, &json!({"text": "Hola!", "language": "es"})));
+
+        // The same input in another format or with other options is another response
+        let msgpack = TestRequest::post().uri("/api/v1/tokenize").insert_header(("accept", "application/msgpack")).to_http_request();
+        let flagged = TestRequest::post().uri("/api/v1/tokenize?content_flags=true").to_http_request();
+        assert_ne!(etag, tag(&msgpack, &json!({"text": "Hola", "language": "es"})));
+        assert_ne!(etag, tag(&flagged, &json!({"text": "Hola", "language": "es"})));
+    }
+
+    #[test]
+    fn test_is_fresh() {
+        let etag = "W/\"abc\"";
+        let fresh = |if_none_match: &str| is_fresh(&TestRequest::default().insert_header(("if-none-match", if_none_match)).to_http_request(), etag);
+
+        assert!(fresh("W/\"abc\""));
+        assert!(fresh("\"abc\""));
+        assert!(fresh("\"xyz\", W/\"abc\""));
+        assert!(!fresh("*"));
+        assert!(!fresh("W/\"xyz\""));
+        assert!(!is_fresh(&TestRequest::default().to_http_request(), etag));
+    }
+
+    #[test]
+    fn test_attach() {
+        let tagged = attach(HttpResponse::Ok().finish(), Some("W/\"abc\""));
+        assert_eq!(tagged.headers().get(header::ETAG).unwrap(), "W/\"abc\"");
+
+        let failed = attach(HttpResponse::BadRequest().finish(), Some("W/\"abc\""));
+        assert!(failed.headers().get(header::ETAG).is_none());
+
+        let not_modified = not_modified("W/\"abc\"");
+        assert_eq!(not_modified.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(not_modified.headers().get(header::ETAG).unwrap(), "W/\"abc\"");
+    }
+}
@@ -0,0 +1,199 @@
+use crate::aligner::align_weighted;
+use crate::frequency::{self, FrequencyList};
+use crate::models::{AlignmentRequest, ClozeGap, ClozeItem, Cue, WordTiming};
+
+/// What's shown in place of a gapped word
+const GAP_MARKER: &str = "_____";
+
+/// Which words to gap and how many choices to offer
+#[derive(Debug, Clone, Copy)]
+pub struct ClozeOptions {
+    pub gaps_per_cue: usize,
+    /// Skip words ranked more frequent than this (function words)
+    pub min_rank: usize,
+    /// Skip words ranked rarer than this; None also allows unlisted words
+    pub max_rank: Option<usize>,
+    pub distractors: usize,
+}
+
+/// Build listening fill-in-the-blank exercises from cues
+///
+/// # How it works:
+/// 1. Align each cue's words to get their timings
+/// 2. Keep words inside the frequency band (no part-of-speech tagger yet,
+///    so `min_rank` and the language's stopwords are what keep out
+///    articles and pronouns), preferring the rarest, then the longest
+/// 3. Offer distractors of similar frequency and length, taken from the
+///    file itself and the language's frequency list
+///
+/// Cues with nothing worth gapping are skipped.
+pub fn build_cloze(cues: &[Cue], language: &str, options: &ClozeOptions) -> Vec<ClozeItem> {
+    let lists = frequency::lists();
+    let list = lists.get(language);
+    let stopwords = lists.stopwords(language);
+
+    // Step 1: Align every cue
+    let mut aligned = Vec::with_capacity(cues.len());
+    for cue in cues {
+        let request = AlignmentRequest {
+            text: cue.text.clone(),
+            language: language.to_string(),
+            subtitle_start: cue.start,
+            subtitle_end: cue.end,
+            ..Default::default()
+        };
+        match align_weighted(&request) {
+            Ok(response) => aligned.push((cue, response.timings)),
+            Err(e) => log::debug!("Skipping cue {} for cloze: {}", cue.index, e),
+        }
+    }
+
+    // Distractor pool: every distinct gappable word in the file plus the frequency list
+    let mut pool: Vec<String> = aligned.iter()
+        .flat_map(|(_, timings)| timings.iter().map(|t| t.word.to_lowercase()))
+        .chain(list.words().iter().cloned())
+        .filter(|word| is_gappable(word) && !stopwords.contains(word))
+        .collect();
+    pool.sort();
+    pool.dedup();
+
+    let mut items = Vec::new();
+
+    for (cue, timings) in aligned {
+        // Step 2: Pick target words
+        let mut targets: Vec<&WordTiming> = timings.iter()
+            .filter(|t| is_gappable(&t.word) && !stopwords.contains(&t.word) && in_band(list.rank(&t.word), options))
+            .collect();
+        targets.sort_by_key(|t| (std::cmp::Reverse(rarity(list, &t.word)), std::cmp::Reverse(t.word.chars().count())));
+        targets.truncate(options.gaps_per_cue);
+        targets.sort_by_key(|t| t.char_start);
+
+        if targets.is_empty() {
+            continue;
+        }
+
+        // Step 3: Distractors and the gapped text
+        let gaps: Vec<ClozeGap> = targets.iter()
+            .map(|t| ClozeGap {
+                word: t.word.clone(),
+                char_start: t.char_start,
+                char_end: t.char_end,
+                start: t.start,
+                end: t.end,
+                rank: list.rank(&t.word),
+                distractors: distractors(&t.word, &pool, list, options.distractors),
+            })
+            .collect();
+
+        let mut gapped_text = cue.text.clone();
+        for gap in gaps.iter().rev() {
+            gapped_text.replace_range(gap.char_start..gap.char_end, GAP_MARKER);
+        }
+
+        items.push(ClozeItem {
+            cue_index: cue.index,
+            start: cue.start,
+            end: cue.end,
+            text: cue.text.clone(),
+            gapped_text,
+            gaps,
+            timings,
+        });
+    }
+
+    items
+}
+
+/// Real words only: no numbers, no single letters
+fn is_gappable(word: &str) -> bool {
+    word.chars().count() >= 2 && word.chars().all(|c| c.is_alphabetic() || c == '\'' || c == '’' || c == '-')
+}
+
+fn in_band(rank: Option<usize>, options: &ClozeOptions) -> bool {
+    match (rank, options.max_rank) {
+        (Some(rank), max_rank) => rank >= options.min_rank && max_rank.is_none_or(|max| rank <= max),
+        (None, max_rank) => max_rank.is_none(),
+    }
+}
+
+/// Higher is rarer; unlisted words rank just past the end of the list
+fn rarity(list: &FrequencyList, word: &str) -> usize {
+    list.rank(word).unwrap_or(list.len() + 1)
+}
+
+/// Words closest to the target in length, then in frequency
+///
+/// Capitalization follows the target so the choice doesn't give the answer away.
+fn distractors(target: &str, pool: &[String], list: &FrequencyList, count: usize) -> Vec<String> {
+    let target_lower = target.to_lowercase();
+    let target_len = target.chars().count();
+    let target_rarity = rarity(list, target);
+
+    let mut candidates: Vec<&String> = pool.iter()
+        .filter(|word| **word != target_lower)
+        .collect();
+    candidates.sort_by_key(|word| {
+        (word.chars().count().abs_diff(target_len), rarity(list, word).abs_diff(target_rarity))
+    });
+
+    let capitalized = target.chars().next().is_some_and(char::is_uppercase);
+
+    candidates.into_iter()
+        .take(count)
+        .map(|word| {
+            if capitalized {
+                let mut chars = word.chars();
+                chars.next().map(|first| first.to_uppercase().chain(chars).collect()).unwrap_or_default()
+            } else {
+                word.clone()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::cue;
+
+    const OPTIONS: ClozeOptions = ClozeOptions { gaps_per_cue: 1, min_rank: 3, max_rank: None, distractors: 2 };
+
+    #[test]
+    fn test_gaps_rarest_longest_word() {
+        let cues = vec![cue(1, 0.0, 2.0, "I saw an elephant"), cue(2, 2.0, 4.0, "We bought bread")];
+        let items = build_cloze(&cues, "xx", &OPTIONS);
+
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].gaps[0].word, "elephant");
+        assert_eq!(items[0].gapped_text, "I saw an _____");
+        assert!(items[0].gaps[0].start > 0.5);
+        assert_eq!(items[0].timings.len(), 4);
+        assert_eq!(items[0].gaps[0].distractors.len(), 2);
+        assert!(!items[0].gaps[0].distractors.contains(&"elephant".to_string()));
+    }
+
+    #[test]
+    fn test_frequency_band() {
+        let list = FrequencyList::parse("the\nyou\nhouse\n");
+        assert!(!in_band(list.rank("the"), &OPTIONS));
+        assert!(in_band(list.rank("house"), &OPTIONS));
+        assert!(in_band(list.rank("zebra"), &OPTIONS));
+
+        let listed_only = ClozeOptions { max_rank: Some(10), ..OPTIONS };
+        assert!(!in_band(list.rank("zebra"), &listed_only));
+    }
+
+    #[test]
+    fn test_distractors_match_capitalization() {
+        let pool = vec!["house".to_string(), "mouse".to_string(), "extraordinary".to_string()];
+        let list = FrequencyList::default();
+
+        assert_eq!(distractors("House", &pool, &list, 2), vec!["Mouse", "Extraordinary"]);
+    }
+
+    #[test]
+    fn test_numbers_not_gapped() {
+        let items = build_cloze(&[cue(1, 0.0, 1.0, "42 7")], "xx", &OPTIONS);
+        assert!(items.is_empty());
+    }
+}
@@ -0,0 +1,167 @@
+use crate::models::{OutputFormat, VocabEntry, WordTiming};
+use crate::subtitles::writer::format_timestamp;
+
+const HEADER: [&str; 5] = ["word", "start", "end", "confidence", "cue_index"];
+
+const ANKI_COLUMNS: [&str; 10] = ["Word", "Forms", "Context", "Timestamp", "Count", "Rank", "Band", "LastTimestamp", "Contexts", "Interval"];
+
+impl OutputFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            OutputFormat::Json => "application/json",
+            OutputFormat::Csv => "text/csv; charset=utf-8",
+            OutputFormat::Tsv => "text/tab-separated-values; charset=utf-8",
+        }
+    }
+}
+
+/// Render word timings as a flat CSV/TSV table
+///
+/// One row per word: word, start, end, confidence, cue_index.
+/// `cues` pairs each cue's index with its timings.
+pub fn timings_table(cues: &[(usize, &[WordTiming])], format: OutputFormat) -> String {
+    let delimiter = match format {
+        OutputFormat::Tsv => '\t',
+        _ => ',',
+    };
+
+    let mut out = HEADER.join(&delimiter.to_string());
+    out.push('\n');
+
+    for (cue_index, timings) in cues {
+        for timing in timings.iter() {
+            let row = [
+                escape_field(&timing.word, delimiter),
+                timing.start.to_string(),
+                timing.end.to_string(),
+                timing.confidence.to_string(),
+                cue_index.to_string(),
+            ];
+            out.push_str(&row.join(&delimiter.to_string()));
+            out.push('\n');
+        }
+    }
+
+    out
+}
+
+/// Render extracted vocabulary as an Anki-importable note file
+///
+/// The `#` header lines tell Anki (2.1.54+) the separator and field order,
+/// so the file imports without choosing options by hand. Fields: lemma,
+/// surface forms, the sentence the word first appears in, and that
+/// cue's timestamp, plus count and frequency rank for sorting, and for
+/// scheduling the frequency band, last timestamp, number of cues it's in
+/// and suggested first interval in days.
+pub fn anki_notes(entries: &[VocabEntry], format: OutputFormat) -> String {
+    let (delimiter, separator_name) = match format {
+        OutputFormat::Tsv => ('\t', "tab"),
+        _ => (',', "comma"),
+    };
+    let delimiter_str = delimiter.to_string();
+
+    let mut out = format!("#separator:{}\n#html:false\n#columns:{}\n",
+        separator_name, ANKI_COLUMNS.join(&delimiter_str));
+
+    for entry in entries {
+        let row = [
+            escape_field(&entry.lemma, delimiter),
+            escape_field(&entry.forms.join(" "), delimiter),
+            escape_field(&entry.context, delimiter),
+            format_timestamp(entry.first_start, '.'),
+            entry.count.to_string(),
+            entry.rank.map(|rank| rank.to_string()).unwrap_or_default(),
+            entry.band.name().to_string(),
+            format_timestamp(entry.last_start, '.'),
+            entry.n_contexts.to_string(),
+            entry.initial_interval.to_string(),
+        ];
+        out.push_str(&row.join(&delimiter_str));
+        out.push('\n');
+    }
+
+    out
+}
+
+/// Quote CSV fields per RFC 4180; TSV has no quoting so separators become spaces
+fn escape_field(field: &str, delimiter: char) -> String {
+    if delimiter == '\t' {
+        return field.replace(['\t', '\n', '\r'], " ");
+    }
+
+    if field.contains([delimiter, '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::FrequencyBand;
+
+    fn timing(word: &str, start: f64, end: f64) -> WordTiming {
+        WordTiming {
+            word: word.to_string(),
+            start,
+            end,
+            confidence: 0.75,
+            char_start: 0,
+            char_end: word.len(),
+            flagged: false,
+        }
+    }
+
+    #[test]
+    fn test_csv_rows() {
+        let timings = vec![timing("Hello", 0.0, 1.0), timing("world", 1.0, 2.0)];
+        let csv = timings_table(&[(0, &timings)], OutputFormat::Csv);
+
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "word,start,end,confidence,cue_index");
+        assert_eq!(lines[1], "Hello,0,1,0.75,0");
+        assert_eq!(lines[2], "world,1,2,0.75,0");
+    }
+
+    #[test]
+    fn test_csv_quoting() {
+        let timings = vec![timing("a,\"b\"", 0.0, 1.0)];
+        let csv = timings_table(&[(3, &timings)], OutputFormat::Csv);
+        assert!(csv.contains("\"a,\"\"b\"\"\",0,1,0.75,3"));
+    }
+
+    #[test]
+    fn test_tsv() {
+        let timings = vec![timing("Hello", 0.5, 1.0)];
+        let tsv = timings_table(&[(1, &timings)], OutputFormat::Tsv);
+        assert_eq!(tsv.lines().nth(1), Some("Hello\t0.5\t1\t0.75\t1"));
+    }
+
+    #[test]
+    fn test_anki_notes() {
+        let entries = vec![VocabEntry {
+            lemma: "run".to_string(),
+            forms: vec!["Run".to_string(), "run".to_string()],
+            count: 2,
+            rank: None,
+            band: FrequencyBand::Unlisted,
+            first_start: 61.5,
+            first_cue_index: 1,
+            last_start: 75.0,
+            last_cue_index: 4,
+            n_contexts: 2,
+            initial_interval: 1,
+            context: "Run, Forrest!".to_string(),
+            known: None,
+        }];
+
+        let tsv = anki_notes(&entries, OutputFormat::Tsv);
+        let lines: Vec<&str> = tsv.lines().collect();
+        assert_eq!(lines[0], "#separator:tab");
+        assert_eq!(lines[3], "run\tRun run\tRun, Forrest!\t00:01:01.500\t2\t\tunlisted\t00:01:15.000\t2\t1");
+
+        let csv = anki_notes(&entries, OutputFormat::Csv);
+        assert!(csv.contains("run,Run run,\"Run, Forrest!\",00:01:01.500,2,,unlisted,00:01:15.000,2,1"));
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Behaviour that can be switched on or off while the service runs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Feature {
+    /// Ask the TTS engine for `mode=tts` word timings; when off, they're
+    /// predicted from the duration models
+    TtsEngine,
+}
+
+/// Current state of each feature, indexed by `Feature as usize`
+static ENABLED: [AtomicBool; 1] = [
+    AtomicBool::new(true),
+];
+
+impl Feature {
+    pub const ALL: [Feature; 1] = [Feature::TtsEngine];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::TtsEngine => "tts_engine",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self, String> {
+        Feature::ALL.into_iter().find(|feature| feature.name() == name.trim()).ok_or_else(|| {
+            let known: Vec<&str> = Feature::ALL.iter().map(|feature| feature.name()).collect();
+            format!("Unknown feature flag '{}', expected one of {}", name.trim(), known.join(", "))
+        })
+    }
+
+    pub fn is_enabled(self) -> bool {
+        ENABLED[self as usize].load(Ordering::Relaxed)
+    }
+
+    pub fn set(self, enabled: bool) {
+        ENABLED[self as usize].store(enabled, Ordering::Relaxed);
+    }
+}
+
+/// Parse `FEATURE_FLAGS`, e.g. `tts_engine=false`
+pub fn parse(flags: &str) -> Result<Vec<(Feature, bool)>, String> {
+    flags.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (name, enabled) = entry.split_once('=')
+                .ok_or_else(|| format!("Invalid feature flag '{}', expected name=true or name=false", entry))?;
+            let enabled = enabled.trim().parse()
+                .map_err(|_| format!("Invalid value for feature flag '{}', expected true or false", name.trim()))?;
+            Ok((Feature::parse(name)?, enabled))
+        })
+        .collect()
+}
+
+/// Apply the configured flags over the built-in defaults
+pub fn init(flags: Option<&str>) -> Result<(), String> {
+    for (feature, enabled) in parse(flags.unwrap_or_default())? {
+        feature.set(enabled);
+    }
+    log::info!("Feature flags: {}", snapshot().iter()
+        .map(|(name, enabled)| format!("{}={}", name, enabled))
+        .collect::<Vec<_>>()
+        .join(", "));
+    Ok(())
+}
+
+/// Every feature and whether it's on
+pub fn snapshot() -> BTreeMap<String, bool> {
+    Feature::ALL.iter().map(|feature| (feature.name().to_string(), feature.is_enabled())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let flags = parse(" tts_engine = false").unwrap();
+        assert_eq!(flags, vec![(Feature::TtsEngine, false)]);
+
+        assert!(parse("").unwrap().is_empty());
+        assert!(parse("tts_engine").is_err());
+        assert!(parse("tts_engine=yes").is_err());
+        assert!(parse("forced_alignment=true").unwrap_err().contains("tts_engine"));
+    }
+}
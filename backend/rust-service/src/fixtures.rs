@@ -0,0 +1,8 @@
+//! Test data shared across modules
+
+use crate::models::Cue;
+
+/// A cue with no style or actor
+pub fn cue(index: usize, start: f64, end: f64, text: &str) -> Cue {
+    Cue { index, start, end, text: text.to_string(), ..Default::default() }
+}
@@ -0,0 +1,563 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::models::{DataFile, FailedDataFile, ResidentData};
+use crate::reload::{self, Reloadable};
+use crate::resources::HeapSize;
+
+/// Lists loaded at startup, shared by every request until reloaded
+static LISTS: Reloadable<FrequencyLists> = Reloadable::new();
+
+/// Word frequency ranking for one language
+///
+/// On disk (`data/frequency/en.txt`) this is one word per line, most
+/// frequent first. Blank lines and `#` comments are ignored, and anything
+/// after a tab (e.g. a raw count) is dropped.
+#[derive(Debug, Clone, Default)]
+pub struct FrequencyList {
+    words: Vec<String>,
+    ranks: HashMap<String, usize>,
+}
+
+impl FrequencyList {
+    pub fn parse(content: &str) -> Self {
+        let mut list = FrequencyList::default();
+
+        for line in content.lines() {
+            let word = line.split('\t').next().unwrap_or("").trim();
+            if word.is_empty() || word.starts_with('#') {
+                continue;
+            }
+
+            let word = normalize(word);
+            if !list.ranks.contains_key(&word) {
+                list.ranks.insert(word.clone(), list.words.len() + 1);
+                list.words.push(word);
+            }
+        }
+
+        list
+    }
+
+    /// 1-based rank of a word (1 = most frequent), or None if not listed
+    pub fn rank(&self, word: &str) -> Option<usize> {
+        self.ranks.get(&normalize(word)).copied()
+    }
+
+    /// All words, most frequent first
+    pub fn words(&self) -> &[String] {
+        &self.words
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+}
+
+impl HeapSize for FrequencyList {
+    fn heap_size(&self) -> usize {
+        self.words.heap_size() + self.ranks.heap_size()
+    }
+}
+
+/// Lowercase and use a plain apostrophe so "Don’t" finds "don't"
+fn normalize(word: &str) -> String {
+    word.to_lowercase().replace('’', "'")
+}
+
+/// A set of words from a per-language list, e.g. stopwords
+///
+/// On disk this is one entry per line; blank lines and `#` comments are
+/// ignored. Abbreviations are stored without their final period, as the
+/// tokenizer leaves it out of the word.
+#[derive(Debug, Clone, Default)]
+pub struct WordSet {
+    words: HashSet<String>,
+}
+
+impl WordSet {
+    pub fn parse(content: &str) -> Self {
+        let words = entries(content)
+            .map(|(_, entry)| normalize(entry.trim_end_matches('.')))
+            .filter(|word| !word.is_empty())
+            .collect();
+        WordSet { words }
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        self.words.contains(&normalize(word.trim_end_matches('.')))
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+}
+
+impl HeapSize for WordSet {
+    fn heap_size(&self) -> usize {
+        self.words.heap_size()
+    }
+}
+
+/// What a file in the frequency directory holds, from its name
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ListKind {
+    /// `<language>.txt`
+    Frequency,
+    /// `<language>.stopwords.txt`: function words to leave out of exercises
+    Stopwords,
+    /// `<language>.abbreviations.txt`: words whose period doesn't end a sentence
+    Abbreviations,
+}
+
+impl ListKind {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "stopwords" => Some(ListKind::Stopwords),
+            "abbreviations" => Some(ListKind::Abbreviations),
+            _ => None,
+        }
+    }
+}
+
+/// Non-comment lines with their 1-based line numbers, trimmed
+fn entries(content: &str) -> impl Iterator<Item = (usize, &str)> {
+    content.lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+}
+
+/// Format problems in a list, one message per offending line
+///
+/// Problems are reported, not fatal: the lines are read as well as they
+/// can be, so a stray count or duplicate doesn't take a language offline.
+fn check(content: &str, kind: ListKind) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut previous_count: Option<u64> = None;
+
+    for (line_number, line) in entries(content) {
+        let (word, count) = match line.split_once('\t') {
+            Some((word, rest)) if kind == ListKind::Frequency => (word.trim(), rest.split('\t').next().map(str::trim)),
+            _ => (line, None),
+        };
+
+        if kind != ListKind::Abbreviations && word.contains(char::is_whitespace) {
+            problems.push(format!("line {}: '{}' has spaces; separate a count with a tab", line_number, word));
+        }
+        if let Some(&first) = seen.get(&normalize(word)) {
+            problems.push(format!("line {}: '{}' repeats line {}", line_number, word, first));
+        } else {
+            seen.insert(normalize(word), line_number);
+        }
+
+        match count.map(str::parse::<u64>) {
+            Some(Ok(count)) => {
+                if previous_count.is_some_and(|previous| count > previous) {
+                    problems.push(format!("line {}: count {} is higher than the line before; list the most frequent first", line_number, count));
+                }
+                previous_count = Some(count);
+            }
+            Some(Err(_)) => problems.push(format!("line {}: count '{}' isn't a whole number", line_number, count.unwrap_or_default())),
+            None => {}
+        }
+    }
+
+    problems
+}
+
+/// Problems logged per file; the rest are only counted
+const LOGGED_PROBLEMS: usize = 5;
+
+/// All loaded frequency lists, keyed by lowercase language code, with the
+/// stopword and abbreviation lists kept alongside them
+#[derive(Debug, Default)]
+pub struct FrequencyLists {
+    /// Shared, so evicting one language doesn't copy the others
+    lists: HashMap<String, Arc<FrequencyList>>,
+    stopwords: HashMap<String, Arc<WordSet>>,
+    abbreviations: HashMap<String, Arc<WordSet>>,
+    empty: FrequencyList,
+    empty_set: WordSet,
+    /// Lists loaded, by language
+    files: Vec<DataFile>,
+    /// Files that couldn't be read or had no entries
+    failed: Vec<FailedDataFile>,
+    /// Format problems found across all files
+    problems: usize,
+}
+
+impl FrequencyLists {
+    /// Load every `<language>.txt` frequency list in `dir`, and any
+    /// `<language>.stopwords.txt` and `<language>.abbreviations.txt`
+    ///
+    /// Each file's format is checked as it's read: problems are logged and
+    /// counted, and files without a single entry are skipped and listed as
+    /// failed.
+    pub fn load_dir(dir: &Path) -> Self {
+        Self::load(dir, |_| true)
+    }
+
+    /// `load_dir`, reading only the files of languages `wanted` accepts
+    /// (by lowercase code)
+    pub fn load(dir: &Path, wanted: impl Fn(&str) -> bool) -> Self {
+        let mut loaded = FrequencyLists::default();
+
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::warn!("No frequency lists loaded from {}: {}", dir.display(), e);
+                return loaded;
+            }
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("txt") {
+                continue;
+            }
+            let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            if !wanted(&stem.split('.').next().unwrap_or_default().to_lowercase()) {
+                continue;
+            }
+            let (language, kind) = match stem.split_once('.') {
+                None => (stem, ListKind::Frequency),
+                Some((language, name)) => match ListKind::parse(name) {
+                    Some(kind) => (language, kind),
+                    None => {
+                        let error = "expected <language>.txt, <language>.stopwords.txt or <language>.abbreviations.txt";
+                        log::warn!("Skipping {}: {}", path.display(), error);
+                        loaded.failed.push(FailedDataFile::new(language, &path, error));
+                        continue;
+                    }
+                },
+            };
+
+            let content = match fs::read_to_string(&path) {
+                Ok(content) => content,
+                Err(e) => {
+                    log::warn!("Skipping frequency list {}: {}", path.display(), e);
+                    loaded.failed.push(FailedDataFile::new(language, &path, e.to_string()));
+                    continue;
+                }
+            };
+
+            let problems = check(&content, kind);
+            for problem in problems.iter().take(LOGGED_PROBLEMS) {
+                log::warn!("{}: {}", path.display(), problem);
+            }
+            if problems.len() > LOGGED_PROBLEMS {
+                log::warn!("{}: {} more problems", path.display(), problems.len() - LOGGED_PROBLEMS);
+            }
+            loaded.problems += problems.len();
+
+            let language_key = language.to_lowercase();
+            let n_entries = match kind {
+                ListKind::Frequency => {
+                    let list = FrequencyList::parse(&content);
+                    let len = list.len();
+                    if len > 0 {
+                        loaded.lists.insert(language_key, Arc::new(list));
+                    }
+                    len
+                }
+                ListKind::Stopwords | ListKind::Abbreviations => {
+                    let set = WordSet::parse(&content);
+                    let len = set.len();
+                    if len > 0 {
+                        let sets = if kind == ListKind::Stopwords { &mut loaded.stopwords } else { &mut loaded.abbreviations };
+                        sets.insert(language_key, Arc::new(set));
+                    }
+                    len
+                }
+            };
+            if n_entries == 0 {
+                log::warn!("Skipping {}: no entries", path.display());
+                loaded.failed.push(FailedDataFile::new(language, &path, "no entries"));
+                continue;
+            }
+
+            log::info!("Loaded {} entries for '{}' from {}", n_entries, language, path.display());
+            loaded.files.push(DataFile::new(language, &path, &content, n_entries));
+        }
+
+        loaded.files.sort_by(|a, b| (&a.language, &a.file).cmp(&(&b.language, &b.file)));
+        loaded
+    }
+
+    /// Number of languages with a list
+    pub fn len(&self) -> usize {
+        self.lists.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lists.is_empty()
+    }
+
+    /// The files the lists came from
+    pub fn files(&self) -> &[DataFile] {
+        &self.files
+    }
+
+    /// The files that failed to load
+    pub fn failed(&self) -> &[FailedDataFile] {
+        &self.failed
+    }
+
+    /// Format problems found while loading
+    pub fn problems(&self) -> usize {
+        self.problems
+    }
+
+    /// These lists with every language that had a file fail to load back
+    /// on all of its lists from `previous`
+    pub fn keeping(mut self, previous: &Self) -> Self {
+        for language in reload::failed_languages(&self.failed) {
+            reload::restore(&mut self.lists, &previous.lists, &language);
+            reload::restore(&mut self.stopwords, &previous.stopwords, &language);
+            reload::restore(&mut self.abbreviations, &previous.abbreviations, &language);
+            reload::restore_files(&mut self.files, &previous.files, &language);
+        }
+        self
+    }
+
+    /// Languages with a frequency list, and with stopwords and abbreviations
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "{} languages, stopwords for {}, abbreviations for {}",
+            self.lists.len(), self.stopwords.len(), self.abbreviations.len()
+        );
+        if self.problems > 0 {
+            summary.push_str(&format!(", {} format problems (see logs)", self.problems));
+        }
+        summary
+    }
+
+    /// List for a language (`pt-BR` falls back to `pt`), or an empty list
+    /// (every word unranked)
+    pub fn get(&self, language: &str) -> &FrequencyList {
+        by_language(&self.lists, language).unwrap_or(&self.empty)
+    }
+
+    /// Stopwords for a language, or none
+    pub fn stopwords(&self, language: &str) -> &WordSet {
+        by_language(&self.stopwords, language).unwrap_or(&self.empty_set)
+    }
+
+    /// Abbreviations for a language, or none
+    pub fn abbreviations(&self, language: &str) -> &WordSet {
+        by_language(&self.abbreviations, language).unwrap_or(&self.empty_set)
+    }
+
+    /// Each language's lists with their sizes, for `/admin/languages`
+    pub fn resident(&self) -> Vec<(String, ResidentData)> {
+        let lists = self.lists.iter().map(|(language, list)| (language, "frequency_list", list.len(), list.heap_size()));
+        let stopwords = self.stopwords.iter().map(|(language, set)| (language, "stopwords", set.len(), set.heap_size()));
+        let abbreviations = self.abbreviations.iter().map(|(language, set)| (language, "abbreviations", set.len(), set.heap_size()));
+        lists.chain(stopwords).chain(abbreviations)
+            .map(|(language, name, entries, approx_bytes)| (language.clone(), ResidentData { name: name.to_string(), entries, approx_bytes }))
+            .collect()
+    }
+
+    /// These lists with `language`'s taken from `loaded`
+    pub fn with(&self, language: &str, loaded: &Self) -> Self {
+        let mut lists = self.without(language);
+        reload::restore(&mut lists.lists, &loaded.lists, language);
+        reload::restore(&mut lists.stopwords, &loaded.stopwords, language);
+        reload::restore(&mut lists.abbreviations, &loaded.abbreviations, language);
+        reload::restore_files(&mut lists.files, &loaded.files, language);
+        lists
+    }
+
+    /// These lists without `language`'s
+    pub fn without(&self, language: &str) -> Self {
+        FrequencyLists {
+            lists: others(&self.lists, language),
+            stopwords: others(&self.stopwords, language),
+            abbreviations: others(&self.abbreviations, language),
+            empty: FrequencyList::default(),
+            empty_set: WordSet::default(),
+            files: self.files.iter().filter(|file| file.language != language).cloned().collect(),
+            failed: self.failed.clone(),
+            problems: self.problems,
+        }
+    }
+}
+
+/// Every language's entry but `language`'s
+fn others<T>(map: &HashMap<String, Arc<T>>, language: &str) -> HashMap<String, Arc<T>> {
+    map.iter()
+        .filter(|(code, _)| *code != language)
+        .map(|(code, data)| (code.clone(), data.clone()))
+        .collect()
+}
+
+/// Exact match on the lowercase code, else its primary subtag
+fn by_language<'a, T>(map: &'a HashMap<String, Arc<T>>, language: &str) -> Option<&'a T> {
+    let language = language.to_lowercase();
+    map.get(&language)
+        .or_else(|| map.get(language.split(['-', '_']).next().unwrap_or_default()))
+        .map(Arc::as_ref)
+}
+
+/// Load lists at startup
+pub fn init(dir: &Path) {
+    if !LISTS.init(dir, FrequencyLists::load_dir) {
+        log::warn!("Frequency lists already initialised");
+    }
+}
+
+/// Load the lists of the languages `wanted` accepts again from the `init`
+/// directory (see `reload::reload`)
+pub fn reload(wanted: impl Fn(&str) -> bool) -> Option<Arc<FrequencyLists>> {
+    LISTS.reload(|dir, previous| FrequencyLists::load(dir, wanted).keeping(previous))
+}
+
+/// Load `language`'s lists back after an eviction (see `resources::restore`)
+pub fn restore(language: &str) -> bool {
+    LISTS.reload(|dir, current| current.with(language, &FrequencyLists::load(dir, |code| code == language))).is_some()
+}
+
+/// Drop `language`'s lists until they're next used (see `resources::evict`)
+pub fn evict(language: &str) -> bool {
+    let lists = LISTS.get();
+    let resident = lists.lists.contains_key(language)
+        || lists.stopwords.contains_key(language)
+        || lists.abbreviations.contains_key(language);
+    resident && LISTS.update(|lists| lists.without(language)).is_some()
+}
+
+/// Whether `init` has run
+pub fn is_loaded() -> bool {
+    LISTS.is_loaded()
+}
+
+/// Lists loaded by `init` or the latest reload, or no lists if it was never called
+pub fn lists() -> Arc<FrequencyLists> {
+    LISTS.get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ranks() {
+        let list = FrequencyList::parse("# comment\nthe\t1000\n\nYou\nthe\ndon't\n");
+
+        assert_eq!(list.rank("the"), Some(1));
+        assert_eq!(list.rank("you"), Some(2));
+        assert_eq!(list.rank("Don’t"), Some(3));
+        assert_eq!(list.rank("zebra"), None);
+        assert_eq!(list.len(), 3);
+    }
+
+    #[test]
+    fn test_bundled_lists_load() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("data/frequency");
+        let lists = FrequencyLists::load_dir(&dir);
+
+        assert_eq!(lists.get("en").rank("you"), Some(1));
+        assert!(lists.get("es").rank("gracias").is_some());
+        assert_eq!(lists.get("xx").len(), 0);
+        assert_eq!(lists.files().iter().find(|file| file.file == "en.txt").unwrap().entries, lists.get("en").len());
+        assert!(lists.stopwords("en-US").contains("The"));
+        assert!(lists.abbreviations("en").contains("Mr."));
+        assert_eq!(lists.problems(), 0);
+    }
+
+    #[test]
+    fn test_check_format() {
+        let problems = check("# comment\nthe\t900\nyou\t1000\nThe\nhello world\nit\tmany\n", ListKind::Frequency);
+        assert_eq!(problems.len(), 4);
+        assert!(problems[0].starts_with("line 3: count 1000"));
+        assert!(problems[1].starts_with("line 4: 'The' repeats line 2"));
+        assert!(problems[2].starts_with("line 5"));
+        assert!(problems[3].starts_with("line 6: count 'many'"));
+
+        assert!(check("e.g.\nMr.\n", ListKind::Abbreviations).is_empty());
+    }
+
+    #[test]
+    fn test_load_dir_kinds() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("de.txt"), "der\ndie\n").unwrap();
+        fs::write(dir.path().join("de.stopwords.txt"), "der\nder\n").unwrap();
+        fs::write(dir.path().join("de.abbreviations.txt"), "# none yet\n").unwrap();
+        fs::write(dir.path().join("de.unknown.txt"), "x\n").unwrap();
+
+        let lists = FrequencyLists::load_dir(dir.path());
+        assert_eq!(lists.len(), 1);
+        assert_eq!(lists.get("de-AT").rank("die"), Some(2));
+        assert_eq!(lists.stopwords("de").len(), 1);
+        assert!(lists.abbreviations("de").is_empty());
+        assert_eq!(lists.files().len(), 2);
+        assert_eq!(lists.failed().iter().map(|file| file.file.as_str()).collect::<HashSet<_>>(), HashSet::from(["de.abbreviations.txt", "de.unknown.txt"]));
+        assert_eq!(lists.problems(), 1);
+
+        let mut resident: Vec<(String, String)> = lists.resident().into_iter().map(|(language, data)| (language, data.name)).collect();
+        resident.sort();
+        assert_eq!(resident, vec![("de".to_string(), "frequency_list".to_string()), ("de".to_string(), "stopwords".to_string())]);
+
+        let trimmed = lists.without("de");
+        assert!(trimmed.is_empty());
+        assert!(trimmed.stopwords("de").is_empty());
+        assert!(trimmed.files().is_empty());
+    }
+
+    #[test]
+    fn test_keeping_previous_data_for_failed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("de.txt"), "der
+die
+").unwrap();
+        fs::write(dir.path().join("fr.txt"), "le
+la
+").unwrap();
+        let previous = FrequencyLists::load_dir(dir.path());
+
+        fs::write(dir.path().join("de.txt"), "# emptied by mistake
+").unwrap();
+        fs::write(dir.path().join("fr.txt"), "la
+le
+").unwrap();
+        let lists = FrequencyLists::load_dir(dir.path()).keeping(&previous);
+
+        assert_eq!(lists.get("de").rank("die"), Some(2));
+        assert_eq!(lists.get("fr").rank("la"), Some(1));
+        assert_eq!(lists.files().len(), 2);
+        assert_eq!(lists.failed().len(), 1);
+        assert_eq!((lists.failed()[0].language.as_str(), lists.failed()[0].file.as_str()), ("de", "de.txt"));
+    }
+
+    #[test]
+    fn test_evicted_language_loads_back_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("de.txt"), "der\ndie\n").unwrap();
+        fs::write(dir.path().join("de.stopwords.txt"), "der\n").unwrap();
+        fs::write(dir.path().join("fr.txt"), "le\nla\n").unwrap();
+
+        let kept = FrequencyLists::load(dir.path(), |language| language != "de");
+        assert_eq!(kept.len(), 1);
+        assert!(kept.files().iter().all(|file| file.language == "fr"));
+
+        let restored = kept.with("de", &FrequencyLists::load(dir.path(), |language| language == "de"));
+        assert_eq!(restored.get("de").rank("die"), Some(2));
+        assert!(restored.stopwords("de").contains("der"));
+        assert_eq!(restored.get("fr").rank("la"), Some(2));
+        assert_eq!(restored.files().len(), 3);
+    }
+}
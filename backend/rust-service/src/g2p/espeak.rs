@@ -0,0 +1,276 @@
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use super::{primary_subtag, Backend};
+use crate::models::PronunciationSource;
+
+/// espeak-ng processes running at once, across requests
+const MAX_PROCESSES: usize = 4;
+
+/// How long one run may take before it's killed
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often a running process is checked on
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// The espeak-ng command line, for languages without a lexicon or rules
+///
+/// Its voices are listed once at startup. The words of a request that the
+/// in-process backends don't know then go to one run of `espeak-ng -q
+/// --ipa`, one word per sentence on its stdin. At most `MAX_PROCESSES` run
+/// at once, each for up to `TIMEOUT`.
+#[derive(Debug, Clone)]
+pub struct Espeak {
+    program: PathBuf,
+    /// Lowercase language code → the code to pass to `-v`
+    voices: BTreeMap<String, String>,
+    slots: Arc<Slots>,
+    timeout: Duration,
+}
+
+/// A blocking counting semaphore; the backends are called from blocking code
+#[derive(Debug)]
+struct Slots {
+    free: Mutex<usize>,
+    freed: Condvar,
+}
+
+impl Slots {
+    fn new(count: usize) -> Self {
+        Slots { free: Mutex::new(count), freed: Condvar::new() }
+    }
+
+    /// Wait for a free slot, taken until the guard is dropped
+    fn take(&self) -> Slot<'_> {
+        let mut free = self.freed.wait_while(self.free.lock().unwrap(), |free| *free == 0).unwrap();
+        *free -= 1;
+        Slot(self)
+    }
+}
+
+struct Slot<'a>(&'a Slots);
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        *self.0.free.lock().unwrap() += 1;
+        self.0.freed.notify_one();
+    }
+}
+
+impl Espeak {
+    /// Ask `program` which languages it has voices for
+    pub fn discover(program: &Path) -> Result<Self, String> {
+        let output = Command::new(program).arg("--voices").output()
+            .map_err(|e| format!("Can't run {}: {}", program.display(), e))?;
+        if !output.status.success() {
+            return Err(format!("{} --voices failed: {}", program.display(), String::from_utf8_lossy(&output.stderr).trim()));
+        }
+
+        let voices = parse_voices(&String::from_utf8_lossy(&output.stdout));
+        if voices.is_empty() {
+            return Err(format!("{} lists no voices", program.display()));
+        }
+        Ok(Espeak::new(program, voices))
+    }
+
+    fn new(program: &Path, voices: BTreeMap<String, String>) -> Self {
+        Espeak { program: program.to_path_buf(), voices, slots: Arc::new(Slots::new(MAX_PROCESSES)), timeout: TIMEOUT }
+    }
+
+    /// Voice for a language: an exact match, else `pt` for `pt-BR`, else the
+    /// first regional voice (`en-gb-x-rp`... for `en`)
+    fn voice(&self, language: &str) -> Option<&str> {
+        let language = language.to_lowercase();
+        let primary = primary_subtag(&language);
+        self.voices.get(&language)
+            .or_else(|| self.voices.get(primary))
+            .or_else(|| self.voices.iter().find(|(code, _)| primary_subtag(code) == primary).map(|(_, voice)| voice))
+            .map(String::as_str)
+    }
+
+    /// One run of espeak-ng over `words`, its stdout split into lines
+    fn run(&self, words: &[&str], voice: &str) -> Result<Vec<String>, String> {
+        let _slot = self.slots.take();
+        let mut child = Command::new(&self.program)
+            .args(["-q", "--ipa", "-v", voice])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Can't run {}: {}", self.program.display(), e))?;
+
+        // A sentence each, so every word comes back on its own line. Pipes
+        // are fed and drained on their own threads so a full one can't
+        // stall the process.
+        let input: String = words.iter().map(|word| format!("{}.\n", word)).collect();
+        let mut stdin = child.stdin.take().expect("piped stdin");
+        std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+        let stdout = drain(child.stdout.take().expect("piped stdout"));
+        let stderr = drain(child.stderr.take().expect("piped stderr"));
+
+        let deadline = Instant::now() + self.timeout;
+        let status = loop {
+            if let Some(status) = child.try_wait().map_err(|e| e.to_string())? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("espeak-ng took over {:?}, killed it", self.timeout));
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        };
+
+        if !status.success() {
+            return Err(format!("espeak-ng failed: {}", stderr.join().unwrap_or_default().trim()));
+        }
+        Ok(stdout.join().unwrap_or_default().lines()
+            .map(strip_language_switches)
+            .filter(|ipa| !ipa.is_empty())
+            .collect())
+    }
+}
+
+/// Read `pipe` to the end on another thread
+fn drain(mut pipe: impl Read + Send + 'static) -> std::thread::JoinHandle<String> {
+    std::thread::spawn(move || {
+        let mut bytes = Vec::new();
+        let _ = pipe.read_to_end(&mut bytes);
+        String::from_utf8_lossy(&bytes).into_owned()
+    })
+}
+
+/// Language codes from `espeak-ng --voices`: a header line, then one voice
+/// per line with the language in the second column
+fn parse_voices(output: &str) -> BTreeMap<String, String> {
+    output.lines()
+        .skip(1)
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .map(|language| (language.to_lowercase(), language.to_string()))
+        .collect()
+}
+
+/// Drop the `(en)` markers espeak-ng adds when it switches language mid-word
+fn strip_language_switches(ipa: &str) -> String {
+    let mut stripped = String::new();
+    let mut depth = 0;
+    for c in ipa.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = (depth - 1).max(0),
+            c if depth == 0 && !c.is_whitespace() => stripped.push(c),
+            _ => {}
+        }
+    }
+    stripped
+}
+
+impl Backend for Espeak {
+    fn source(&self) -> PronunciationSource {
+        PronunciationSource::Espeak
+    }
+
+    fn languages(&self) -> Vec<String> {
+        self.voices.keys().cloned().collect()
+    }
+
+    fn supports(&self, language: &str) -> bool {
+        self.voice(language).is_some()
+    }
+
+    fn phonemize(&self, word: &str, language: &str) -> Option<String> {
+        self.phonemize_all(&[word], language).pop().flatten()
+    }
+
+    /// Every word in one run; if the output doesn't come back one line per
+    /// word, nothing can be matched up and no word gets a pronunciation
+    fn phonemize_all(&self, words: &[&str], language: &str) -> Vec<Option<String>> {
+        let unknown = || vec![None; words.len()];
+        let Some(voice) = self.voice(language) else {
+            return unknown();
+        };
+        // A word spanning lines would throw out the count
+        if words.is_empty() || words.iter().any(|word| word.contains(['\n', '\r'])) {
+            return unknown();
+        }
+
+        match self.run(words, voice) {
+            Ok(lines) if lines.len() == words.len() => lines.into_iter().map(Some).collect(),
+            Ok(lines) => {
+                log::debug!("espeak-ng ({}) answered {} words with {} lines", voice, words.len(), lines.len());
+                unknown()
+            }
+            Err(e) => {
+                log::warn!("{}", e);
+                unknown()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_voices() {
+        let output = "Pty Language       Age/Gender VoiceName          File                 Other Languages\n \
+            5  af              --/M      Afrikaans          gmw/af\n \
+            2  en-US           --/M      English_(America)  gmw/en-US            (en 3)\n";
+        let voices = parse_voices(output);
+        assert_eq!(voices.len(), 2);
+        assert_eq!(voices.get("en-us").map(String::as_str), Some("en-US"));
+
+        let espeak = Espeak::new(Path::new("espeak-ng"), voices);
+        assert_eq!(espeak.voice("EN"), Some("en-US"));
+        assert_eq!(espeak.voice("af-ZA"), Some("af"));
+        assert_eq!(espeak.voice("de"), None);
+    }
+
+    /// A stand-in for espeak-ng running `script`
+    fn fake(dir: &Path, script: &str) -> Espeak {
+        use std::os::unix::fs::PermissionsExt;
+
+        let program = dir.join("espeak-ng");
+        std::fs::write(&program, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755)).unwrap();
+        Espeak::new(&program, BTreeMap::from([("en".to_string(), "en".to_string())]))
+    }
+
+    #[test]
+    fn test_words_share_one_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let counter = dir.path().join("runs");
+        let script = format!("echo run >> {}\nwhile read -r line; do echo \" (en)ipa-${{line%.}}\"; done", counter.display());
+        let espeak = fake(dir.path(), &script);
+
+        let ipa = espeak.phonemize_all(&["hello", "world", "again"], "en");
+        assert_eq!(ipa, vec![Some("ipa-hello".to_string()), Some("ipa-world".to_string()), Some("ipa-again".to_string())]);
+        assert_eq!(std::fs::read_to_string(&counter).unwrap().lines().count(), 1);
+        assert_eq!(espeak.phonemize("hello", "en").as_deref(), Some("ipa-hello"));
+        assert_eq!(espeak.phonemize_all(&["hello"], "de"), vec![None]);
+
+        // Output that doesn't line up with the words is thrown away
+        let espeak = fake(dir.path(), "cat > /dev/null; echo one; echo two; echo three");
+        assert_eq!(espeak.phonemize_all(&["hello", "world"], "en"), vec![None, None]);
+    }
+
+    #[test]
+    fn test_slow_runs_are_killed() {
+        let dir = tempfile::tempdir().unwrap();
+        let espeak = Espeak { timeout: Duration::from_millis(100), ..fake(dir.path(), "sleep 5") };
+        let started = Instant::now();
+        assert_eq!(espeak.phonemize("hello", "en"), None);
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_missing_program() {
+        assert!(Espeak::discover(Path::new("/nonexistent/espeak-ng")).is_err());
+        assert_eq!(strip_language_switches("(en)wˈiːkˌɛnd(fr) \n"), "wˈiːkˌɛnd");
+    }
+}
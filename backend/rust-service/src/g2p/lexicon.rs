@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use super::{primary_subtag, Backend};
+use crate::models::{DataFile, PronunciationSource};
+
+/// Word pronunciations for one language
+///
+/// Two layouts are read from disk:
+/// - ipa-dict (`data/pronunciation/en.tsv`): a word, a tab, then one or more
+///   `/slash-delimited/` IPA transcriptions separated by commas. The first
+///   transcription is used; slashes are optional.
+/// - CMUdict (`data/pronunciation/en.dict`): a word and its ARPAbet phones,
+///   converted to IPA. Alternates (`word(2)`) and `;;;` comments are skipped.
+#[derive(Debug, Clone, Default)]
+pub struct Lexicon {
+    pronunciations: HashMap<String, String>,
+}
+
+impl Lexicon {
+    pub fn parse(content: &str) -> Self {
+        let mut lexicon = Lexicon::default();
+
+        for line in content.lines() {
+            let Some((word, ipa)) = line.split_once('\t') else {
+                continue;
+            };
+            let ipa = ipa.split(',').next().unwrap_or_default().trim().trim_matches('/');
+            if word.trim().is_empty() || word.starts_with('#') || ipa.is_empty() {
+                continue;
+            }
+            lexicon.pronunciations.entry(normalize(word)).or_insert_with(|| ipa.to_string());
+        }
+
+        lexicon
+    }
+
+    /// Parse a CMUdict file, skipping lines with phones it doesn't know
+    pub fn parse_cmudict(content: &str) -> Self {
+        let mut lexicon = Lexicon::default();
+
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut fields = line.split_whitespace();
+            let Some(word) = fields.next().filter(|word| !word.starts_with(";;;") && !word.ends_with(')')) else {
+                continue;
+            };
+            let Some(ipa) = fields.map(arpabet_to_ipa).collect::<Option<String>>().filter(|ipa| !ipa.is_empty()) else {
+                continue;
+            };
+            lexicon.pronunciations.entry(normalize(word)).or_insert(ipa);
+        }
+
+        lexicon
+    }
+
+    pub fn get(&self, word: &str) -> Option<&str> {
+        self.pronunciations.get(&normalize(word)).map(String::as_str)
+    }
+
+    /// Add `other`'s words, keeping this lexicon's pronunciation of any in both
+    pub fn merge(&mut self, other: Lexicon) {
+        for (word, ipa) in other.pronunciations {
+            self.pronunciations.entry(word).or_insert(ipa);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pronunciations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pronunciations.is_empty()
+    }
+}
+
+/// Lowercase and use a plain apostrophe so "Don’t" finds "don't"
+fn normalize(word: &str) -> String {
+    word.to_lowercase().replace('’', "'")
+}
+
+/// One ARPAbet phone as IPA, with the stress mark before a stressed vowel
+fn arpabet_to_ipa(phone: &str) -> Option<String> {
+    let (base, stress) = match phone.strip_suffix(['0', '1', '2']) {
+        Some(base) => (base, phone.chars().last()),
+        None => (phone, None),
+    };
+    let ipa = match (base.to_uppercase().as_str(), stress) {
+        ("AH", Some('0')) => "ə",
+        ("ER", Some('0')) => "ɚ",
+        ("AA", _) => "ɑ",
+        ("AE", _) => "æ",
+        ("AH", _) => "ʌ",
+        ("AO", _) => "ɔ",
+        ("AW", _) => "aʊ",
+        ("AY", _) => "aɪ",
+        ("EH", _) => "ɛ",
+        ("ER", _) => "ɝ",
+        ("EY", _) => "eɪ",
+        ("IH", _) => "ɪ",
+        ("IY", _) => "i",
+        ("OW", _) => "oʊ",
+        ("OY", _) => "ɔɪ",
+        ("UH", _) => "ʊ",
+        ("UW", _) => "u",
+        ("B", _) => "b",
+        ("CH", _) => "tʃ",
+        ("D", _) => "d",
+        ("DH", _) => "ð",
+        ("F", _) => "f",
+        ("G", _) => "ɡ",
+        ("HH", _) => "h",
+        ("JH", _) => "dʒ",
+        ("K", _) => "k",
+        ("L", _) => "l",
+        ("M", _) => "m",
+        ("N", _) => "n",
+        ("NG", _) => "ŋ",
+        ("P", _) => "p",
+        ("R", _) => "ɹ",
+        ("S", _) => "s",
+        ("SH", _) => "ʃ",
+        ("T", _) => "t",
+        ("TH", _) => "θ",
+        ("V", _) => "v",
+        ("W", _) => "w",
+        ("Y", _) => "j",
+        ("Z", _) => "z",
+        ("ZH", _) => "ʒ",
+        _ => return None,
+    };
+    let mark = match stress {
+        Some('1') => "ˈ",
+        Some('2') => "ˌ",
+        _ => "",
+    };
+    Some(format!("{}{}", mark, ipa))
+}
+
+/// All loaded lexicons, keyed by lowercase language code
+#[derive(Debug, Default)]
+pub struct Lexicons {
+    lexicons: HashMap<String, Lexicon>,
+    /// Files loaded, by language
+    files: Vec<DataFile>,
+}
+
+impl Lexicons {
+    /// Load every `<language>.tsv` and `<language>.dict` lexicon in `dir`;
+    /// a language with both gets the words of both
+    pub fn load_dir(dir: &Path) -> Self {
+        let mut lexicons: HashMap<String, Lexicon> = HashMap::new();
+        let mut files = Vec::new();
+
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::info!("No pronunciation lexicons loaded from {}: {}", dir.display(), e);
+                return Lexicons::default();
+            }
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let parse = match path.extension().and_then(|ext| ext.to_str()) {
+                Some("tsv") => Lexicon::parse,
+                Some("dict") => Lexicon::parse_cmudict,
+                _ => continue,
+            };
+            let Some(language) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+
+            match fs::read_to_string(&path) {
+                Ok(content) => {
+                    let lexicon = parse(&content);
+                    log::info!("Loaded {} pronunciations for '{}' from {}", lexicon.len(), language, path.display());
+                    files.push(DataFile::new(language, &path, &content, lexicon.len()));
+                    lexicons.entry(language.to_lowercase()).or_default().merge(lexicon);
+                }
+                Err(e) => log::warn!("Skipping pronunciation lexicon {}: {}", path.display(), e),
+            }
+        }
+
+        files.sort_by(|a, b| a.language.cmp(&b.language));
+        Lexicons { lexicons, files }
+    }
+
+    /// Number of languages with a lexicon
+    pub fn len(&self) -> usize {
+        self.lexicons.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lexicons.is_empty()
+    }
+
+    /// The files the lexicons came from
+    pub fn files(&self) -> &[DataFile] {
+        &self.files
+    }
+
+    /// Lexicon for a language (`pt-BR` falls back to `pt`), if one was loaded
+    pub fn get(&self, language: &str) -> Option<&Lexicon> {
+        let language = language.to_lowercase();
+        self.lexicons.get(&language).or_else(|| self.lexicons.get(primary_subtag(&language)))
+    }
+}
+
+impl Backend for Lexicons {
+    fn source(&self) -> PronunciationSource {
+        PronunciationSource::Lexicon
+    }
+
+    fn languages(&self) -> Vec<String> {
+        self.lexicons.keys().cloned().collect()
+    }
+
+    fn supports(&self, language: &str) -> bool {
+        self.get(language).is_some()
+    }
+
+    fn phonemize(&self, word: &str, language: &str) -> Option<String> {
+        self.get(language)?.get(word).map(str::to_string)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lexicon_takes_first_transcription() {
+        let lexicon = Lexicon::parse("# comment\nread\t/ɹiːd/, /ɹɛd/\nDon't\tdoʊnt\nbroken line\n");
+        assert_eq!(lexicon.get("READ"), Some("ɹiːd"));
+        assert_eq!(lexicon.get("don’t"), Some("doʊnt"));
+        assert_eq!(lexicon.len(), 2);
+    }
+
+    #[test]
+    fn test_cmudict() {
+        let lexicon = Lexicon::parse_cmudict(";;; comment\nHELLO  HH AH0 L OW1\nhello(2) HH EH0 L OW1\nthinking TH IH1 NG K IH0 NG # comment\nbad XX\n");
+        assert_eq!(lexicon.get("hello"), Some("həlˈoʊ"));
+        assert_eq!(lexicon.get("Thinking"), Some("θˈɪŋkɪŋ"));
+        assert_eq!(lexicon.len(), 2);
+    }
+}
@@ -0,0 +1,225 @@
+mod espeak;
+mod lexicon;
+mod rules;
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::OnceLock;
+
+pub use espeak::Espeak;
+pub use lexicon::{Lexicon, Lexicons};
+pub use rules::Rules;
+
+use crate::models::{DataFile, G2pLanguage, PronunciationSource};
+use crate::stages::{self, Stage};
+
+/// Backends set up at startup, shared by every request
+static G2P: OnceLock<G2p> = OnceLock::new();
+
+/// One way of turning a written word into broad IPA
+///
+/// Implementations must be cheap to ask `supports` (it's checked per
+/// request); `phonemize` may be slow.
+pub trait Backend: Send + Sync {
+    /// What pronunciations from this backend are reported as
+    fn source(&self) -> PronunciationSource;
+
+    /// Languages this backend can transcribe, as configured
+    fn languages(&self) -> Vec<String>;
+
+    fn supports(&self, language: &str) -> bool;
+
+    /// IPA for `word`, or `None` if this backend doesn't know it
+    fn phonemize(&self, word: &str, language: &str) -> Option<String>;
+
+    /// `phonemize` for each of `words`, in order; backends with a cost per
+    /// call answer them all at once
+    fn phonemize_all(&self, words: &[&str], language: &str) -> Vec<Option<String>> {
+        words.iter().map(|word| self.phonemize(word, language)).collect()
+    }
+}
+
+/// Backends in the order they're asked, most trusted first: lexicons,
+/// then espeak-ng if configured, then spelling rules
+pub struct G2p {
+    backends: Vec<Box<dyn Backend>>,
+    /// Lexicon files loaded, for deep health
+    files: Vec<DataFile>,
+}
+
+impl Default for G2p {
+    fn default() -> Self {
+        G2p::new(Lexicons::default(), None)
+    }
+}
+
+impl G2p {
+    pub fn new(lexicons: Lexicons, espeak: Option<Espeak>) -> Self {
+        let files = lexicons.files().to_vec();
+        let mut backends: Vec<Box<dyn Backend>> = vec![Box::new(lexicons)];
+        if let Some(espeak) = espeak {
+            backends.push(Box::new(espeak));
+        }
+        backends.push(Box::new(Rules));
+        G2p { backends, files }
+    }
+
+    /// Whether any backend can transcribe `language`
+    pub fn supports(&self, language: &str) -> bool {
+        self.backends.iter().any(|backend| backend.supports(language))
+    }
+
+    /// The first backend's pronunciation of `word`
+    pub fn phonemize(&self, word: &str, language: &str) -> Option<(String, PronunciationSource)> {
+        self.backends.iter()
+            .filter(|backend| backend.supports(language))
+            .find_map(|backend| backend.phonemize(word, language).map(|ipa| (ipa, backend.source())))
+    }
+
+    /// `phonemize` for each of `words`, in order, asking each backend once
+    /// about the words the ones before it didn't know
+    pub fn phonemize_all(&self, words: &[&str], language: &str) -> Vec<Option<(String, PronunciationSource)>> {
+        let mut pronunciations = vec![None; words.len()];
+        for backend in self.backends.iter().filter(|backend| backend.supports(language)) {
+            let unknown: Vec<usize> = (0..words.len()).filter(|&i| pronunciations[i].is_none()).collect();
+            if unknown.is_empty() {
+                break;
+            }
+            let asked: Vec<&str> = unknown.iter().map(|&i| words[i]).collect();
+            for (i, ipa) in unknown.into_iter().zip(backend.phonemize_all(&asked, language)) {
+                pronunciations[i] = ipa.map(|ipa| (ipa, backend.source()));
+            }
+        }
+        pronunciations
+    }
+
+    /// Every language some backend covers, with the backends in the order
+    /// they're asked
+    pub fn availability(&self) -> Vec<G2pLanguage> {
+        let mut languages: BTreeMap<String, Vec<PronunciationSource>> = BTreeMap::new();
+        for backend in &self.backends {
+            for language in backend.languages() {
+                languages.entry(language).or_default().push(backend.source());
+            }
+        }
+        languages.into_iter().map(|(language, sources)| G2pLanguage { language, sources }).collect()
+    }
+
+    /// The lexicon files loaded
+    pub fn files(&self) -> &[DataFile] {
+        &self.files
+    }
+
+    /// Languages per backend, e.g. "lexicon: 2 languages, rules: 1 language"
+    pub fn summary(&self) -> String {
+        self.backends.iter()
+            .map(|backend| {
+                let count = backend.languages().len();
+                format!("{}: {} language{}", backend.source().name(), count, if count == 1 { "" } else { "s" })
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+fn primary_subtag(language: &str) -> &str {
+    language.split(['-', '_']).next().unwrap_or(language)
+}
+
+/// Load lexicons and find espeak-ng once at startup
+///
+/// An `espeak` binary that can't be run is logged and left out rather than
+/// stopping the service.
+pub fn init(pronunciation_dir: &Path, espeak: Option<&Path>) {
+    let espeak = espeak.and_then(|program| match Espeak::discover(program) {
+        Ok(espeak) => {
+            log::info!("espeak-ng G2P: {} voices from {}", espeak.languages().len(), program.display());
+            Some(espeak)
+        }
+        Err(e) => {
+            log::warn!("espeak-ng G2P disabled: {}", e);
+            None
+        }
+    });
+
+    if G2P.set(G2p::new(Lexicons::load_dir(pronunciation_dir), espeak)).is_err() {
+        log::warn!("G2P backends already initialised");
+    }
+}
+
+/// Backends set up by `init`, or the built-in ones if it was never called
+pub fn g2p() -> &'static G2p {
+    G2P.get_or_init(G2p::default)
+}
+
+/// Whether `language` can be phonemized at all
+pub fn supports(language: &str) -> bool {
+    g2p().supports(language)
+}
+
+/// Broad IPA for one word from the first backend that knows it
+pub fn phonemize(word: &str, language: &str) -> Option<(String, PronunciationSource)> {
+    stages::time(Stage::G2p, || g2p().phonemize(word, language))
+}
+
+/// Broad IPA for each of `words`, from the first backend that knows it
+pub fn phonemize_all(words: &[&str], language: &str) -> Vec<Option<(String, PronunciationSource)>> {
+    stages::time(Stage::G2p, || g2p().phonemize_all(words, language))
+}
+
+/// IPA symbols with a different X-SAMPA spelling; anything else is kept
+const XSAMPA: &[(char, &str)] = &[
+    ('ɑ', "A"), ('æ', "{"), ('ɐ', "6"), ('ɒ', "Q"), ('ɔ', "O"), ('ə', "@"), ('ɚ', "@`"), ('ɛ', "E"),
+    ('ɜ', "3"), ('ɝ', "3`"), ('ɪ', "I"), ('ʊ', "U"), ('ʌ', "V"), ('ø', "2"), ('œ', "9"), ('ɨ', "1"), ('ʉ', "}"),
+    ('ɯ', "M"), ('ɤ', "7"), ('ʏ', "Y"),
+    ('β', "B"), ('ç', "C"), ('ð', "D"), ('ɣ', "G"), ('ɦ', "h\\"), ('ʝ', "j\\"), ('ɲ', "J"), ('ŋ', "N"),
+    ('ɴ', "N\\"), ('ɹ', "r\\"), ('ɾ', "4"), ('ʁ', "R"), ('ʃ', "S"), ('θ', "T"), ('ʒ', "Z"), ('ʔ', "?"),
+    ('ɡ', "g"), ('ɫ', "5"), ('ʎ', "L"), ('ɬ', "K"), ('χ', "X"), ('ħ', "X\\"), ('ʕ', "?\\"), ('ɱ', "F"),
+    ('ɳ', "n`"), ('ɽ', "r`"), ('ʂ', "s`"), ('ʐ', "z`"), ('ʈ', "t`"), ('ɖ', "d`"), ('ɕ', "s\\"), ('ʑ', "z\\"),
+    ('ɟ', "J\\"), ('ɥ', "H"), ('ɰ', "M\\"), ('ʋ', "v\\"), ('ɸ', "p\\"), ('ɻ', "r\\`"),
+    ('ʧ', "tS"), ('ʤ', "dZ"),
+    ('ˈ', "\""), ('ˌ', "%"), ('ː', ":"), ('ˑ', ":\\"), ('\u{303}', "~"), ('ʰ', "_h"), ('ʲ', "'"), ('ʷ', "_w"),
+    ('\u{329}', "="), ('\u{361}', ""), ('\u{35c}', ""),
+];
+
+/// The same transcription in X-SAMPA, for systems that only take ASCII
+pub fn to_xsampa(ipa: &str) -> String {
+    ipa.chars()
+        .map(|c| XSAMPA.iter().find(|(symbol, _)| *symbol == c).map_or_else(|| c.to_string(), |(_, xsampa)| xsampa.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backends_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("es.tsv"), "casa\tˈkasa\n").unwrap();
+        let g2p = G2p::new(Lexicons::load_dir(dir.path()), None);
+
+        assert_eq!(g2p.phonemize("casa", "es"), Some(("ˈkasa".to_string(), PronunciationSource::Lexicon)));
+        assert_eq!(g2p.phonemize("gato", "es"), Some(("ɡato".to_string(), PronunciationSource::Rules)));
+        assert_eq!(g2p.phonemize_all(&["gato", "casa"], "es"), vec![g2p.phonemize("gato", "es"), g2p.phonemize("casa", "es")]);
+        assert_eq!(g2p.availability(), vec![G2pLanguage {
+            language: "es".to_string(),
+            sources: vec![PronunciationSource::Lexicon, PronunciationSource::Rules],
+        }]);
+        assert_eq!(g2p.summary(), "lexicon: 1 language, rules: 1 language");
+    }
+
+    #[test]
+    fn test_unsupported_without_lexicon() {
+        assert_eq!(phonemize("gato", "es-MX"), Some(("ɡato".to_string(), PronunciationSource::Rules)));
+        assert!(phonemize("cat", "xx").is_none());
+        assert!(!supports("xx"));
+    }
+
+    #[test]
+    fn test_xsampa() {
+        assert_eq!(to_xsampa("ˈθɪŋk"), "\"TINk");
+        assert_eq!(to_xsampa("tʃiko"), "tSiko");
+        assert_eq!(to_xsampa("ʝama"), "j\\ama");
+    }
+}
@@ -0,0 +1,110 @@
+use super::{primary_subtag, Backend};
+use crate::models::PronunciationSource;
+
+/// Spelling rules for languages spelled regularly enough to transcribe
+/// without a lexicon; the fallback when no other backend knows a word
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Rules;
+
+impl Rules {
+    fn rules_for(language: &str) -> Option<fn(&str) -> String> {
+        match primary_subtag(&language.to_lowercase()) {
+            "es" | "spanish" => Some(spanish),
+            _ => None,
+        }
+    }
+}
+
+impl Backend for Rules {
+    fn source(&self) -> PronunciationSource {
+        PronunciationSource::Rules
+    }
+
+    fn languages(&self) -> Vec<String> {
+        vec!["es".to_string()]
+    }
+
+    fn supports(&self, language: &str) -> bool {
+        Rules::rules_for(language).is_some()
+    }
+
+    fn phonemize(&self, word: &str, language: &str) -> Option<String> {
+        Rules::rules_for(language).map(|rules| rules(word))
+    }
+}
+
+/// Spanish spelling to broad, unstressed IPA
+///
+/// Latin American pronunciation: `c`/`z` before front vowels are /s/
+/// (seseo) and `ll` merges with `y` (yeísmo).
+fn spanish(word: &str) -> String {
+    let letters: Vec<char> = word.to_lowercase().chars()
+        .map(|c| match c {
+            'á' => 'a',
+            'é' => 'e',
+            'í' => 'i',
+            'ó' => 'o',
+            'ú' => 'u',
+            c => c,
+        })
+        .collect();
+    let at = |i: usize| letters.get(i).copied();
+    let front = |c: Option<char>| matches!(c, Some('e' | 'i'));
+    let vowel = |c: Option<char>| matches!(c, Some('a' | 'e' | 'i' | 'o' | 'u'));
+
+    let mut ipa = String::new();
+    let mut i = 0;
+    while i < letters.len() {
+        let (sound, consumed) = match (letters[i], at(i + 1)) {
+            ('c', Some('h')) => ("tʃ", 2),
+            ('l', Some('l')) => ("ʝ", 2),
+            ('r', Some('r')) => ("r", 2),
+            ('q', Some('u')) => ("k", 2),
+            ('g', Some('u')) if front(at(i + 2)) => ("ɡ", 2),
+            ('g', Some('ü')) => ("ɡw", 2),
+            ('c', next) if front(next) => ("s", 1),
+            ('c', _) | ('k', _) => ("k", 1),
+            ('g', next) if front(next) => ("x", 1),
+            ('g', _) => ("ɡ", 1),
+            ('j', _) => ("x", 1),
+            ('h', _) => ("", 1),
+            ('ñ', _) => ("ɲ", 1),
+            ('v', _) | ('b', _) => ("b", 1),
+            ('z', _) | ('s', _) => ("s", 1),
+            ('x', _) => ("ks", 1),
+            // A lone or final "y" is the vowel
+            ('y', next) if !vowel(next) => ("i", 1),
+            ('y', _) => ("ʝ", 1),
+            ('r', _) if i == 0 || matches!(at(i - 1), Some('l' | 'n' | 's')) => ("r", 1),
+            ('r', _) => ("ɾ", 1),
+            ('ü', _) => ("w", 1),
+            (c @ ('a' | 'e' | 'i' | 'o' | 'u' | 'd' | 'f' | 'l' | 'm' | 'n' | 'p' | 't' | 'w'), _) => {
+                ipa.push(c);
+                i += 1;
+                continue;
+            }
+            _ => ("", 1),
+        };
+        ipa.push_str(sound);
+        i += consumed;
+    }
+    ipa
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spanish_rules() {
+        let cases = [
+            ("chico", "tʃiko"), ("llama", "ʝama"), ("perro", "pero"), ("rosa", "rosa"), ("pero", "peɾo"),
+            ("queso", "keso"), ("guerra", "ɡera"), ("pingüino", "pinɡwino"), ("gente", "xente"),
+            ("cielo", "sielo"), ("hola", "ola"), ("niño", "niɲo"), ("vaca", "baka"), ("rey", "rei"),
+            ("Canción", "kansion"), ("Israel", "israel"),
+        ];
+        for (word, ipa) in cases {
+            assert_eq!(spanish(word), ipa, "{}", word);
+        }
+    }
+}
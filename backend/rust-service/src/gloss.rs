@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use futures::future::{self, BoxFuture, FutureExt};
+use serde::{Deserialize, Serialize};
+
+use crate::dictionary;
+use crate::error::{ApiError, ErrorCode};
+use crate::models::{GlossSource, TokenGloss, Warning, WarningCode};
+use crate::upstream::{self, Breaker, Failure, RetryPolicy, DEFAULT_BREAKER_COOLDOWN_SECS, DEFAULT_BREAKER_THRESHOLD};
+
+/// Providers set up at startup, shared by every request
+static GLOSSER: OnceLock<Glosser> = OnceLock::new();
+
+/// One source of word glosses
+///
+/// Implementations must be cheap to ask `supports` (it's checked per
+/// request); `gloss` may go over the network.
+pub trait Provider: Send + Sync {
+    /// What glosses from this provider are reported as
+    fn source(&self) -> GlossSource;
+
+    /// Whether it can gloss words of `from` in `to`
+    fn supports(&self, from: &str, to: &str) -> bool;
+
+    /// A gloss per word, in order; `None` for words it doesn't know
+    fn gloss<'a>(&'a self, words: &'a [String], from: &'a str, to: &'a str) -> BoxFuture<'a, Result<Vec<Option<String>>, ApiError>>;
+}
+
+/// The loaded dictionaries, which gloss in English (CC-CEDICT, JMdict and
+/// kaikki.org's Wiktionary extracts all do)
+pub struct Dictionaries;
+
+impl Provider for Dictionaries {
+    fn source(&self) -> GlossSource {
+        GlossSource::Dictionary
+    }
+
+    fn supports(&self, from: &str, to: &str) -> bool {
+        primary_subtag(to) == "en" && dictionary::dictionaries().get(from).is_some()
+    }
+
+    fn gloss<'a>(&'a self, words: &'a [String], from: &'a str, _to: &'a str) -> BoxFuture<'a, Result<Vec<Option<String>>, ApiError>> {
+        let dictionaries = dictionary::dictionaries();
+        let dictionary = dictionaries.get(from);
+        let glosses = words.iter()
+            .map(|word| {
+                // The word's own entries come before its lemmas'
+                dictionary?.lookup(word).into_iter().find_map(|entry| entry.glosses.first().cloned())
+            })
+            .collect();
+        future::ready(Ok(glosses)).boxed()
+    }
+}
+
+/// Body sent to a LibreTranslate-compatible `/translate` endpoint
+#[derive(Debug, Serialize)]
+struct TranslateRequest<'a> {
+    q: &'a [String],
+    source: &'a str,
+    target: &'a str,
+    format: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    api_key: Option<&'a str>,
+}
+
+/// Its reply: one translation per word sent
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TranslateResponse {
+    translated_text: Vec<String>,
+}
+
+/// A machine translation service (`GLOSS_MT_URL`), asked about every word
+/// the dictionaries left in one request
+///
+/// A service that keeps failing is left alone for a while, its words
+/// going unglossed, rather than making every tokenize request wait on it.
+pub struct MachineTranslation {
+    url: String,
+    api_key: Option<String>,
+    client: reqwest::Client,
+    breaker: Breaker,
+}
+
+/// One retry: words it can't gloss are left without, so waiting longer
+/// only slows the answer down
+const TRANSLATION_RETRY: RetryPolicy = RetryPolicy { retries: 1, initial_backoff: Duration::from_millis(250) };
+
+/// How long one translation request may take; a tokenize request waits on it
+const TRANSLATION_TIMEOUT: Duration = Duration::from_secs(5);
+
+const TRANSLATION_CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+impl MachineTranslation {
+    pub fn new(url: String, api_key: Option<String>) -> Result<Self, String> {
+        let client = reqwest::Client::builder()
+            .timeout(TRANSLATION_TIMEOUT)
+            .read_timeout(TRANSLATION_TIMEOUT)
+            .connect_timeout(TRANSLATION_CONNECT_TIMEOUT)
+            .build()
+            .map_err(|e| format!("Can't build the translation client: {}", e))?;
+        let breaker = Breaker::new("The translation service", DEFAULT_BREAKER_THRESHOLD, Duration::from_secs(DEFAULT_BREAKER_COOLDOWN_SECS));
+        Ok(MachineTranslation { url, api_key, client, breaker })
+    }
+}
+
+impl Provider for MachineTranslation {
+    fn source(&self) -> GlossSource {
+        GlossSource::MachineTranslation
+    }
+
+    fn supports(&self, from: &str, to: &str) -> bool {
+        primary_subtag(from) != primary_subtag(to)
+    }
+
+    #[tracing::instrument(skip_all, fields(from = %from, to = %to, words = words.len()))]
+    fn gloss<'a>(&'a self, words: &'a [String], from: &'a str, to: &'a str) -> BoxFuture<'a, Result<Vec<Option<String>>, ApiError>> {
+        async move {
+            let (source, target) = (primary_subtag(from), primary_subtag(to));
+            let body = TranslateRequest {
+                q: words,
+                source: &source,
+                target: &target,
+                format: "text",
+                api_key: self.api_key.as_deref(),
+            };
+            let body = &body;
+            let response: TranslateResponse = upstream::call(&self.breaker, TRANSLATION_RETRY, || async move {
+                self.client.post(&self.url)
+                    .json(body)
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status())
+                    .map_err(|e| Failure::from_reqwest(e, "Translation request failed"))?
+                    .json()
+                    .await
+                    .map_err(|e| Failure::Final(ApiError::new(ErrorCode::Upstream, format!("Invalid translation response: {}", e))))
+            }).await?;
+
+            if response.translated_text.len() != words.len() {
+                return Err(ApiError::new(ErrorCode::Upstream, format!("Translation service returned {} glosses, expected {}",
+                    response.translated_text.len(), words.len())));
+            }
+            Ok(response.translated_text.into_iter()
+                .map(|gloss| Some(gloss.trim().to_string()).filter(|gloss| !gloss.is_empty()))
+                .collect())
+        }.boxed()
+    }
+}
+
+/// Providers in the order they're asked, most trusted first: dictionaries,
+/// then machine translation if configured
+pub struct Glosser {
+    providers: Vec<Box<dyn Provider>>,
+}
+
+impl Default for Glosser {
+    fn default() -> Self {
+        Glosser::new(vec![Box::new(Dictionaries)])
+    }
+}
+
+impl Glosser {
+    pub fn new(providers: Vec<Box<dyn Provider>>) -> Self {
+        Glosser { providers }
+    }
+
+    /// Whether any provider can gloss `from` in `to`
+    pub fn supports(&self, from: &str, to: &str) -> bool {
+        self.providers.iter().any(|provider| provider.supports(from, to))
+    }
+
+    /// A gloss per token, `None` for tokens no provider knows, and a warning
+    /// for each provider that failed
+    ///
+    /// Each distinct word is asked about once, and only words a provider
+    /// didn't know are passed to the next one. A provider that fails is
+    /// logged and skipped, so glosses never fail the request they're for.
+    pub async fn gloss(&self, tokens: &[String], from: &str, to: &str) -> (Vec<Option<TokenGloss>>, Vec<Warning>) {
+        let mut found: HashMap<String, TokenGloss> = HashMap::new();
+        let mut pending: Vec<String> = Vec::new();
+        let mut warnings = Vec::new();
+        for token in tokens {
+            if token.chars().any(char::is_alphabetic) && !pending.contains(token) {
+                pending.push(token.clone());
+            }
+        }
+
+        for provider in self.providers.iter().filter(|provider| provider.supports(from, to)) {
+            if pending.is_empty() {
+                break;
+            }
+            let glosses = match provider.gloss(&pending, from, to).await {
+                Ok(glosses) => glosses,
+                Err(e) => {
+                    log::warn!("{} glosses failed, skipping: {}", provider.source().name(), e);
+                    warnings.push(Warning::new(WarningCode::GlossFailed,
+                        format!("{} glosses failed; its words are glossed by other sources or not at all", provider.source().name())));
+                    continue;
+                }
+            };
+            let mut missing = Vec::new();
+            for (word, gloss) in pending.into_iter().zip(glosses) {
+                match gloss {
+                    Some(gloss) => {
+                        found.insert(word, TokenGloss { gloss, source: provider.source() });
+                    }
+                    None => missing.push(word),
+                }
+            }
+            pending = missing;
+        }
+
+        (tokens.iter().map(|token| found.get(token.as_str()).cloned()).collect(), warnings)
+    }
+
+    /// Providers in order, e.g. "dictionary, machine_translation"
+    pub fn summary(&self) -> String {
+        self.providers.iter().map(|provider| provider.source().name()).collect::<Vec<_>>().join(", ")
+    }
+}
+
+fn primary_subtag(language: &str) -> String {
+    language.split(['-', '_']).next().unwrap_or(language).to_lowercase()
+}
+
+/// Set up the providers once at startup: dictionaries, then the machine
+/// translation service at `mt_url` if configured
+pub fn init(mt_url: Option<String>, mt_api_key: Option<String>) -> Result<(), String> {
+    let mut providers: Vec<Box<dyn Provider>> = vec![Box::new(Dictionaries)];
+    if let Some(url) = mt_url {
+        log::info!("Gloss machine translation: {}", url);
+        providers.push(Box::new(MachineTranslation::new(url, mt_api_key)?));
+    }
+    if GLOSSER.set(Glosser::new(providers)).is_err() {
+        log::warn!("Gloss providers already initialised");
+    }
+    Ok(())
+}
+
+/// Providers set up by `init`, or the dictionaries alone if it was never called
+pub fn glosser() -> &'static Glosser {
+    GLOSSER.get_or_init(Glosser::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Knows the words in its list
+    struct Fake {
+        source: GlossSource,
+        known: &'static [(&'static str, &'static str)],
+        fails: bool,
+    }
+
+    impl Fake {
+        fn new(source: GlossSource, known: &'static [(&'static str, &'static str)]) -> Self {
+            Fake { source, known, fails: false }
+        }
+    }
+
+    impl Provider for Fake {
+        fn source(&self) -> GlossSource {
+            self.source
+        }
+
+        fn supports(&self, _from: &str, to: &str) -> bool {
+            to == "en"
+        }
+
+        fn gloss<'a>(&'a self, words: &'a [String], _from: &'a str, _to: &'a str) -> BoxFuture<'a, Result<Vec<Option<String>>, ApiError>> {
+            let glosses = words.iter()
+                .map(|word| self.known.iter().find(|(known, _)| known == word).map(|(_, gloss)| gloss.to_string()))
+                .collect();
+            let result = if self.fails { Err(ApiError::new(ErrorCode::Upstream, "down")) } else { Ok(glosses) };
+            future::ready(result).boxed()
+        }
+    }
+
+    fn tokens(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_providers_in_order() {
+        let dictionary = Fake::new(GlossSource::Dictionary, &[("gato", "cat")]);
+        let mt = Fake::new(GlossSource::MachineTranslation, &[("gato", "kitty"), ("negro", "black")]);
+        let glosser = Glosser::new(vec![Box::new(dictionary), Box::new(mt)]);
+
+        let (glosses, warnings) = glosser.gloss(&tokens(&["gato", "negro", "gato", "42", "zzz"]), "es", "en").await;
+        assert!(warnings.is_empty());
+        assert_eq!(glosses[0], Some(TokenGloss { gloss: "cat".to_string(), source: GlossSource::Dictionary }));
+        assert_eq!(glosses[1], Some(TokenGloss { gloss: "black".to_string(), source: GlossSource::MachineTranslation }));
+        assert_eq!(glosses[2], glosses[0]);
+        assert_eq!(glosses[3], None);
+        assert_eq!(glosses[4], None);
+        assert!(glosser.supports("es", "en"));
+        assert!(!glosser.supports("es", "fr"));
+    }
+
+    #[tokio::test]
+    async fn test_failing_provider_is_skipped() {
+        let mut broken = Fake::new(GlossSource::MachineTranslation, &[("gato", "kitty")]);
+        broken.fails = true;
+        let dictionary = Fake::new(GlossSource::Dictionary, &[("gato", "cat")]);
+        let glosser = Glosser::new(vec![Box::new(broken), Box::new(dictionary)]);
+
+        let (glosses, warnings) = glosser.gloss(&tokens(&["gato", "perro"]), "es", "en").await;
+        assert_eq!(glosses[0].as_ref().map(|gloss| gloss.source), Some(GlossSource::Dictionary));
+        assert_eq!(warnings.iter().map(|warning| warning.code).collect::<Vec<_>>(), vec![WarningCode::GlossFailed]);
+        assert_eq!(glosses[1], None);
+    }
+}
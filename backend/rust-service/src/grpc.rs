@@ -0,0 +1,449 @@
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::{Stream, StreamExt};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::TlsAcceptor;
+use tonic::transport::server::{Connected, TcpConnectInfo};
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::aligner::align_smart;
+use crate::auth;
+use crate::cache;
+use crate::error::{ApiError, ErrorCode};
+use crate::models::{AlignmentRequest, AlignmentResponse, TokenizeRequest, Warning};
+use crate::tokenizer::tokenize_text;
+use crate::validation::Validate;
+
+use pb::dubdub_server::{Dubdub, DubdubServer};
+
+/// Messages of `proto/dubdub.proto`, plus the generated server stubs
+///
+/// Written out by hand (what prost would generate) so building doesn't need
+/// protoc; field tags must match the .proto.
+pub mod pb {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct TokenizeRequest {
+        #[prost(string, tag = "1")]
+        pub text: String,
+        #[prost(string, tag = "2")]
+        pub language: String,
+    }
+
+    #[derive(Clone, Copy, PartialEq, prost::Message)]
+    pub struct TokenPosition {
+        #[prost(uint32, tag = "1")]
+        pub start: u32,
+        #[prost(uint32, tag = "2")]
+        pub end: u32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct TokenizeResponse {
+        #[prost(string, tag = "1")]
+        pub text: String,
+        #[prost(string, tag = "2")]
+        pub language: String,
+        #[prost(string, repeated, tag = "3")]
+        pub tokens: Vec<String>,
+        #[prost(message, repeated, tag = "4")]
+        pub positions: Vec<TokenPosition>,
+        #[prost(message, repeated, tag = "5")]
+        pub warnings: Vec<Warning>,
+        #[prost(string, tag = "6")]
+        pub error: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Warning {
+        #[prost(string, tag = "1")]
+        pub code: String,
+        #[prost(string, tag = "2")]
+        pub message: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct BatchTokenizeRequest {
+        #[prost(message, repeated, tag = "1")]
+        pub items: Vec<TokenizeRequest>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct BatchTokenizeResponse {
+        #[prost(message, repeated, tag = "1")]
+        pub items: Vec<TokenizeResponse>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct AlignRequest {
+        #[prost(string, tag = "1")]
+        pub id: String,
+        #[prost(string, tag = "2")]
+        pub text: String,
+        #[prost(string, tag = "3")]
+        pub language: String,
+        #[prost(double, tag = "4")]
+        pub subtitle_start: f64,
+        #[prost(double, tag = "5")]
+        pub subtitle_end: f64,
+        #[prost(double, optional, tag = "6")]
+        pub min_confidence: Option<f64>,
+        #[prost(bool, tag = "7")]
+        pub exclude_flagged: bool,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct WordTiming {
+        #[prost(string, tag = "1")]
+        pub word: String,
+        #[prost(double, tag = "2")]
+        pub start: f64,
+        #[prost(double, tag = "3")]
+        pub end: f64,
+        #[prost(double, tag = "4")]
+        pub confidence: f64,
+        #[prost(uint32, tag = "5")]
+        pub char_start: u32,
+        #[prost(uint32, tag = "6")]
+        pub char_end: u32,
+        #[prost(bool, tag = "7")]
+        pub flagged: bool,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct AlignResponse {
+        #[prost(string, tag = "1")]
+        pub id: String,
+        #[prost(message, repeated, tag = "2")]
+        pub timings: Vec<WordTiming>,
+        #[prost(double, tag = "3")]
+        pub duration: f64,
+        #[prost(string, tag = "4")]
+        pub method: String,
+        #[prost(string, tag = "5")]
+        pub error: String,
+        #[prost(message, repeated, tag = "6")]
+        pub warnings: Vec<Warning>,
+    }
+
+    include!(concat!(env!("OUT_DIR"), "/dubdub.v1.Dubdub.rs"));
+}
+
+/// Longest a client gets to finish the TLS handshake
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Serve gRPC on its own thread and runtime, next to the HTTP server
+///
+/// Calls need the same credentials as `/api` routes (an `authorization:
+/// Bearer` token or an `x-api-key`), and are served over TLS when the HTTP
+/// server is.
+pub fn spawn(addr: SocketAddr, tls: Option<rustls::ServerConfig>) {
+    if !addr.ip().is_loopback() && auth::authenticator().is_none() {
+        log::warn!("gRPC on {} is open to anyone who can reach it: no API keys or JWT issuer configured", addr);
+    }
+
+    std::thread::spawn(move || {
+        let runtime = match tokio::runtime::Runtime::new() {
+            Ok(runtime) => runtime,
+            Err(e) => {
+                log::error!("Failed to start gRPC runtime: {}", e);
+                return;
+            }
+        };
+
+        log::info!("gRPC listening on {} ({})", addr, if tls.is_some() { "TLS" } else { "plaintext" });
+        let router = tonic::transport::Server::builder()
+            .add_service(DubdubServer::with_interceptor(GrpcService, authenticate));
+
+        let served = runtime.block_on(async move {
+            match tls {
+                None => router.serve(addr).await.map_err(|e| e.to_string()),
+                Some(tls) => {
+                    let listener = TcpListener::bind(addr).await.map_err(|e| e.to_string())?;
+                    router.serve_with_incoming(tls_connections(listener, tls)).await.map_err(|e| e.to_string())
+                }
+            }
+        });
+        if let Err(e) = served {
+            log::error!("gRPC server stopped: {}", e);
+        }
+    });
+}
+
+/// Check a call's credentials as `auth` does for `/api` routes, keeping
+/// the client in the request extensions
+fn authenticate(mut request: Request<()>) -> Result<Request<()>, Status> {
+    let Some(authenticator) = auth::authenticator() else {
+        return Ok(request);
+    };
+
+    let metadata = request.metadata();
+    let value = |name: &str| metadata.get(name).and_then(|value| value.to_str().ok()).map(str::trim);
+    let token = value("authorization").and_then(|value| value.strip_prefix("Bearer ")).map(str::trim);
+    let client = authenticator.authenticate(token, value("x-api-key"))?;
+
+    request.extensions_mut().insert(client);
+    Ok(request)
+}
+
+/// Connections accepted on `listener` once their TLS handshake is done
+///
+/// Handshakes run on their own tasks, so a slow client doesn't hold up the
+/// rest; failed ones are dropped.
+fn tls_connections(listener: TcpListener, mut tls: rustls::ServerConfig) -> impl Stream<Item = io::Result<TlsConnection>> {
+    tls.alpn_protocols = vec![b"h2".to_vec()];
+    let acceptor = TlsAcceptor::from(Arc::new(tls));
+    let (sender, receiver) = tokio::sync::mpsc::channel(64);
+
+    tokio::spawn(async move {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    log::warn!("gRPC accept failed: {}", e);
+                    continue;
+                }
+            };
+            let (acceptor, sender) = (acceptor.clone(), sender.clone());
+            tokio::spawn(async move {
+                match tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                    Ok(Ok(stream)) => {
+                        let _ = sender.send(Ok(TlsConnection(stream))).await;
+                    }
+                    Ok(Err(e)) => log::debug!("gRPC TLS handshake failed: {}", e),
+                    Err(_) => log::debug!("gRPC TLS handshake timed out"),
+                }
+            });
+        }
+    });
+
+    futures::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|connection| (connection, receiver))
+    })
+}
+
+/// A TLS connection tonic can serve
+struct TlsConnection(tokio_rustls::server::TlsStream<TcpStream>);
+
+impl Connected for TlsConnection {
+    type ConnectInfo = TcpConnectInfo;
+
+    fn connect_info(&self) -> Self::ConnectInfo {
+        self.0.get_ref().0.connect_info()
+    }
+}
+
+impl AsyncRead for TlsConnection {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().0).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TlsConnection {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().0).poll_shutdown(cx)
+    }
+}
+
+/// Same operations as the HTTP handlers, over gRPC
+pub struct GrpcService;
+
+#[tonic::async_trait]
+impl Dubdub for GrpcService {
+    async fn tokenize(&self, request: Request<pb::TokenizeRequest>) -> Result<Response<pb::TokenizeResponse>, Status> {
+        let req = request.into_inner();
+        tokenize(&req)
+            .map(Response::new)
+            .map_err(Status::from)
+    }
+
+    async fn batch_tokenize(&self, request: Request<pb::BatchTokenizeRequest>) -> Result<Response<pb::BatchTokenizeResponse>, Status> {
+        let items = request.into_inner().items.into_iter()
+            .map(|item| match tokenize(&item) {
+                Ok(response) => response,
+                Err(e) => pb::TokenizeResponse { text: item.text, language: item.language, error: e.to_string(), ..Default::default() },
+            })
+            .collect();
+
+        Ok(Response::new(pb::BatchTokenizeResponse { items }))
+    }
+
+    async fn align(&self, request: Request<pb::AlignRequest>) -> Result<Response<pb::AlignResponse>, Status> {
+        let req = request.into_inner();
+        align(&req)
+            .map(|response| Response::new(align_response(req.id, response)))
+            .map_err(Status::from)
+    }
+
+    type BatchAlignStream = Pin<Box<dyn Stream<Item = Result<pb::AlignResponse, Status>> + Send>>;
+
+    async fn batch_align(&self, request: Request<Streaming<pb::AlignRequest>>) -> Result<Response<Self::BatchAlignStream>, Status> {
+        let responses = request.into_inner().map(|item| {
+            let req = item?;
+            Ok(match align(&req) {
+                Ok(response) => align_response(req.id, response),
+                Err(e) => pb::AlignResponse { id: req.id, error: e.to_string(), ..Default::default() },
+            })
+        });
+
+        Ok(Response::new(Box::pin(responses)))
+    }
+}
+
+impl From<ApiError> for Status {
+    fn from(error: ApiError) -> Self {
+        let message = error.to_string();
+        match error.code {
+            ErrorCode::InvalidInput | ErrorCode::InvalidSubtitles | ErrorCode::NoWords => Status::invalid_argument(message),
+            ErrorCode::Unsupported | ErrorCode::UnsupportedVersion => Status::unimplemented(message),
+            ErrorCode::NotFound => Status::not_found(message),
+            ErrorCode::Unauthorized => Status::unauthenticated(message),
+            ErrorCode::PayloadTooLarge | ErrorCode::QuotaExceeded => Status::resource_exhausted(message),
+            ErrorCode::Unavailable | ErrorCode::Upstream | ErrorCode::UpstreamUnavailable => Status::unavailable(message),
+            ErrorCode::Cancelled => Status::cancelled(message),
+            ErrorCode::Conflict => Status::aborted(message),
+            ErrorCode::Timeout => Status::deadline_exceeded(message),
+            ErrorCode::Internal => Status::internal(message),
+        }
+    }
+}
+
+fn tokenize(req: &pb::TokenizeRequest) -> Result<pb::TokenizeResponse, ApiError> {
+    TokenizeRequest { text: req.text.clone(), language: req.language.clone(), known: None }.validate()?;
+    let response = tokenize_text(&req.text, &req.language)?;
+
+    Ok(pb::TokenizeResponse {
+        text: response.text,
+        language: response.language,
+        tokens: response.tokens,
+        positions: response.positions.iter()
+            .map(|p| pb::TokenPosition { start: p.start as u32, end: p.end as u32 })
+            .collect(),
+        warnings: warnings(response.warnings),
+        error: String::new(),
+    })
+}
+
+fn align(req: &pb::AlignRequest) -> Result<AlignmentResponse, ApiError> {
+    let req = alignment_request(req);
+    req.validate()?;
+    cache::get_or_align(&req, align_smart)
+}
+
+fn alignment_request(req: &pb::AlignRequest) -> AlignmentRequest {
+    AlignmentRequest {
+        text: req.text.clone(),
+        language: req.language.clone(),
+        subtitle_start: req.subtitle_start,
+        subtitle_end: req.subtitle_end,
+        min_confidence: req.min_confidence,
+        exclude_flagged: req.exclude_flagged,
+        ..Default::default()
+    }
+}
+
+fn align_response(id: String, response: AlignmentResponse) -> pb::AlignResponse {
+    pb::AlignResponse {
+        id,
+        duration: response.duration,
+        method: snake_case_name(&response.method),
+        timings: response.timings.into_iter()
+            .map(|t| pb::WordTiming {
+                word: t.word,
+                start: t.start,
+                end: t.end,
+                confidence: t.confidence,
+                char_start: t.char_start as u32,
+                char_end: t.char_end as u32,
+                flagged: t.flagged,
+            })
+            .collect(),
+        error: String::new(),
+        warnings: warnings(response.warnings),
+    }
+}
+
+fn warnings(warnings: Vec<Warning>) -> Vec<pb::Warning> {
+    warnings.into_iter()
+        .map(|warning| pb::Warning { code: snake_case_name(&warning.code), message: warning.message })
+        .collect()
+}
+
+/// A unit enum variant as it's named in JSON, e.g. `"tts_prediction"`
+fn snake_case_name(value: &impl serde::Serialize) -> String {
+    serde_json::to_value(value).ok()
+        .and_then(|name| name.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_tokenize() {
+        let request = Request::new(pb::TokenizeRequest { text: "Hello world".to_string(), language: "en".to_string() });
+        let response = GrpcService.tokenize(request).await.unwrap().into_inner();
+
+        assert_eq!(response.tokens, vec!["Hello", "world"]);
+        assert_eq!(response.positions[1], pb::TokenPosition { start: 6, end: 11 });
+        assert!(response.warnings.is_empty());
+
+        let request = Request::new(pb::TokenizeRequest { text: "สวัสดีครับ".to_string(), language: "th".to_string() });
+        let response = GrpcService.tokenize(request).await.unwrap().into_inner();
+        assert_eq!(response.warnings[0].code, "default_tokenizer");
+    }
+
+    #[tokio::test]
+    async fn test_batch_tokenize_keeps_failed_items_in_place() {
+        let items = vec![
+            pb::TokenizeRequest { text: "Hello".to_string(), language: "en".to_string() },
+            pb::TokenizeRequest { text: String::new(), language: "en".to_string() },
+            pb::TokenizeRequest { text: "world".to_string(), language: "en".to_string() },
+        ];
+        let response = GrpcService.batch_tokenize(Request::new(pb::BatchTokenizeRequest { items })).await.unwrap().into_inner();
+
+        assert_eq!(response.items.len(), 3);
+        assert_eq!(response.items[0].tokens, vec!["Hello"]);
+        assert!(response.items[1].tokens.is_empty() && !response.items[1].error.is_empty());
+        assert_eq!(response.items[2].tokens, vec!["world"]);
+    }
+
+    #[tokio::test]
+    async fn test_align() {
+        let request = Request::new(pb::AlignRequest {
+            id: "cue-1".to_string(),
+            text: "Hello world".to_string(),
+            language: "en".to_string(),
+            subtitle_start: 0.0,
+            subtitle_end: 2.0,
+            ..Default::default()
+        });
+        let response = GrpcService.align(request).await.unwrap().into_inner();
+
+        assert_eq!(response.id, "cue-1");
+        assert_eq!(response.timings.len(), 2);
+        assert_eq!(response.method, "weighted");
+    }
+
+    #[tokio::test]
+    async fn test_align_invalid_timing() {
+        let request = Request::new(pb::AlignRequest { text: "Hi".to_string(), subtitle_end: -1.0, ..Default::default() });
+        let status = GrpcService.align(request).await.unwrap_err();
+
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+}
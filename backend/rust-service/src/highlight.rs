@@ -0,0 +1,155 @@
+use crate::models::{HighlightSegment, WordTiming};
+
+/// Class of every word segment
+pub const WORD_CLASS: &str = "dd-word";
+/// Added to words below the request's `min_confidence`
+pub const FLAGGED_CLASS: &str = "dd-flagged";
+/// Class of the text between words (spaces, punctuation)
+pub const GAP_CLASS: &str = "dd-gap";
+
+/// The whole text cut into words and the gaps between them
+///
+/// `timings` must be in text order without overlapping, as `/align`
+/// returns them.
+pub fn segments(text: &str, timings: &[WordTiming]) -> Vec<HighlightSegment> {
+    let mut segments = Vec::new();
+    let mut at = 0;
+
+    let gap = |from: usize, to: usize| HighlightSegment {
+        html: escape(&text[from..to]),
+        class: GAP_CLASS.to_string(),
+        index: None,
+        start: None,
+        end: None,
+    };
+
+    for (index, timing) in timings.iter().enumerate() {
+        if timing.char_start > at {
+            segments.push(gap(at, timing.char_start));
+        }
+        let class = match timing.flagged {
+            true => format!("{} {}", WORD_CLASS, FLAGGED_CLASS),
+            false => WORD_CLASS.to_string(),
+        };
+        segments.push(HighlightSegment {
+            html: escape(&text[timing.char_start..timing.char_end]),
+            class,
+            index: Some(index),
+            start: Some(timing.start),
+            end: Some(timing.end),
+        });
+        at = timing.char_end;
+    }
+    if at < text.len() {
+        segments.push(gap(at, text.len()));
+    }
+
+    segments
+}
+
+/// Segments as one string of `<span>`s, ready to drop into a page
+pub fn html(segments: &[HighlightSegment]) -> String {
+    segments.iter()
+        .map(|segment| match (segment.index, segment.start, segment.end) {
+            (Some(index), Some(start), Some(end)) => format!(
+                "<span class=\"{}\" data-index=\"{}\" data-start=\"{:.3}\" data-end=\"{:.3}\">{}</span>",
+                segment.class, index, start, end, segment.html
+            ),
+            _ => format!("<span class=\"{}\">{}</span>", segment.class, segment.html),
+        })
+        .collect()
+}
+
+/// `[start_ms, end_ms, from, to]` per word, by start time
+///
+/// Offsets are in UTF-16 code units rather than bytes, so clients can
+/// slice their own strings without converting.
+pub fn ranges(text: &str, timings: &[WordTiming]) -> Vec<[u32; 4]> {
+    let mut ranges = Vec::with_capacity(timings.len());
+    let (mut byte, mut utf16) = (0, 0);
+    let mut advance = |to: usize| {
+        utf16 += text[byte..to].encode_utf16().count();
+        byte = to;
+        utf16 as u32
+    };
+
+    for timing in timings {
+        let from = advance(timing.char_start);
+        let to = advance(timing.char_end);
+        ranges.push([millis(timing.start), millis(timing.end), from, to]);
+    }
+
+    ranges.sort_by_key(|range| (range[0], range[2]));
+    ranges
+}
+
+/// Ranges packed as little-endian `u32`s, 16 bytes per word
+pub fn encode(ranges: &[[u32; 4]]) -> Vec<u8> {
+    ranges.iter().flatten().flat_map(|value| value.to_le_bytes()).collect()
+}
+
+fn millis(seconds: f64) -> u32 {
+    (seconds * 1000.0).round() as u32
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timing(text: &str, word: &str, start: f64, end: f64) -> WordTiming {
+        let char_start = text.find(word).unwrap();
+        WordTiming {
+            word: word.to_string(),
+            start,
+            end,
+            confidence: 1.0,
+            char_start,
+            char_end: char_start + word.len(),
+            flagged: false,
+        }
+    }
+
+    #[test]
+    fn test_segments_and_html() {
+        let text = "<Tom> & Jerry!";
+        let mut timings = vec![timing(text, "Tom", 0.0, 0.5), timing(text, "Jerry", 0.6, 1.2)];
+        timings[1].flagged = true;
+
+        let segments = segments(text, &timings);
+        let pieces: Vec<&str> = segments.iter().map(|segment| segment.html.as_str()).collect();
+        assert_eq!(pieces, vec!["&lt;", "Tom", "&gt; &amp; ", "Jerry", "!"]);
+        assert_eq!(segments[3].class, "dd-word dd-flagged");
+        assert_eq!(segments[3].index, Some(1));
+
+        assert_eq!(
+            html(&segments[..2]),
+            "<span class=\"dd-gap\">&lt;</span><span class=\"dd-word\" data-index=\"0\" data-start=\"0.000\" data-end=\"0.500\">Tom</span>"
+        );
+    }
+
+    #[test]
+    fn test_ranges_in_utf16() {
+        let text = "😀 café ok";
+        let ranges = ranges(text, &[timing(text, "café", 0.1, 0.8), timing(text, "ok", 0.9, 1.25)]);
+        assert_eq!(ranges, vec![[100, 800, 3, 7], [900, 1250, 8, 10]]);
+
+        let bytes = encode(&ranges);
+        assert_eq!(bytes.len(), 32);
+        assert_eq!(&bytes[..4], &100u32.to_le_bytes());
+    }
+}
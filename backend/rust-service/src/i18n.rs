@@ -0,0 +1,324 @@
+use std::future::Future;
+
+use serde_json::{Map, Value};
+
+/// UI languages the validation messages are translated into
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    Es,
+    Fr,
+    De,
+    Pt,
+    Ja,
+}
+
+/// Every locale, in the order of each message's translations
+const LOCALES: [Locale; 6] = [Locale::En, Locale::Es, Locale::Fr, Locale::De, Locale::Pt, Locale::Ja];
+
+impl Locale {
+    pub fn code(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Es => "es",
+            Locale::Fr => "fr",
+            Locale::De => "de",
+            Locale::Pt => "pt",
+            Locale::Ja => "ja",
+        }
+    }
+
+    /// By primary language subtag, so `pt-BR` is Portuguese
+    pub fn from_tag(tag: &str) -> Option<Locale> {
+        let primary = tag.trim().split(['-', '_']).next().unwrap_or_default();
+        LOCALES.into_iter().find(|locale| locale.code().eq_ignore_ascii_case(primary))
+    }
+
+    /// The best supported language of an `Accept-Language` header, by
+    /// q-value then order; English when none is supported
+    pub fn negotiate(accept_language: &str) -> Locale {
+        let mut ranges: Vec<(&str, f64)> = accept_language.split(',')
+            .map(|range| {
+                let mut parts = range.split(';');
+                let tag = parts.next().unwrap_or_default().trim();
+                let q = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .and_then(|q| q.trim().parse().ok())
+                    .unwrap_or(1.0);
+                (tag, q)
+            })
+            .filter(|(_, q)| *q > 0.0)
+            .collect();
+        ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranges.into_iter().find_map(|(tag, _)| Locale::from_tag(tag)).unwrap_or_default()
+    }
+
+    fn position(self) -> usize {
+        LOCALES.iter().position(|locale| *locale == self).unwrap_or_default()
+    }
+}
+
+tokio::task_local! {
+    static LOCALE: Locale;
+}
+
+/// Run a request's future with `locale` as its UI language
+pub async fn scope<F: Future>(locale: Locale, fut: F) -> F::Output {
+    LOCALE.scope(locale, fut).await
+}
+
+/// The current request's UI language; English outside a request's task
+pub fn current() -> Locale {
+    LOCALE.try_with(|locale| *locale).unwrap_or_default()
+}
+
+/// A problem with one request field, as a stable code and parameters that
+/// clients can localize themselves, or `render` in a supported language
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    Empty,
+    TooLong { max: usize },
+    UnknownLanguage { language: String },
+    NotFinite,
+    Negative,
+    NotAfter { other: String },
+    OutOfRange { min: f64, max: f64 },
+    AtLeast { min: usize },
+    TooManyWords { max: usize, count: usize },
+    NotPositive,
+    /// A word timing's characters don't follow the previous word's
+    OutOfOrder,
+    EmptyListId,
+    NeedsKnownWords,
+    /// Set along with a field it can't be combined with
+    Excludes { other: String },
+    /// The first error of several
+    AndMore { message: String, count: usize },
+}
+
+/// Each code's template in every locale, in `LOCALES` order
+const TEMPLATES: &[(&str, [&str; 6])] = &[
+    ("empty", [
+        "must not be empty",
+        "no debe estar vacío",
+        "ne doit pas être vide",
+        "darf nicht leer sein",
+        "não pode estar vazio",
+        "空にできません",
+    ]),
+    ("too_long", [
+        "must be at most {max} characters",
+        "debe tener como máximo {max} caracteres",
+        "doit contenir au plus {max} caractères",
+        "darf höchstens {max} Zeichen lang sein",
+        "deve ter no máximo {max} caracteres",
+        "{max} 文字以内にしてください",
+    ]),
+    ("unknown_language", [
+        "unknown language '{language}', expected an ISO 639-1 code such as 'en' or 'pt-BR'",
+        "idioma desconocido '{language}'; se espera un código ISO 639-1 como 'en' o 'pt-BR'",
+        "langue inconnue '{language}', un code ISO 639-1 tel que 'en' ou 'pt-BR' est attendu",
+        "unbekannte Sprache '{language}', erwartet wird ein ISO-639-1-Code wie 'en' oder 'pt-BR'",
+        "idioma desconhecido '{language}'; esperava-se um código ISO 639-1 como 'en' ou 'pt-BR'",
+        "不明な言語 '{language}' です。'en' や 'pt-BR' のような ISO 639-1 コードを指定してください",
+    ]),
+    ("not_finite", [
+        "must be a finite number",
+        "debe ser un número finito",
+        "doit être un nombre fini",
+        "muss eine endliche Zahl sein",
+        "deve ser um número finito",
+        "有限の数値にしてください",
+    ]),
+    ("negative", [
+        "must not be negative",
+        "no debe ser negativo",
+        "ne doit pas être négatif",
+        "darf nicht negativ sein",
+        "não pode ser negativo",
+        "負の値にはできません",
+    ]),
+    ("not_after", [
+        "must be after {other}",
+        "debe ser posterior a {other}",
+        "doit être postérieur à {other}",
+        "muss nach {other} liegen",
+        "deve ser posterior a {other}",
+        "{other} より後にしてください",
+    ]),
+    ("out_of_range", [
+        "must be between {min} and {max}",
+        "debe estar entre {min} y {max}",
+        "doit être compris entre {min} et {max}",
+        "muss zwischen {min} und {max} liegen",
+        "deve estar entre {min} e {max}",
+        "{min} から {max} の範囲にしてください",
+    ]),
+    ("at_least", [
+        "must be at least {min}",
+        "debe ser al menos {min}",
+        "doit être au moins {min}",
+        "muss mindestens {min} sein",
+        "deve ser pelo menos {min}",
+        "{min} 以上にしてください",
+    ]),
+    ("too_many_words", [
+        "must list at most {max} words, got {count}",
+        "debe incluir como máximo {max} palabras, pero tiene {count}",
+        "doit lister au plus {max} mots, {count} reçus",
+        "darf höchstens {max} Wörter enthalten, enthält aber {count}",
+        "deve listar no máximo {max} palavras, mas tem {count}",
+        "単語は {max} 個までです（{count} 個あります）",
+    ]),
+    ("not_positive", [
+        "must be a positive number",
+        "debe ser un número positivo",
+        "doit être un nombre positif",
+        "muss eine positive Zahl sein",
+        "deve ser um número positivo",
+        "正の数にしてください",
+    ]),
+    ("out_of_order", [
+        "char_start..char_end must be a range of the text after the previous word",
+        "char_start..char_end debe ser un rango del texto posterior a la palabra anterior",
+        "char_start..char_end doit désigner une partie du texte située après le mot précédent",
+        "char_start..char_end muss ein Bereich des Textes nach dem vorherigen Wort sein",
+        "char_start..char_end deve ser um intervalo do texto depois da palavra anterior",
+        "char_start..char_end は前の単語より後のテキスト範囲にしてください",
+    ]),
+    ("empty_list_id", [
+        "list ID must not be empty",
+        "el ID de la lista no debe estar vacío",
+        "l'identifiant de liste ne doit pas être vide",
+        "die Listen-ID darf nicht leer sein",
+        "o ID da lista não pode estar vazio",
+        "リスト ID は空にできません",
+    ]),
+    ("needs_known_words", [
+        "needs known words",
+        "necesita palabras conocidas",
+        "nécessite des mots connus",
+        "benötigt bekannte Wörter",
+        "precisa de palavras conhecidas",
+        "既知の単語が必要です",
+    ]),
+    ("excludes", [
+        "must be empty when {other} are given",
+        "debe estar vacío cuando se indica {other}",
+        "doit être vide lorsque {other} est fourni",
+        "muss leer sein, wenn {other} angegeben ist",
+        "deve estar vazio quando {other} é informado",
+        "{other} を指定する場合は空にしてください",
+    ]),
+    ("and_more", [
+        "{message} (and {count} more invalid fields)",
+        "{message} (y {count} campos no válidos más)",
+        "{message} (et {count} autres champs invalides)",
+        "{message} (und {count} weitere ungültige Felder)",
+        "{message} (e mais {count} campos inválidos)",
+        "{message}（ほか {count} 件の無効なフィールド）",
+    ]),
+];
+
+impl Message {
+    /// Stable, machine-readable: add new codes, but never rename or reuse them
+    pub fn code(&self) -> &'static str {
+        match self {
+            Message::Empty => "empty",
+            Message::TooLong { .. } => "too_long",
+            Message::UnknownLanguage { .. } => "unknown_language",
+            Message::NotFinite => "not_finite",
+            Message::Negative => "negative",
+            Message::NotAfter { .. } => "not_after",
+            Message::OutOfRange { .. } => "out_of_range",
+            Message::AtLeast { .. } => "at_least",
+            Message::TooManyWords { .. } => "too_many_words",
+            Message::NotPositive => "not_positive",
+            Message::OutOfOrder => "out_of_order",
+            Message::EmptyListId => "empty_list_id",
+            Message::NeedsKnownWords => "needs_known_words",
+            Message::Excludes { .. } => "excludes",
+            Message::AndMore { .. } => "and_more",
+        }
+    }
+
+    /// The values the template's `{placeholders}` stand for
+    pub fn params(&self) -> Map<String, Value> {
+        let params = match self {
+            Message::TooLong { max } => serde_json::json!({ "max": max }),
+            Message::UnknownLanguage { language } => serde_json::json!({ "language": language }),
+            Message::NotAfter { other } | Message::Excludes { other } => serde_json::json!({ "other": other }),
+            Message::OutOfRange { min, max } => serde_json::json!({ "min": min, "max": max }),
+            Message::AtLeast { min } => serde_json::json!({ "min": min }),
+            Message::TooManyWords { max, count } => serde_json::json!({ "max": max, "count": count }),
+            Message::AndMore { message, count } => serde_json::json!({ "message": message, "count": count }),
+            _ => serde_json::json!({}),
+        };
+        match params {
+            Value::Object(params) => params,
+            _ => Map::new(),
+        }
+    }
+
+    /// The message in `locale`
+    pub fn render(&self, locale: Locale) -> String {
+        let template = TEMPLATES.iter()
+            .find(|(code, _)| *code == self.code())
+            .map_or("", |(_, templates)| templates[locale.position()]);
+        self.params().iter().fold(template.to_string(), |text, (name, value)| {
+            let value = match value {
+                Value::String(value) => value.clone(),
+                Value::Number(number) => number.as_f64().map_or_else(|| number.to_string(), |number| number.to_string()),
+                value => value.to_string(),
+            };
+            text.replace(&format!("{{{}}}", name), &value)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(Locale::negotiate("pt-BR,pt;q=0.9,en;q=0.8"), Locale::Pt);
+        assert_eq!(Locale::negotiate("en;q=0.5, ja"), Locale::Ja);
+        assert_eq!(Locale::negotiate("ko, de;q=0.3"), Locale::De);
+        assert_eq!(Locale::negotiate("ko, fr;q=0"), Locale::En);
+        assert_eq!(Locale::negotiate(""), Locale::En);
+    }
+
+    #[test]
+    fn test_render() {
+        let message = Message::OutOfRange { min: 0.0, max: 1.0 };
+        assert_eq!(message.render(Locale::En), "must be between 0 and 1");
+        assert_eq!(message.render(Locale::Es), "debe estar entre 0 y 1");
+        assert_eq!(Message::TooLong { max: 5000 }.render(Locale::Ja), "5000 文字以内にしてください");
+        assert_eq!(Message::UnknownLanguage { language: "xx".to_string() }.params()["language"], "xx");
+    }
+
+    #[test]
+    fn test_every_code_has_templates() {
+        let messages = [
+            Message::Empty, Message::TooLong { max: 1 }, Message::UnknownLanguage { language: String::new() },
+            Message::NotFinite, Message::Negative, Message::NotAfter { other: String::new() },
+            Message::OutOfRange { min: 0.0, max: 1.0 }, Message::AtLeast { min: 1 }, Message::TooManyWords { max: 1, count: 2 },
+            Message::NotPositive, Message::OutOfOrder, Message::EmptyListId, Message::NeedsKnownWords,
+            Message::Excludes { other: String::new() }, Message::AndMore { message: String::new(), count: 1 },
+        ];
+        for message in messages {
+            for locale in LOCALES {
+                let text = message.render(locale);
+                assert!(!text.is_empty() && !text.contains('{'), "{:?} in {:?}: {}", message, locale, text);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scope() {
+        assert_eq!(current(), Locale::En);
+        assert_eq!(scope(Locale::Fr, async { current() }).await, Locale::Fr);
+    }
+}
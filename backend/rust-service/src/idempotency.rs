@@ -0,0 +1,336 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Mutex, OnceLock};
+use std::task::Poll;
+use std::time::{Duration, Instant};
+
+use actix_web::body::{self, BodySize, BodyStream, BoxBody, MessageBody};
+use actix_web::http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use actix_web::http::StatusCode;
+use actix_web::web::{Bytes, BytesMut};
+use actix_web::{HttpMessage, HttpRequest, HttpResponse};
+use sha2::{Digest, Sha256};
+
+use crate::auth::Client;
+use crate::error::{ApiError, ErrorCode};
+use crate::jobs;
+use crate::versioning;
+
+/// Request header naming a submission, so retries can be recognised
+pub const HEADER: &str = "idempotency-key";
+/// Set to `true` on responses replayed from an earlier request
+pub const REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// How long keys are remembered, unless configured: as long as finished
+/// jobs are kept
+pub const DEFAULT_IDEMPOTENCY_TTL_SECS: u64 = 60 * 60;
+
+const MAX_KEY_LENGTH: usize = 255;
+
+/// Keys remembered at once; past this, new keys are handled untracked
+const MAX_ENTRIES: usize = 10_000;
+
+/// What the original request produced
+#[derive(Debug, Clone)]
+enum Outcome {
+    /// A queued job; replays report its current status
+    Job(String),
+    Response { status: StatusCode, headers: HeaderMap, body: Bytes },
+}
+
+struct Entry {
+    /// Hash of the request, so a key reused for different work is caught
+    fingerprint: [u8; 32],
+    created: Instant,
+    /// `None` while the original request is still being handled
+    outcome: Option<Outcome>,
+}
+
+/// Recent `Idempotency-Key`s and their outcomes, in memory
+///
+/// Only successful submissions are remembered: when the original request
+/// fails, the key is released and a retry runs again.
+pub struct IdempotencyStore {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl IdempotencyStore {
+    pub fn new(ttl: Duration) -> Self {
+        IdempotencyStore { ttl, entries: Mutex::new(HashMap::new()) }
+    }
+
+    /// The original outcome for `key`, or `None` if this is the first request
+    /// (which then holds the key until `complete` or `release`)
+    ///
+    /// Outcomes that are no longer `live` count as never seen.
+    fn claim(&self, key: &str, fingerprint: [u8; 32], live: impl Fn(&Outcome) -> bool) -> Result<Option<Outcome>, ApiError> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, entry| entry.created.elapsed() < self.ttl);
+
+        match entries.get(key).filter(|entry| entry.outcome.as_ref().is_none_or(&live)) {
+            Some(entry) if entry.fingerprint != fingerprint => Err(
+                ApiError::invalid_input("Idempotency-Key was already used for a different request").with_field(HEADER)
+            ),
+            Some(Entry { outcome: None, .. }) => Err(
+                ApiError::new(ErrorCode::Conflict, "A request with this Idempotency-Key is still in progress").with_field(HEADER)
+            ),
+            Some(Entry { outcome: Some(outcome), .. }) => Ok(Some(outcome.clone())),
+            // Handled as usual; `complete` and `release` find nothing to update
+            None if entries.len() >= MAX_ENTRIES && !entries.contains_key(key) => {
+                log::warn!("Too many idempotency keys ({}), not tracking another", MAX_ENTRIES);
+                Ok(None)
+            }
+            None => {
+                entries.insert(key.to_string(), Entry { fingerprint, created: Instant::now(), outcome: None });
+                Ok(None)
+            }
+        }
+    }
+
+    fn complete(&self, key: &str, outcome: Outcome) {
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(key) {
+            entry.outcome = Some(outcome);
+        }
+    }
+
+    /// Forget a key whose request didn't finish, so it can be retried
+    fn release(&self, key: &str) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.get(key).is_some_and(|entry| entry.outcome.is_none()) {
+            entries.remove(key);
+        }
+    }
+}
+
+static STORE: OnceLock<Option<IdempotencyStore>> = OnceLock::new();
+
+/// Remember keys for `ttl`; `None` turns idempotency keys off
+pub fn init(ttl: Option<Duration>) {
+    match ttl {
+        Some(ttl) => log::info!("Remembering idempotency keys for {}s", ttl.as_secs()),
+        None => log::info!("Idempotency keys disabled"),
+    }
+    if STORE.set(ttl.map(IdempotencyStore::new)).is_err() {
+        log::warn!("Idempotency store already initialised");
+    }
+}
+
+fn store() -> Option<&'static IdempotencyStore> {
+    STORE.get_or_init(|| Some(IdempotencyStore::new(Duration::from_secs(DEFAULT_IDEMPOTENCY_TTL_SECS)))).as_ref()
+}
+
+/// Keys currently remembered, or `None` when keys are disabled
+pub fn len() -> Option<usize> {
+    store().map(|store| store.entries.lock().unwrap().len())
+}
+
+/// The first request with a key; releases the key if dropped before completing
+pub struct Pending {
+    store: &'static IdempotencyStore,
+    key: Option<String>,
+}
+
+impl Pending {
+    fn complete(mut self, outcome: Outcome) {
+        if let Some(key) = self.key.take() {
+            self.store.complete(&key, outcome);
+        }
+    }
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.store.release(&key);
+        }
+    }
+}
+
+/// Where a submission stands with respect to its `Idempotency-Key`
+pub enum Claim {
+    /// No key (or keys are disabled): handle the request as usual
+    Untracked,
+    /// First request with this key: handle it, then record the outcome
+    First(Pending),
+    /// Seen before: send this instead of doing the work again
+    Replay(HttpResponse),
+}
+
+/// Look up the request's `Idempotency-Key`
+///
+/// # How it works:
+/// 1. Keys are scoped to the API client, so clients can't see each other's results
+/// 2. The route, query, `Accept` and `body` are hashed; reusing a key for a
+///    different request is a 400, and retrying while the original is still
+///    running is a 409
+/// 3. A finished original is replayed with `Idempotent-Replayed: true`
+pub fn claim(req: &HttpRequest, body: &[u8]) -> Result<Claim, ApiError> {
+    let Some(value) = req.headers().get(HEADER) else {
+        return Ok(Claim::Untracked);
+    };
+    let Some(store) = store() else {
+        return Ok(Claim::Untracked);
+    };
+    let key = value.to_str().ok()
+        .map(str::trim)
+        .filter(|key| !key.is_empty() && key.len() <= MAX_KEY_LENGTH)
+        .ok_or_else(|| ApiError::invalid_input(format!("Idempotency-Key must be 1 to {} visible ASCII characters", MAX_KEY_LENGTH)).with_field(HEADER))?;
+
+    // Step 1: Scope
+    let client = req.extensions().get::<Client>().map(|client| client.to_string()).unwrap_or_default();
+    let key = format!("{}\n{}", client, key);
+
+    // Step 2: Fingerprint
+    let mut hasher = Sha256::new();
+    for part in [
+        req.method().as_str(),
+        versioning::api_path(req.path()).unwrap_or(req.path()),
+        req.query_string(),
+        req.headers().get(header::ACCEPT).and_then(|accept| accept.to_str().ok()).unwrap_or_default(),
+    ] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    hasher.update(body);
+    let fingerprint = hasher.finalize().into();
+
+    // Step 3: Replay
+    let live = |outcome: &Outcome| match outcome {
+        // Expired before the key did: start over
+        Outcome::Job(id) => jobs::store().get(id).is_some(),
+        Outcome::Response { .. } => true,
+    };
+    match store.claim(&key, fingerprint, live)? {
+        None => Ok(Claim::First(Pending { store, key: Some(key) })),
+        Some(Outcome::Job(id)) => {
+            let status = jobs::store().get(&id).ok_or_else(|| ApiError::not_found(format!("No job {}", id)))?;
+            Ok(Claim::Replay(replayed(HttpResponse::Accepted()).json(status)))
+        }
+        Some(Outcome::Response { status, headers, body }) => {
+            let mut res = replayed(HttpResponse::build(status)).body(body);
+            for (name, value) in &headers {
+                res.headers_mut().insert(name.clone(), value.clone());
+            }
+            Ok(Claim::Replay(res))
+        }
+    }
+}
+
+fn replayed(mut builder: actix_web::HttpResponseBuilder) -> actix_web::HttpResponseBuilder {
+    builder.insert_header((HeaderName::from_static(REPLAYED_HEADER), HeaderValue::from_static("true")));
+    builder
+}
+
+impl Claim {
+    /// Record a queued job; replays get its status at the time
+    pub fn job(self, id: &str) {
+        if let Claim::First(pending) = self {
+            pending.complete(Outcome::Job(id.to_string()));
+        }
+    }
+
+    /// Record a response to send again on replay
+    ///
+    /// Only successes are kept. Streamed bodies still stream: they're copied
+    /// as they go out, and remembered once the last chunk is sent.
+    pub async fn response(self, res: HttpResponse) -> Result<HttpResponse, ApiError> {
+        let Claim::First(pending) = self else {
+            return Ok(res);
+        };
+        if !res.status().is_success() {
+            return Ok(res);
+        }
+
+        let (res, body) = res.into_parts();
+        let status = res.status();
+        let headers = res.headers().clone();
+
+        if let BodySize::Sized(_) = body.size() {
+            let body = body::to_bytes(body).await
+                .map_err(|e| ApiError::internal(format!("Can't read response: {}", e)))?;
+            pending.complete(Outcome::Response { status, headers, body: body.clone() });
+            return Ok(res.set_body(BoxBody::new(body)));
+        }
+
+        let mut body = body;
+        let mut copy = BytesMut::new();
+        let mut pending = Some(pending);
+        let stream = futures::stream::poll_fn(move |cx| {
+            let next = Pin::new(&mut body).poll_next(cx);
+            match &next {
+                Poll::Ready(Some(Ok(chunk))) => copy.extend_from_slice(chunk),
+                Poll::Ready(None) => {
+                    if let Some(pending) = pending.take() {
+                        pending.complete(Outcome::Response { status, headers: headers.clone(), body: copy.split().freeze() });
+                    }
+                }
+                _ => {}
+            }
+            next
+        });
+        Ok(res.set_body(BoxBody::new(BodyStream::new(stream))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    fn leaked(ttl: Duration) -> &'static IdempotencyStore {
+        Box::leak(Box::new(IdempotencyStore::new(ttl)))
+    }
+
+    #[test]
+    fn test_replays_and_conflicts() {
+        let store = leaked(Duration::from_secs(60));
+
+        assert!(store.claim("a", [1; 32], |_| true).unwrap().is_none());
+        assert_eq!(store.claim("a", [1; 32], |_| true).unwrap_err().code, ErrorCode::Conflict);
+        assert_eq!(store.claim("a", [2; 32], |_| true).unwrap_err().code, ErrorCode::InvalidInput);
+
+        Pending { store, key: Some("a".to_string()) }.complete(Outcome::Job("job-1".to_string()));
+        assert!(matches!(store.claim("a", [1; 32], |_| true).unwrap(), Some(Outcome::Job(id)) if id == "job-1"));
+        // The job is gone: the key is free again
+        assert!(store.claim("a", [2; 32], |_| false).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_released_when_dropped() {
+        let store = leaked(Duration::from_secs(60));
+
+        assert!(store.claim("a", [1; 32], |_| true).unwrap().is_none());
+        drop(Pending { store, key: Some("a".to_string()) });
+        assert!(store.claim("a", [1; 32], |_| true).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_expires() {
+        let store = leaked(Duration::ZERO);
+
+        assert!(store.claim("a", [1; 32], |_| true).unwrap().is_none());
+        assert!(store.claim("a", [2; 32], |_| true).unwrap().is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_replays_response() {
+        let req = || TestRequest::post().uri("/api/v1/batch-align").insert_header((HEADER, "retry-me")).to_http_request();
+
+        assert!(matches!(claim(&TestRequest::post().to_http_request(), b"[]").unwrap(), Claim::Untracked));
+
+        let res = claim(&req(), b"[1]").unwrap()
+            .response(HttpResponse::Ok().content_type("application/json").body("[\"done\"]")).await
+            .unwrap();
+        assert!(res.headers().get(REPLAYED_HEADER).is_none());
+
+        let Claim::Replay(replay) = claim(&req(), b"[1]").unwrap() else {
+            panic!("expected a replay");
+        };
+        assert_eq!(replay.headers().get(REPLAYED_HEADER).unwrap(), "true");
+        assert_eq!(replay.headers().get(header::CONTENT_TYPE).unwrap(), "application/json");
+        assert_eq!(body::to_bytes(replay.into_body()).await.unwrap(), Bytes::from("[\"done\"]"));
+
+        assert!(claim(&req(), b"[2]").is_err());
+    }
+}
@@ -0,0 +1,350 @@
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use sqlx::any::{AnyPoolOptions, AnyRow};
+use sqlx::{AnyPool, Row};
+
+use super::list_limit;
+use crate::error::ApiError;
+use crate::models::{JobQuery, JobRequest, JobStatus};
+
+/// Plain SQL that SQLite and Postgres both accept
+const SCHEMA: [&str; 2] = [
+    "CREATE TABLE IF NOT EXISTS jobs (
+        id TEXT PRIMARY KEY,
+        kind TEXT NOT NULL,
+        state TEXT NOT NULL,
+        tenant TEXT,
+        file TEXT,
+        request TEXT NOT NULL,
+        callback_url TEXT,
+        processed BIGINT,
+        total BIGINT,
+        result TEXT,
+        error TEXT,
+        created_at BIGINT NOT NULL,
+        finished_at BIGINT,
+        owner TEXT,
+        lease_until BIGINT
+    )",
+    "CREATE INDEX IF NOT EXISTS jobs_tenant_created ON jobs (tenant, created_at)",
+];
+
+/// Columns added since the table was first created, for databases that
+/// predate them; fails harmlessly where the column exists
+const MIGRATIONS: [&str; 2] = [
+    "ALTER TABLE jobs ADD COLUMN owner TEXT",
+    "ALTER TABLE jobs ADD COLUMN lease_until BIGINT",
+];
+
+/// Everything `JobStatus` needs except `result`, which listings leave out
+const STATUS_COLUMNS: &str = "id, kind, state, tenant, file, processed, total, error, created_at, finished_at";
+
+/// Durable copy of the job store, so jobs and results survive restarts
+pub struct JobDb {
+    pool: AnyPool,
+}
+
+/// A queued or running job whose lease this instance took over
+pub struct Unfinished {
+    pub status: JobStatus,
+    pub request: JobRequest,
+    pub callback_url: Option<String>,
+}
+
+/// Bound values of a dynamically built query
+enum Param {
+    Text(String),
+    Int(i64),
+}
+
+impl JobDb {
+    /// Open `url` and create the table if it's missing
+    ///
+    /// `sqlite://jobs.db?mode=rwc` creates the file; `postgres://` URLs
+    /// need the `postgres` feature.
+    pub async fn connect(url: &str) -> Result<Self, String> {
+        sqlx::any::install_default_drivers();
+        let pool = AnyPoolOptions::new()
+            .max_connections(5)
+            .connect(url)
+            .await
+            .map_err(|e| format!("Can't open job database: {}", e))?;
+
+        for statement in SCHEMA {
+            sqlx::query(statement).execute(&pool).await
+                .map_err(|e| format!("Can't create the jobs table: {}", e))?;
+        }
+        for statement in MIGRATIONS {
+            let _ = sqlx::query(statement).execute(&pool).await;
+        }
+        Ok(JobDb { pool })
+    }
+
+    /// Store a new job, leased to `lease.0` until `lease.1`
+    pub async fn insert(&self, status: &JobStatus, request: &JobRequest, callback_url: Option<&str>, lease: (&str, u64)) -> Result<(), ApiError> {
+        sqlx::query("INSERT INTO jobs (id, kind, state, tenant, file, request, callback_url, created_at, owner, lease_until)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)")
+            .bind(&status.id)
+            .bind(to_name(&status.kind)?)
+            .bind(to_name(&status.state)?)
+            .bind(status.tenant.clone())
+            .bind(status.file.clone())
+            .bind(to_json(request)?)
+            .bind(callback_url.map(str::to_string))
+            .bind(status.created_at as i64)
+            .bind(lease.0)
+            .bind(lease.1 as i64)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    /// Record a job's state, progress and outcome
+    pub async fn update(&self, status: &JobStatus) -> Result<(), ApiError> {
+        sqlx::query("UPDATE jobs SET state = $2, processed = $3, total = $4, result = $5, error = $6, finished_at = $7
+            WHERE id = $1")
+            .bind(&status.id)
+            .bind(to_name(&status.state)?)
+            .bind(status.processed.map(|n| n as i64))
+            .bind(status.total.map(|n| n as i64))
+            .bind(status.result.as_ref().map(to_json).transpose()?)
+            .bind(status.error.as_ref().map(to_json).transpose()?)
+            .bind(status.finished_at.map(|at| at as i64))
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<JobStatus>, ApiError> {
+        let row = sqlx::query(&format!("SELECT {}, result FROM jobs WHERE id = $1", STATUS_COLUMNS))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        row.map(|row| {
+            let mut status = status_from_row(&row)?;
+            status.result = row.try_get::<Option<String>, _>("result").map_err(db_error)?
+                .map(|result| from_json(&result))
+                .transpose()?;
+            Ok(status)
+        }).transpose()
+    }
+
+    /// Take over up to `limit` queued or running jobs whose lease ran out
+    /// (their instance stopped or crashed), oldest first, leasing them to
+    /// `owner` until `lease_until`
+    ///
+    /// Each job is claimed with a conditional update, so when several
+    /// instances share the database only one of them gets it.
+    pub async fn claim(&self, owner: &str, now: u64, lease_until: u64, limit: usize) -> Result<Vec<Unfinished>, ApiError> {
+        let rows = sqlx::query(&format!("SELECT {}, request, callback_url FROM jobs
+            WHERE state IN ('queued', 'running') AND (lease_until IS NULL OR lease_until < $1)
+            ORDER BY created_at LIMIT {}", STATUS_COLUMNS, limit))
+            .bind(now as i64)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        let mut claimed = Vec::new();
+        for row in &rows {
+            let id: String = row.try_get("id").map_err(db_error)?;
+            let taken = sqlx::query("UPDATE jobs SET owner = $1, lease_until = $2
+                WHERE id = $3 AND (lease_until IS NULL OR lease_until < $4)")
+                .bind(owner)
+                .bind(lease_until as i64)
+                .bind(&id)
+                .bind(now as i64)
+                .execute(&self.pool)
+                .await
+                .map_err(db_error)?;
+            if taken.rows_affected() == 1 {
+                claimed.push(Unfinished {
+                    status: status_from_row(row)?,
+                    request: from_json(&row.try_get::<String, _>("request").map_err(db_error)?)?,
+                    callback_url: row.try_get("callback_url").map_err(db_error)?,
+                });
+            }
+        }
+        Ok(claimed)
+    }
+
+    /// Extend the lease on every unfinished job `owner` holds
+    pub async fn renew(&self, owner: &str, lease_until: u64) -> Result<(), ApiError> {
+        sqlx::query("UPDATE jobs SET lease_until = $2 WHERE owner = $1 AND state IN ('queued', 'running')")
+            .bind(owner)
+            .bind(lease_until as i64)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    /// Give up `owner`'s unfinished jobs, for another instance to claim now
+    pub async fn release(&self, owner: &str) -> Result<(), ApiError> {
+        sqlx::query("UPDATE jobs SET lease_until = 0 WHERE owner = $1 AND state IN ('queued', 'running')")
+            .bind(owner)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    /// Jobs matching `query`, newest first, without their results
+    pub async fn list(&self, query: &JobQuery) -> Result<Vec<JobStatus>, ApiError> {
+        let mut params = Vec::new();
+        let mut conditions = Vec::new();
+        let mut filter = |condition: &str, param: Param| {
+            params.push(param);
+            conditions.push(format!("{} ${}", condition, params.len()));
+        };
+
+        if let Some(tenant) = &query.tenant {
+            filter("tenant =", Param::Text(tenant.clone()));
+        }
+        if let Some(file) = &query.file {
+            filter("file =", Param::Text(file.clone()));
+        }
+        if let Some(state) = &query.state {
+            filter("state =", Param::Text(to_name(state)?));
+        }
+        if let Some(since) = query.since {
+            filter("created_at >=", Param::Int(since as i64));
+        }
+        if let Some(until) = query.until {
+            filter("created_at <", Param::Int(until as i64));
+        }
+
+        let mut sql = format!("SELECT {} FROM jobs", STATUS_COLUMNS);
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        sql.push_str(&format!(" ORDER BY created_at DESC, id LIMIT {}", list_limit(query)));
+
+        let mut statement = sqlx::query(&sql);
+        for param in params {
+            statement = match param {
+                Param::Text(text) => statement.bind(text),
+                Param::Int(int) => statement.bind(int),
+            };
+        }
+
+        let rows = statement.fetch_all(&self.pool).await.map_err(db_error)?;
+        rows.iter().map(status_from_row).collect()
+    }
+}
+
+fn status_from_row(row: &AnyRow) -> Result<JobStatus, ApiError> {
+    let count = |column: &str| row.try_get::<Option<i64>, _>(column).map(|n| n.map(|n| n as usize)).map_err(db_error);
+
+    Ok(JobStatus {
+        id: row.try_get("id").map_err(db_error)?,
+        kind: from_name(&row.try_get::<String, _>("kind").map_err(db_error)?)?,
+        state: from_name(&row.try_get::<String, _>("state").map_err(db_error)?)?,
+        tenant: row.try_get("tenant").map_err(db_error)?,
+        file: row.try_get("file").map_err(db_error)?,
+        processed: count("processed")?,
+        total: count("total")?,
+        result: None,
+        error: row.try_get::<Option<String>, _>("error").map_err(db_error)?
+            .map(|error| from_json(&error))
+            .transpose()?,
+        created_at: row.try_get::<i64, _>("created_at").map_err(db_error)? as u64,
+        finished_at: row.try_get::<Option<i64>, _>("finished_at").map_err(db_error)?.map(|at| at as u64),
+    })
+}
+
+/// A unit enum's serde name, e.g. `JobState::Running` as `running`
+fn to_name<T: Serialize>(value: &T) -> Result<String, ApiError> {
+    serde_json::to_value(value).ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .ok_or_else(|| ApiError::internal("Expected a unit enum"))
+}
+
+fn from_name<T: DeserializeOwned>(name: &str) -> Result<T, ApiError> {
+    serde_json::from_value(serde_json::Value::String(name.to_string()))
+        .map_err(|e| ApiError::internal(format!("Unexpected value '{}' in job database: {}", name, e)))
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String, ApiError> {
+    serde_json::to_string(value).map_err(|e| ApiError::internal(format!("Failed to serialize job: {}", e)))
+}
+
+fn from_json<T: DeserializeOwned>(json: &str) -> Result<T, ApiError> {
+    serde_json::from_str(json).map_err(|e| ApiError::internal(format!("Corrupt job in database: {}", e)))
+}
+
+fn db_error(e: sqlx::Error) -> ApiError {
+    log::error!("Job database error: {}", e);
+    ApiError::internal("Job database unavailable")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AlignmentRequest, JobKind, JobState};
+
+    fn status(id: &str, tenant: Option<&str>, created_at: u64) -> JobStatus {
+        JobStatus {
+            id: id.to_string(),
+            kind: JobKind::Align,
+            state: JobState::Queued,
+            tenant: tenant.map(str::to_string),
+            file: Some("ep1.srt".to_string()),
+            processed: None,
+            total: None,
+            result: None,
+            error: None,
+            created_at,
+            finished_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let path = std::env::temp_dir().join(format!("dubdub-jobs-test-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let db = JobDb::connect(&format!("sqlite://{}?mode=rwc", path.display())).await.unwrap();
+        let request = JobRequest::Align(AlignmentRequest { text: "Hello".to_string(), ..Default::default() });
+
+        db.insert(&status("a", Some("acme"), 100), &request, Some("https://example.com/hook"), ("old", 1000)).await.unwrap();
+        db.insert(&status("b", Some("acme"), 200), &request, None, ("old", 1000)).await.unwrap();
+        db.insert(&status("c", None, 300), &request, None, ("old", 1000)).await.unwrap();
+
+        let mut done = status("a", Some("acme"), 100);
+        done.state = JobState::Succeeded;
+        done.result = Some(serde_json::json!({ "words": 1 }));
+        done.finished_at = Some(150);
+        db.update(&done).await.unwrap();
+
+        let stored = db.get("a").await.unwrap().unwrap();
+        assert_eq!(stored.state, JobState::Succeeded);
+        assert_eq!(stored.result, Some(serde_json::json!({ "words": 1 })));
+        assert_eq!(stored.tenant.as_deref(), Some("acme"));
+        assert!(db.get("z").await.unwrap().is_none());
+
+        // Leased until 1000: nothing to claim before then, and only once after
+        assert!(db.claim("new", 999, 2000, 10).await.unwrap().is_empty());
+        let unfinished = db.claim("new", 1001, 2000, 10).await.unwrap();
+        assert_eq!(unfinished.iter().map(|job| job.status.id.as_str()).collect::<Vec<_>>(), ["b", "c"]);
+        assert!(matches!(&unfinished[0].request, JobRequest::Align(req) if req.text == "Hello"));
+        assert!(db.claim("other", 1001, 2000, 10).await.unwrap().is_empty());
+
+        // Renewed leases hold, released ones go to whoever claims next, up to the limit
+        db.renew("new", 3000).await.unwrap();
+        assert!(db.claim("other", 2500, 4000, 10).await.unwrap().is_empty());
+        db.release("new").await.unwrap();
+        assert_eq!(db.claim("other", 2500, 4000, 1).await.unwrap().len(), 1);
+
+        let query = JobQuery { tenant: Some("acme".to_string()), since: Some(150), ..Default::default() };
+        let listed = db.list(&query).await.unwrap();
+        assert_eq!(listed.iter().map(|job| job.id.as_str()).collect::<Vec<_>>(), ["b"]);
+
+        let all = db.list(&JobQuery { file: Some("ep1.srt".to_string()), ..Default::default() }).await.unwrap();
+        assert_eq!(all.iter().map(|job| job.id.as_str()).collect::<Vec<_>>(), ["c", "b", "a"]);
+        assert!(all[2].result.is_none());
+    }
+}
@@ -0,0 +1,619 @@
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use tokio::sync::Semaphore;
+use tracing::Instrument;
+
+use crate::aligner;
+use crate::error::{ApiError, ErrorCode};
+use crate::models::{AlignmentMode, JobKind, JobQuery, JobRequest, JobState, JobStatus, JobSubmission};
+use crate::tts;
+use crate::webhooks;
+
+mod db;
+
+use db::JobDb;
+
+/// Jobs running at once; the rest wait as `queued`
+const MAX_RUNNING: usize = 4;
+
+/// How long finished jobs stay available for `GET /api/jobs/{id}`
+const RETENTION: Duration = Duration::from_secs(60 * 60);
+
+/// Unfinished jobs accepted before new submissions are refused
+const MAX_PENDING: usize = 1000;
+
+/// How often `drain` checks for running jobs
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// How long a stored job stays with the instance that runs it without that
+/// instance renewing the lease; after that, any instance may take it over
+const LEASE: Duration = Duration::from_secs(60);
+
+/// How often leases are renewed and expired ones claimed
+const LEASE_RENEWAL: Duration = Duration::from_secs(20);
+
+/// Jobs listed when the query doesn't say
+const DEFAULT_LIST_LIMIT: usize = 100;
+
+/// Most jobs one listing returns
+const MAX_LIST_LIMIT: usize = 1000;
+
+static STORE: LazyLock<JobStore> = LazyLock::new(JobStore::new);
+
+/// The process-wide job store
+pub fn store() -> &'static JobStore {
+    &STORE
+}
+
+/// Keep jobs in the database at `database_url` (`JOB_DATABASE_URL`), if set
+///
+/// Each stored job is leased to the instance running it, which renews the
+/// lease while it lives. Jobs whose lease ran out (the instance stopped or
+/// crashed) are claimed by one instance and started again from the
+/// beginning, no more than `MAX_PENDING` allows. Must be called from within
+/// a Tokio runtime.
+pub async fn init(database_url: Option<&str>) -> Result<(), String> {
+    let Some(url) = database_url else {
+        log::info!("No JOB_DATABASE_URL configured, jobs are kept in memory only");
+        return Ok(());
+    };
+
+    let db = JobDb::connect(url).await?;
+    if STORE.db.set(db).is_err() {
+        log::warn!("Job database already initialised");
+        return Ok(());
+    }
+    log::info!("Job database connected");
+
+    STORE.claim_expired().await.map_err(|e| e.to_string())?;
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(LEASE_RENEWAL);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            if let Err(e) = STORE.renew_leases().await {
+                log::warn!("Can't renew job leases: {}", e);
+            }
+            if let Err(e) = STORE.claim_expired().await {
+                log::warn!("Can't claim unfinished jobs: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Whether a caller of `tenant` may see or cancel the job: with a tenant,
+/// only that tenant's jobs, without one, all of them (as `list` does)
+pub fn visible_to(status: &JobStatus, tenant: Option<&str>) -> bool {
+    tenant.is_none_or(|tenant| status.tenant.as_deref() == Some(tenant))
+}
+
+struct Job {
+    status: JobStatus,
+    callback_url: Option<String>,
+    cancelled: Arc<AtomicBool>,
+    finished: Option<Instant>,
+}
+
+/// Queue of long-running work
+///
+/// Live state is kept in memory; callers poll `get` until the state is
+/// finished. With a database (see `init`), every job is also written there
+/// as it's submitted, starts and finishes, so it survives restarts and
+/// stays available after it expires from memory.
+pub struct JobStore {
+    jobs: Mutex<HashMap<String, Job>>,
+    db: OnceLock<JobDb>,
+    /// This instance, as the owner of the job leases it holds
+    instance: String,
+    permits: Semaphore,
+    /// Set on shutdown: no new submissions
+    closed: AtomicBool,
+    /// Spawned jobs not yet done, including their callback
+    active: AtomicUsize,
+}
+
+impl JobStore {
+    fn new() -> Self {
+        JobStore {
+            jobs: Mutex::new(HashMap::new()),
+            db: OnceLock::new(),
+            instance: uuid::Uuid::new_v4().to_string(),
+            permits: Semaphore::new(MAX_RUNNING),
+            closed: AtomicBool::new(false),
+            active: AtomicUsize::new(0),
+        }
+    }
+
+    /// Queue a job and start it as soon as a worker is free
+    ///
+    /// If `callback_url` is given, the final status is POSTed there (see
+    /// `webhooks::deliver`). With a database, the job is stored before
+    /// this returns. Must be called from within a Tokio runtime.
+    pub async fn submit(&'static self, submission: JobSubmission, tenant: Option<String>) -> Result<JobStatus, ApiError> {
+        let JobSubmission { job: request, callback_url, file } = submission;
+        if self.closed.load(Ordering::Relaxed) {
+            return Err(ApiError::new(ErrorCode::Unavailable, "Shutting down, not accepting new jobs"));
+        }
+        if let Some(url) = &callback_url {
+            webhooks::validate_url(url).await?;
+        }
+
+        let kind = match &request {
+            JobRequest::AlignFile(_) => JobKind::AlignFile,
+            JobRequest::Align(_) => JobKind::Align,
+        };
+        let status = JobStatus {
+            id: uuid::Uuid::new_v4().to_string(),
+            kind,
+            state: JobState::Queued,
+            tenant,
+            file,
+            processed: None,
+            total: None,
+            result: None,
+            error: None,
+            created_at: unix_now(),
+            finished_at: None,
+        };
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        {
+            let mut jobs = self.jobs.lock().unwrap();
+            jobs.retain(|_, job| job.finished.is_none_or(|at| at.elapsed() < RETENTION));
+
+            let pending = jobs.values().filter(|job| job.finished.is_none()).count();
+            if pending >= MAX_PENDING {
+                return Err(ApiError::new(ErrorCode::Unavailable, format!("Too many pending jobs ({}), try again later", pending)));
+            }
+
+            jobs.insert(status.id.clone(), Job {
+                status: status.clone(),
+                callback_url: callback_url.clone(),
+                cancelled: cancelled.clone(),
+                finished: None,
+            });
+        }
+
+        if let Some(db) = self.db.get()
+            && let Err(e) = db.insert(&status, &request, callback_url.as_deref(), (&self.instance, lease_until())).await
+        {
+            self.jobs.lock().unwrap().remove(&status.id);
+            return Err(e);
+        }
+
+        self.spawn(status.id.clone(), request, cancelled);
+        Ok(status)
+    }
+
+    /// Claim stored jobs whose lease ran out and queue them again, as many
+    /// as fit under `MAX_PENDING`
+    async fn claim_expired(&'static self) -> Result<(), ApiError> {
+        let Some(db) = self.db.get() else {
+            return Ok(());
+        };
+        let room = MAX_PENDING.saturating_sub(self.pending());
+        if self.closed.load(Ordering::Relaxed) || room == 0 {
+            return Ok(());
+        }
+
+        for job in db.claim(&self.instance, unix_now(), lease_until(), room).await? {
+            log::info!("Resuming job {}, its lease ran out", job.status.id);
+            self.resume(job);
+        }
+        Ok(())
+    }
+
+    /// Keep the jobs this instance runs from being claimed by another
+    async fn renew_leases(&self) -> Result<(), ApiError> {
+        match self.db.get() {
+            Some(db) => db.renew(&self.instance, lease_until()).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Queue a stored job again, from the start
+    fn resume(&'static self, job: db::Unfinished) {
+        let mut status = job.status;
+        status.state = JobState::Queued;
+        status.processed = None;
+        status.total = None;
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        self.jobs.lock().unwrap().insert(status.id.clone(), Job {
+            status: status.clone(),
+            callback_url: job.callback_url,
+            cancelled: cancelled.clone(),
+            finished: None,
+        });
+        self.spawn(status.id, job.request, cancelled);
+    }
+
+    fn spawn(&'static self, id: String, request: JobRequest, cancelled: Arc<AtomicBool>) {
+        let span = tracing::info_span!("job", id = %id);
+        self.active.fetch_add(1, Ordering::SeqCst);
+        tokio::spawn(async move {
+            self.run(id, request, cancelled).await;
+            self.active.fetch_sub(1, Ordering::SeqCst);
+        }.instrument(span));
+    }
+
+    /// Jobs queued or running
+    pub fn pending(&self) -> usize {
+        self.jobs.lock().unwrap().values().filter(|job| job.finished.is_none()).count()
+    }
+
+    /// Jobs held in memory, finished ones included until they expire
+    pub fn retained(&self) -> usize {
+        self.jobs.lock().unwrap().len()
+    }
+
+    /// Whether jobs are also kept in a database
+    pub fn is_persistent(&self) -> bool {
+        self.db.get().is_some()
+    }
+
+    /// Whether `submit` would take another job
+    pub fn is_accepting(&self) -> bool {
+        !self.closed.load(Ordering::Relaxed) && self.pending() < MAX_PENDING
+    }
+
+    /// Refuse new jobs; those already accepted keep going
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+    }
+
+    /// Wait for accepted jobs (and their callbacks) to finish
+    ///
+    /// Returns how many were still going when `timeout` ran out. Their
+    /// leases are released, for another instance to take them over now
+    /// rather than once the lease runs out.
+    pub async fn drain(&self, timeout: Duration) -> usize {
+        let deadline = Instant::now() + timeout;
+        let active = loop {
+            let active = self.active.load(Ordering::SeqCst);
+            if active == 0 || Instant::now() >= deadline {
+                break active;
+            }
+            tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+        };
+
+        if active > 0
+            && let Some(db) = self.db.get()
+            && let Err(e) = db.release(&self.instance).await
+        {
+            log::warn!("Can't release job leases: {}", e);
+        }
+        active
+    }
+
+    /// A job still held in memory
+    pub fn get(&self, id: &str) -> Option<JobStatus> {
+        self.jobs.lock().unwrap().get(id).map(|job| job.status.clone())
+    }
+
+    /// Like `get`, then falling back to the database for jobs that expired
+    /// from memory or finished before a restart
+    pub async fn lookup(&self, id: &str) -> Result<Option<JobStatus>, ApiError> {
+        match (self.get(id), self.db.get()) {
+            (Some(status), _) => Ok(Some(status)),
+            (None, Some(db)) => db.get(id).await,
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Jobs matching `query`, newest first, without their results
+    ///
+    /// From the database when there is one, otherwise from the jobs still
+    /// in memory.
+    pub async fn list(&self, query: &JobQuery) -> Result<Vec<JobStatus>, ApiError> {
+        if let Some(db) = self.db.get() {
+            return db.list(query).await;
+        }
+
+        let mut statuses: Vec<JobStatus> = self.jobs.lock().unwrap().values()
+            .map(|job| &job.status)
+            .filter(|status| matches(status, query))
+            .map(|status| JobStatus { result: None, ..status.clone() })
+            .collect();
+        statuses.sort_by(|a, b| b.created_at.cmp(&a.created_at).then_with(|| a.id.cmp(&b.id)));
+        statuses.truncate(list_limit(query));
+        Ok(statuses)
+    }
+
+    /// Ask a job to stop
+    ///
+    /// Queued jobs never start; running file alignments stop after the
+    /// current cue. Finished jobs are left as they are, including those
+    /// only in the database (see `lookup`).
+    pub async fn cancel(&self, id: &str) -> Result<Option<JobStatus>, ApiError> {
+        let status = self.jobs.lock().unwrap().get(id).map(|job| {
+            if !job.status.state.is_finished() {
+                job.cancelled.store(true, Ordering::Relaxed);
+            }
+            job.status.clone()
+        });
+        let Some(status) = status else {
+            return self.lookup(id).await;
+        };
+        if status.state.is_finished() {
+            return Ok(Some(status));
+        }
+
+        // A queued job has nothing to interrupt, so it is done right away
+        if status.state == JobState::Queued {
+            self.finish(id, Err(ApiError::new(ErrorCode::Cancelled, "Cancelled")), true);
+        }
+        Ok(self.get(id))
+    }
+
+    async fn run(&'static self, id: String, request: JobRequest, cancelled: Arc<AtomicBool>) {
+        let Ok(permit) = self.permits.acquire().await else {
+            return;
+        };
+        // Cancelled while queued: already finished by `cancel`
+        if cancelled.load(Ordering::Relaxed) {
+            drop(permit);
+            self.persist(&id).await;
+            self.notify(&id).await;
+            return;
+        }
+        self.update(&id, |status| status.state = JobState::Running);
+        self.persist(&id).await;
+
+        let result = match request {
+            JobRequest::AlignFile(req) => {
+                let job_id = id.clone();
+                let flag = cancelled.clone();
+                let span = tracing::Span::current();
+                let task = tokio::task::spawn_blocking(move || {
+                    let _entered = span.enter();
+                    aligner::align_file_with_progress(&req, |_, processed, total| {
+                        self.update(&job_id, |status| {
+                            status.processed = Some(processed);
+                            status.total = Some(total);
+                        });
+                        if flag.load(Ordering::Relaxed) {
+                            ControlFlow::Break(())
+                        } else {
+                            ControlFlow::Continue(())
+                        }
+                    })
+                });
+                match task.await {
+                    Ok(result) => result.and_then(|response| to_json(&response)),
+                    Err(e) => Err(ApiError::internal(format!("Worker failed: {}", e))),
+                }
+            }
+            JobRequest::Align(req) => {
+                let result = match req.mode {
+                    AlignmentMode::Tts => tts::align_tts(&req).await,
+                    AlignmentMode::Subtitle => aligner::align_smart(&req),
+                };
+                result.and_then(|response| to_json(&response))
+            }
+        };
+
+        self.finish(&id, result, cancelled.load(Ordering::Relaxed));
+        drop(permit);
+        self.persist(&id).await;
+        self.notify(&id).await;
+    }
+
+    /// Write the job's current status to the database, if there is one
+    ///
+    /// A failed write is logged, not fatal: the job itself still completes.
+    async fn persist(&self, id: &str) {
+        let Some(db) = self.db.get() else {
+            return;
+        };
+        let Some(status) = self.get(id) else {
+            return;
+        };
+        if let Err(e) = db.update(&status).await {
+            log::warn!("Couldn't store job {}: {}", id, e);
+        }
+    }
+
+    /// Send the final status to the job's callback URL, if it has one
+    async fn notify(&self, id: &str) {
+        let callback = self.jobs.lock().unwrap().get(id)
+            .and_then(|job| Some((job.callback_url.clone()?, job.status.clone())));
+
+        if let Some((url, status)) = callback {
+            webhooks::deliver(&url, &status).await;
+        }
+    }
+
+    fn update(&self, id: &str, apply: impl FnOnce(&mut JobStatus)) {
+        if let Some(job) = self.jobs.lock().unwrap().get_mut(id) {
+            apply(&mut job.status);
+        }
+    }
+
+    fn finish(&self, id: &str, result: Result<serde_json::Value, ApiError>, cancelled: bool) {
+        let mut jobs = self.jobs.lock().unwrap();
+        let Some(job) = jobs.get_mut(id) else {
+            return;
+        };
+        if job.status.state.is_finished() {
+            return;
+        }
+
+        match result {
+            _ if cancelled => job.status.state = JobState::Cancelled,
+            Ok(value) => {
+                job.status.state = JobState::Succeeded;
+                job.status.result = Some(value);
+            }
+            Err(e) => {
+                log::warn!("Job {} failed: {}", id, e);
+                job.status.state = JobState::Failed;
+                job.status.error = Some(e);
+            }
+        }
+        job.status.finished_at = Some(unix_now());
+        job.finished = Some(Instant::now());
+    }
+}
+
+fn matches(status: &JobStatus, query: &JobQuery) -> bool {
+    query.tenant.as_ref().is_none_or(|tenant| status.tenant.as_ref() == Some(tenant))
+        && query.file.as_ref().is_none_or(|file| status.file.as_ref() == Some(file))
+        && query.state.is_none_or(|state| status.state == state)
+        && query.since.is_none_or(|since| status.created_at >= since)
+        && query.until.is_none_or(|until| status.created_at < until)
+}
+
+fn list_limit(query: &JobQuery) -> usize {
+    query.limit.unwrap_or(DEFAULT_LIST_LIMIT).clamp(1, MAX_LIST_LIMIT)
+}
+
+fn to_json(value: &impl serde::Serialize) -> Result<serde_json::Value, ApiError> {
+    serde_json::to_value(value).map_err(|e| ApiError::internal(format!("Failed to serialize result: {}", e)))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// When a lease taken or renewed now runs out
+fn lease_until() -> u64 {
+    unix_now() + LEASE.as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AlignmentRequest, Cue, FileAlignmentRequest, OverlapPolicy};
+
+    fn submission(job: JobRequest) -> JobSubmission {
+        JobSubmission { job, callback_url: None, file: None }
+    }
+
+    #[test]
+    fn test_visible_to() {
+        let status = |tenant: Option<&str>| JobStatus {
+            id: "a".to_string(),
+            kind: JobKind::Align,
+            state: JobState::Queued,
+            tenant: tenant.map(str::to_string),
+            file: None,
+            processed: None,
+            total: None,
+            result: None,
+            error: None,
+            created_at: 0,
+            finished_at: None,
+        };
+        assert!(visible_to(&status(Some("acme")), None));
+        assert!(visible_to(&status(Some("acme")), Some("acme")));
+        assert!(!visible_to(&status(Some("acme")), Some("globex")));
+        assert!(!visible_to(&status(None), Some("acme")));
+    }
+
+    async fn wait_until_finished(id: &str) -> JobStatus {
+        for _ in 0..200 {
+            let status = store().get(id).unwrap();
+            if status.state.is_finished() {
+                return status;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("Job {} did not finish", id);
+    }
+
+    #[tokio::test]
+    async fn test_file_alignment_job() {
+        let request = JobRequest::AlignFile(FileAlignmentRequest {
+            language: "en".to_string(),
+            cues: vec![
+                Cue { index: 1, start: 0.0, end: 1.0, text: "Hello there".to_string(), ..Default::default() },
+                Cue { index: 2, start: 1.0, end: 2.0, text: "General Kenobi".to_string(), ..Default::default() },
+            ],
+            overlap_policy: OverlapPolicy::Clamp,
+        });
+
+        let submitted = store().submit(submission(request), None).await.unwrap();
+        assert_eq!(submitted.state, JobState::Queued);
+
+        let status = wait_until_finished(&submitted.id).await;
+        assert_eq!(status.state, JobState::Succeeded);
+        assert_eq!(status.processed, Some(2));
+        assert_eq!(status.result.unwrap()["cues"].as_array().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_failed_job_reports_error() {
+        let request = JobRequest::Align(AlignmentRequest {
+            text: "   ".to_string(),
+            language: "en".to_string(),
+            subtitle_start: 0.0,
+            subtitle_end: 1.0,
+            ..Default::default()
+        });
+
+        let submitted = store().submit(submission(request), None).await.unwrap();
+        let status = wait_until_finished(&submitted.id).await;
+
+        assert_eq!(status.state, JobState::Failed);
+        assert!(status.error.is_some());
+        assert!(status.finished_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_bad_callback_url() {
+        let request = JobRequest::Align(AlignmentRequest::default());
+        let submitted = JobSubmission { callback_url: Some("file:///etc/passwd".to_string()), ..submission(request) };
+        assert!(store().submit(submitted, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_close_and_drain() {
+        // A separate store, so closing it doesn't affect the other tests
+        let store: &'static JobStore = Box::leak(Box::new(JobStore::new()));
+        let request = || JobRequest::Align(AlignmentRequest {
+            text: "Hello there".to_string(),
+            language: "en".to_string(),
+            subtitle_start: 0.0,
+            subtitle_end: 1.0,
+            ..Default::default()
+        });
+
+        let submitted = store.submit(submission(request()), None).await.unwrap();
+        store.close();
+        assert!(!store.is_accepting());
+        assert_eq!(store.submit(submission(request()), None).await.unwrap_err().code, ErrorCode::Unavailable);
+
+        assert_eq!(store.drain(Duration::from_secs(5)).await, 0);
+        assert_eq!(store.get(&submitted.id).unwrap().state, JobState::Succeeded);
+        assert_eq!(store.pending(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_list_filters_by_tenant_and_file() {
+        let store: &'static JobStore = Box::leak(Box::new(JobStore::new()));
+        let request = || JobRequest::Align(AlignmentRequest::default());
+        let file = |name: &str| JobSubmission { file: Some(name.to_string()), ..submission(request()) };
+
+        let ours = store.submit(file("ep1.srt"), Some("acme".to_string())).await.unwrap();
+        store.submit(file("ep2.srt"), Some("acme".to_string())).await.unwrap();
+        store.submit(file("ep1.srt"), Some("globex".to_string())).await.unwrap();
+
+        let query = JobQuery { tenant: Some("acme".to_string()), file: Some("ep1.srt".to_string()), ..Default::default() };
+        let listed = store.list(&query).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].id, ours.id);
+        assert_eq!(store.list(&JobQuery { limit: Some(2), ..Default::default() }).await.unwrap().len(), 2);
+        assert!(store.lookup(&ours.id).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_unknown_job() {
+        assert!(store().cancel("no-such-job").await.unwrap().is_none());
+    }
+}
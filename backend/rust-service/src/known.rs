@@ -0,0 +1,231 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use actix_web::{HttpMessage, HttpRequest};
+
+use crate::auth::Client;
+use crate::dictionary;
+use crate::error::{ApiError, ErrorCode};
+use crate::models::KnownWords;
+
+/// Words per list, inline or registered
+pub const MAX_KNOWN_WORDS: usize = 50_000;
+
+/// Lists registered at once, across all clients
+const MAX_LISTS: usize = 10_000;
+
+/// Lists one client has registered at once
+const MAX_LISTS_PER_OWNER: usize = 100;
+
+/// Bytes of words in all registered lists together
+const MAX_TOTAL_BYTES: usize = 256 * 1024 * 1024;
+
+/// Lists nobody has used for this long are forgotten
+const IDLE_EXPIRY: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Lemmas a learner knows, in one language
+#[derive(Debug, Default)]
+pub struct KnownSet {
+    language: String,
+    lemmas: HashSet<String>,
+}
+
+impl KnownSet {
+    pub fn new(words: &[String], language: &str) -> Self {
+        let lemmas = words.iter()
+            .map(|word| normalize(word.trim()))
+            .filter(|word| !word.is_empty())
+            .collect();
+        KnownSet { language: language.to_string(), lemmas }
+    }
+
+    /// Whether `word` is known: listed itself, or an inflection of a listed
+    /// lemma according to the language's dictionary ("ran" for "run")
+    ///
+    /// Tokens without letters (numbers, symbols) have nothing to learn and
+    /// count as known.
+    pub fn contains(&self, word: &str) -> bool {
+        if !word.chars().any(char::is_alphabetic) || self.lemmas.contains(&normalize(word)) {
+            return true;
+        }
+        dictionary::dictionaries().get(&self.language)
+            .is_some_and(|dictionary| dictionary.lemmas(word).iter().any(|lemma| self.lemmas.contains(&normalize(lemma))))
+    }
+
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    /// Words in alphabetical order
+    pub fn words(&self) -> Vec<String> {
+        let mut words: Vec<String> = self.lemmas.iter().cloned().collect();
+        words.sort();
+        words
+    }
+
+    pub fn len(&self) -> usize {
+        self.lemmas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lemmas.is_empty()
+    }
+
+    /// Bytes of words held
+    fn bytes(&self) -> usize {
+        self.lemmas.iter().map(String::len).sum()
+    }
+}
+
+/// Lowercase and use a plain apostrophe so "Don’t" matches "don't"
+fn normalize(word: &str) -> String {
+    word.to_lowercase().replace('’', "'")
+}
+
+/// Registered lists, by owner and ID, in memory
+type Lists = Mutex<HashMap<(String, String), Registered>>;
+
+struct Registered {
+    set: Arc<KnownSet>,
+    bytes: usize,
+    used: Instant,
+}
+
+static LISTS: OnceLock<Lists> = OnceLock::new();
+
+fn lists() -> &'static Lists {
+    LISTS.get_or_init(Lists::default)
+}
+
+/// Who registered a list: the API key or token's client, so clients can't
+/// read or replace each other's lists
+///
+/// Without authentication, the caller's address stands in, so anonymous
+/// callers don't share one set of lists (or one per-owner cap).
+pub fn owner(req: &HttpRequest) -> String {
+    match req.extensions().get::<Client>() {
+        Some(client) => client.to_string(),
+        None => format!("anonymous:{}", req.peer_addr().map(|addr| addr.ip().to_string()).unwrap_or_default()),
+    }
+}
+
+/// Store `set` under `id`, replacing any list `owner` had there; returns
+/// whether one was replaced
+///
+/// Lists idle for `IDLE_EXPIRY` are dropped first. An owner may keep
+/// `MAX_LISTS_PER_OWNER` lists; all owners together `MAX_LISTS` lists of
+/// `MAX_TOTAL_BYTES`.
+pub fn register(owner: &str, id: &str, set: KnownSet) -> Result<bool, ApiError> {
+    let mut lists = lists().lock().unwrap();
+    lists.retain(|_, list| list.used.elapsed() < IDLE_EXPIRY);
+
+    let key = (owner.to_string(), id.to_string());
+    let replacing = lists.get(&key).map(|list| list.bytes);
+    if replacing.is_none() {
+        if lists.keys().filter(|(listed, _)| listed == owner).count() >= MAX_LISTS_PER_OWNER {
+            return Err(ApiError::new(ErrorCode::QuotaExceeded,
+                format!("At most {} known-word lists per client, delete one first", MAX_LISTS_PER_OWNER)));
+        }
+        if lists.len() >= MAX_LISTS {
+            log::warn!("Too many known-word lists ({}), not registering another", MAX_LISTS);
+            return Err(ApiError::new(ErrorCode::Unavailable, "Too many known-word lists registered, try again later"));
+        }
+    }
+
+    let bytes = set.bytes();
+    let held: usize = lists.values().map(|list| list.bytes).sum::<usize>() - replacing.unwrap_or(0);
+    if held + bytes > MAX_TOTAL_BYTES {
+        log::warn!("Known-word lists hold {} bytes, not registering {} more", held, bytes);
+        return Err(ApiError::new(ErrorCode::Unavailable, "Known-word lists are using all their memory, try again later"));
+    }
+
+    let list = Registered { set: Arc::new(set), bytes, used: Instant::now() };
+    Ok(lists.insert(key, list).is_some())
+}
+
+/// A registered list, which then counts as used
+pub fn get(owner: &str, id: &str) -> Option<Arc<KnownSet>> {
+    let mut lists = lists().lock().unwrap();
+    let list = lists.get_mut(&(owner.to_string(), id.to_string())).filter(|list| list.used.elapsed() < IDLE_EXPIRY)?;
+    list.used = Instant::now();
+    Some(list.set.clone())
+}
+
+/// Forget a list; returns whether there was one
+pub fn remove(owner: &str, id: &str) -> bool {
+    lists().lock().unwrap().remove(&(owner.to_string(), id.to_string())).is_some()
+}
+
+/// Lists registered at once
+pub fn len() -> usize {
+    lists().lock().unwrap().len()
+}
+
+/// The words a request lists, or the registered list it names
+///
+/// A registered list must be for the request's language (or its primary
+/// subtag: an `es` list serves `es-MX`).
+pub fn resolve(known: &KnownWords, owner: &str, language: &str) -> Result<Arc<KnownSet>, ApiError> {
+    match known {
+        KnownWords::Words(words) => Ok(Arc::new(KnownSet::new(words, language))),
+        KnownWords::List(id) => {
+            let set = get(owner, id)
+                .ok_or_else(|| ApiError::not_found(format!("No known-word list '{}'", id)).with_field("known"))?;
+            let primary = |code: &str| code.split(['-', '_']).next().unwrap_or_default().to_lowercase();
+            if primary(set.language()) != primary(language) {
+                return Err(ApiError::invalid_input(format!("Known-word list '{}' is for '{}', not '{}'", id, set.language(), language))
+                    .with_field("known"));
+            }
+            Ok(set)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contains() {
+        let set = KnownSet::new(&["Don't".to_string(), " run ".to_string(), "".to_string()], "en");
+        assert_eq!(set.len(), 2);
+        assert!(set.contains("RUN"));
+        assert!(set.contains("don’t"));
+        assert!(set.contains("42"));
+        assert!(!set.contains("runs"));
+        assert_eq!(set.words(), vec!["don't", "run"]);
+    }
+
+    #[test]
+    fn test_registered_lists() {
+        let words = vec!["hola".to_string()];
+        assert!(!register("alice", "es", KnownSet::new(&words, "es")).unwrap());
+        assert!(register("alice", "es", KnownSet::new(&words, "es")).unwrap());
+
+        let list = KnownWords::List("es".to_string());
+        assert!(resolve(&list, "alice", "es-MX").unwrap().contains("Hola"));
+        assert_eq!(resolve(&list, "bob", "es").unwrap_err().code, ErrorCode::NotFound);
+        assert_eq!(resolve(&list, "alice", "en").unwrap_err().code, ErrorCode::InvalidInput);
+
+        assert!(remove("alice", "es"));
+        assert!(get("alice", "es").is_none());
+    }
+
+    #[test]
+    fn test_lists_per_owner() {
+        let words = vec!["hola".to_string()];
+        for i in 0..MAX_LISTS_PER_OWNER {
+            register("carol", &i.to_string(), KnownSet::new(&words, "es")).unwrap();
+        }
+        let error = register("carol", "one more", KnownSet::new(&words, "es")).unwrap_err();
+        assert_eq!(error.code, ErrorCode::QuotaExceeded);
+
+        // Replacing one of them, or another owner's list, is fine
+        assert!(register("carol", "0", KnownSet::new(&words, "es")).unwrap());
+        assert!(!register("dave", "es", KnownSet::new(&words, "es")).unwrap());
+        for i in 0..MAX_LISTS_PER_OWNER {
+            remove("carol", &i.to_string());
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! Tokenization, word alignment and subtitle tooling behind the dubdub
+//! service, for the server binary, the `dubdub` CLI and native bindings
+
+pub mod config;
+pub mod error;
+pub mod i18n;
+pub mod tokenizer;
+pub mod terms;
+pub mod models;
+pub mod aligner;
+pub mod analytics;
+pub mod quality;
+pub mod duration;
+pub mod calibration;
+pub mod export;
+pub mod highlight;
+pub mod cues;
+pub mod tts;
+pub mod dubbing;
+pub mod frequency;
+pub mod dictionary;
+pub mod morphology;
+pub mod g2p;
+pub mod gloss;
+pub mod content;
+pub mod known;
+pub mod normalize;
+pub mod syllables;
+pub mod speech_rate;
+pub mod snippets;
+pub mod pronunciation;
+pub mod practice;
+pub mod exercises;
+pub mod vocabulary;
+pub mod difficulty;
+pub mod collocations;
+pub mod diff;
+pub mod batch;
+pub mod cache;
+pub mod grpc;
+pub mod ws;
+pub mod sse;
+pub mod ndjson;
+pub mod codec;
+pub mod compression;
+pub mod jobs;
+pub mod queue;
+pub mod storage;
+pub mod spool;
+pub mod downloads;
+pub mod egress;
+pub mod upstream;
+pub mod stages;
+pub mod webhooks;
+pub mod auth;
+pub mod audit;
+pub mod usage;
+pub mod lifecycle;
+pub mod reload;
+pub mod resources;
+pub mod telemetry;
+pub mod versioning;
+pub mod envelope;
+pub mod etag;
+pub mod tls;
+pub mod cors;
+pub mod concurrency;
+pub mod admin;
+pub mod cli;
+pub mod features;
+pub mod idempotency;
+pub mod validation;
+pub mod preflight;
+#[allow(dead_code)] // Foundation for audio-based alignment features
+pub mod audio;
+pub mod subtitles;
+pub mod upload;
+#[cfg(test)]
+mod fixtures;
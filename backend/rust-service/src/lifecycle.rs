@@ -0,0 +1,309 @@
+use std::collections::BTreeSet;
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+use std::path::Path;
+use std::{fs, io};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use actix_web::dev::ServerHandle;
+
+use crate::features::Feature;
+use crate::models::{DeepHealthResponse, LanguageWarmup, ReadinessCheck, ReadinessResponse, SubsystemStatus, WarmupResponse};
+use crate::{auth, cache, content, dictionary, duration, frequency, g2p, gloss, syllables, idempotency, jobs, storage, tokenizer, tts};
+
+/// Mixed-script text pushed through each warmed language's pipeline
+const WARMUP_SAMPLE: &str = "Ready, steady — go! 準備はいい？";
+
+/// Set once a shutdown signal arrives; `/readyz` fails from then on
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// When in-flight requests and queued jobs must be done by, set once the
+/// listeners start closing
+static STOP_DEADLINE: OnceLock<Instant> = OnceLock::new();
+
+pub fn is_shutting_down() -> bool {
+    SHUTTING_DOWN.load(Ordering::Relaxed)
+}
+
+/// What's left of the shutdown timeout, shared by in-flight requests and
+/// then queued jobs; all of `timeout` if the server stopped without a signal
+pub fn time_left(timeout: Duration) -> Duration {
+    STOP_DEADLINE.get().map_or(timeout, |deadline| deadline.saturating_duration_since(Instant::now()))
+}
+
+/// Whether the service should receive traffic, check by check
+pub fn readiness() -> ReadinessResponse {
+    let check = |name: &str, ok: bool, detail: String| ReadinessCheck { name: name.to_string(), ok, detail };
+
+    let checks = vec![
+        check("shutdown", !is_shutting_down(), if is_shutting_down() { "shutting down" } else { "running" }.to_string()),
+        check("duration_models", duration::is_loaded(), format!("{} languages", duration::models().len())),
+        check("frequency_lists", frequency::is_loaded(), format!("{} languages", frequency::lists().len())),
+        check("dictionaries", dictionary::is_loaded(), format!("{} languages", dictionary::dictionaries().len())),
+    ];
+
+    ReadinessResponse { ready: checks.iter().all(|check| check.ok), checks }
+}
+
+/// What each subsystem has loaded and holds in memory
+///
+/// Unlike `readiness`, which gates traffic, this is for operators: data file
+/// versions, cache sizes and optional integrations. Any subsystem that isn't
+/// ok makes the overall status `degraded`.
+pub fn deep_health() -> DeepHealthResponse {
+    let status = |name: &str, ok: bool, detail: String| SubsystemStatus {
+        name: name.to_string(),
+        ok,
+        detail,
+        entries: None,
+        files: Vec::new(),
+    };
+
+    let models = duration::models();
+    let lists = frequency::lists();
+    let dictionaries = dictionary::dictionaries();
+    let g2p = g2p::g2p();
+    let patterns = syllables::patterns();
+    let content_lists = content::lists();
+    let jwt = auth::authenticator().and_then(|authenticator| authenticator.jwt.as_ref());
+
+    let subsystems = vec![
+        SubsystemStatus {
+            files: models.files().to_vec(),
+            ..status("duration_models", duration::is_loaded() && !models.files().is_empty(), format!("{} languages and aliases", models.len()))
+        },
+        SubsystemStatus {
+            files: lists.files().to_vec(),
+            ..status("frequency_lists", frequency::is_loaded() && !lists.files().is_empty(), lists.summary())
+        },
+        // Optional, so none loaded is fine; /lookup answers 422 for languages without one
+        SubsystemStatus {
+            files: dictionaries.files().to_vec(),
+            ..status("dictionaries", dictionary::is_loaded(), format!("{} languages", dictionaries.len()))
+        },
+        SubsystemStatus {
+            files: g2p.files().to_vec(),
+            ..status("g2p", true, g2p.summary())
+        },
+        SubsystemStatus {
+            files: patterns.files().to_vec(),
+            ..status("hyphenation_patterns", true, format!("{} languages, vowel groups for the rest", patterns.len()))
+        },
+        SubsystemStatus {
+            files: content_lists.files().to_vec(),
+            ..status("content_lists", true, format!("{} languages", content_lists.len()))
+        },
+        status("tts_engine", true, match tts::engine_url() {
+            Some(url) if Feature::TtsEngine.is_enabled() => format!("using {}", url),
+            Some(url) => format!("{} configured, switched off by feature flag", url),
+            None => "not configured, predicting from duration models".to_string(),
+        }),
+        status("gloss_providers", true, gloss::glosser().summary()),
+        status("object_storage", true, match storage::bucket() {
+            Some(bucket) => format!("s3://{}", bucket),
+            None => "not configured, store=true is rejected".to_string(),
+        }),
+        match jwt {
+            Some(jwt) => SubsystemStatus {
+                entries: Some(jwt.key_count()),
+                ..status("jwt_keys", jwt.key_count() > 0, format!("{} signing keys", jwt.key_count()))
+            },
+            None => status("jwt_keys", true, "bearer tokens not configured".to_string()),
+        },
+        SubsystemStatus {
+            entries: Some(jobs::store().retained()),
+            ..status("job_store", jobs::store().is_accepting(), format!("{} pending, {}", jobs::store().pending(),
+                if jobs::store().is_persistent() { "persisted" } else { "in memory only" }))
+        },
+        match cache::stats() {
+            stats if stats.enabled => SubsystemStatus {
+                entries: Some(stats.entries),
+                ..status("alignment_cache", true, format!("{} of {} entries, {} of {} bytes, {:.1}% hit rate ({} hits, {} misses)",
+                    stats.entries, stats.capacity, stats.bytes, stats.max_bytes, stats.hit_rate * 100.0, stats.hits, stats.misses))
+            },
+            _ => status("alignment_cache", true, "disabled".to_string()),
+        },
+        match idempotency::len() {
+            Some(keys) => SubsystemStatus {
+                entries: Some(keys),
+                ..status("idempotency_keys", true, format!("{} keys remembered", keys))
+            },
+            None => status("idempotency_keys", true, "disabled".to_string()),
+        },
+    ];
+
+    let healthy = subsystems.iter().all(|subsystem| subsystem.ok);
+    DeepHealthResponse {
+        status: if healthy { "healthy" } else { "degraded" }.to_string(),
+        service: "dubdub-rust-service".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        subsystems,
+    }
+}
+
+/// Load what `languages` need ahead of traffic
+///
+/// # How it works:
+/// 1. Build the language's segmenter (compiling the word pattern is the
+///    slow part of a cold first request)
+/// 2. Tokenize a sample and run it through the duration model and
+///    frequency list, so the first real request takes the same path warm
+pub fn warm_up(languages: &[String]) -> WarmupResponse {
+    let started = Instant::now();
+
+    let languages = languages.iter()
+        .map(|language| {
+            let language_started = Instant::now();
+
+            // Step 1: Segmenter
+            let segmenter = tokenizer::warm_up(language);
+
+            // Step 2: Sample through the tokenizer, duration model and frequency list
+            let model = duration::models().get(language);
+            let lists = frequency::lists();
+            let list = lists.get(language);
+            for token in tokenizer::tokens(WARMUP_SAMPLE, language) {
+                model.word_weight(token.text);
+                list.rank(token.text);
+            }
+
+            log::info!("Warmed up '{}' in {:?}", language, language_started.elapsed());
+            LanguageWarmup {
+                language: language.clone(),
+                segmenter: segmenter.to_string(),
+                duration_model: duration::models().contains(language),
+                frequency_ranks: list.len(),
+                dictionary_entries: dictionary::dictionaries().get(language).map_or(0, |dictionary| dictionary.len()),
+                elapsed_seconds: language_started.elapsed().as_secs_f64(),
+            }
+        })
+        .collect();
+
+    WarmupResponse { languages, elapsed_seconds: started.elapsed().as_secs_f64() }
+}
+
+/// Warm up `languages` before the server takes traffic, or every language
+/// with a duration model or frequency list if none are configured
+///
+/// What's built stays loaded for the life of the process and is shared by
+/// every worker, so even the first request in each language is warm.
+pub fn preload(languages: Option<Vec<String>>) -> WarmupResponse {
+    let languages = languages.unwrap_or_else(|| {
+        let with_data: BTreeSet<String> = duration::models().files().iter()
+            .chain(frequency::lists().files())
+            .map(|file| file.language.to_lowercase())
+            .collect();
+        with_data.into_iter().collect()
+    });
+
+    let preloaded = warm_up(&languages);
+    if !languages.is_empty() {
+        log::info!("Preloaded {} in {:.3}s", languages.join(", "), preloaded.elapsed_seconds);
+    }
+    preloaded
+}
+
+/// Resolve on SIGTERM (Kubernetes) or Ctrl-C
+pub(crate) async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = terminate.recv() => {},
+                    _ = tokio::signal::ctrl_c() => {},
+                }
+            }
+            Err(e) => {
+                log::warn!("Can't listen for SIGTERM, only Ctrl-C stops the service: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Listen on a Unix socket at `path`, with `mode` applied before anyone can
+/// connect
+///
+/// The socket is bound inside a fresh owner-only directory next to `path`
+/// and renamed into place once its permissions are set. A socket left by an
+/// earlier run is replaced; any other file at `path` is an error.
+#[cfg(unix)]
+pub fn bind_socket(path: &Path, mode: Option<u32>) -> io::Result<UnixListener> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => fs::remove_file(path)?,
+        Ok(_) => return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} exists and isn't a socket", path.display()))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+
+    let parent = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let staging = tempfile::Builder::new().prefix(".dubdub-socket").tempdir_in(parent)?;
+    let staged = staging.path().join("socket");
+    let listener = UnixListener::bind(&staged)?;
+    if let Some(mode) = mode {
+        fs::set_permissions(&staged, fs::Permissions::from_mode(mode))?;
+    }
+    fs::rename(&staged, path)?;
+    Ok(listener)
+}
+
+/// Stop the HTTP server gracefully once a shutdown signal arrives
+///
+/// # How it works:
+/// 1. Fail readiness and refuse new jobs, then keep serving for `drain_delay`
+///    so load balancers stop routing here before the listener closes
+/// 2. Stop accepting connections and let in-flight requests finish (bounded
+///    by `shutdown_timeout`)
+///
+/// Accepted jobs are drained separately, after the server stops (see
+/// `jobs::JobStore::drain`), in whatever `shutdown_timeout` the requests
+/// left (see `time_left`).
+pub async fn stop_on_signal(server: ServerHandle, drain_delay: Duration, shutdown_timeout: Duration) {
+    shutdown_signal().await;
+
+    // Step 1: Drop out of rotation
+    log::info!("Shutdown requested, draining for {:?} before closing listeners", drain_delay);
+    SHUTTING_DOWN.store(true, Ordering::Relaxed);
+    jobs::store().close();
+    tokio::time::sleep(drain_delay).await;
+
+    // Step 2: Finish in-flight requests
+    let _ = STOP_DEADLINE.set(Instant::now() + shutdown_timeout);
+    server.stop(true).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_bind_socket() {
+        use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dubdub.sock");
+
+        drop(bind_socket(&path, Some(0o600)).unwrap());
+        let metadata = fs::metadata(&path).unwrap();
+        assert!(metadata.file_type().is_socket());
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+
+        // A stale socket is replaced, anything else is left alone
+        bind_socket(&path, None).unwrap();
+        let file = dir.path().join("notes.txt");
+        fs::write(&file, "keep me").unwrap();
+        assert!(bind_socket(&file, None).is_err());
+        assert_eq!(fs::read_to_string(&file).unwrap(), "keep me");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+}
@@ -1,16 +1,30 @@
-use actix_web::{web, App, HttpResponse, HttpServer, Responder, middleware::Logger};
-use actix_cors::Cors;
-use serde::{Deserialize, Serialize};
-use aligner::{align_smart};
-use std::env;
+use actix_web::{web, App, HttpResponse, HttpServer, Responder, middleware::{Compress, Logger}};
+use actix_web::http::header::ContentEncoding;
 use actix_web::dev::Service;
-mod tokenizer;
-mod models;
-mod aligner;
+use actix_web::HttpMessage;
+use futures::future::{self, Either};
+use futures::StreamExt;
+use tracing_actix_web::{RequestId, TracingLogger};
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
+use dubdub::{config, error, tokenizer, models, aligner, analytics, quality, duration, calibration, export, highlight, tts, dubbing, frequency, dictionary, morphology, g2p, gloss, normalize, content, known, syllables, snippets, practice, exercises, vocabulary, difficulty, collocations, diff, batch, cache, spool, downloads, stages, grpc, ws, sse, ndjson, codec, compression, jobs, storage, webhooks, auth, audit, usage, lifecycle, reload, resources, telemetry, versioning, envelope, etag, i18n, cors, concurrency, admin, cli, features, idempotency, validation, preflight, subtitles, upload};
+use error::{ApiError, ErrorCode, ErrorResponse};
+use validation::Validate;
+mod openapi;
 
-use models::{TokenizeRequest, TokenizeResponse, HealthResponse,AlignmentRequest};
+use models::{TokenizeRequest, TokenizeResponse, TokenizeQuery, HealthResponse, AlignmentRequest, ScoreRequest, HighlightRequest, HighlightFormat, HighlightResponse, OutputFormat, OutputQuery, FileAlignmentRequest, AlignmentMode, SubtitleQuery, GenerateSubtitlesRequest, ResyncRequest, ValidateSubtitlesRequest, RestructureRequest, CuesResponse,
+    PairSubtitlesRequest, PairSubtitlesResponse, DubFitRequest, DubFitResponse, DubFitStatus, DurationPredictionRequest, DurationPredictionResponse,
+    ClozeRequest, ClozeResponse, VocabularyRequest, VocabularyResponse, CollocationRequest, CollocationResponse, DifficultyRequest, DifficultyResponse, KnownWordsList, KnownWordsListResponse, LookupQuery, LookupResponse, DictionaryEntry, PhonemizeRequest, PhonemizeResponse, NormalizeRequest, NormalizeResponse, G2pLanguagesResponse, SyllabifyRequest, SyllabifyResponse,
+    TranscriptDiffRequest, BatchQuery, BatchResponse, BundleFormat, JobSubmission, JobQuery, DryRun, RequestValidation, StoreQuery, DebugQuery, ReadinessResponse};
 
 
+/// Service health
+#[utoipa::path(
+    get,
+    path = "/api/v1/health",
+    tag = "system",
+    responses((status = 200, body = HealthResponse))
+)]
 async fn health() -> impl Responder {
     HttpResponse::Ok().json(HealthResponse {
         status: "healthy".to_string(),
@@ -19,53 +33,1773 @@ async fn health() -> impl Responder {
     })
 }
 
+/// Loaded data files with their versions, cache sizes and integrations
+#[utoipa::path(
+    get,
+    path = "/api/v1/health/deep",
+    tag = "system",
+    responses((status = 200, description = "Always 200; see `status` and each subsystem's `ok`", body = models::DeepHealthResponse))
+)]
+async fn deep_health() -> impl Responder {
+    HttpResponse::Ok().json(lifecycle::deep_health())
+}
+
+/// Liveness: the process is up and serving requests
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "system",
+    responses((status = 200, body = HealthResponse))
+)]
+async fn liveness() -> impl Responder {
+    health().await
+}
+
+/// Readiness: data loaded, job queue accepting work and not shutting down
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "system",
+    responses(
+        (status = 200, body = ReadinessResponse),
+        (status = 503, description = "Not ready; see the failing checks", body = ReadinessResponse)
+    )
+)]
+async fn readiness() -> impl Responder {
+    let readiness = lifecycle::readiness();
+    if readiness.ready {
+        HttpResponse::Ok().json(readiness)
+    } else {
+        HttpResponse::ServiceUnavailable().json(readiness)
+    }
+}
+
+/// Load segmenters, duration models and frequency lists for some languages
+/// ahead of traffic
+///
+/// Meant for deploy hooks and cron jobs, so the first request in a
+/// language doesn't pay for building its segmenter.
+#[utoipa::path(
+    post,
+    path = "/api/v1/warmup",
+    tag = "system",
+    request_body(content = models::WarmupRequest),
+    responses(
+        (status = 200, body = models::WarmupResponse),
+        (status = 400, description = "Invalid input", body = ErrorResponse),
+        (status = 413, description = "More languages than MAX_BATCH_SIZE", body = ErrorResponse)
+    )
+)]
+async fn warmup(req: web::Json<models::WarmupRequest>) -> Result<HttpResponse, ApiError> {
+    req.admit().await?;
+    log::info!("Warming up {}", req.languages.join(", "));
+    
+    let span = tracing::Span::current();
+    let response = web::block(move || span.in_scope(|| lifecycle::warm_up(&req.languages))).await
+        .map_err(|e| ApiError::internal(format!("Worker error: {}", e)))?;
+    
+    log::info!("Warmed up {} languages in {:.3}s", response.languages.len(), response.elapsed_seconds);
+    Ok(HttpResponse::Ok().json(response))
+}
 
-async fn tokenize(req: web::Json<TokenizeRequest>) -> impl Responder {
+/// Split text into words with byte offsets
+///
+/// With `content_flags=true`, also flags each token the language's
+/// profanity, slur or adult-content lists name, for blurring in kids mode.
+/// With `known` words, each token is marked known or not. With
+/// `gloss=<language>`, each token gets its meaning in that language, so
+/// tap-to-translate needs no second request. With `morphology=true`, each
+/// token gets its lemma and grammatical features from the language's
+/// dictionary ("estás": estar, 2sg present).
+#[utoipa::path(
+    post,
+    path = "/api/v1/tokenize",
+    tag = "text",
+    request_body(content = TokenizeRequest),
+    params(TokenizeQuery),
+    responses(
+        (status = 200, description = "Tokens, with a weak ETag unless `known` or `gloss` is used", body = TokenizeResponse),
+        (status = 304, description = "If-None-Match names the current ETag"),
+        (status = 400, description = "Invalid input", body = ErrorResponse),
+        (status = 404, description = "Unknown known-word list", body = ErrorResponse),
+        (status = 422, description = "content_flags for a language without content lists, gloss for a language pair nothing covers, or morphology for a language without a dictionary", body = ErrorResponse)
+    )
+)]
+async fn tokenize(http_req: actix_web::HttpRequest, req: codec::Body<TokenizeRequest>, query: web::Query<TokenizeQuery>, format: codec::Format) -> Result<HttpResponse, ApiError> {
     log::info!("📝 Tokenize request for language: {}", req.language);
     log::info!("📖 Subtitle text: \"{}\"", req.text);
     
-    match tokenizer::tokenize_text(&req.text, &req.language) {
-        Ok(response) => {
-            log::info!("✅ Tokenized into {} tokens", response.tokens.len());
-            HttpResponse::Ok().json(response)
-        },
-        Err(e) => {
-            log::error!("❌ Tokenization error: {}", e);
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Tokenization failed: {}", e)
-            }))
+    // Known words are per user and glosses may come from a translation
+    // service; without them the tokens depend only on the request
+    let etag = (req.known.is_none() && query.gloss.is_none()).then(|| etag::tag(&http_req, &*req));
+    if let Some(etag) = etag.as_deref().filter(|etag| etag::is_fresh(&http_req, etag)) {
+        return Ok(etag::not_modified(etag));
+    }
+    
+    req.admit().await?;
+    let mut response = tokenizer::tokenize_text(&req.text, &req.language)?;
+    if query.content_flags {
+        let lists = content::lists();
+        let list = lists.get(&req.language)
+            .ok_or_else(|| ApiError::unsupported(format!("No content lists for '{}'", req.language)))?;
+        response.content_flags = Some(list.flags(&req.text, &response.positions));
+    }
+    if let Some(known) = &req.known {
+        let known = known::resolve(known, &known::owner(&http_req), &req.language)?;
+        response.known = Some(response.tokens.iter().map(|token| known.contains(token)).collect());
+    }
+    if let Some(target) = &query.gloss {
+        let mut validator = validation::Validator::default();
+        validator.language("gloss", target);
+        validator.finish()?;
+        let glosser = gloss::glosser();
+        if !glosser.supports(&req.language, target) {
+            return Err(ApiError::unsupported(format!("No dictionary or translation service glosses '{}' in '{}'", req.language, target)));
         }
+        let (glosses, warnings) = glosser.gloss(&response.tokens, &req.language, target).await;
+        response.glosses = Some(glosses);
+        response.warnings.extend(warnings);
+    }
+    if query.morphology {
+        let dictionaries = dictionary::dictionaries();
+        let dictionary = dictionaries.get(&req.language)
+            .ok_or_else(|| ApiError::unsupported(format!("No dictionary for '{}'", req.language)))?;
+        response.morphology = Some(response.tokens.iter().map(|token| morphology::analyze(token, &req.language, dictionary)).collect());
     }
+    log::info!("✅ Tokenized into {} tokens", response.tokens.len());
+    format.respond(&response).map(|response| etag::attach(response, etag.as_deref()))
 }
 
 
-async fn batch_tokenize(req: web::Json<Vec<TokenizeRequest>>) -> impl Responder {
+/// Check a tokenize, align or file alignment request without doing the work
+///
+/// Runs the endpoint's own validation (language, text length, timings) and,
+/// for alignment against audio, checks the audio answers, so a pipeline can
+/// turn away bad input before queueing expensive jobs. `valid` says whether
+/// the request would be accepted, and `errors` what it would fail with.
+#[utoipa::path(
+    post,
+    path = "/api/v1/validate-request",
+    tag = "system",
+    request_body(content = DryRun),
+    responses(
+        (status = 200, body = RequestValidation),
+        (status = 400, description = "Not a tokenize, align or align_file request", body = ErrorResponse)
+    )
+)]
+async fn validate_request(req: codec::Body<DryRun>, format: codec::Format) -> Result<HttpResponse, ApiError> {
+    let validation = preflight::check(&req).await;
+    log::info!("Dry run: {} ({} errors, {} warnings)",
+        if validation.valid { "valid" } else { "invalid" }, validation.errors.len(), validation.warnings.len());
+    format.respond(&validation)
+}
+
+/// Tokenize several texts, reporting failures per item
+///
+/// With `Accept: application/x-ndjson`, results stream one per line as
+/// they're computed.
+#[utoipa::path(
+    post,
+    path = "/api/v1/batch-tokenize",
+    tag = "text",
+    request_body(content = Vec<TokenizeRequest>),
+    responses(
+        (status = 200, description = "JSON array, or one result per line as application/x-ndjson", body = Vec<models::BatchTokenizeResult>),
+        (status = 413, description = "More items than MAX_BATCH_SIZE", body = ErrorResponse)
+    )
+)]
+async fn batch_tokenize(http_req: actix_web::HttpRequest, req: codec::Body<Vec<TokenizeRequest>>, format: codec::Format) -> Result<HttpResponse, ApiError> {
     log::info!("Batch tokenize request for {} items", req.len());
     
-    let responses: Vec<TokenizeResponse> = req.iter()
-        .filter_map(|item| tokenizer::tokenize_text(&item.text, &item.language).ok())
-        .collect();
+    let mut validator = validation::Validator::default();
+    validator.batch("items", req.len());
+    validator.finish()?;
     
-    HttpResponse::Ok().json(responses)
+    // Each item counts as a request of its own
+    let meter = usage::current();
+    let results = futures::stream::iter(req.into_inner().into_iter().enumerate())
+        .map(move |(index, item)| {
+            let meter = meter.clone();
+            async move {
+                let result = batch::blocking(meter, move || {
+                    item.validate()?;
+                    tokenizer::tokenize_text(&item.text, &item.language)
+                }).await;
+                match result {
+                    Ok(tokenization) => models::BatchTokenizeResult { index, tokenization: Some(tokenization), error: None },
+                    Err(error) => models::BatchTokenizeResult { index, tokenization: None, error: Some(error) },
+                }
+            }
+        })
+        .buffered(batch::parallelism());
+    
+    if ndjson::accepted(&http_req) {
+        return Ok(ndjson::response(results));
+    }
+    format.respond(&results.collect::<Vec<_>>().await)
+}
+
+/// Estimate word timings for several subtitles, reporting failures per item
+///
+/// With `Accept: application/x-ndjson`, results stream one per line as
+/// they're computed.
+#[utoipa::path(
+    post,
+    path = "/api/v1/batch-align",
+    tag = "alignment",
+    request_body(content = Vec<AlignmentRequest>),
+    params(("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the original results")),
+    responses(
+        (status = 200, description = "JSON array, or one result per line as application/x-ndjson", body = Vec<models::BatchAlignResult>),
+        (status = 409, description = "A request with the same Idempotency-Key is still in progress", body = ErrorResponse),
+        (status = 413, description = "More items than MAX_BATCH_SIZE", body = ErrorResponse)
+    )
+)]
+async fn batch_align(http_req: actix_web::HttpRequest, req: codec::Body<Vec<AlignmentRequest>>, format: codec::Format) -> Result<HttpResponse, ApiError> {
+    log::info!("Batch align request for {} items", req.len());
+    
+    let mut validator = validation::Validator::default();
+    validator.batch("items", req.len());
+    validator.finish()?;
+    
+    let claim = idempotency::claim(&http_req, &serde_json::to_vec(&*req).unwrap_or_default())?;
+    if let idempotency::Claim::Replay(res) = claim {
+        log::info!("Replaying batch align for a repeated Idempotency-Key");
+        return Ok(res);
+    }
+    
+    // Each item counts as a request of its own
+    let meter = usage::current();
+    let results = futures::stream::iter(req.into_inner().into_iter().enumerate())
+        .map(move |(index, item)| {
+            let meter = meter.clone();
+            async move {
+                let result = match item.mode {
+                    AlignmentMode::Tts => usage::item_async(meter.as_ref(), align_request(&item)).await,
+                    AlignmentMode::Subtitle => batch::blocking(meter, move || {
+                        item.validate()?;
+                        cache::get_or_align(&item, aligner::align_smart)
+                    }).await,
+                };
+                match result {
+                    Ok(alignment) => models::BatchAlignResult { index, alignment: Some(alignment), error: None },
+                    Err(error) => models::BatchAlignResult { index, alignment: None, error: Some(error) },
+                }
+            }
+        })
+        .buffered(batch::parallelism());
+    
+    let res = if ndjson::accepted(&http_req) {
+        ndjson::response(results)
+    } else {
+        format.respond(&results.collect::<Vec<_>>().await)?
+    };
+    claim.response(res).await
+}
+
+/// Validate and align one subtitle, by TTS or estimation, or from the cache
+async fn align_request(req: &AlignmentRequest) -> Result<models::AlignmentResponse, ApiError> {
+    req.admit().await?;
+    if let Some(response) = cache::get(req) {
+        return Ok(response);
+    }
+    
+    let response = align_uncached(req).await?;
+    cache::insert(req, &response);
+    Ok(response)
 }
 
-async fn align_words(req: web::Json<AlignmentRequest>) -> impl Responder {
+/// Align an already validated subtitle, by TTS or estimation
+async fn align_uncached(req: &AlignmentRequest) -> Result<models::AlignmentResponse, ApiError> {
+    let req = normalize::alignment_request(req);
+    match req.mode {
+        AlignmentMode::Tts => tts::align_tts(&req).await,
+        AlignmentMode::Subtitle => aligner::align_smart(&req),
+    }
+}
+
+/// Estimate word timings for one subtitle, or for several cues of the same
+/// audio sent as `cues`
+///
+/// With `cues`, each cue is aligned on its own and the results are grouped
+/// by cue index; in subtitle mode, the gap after a cue hints at whether its
+/// subtitle stays up after the speech ends.
+#[utoipa::path(
+    post,
+    path = "/api/v1/align",
+    tag = "alignment",
+    request_body(content = AlignmentRequest),
+    params(OutputQuery, DebugQuery),
+    responses(
+        (status = 200, description = "JSON alignment (a MultiAlignmentResponse with cues), or CSV/TSV table with output_format; subtitle mode responses carry a weak ETag", body = models::AlignmentResponse),
+        (status = 304, description = "If-None-Match names the current ETag"),
+        (status = 400, description = "Invalid input", body = ErrorResponse),
+        (status = 422, description = "Forced alignment (audio_url) is unavailable and allow_fallback isn't set", body = ErrorResponse)
+    )
+)]
+async fn align_words(http_req: actix_web::HttpRequest, req: codec::Body<AlignmentRequest>, query: web::Query<OutputQuery>, debug: web::Query<DebugQuery>, format: codec::Format) -> Result<HttpResponse, ApiError> {
+    // Estimated timings depend only on the request; a TTS engine's or the
+    // audio's don't, and stage timings differ every time
+    let etag = (req.mode == AlignmentMode::Subtitle && req.audio_url.is_none() && !debug.debug_timings)
+        .then(|| etag::tag(&http_req, &*req));
+    if let Some(etag) = etag.as_deref().filter(|etag| etag::is_fresh(&http_req, etag)) {
+        return Ok(etag::not_modified(etag));
+    }
+    
+    if !req.cues.is_empty() {
+        return align_cues(&req, query.output_format, format).await
+            .map(|response| etag::attach(response, etag.as_deref()));
+    }
+    
     log::info!("Alignment request: '{}' ({} to {})", 
         req.text, req.subtitle_start, req.subtitle_end);
     
-    match aligner::align_smart(&req) {
-        Ok(response) => {
-            log::info!("Aligned {} words using {:?}", 
-                response.timings.len(), response.method);
-            HttpResponse::Ok().json(response)
-        },
-        Err(e) => {
-            log::error!("Alignment error: {}", e);
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Alignment failed: {}", e)
-            }))
+    let response = if debug.debug_timings {
+        // Bypasses the cache, which would leave nothing to time
+        let recorder = stages::Recorder::new();
+        let mut response = recorder.wrap(async {
+            req.admit().await?;
+            align_uncached(&req).await
+        }).await?;
+        response.debug_timings = Some(recorder.timings());
+        response
+    } else {
+        align_request(&req).await?
+    };
+    
+    log::info!("Aligned {} words using {:?}", 
+        response.timings.len(), response.method);
+    let response = match query.output_format {
+        OutputFormat::Json => format.respond(&response)?,
+        table => HttpResponse::Ok()
+            .content_type(table.content_type())
+            .body(export::timings_table(&[(0, &response.timings)], table)),
+    };
+    Ok(etag::attach(response, etag.as_deref()))
+}
+
+/// `/align` with `cues`: every cue's alignment, grouped by cue index
+async fn align_cues(req: &AlignmentRequest, output_format: OutputFormat, format: codec::Format) -> Result<HttpResponse, ApiError> {
+    log::info!("Multi-cue alignment request: {} cues ({})", req.cues.len(), req.language);
+    
+    req.admit().await?;
+    let cues = match req.mode {
+        AlignmentMode::Tts => {
+            let mut cues = Vec::with_capacity(req.cues.len());
+            for (index, cue) in aligner::cue_requests(req) {
+                let response = align_request(&cue).await.map_err(|e| e.context(&format!("Cue {}", index)))?;
+                cues.push((index, None, response));
+            }
+            cues
+        }
+        AlignmentMode::Subtitle => {
+            let req = req.clone();
+            let span = tracing::Span::current();
+            web::block(move || span.in_scope(|| aligner::align_cues_hinted(&req))).await
+                .map_err(|e| ApiError::internal(format!("Worker error: {}", e)))??
+        }
+    };
+    
+    let response = models::MultiAlignmentResponse {
+        language: req.language.clone(),
+        n_hinted: cues.iter().filter(|(_, speech_end, _)| speech_end.is_some()).count(),
+        cues: cues.into_iter()
+            .map(|(index, speech_end, alignment)| models::CueAlignmentResult { index, speech_end, alignment })
+            .collect(),
+    };
+    log::info!("Aligned {} cues, {} ending before their window", response.cues.len(), response.n_hinted);
+    match output_format {
+        OutputFormat::Json => format.respond(&response),
+        table => {
+            let cues: Vec<(usize, &[models::WordTiming])> = response.cues.iter()
+                .map(|cue| (cue.index, cue.alignment.timings.as_slice()))
+                .collect();
+            Ok(HttpResponse::Ok()
+                .content_type(table.content_type())
+                .body(export::timings_table(&cues, table)))
+        }
+    }
+}
+
+/// Align every cue of a file
+#[utoipa::path(
+    post,
+    path = "/api/v1/align/file",
+    tag = "alignment",
+    request_body(content = FileAlignmentRequest),
+    params(OutputQuery, StoreQuery, DebugQuery),
+    responses(
+        (status = 200, description = "JSON alignment, CSV/TSV table with output_format, or a StoredObject link with store=true", body = models::FileAlignmentResponse),
+        (status = 400, description = "Invalid input", body = ErrorResponse),
+        (status = 422, description = "store=true without object storage configured", body = ErrorResponse)
+    )
+)]
+async fn align_file(req: codec::Body<FileAlignmentRequest>, query: web::Query<OutputQuery>, store: web::Query<StoreQuery>, debug: web::Query<DebugQuery>, format: codec::Format) -> Result<HttpResponse, ApiError> {
+    log::info!("File alignment request: {} cues ({})", req.cues.len(), req.language);
+    
+    req.admit().await?;
+    let recorder = debug.debug_timings.then(stages::Recorder::new);
+    let mut response = stages::scoped(recorder.as_ref(), || aligner::align_file(&req))?;
+    response.debug_timings = recorder.map(|recorder| recorder.timings());
+    log::info!("Aligned {} cues with {} warnings",
+        response.cues.len(), response.warnings.len());
+    file_alignment_response(response, query.output_format, store.store, format).await
+}
+
+/// Align every cue of a file and return statistics instead of timings
+///
+/// Mean word duration overall, by length and by part of speech, speech
+/// versus silence, and talk time per speaker (the cues' actors).
+#[utoipa::path(
+    post,
+    path = "/api/v1/align/file/stats",
+    tag = "alignment",
+    request_body(content = FileAlignmentRequest),
+    responses(
+        (status = 200, body = models::TimingStats),
+        (status = 400, description = "Invalid input", body = ErrorResponse)
+    )
+)]
+async fn align_file_stats(req: codec::Body<FileAlignmentRequest>, format: codec::Format) -> Result<HttpResponse, ApiError> {
+    log::info!("File timing stats request: {} cues ({})", req.cues.len(), req.language);
+    
+    req.admit().await?;
+    let stats = analytics::timing_stats(&aligner::align_file(&req)?);
+    log::info!("Summarised {} words, {:.0}% speech", stats.n_words, stats.speech_ratio * 100.0);
+    format.respond(&stats)
+}
+
+/// JSON (or MessagePack/CBOR), or one CSV/TSV row per word; with `store`,
+/// written to object storage as JSON or the table instead
+async fn file_alignment_response(response: models::FileAlignmentResponse, output_format: OutputFormat, store: bool, format: codec::Format) -> Result<HttpResponse, ApiError> {
+    let table = |table: OutputFormat| {
+        let cues: Vec<(usize, &[models::WordTiming])> = response.cues.iter()
+            .map(|cue| (cue.index, cue.timings.as_slice()))
+            .collect();
+        export::timings_table(&cues, table)
+    };
+    
+    match (output_format, store) {
+        (OutputFormat::Json, false) => format.respond(&response),
+        (OutputFormat::Json, true) => {
+            let body = serde_json::to_vec(&response)
+                .map_err(|e| ApiError::internal(format!("Failed to serialize result: {}", e)))?;
+            stored_response("alignment.json", "application/json", body).await
+        }
+        (OutputFormat::Csv, true) => stored_response("alignment.csv", OutputFormat::Csv.content_type(), table(OutputFormat::Csv).into_bytes()).await,
+        (OutputFormat::Tsv, true) => stored_response("alignment.tsv", OutputFormat::Tsv.content_type(), table(OutputFormat::Tsv).into_bytes()).await,
+        (output_format, false) => Ok(HttpResponse::Ok()
+            .content_type(output_format.content_type())
+            .body(table(output_format))),
+    }
+}
+
+/// Write `body` to object storage and answer with a presigned link to it
+async fn stored_response(name: &str, content_type: &str, body: Vec<u8>) -> Result<HttpResponse, ApiError> {
+    let stored = storage::put(name, content_type, body).await?;
+    Ok(HttpResponse::Ok().json(stored))
+}
+
+/// Align every cue of a file, streaming progress as Server-Sent Events
+#[utoipa::path(
+    post,
+    path = "/api/v1/align/file/stream",
+    tag = "alignment",
+    request_body(content = FileAlignmentRequest),
+    responses(
+        (status = 200, description = "text/event-stream of cue, progress and done (or error) events"),
+        (status = 400, description = "Invalid input", body = ErrorResponse)
+    )
+)]
+async fn align_file_stream(req: web::Json<FileAlignmentRequest>) -> Result<HttpResponse, ApiError> {
+    log::info!("Streaming file alignment request: {} cues ({})", req.cues.len(), req.language);
+    
+    req.admit().await?;
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("cache-control", "no-cache"))
+        // Compressing would buffer events until the encoder flushes
+        .insert_header(ContentEncoding::Identity)
+        .streaming(sse::align_file_events(req.into_inner())))
+}
+
+/// Parse an SRT, WebVTT or ASS file (raw request body)
+#[utoipa::path(
+    post,
+    path = "/api/v1/subtitles/parse",
+    tag = "subtitles",
+    request_body(content = String, content_type = "text/plain"),
+    params(SubtitleQuery),
+    responses(
+        (status = 200, body = models::ParseSubtitlesResponse),
+        (status = 400, description = "Invalid input", body = ErrorResponse)
+    )
+)]
+async fn parse_subtitles(body: String, query: web::Query<SubtitleQuery>) -> Result<HttpResponse, ApiError> {
+    log::info!("Subtitle parse request ({} bytes)", body.len());
+    
+    let response = subtitles::parse(&body, query.format)?;
+    log::info!("Parsed {} cues ({:?}) with {} warnings",
+        response.cues.len(), response.format, response.warnings.len());
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Write cues as a subtitle file
+#[utoipa::path(
+    post,
+    path = "/api/v1/subtitles/generate",
+    tag = "subtitles",
+    request_body(content = GenerateSubtitlesRequest),
+    responses(
+        (status = 200, description = "Subtitle file; warnings in the x-subtitle-warnings header", body = String),
+        (status = 400, description = "Invalid input", body = ErrorResponse)
+    )
+)]
+async fn generate_subtitles(req: web::Json<GenerateSubtitlesRequest>) -> Result<HttpResponse, ApiError> {
+    log::info!("Subtitle generation request: {} cues as {:?}", req.cues.len(), req.format);
+    
+    req.admit().await?;
+    let options = subtitles::writer::WriteOptions {
+        max_line_length: req.max_line_length,
+        max_lines: req.max_lines,
+        max_cps: req.max_cps,
+    };
+    
+    let (content, warnings) = subtitles::writer::write(&req.cues, req.format, &options);
+    
+    for warning in &warnings {
+        log::warn!("Cue {}: {}", warning.cue_index, warning.message);
+    }
+    
+    Ok(HttpResponse::Ok()
+        .content_type(req.format.content_type())
+        .insert_header(("x-subtitle-warnings", warnings.len().to_string()))
+        .body(content))
+}
+
+/// Shift, stretch or two-point sync a subtitle file
+#[utoipa::path(
+    post,
+    path = "/api/v1/subtitles/resync",
+    tag = "subtitles",
+    request_body(content = ResyncRequest),
+    responses(
+        (status = 200, description = "Resynced subtitle file; warnings in the x-subtitle-warnings header", body = String),
+        (status = 400, description = "Invalid input", body = ErrorResponse)
+    )
+)]
+async fn resync_subtitles(req: web::Json<ResyncRequest>) -> Result<HttpResponse, ApiError> {
+    log::info!("Subtitle resync request ({} bytes)", req.content.len());
+    
+    req.admit().await?;
+    let transform = match &req.sync_points {
+        Some(points) => subtitles::resync::TimeTransform::from_sync_points(points)?,
+        None => subtitles::resync::TimeTransform::new(req.scale, req.offset)?,
+    };
+    let parsed = subtitles::parse(&req.content, req.format)?;
+    let format = req.output_format.unwrap_or(parsed.format);
+    
+    // Same format: rewrite only the timestamps, leaving text and numbering as they were
+    let (content, kept, warnings) = if format == parsed.format {
+        subtitles::resync::retime(&req.content, format, &transform)
+    } else {
+        let (cues, mut warnings) = subtitles::resync::resync(&parsed.cues, &transform);
+        let options = subtitles::writer::WriteOptions {
+            max_line_length: None,
+            max_lines: usize::MAX,
+            max_cps: None,
+        };
+        let (content, write_warnings) = subtitles::writer::write(&cues, format, &options);
+        warnings.extend(write_warnings);
+        (content, cues.len(), warnings)
+    };
+    
+    log::info!("Resynced {} cues (scale {}, offset {}s)",
+        kept, transform.scale, transform.offset);
+    
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header(("x-subtitle-warnings", warnings.len().to_string()))
+        .body(content))
+}
+
+/// Run QC checks on a subtitle file
+#[utoipa::path(
+    post,
+    path = "/api/v1/subtitles/validate",
+    tag = "subtitles",
+    request_body(content = ValidateSubtitlesRequest),
+    responses(
+        (status = 200, body = models::ValidationReport),
+        (status = 400, description = "Invalid input", body = ErrorResponse)
+    )
+)]
+async fn validate_subtitles(req: web::Json<ValidateSubtitlesRequest>) -> Result<HttpResponse, ApiError> {
+    req.admit().await?;
+    let (cues, parse_warnings) = subtitles::cues_or_content(&*req)?;
+    
+    let limits = subtitles::validate::Limits {
+        max_cps: req.max_cps,
+        max_lines: req.max_lines,
+        max_line_length: req.max_line_length,
+        min_gap: req.min_gap,
+    };
+    let report = subtitles::validate::validate(&cues, &parse_warnings, &limits);
+    
+    log::info!("Validated {} cues: {} errors, {} warnings",
+        report.cue_count, report.error_count, report.warning_count);
+    Ok(HttpResponse::Ok().json(report))
+}
+
+fn restructure_options(req: &RestructureRequest) -> subtitles::restructure::RestructureOptions {
+    subtitles::restructure::RestructureOptions {
+        min_duration: req.min_duration,
+        max_duration: req.max_duration,
+        max_chars: req.max_chars,
+        max_gap: req.max_gap,
+    }
+}
+
+/// Merge consecutive short cues
+#[utoipa::path(
+    post,
+    path = "/api/v1/subtitles/merge",
+    tag = "subtitles",
+    request_body(content = RestructureRequest),
+    responses(
+        (status = 200, body = CuesResponse),
+        (status = 400, description = "Invalid input", body = ErrorResponse)
+    )
+)]
+async fn merge_cues(req: web::Json<RestructureRequest>) -> Result<HttpResponse, ApiError> {
+    req.admit().await?;
+    let cues = subtitles::restructure::merge_short(&req.cues, &restructure_options(&req));
+    log::info!("Merged {} cues into {}", req.cues.len(), cues.len());
+    Ok(HttpResponse::Ok().json(CuesResponse { cues }))
+}
+
+/// Split over-long cues at sentence or clause boundaries
+#[utoipa::path(
+    post,
+    path = "/api/v1/subtitles/split",
+    tag = "subtitles",
+    request_body(content = RestructureRequest),
+    responses(
+        (status = 200, body = CuesResponse),
+        (status = 400, description = "Invalid input", body = ErrorResponse)
+    )
+)]
+async fn split_cues(req: web::Json<RestructureRequest>) -> Result<HttpResponse, ApiError> {
+    req.admit().await?;
+    let cues = subtitles::restructure::split_long(&req.cues, &req.language, &restructure_options(&req));
+    log::info!("Split {} cues into {}", req.cues.len(), cues.len());
+    Ok(HttpResponse::Ok().json(CuesResponse { cues }))
+}
+
+/// Pair the cues of two languages' subtitle files
+#[utoipa::path(
+    post,
+    path = "/api/v1/subtitles/pair",
+    tag = "subtitles",
+    request_body(content = PairSubtitlesRequest),
+    responses(
+        (status = 200, body = PairSubtitlesResponse),
+        (status = 400, description = "Invalid input", body = ErrorResponse)
+    )
+)]
+async fn pair_subtitles(req: web::Json<PairSubtitlesRequest>) -> Result<HttpResponse, ApiError> {
+    req.admit().await?;
+    let source = subtitles::parse(&req.source, req.source_format)
+        .map_err(|e| e.with_field("source"))?
+        .cues;
+    let target = subtitles::parse(&req.target, req.target_format)
+        .map_err(|e| e.with_field("target"))?
+        .cues;
+    
+    let pairs = subtitles::bilingual::pair_cues(&source, &target, req.min_overlap);
+    let unpaired_source = pairs.iter().filter(|p| p.target_indices.is_empty()).count();
+    let unpaired_target = pairs.iter().filter(|p| p.source_indices.is_empty()).count();
+    
+    log::info!("Paired {} source and {} target cues into {} pairs", source.len(), target.len(), pairs.len());
+    Ok(HttpResponse::Ok().json(PairSubtitlesResponse { pairs, unpaired_source, unpaired_target }))
+}
+
+/// Check translated lines against the original timing
+#[utoipa::path(
+    post,
+    path = "/api/v1/dub/fit",
+    tag = "dubbing",
+    request_body(content = DubFitRequest),
+    responses(
+        (status = 200, body = DubFitResponse),
+        (status = 400, description = "Invalid input", body = ErrorResponse)
+    )
+)]
+async fn fit_dub_script(req: web::Json<DubFitRequest>) -> Result<HttpResponse, ApiError> {
+    log::info!("Fitting {} dub lines ({})", req.cues.len(), req.language);
+    
+    req.admit().await?;
+    let tolerance = dubbing::FitTolerance { max_stretch: req.max_stretch, min_fill: req.min_fill };
+    let speaking_rate = req.speaking_rate
+        .or_else(|| req.voice.as_deref().and_then(|voice| duration::models().get(&req.language).voice_rate(voice)));
+    let lines = dubbing::fit_script(&req.cues, &req.language, speaking_rate, &tolerance)?;
+    
+    let count = |status| lines.iter().filter(|line| line.status == status).count();
+    Ok(HttpResponse::Ok().json(DubFitResponse {
+        language: req.language.clone(),
+        n_too_long: count(DubFitStatus::TooLong),
+        n_too_short: count(DubFitStatus::TooShort),
+        lines,
+    }))
+}
+
+/// Predict how long a text takes a TTS voice to say
+///
+/// Uses the language's duration model at the voice's speaking rate (when
+/// the model has a profile for it), weighing words on their pronunciation
+/// where G2P knows it. Check a line before recording; `/dub/fit` checks a
+/// whole script against its original timing.
+#[utoipa::path(
+    post,
+    path = "/api/v1/predict-duration",
+    tag = "dubbing",
+    request_body(content = DurationPredictionRequest),
+    responses(
+        (status = 200, body = DurationPredictionResponse),
+        (status = 400, description = "Invalid input", body = ErrorResponse)
+    )
+)]
+async fn predict_duration(req: web::Json<DurationPredictionRequest>) -> Result<HttpResponse, ApiError> {
+    req.admit().await?;
+    
+    // espeak-ng runs a process per word, so keep it off the async workers
+    let req = req.into_inner();
+    let response = web::block(move || tts::predict_duration(&req, duration::models().get(&req.language))).await
+        .map_err(|e| ApiError::internal(format!("Worker error: {}", e)))??;
+    
+    log::info!("Predicted {:.2}s for {} words ({})", response.duration, response.words.len(), response.language);
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Build listening cloze exercises from cues
+#[utoipa::path(
+    post,
+    path = "/api/v1/exercises/cloze",
+    tag = "learning",
+    request_body(content = ClozeRequest),
+    responses(
+        (status = 200, body = ClozeResponse),
+        (status = 400, description = "Invalid input", body = ErrorResponse)
+    )
+)]
+async fn cloze_exercises(req: web::Json<ClozeRequest>) -> Result<HttpResponse, ApiError> {
+    req.admit().await?;
+    let req = req.into_inner();
+    
+    let (cues, _) = subtitles::cues_or_content(&req)?;
+    
+    let options = exercises::ClozeOptions {
+        gaps_per_cue: req.gaps_per_cue,
+        min_rank: req.min_rank,
+        max_rank: req.max_rank,
+        distractors: req.distractors,
+    };
+    
+    let items = exercises::build_cloze(&cues, &req.language, &options);
+    log::info!("Built {} cloze exercises ({})", items.len(), req.language);
+    Ok(HttpResponse::Ok().json(ClozeResponse { language: req.language, items }))
+}
+
+/// Extract the vocabulary of a subtitle file
+///
+/// With `known` words, each entry is marked known or not; `only_new`
+/// leaves the known ones out.
+#[utoipa::path(
+    post,
+    path = "/api/v1/vocabulary",
+    tag = "learning",
+    request_body(content = VocabularyRequest),
+    params(OutputQuery),
+    responses(
+        (status = 200, description = "JSON, or an Anki-importable CSV/TSV with output_format", body = VocabularyResponse),
+        (status = 400, description = "Invalid input", body = ErrorResponse),
+        (status = 404, description = "Unknown known-word list", body = ErrorResponse)
+    )
+)]
+async fn extract_vocabulary(http_req: actix_web::HttpRequest, req: web::Json<VocabularyRequest>, query: web::Query<OutputQuery>) -> Result<HttpResponse, ApiError> {
+    req.admit().await?;
+    let req = req.into_inner();
+    let known = req.known.as_ref()
+        .map(|known| known::resolve(known, &known::owner(&http_req), &req.language))
+        .transpose()?;
+    
+    let (cues, _) = subtitles::cues_or_content(&req)?;
+    
+    let (mut entries, n_tokens) = vocabulary::extract(&cues, &req.language);
+    if let Some(known) = known {
+        for entry in &mut entries {
+            entry.known = Some(known.contains(&entry.lemma));
+        }
+        if req.only_new {
+            entries.retain(|entry| entry.known == Some(false));
         }
     }
+    log::info!("Extracted {} words ({} tokens) from {} cues", entries.len(), n_tokens, cues.len());
+    Ok(match query.output_format {
+        OutputFormat::Json => HttpResponse::Ok().json(VocabularyResponse { language: req.language, n_tokens, entries }),
+        format => HttpResponse::Ok()
+            .content_type(format.content_type())
+            .body(export::anki_notes(&entries, format)),
+    })
+}
+
+/// Find phrases a subtitle file keeps using ("take care of"), to teach
+/// them whole rather than word by word
+///
+/// Word sequences used at least `min_count` times are scored by
+/// log-likelihood or PMI; phrases of stopwords alone, and phrases only
+/// seen inside a longer one, are left out.
+#[utoipa::path(
+    post,
+    path = "/api/v1/collocations",
+    tag = "learning",
+    request_body(content = CollocationRequest),
+    responses(
+        (status = 200, body = CollocationResponse),
+        (status = 400, description = "Invalid input", body = ErrorResponse)
+    )
+)]
+async fn find_collocations(req: web::Json<CollocationRequest>) -> Result<HttpResponse, ApiError> {
+    req.admit().await?;
+    let req = req.into_inner();
+    let (cues, _) = subtitles::cues_or_content(&req)?;
+    
+    let options = collocations::CollocationOptions {
+        max_words: req.max_words,
+        min_count: req.min_count,
+        measure: req.measure,
+        limit: req.limit,
+    };
+    let (phrases, n_tokens) = collocations::find(&cues, &req.language, &options);
+    log::info!("Found {} collocations in {} cues ({} tokens)", phrases.len(), cues.len(), n_tokens);
+    Ok(HttpResponse::Ok().json(CollocationResponse { language: req.language, measure: req.measure, n_tokens, collocations: phrases }))
+}
+
+/// Score a cue's text or a subtitle file for learner difficulty
+///
+/// Lexical frequency profile, sentence length, the vocabulary size that
+/// covers 95% of the words and a CEFR estimate, so content can be
+/// labelled "A2-friendly". Files are also scored cue by cue.
+#[utoipa::path(
+    post,
+    path = "/api/v1/difficulty",
+    tag = "learning",
+    request_body(content = DifficultyRequest),
+    responses(
+        (status = 200, body = DifficultyResponse),
+        (status = 400, description = "Invalid input", body = ErrorResponse),
+        (status = 422, description = "No frequency list for the language", body = ErrorResponse)
+    )
+)]
+async fn score_difficulty(req: web::Json<DifficultyRequest>) -> Result<HttpResponse, ApiError> {
+    req.admit().await?;
+    let req = req.into_inner();
+    if frequency::lists().get(&req.language).is_empty() {
+        return Err(ApiError::unsupported(format!("No frequency list for '{}'", req.language)));
+    }
+    
+    let (score, cues) = match &req.text {
+        Some(text) => (difficulty::score(text, &req.language), None),
+        None if req.cues.is_none() && req.content.is_none() => {
+            return Err(ApiError::invalid_input("Provide 'text', 'content' or 'cues'").with_field("text"));
+        }
+        None => {
+            let (cues, _) = subtitles::cues_or_content(&req)?;
+            let (score, per_cue) = difficulty::score_cues(&cues, &req.language);
+            (score, Some(per_cue))
+        }
+    };
+    
+    log::info!("Scored {} words of '{}' text as {:?}", score.n_words, req.language, score.cefr);
+    Ok(HttpResponse::Ok().json(DifficultyResponse { language: req.language, score, cues }))
+}
+
+/// Register a learner's known words under an ID, replacing any list there
+///
+/// Pass the ID as `known` to `/tokenize` and `/vocabulary` instead of
+/// sending the words each time. Lists are kept in memory, per API key or
+/// token, until deleted or the service restarts.
+#[utoipa::path(
+    put,
+    path = "/api/v1/known-words/{id}",
+    tag = "learning",
+    params(("id" = String, Path, description = "List ID of the caller's choosing")),
+    request_body(content = KnownWordsList),
+    responses(
+        (status = 200, description = "Replaced an existing list", body = KnownWordsListResponse),
+        (status = 201, description = "Registered a new list", body = KnownWordsListResponse),
+        (status = 400, description = "Invalid input", body = ErrorResponse),
+        (status = 503, description = "Too many lists registered", body = ErrorResponse)
+    )
+)]
+async fn put_known_words(http_req: actix_web::HttpRequest, id: web::Path<String>, req: web::Json<KnownWordsList>) -> Result<HttpResponse, ApiError> {
+    req.admit().await?;
+    let KnownWordsList { language, words } = req.into_inner();
+    
+    let set = known::KnownSet::new(&words, &language);
+    let response = KnownWordsListResponse { id: id.clone(), language, words: set.words() };
+    let replaced = known::register(&known::owner(&http_req), &id, set)?;
+    log::info!("Registered {} known words as '{}' ({} lists)", response.words.len(), id, known::len());
+    Ok(match replaced {
+        true => HttpResponse::Ok().json(response),
+        false => HttpResponse::Created().json(response),
+    })
+}
+
+/// A registered known-words list
+#[utoipa::path(
+    get,
+    path = "/api/v1/known-words/{id}",
+    tag = "learning",
+    params(("id" = String, Path, description = "List ID")),
+    responses(
+        (status = 200, body = KnownWordsListResponse),
+        (status = 404, description = "Unknown list", body = ErrorResponse)
+    )
+)]
+async fn get_known_words(http_req: actix_web::HttpRequest, id: web::Path<String>) -> Result<HttpResponse, ApiError> {
+    let set = known::get(&known::owner(&http_req), &id).ok_or_else(|| unknown_known_words(&id))?;
+    Ok(HttpResponse::Ok().json(KnownWordsListResponse { id: id.into_inner(), language: set.language().to_string(), words: set.words() }))
+}
+
+/// Forget a known-words list
+#[utoipa::path(
+    delete,
+    path = "/api/v1/known-words/{id}",
+    tag = "learning",
+    params(("id" = String, Path, description = "List ID")),
+    responses(
+        (status = 204, description = "Deleted"),
+        (status = 404, description = "Unknown list", body = ErrorResponse)
+    )
+)]
+async fn delete_known_words(http_req: actix_web::HttpRequest, id: web::Path<String>) -> Result<HttpResponse, ApiError> {
+    if !known::remove(&known::owner(&http_req), &id) {
+        return Err(unknown_known_words(&id));
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+fn unknown_known_words(id: &str) -> ApiError {
+    ApiError::not_found(format!("No known-word list '{}'", id)).with_field("id")
+}
+
+/// Define a word from the loaded dictionaries
+///
+/// Inflections listed in the dictionary ("ran") also return their lemma's
+/// entries ("run"). Unknown words get an empty `entries` list.
+#[utoipa::path(
+    get,
+    path = "/api/v1/lookup",
+    tag = "learning",
+    params(LookupQuery),
+    responses(
+        (status = 200, body = LookupResponse),
+        (status = 400, description = "Invalid input", body = ErrorResponse),
+        (status = 422, description = "No dictionary loaded for the language", body = ErrorResponse)
+    )
+)]
+async fn lookup(query: web::Query<LookupQuery>) -> Result<HttpResponse, ApiError> {
+    query.admit().await?;
+    let LookupQuery { word, language } = query.into_inner();
+    
+    let dictionaries = dictionary::dictionaries();
+    let dictionary = dictionaries.get(&language)
+        .ok_or_else(|| ApiError::unsupported(format!("No dictionary loaded for '{}'", language)))?;
+    let lemmas = dictionary.lemmas(&word).to_vec();
+    let entries: Vec<DictionaryEntry> = dictionary.lookup(&word).into_iter().cloned().collect();
+    
+    log::debug!("Lookup '{}' ({}): {} entries", word, language, entries.len());
+    Ok(HttpResponse::Ok().json(LookupResponse { word, language, lemmas, entries }))
+}
+
+/// IPA and X-SAMPA for each word of a text
+///
+/// Each word comes from the first G2P backend that knows it: the language's
+/// pronunciation lexicon, espeak-ng if configured, then spelling rules where
+/// the language has them. Words none of them know are returned without one.
+#[utoipa::path(
+    post,
+    path = "/api/v1/phonemize",
+    tag = "learning",
+    request_body(content = PhonemizeRequest),
+    responses(
+        (status = 200, body = PhonemizeResponse),
+        (status = 400, description = "Invalid input", body = ErrorResponse),
+        (status = 422, description = "No G2P backend covers the language", body = ErrorResponse)
+    )
+)]
+async fn phonemize(req: web::Json<PhonemizeRequest>) -> Result<HttpResponse, ApiError> {
+    req.admit().await?;
+    if !g2p::supports(&req.language) {
+        return Err(ApiError::unsupported(format!("No pronunciation lexicon, espeak-ng voice or spelling rules for '{}'", req.language)));
+    }
+    
+    // espeak-ng runs as a process, so keep it off the async workers
+    let req = req.into_inner();
+    let response = web::block(move || {
+        let tokens = tokenizer::tokens(&req.text, &req.language);
+        let pronunciations = g2p::phonemize_all(&tokens.iter().map(|token| token.text).collect::<Vec<_>>(), &req.language);
+        let tokens: Vec<models::PhonemizedToken> = tokens.into_iter().zip(pronunciations)
+            .map(|(token, pronunciation)| {
+                models::PhonemizedToken {
+                    word: token.text.to_string(),
+                    char_start: token.start,
+                    char_end: token.end,
+                    xsampa: pronunciation.as_ref().map(|(ipa, _)| g2p::to_xsampa(ipa)),
+                    source: pronunciation.as_ref().map(|(_, source)| *source),
+                    ipa: pronunciation.map(|(ipa, _)| ipa),
+                }
+            })
+            .collect();
+        let n_unknown = tokens.iter().filter(|token| token.ipa.is_none()).count();
+        PhonemizeResponse { text: req.text, language: req.language, tokens, n_unknown }
+    }).await
+        .map_err(|e| ApiError::internal(format!("Worker error: {}", e)))?;
+    
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Languages `/phonemize` covers and which G2P backends cover each
+#[utoipa::path(
+    get,
+    path = "/api/v1/phonemize/languages",
+    tag = "learning",
+    responses((status = 200, body = G2pLanguagesResponse))
+)]
+async fn phonemize_languages() -> HttpResponse {
+    HttpResponse::Ok().json(G2pLanguagesResponse { languages: g2p::g2p().availability() })
+}
+
+/// Spell out numbers, dates, times, amounts and symbols for speech
+///
+/// "$5" becomes "five dollars", "3:30" "three thirty". For TTS
+/// preprocessing; `/align` does the same with `normalize: true`.
+#[utoipa::path(
+    post,
+    path = "/api/v1/normalize",
+    tag = "text",
+    request_body(content = NormalizeRequest),
+    responses(
+        (status = 200, body = NormalizeResponse),
+        (status = 400, description = "Invalid input", body = ErrorResponse),
+        (status = 422, description = "No normalization rules for the language", body = ErrorResponse)
+    )
+)]
+async fn normalize_text(req: web::Json<NormalizeRequest>) -> Result<HttpResponse, ApiError> {
+    req.admit().await?;
+    if !normalize::supports(&req.language) {
+        return Err(ApiError::unsupported(format!("No normalization rules for '{}'", req.language)));
+    }
+    
+    let expansions = normalize::expansions(&req.text, &req.language);
+    let normalized = normalize::apply(&req.text, &expansions);
+    let spans = expansions.into_iter()
+        .map(|expansion| models::NormalizedSpan {
+            original: req.text[expansion.start..expansion.end].to_string(),
+            spoken: expansion.spoken,
+            char_start: expansion.start,
+            char_end: expansion.end,
+        })
+        .collect();
+    
+    let req = req.into_inner();
+    Ok(HttpResponse::Ok().json(NormalizeResponse { text: req.text, language: req.language, normalized, spans }))
+}
+
+/// Turn word timings from `/align` into render-ready highlight spans
+///
+/// `html` (the default) returns HTML-escaped segments covering the whole
+/// text, with class names and timings, plus the joined-up markup;
+/// `ranges` returns `[start_ms, end_ms, from, to]` per word by start time
+/// and `binary` the same ranges packed as little-endian `u32`s.
+#[utoipa::path(
+    post,
+    path = "/api/v1/highlight",
+    tag = "alignment",
+    request_body(content = HighlightRequest),
+    responses(
+        (status = 200, description = "JSON, or 16 bytes per word as application/octet-stream with format binary", body = HighlightResponse),
+        (status = 400, description = "Invalid input", body = ErrorResponse)
+    )
+)]
+async fn highlight_spans(req: web::Json<HighlightRequest>) -> Result<HttpResponse, ApiError> {
+    req.admit().await?;
+    
+    let response = match req.format {
+        HighlightFormat::Html => {
+            let segments = highlight::segments(&req.text, &req.timings);
+            HighlightResponse { format: req.format, html: Some(highlight::html(&segments)), segments: Some(segments), ranges: None }
+        }
+        HighlightFormat::Ranges => {
+            HighlightResponse { format: req.format, html: None, segments: None, ranges: Some(highlight::ranges(&req.text, &req.timings)) }
+        }
+        HighlightFormat::Binary => {
+            return Ok(HttpResponse::Ok()
+                .content_type("application/octet-stream")
+                .body(highlight::encode(&highlight::ranges(&req.text, &req.timings))));
+        }
+    };
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Split each word of a text into syllables
+///
+/// Uses the language's hyphenation patterns if loaded, otherwise one
+/// syllable per vowel group (or per character for CJK); `method` says which.
+#[utoipa::path(
+    post,
+    path = "/api/v1/syllabify",
+    tag = "learning",
+    request_body(content = SyllabifyRequest),
+    responses(
+        (status = 200, body = SyllabifyResponse),
+        (status = 400, description = "Invalid input", body = ErrorResponse)
+    )
+)]
+async fn syllabify(req: web::Json<SyllabifyRequest>) -> Result<HttpResponse, ApiError> {
+    req.admit().await?;
+    
+    let words = tokenizer::tokens(&req.text, &req.language).into_iter()
+        .map(|token| models::SyllabifiedWord {
+            word: token.text.to_string(),
+            char_start: token.start,
+            char_end: token.end,
+            syllables: syllables::syllabify(token.text, &req.language).into_iter()
+                .map(|syllable| models::Syllable { start: token.start + syllable.start, end: token.start + syllable.end, ..syllable })
+                .collect(),
+        })
+        .collect();
+    
+    let req = req.into_inner();
+    let method = syllables::method(&req.language);
+    Ok(HttpResponse::Ok().json(SyllabifyResponse { text: req.text, language: req.language, method, words }))
+}
+
+/// Word-level diff of an ASR transcript against subtitles
+#[utoipa::path(
+    post,
+    path = "/api/v1/subtitles/diff",
+    tag = "subtitles",
+    request_body(content = TranscriptDiffRequest),
+    responses(
+        (status = 200, body = models::TranscriptDiff),
+        (status = 400, description = "Invalid input", body = ErrorResponse)
+    )
+)]
+async fn diff_transcript(req: web::Json<TranscriptDiffRequest>) -> Result<HttpResponse, ApiError> {
+    req.admit().await?;
+    let req = req.into_inner();
+    
+    let (cues, _) = subtitles::cues_or_content(&req)?;
+    
+    let result = diff::diff_transcript(&req.transcript, &cues, &req.language);
+    log::info!("Transcript diff: {} matched, {} substituted, {} missing, {} extra",
+        result.n_matched, result.n_substituted, result.n_missing, result.n_extra);
+    Ok(HttpResponse::Ok().json(result))
+}
+
+/// Open an interactive session: JSON messages in, results out as they're computed
+async fn ws_session(req: actix_web::HttpRequest, body: web::Payload) -> actix_web::Result<HttpResponse> {
+    let (response, session, stream) = actix_ws::handle(&req, body)?;
+    log::info!("WebSocket session opened");
+    
+    // Each message is metered on its own, against the caller's account
+    actix_web::rt::spawn(ws::run(session, stream, usage::current()));
+    
+    Ok(response)
+}
+
+/// Process every subtitle file in a ZIP archive (raw request body)
+#[utoipa::path(
+    post,
+    path = "/api/v1/batch/zip",
+    tag = "batch",
+    request_body(content = Vec<u8>, content_type = "application/zip"),
+    params(
+        BatchQuery,
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the original results")
+    ),
+    responses(
+        (status = 200, description = "JSON results, a ZIP with bundle=zip, or a StoredObject link with store=true", body = BatchResponse),
+        (status = 400, description = "Invalid input", body = ErrorResponse),
+        (status = 409, description = "A request with the same Idempotency-Key is still in progress", body = ErrorResponse),
+        (status = 413, description = "Too many files in the archive", body = ErrorResponse)
+    )
+)]
+async fn batch_zip(http_req: actix_web::HttpRequest, body: web::Bytes, query: web::Query<BatchQuery>) -> Result<HttpResponse, ApiError> {
+    let query = query.into_inner();
+    log::info!("Batch {:?} request: {} byte archive", query.operation, body.len());
+    
+    let claim = idempotency::claim(&http_req, &body)?;
+    if let idempotency::Claim::Replay(res) = claim {
+        log::info!("Replaying batch for a repeated Idempotency-Key");
+        return Ok(res);
+    }
+    
+    let language = query.language.clone();
+    let span = tracing::Span::current();
+    let meter = usage::current();
+    let files = web::block(move || span.in_scope(|| batch::process_zip(&body, query.operation, language.as_deref(), meter.as_ref()))).await
+        .map_err(|e| ApiError::internal(format!("Worker error: {}", e)))??;
+    
+    let n_failed = files.iter().filter(|file| file.error.is_some()).count();
+    log::info!("Batch processed {} files, {} failed", files.len(), n_failed);
+    
+    let res = match (query.bundle, query.store) {
+        (BundleFormat::Json, false) => HttpResponse::Ok().json(BatchResponse { files, n_failed }),
+        (BundleFormat::Json, true) => {
+            let body = serde_json::to_vec(&BatchResponse { files, n_failed })
+                .map_err(|e| ApiError::internal(format!("Failed to serialize results: {}", e)))?;
+            stored_response("results.json", "application/json", body).await?
+        }
+        (BundleFormat::Zip, true) => stored_response("results.zip", "application/zip", batch::bundle_zip(&files)?).await?,
+        (BundleFormat::Zip, false) => HttpResponse::Ok()
+            .content_type("application/zip")
+            .insert_header(("content-disposition", "attachment; filename=\"results.zip\""))
+            // Already deflated
+            .insert_header(ContentEncoding::Identity)
+            .body(batch::bundle_zip(&files)?),
+    };
+    claim.response(res).await
+}
+
+/// Align an uploaded subtitle file (multipart: subtitles, audio or audio_url, language, overlap_policy)
+#[utoipa::path(
+    post,
+    path = "/api/v1/upload/align",
+    tag = "alignment",
+    params(OutputQuery, StoreQuery, DebugQuery),
+    responses(
+        (status = 200, body = models::FileAlignmentResponse),
+        (status = 400, description = "Invalid input", body = ErrorResponse),
+        (status = 413, description = "Uploaded file too large", body = ErrorResponse),
+        (status = 422, description = "Unsupported audio format", body = ErrorResponse)
+    )
+)]
+async fn upload_align(payload: actix_multipart::Multipart, query: web::Query<OutputQuery>, store: web::Query<StoreQuery>, debug: web::Query<DebugQuery>, format: codec::Format) -> Result<HttpResponse, ApiError> {
+    let recorder = debug.debug_timings.then(stages::Recorder::new);
+    let upload = stages::scoped_async(recorder.as_ref(), upload::read_align_upload(payload)).await?;
+    
+    log::info!("Upload alignment request: {} byte subtitle file, audio: {}",
+        upload.subtitles.bytes.len(), upload.audio.is_some());
+    
+    let span = tracing::Span::current();
+    let block_recorder = recorder.clone();
+    let mut response = web::block(move || span.in_scope(|| stages::scoped(block_recorder.as_ref(), || upload::align_upload(upload)))).await
+        .map_err(|e| ApiError::internal(format!("Worker error: {}", e)))??;
+    response.debug_timings = recorder.map(|recorder| recorder.timings());
+    
+    log::info!("Aligned {} uploaded cues with {} warnings",
+        response.cues.len(), response.warnings.len());
+    file_alignment_response(response, query.output_format, store.store, format).await
+}
+
+/// Estimate how fast uploaded audio is spoken, per cue (multipart: audio or audio_url, subtitles or text, language, comfortable_rate)
+#[utoipa::path(
+    post,
+    path = "/api/v1/speech-rate",
+    tag = "learning",
+    responses(
+        (status = 200, body = models::SpeechRateResponse),
+        (status = 400, description = "Invalid input", body = ErrorResponse),
+        (status = 413, description = "Uploaded file too large", body = ErrorResponse),
+        (status = 422, description = "Unsupported audio format", body = ErrorResponse)
+    )
+)]
+async fn speech_rate(payload: actix_multipart::Multipart) -> Result<HttpResponse, ApiError> {
+    let upload = upload::read_speech_rate_upload(payload).await?;
+    log::info!("Speech rate request: language '{}', comfortable rate {:.1} syllables/s",
+        upload.language, upload.comfortable_rate);
+    
+    let span = tracing::Span::current();
+    let response = web::block(move || span.in_scope(|| upload::speech_rate(upload))).await
+        .map_err(|e| ApiError::internal(format!("Worker error: {}", e)))??;
+    
+    log::info!("Measured {} cues, {} too fast", response.cues.len(), response.n_too_fast);
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Cut a clip of each aligned word or phrase from uploaded audio, for flashcards (multipart: audio or audio_url, timings, language, phrases, padding_ms)
+#[utoipa::path(
+    post,
+    path = "/api/v1/audio-snippets",
+    tag = "learning",
+    params(StoreQuery),
+    responses(
+        (status = 200, description = "ZIP of WAV clips and snippets.json, or with store=true the snippets with a StoredObject link each", body = models::AudioSnippetResponse),
+        (status = 400, description = "Invalid input", body = ErrorResponse),
+        (status = 413, description = "Uploaded file too large, or too many snippets", body = ErrorResponse),
+        (status = 422, description = "Unsupported audio format, or store=true without object storage", body = ErrorResponse)
+    )
+)]
+async fn audio_snippets(payload: actix_multipart::Multipart, store: web::Query<StoreQuery>) -> Result<HttpResponse, ApiError> {
+    if store.store {
+        // Fail before the upload is read, not after the clips are cut
+        storage::bucket().ok_or_else(|| ApiError::unsupported("Object storage is not configured (S3_BUCKET)"))?;
+    }
+    let upload = upload::read_snippet_upload(payload).await?;
+    log::info!("Audio snippet request: {} timings, {} phrases", upload.timings.len(), upload.phrases.len());
+    
+    let span = tracing::Span::current();
+    let (mut response, clips) = web::block(move || span.in_scope(|| upload::audio_snippets(upload))).await
+        .map_err(|e| ApiError::internal(format!("Worker error: {}", e)))??;
+    log::info!("Cut {} snippets, {} phrases missing", response.snippets.len(), response.missing.len());
+    
+    if !store.store {
+        // Hundreds of WAV clips: written to a temporary file off the workers
+        let zip = web::block(move || spool::temp_file(|file| snippets::bundle_zip(&response, &clips, file).map(drop))).await
+            .map_err(|e| ApiError::internal(format!("Worker error: {}", e)))??;
+        return Ok(HttpResponse::Ok()
+            .content_type("application/zip")
+            .insert_header(("content-disposition", "attachment; filename=\"snippets.zip\""))
+            // Already deflated
+            .insert_header(ContentEncoding::Identity)
+            .body(spool::body(zip).await?));
+    }
+    
+    snippets::store(&mut response, clips).await?;
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Score a learner's recording of a cue against its reference word timings, for shadowing practice (multipart: audio or audio_url, timings)
+#[utoipa::path(
+    post,
+    path = "/api/v1/pronunciation/score",
+    tag = "learning",
+    responses(
+        (status = 200, body = models::PronunciationResponse),
+        (status = 400, description = "Invalid input, or no speech in the recording", body = ErrorResponse),
+        (status = 413, description = "Uploaded file too large", body = ErrorResponse),
+        (status = 422, description = "Unsupported audio format", body = ErrorResponse)
+    )
+)]
+async fn score_pronunciation(payload: actix_multipart::Multipart) -> Result<HttpResponse, ApiError> {
+    let upload = upload::read_pronunciation_upload(payload).await?;
+    log::info!("Pronunciation request: {} reference words", upload.timings.len());
+    
+    let span = tracing::Span::current();
+    let response = web::block(move || span.in_scope(|| upload::pronunciation(upload))).await
+        .map_err(|e| ApiError::internal(format!("Worker error: {}", e)))??;
+    
+    log::info!("Scored pronunciation {:.2}: {}/{} words matched, tempo {:.2}",
+        response.score, response.n_matched, response.words.len(), response.tempo);
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Bundle a subtitle file into a practice pack for offline use: word timings, a clip of each cue, vocabulary and difficulty (multipart: subtitles, audio or audio_url, language, gloss)
+#[utoipa::path(
+    post,
+    path = "/api/v1/practice-pack",
+    tag = "learning",
+    params(StoreQuery),
+    responses(
+        (status = 200, description = "ZIP of pack.json and the media it lists, or a StoredObject link with store=true", body = models::PracticePack),
+        (status = 400, description = "Invalid input", body = ErrorResponse),
+        (status = 413, description = "Uploaded file too large, or too many or too long cues to clip", body = ErrorResponse),
+        (status = 422, description = "Unsupported audio format, gloss for a language pair nothing covers, or store=true without object storage", body = ErrorResponse)
+    )
+)]
+async fn practice_pack(payload: actix_multipart::Multipart, store: web::Query<StoreQuery>) -> Result<HttpResponse, ApiError> {
+    if store.store {
+        storage::bucket().ok_or_else(|| ApiError::unsupported("Object storage is not configured (S3_BUCKET)"))?;
+    }
+    let upload = upload::read_practice_pack_upload(payload).await?;
+    let glosser = gloss::glosser();
+    if let Some(target) = &upload.gloss && !glosser.supports(&upload.language, target) {
+        return Err(ApiError::unsupported(format!("No dictionary or translation service glosses '{}' in '{}'", upload.language, target)));
+    }
+    log::info!("Practice pack request: language '{}', audio {}", upload.language, upload.audio.is_some());
+    
+    let target = upload.gloss.clone();
+    let span = tracing::Span::current();
+    let (mut pack, files) = web::block(move || span.in_scope(|| upload::practice_pack(upload))).await
+        .map_err(|e| ApiError::internal(format!("Worker error: {}", e)))??;
+    if let Some(target) = target {
+        practice::gloss(&mut pack, glosser, &target).await;
+    }
+    log::info!("Built practice pack: {} cues, {} words, {} media files", pack.cues.len(), pack.vocabulary.len(), pack.media.len());
+    
+    // A clip of every cue: zipped into a temporary file off the workers
+    let zip = web::block(move || spool::temp_file(|file| practice::bundle_zip(&pack, &files, file).map(drop))).await
+        .map_err(|e| ApiError::internal(format!("Worker error: {}", e)))??;
+    if store.store {
+        let bytes = tokio::fs::read(zip.path()).await
+            .map_err(|e| ApiError::internal(format!("Failed to read pack: {}", e)))?;
+        return stored_response("practice-pack.zip", "application/zip", bytes).await;
+    }
+    Ok(HttpResponse::Ok()
+        .content_type("application/zip")
+        .insert_header(("content-disposition", "attachment; filename=\"practice-pack.zip\""))
+        // Already deflated
+        .insert_header(ContentEncoding::Identity)
+        .body(spool::body(zip).await?))
+}
+
+/// Score the quality of an existing alignment
+#[utoipa::path(
+    post,
+    path = "/api/v1/align/score",
+    tag = "alignment",
+    request_body(content = ScoreRequest),
+    responses(
+        (status = 200, body = models::ScoreResponse),
+        (status = 400, description = "Invalid input", body = ErrorResponse)
+    )
+)]
+async fn score_alignment(req: web::Json<ScoreRequest>) -> Result<HttpResponse, ApiError> {
+    log::info!("Score request: '{}' ({} timings)", req.text, req.timings.len());
+
+    req.admit().await?;
+    let response = quality::score_alignment(&req)?;
+    log::info!("Alignment score {:.2} (needs review: {})",
+        response.score, response.needs_review);
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Queue long-running work and return its job ID right away
+#[utoipa::path(
+    post,
+    path = "/api/v1/jobs",
+    tag = "jobs",
+    request_body(content = JobSubmission),
+    params(("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the original job instead of a new one")),
+    responses(
+        (status = 202, description = "The new job, or the original one for a repeated Idempotency-Key (with Idempotent-Replayed: true)", body = models::JobStatus),
+        (status = 400, description = "Invalid callback_url, or Idempotency-Key reused for a different job", body = ErrorResponse),
+        (status = 409, description = "A request with the same Idempotency-Key is still in progress", body = ErrorResponse),
+        (status = 503, description = "Too many pending jobs", body = ErrorResponse)
+    )
+)]
+async fn submit_job(http_req: actix_web::HttpRequest, req: web::Json<JobSubmission>) -> Result<HttpResponse, ApiError> {
+    let req = req.into_inner();
+    
+    let claim = idempotency::claim(&http_req, &serde_json::to_vec(&req).unwrap_or_default())?;
+    if let idempotency::Claim::Replay(res) = claim {
+        log::info!("Replaying job submission for a repeated Idempotency-Key");
+        return Ok(res);
+    }
+    
+    req.job.validate()?;
+    let status = jobs::store().submit(req, caller_tenant(&http_req)).await?;
+    log::info!("Job {} queued ({:?})", status.id, status.kind);
+    claim.job(&status.id);
+    Ok(HttpResponse::Accepted().json(status))
+}
+
+/// Job state, progress and, once finished, its result or error
+#[utoipa::path(
+    get,
+    path = "/api/v1/jobs/{id}",
+    tag = "jobs",
+    params(("id" = String, Path, description = "Job ID returned on submission")),
+    responses(
+        (status = 200, body = models::JobStatus),
+        (status = 404, description = "Unknown or expired job", body = ErrorResponse)
+    )
+)]
+async fn get_job(http_req: actix_web::HttpRequest, id: web::Path<String>, format: codec::Format) -> Result<HttpResponse, ApiError> {
+    let status = jobs::store().lookup(&id).await?
+        .filter(|status| jobs::visible_to(status, caller_tenant(&http_req).as_deref()))
+        .ok_or_else(|| unknown_job(&id))?;
+    format.respond(&status)
+}
+
+/// The tenant of a bearer-token caller, whose jobs are the only ones it sees
+fn caller_tenant(http_req: &actix_web::HttpRequest) -> Option<String> {
+    http_req.extensions().get::<auth::Client>().and_then(|client| client.tenant.clone())
+}
+
+/// Jobs by tenant, file, state and submission time, newest first
+///
+/// Results are left out; fetch a job by ID for its result. Callers with a
+/// tenant only see that tenant's jobs. Without `JOB_DATABASE_URL`, only
+/// jobs that haven't expired from memory are listed.
+#[utoipa::path(
+    get,
+    path = "/api/v1/jobs",
+    tag = "jobs",
+    params(JobQuery),
+    responses(
+        (status = 200, body = models::JobList),
+        (status = 400, description = "Invalid query", body = ErrorResponse)
+    )
+)]
+async fn list_jobs(http_req: actix_web::HttpRequest, query: web::Query<JobQuery>) -> Result<HttpResponse, ApiError> {
+    let mut query = query.into_inner();
+    if let Some(tenant) = caller_tenant(&http_req) {
+        query.tenant = Some(tenant);
+    }
+    
+    let jobs = jobs::store().list(&query).await?;
+    Ok(HttpResponse::Ok().json(models::JobList { jobs }))
+}
+
+/// Cancel a queued or running job
+#[utoipa::path(
+    delete,
+    path = "/api/v1/jobs/{id}",
+    tag = "jobs",
+    params(("id" = String, Path, description = "Job ID returned on submission")),
+    responses(
+        (status = 200, description = "Current status; running jobs become cancelled shortly after", body = models::JobStatus),
+        (status = 404, description = "Unknown or expired job", body = ErrorResponse)
+    )
+)]
+async fn cancel_job(http_req: actix_web::HttpRequest, id: web::Path<String>) -> Result<HttpResponse, ApiError> {
+    log::info!("Cancelling job {}", id);
+    
+    if !jobs::store().lookup(&id).await?.is_some_and(|status| jobs::visible_to(&status, caller_tenant(&http_req).as_deref())) {
+        return Err(unknown_job(&id));
+    }
+    let status = jobs::store().cancel(&id).await?.ok_or_else(|| unknown_job(&id))?;
+    Ok(HttpResponse::Ok().json(status))
+}
+
+/// Presigned URL to upload a large file (e.g. audio) straight to object storage
+///
+/// PUT the file to `url`, then pass `audio_url` to `/upload/align`.
+#[utoipa::path(
+    post,
+    path = "/api/v1/storage/uploads",
+    tag = "storage",
+    request_body(content = models::UploadUrlRequest),
+    responses(
+        (status = 200, body = models::PresignedUpload),
+        (status = 422, description = "Object storage not configured", body = ErrorResponse)
+    )
+)]
+async fn create_upload_url(req: web::Json<models::UploadUrlRequest>) -> Result<HttpResponse, ApiError> {
+    log::info!("Upload URL request for '{}'", req.filename);
+    
+    let upload = storage::upload_url(&req.filename).await?;
+    Ok(HttpResponse::Ok().json(upload))
+}
+
+/// The caller's requests, characters and audio this month, against its quotas
+///
+/// Counted by month, per tenant for bearer tokens and per API key
+/// otherwise. With `JOB_DATABASE_URL`, counts are shared by every instance
+/// (synced every 10 seconds); without it, they're this instance's alone
+/// and start over when it restarts.
+#[utoipa::path(
+    get,
+    path = "/api/v1/usage",
+    tag = "system",
+    responses(
+        (status = 200, body = models::UsageReport),
+        (status = 404, description = "Authentication is off, so usage isn't counted", body = ErrorResponse)
+    )
+)]
+async fn get_usage(http_req: actix_web::HttpRequest) -> Result<HttpResponse, ApiError> {
+    let client = http_req.extensions().get::<auth::Client>().cloned()
+        .ok_or_else(|| ApiError::not_found("Usage is counted per API key or tenant, and authentication is off"))?;
+    
+    Ok(HttpResponse::Ok().json(usage::report(&client)))
+}
+
+/// Current log filter, request logging and feature flags
+#[utoipa::path(
+    get,
+    path = "/admin/settings",
+    tag = "admin",
+    responses(
+        (status = 200, body = models::AdminSettings),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse)
+    )
+)]
+async fn get_settings() -> impl Responder {
+    HttpResponse::Ok().json(admin::settings())
+}
+
+/// Change the log filter, request logging or feature flags without restarting
+#[utoipa::path(
+    patch,
+    path = "/admin/settings",
+    tag = "admin",
+    request_body(content = models::AdminSettingsUpdate),
+    responses(
+        (status = 200, description = "Settings after the change", body = models::AdminSettings),
+        (status = 400, description = "Invalid log filter or unknown feature flag; nothing changed", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse)
+    )
+)]
+async fn update_settings(http_req: actix_web::HttpRequest, req: web::Json<models::AdminSettingsUpdate>) -> Result<HttpResponse, ApiError> {
+    let admin = http_req.extensions().get::<auth::Client>().cloned()
+        .ok_or_else(|| ApiError::new(ErrorCode::Unauthorized, "Missing admin key"))?;
+    
+    let settings = admin::update(req.into_inner(), &admin)?;
+    Ok(HttpResponse::Ok().json(settings))
+}
+
+/// Size and hit rate of the alignment cache
+#[utoipa::path(
+    get,
+    path = "/admin/cache",
+    tag = "admin",
+    responses(
+        (status = 200, body = models::CacheStats),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse)
+    )
+)]
+async fn get_cache() -> impl Responder {
+    HttpResponse::Ok().json(cache::stats())
+}
+
+/// Empty the alignment cache, e.g. after deploying new duration models
+#[utoipa::path(
+    delete,
+    path = "/admin/cache",
+    tag = "admin",
+    responses(
+        (status = 200, description = "The cache after flushing", body = models::CacheStats),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse)
+    )
+)]
+async fn flush_cache(http_req: actix_web::HttpRequest) -> Result<HttpResponse, ApiError> {
+    let admin = http_req.extensions().get::<auth::Client>().cloned()
+        .ok_or_else(|| ApiError::new(ErrorCode::Unauthorized, "Missing admin key"))?;
+    
+    let flushed = cache::flush();
+    log::warn!("{} flushed {} cached alignments", admin, flushed);
+    Ok(HttpResponse::Ok().json(cache::stats()))
+}
+
+/// Read dictionaries, frequency and stopword lists and content lists from
+/// disk again, e.g. after a terminology update
+///
+/// Requests already running finish with the data they started with. Same as
+/// sending the process SIGHUP.
+#[utoipa::path(
+    post,
+    path = "/admin/reload",
+    tag = "admin",
+    responses(
+        (status = 200, description = "The data files now loaded", body = models::DataReload),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse)
+    )
+)]
+async fn reload_data(http_req: actix_web::HttpRequest) -> Result<HttpResponse, ApiError> {
+    let admin = http_req.extensions().get::<auth::Client>().cloned()
+        .ok_or_else(|| ApiError::new(ErrorCode::Unauthorized, "Missing admin key"))?;
+    
+    log::warn!("{} is reloading data files", admin);
+    let reloaded = web::block(reload::reload).await
+        .map_err(|e| ApiError::internal(format!("Worker error: {}", e)))?;
+    Ok(HttpResponse::Ok().json(reloaded))
+}
+
+/// Languages with data in memory, what each costs, and when each was last
+/// requested, with alignment cache occupancy
+#[utoipa::path(
+    get,
+    path = "/admin/languages",
+    tag = "admin",
+    responses(
+        (status = 200, body = models::LanguageResourcesReport),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse)
+    )
+)]
+async fn get_languages() -> Result<HttpResponse, ApiError> {
+    let report = web::block(resources::report).await
+        .map_err(|e| ApiError::internal(format!("Worker error: {}", e)))?;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/// Drop languages' dictionaries, frequency and content lists and cached
+/// alignments, by code or by how long they've gone unused, to free memory
+///
+/// Requests in an evicted language work as if it had no data files until
+/// the next `/admin/reload` or SIGHUP reads them back.
+#[utoipa::path(
+    post,
+    path = "/admin/languages/evict",
+    tag = "admin",
+    request_body = models::LanguageEviction,
+    responses(
+        (status = 200, body = models::EvictedLanguages),
+        (status = 400, description = "Neither languages nor idle_seconds given", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse)
+    )
+)]
+async fn evict_languages(http_req: actix_web::HttpRequest, req: web::Json<models::LanguageEviction>) -> Result<HttpResponse, ApiError> {
+    let admin = http_req.extensions().get::<auth::Client>().cloned()
+        .ok_or_else(|| ApiError::new(ErrorCode::Unauthorized, "Missing admin key"))?;
+
+    let evicted = web::block(move || resources::evict(&req, &admin)).await
+        .map_err(|e| ApiError::internal(format!("Worker error: {}", e)))??;
+    Ok(HttpResponse::Ok().json(evicted))
+}
+
+fn unknown_job(id: &str) -> ApiError {
+    ApiError::not_found(format!("No job {}", id)).with_field("id")
+}
+
+/// Every `/api` endpoint, relative to the version scope it's mounted under
+fn api_routes(cfg: &mut web::ServiceConfig, max_batch_zip_bytes: usize) {
+    cfg
+        .route("/health", web::get().to(health))
+        .route("/health/deep", web::get().to(deep_health))
+        .route("/usage", web::get().to(get_usage))
+        .route("/warmup", web::post().to(warmup))
+        .route("/validate-request", web::post().to(validate_request))
+        .route("/tokenize", web::post().to(tokenize))
+        .route("/batch-tokenize", web::post().to(batch_tokenize))
+        .route("/batch-align", web::post().to(batch_align))
+        .route("/align", web::post().to(align_words))  // Changed from /api/align-words
+        .route("/align/file", web::post().to(align_file))
+        .route("/align/file/stream", web::post().to(align_file_stream))
+        .route("/align/file/stats", web::post().to(align_file_stats))
+        .route("/align/score", web::post().to(score_alignment))
+        .route("/subtitles/parse", web::post().to(parse_subtitles))
+        .route("/subtitles/generate", web::post().to(generate_subtitles))
+        .route("/subtitles/resync", web::post().to(resync_subtitles))
+        .route("/subtitles/validate", web::post().to(validate_subtitles))
+        .route("/subtitles/merge", web::post().to(merge_cues))
+        .route("/subtitles/split", web::post().to(split_cues))
+        .route("/subtitles/pair", web::post().to(pair_subtitles))
+        .route("/dub/fit", web::post().to(fit_dub_script))
+        .route("/predict-duration", web::post().to(predict_duration))
+        .route("/exercises/cloze", web::post().to(cloze_exercises))
+        .route("/vocabulary", web::post().to(extract_vocabulary))
+        .route("/collocations", web::post().to(find_collocations))
+        .route("/difficulty", web::post().to(score_difficulty))
+        .route("/known-words/{id}", web::put().to(put_known_words))
+        .route("/known-words/{id}", web::get().to(get_known_words))
+        .route("/known-words/{id}", web::delete().to(delete_known_words))
+        .route("/lookup", web::get().to(lookup))
+        .route("/phonemize", web::post().to(phonemize))
+        .route("/phonemize/languages", web::get().to(phonemize_languages))
+        .route("/syllabify", web::post().to(syllabify))
+        .route("/highlight", web::post().to(highlight_spans))
+        .route("/normalize", web::post().to(normalize_text))
+        .route("/subtitles/diff", web::post().to(diff_transcript))
+        .route("/ws", web::get().to(ws_session))
+        .service(
+            web::resource("/batch/zip")
+                .app_data(web::PayloadConfig::new(max_batch_zip_bytes))
+                .route(web::post().to(batch_zip))
+        )
+        .route("/upload/align", web::post().to(upload_align))
+        .route("/speech-rate", web::post().to(speech_rate))
+        .route("/audio-snippets", web::post().to(audio_snippets))
+        .route("/pronunciation/score", web::post().to(score_pronunciation))
+        .route("/practice-pack", web::post().to(practice_pack))
+        .route("/storage/uploads", web::post().to(create_upload_url))
+        .route("/jobs", web::post().to(submit_job))
+        .route("/jobs", web::get().to(list_jobs))
+        .route("/jobs/{id}", web::get().to(get_job))
+        .route("/jobs/{id}", web::delete().to(cancel_job));
 }
 
 #[actix_web::main]
@@ -73,49 +1807,328 @@ async fn main() -> std::io::Result<()> {
 
     dotenv::dotenv().ok();
     
-    env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
+    let config = config::Config::load().expect("Invalid configuration");
     
+    let tracer_provider = telemetry::init(config.otlp_endpoint.as_deref(), &config.service_name);
     
-    let port = env::var("RUST_SERVICE_PORT")
-        .unwrap_or_else(|_| "8080".to_string())
-        .parse::<u16>()
-        .expect("Invalid port number");
+    duration::init(&config.duration_dir);
+    calibration::init(config.calibration.clone());
+    frequency::init(&config.frequency_dir);
+    dictionary::init(&config.dictionary_dir);
+    g2p::init(&config.pronunciation_dir, config.espeak_ng_path.as_deref());
+    syllables::init(&config.hyphenation_dir);
+    content::init(&config.content_dir);
+    resources::init();
+    tts::init(config.tts_engine_url.clone());
+    gloss::init(config.gloss_mt_url.clone(), config.gloss_mt_api_key.clone()).expect("Invalid gloss configuration");
+    validation::init(validation::Limits {
+        max_text_length: config.max_text_length,
+        max_batch_size: config.max_batch_size,
+    });
+    features::init(config.feature_flags.as_deref()).expect("Invalid feature flags");
+    batch::init(config.batch_parallelism);
+    spool::init(config.upload_memory_budget);
     
-    let bind_address = format!("0.0.0.0:{}", port);
+    if let Some(command) = &config.command {
+        let result = cli::run(command).await;
+        telemetry::shutdown(tracer_provider);
+        if let Err(e) = result {
+            log::error!("{}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    
+    let bind_address = format!("0.0.0.0:{}", config.port);
+    let tls_config = config.tls()
+        .and_then(|tls| tls.map(|tls| tls.server_config()).transpose())
+        .expect("Invalid TLS configuration");
+    let socket_mode = config.socket_mode().expect("Invalid socket configuration");
+    
+    webhooks::init(config.webhook_secret.clone(), config.egress());
+    jobs::init(config.job_database_url.as_deref()).await.expect("Invalid job database");
+    audit::init(config.audit_log.as_deref(), config.audit_key.as_deref()).await.expect("Invalid audit log");
+    storage::init(config.storage()).expect("Invalid object storage configuration");
+    downloads::init(config.downloads()).expect("Invalid audio fetch configuration");
+    lifecycle::preload(config.preload_languages());
+    let api_keys = auth::ApiKeys::load(config.api_keys.as_deref(), config.api_keys_file.as_deref())
+        .expect("Invalid API key configuration");
+    let jwt = config.jwt_issuer.clone().map(|issuer| auth::jwt::JwtValidator::new(auth::jwt::JwtConfig {
+        issuer,
+        jwks_url: config.jwt_jwks_url.clone(),
+        audience: config.jwt_audience.clone(),
+        tenant_claim: config.jwt_tenant_claim.clone(),
+    }));
+    auth::init(api_keys, jwt);
+    admin::init(auth::ApiKeys::load(config.admin_keys.as_deref(), config.admin_keys_file.as_deref())
+        .expect("Invalid admin key configuration"));
     
-    log::info!(" Starting DuoTok Enhanced Rust Service on {}", bind_address);
+    if let Some(jwt) = auth::authenticator().and_then(|auth| auth.jwt.as_ref()) {
+        match jwt.refresh().await {
+            Ok(count) => log::info!("Loaded {} JWT signing keys", count),
+            Err(e) => log::error!("No JWT signing keys yet, bearer tokens will be rejected: {}", e),
+        }
+        auth::jwt::spawn_refresh(jwt);
+    }
+    
+    if let Some(grpc_port) = config.grpc_port {
+        grpc::spawn((config.grpc_host, grpc_port).into(), tls_config.clone());
+    }
+    
+    if config.tcp {
+        log::info!(" Starting DuoTok Enhanced Rust Service on {}://{}", if tls_config.is_some() { "https" } else { "http" }, bind_address);
+    }
     log::info!(" Supported languages: 30+ languages");
     log::info!(" High-performance tokenization ready");
     
-    HttpServer::new(|| {
-        let cors = Cors::default()
-            .allow_any_origin()
-            .allow_any_method()
-            .allow_any_header()
-            .expose_any_header()
-            .max_age(3600);
+    concurrency::init(concurrency::ConcurrencyLimits::parse(
+        config.max_concurrent_requests,
+        config.route_concurrency.as_deref(),
+    ).expect("Invalid concurrency limits"));
+    idempotency::init(config.idempotency_ttl);
+    usage::init(usage::Quotas::parse(config.usage_quotas.as_deref()).expect("Invalid usage quotas"), config.job_database_url.as_deref()).await
+        .expect("Invalid usage database");
+    cache::init(config.alignment_cache_size, config.alignment_cache_bytes);
+    
+    let cors_config = cors::CorsConfig::parse(
+        config.cors_mode.as_deref(),
+        config.cors_allowed_origins.as_deref(),
+        config.cors_allowed_methods.as_deref(),
+        config.cors_allowed_headers.as_deref(),
+        config.cors_private_network,
+    ).expect("Invalid CORS configuration");
+    match cors_config.mode {
+        cors::CorsMode::Dev => log::warn!("CORS_MODE=dev: any origin may call the API"),
+        cors::CorsMode::Strict => log::info!("CORS origins allowed: {}", cors_config.origins.join(", ")),
+    }
+    
+    let max_batch_zip_bytes = config.max_batch_zip_bytes;
+    let max_json_bytes = config.max_json_bytes;
+    codec::init(max_json_bytes);
+    let request_timeout = config.request_timeout;
+    let max_concurrent_streams = config.max_concurrent_streams;
+    match (config.http2, max_concurrent_streams) {
+        (false, _) => log::info!("Speaking HTTP/1.1 only"),
+        (true, Some(max)) => log::info!("Speaking HTTP/2 (h2c on plain TCP), up to {} concurrent streams per connection", max),
+        (true, None) => log::info!("Speaking HTTP/2 (h2c on plain TCP)"),
+    }
+    let server = HttpServer::new(move || {
+        let cors_config = cors_config.clone();
         
         App::new()
-            .wrap(Logger::default())
-            .wrap(cors)
-            // Add middleware to set Private Network Access header
+            // 504 for requests that run too long; dropping the handler stops
+            // async work such as audio fetches (blocking work runs to completion)
+            .wrap_fn(move |req, srv| {
+                let fut = srv.call(req);
+                async move {
+                    let Some(timeout) = request_timeout else {
+                        return fut.await;
+                    };
+                    tokio::time::timeout(timeout, fut).await.unwrap_or_else(|_| {
+                        Err(ApiError::new(ErrorCode::Timeout, format!("Request took longer than {}s", timeout.as_secs())).into())
+                    })
+                }
+            })
+            // Answer 503 at once when saturated, rather than queueing without
+            // bound; a streamed body keeps its slots, and the request timeout,
+            // until it's sent
+            .wrap_fn(move |req, srv| {
+                let deadline = request_timeout.map(|timeout| tokio::time::Instant::now() + timeout);
+                let call = match concurrency::acquire(req.path(), req.conn_data::<concurrency::ConnectionStreams>()) {
+                    Ok(permits) => Ok((srv.call(req), permits)),
+                    Err(e) => Err(req.into_response(concurrency::shed_response(e))),
+                };
+                async move {
+                    match call {
+                        Ok((fut, permits)) => fut.await.map(|res| concurrency::hold(res, permits, deadline)),
+                        Err(res) => Ok(res),
+                    }
+                }
+            })
+            // Reject /api requests without valid credentials, when auth is
+            // configured, or from clients that have used up a monthly quota
+            // (429); count what the rest use
             .wrap_fn(|req, srv| {
+                let Some(authenticator) = auth::authenticator() else {
+                    return Either::Left(usage::track(None, srv.call(req)));
+                };
+                let client = match authenticator.authorize(&req) {
+                    Ok(client) => client,
+                    Err(e) => return Either::Right(future::ready(Ok(req.error_response(e)))),
+                };
+                // Named for the access log and audit even if over quota
+                if let Some(client) = &client {
+                    audit::identify(&req, client);
+                    req.extensions_mut().insert(client.clone());
+                }
+                match usage::admit(client.as_ref(), req.path()) {
+                    Ok(account) => Either::Left(usage::track(account, srv.call(req))),
+                    Err(e) if e.code == ErrorCode::QuotaExceeded => {
+                        Either::Right(future::ready(Ok(req.into_response(usage::exceeded_response(e)))))
+                    }
+                    Err(e) => {
+                        Either::Right(future::ready(Ok(req.error_response(e))))
+                    }
+                }
+            })
+            // Default format, prefixed with the API client (and tenant), ending
+            // with the request ID; headers are appended while verbose request
+            // logging is on (see /admin/settings)
+            .wrap(Logger::new("%{client}xo %a \"%r\" %s %b \"%{Referer}i\" \"%{User-Agent}i\" %T %{request_id}xi%{request_headers}xi%{response_headers}xo")
+                // As authenticated by the middleware below, not checked again
+                .custom_response_replace("client", |res| {
+                    res.request().extensions().get::<auth::Client>().map_or_else(|| "-".to_string(), ToString::to_string)
+                })
+                .custom_request_replace("request_id", |req| req.extensions().get::<RequestId>().map_or_else(|| "-".to_string(), ToString::to_string))
+                .custom_request_replace("request_headers", |req| telemetry::verbose_headers("request", req.headers()))
+                .custom_response_replace("response_headers", |res| telemetry::verbose_headers("response", res.headers())))
+            // Root span per request, continuing the caller's trace from `traceparent`
+            .wrap(TracingLogger::default())
+            // Negotiate the API version and UI language, label responses
+            // with the version, their request ID (the tracing span's) and
+            // processing time, and write the audit log
+            .wrap_fn(|mut req, srv| {
+                let started = std::time::Instant::now();
+                let audit = audit::start(&mut req, started);
+                let wrap_arrays = envelope::requested(req.query_string());
+                let locale = req.headers().get(actix_web::http::header::ACCEPT_LANGUAGE)
+                    .and_then(|value| value.to_str().ok())
+                    .map(i18n::Locale::negotiate)
+                    .unwrap_or_default();
+                let call = match versioning::requested(req.path(), req.headers()) {
+                    Ok(version) => Ok((srv.call(req), version)),
+                    Err(e) => Err(req.error_response(e)),
+                };
+                async move {
+                    match call {
+                        Ok((fut, version)) => match audit::scope(i18n::scope(locale, fut)).await {
+                            (Ok(res), language) => {
+                                let request_id = res.request().extensions().get::<RequestId>().map(ToString::to_string);
+                                if let Some(audit) = audit {
+                                    audit.finish(&res, request_id.clone(), language);
+                                }
+                                let meta = envelope::meta(request_id, started, version);
+                                let res = envelope::wrap(res, meta, version.is_some(), wrap_arrays).await?;
+                                versioning::tag(res, version).await.map(compression::exempt)
+                            }
+                            (Err(err), _) => {
+                                if let Some(audit) = audit {
+                                    audit.fail(&err);
+                                }
+                                Err(versioning::tag_error(err, version))
+                            }
+                        },
+                        Err(res) => Ok(res),
+                    }
+                }
+            })
+            // gzip/br/zstd responses by Accept-Encoding, except small and
+            // already compressed ones (see `compression::exempt`); compressed
+            // request bodies are decoded by the extractors, limits apply after
+            // decoding
+            .wrap(Compress::default())
+            .wrap(cors_config.build())
+            // Add middleware to set Private Network Access header, when
+            // enabled; first, take any `api_key` out of the URL so no log
+            // below records it
+            .wrap_fn(move |mut req, srv| {
+                auth::hoist_query_key(&mut req);
+                let header = cors_config.private_network_header(req.headers().get(actix_web::http::header::ORIGIN));
                 let fut = srv.call(req);
                 async {
                     let mut res = fut.await?;
-                    res.headers_mut().insert(
-                        actix_web::http::header::HeaderName::from_static("access-control-allow-private-network"),
-                        actix_web::http::header::HeaderValue::from_static("true")
-                    );
+                    if let Some((name, value)) = header {
+                        res.headers_mut().insert(name, value);
+                    }
                     Ok(res)
                 }
             })
-            .route("/api/health", web::get().to(health))
-            .route("/api/tokenize", web::post().to(tokenize))
-            .route("/api/batch-tokenize", web::post().to(batch_tokenize))
-            .route("/api/align", web::post().to(align_words))  // Changed from /api/align-words
+            // Malformed bodies and query strings get the same error shape as everything else
+            .app_data(web::JsonConfig::default().limit(max_json_bytes).error_handler(error::json_error_handler))
+            .app_data(web::QueryConfig::default().error_handler(error::query_error_handler))
+            .route("/healthz", web::get().to(liveness))
+            .route("/readyz", web::get().to(readiness))
+            .service(web::redirect("/api/docs", "/api/docs/"))
+            .service(SwaggerUi::new("/api/docs/{_:.*}").url("/api/docs/openapi.json", openapi::ApiDoc::openapi()))
+            // Unversioned paths stay as aliases of the current version
+            .service(web::scope("/api/v1").configure(|cfg| api_routes(cfg, max_batch_zip_bytes)))
+            .service(web::scope("/api").configure(|cfg| api_routes(cfg, max_batch_zip_bytes)))
+            // Only with ADMIN_KEYS; API keys and tokens don't open it
+            .configure(|cfg| {
+                if admin::is_enabled() {
+                    cfg.service(web::scope("/admin")
+                        .wrap_fn(|req, srv| match admin::authorize(&req) {
+                            Ok(admin) => {
+                                req.extensions_mut().insert(admin);
+                                Either::Left(srv.call(req))
+                            }
+                            Err(e) => Either::Right(future::ready(Ok(req.error_response(e)))),
+                        })
+                        .route("/settings", web::get().to(get_settings))
+                        .route("/settings", web::patch().to(update_settings))
+                        .route("/cache", web::get().to(get_cache))
+                        .route("/cache", web::delete().to(flush_cache))
+                        .route("/reload", web::post().to(reload_data))
+                        .route("/languages", web::get().to(get_languages))
+                        .route("/languages/evict", web::post().to(evict_languages)));
+                }
+            })
+    });
+    
+    let server = match config.workers {
+        Some(workers) => server.workers(workers),
+        None => server,
+    }
+    .keep_alive(config.keep_alive)
+    .client_request_timeout(config.client_request_timeout)
+    .backlog(config.backlog)
+    .on_connect(move |_, data| {
+        if let Some(max) = max_concurrent_streams {
+            data.insert(concurrency::ConnectionStreams::new(max));
+        }
     })
-    .bind(&bind_address)?
-    .run()
-    .await
+    .disable_signals()
+    .shutdown_timeout(config.shutdown_timeout.as_secs());
+    let server = match tls_config {
+        _ if !config.tcp => server,
+        Some(tls_config) => server.bind_rustls_0_23(&bind_address, tls_config)?,
+        // h2c for internal clients that know to speak HTTP/2 without TLS
+        None if config.http2 => server.bind_auto_h2c(&bind_address)?,
+        None => server.bind(&bind_address)?,
+    };
+    // Sidecars on the same host can skip TCP entirely; always plain HTTP
+    #[cfg(unix)]
+    let server = match &config.socket {
+        Some(path) => {
+            let server = server.listen_uds(lifecycle::bind_socket(path, socket_mode)?)?;
+            log::info!(" Listening on Unix socket {}", path.display());
+            server
+        }
+        None => server,
+    };
+    #[cfg(not(unix))]
+    if config.socket.is_some() {
+        return Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "Unix sockets need a Unix platform"));
+    }
+    let server = server.run();
+    
+    tokio::spawn(lifecycle::stop_on_signal(server.handle(), config.drain_delay, config.shutdown_timeout));
+    #[cfg(unix)]
+    tokio::spawn(reload::reload_on_hangup());
+    let result = server.await;
+    
+    // Queued jobs were accepted, so give them the chance to finish (and call
+    // back) in the rest of the shutdown timeout
+    let remaining = jobs::store().drain(lifecycle::time_left(config.shutdown_timeout)).await;
+    if remaining > 0 {
+        log::warn!("Shutting down with {} jobs unfinished", remaining);
+    }
+    usage::flush().await;
+    
+    if let Some(path) = &config.socket {
+        let _ = std::fs::remove_file(path);
+    }
+    
+    telemetry::shutdown(tracer_provider);
+    result
 }
@@ -1,37 +1,342 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::error::{ApiError, ErrorCode};
+use crate::subtitles;
 
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
 pub struct TokenizeRequest {
     pub text: String,
     pub language: String,
+    /// Words the learner knows; `/tokenize` then marks each token known or not
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub known: Option<KnownWords>,
 }
 
+/// Lemmas a learner already knows: listed inline, or the ID of a list
+/// registered with `PUT /known-words/{id}`
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum KnownWords {
+    Words(Vec<String>),
+    List(String),
+}
 
-#[derive(Debug, Deserialize, Serialize)]
+/// A learner's known words, registered under an ID of the caller's choosing
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct KnownWordsList {
+    pub language: String,
+    pub words: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct KnownWordsListResponse {
+    pub id: String,
+    pub language: String,
+    /// Lowercased and deduplicated, in alphabetical order
+    pub words: Vec<String>,
+}
+
+
+/// Why a tokenize, alignment or score result is a best guess rather than a clean one
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WarningCode {
+    /// The language has no word segmenter of its own (or isn't one the
+    /// tokenizer knows), so text was split on spaces and punctuation
+    DefaultTokenizer,
+    /// No duration model for the language, so words were weighted by length
+    NoDurationModel,
+    /// Forced alignment failed, so timings were estimated from the subtitle
+    /// window (`allow_fallback`)
+    ForcedAlignmentFallback,
+    /// The TTS engine failed, so timings were predicted from the duration model
+    TtsEngineFallback,
+    /// A gloss source failed, so its words were glossed by other sources or
+    /// not at all
+    GlossFailed,
+    /// `audio_url` was given but only the timings were scored
+    AudioIgnored,
+    /// No G2P data for the language, so words were weighed by their
+    /// spelling rather than their pronunciation
+    NoG2pData,
+}
+
+/// Something that didn't stop a request but makes its result less reliable
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, ToSchema)]
+pub struct Warning {
+    pub code: WarningCode,
+    pub message: String,
+}
+
+impl Warning {
+    pub fn new(code: WarningCode, message: impl Into<String>) -> Self {
+        Warning { code, message: message.into() }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
 pub struct TokenizeResponse {
     pub text: String,
     pub language: String,
     pub tokens: Vec<String>,
     pub positions: Vec<TokenPosition>,
+    /// Empty for a clean result
+    #[serde(default)]
+    pub warnings: Vec<Warning>,
+    /// Categories of each token, with `content_flags=true`; empty for clean tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_flags: Option<Vec<Vec<ContentCategory>>>,
+    /// Whether each token is a known word, when the request lists some
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub known: Option<Vec<bool>>,
+    /// Each token's gloss, with `gloss=<language>`; `null` where none was found
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub glosses: Option<Vec<Option<TokenGloss>>>,
+    /// Each token's possible analyses, with `morphology=true`; empty where
+    /// the dictionary doesn't know the word
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub morphology: Option<Vec<Vec<MorphAnalysis>>>,
 }
 
+/// Options for `/tokenize` (query string)
+#[derive(Debug, Default, Deserialize, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TokenizeQuery {
+    /// Flag profanity, slurs and adult content per token from the language's lists
+    #[serde(default)]
+    pub content_flags: bool,
+    /// Attach a gloss in this language to each token
+    #[serde(default)]
+    pub gloss: Option<String>,
+    /// Attach each token's lemma and grammatical features, from the
+    /// language's dictionary
+    #[serde(default)]
+    pub morphology: bool,
+}
 
-#[derive(Debug, Deserialize, Serialize)]
+/// One reading of a word: its lemma and grammatical features
+///
+/// "estás" is `{"lemma": "estar", "person": 2, "number": "singular",
+/// "tense": "present", "mood": "indicative", "summary": "2sg present indicative"}`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct MorphAnalysis {
+    pub lemma: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub part_of_speech: Option<String>,
+    /// 1, 2 or 3
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub person: Option<u8>,
+    /// singular, plural or dual
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub number: Option<String>,
+    /// present, past, preterite, imperfect, future...
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tense: Option<String>,
+    /// indicative, subjunctive, imperative or conditional
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mood: Option<String>,
+    /// nominative, accusative, genitive, dative...
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub case: Option<String>,
+    /// masculine, feminine or neuter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gender: Option<String>,
+    /// infinitive, participle or gerund
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verb_form: Option<String>,
+    /// The lemma's infinitive ending for Romance verbs: "-ar", "-er", "-ir"...
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conjugation_group: Option<String>,
+    /// The features in a few words, e.g. "2sg present indicative"
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub summary: String,
+    /// Every tag the dictionary gives, including ones without a field above
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+/// A token's meaning in the learner's language
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct TokenGloss {
+    pub gloss: String,
+    pub source: GlossSource,
+}
+
+/// Where a gloss came from
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GlossSource {
+    /// A loaded dictionary's first sense
+    Dictionary,
+    /// The configured machine translation service
+    MachineTranslation,
+}
+
+impl GlossSource {
+    pub fn name(self) -> &'static str {
+        match self {
+            GlossSource::Dictionary => "dictionary",
+            GlossSource::MachineTranslation => "machine_translation",
+        }
+    }
+}
+
+/// Why a word may need blurring or age-gating
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentCategory {
+    Profanity,
+    Slur,
+    /// Sexual or otherwise adult-only
+    Adult,
+}
+
+impl ContentCategory {
+    pub const ALL: [ContentCategory; 3] = [ContentCategory::Profanity, ContentCategory::Slur, ContentCategory::Adult];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ContentCategory::Profanity => "profanity",
+            ContentCategory::Slur => "slur",
+            ContentCategory::Adult => "adult",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        ContentCategory::ALL.into_iter().find(|category| category.name() == name)
+    }
+}
+
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
 pub struct TokenPosition {
     pub start: usize,
     pub end: usize,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct HealthResponse {
     pub status: String,
     pub service: String,
     pub version: String,
 }
 
+/// One readiness condition, e.g. data files loaded
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReadinessCheck {
+    pub name: String,
+    pub ok: bool,
+    pub detail: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReadinessResponse {
+    /// All checks passed
+    pub ready: bool,
+    pub checks: Vec<ReadinessCheck>,
+}
+
+/// A per-language data file loaded at startup
+#[derive(Debug, Serialize, Clone, PartialEq, ToSchema)]
+pub struct DataFile {
+    pub language: String,
+    /// File name within its data directory
+    pub file: String,
+    /// First 12 hex digits of the content's SHA-256, to tell deployed data apart
+    pub version: String,
+    /// Weights or ranked words read from the file
+    pub entries: usize,
+}
+
+impl DataFile {
+    pub fn new(language: &str, path: &Path, content: &str, entries: usize) -> Self {
+        DataFile {
+            language: language.to_lowercase(),
+            file: path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default(),
+            version: hex::encode(&Sha256::digest(content.as_bytes())[..6]),
+            entries,
+        }
+    }
+}
+
+/// A data file that failed to load; on a reload, its language keeps the
+/// data it had before
+#[derive(Debug, Serialize, Clone, PartialEq, ToSchema)]
+pub struct FailedDataFile {
+    pub language: String,
+    /// File name within its data directory
+    pub file: String,
+    pub error: String,
+}
+
+impl FailedDataFile {
+    pub fn new(language: &str, path: &Path, error: impl Into<String>) -> Self {
+        FailedDataFile {
+            language: language.to_lowercase(),
+            file: path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default(),
+            error: error.into(),
+        }
+    }
+}
+
+/// One part of the service, as reported by the deep health check
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SubsystemStatus {
+    pub name: String,
+    pub ok: bool,
+    pub detail: String,
+    /// Items held in memory, for caches and stores
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entries: Option<usize>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<DataFile>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DeepHealthResponse {
+    /// `healthy`, or `degraded` when any subsystem isn't ok
+    pub status: String,
+    pub service: String,
+    pub version: String,
+    pub subsystems: Vec<SubsystemStatus>,
+}
+
+/// Languages to get ready before traffic arrives
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct WarmupRequest {
+    /// Language codes, e.g. `["ja", "es"]`
+    pub languages: Vec<String>,
+}
+
+/// What was loaded for one language
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LanguageWarmup {
+    pub language: String,
+    /// `words` for space-separated scripts, `graphemes` for Chinese, Japanese and Korean
+    pub segmenter: String,
+    /// A duration model was loaded for the language; otherwise char counting is used
+    pub duration_model: bool,
+    /// Ranked words in the language's frequency list, 0 if there is none
+    pub frequency_ranks: usize,
+    /// Headwords in the language's dictionaries, 0 if there are none
+    pub dictionary_entries: usize,
+    pub elapsed_seconds: f64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WarmupResponse {
+    pub languages: Vec<LanguageWarmup>,
+    pub elapsed_seconds: f64,
+}
+
 /// Timing information for a single word
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
 pub struct WordTiming {
     pub word: String,
     pub start: f64,
@@ -39,33 +344,1934 @@ pub struct WordTiming {
     pub confidence: f64,
     pub char_start: usize,
     pub char_end: usize,
+
+    /// Confidence is below the request's `min_confidence`
+    #[serde(default)]
+    pub flagged: bool,
 }
 
-#[derive(Debug, Deserialize,Serialize)]
+#[derive(Debug, Deserialize,Serialize, Clone, Default, ToSchema)]
 pub struct AlignmentRequest {
+    /// Required unless `cues` is given
+    #[serde(default)]
     pub text: String,
     pub language: String,
-    pub subtitle_start: f64,  
+    /// Seconds, or a subtitle timestamp such as "00:01:02,500" or "00:01:02.500"
+    #[serde(default, deserialize_with = "subtitles::deserialize_seconds")]
+    pub subtitle_start: f64,
+    /// As `subtitle_start`
+    #[serde(default, deserialize_with = "subtitles::deserialize_seconds")]
     pub subtitle_end: f64,
 
+    /// Several cues of the same audio to align at once, instead of `text`
+    /// (`POST /align` only); the gaps between them hint at where speech ends
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cues: Vec<AlignmentCue>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub audio_url: Option<String>,
+
+    /// With `audio_url`, estimate timings from the subtitle window when
+    /// forced alignment fails, instead of failing; the response's `method`
+    /// says which was used and its `warnings` why
+    #[serde(default)]
+    pub allow_fallback: bool,
+
+    /// Words below this confidence are flagged
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_confidence: Option<f64>,
+
+    /// Drop flagged words from the response instead of just marking them
+    #[serde(default)]
+    pub exclude_flagged: bool,
+
+    #[serde(default)]
+    pub mode: AlignmentMode,
+
+    /// TTS voice to predict timings for (tts mode only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub voice: Option<String>,
+
+    /// Override the language's TTS speaking rate, in weight units per second (tts mode only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speaking_rate: Option<f64>,
+
+    /// Spell out numbers, dates and symbols first (English and Spanish), so
+    /// "42" gets the time "forty-two" takes; timings are then for the
+    /// spoken text, returned as `text`
+    #[serde(default)]
+    pub normalize: bool,
+}
+
+/// One cue of a multi-cue `AlignmentRequest`
+#[derive(Debug, Deserialize, Serialize, Clone, Default, ToSchema)]
+pub struct AlignmentCue {
+    /// Reported with its results; its position from 1 if omitted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<usize>,
+    pub text: String,
+    /// Seconds or a subtitle timestamp, as for `AlignmentRequest`
+    #[serde(deserialize_with = "subtitles::deserialize_seconds")]
+    pub subtitle_start: f64,
+    #[serde(deserialize_with = "subtitles::deserialize_seconds")]
+    pub subtitle_end: f64,
+}
+
+/// What the timings are aligned against
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AlignmentMode {
+    /// Distribute words across the existing subtitle window
+    #[default]
+    Subtitle,
+    /// Predict how long a TTS voice takes to say each word, starting at `subtitle_start`
+    Tts,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GapKind {
+    /// Break at a punctuation mark
+    Punctuation,
+    /// Break between words with no punctuation
+    Pause,
+    /// No speech at the start or end of the window
+    Silence,
+}
+
+/// Stretch of time where no word should be highlighted
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct Gap {
+    pub start: f64,
+    pub end: f64,
+    pub kind: GapKind,
 }
 
 /// Response containing aligned word timings
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Clone, ToSchema)]
 pub struct AlignmentResponse {
     pub text: String,
     pub language: String,
     pub duration: f64,
     pub timings: Vec<WordTiming>,  // Changed from WordAlignment
+    pub gaps: Vec<Gap>,
     pub method: AlignmentMethod,
+    pub n_flagged: usize,
+    pub mean_confidence: f64,
+    /// Empty for a clean result
+    pub warnings: Vec<Warning>,
+    /// Where the time went, with `debug_timings=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub debug_timings: Option<StageTimings>,
+}
+
+/// One cue's alignment in a `MultiAlignmentResponse`
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CueAlignmentResult {
+    pub index: usize,
+    /// Where the words were taken to end, when the gap after the cue
+    /// suggested its subtitle stays up after the speech; the rest of the
+    /// window is a trailing silence gap
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speech_end: Option<f64>,
+    #[serde(flatten)]
+    pub alignment: AlignmentResponse,
+}
+
+/// `POST /align` with `cues`: each cue's alignment, in request order
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MultiAlignmentResponse {
+    pub language: String,
+    pub cues: Vec<CueAlignmentResult>,
+    /// Cues whose speech was taken to end before their window does
+    pub n_hinted: usize,
+}
+
+/// Word timings (as returned by `/align`) to turn into render-ready spans
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct HighlightRequest {
+    /// The aligned text; `char_start`/`char_end` of each timing are byte offsets into it
+    pub text: String,
+    pub timings: Vec<WordTiming>,
+    #[serde(default)]
+    pub format: HighlightFormat,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HighlightFormat {
+    /// HTML-escaped segments covering the whole text, and the markup joined up
+    #[default]
+    Html,
+    /// `[start_ms, end_ms, from, to]` per word, by start time
+    Ranges,
+    /// The ranges as little-endian `u32`s, 16 bytes per word (`application/octet-stream`)
+    Binary,
+}
+
+/// A piece of the text: a word with its timing, or the text between words
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct HighlightSegment {
+    /// HTML-escaped text
+    pub html: String,
+    /// `dd-word` (plus `dd-flagged` for low-confidence words) or `dd-gap`
+    pub class: String,
+    /// Position among the words, for words
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<f64>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HighlightResponse {
+    pub format: HighlightFormat,
+    /// Every segment as a `<span>` with its class and `data-start`/`data-end`/`data-index`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub html: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub segments: Option<Vec<HighlightSegment>>,
+    /// `[start_ms, end_ms, from, to]` per word, by start time; `from`/`to`
+    /// are UTF-16 offsets, as JavaScript, Kotlin and Swift strings index
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Vec<Vec<u32>>>)]
+    pub ranges: Option<Vec<[u32; 4]>>,
+}
+
+/// One text of a batch tokenization: its tokens, or why it failed
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BatchTokenizeResult {
+    /// Position of the request in the batch
+    pub index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokenization: Option<TokenizeResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ApiError>,
+}
+
+/// One subtitle of a batch alignment: its timings, or why it failed
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BatchAlignResult {
+    /// Position of the request in the batch
+    pub index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alignment: Option<AlignmentResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ApiError>,
+}
+
+#[derive(Debug, Serialize, Clone, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum AlignmentMethod {
     Linear,          
     Weighted,        
+    #[allow(dead_code)] // Reserved for audio-based alignment
     ForcedAligner,   
+    TtsPrediction,
+    TtsEngine,
+}
+
+/// Request to score an existing alignment
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ScoreRequest {
+    pub text: String,
+    pub language: String,
+    /// Seconds, or a subtitle timestamp, as for `AlignmentRequest`
+    #[serde(deserialize_with = "subtitles::deserialize_seconds")]
+    pub subtitle_start: f64,
+    #[serde(deserialize_with = "subtitles::deserialize_seconds")]
+    pub subtitle_end: f64,
+    pub timings: Vec<WordTiming>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio_url: Option<String>,
+
+    /// Scores below this are flagged for manual review
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub review_threshold: Option<f64>,
+}
+
+/// Individual quality metrics, each normalised to 0.0..=1.0 (except words_per_second)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct QualityMetrics {
+    pub words_per_second: f64,
+    pub rate_plausibility: f64,
+    pub monotonicity: f64,
+    pub method_agreement: f64,
+    pub coverage: f64,
+}
+
+/// Overall quality score for an alignment
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ScoreResponse {
+    pub text: String,
+    pub language: String,
+    pub score: f64,
+    pub needs_review: bool,
+    pub metrics: QualityMetrics,
+    pub warnings: Vec<Warning>,
+}
+
+/// Output format selected with `?output_format=` on alignment endpoints
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    #[default]
+    Json,
+    Csv,
+    Tsv,
+}
+
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OutputQuery {
+    #[serde(default)]
+    pub output_format: OutputFormat,
+}
+
+/// For endpoints whose results can run to hundreds of MB (query string)
+#[derive(Debug, Default, Deserialize, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StoreQuery {
+    /// Write the result to object storage (`S3_BUCKET`) and return a `StoredObject` link instead
+    #[serde(default)]
+    pub store: bool,
+}
+
+/// Opt-in profiling of one request (query string)
+#[derive(Debug, Default, Deserialize, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DebugQuery {
+    /// Report how long each stage took in `debug_timings`; skips the alignment cache
+    #[serde(default)]
+    pub debug_timings: bool,
+}
+
+/// How long each stage of a request took
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct StageTimings {
+    /// From the start of handling to the end of the last stage
+    pub total_ms: f64,
+    /// Milliseconds per stage that ran: `subtitle_parse`, `tokenization`,
+    /// `alignment`, `tts_engine`, `audio_fetch`, `audio_decode`. A stage
+    /// nested in another (tokenizing while aligning) counts only once, so
+    /// these add up to at most `total_ms`.
+    pub stages: BTreeMap<String, f64>,
+}
+
+/// `meta` of every JSON object a versioned endpoint returns (and of
+/// `envelope=true` batch results), for correlating with the service's logs
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ResponseMeta {
+    /// Also in the `X-Request-Id` header and the request's trace spans
+    pub request_id: String,
+    /// From receiving the request to the response being ready to send
+    pub processing_time_ms: f64,
+    pub api_version: String,
+    /// Things worth knowing about a request that still succeeded, such as
+    /// a fallback taken: the messages of the response's own `warnings`
+    pub warnings: Vec<String>,
+}
+
+/// A single subtitle cue
+#[derive(Debug, Deserialize, Serialize, Clone, Default, ToSchema)]
+pub struct Cue {
+    pub index: usize,
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+
+    /// Style name (ASS/SSA)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub style: Option<String>,
+
+    /// Speaker / actor name (ASS/SSA)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+}
+
+/// How overlapping cues are resolved before alignment
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OverlapPolicy {
+    /// Trim the earlier cue so it ends where the next one starts
+    #[default]
+    Clamp,
+    /// Combine overlapping cues into a single cue
+    Merge,
+    /// Leave cues untouched, only report the overlap
+    Keep,
+}
+
+/// Align every cue of a parsed subtitle file
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct FileAlignmentRequest {
+    pub language: String,
+    pub cues: Vec<Cue>,
+
+    #[serde(default)]
+    pub overlap_policy: OverlapPolicy,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CueWarningKind {
+    OutOfOrder,
+    Overlap,
+    Clamped,
+    Merged,
+    InvalidTiming,
+    AlignmentFailed,
+    MalformedIndex,
+    MalformedTimestamp,
+    BeyondAudio,
+    TooManyLines,
+    CpsExceeded,
+}
+
+/// Non-fatal problem found with a specific cue
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CueWarning {
+    pub cue_index: usize,
+    pub kind: CueWarningKind,
+    pub message: String,
+}
+
+/// Word timings for one cue
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CueAlignment {
+    pub index: usize,
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub style: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+
+    pub timings: Vec<WordTiming>,
+    pub gaps: Vec<Gap>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FileAlignmentResponse {
+    pub language: String,
+    pub cues: Vec<CueAlignment>,
+    pub warnings: Vec<CueWarning>,
+    /// Where the time went, with `debug_timings=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub debug_timings: Option<StageTimings>,
+}
+
+/// Aggregate statistics over a file's word timings
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TimingStats {
+    pub language: String,
+    pub n_cues: usize,
+    pub n_words: usize,
+    /// From the first cue's start to the last cue's end (seconds)
+    pub duration: f64,
+    /// Time covered by at least one word
+    pub speech: f64,
+    pub silence: f64,
+    /// `speech` over `duration`
+    pub speech_ratio: f64,
+    pub words: WordDurationStats,
+    /// By length in characters
+    pub by_length: BTreeMap<usize, WordDurationStats>,
+    /// By the first part of speech the language's dictionary gives;
+    /// `unknown` for words it lacks. Empty without a dictionary.
+    pub by_part_of_speech: BTreeMap<String, WordDurationStats>,
+    /// Most talk time first; cues without an actor count as an unnamed speaker
+    pub speakers: Vec<SpeakerStats>,
+    /// Cues that failed to align or were adjusted
+    pub n_warnings: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, ToSchema)]
+pub struct WordDurationStats {
+    pub n_words: usize,
+    /// Seconds
+    pub mean_duration: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct SpeakerStats {
+    /// Actor name (ASS/SSA), if the cues have one
+    pub speaker: Option<String>,
+    pub n_cues: usize,
+    pub n_words: usize,
+    /// Seconds of words spoken
+    pub talk_time: f64,
+    /// Share of all talk time
+    pub share: f64,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SubtitleFormat {
+    Srt,
+    Vtt,
+    Ass,
+}
+
+/// Force a subtitle format instead of detecting it (`?format=ass`)
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SubtitleQuery {
+    pub format: Option<SubtitleFormat>,
+}
+
+/// Cues parsed from a subtitle file
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ParseSubtitlesResponse {
+    pub format: SubtitleFormat,
+    pub cues: Vec<Cue>,
+    pub warnings: Vec<CueWarning>,
+}
+
+/// Write cues (or the cues of an alignment result) as a subtitle file
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct GenerateSubtitlesRequest {
+    pub format: SubtitleFormat,
+    pub cues: Vec<Cue>,
+
+    /// Re-wrap text to this many characters per line; null keeps the text as-is
+    #[serde(default = "default_max_line_length")]
+    pub max_line_length: Option<usize>,
+
+    #[serde(default = "default_max_lines")]
+    pub max_lines: usize,
+
+    /// Reading speed limit in characters per second; null disables it
+    #[serde(default = "default_max_cps")]
+    pub max_cps: Option<f64>,
+}
+
+fn default_max_line_length() -> Option<usize> {
+    Some(42)
+}
+
+fn default_max_lines() -> usize {
+    2
+}
+
+fn default_max_cps() -> Option<f64> {
+    Some(17.0)
+}
+
+/// A moment that should move: where it is in the subtitle file, and where it belongs in the video
+#[derive(Debug, Deserialize, Clone, Copy, ToSchema)]
+pub struct SyncPoint {
+    pub subtitle_time: f64,
+    pub video_time: f64,
+}
+
+/// Shift and/or scale every cue of a subtitle file
+///
+/// Either give `offset` / `scale` directly (new = old * scale + offset), or two
+/// `sync_points` from which both are computed. Only the timestamps change:
+/// cue numbers and text come back byte for byte, unless `output_format`
+/// converts the file.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ResyncRequest {
+    /// Raw subtitle file
+    pub content: String,
+
+    /// Input format; detected from the content when omitted
+    pub format: Option<SubtitleFormat>,
+
+    /// Output format; same as the input when omitted
+    pub output_format: Option<SubtitleFormat>,
+
+    #[serde(default)]
+    pub offset: f64,
+
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+
+    pub sync_points: Option<Vec<SyncPoint>>,
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+/// Subtitle QC: give either raw `content` or already-parsed `cues`
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ValidateSubtitlesRequest {
+    pub content: Option<String>,
+    pub cues: Option<Vec<Cue>>,
+    pub format: Option<SubtitleFormat>,
+
+    #[serde(default = "default_validate_max_cps")]
+    pub max_cps: f64,
+
+    #[serde(default = "default_max_lines")]
+    pub max_lines: usize,
+
+    #[serde(default = "default_validate_max_line_length")]
+    pub max_line_length: usize,
+
+    /// Shortest allowed gap between consecutive cues, in seconds (2 frames at 24 fps)
+    #[serde(default = "default_min_gap")]
+    pub min_gap: f64,
+}
+
+fn default_validate_max_cps() -> f64 {
+    17.0
+}
+
+fn default_validate_max_line_length() -> usize {
+    42
+}
+
+fn default_min_gap() -> f64 {
+    0.083
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, PartialOrd, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationCode {
+    Overlap,
+    NegativeDuration,
+    CpsExceeded,
+    TooManyLines,
+    LineTooLong,
+    GapTooShort,
+    ParseProblem,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ValidationIssue {
+    pub cue_index: usize,
+    pub severity: Severity,
+    pub code: ValidationCode,
+    pub message: String,
+}
+
+/// QC report; `valid` is false when any issue has error severity
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ValidationReport {
+    pub valid: bool,
+    pub cue_count: usize,
+    pub error_count: usize,
+    pub warning_count: usize,
+    pub issues: Vec<ValidationIssue>,
+}
+
+/// Merge short cues or split long ones
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RestructureRequest {
+    pub language: String,
+    pub cues: Vec<Cue>,
+
+    #[serde(default = "default_min_duration")]
+    pub min_duration: f64,
+
+    #[serde(default = "default_max_duration")]
+    pub max_duration: f64,
+
+    #[serde(default = "default_max_chars")]
+    pub max_chars: usize,
+
+    #[serde(default = "default_max_gap")]
+    pub max_gap: f64,
+}
+
+fn default_min_duration() -> f64 {
+    1.0
+}
+
+fn default_max_duration() -> f64 {
+    7.0
+}
+
+fn default_max_chars() -> usize {
+    84
+}
+
+fn default_max_gap() -> f64 {
+    0.5
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CuesResponse {
+    pub cues: Vec<Cue>,
+}
+
+/// Two subtitle files of the same video in different languages
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PairSubtitlesRequest {
+    pub source: String,
+    pub target: String,
+    pub source_format: Option<SubtitleFormat>,
+    pub target_format: Option<SubtitleFormat>,
+
+    /// Fraction of the shorter cue two cues must overlap by to be paired
+    #[serde(default = "default_min_overlap")]
+    pub min_overlap: f64,
+}
+
+fn default_min_overlap() -> f64 {
+    0.3
+}
+
+/// Source and target cues covering the same stretch of the video
+///
+/// Either side may span several cues, or none if the other language
+/// has nothing at that time.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CuePair {
+    pub start: f64,
+    pub end: f64,
+    pub source_indices: Vec<usize>,
+    pub target_indices: Vec<usize>,
+    pub source_text: String,
+    pub target_text: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PairSubtitlesResponse {
+    pub pairs: Vec<CuePair>,
+    pub unpaired_source: usize,
+    pub unpaired_target: usize,
+}
+
+/// Original cue timing with translated text, to check for dubbing
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct DubFitRequest {
+    /// Language of the translation
+    pub language: String,
+    pub cues: Vec<Cue>,
+
+    /// TTS voice to voice the lines with, for its speaking rate if the
+    /// language's duration model has a profile for it
+    #[serde(default)]
+    pub voice: Option<String>,
+
+    /// Override the voice's speaking rate (weight units per second)
+    pub speaking_rate: Option<f64>,
+
+    #[serde(default = "default_max_stretch")]
+    pub max_stretch: f64,
+
+    #[serde(default = "default_min_fill")]
+    pub min_fill: f64,
+}
+
+fn default_max_stretch() -> f64 {
+    1.1
+}
+
+fn default_min_fill() -> f64 {
+    0.6
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DubFitStatus {
+    Fits,
+    TooLong,
+    TooShort,
+}
+
+/// How one translated line fits its original slot
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DubFit {
+    pub index: usize,
+    pub text: String,
+    /// Length of the original cue (seconds)
+    pub available: f64,
+    /// Predicted spoken length of the translation (seconds)
+    pub estimated: f64,
+    /// `estimated / available`
+    pub ratio: f64,
+    pub status: DubFitStatus,
+    /// Suggested window to voice the line in
+    pub budget_start: f64,
+    pub budget_end: f64,
+    /// Speaking rate needed to fill the original slot exactly
+    pub required_rate: f64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DubFitResponse {
+    pub language: String,
+    pub lines: Vec<DubFit>,
+    pub n_too_long: usize,
+    pub n_too_short: usize,
+}
+
+/// Text to predict the spoken length of
+#[derive(Debug, Clone, Default, Deserialize, ToSchema)]
+pub struct DurationPredictionRequest {
+    pub text: String,
+    pub language: String,
+
+    /// TTS voice, for its speaking rate if the language's duration model
+    /// has a profile for it
+    #[serde(default)]
+    pub voice: Option<String>,
+
+    /// Override the speaking rate (weight units per second)
+    #[serde(default)]
+    pub speaking_rate: Option<f64>,
+}
+
+/// Predicted spoken length of one word
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct PredictedWord {
+    pub word: String,
+    pub char_start: usize,
+    pub char_end: usize,
+    /// Pronunciation the length was weighed on, when G2P knows the word
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ipa: Option<String>,
+    /// Seconds
+    pub duration: f64,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DurationPredictionResponse {
+    pub language: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub voice: Option<String>,
+    /// Speaking rate used (weight units per second)
+    pub speaking_rate: f64,
+    /// Whether the rate came from the voice's own profile
+    pub voice_profile: bool,
+    /// Predicted length of the whole text, pauses included (seconds)
+    pub duration: f64,
+    /// Of which pauses at punctuation
+    pub pauses: f64,
+    /// Words weighed on their pronunciation rather than their spelling
+    pub n_phonemized: usize,
+    pub words: Vec<PredictedWord>,
+    pub warnings: Vec<Warning>,
+}
+
+/// How fast a cue is actually spoken, measured on its audio
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct CueSpeechRate {
+    pub index: usize,
+    pub start: f64,
+    pub end: f64,
+    pub syllables: usize,
+    /// Seconds of the cue's window with speech in the audio
+    pub speech_seconds: f64,
+    /// Syllables per second of speech; null when the window is silent
+    pub rate: Option<f64>,
+    /// `rate` over the comfortable rate
+    pub ratio: Option<f64>,
+    /// Spoken faster than the comfortable rate: a place to slow playback down
+    pub too_fast: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SpeechRateResponse {
+    pub language: String,
+    /// Syllables per second the learner is comfortable with
+    pub comfortable_rate: f64,
+    /// Over every cue with speech
+    pub mean_rate: Option<f64>,
+    pub n_too_fast: usize,
+    pub cues: Vec<CueSpeechRate>,
+}
+
+/// How one reference word came out in a learner's recording
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PronouncedWord {
+    pub word: String,
+    /// Where the reference says it (seconds)
+    pub reference_start: f64,
+    pub reference_end: f64,
+    /// Where it falls in the recording (seconds)
+    pub start: f64,
+    pub end: f64,
+    /// Share of its time in the recording with speech
+    pub voiced: f64,
+    /// The reference pauses before it
+    pub pause_expected: bool,
+    /// The learner paused before it
+    pub paused: bool,
+    /// The learner paused where the reference doesn't, inside or just before it
+    pub hesitation: bool,
+    /// 0 to 1
+    pub score: f64,
+    /// `score` is at least 0.5
+    pub matched: bool,
+}
+
+/// A learner's recording of a cue scored against its reference alignment
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PronunciationResponse {
+    /// Recording's speaking time over the reference's: above 1 is slower
+    pub tempo: f64,
+    /// Mean of the words' scores
+    pub score: f64,
+    pub n_matched: usize,
+    /// Reference pauses the learner ran through
+    pub n_missed_pauses: usize,
+    /// Pauses the learner made that the reference doesn't
+    pub n_extra_pauses: usize,
+    /// Speech in the recording, leading and trailing silence trimmed (seconds)
+    pub speech_start: f64,
+    pub speech_end: f64,
+    pub words: Vec<PronouncedWord>,
+}
+
+/// Build fill-in-the-blank exercises from a subtitle file or cues
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ClozeRequest {
+    pub language: String,
+    pub content: Option<String>,
+    pub cues: Option<Vec<Cue>>,
+    pub format: Option<SubtitleFormat>,
+
+    #[serde(default = "default_gaps_per_cue")]
+    pub gaps_per_cue: usize,
+
+    /// Only gap words ranked this or rarer (rank 1 is the most frequent
+    /// word), which skips function words
+    #[serde(default = "default_min_rank")]
+    pub min_rank: usize,
+
+    /// Only gap words ranked this or more frequent; null also allows unlisted words
+    pub max_rank: Option<usize>,
+
+    #[serde(default = "default_distractors")]
+    pub distractors: usize,
+}
+
+fn default_gaps_per_cue() -> usize {
+    1
+}
+
+fn default_min_rank() -> usize {
+    50
+}
+
+fn default_distractors() -> usize {
+    3
+}
+
+/// One blanked-out word
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ClozeGap {
+    pub word: String,
+    pub char_start: usize,
+    pub char_end: usize,
+    pub start: f64,
+    pub end: f64,
+    /// Frequency rank in the language's list, if listed
+    pub rank: Option<usize>,
+    pub distractors: Vec<String>,
+}
+
+/// Exercise built from one cue
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ClozeItem {
+    pub cue_index: usize,
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+    pub gapped_text: String,
+    pub gaps: Vec<ClozeGap>,
+    pub timings: Vec<WordTiming>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ClozeResponse {
+    pub language: String,
+    pub items: Vec<ClozeItem>,
+}
+
+/// Extract the vocabulary of a subtitle file
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct VocabularyRequest {
+    pub language: String,
+    pub content: Option<String>,
+    pub cues: Option<Vec<Cue>>,
+    pub format: Option<SubtitleFormat>,
+    /// Words the learner knows; entries are then marked known or not
+    pub known: Option<KnownWords>,
+    /// Leave out known words (needs `known`)
+    #[serde(default)]
+    pub only_new: bool,
+}
+
+/// One distinct word of a file
+#[derive(Debug, Serialize, ToSchema)]
+pub struct VocabEntry {
+    pub lemma: String,
+    /// Spellings as they appear in the file, in order of appearance
+    pub forms: Vec<String>,
+    /// Uses in the file
+    pub count: usize,
+    /// Rank in the language's frequency list (1 = most common), if listed
+    pub rank: Option<usize>,
+    /// Band of the frequency list `rank` falls in
+    pub band: FrequencyBand,
+    /// When the word is first spoken (seconds)
+    pub first_start: f64,
+    pub first_cue_index: usize,
+    /// When it is last spoken
+    pub last_start: f64,
+    pub last_cue_index: usize,
+    /// Cues it appears in
+    pub n_contexts: usize,
+    /// Suggested days until a new card's first review: longer for words the
+    /// learner will keep meeting, in this file or anywhere
+    pub initial_interval: u32,
+    /// Text of the cue the word first appears in, on one line
+    pub context: String,
+    /// Whether the learner knows the word, when the request lists known words
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub known: Option<bool>,
+}
+
+/// A word of a practice pack's vocabulary
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PracticeWord {
+    #[serde(flatten)]
+    pub entry: VocabEntry,
+    /// The lemma's meaning, when the pack was built with `gloss`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gloss: Option<TokenGloss>,
+}
+
+/// One cue of a practice pack
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PracticeCue {
+    pub index: usize,
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+    pub timings: Vec<WordTiming>,
+    pub difficulty: DifficultyScore,
+    /// The cue's audio in the pack, when it was built with audio
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio: Option<String>,
+}
+
+/// A media file of a practice pack
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PackMedia {
+    /// Path in the ZIP
+    pub file: String,
+    pub cue_index: usize,
+    pub content_type: String,
+    /// Bytes
+    pub size: usize,
+    /// Hex SHA-256 of the file, to check it after download
+    pub sha256: String,
+}
+
+/// `pack.json`: everything the app needs to practise a file offline
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PracticePack {
+    /// Layout of this file; bumped when fields change incompatibly
+    pub version: u32,
+    pub language: String,
+    /// Language of the vocabulary's glosses
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gloss_language: Option<String>,
+    /// The whole file
+    pub difficulty: DifficultyScore,
+    pub cues: Vec<PracticeCue>,
+    pub vocabulary: Vec<PracticeWord>,
+    /// Every other file in the ZIP
+    pub media: Vec<PackMedia>,
+    /// Of the audio files: the uploaded audio's own
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<u32>,
+    /// Problems found parsing and aligning the subtitles
+    pub warnings: Vec<CueWarning>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct VocabularyResponse {
+    pub language: String,
+    /// Words in the file, counting repeats
+    pub n_tokens: usize,
+    pub entries: Vec<VocabEntry>,
+}
+
+/// Find the phrases a subtitle file keeps using
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CollocationRequest {
+    pub language: String,
+    pub content: Option<String>,
+    pub cues: Option<Vec<Cue>>,
+    pub format: Option<SubtitleFormat>,
+    /// Longest phrase, in words (2 to 4)
+    #[serde(default = "default_max_words")]
+    pub max_words: usize,
+    /// Fewest uses for a phrase to count
+    #[serde(default = "default_min_count")]
+    pub min_count: usize,
+    #[serde(default)]
+    pub measure: AssociationMeasure,
+    /// Most phrases returned, best first
+    #[serde(default = "default_collocation_limit")]
+    pub limit: usize,
+}
+
+fn default_max_words() -> usize {
+    3
+}
+
+fn default_min_count() -> usize {
+    2
+}
+
+fn default_collocation_limit() -> usize {
+    50
+}
+
+/// How strongly a phrase's words are tied to each other
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AssociationMeasure {
+    /// Dunning's log-likelihood ratio: favours phrases that are both tight
+    /// and frequent
+    #[default]
+    LogLikelihood,
+    /// Pointwise mutual information: favours tight phrases, however rare
+    Pmi,
+}
+
+/// A phrase used more often than its words alone would explain
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Collocation {
+    /// As first spoken in the file
+    pub phrase: String,
+    /// Lowercased words
+    pub words: Vec<String>,
+    /// Uses in the file
+    pub count: usize,
+    pub score: f64,
+    /// Start of the cue it first appears in (seconds)
+    pub first_start: f64,
+    pub first_cue_index: usize,
+    /// Text of that cue, on one line
+    pub context: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CollocationResponse {
+    pub language: String,
+    pub measure: AssociationMeasure,
+    /// Words in the file, counting repeats
+    pub n_tokens: usize,
+    pub collocations: Vec<Collocation>,
+}
+
+/// Score a cue's text or a whole subtitle file for learner difficulty
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct DifficultyRequest {
+    pub language: String,
+    /// One cue's text; otherwise `content` or `cues`
+    pub text: Option<String>,
+    pub content: Option<String>,
+    pub cues: Option<Vec<Cue>>,
+    pub format: Option<SubtitleFormat>,
+}
+
+/// Common European Framework of Reference level
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, ToSchema)]
+pub enum CefrLevel {
+    A1,
+    A2,
+    B1,
+    B2,
+    C1,
+    C2,
+}
+
+/// Band of the language's frequency list a word falls in
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+pub enum FrequencyBand {
+    /// The 1000 most frequent words
+    #[serde(rename = "k1")]
+    K1,
+    /// Ranks 1001 to 2000
+    #[serde(rename = "k2")]
+    K2,
+    /// Ranks 2001 to 5000
+    #[serde(rename = "k3_5")]
+    K3To5,
+    /// Listed, rarer than 5000
+    #[serde(rename = "rare")]
+    Rare,
+    #[serde(rename = "unlisted")]
+    Unlisted,
+}
+
+impl FrequencyBand {
+    /// The band of a rank, or `Unlisted` for none
+    pub fn of(rank: Option<usize>) -> Self {
+        match rank {
+            Some(1..=1000) => FrequencyBand::K1,
+            Some(1001..=2000) => FrequencyBand::K2,
+            Some(2001..=5000) => FrequencyBand::K3To5,
+            Some(_) => FrequencyBand::Rare,
+            None => FrequencyBand::Unlisted,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FrequencyBand::K1 => "k1",
+            FrequencyBand::K2 => "k2",
+            FrequencyBand::K3To5 => "k3_5",
+            FrequencyBand::Rare => "rare",
+            FrequencyBand::Unlisted => "unlisted",
+        }
+    }
+}
+
+/// Share of words in each band of the language's frequency list
+#[derive(Debug, Clone, Default, PartialEq, Serialize, ToSchema)]
+pub struct FrequencyProfile {
+    /// The 1000 most frequent words
+    pub k1: f64,
+    /// Ranks 1001 to 2000
+    pub k2: f64,
+    /// Ranks 2001 to 5000
+    pub k3_5: f64,
+    /// Listed, rarer than 5000
+    pub rare: f64,
+    pub unlisted: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct DifficultyScore {
+    /// Words scored; capitalised words mid-sentence that the frequency list
+    /// lacks are taken for names and left out
+    pub n_words: usize,
+    pub n_sentences: usize,
+    /// Words per sentence
+    pub mean_sentence_length: f64,
+    pub profile: FrequencyProfile,
+    /// Most frequent words a learner must know to understand 95% of the
+    /// words; null if that reaches beyond the frequency list
+    pub vocabulary_needed: Option<usize>,
+    /// Estimated from `vocabulary_needed`, one level up for long
+    /// sentences; null without words
+    pub cefr: Option<CefrLevel>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CueDifficulty {
+    pub index: usize,
+    #[serde(flatten)]
+    pub score: DifficultyScore,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DifficultyResponse {
+    pub language: String,
+    /// The whole text or file
+    #[serde(flatten)]
+    pub score: DifficultyScore,
+    /// Each cue scored alone, for files
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cues: Option<Vec<CueDifficulty>>,
+}
+
+/// A word to define (query string)
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LookupQuery {
+    /// As it appears in the text; inflections are also looked up by lemma
+    pub word: String,
+    pub language: String,
+}
+
+/// One headword's definitions, from a loaded dictionary
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DictionaryEntry {
+    pub headword: String,
+    /// Pinyin (CC-CEDICT) or kana (JMdict)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reading: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub part_of_speech: Option<String>,
+    /// One per sense
+    pub glosses: Vec<String>,
+    /// Dictionary file it came from
+    pub source: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LookupResponse {
+    pub word: String,
+    pub language: String,
+    /// Dictionary forms the word is an inflection of ("ran" → "run")
+    pub lemmas: Vec<String>,
+    /// Entries for the word itself, then for its lemmas; empty if unknown
+    pub entries: Vec<DictionaryEntry>,
+}
+
+/// Text to transcribe phonetically
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PhonemizeRequest {
+    pub text: String,
+    pub language: String,
+}
+
+/// Where a pronunciation came from
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PronunciationSource {
+    /// The language's pronunciation lexicon
+    Lexicon,
+    /// eSpeak NG, for languages without a lexicon (if configured)
+    Espeak,
+    /// Derived from spelling, for languages that are spelled regularly
+    Rules,
+}
+
+impl PronunciationSource {
+    pub fn name(self) -> &'static str {
+        match self {
+            PronunciationSource::Lexicon => "lexicon",
+            PronunciationSource::Espeak => "espeak",
+            PronunciationSource::Rules => "rules",
+        }
+    }
+}
+
+/// A language `/phonemize` can transcribe and the backends that cover it
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct G2pLanguage {
+    pub language: String,
+    /// In the order they're asked; the first that knows a word wins
+    pub sources: Vec<PronunciationSource>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct G2pLanguagesResponse {
+    pub languages: Vec<G2pLanguage>,
+}
+
+/// One token and how it's pronounced
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PhonemizedToken {
+    pub word: String,
+    /// Byte offsets into the text
+    pub char_start: usize,
+    pub char_end: usize,
+    /// Broad IPA, without delimiting slashes; null if the word isn't known
+    pub ipa: Option<String>,
+    /// The same in X-SAMPA
+    pub xsampa: Option<String>,
+    pub source: Option<PronunciationSource>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PhonemizeResponse {
+    pub text: String,
+    pub language: String,
+    pub tokens: Vec<PhonemizedToken>,
+    /// Tokens without a pronunciation
+    pub n_unknown: usize,
+}
+
+/// Text to spell out for speech
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NormalizeRequest {
+    pub text: String,
+    pub language: String,
+}
+
+/// A span of the text written differently from how it's said
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NormalizedSpan {
+    pub original: String,
+    pub spoken: String,
+    /// Byte offsets into the original text
+    pub char_start: usize,
+    pub char_end: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NormalizeResponse {
+    pub text: String,
+    pub language: String,
+    /// The text with every span replaced by its spoken form
+    pub normalized: String,
+    pub spans: Vec<NormalizedSpan>,
+}
+
+/// Text to split into syllables
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SyllabifyRequest {
+    pub text: String,
+    pub language: String,
+}
+
+/// How a language's words were split
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SyllabificationMethod {
+    /// The language's TeX hyphenation patterns
+    Patterns,
+    /// One syllable per vowel group, for languages without patterns
+    Vowels,
+    /// One syllable per character (Chinese, Japanese, Korean)
+    Characters,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Syllable {
+    pub text: String,
+    /// Byte offsets into the text
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SyllabifiedWord {
+    pub word: String,
+    /// Byte offsets into the text
+    pub char_start: usize,
+    pub char_end: usize,
+    pub syllables: Vec<Syllable>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SyllabifyResponse {
+    pub text: String,
+    pub language: String,
+    pub method: SyllabificationMethod,
+    pub words: Vec<SyllabifiedWord>,
+}
+
+/// One word of an ASR transcript
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct TranscriptWord {
+    pub word: String,
+    pub start: f64,
+    pub end: f64,
+}
+
+/// Compare an ASR transcript with a subtitle file
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TranscriptDiffRequest {
+    pub language: String,
+    pub transcript: Vec<TranscriptWord>,
+    pub content: Option<String>,
+    pub cues: Option<Vec<Cue>>,
+    pub format: Option<SubtitleFormat>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffKind {
+    Match,
+    /// Subtitles show different words than were said
+    Substitution,
+    /// Said but not subtitled
+    Missing,
+    /// Subtitled but not said (or not recognized)
+    Extra,
+}
+
+/// A run of transcript and subtitle words with the same diff outcome
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DiffSegment {
+    pub kind: DiffKind,
+    pub start: f64,
+    pub end: f64,
+    pub transcript_words: Vec<String>,
+    pub subtitle_words: Vec<String>,
+    /// Cues the subtitle words come from
+    pub cue_indices: Vec<usize>,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct TranscriptDiff {
+    pub segments: Vec<DiffSegment>,
+    pub n_matched: usize,
+    pub n_substituted: usize,
+    pub n_missing: usize,
+    pub n_extra: usize,
+    /// Cues where most words don't match the transcript
+    pub flagged_cues: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchOperation {
+    Parse,
+    #[default]
+    Validate,
+    Align,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BundleFormat {
+    #[default]
+    Json,
+    Zip,
+}
+
+/// Options for processing a ZIP of subtitle files (query string)
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BatchQuery {
+    #[serde(default)]
+    pub operation: BatchOperation,
+    /// Required for alignment
+    pub language: Option<String>,
+    #[serde(default)]
+    pub bundle: BundleFormat,
+    /// Write the results to object storage (`S3_BUCKET`) and return a `StoredObject` link instead
+    #[serde(default)]
+    pub store: bool,
+}
+
+/// Outcome for one file of a batch; exactly one of `result` and `error` is set
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BatchFileResult {
+    /// Path inside the archive
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub result: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ApiError>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BatchResponse {
+    pub files: Vec<BatchFileResult>,
+    pub n_failed: usize,
+}
+
+/// Message from a WebSocket client (`/api/ws`), tagged by `type`
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WsClientMessage {
+    /// Set the language used when later messages don't give one
+    Start { language: String },
+    Tokenize {
+        id: String,
+        text: String,
+        language: Option<String>,
+    },
+    /// Align one cue (or live caption) between `start` and `end` seconds
+    Align {
+        id: String,
+        text: String,
+        language: Option<String>,
+        start: f64,
+        end: f64,
+    },
+}
+
+/// Message to a WebSocket client; `id` echoes the request it answers
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WsServerMessage {
+    Ready { language: String },
+    Tokens { id: String, response: TokenizeResponse },
+    Alignment { id: String, response: AlignmentResponse },
+    Error { id: Option<String>, code: ErrorCode, message: String },
+}
+
+/// `progress` event of a streamed file alignment
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AlignmentProgress {
+    pub processed: usize,
+    pub total: usize,
+    pub elapsed_seconds: f64,
+    /// Estimated from the average time per cue so far
+    pub eta_seconds: f64,
+}
+
+/// `done` event of a streamed file alignment; the cues were sent as they finished
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AlignmentDone {
+    pub language: String,
+    pub n_cues: usize,
+    pub warnings: Vec<CueWarning>,
+}
+
+/// Work submitted to `POST /api/jobs`, tagged by `kind`
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+#[serde(tag = "kind", content = "request", rename_all = "snake_case")]
+pub enum JobRequest {
+    AlignFile(FileAlignmentRequest),
+    /// Single-cue alignment, including `mode: tts`
+    Align(AlignmentRequest),
+}
+
+/// Body of `POST /api/jobs`
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct JobSubmission {
+    #[serde(flatten)]
+    pub job: JobRequest,
+
+    /// Receives the final `JobStatus` as a signed POST once the job is done
+    pub callback_url: Option<String>,
+
+    /// Name of the file being processed, to find its jobs with `GET /api/jobs?file=`
+    pub file: Option<String>,
+}
+
+/// A request for another endpoint to check, tagged by `kind`
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(tag = "kind", content = "request", rename_all = "snake_case")]
+pub enum DryRunRequest {
+    /// For `POST /tokenize`
+    Tokenize(TokenizeRequest),
+    /// For `POST /align`, single-cue or with `cues`
+    Align(AlignmentRequest),
+    /// For `POST /align/file`
+    AlignFile(FileAlignmentRequest),
+}
+
+/// Body of `POST /api/validate-request`
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct DryRun {
+    #[serde(flatten)]
+    pub request: DryRunRequest,
+
+    /// Audio the request will be aligned against, e.g. the `audio_url` of
+    /// an `/upload/align` to follow (`align` requests carry their own)
+    pub audio_url: Option<String>,
+}
+
+/// What `POST /api/validate-request` found
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RequestValidation {
+    /// Whether the request would be accepted
+    pub valid: bool,
+    /// What it would fail with, each as the endpoint would report it; input
+    /// errors list every invalid field in `details.fields`
+    pub errors: Vec<ApiError>,
+    /// What wouldn't fail it but may not be what was meant
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    AlignFile,
+    Align,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl JobState {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobState::Succeeded | JobState::Failed | JobState::Cancelled)
+    }
+}
+
+/// Snapshot of a job; `result` is set once it succeeded, `error` once it failed
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct JobStatus {
+    pub id: String,
+    pub kind: JobKind,
+    pub state: JobState,
+    /// Tenant of the bearer token that submitted the job
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// Cues done so far (file alignment only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub processed: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub result: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ApiError>,
+    /// Unix timestamps in seconds
+    pub created_at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<u64>,
+}
+
+/// Filters for `GET /api/jobs` (query string)
+#[derive(Debug, Default, Deserialize, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct JobQuery {
+    /// Only for callers without a tenant of their own; others always see just theirs
+    pub tenant: Option<String>,
+    pub file: Option<String>,
+    pub state: Option<JobState>,
+    /// Submitted at or after this Unix timestamp (seconds)
+    pub since: Option<u64>,
+    /// Submitted before this Unix timestamp (seconds)
+    pub until: Option<u64>,
+    /// At most this many jobs, newest first (default 100, at most 1000)
+    pub limit: Option<usize>,
+}
+
+/// Jobs matching a `JobQuery`, newest first, without their `result`
+#[derive(Debug, Serialize, ToSchema)]
+pub struct JobList {
+    pub jobs: Vec<JobStatus>,
+}
+
+/// A result written to object storage instead of the response body
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StoredObject {
+    /// Object key in the bucket
+    pub key: String,
+    /// Presigned GET URL
+    pub url: String,
+    pub content_type: String,
+    /// Bytes
+    pub size: usize,
+    /// When `url` stops working, Unix seconds
+    pub expires_at: u64,
+}
+
+/// One clip cut by `/audio-snippets`
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AudioSnippet {
+    /// The word or phrase as first aligned
+    pub text: String,
+    /// Clip bounds in the audio (seconds), padding included
+    pub start: f64,
+    pub end: f64,
+    /// Mean confidence of its aligned words
+    pub confidence: f64,
+    /// Times it appears in the timings; the clip is of the most confident
+    pub occurrences: usize,
+    /// Name of the WAV file in the ZIP
+    pub file: String,
+    /// Link to the clip, with `store=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stored: Option<StoredObject>,
+}
+
+/// Clips cut from one audio file, with `store=true` or as `snippets.json`
+/// in the ZIP
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AudioSnippetResponse {
+    /// Of every clip: the audio's own
+    pub sample_rate: u32,
+    pub snippets: Vec<AudioSnippet>,
+    /// Requested phrases the timings don't contain
+    pub missing: Vec<String>,
+}
+
+/// Body of `POST /api/storage/uploads`
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UploadUrlRequest {
+    /// Original file name, kept at the end of the key; its extension helps decoding
+    pub filename: String,
+}
+
+/// Where to PUT a file so it never passes through the API
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PresignedUpload {
+    pub key: String,
+    /// Presigned PUT URL
+    pub url: String,
+    /// `s3://bucket/key`, to pass as `audio_url` once uploaded
+    pub audio_url: String,
+    /// When `url` stops working, Unix seconds
+    pub expires_at: u64,
+}
+
+/// A message taken from the task queue by `dubdub consume`
+#[derive(Debug, Deserialize, Serialize)]
+pub struct QueueTask {
+    /// Echoed in the result, to match the two up
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(flatten)]
+    pub task: TaskRequest,
+}
+
+/// Queued work, tagged by `kind` like `JobRequest`
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "kind", content = "request", rename_all = "snake_case")]
+pub enum TaskRequest {
+    Tokenize(TokenizeRequest),
+    Align(AlignmentRequest),
+    AlignFile(FileAlignmentRequest),
+}
+
+/// Published for every task taken from the queue; exactly one of `result` and `error` is set
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TaskResult {
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ApiError>,
+}
+
+/// The caller's usage this month, from `/api/usage`
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UsageReport {
+    /// What usage is counted against: the token's tenant, or the API key's name
+    pub account: String,
+    /// Calendar month (UTC) being counted, e.g. `2026-10`
+    pub period: String,
+    /// Unix seconds when the counts start over
+    pub resets_at: u64,
+    pub requests: UsageMetric,
+    /// Characters of text submitted
+    pub characters: UsageMetric,
+    /// Audio uploaded or fetched, rounded up to whole seconds
+    pub audio_seconds: UsageMetric,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UsageMetric {
+    pub used: u64,
+    /// Monthly quota; `null` if unlimited
+    pub limit: Option<u64>,
+}
+
+/// Runtime settings changed through `/admin/settings`
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AdminSettings {
+    /// Log filter in `RUST_LOG` syntax, e.g. `info,dubdub::tts=debug`
+    pub log_filter: Option<String>,
+    /// Whether request and response headers are logged
+    pub verbose_requests: bool,
+    /// Every feature flag and whether it's on
+    pub features: BTreeMap<String, bool>,
+}
+
+/// Body of `PATCH /admin/settings`; omitted fields are left as they are
+#[derive(Debug, Default, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AdminSettingsUpdate {
+    pub log_filter: Option<String>,
+    pub verbose_requests: Option<bool>,
+    /// Flags to change, by name
+    #[serde(default)]
+    pub features: BTreeMap<String, bool>,
+}
+
+/// The cross-request alignment cache, from `/admin/cache`
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct CacheStats {
+    /// `false` when `ALIGNMENT_CACHE_SIZE` is 0
+    pub enabled: bool,
+    pub entries: usize,
+    pub capacity: usize,
+    /// Size of the cached responses as JSON, against `max_bytes`
+    pub bytes: usize,
+    pub max_bytes: usize,
+    /// Lookups answered from the cache since startup
+    pub hits: u64,
+    pub misses: u64,
+    /// `hits / (hits + misses)`, 0 before the first lookup
+    pub hit_rate: f64,
+}
+
+/// Data files read again by `/admin/reload` (or SIGHUP)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DataReload {
+    pub reloaded: Vec<ReloadedData>,
+    pub elapsed_seconds: f64,
+}
+
+/// One kind of data after a reload, e.g. `dictionaries`
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReloadedData {
+    pub name: String,
+    pub languages: usize,
+    /// What's loaded now, with versions to confirm the update arrived
+    pub files: Vec<DataFile>,
+    /// Files that failed to load, whose languages kept their previous data
+    pub failed: Vec<FailedDataFile>,
+}
+
+/// Per-language data held in memory, from `GET /admin/languages`
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LanguageResourcesReport {
+    /// Languages with any data loaded, by code
+    pub languages: Vec<LanguageResources>,
+    /// Estimated heap bytes of every language's data
+    pub approx_bytes: usize,
+    pub cache: CacheStats,
+    /// Unix seconds since which use is tracked; languages unused since are
+    /// idle from then
+    pub tracking_since: u64,
+}
+
+/// What one language keeps in memory
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct LanguageResources {
+    pub language: String,
+    pub data: Vec<ResidentData>,
+    /// Estimated heap bytes of `data`
+    pub approx_bytes: usize,
+    /// Whether it has a duration model; these are small and never evicted
+    pub duration_model: bool,
+    /// Alignments of text in the language in the alignment cache
+    pub cached_alignments: usize,
+    /// Unix seconds of the latest request in the language, if any since
+    /// `tracking_since`
+    pub last_used: Option<u64>,
+}
+
+/// One kind of loaded data for a language, e.g. its `dictionary`
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ResidentData {
+    /// `dictionary`, `frequency_list`, `stopwords`, `abbreviations` or `content_list`
+    pub name: String,
+    pub entries: usize,
+    /// Estimated heap bytes, from the lengths and capacities of what it holds
+    pub approx_bytes: usize,
+}
+
+/// Body of `POST /admin/languages/evict`; at least one field is required,
+/// and with both only the listed languages that are idle are evicted
+#[derive(Debug, Default, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct LanguageEviction {
+    /// Languages to evict, by code
+    #[serde(default)]
+    pub languages: Vec<String>,
+    /// Evict languages without a request for this many seconds
+    pub idle_seconds: Option<u64>,
+}
+
+/// Languages dropped by `POST /admin/languages/evict`
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EvictedLanguages {
+    pub evicted: Vec<String>,
+    /// Estimated heap bytes their data held
+    pub approx_bytes: usize,
+    /// Their alignments dropped from the alignment cache
+    pub cached_alignments: usize,
 }
@@ -0,0 +1,158 @@
+use crate::dictionary::{Dictionary, Inflection};
+use crate::models::MorphAnalysis;
+
+/// Wiktionary tags for each feature, in the words reported
+const PERSONS: &[(&str, u8)] = &[("first-person", 1), ("second-person", 2), ("third-person", 3)];
+const NUMBERS: &[&str] = &["singular", "plural", "dual"];
+const TENSES: &[&str] = &["present", "past", "preterite", "imperfect", "future", "perfect", "pluperfect", "aorist"];
+const MOODS: &[&str] = &["indicative", "subjunctive", "imperative", "conditional"];
+const CASES: &[&str] = &[
+    "nominative", "accusative", "genitive", "dative", "instrumental", "locative", "vocative", "ablative",
+    "prepositional", "partitive", "essive", "translative", "inessive", "elative", "illative", "adessive",
+];
+const GENDERS: &[&str] = &["masculine", "feminine", "neuter"];
+const VERB_FORMS: &[&str] = &["infinitive", "participle", "gerund"];
+
+/// Infinitive endings that name a verb's conjugation group, longest first
+fn infinitive_endings(language: &str) -> &'static [&'static str] {
+    match language.split(['-', '_']).next().unwrap_or_default().to_lowercase().as_str() {
+        "es" | "pt" | "gl" | "ca" => &["ar", "er", "ir"],
+        "it" => &["are", "ere", "ire"],
+        "fr" => &["oir", "er", "ir", "re"],
+        _ => &[],
+    }
+}
+
+/// Every reading of `word` the dictionary has: as an inflection of each of
+/// its lemmas, then as a dictionary form itself
+///
+/// Features come from the tags of Wiktionary "form of" senses, so only
+/// dictionaries from kaikki.org extracts have them; others give lemmas and
+/// parts of speech for headwords alone.
+pub fn analyze(word: &str, language: &str, dictionary: &Dictionary) -> Vec<MorphAnalysis> {
+    let mut analyses: Vec<MorphAnalysis> = dictionary.inflections(word).iter()
+        .map(|inflection| from_inflection(inflection, language))
+        .collect();
+
+    for entry in dictionary.lookup(word) {
+        let is_headword = entry.headword.to_lowercase() == word.to_lowercase();
+        // An inflection's own entry ("estás": form of "estar") isn't a lemma
+        let known = analyses.iter().any(|analysis| analysis.part_of_speech == entry.part_of_speech);
+        if is_headword && !known {
+            let inflection = Inflection { lemma: entry.headword.clone(), part_of_speech: entry.part_of_speech.clone(), tags: Vec::new() };
+            analyses.push(from_inflection(&inflection, language));
+        }
+    }
+    analyses
+}
+
+fn from_inflection(inflection: &Inflection, language: &str) -> MorphAnalysis {
+    let tags = &inflection.tags;
+    let find = |names: &[&str]| names.iter().find(|name| tags.iter().any(|tag| tag == *name)).map(|name| name.to_string());
+
+    let is_verb = inflection.part_of_speech.as_deref() == Some("verb");
+    let mut analysis = MorphAnalysis {
+        lemma: inflection.lemma.clone(),
+        part_of_speech: inflection.part_of_speech.clone(),
+        person: PERSONS.iter().find(|(name, _)| tags.iter().any(|tag| tag == name)).map(|(_, person)| *person),
+        number: find(NUMBERS),
+        tense: find(TENSES),
+        mood: find(MOODS),
+        case: find(CASES),
+        gender: find(GENDERS),
+        verb_form: find(VERB_FORMS),
+        conjugation_group: is_verb.then(|| conjugation_group(&inflection.lemma, language)).flatten(),
+        summary: String::new(),
+        tags: tags.clone(),
+    };
+    analysis.summary = summary(&analysis);
+    analysis
+}
+
+/// "-ar" for "estar" in Spanish; `None` where the language has no groups
+/// by ending, or the lemma has none of them
+fn conjugation_group(lemma: &str, language: &str) -> Option<String> {
+    let lemma = lemma.to_lowercase();
+    infinitive_endings(language).iter()
+        .find(|ending| lemma.len() > ending.len() && lemma.ends_with(*ending))
+        .map(|ending| format!("-{}", ending))
+}
+
+/// "2sg present indicative", "genitive plural feminine"
+fn summary(analysis: &MorphAnalysis) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    match (analysis.person, analysis.number.as_deref()) {
+        (Some(person), Some(number)) => parts.push(format!("{}{}", person, abbreviate(number))),
+        (Some(person), None) => parts.push(format!("{}p", person)),
+        (None, _) => {}
+    }
+    parts.extend([&analysis.tense, &analysis.mood, &analysis.verb_form, &analysis.case].into_iter().flatten().cloned());
+    if analysis.person.is_none() {
+        parts.extend(analysis.number.clone());
+    }
+    parts.extend(analysis.gender.clone());
+    parts.join(" ")
+}
+
+fn abbreviate(number: &str) -> &str {
+    match number {
+        "singular" => "sg",
+        "plural" => "pl",
+        "dual" => "du",
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIKTIONARY: &str = concat!(
+        r#"{"word": "estar", "pos": "verb", "senses": [{"glosses": ["to be"]}]}"#, "\n",
+        r#"{"word": "estás", "pos": "verb", "senses": [{"glosses": ["second-person singular present indicative of estar"],"#,
+        r#" "form_of": [{"word": "estar"}], "tags": ["form-of", "indicative", "informal", "present", "second-person", "singular"]}]}"#, "\n",
+        r#"{"word": "casas", "pos": "noun", "senses": [{"glosses": ["plural of casa"], "form_of": [{"word": "casa"}], "tags": ["form-of", "plural"]}]}"#, "\n",
+        r#"{"word": "casas", "pos": "verb", "senses": [{"glosses": ["second-person singular present indicative of casar"],"#,
+        r#" "form_of": [{"word": "casar"}], "tags": ["form-of", "indicative", "present", "second-person", "singular"]}]}"#, "\n",
+    );
+
+    #[test]
+    fn test_inflected_verb() {
+        let dictionary = Dictionary::parse_wiktionary(WIKTIONARY, "es.jsonl");
+        let analyses = analyze("Estás", "es", &dictionary);
+
+        assert_eq!(analyses.len(), 1);
+        let estas = &analyses[0];
+        assert_eq!(estas.lemma, "estar");
+        assert_eq!(estas.person, Some(2));
+        assert_eq!(estas.number.as_deref(), Some("singular"));
+        assert_eq!(estas.mood.as_deref(), Some("indicative"));
+        assert_eq!(estas.conjugation_group.as_deref(), Some("-ar"));
+        assert_eq!(estas.summary, "2sg present indicative");
+        assert!(estas.tags.contains(&"informal".to_string()));
+    }
+
+    #[test]
+    fn test_ambiguous_form_and_headword() {
+        let dictionary = Dictionary::parse_wiktionary(WIKTIONARY, "es.jsonl");
+
+        let analyses = analyze("casas", "es", &dictionary);
+        let casas: Vec<(&str, &str)> = analyses.iter()
+            .map(|analysis| (analysis.lemma.as_str(), analysis.summary.as_str()))
+            .collect();
+        assert_eq!(casas, vec![("casa", "plural"), ("casar", "2sg present indicative")]);
+
+        let estar = analyze("estar", "es", &dictionary);
+        assert_eq!(estar[0].lemma, "estar");
+        assert_eq!(estar[0].conjugation_group.as_deref(), Some("-ar"));
+        assert_eq!(estar[0].summary, "");
+        assert!(analyze("perro", "es", &dictionary).is_empty());
+    }
+
+    #[test]
+    fn test_conjugation_groups() {
+        assert_eq!(conjugation_group("finire", "it").as_deref(), Some("-ire"));
+        assert_eq!(conjugation_group("voir", "fr").as_deref(), Some("-oir"));
+        assert_eq!(conjugation_group("run", "en"), None);
+    }
+}
@@ -0,0 +1,56 @@
+use std::convert::Infallible;
+
+use actix_web::http::header::{self, ContentEncoding};
+use actix_web::web::Bytes;
+use actix_web::{HttpRequest, HttpResponse};
+use futures::{Stream, StreamExt};
+use serde::Serialize;
+
+/// Newline-delimited JSON, one result per line
+pub const CONTENT_TYPE: &str = "application/x-ndjson";
+
+/// Whether the client asked for results as NDJSON
+pub fn accepted(req: &HttpRequest) -> bool {
+    req.headers().get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.split(',').any(|item| item.split(';').next().unwrap_or_default().trim() == CONTENT_TYPE))
+}
+
+/// Format one line
+pub fn line(value: &impl Serialize) -> Bytes {
+    let json = serde_json::to_string(value).unwrap_or_else(|e| format!("{{\"error\":\"{}\"}}", e));
+    Bytes::from(json + "\n")
+}
+
+/// Stream results as they're produced, each on its own line
+///
+/// Lines are only computed as the client reads them, so the first results
+/// arrive before the last ones are ready.
+pub fn response<T: Serialize>(items: impl Stream<Item = T> + 'static) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(CONTENT_TYPE)
+        // Compressing would buffer lines until the encoder flushes
+        .insert_header(ContentEncoding::Identity)
+        .streaming(items.map(|item| Ok::<_, Infallible>(line(&item))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn test_accepted() {
+        let ndjson = TestRequest::default().insert_header(("accept", "application/json;q=0.5, application/x-ndjson")).to_http_request();
+        let json = TestRequest::default().insert_header(("accept", "application/json")).to_http_request();
+
+        assert!(accepted(&ndjson));
+        assert!(!accepted(&json));
+        assert!(!accepted(&TestRequest::default().to_http_request()));
+    }
+
+    #[test]
+    fn test_line() {
+        assert_eq!(line(&serde_json::json!({"index": 0})), Bytes::from("{\"index\":0}\n"));
+    }
+}
@@ -0,0 +1,538 @@
+use std::borrow::Cow;
+use std::sync::LazyLock;
+
+use regex::{Captures, Regex};
+
+use crate::models::AlignmentRequest;
+use crate::stages::{self, Stage};
+
+/// Thousands with commas, decimals with a point: 1,250.75
+const ENGLISH_NUMBER: &str = r"\d{1,3}(?:,\d{3})+(?:\.\d+)?|\d+(?:\.\d+)?";
+/// Thousands with points, decimals with a comma (or a point): 1.250,75
+const SPANISH_NUMBER: &str = r"\d{1,3}(?:\.\d{3})+(?:,\d+)?|\d+(?:[.,]\d+)?";
+
+static ENGLISH: LazyLock<Regex> = LazyLock::new(|| pattern(ENGLISH_NUMBER));
+static SPANISH: LazyLock<Regex> = LazyLock::new(|| pattern(SPANISH_NUMBER));
+
+/// Everything that's written differently from how it's said, most
+/// specific first so "2024-03-15" is a date rather than three numbers
+fn pattern(number: &str) -> Regex {
+    Regex::new(&format!(
+        concat!(
+            r"(?P<iso>\b\d{{4}}-\d{{1,2}}-\d{{1,2}}\b)",
+            r"|(?P<slash>\b\d{{1,2}}/\d{{1,2}}/\d{{4}}\b)",
+            r"|(?P<time>\b\d{{1,2}}:\d{{2}}\b)",
+            r"|(?P<currency>[$€£])\s?(?P<amount>{number})\b",
+            r"|\b(?P<amount_before>{number})\s?(?P<currency_after>[$€£])",
+            r"|\b(?P<percent>{number})\s?%",
+            r"|\b(?P<ordinal>\d+)(?P<ordinal_suffix>st|nd|rd|th|º|ª)",
+            r"|\b(?P<number>{number})\b",
+            r"|(?P<symbol>[&+=@])",
+        ),
+        number = number,
+    ))
+    .unwrap()
+}
+
+/// One span of the text and how it's said, as byte offsets into the text
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expansion {
+    pub start: usize,
+    pub end: usize,
+    pub spoken: String,
+}
+
+/// Languages with spelling-out rules
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Locale {
+    English,
+    Spanish,
+}
+
+/// Spanish number words agree with the noun they count
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Gender {
+    /// Counting on its own: "veintiuno"
+    None,
+    /// Before a masculine noun or "mil": "veintiún euros"
+    Masculine,
+    /// Before a feminine noun: "veintiuna libras", "doscientas"
+    Feminine,
+}
+
+impl Locale {
+    fn for_language(language: &str) -> Option<Self> {
+        match language.to_lowercase().split(['-', '_']).next().unwrap_or_default() {
+            "en" | "english" => Some(Locale::English),
+            "es" | "spanish" => Some(Locale::Spanish),
+            _ => None,
+        }
+    }
+
+    fn pattern(self) -> &'static Regex {
+        match self {
+            Locale::English => &ENGLISH,
+            Locale::Spanish => &SPANISH,
+        }
+    }
+
+    /// How one match is said, or `None` to leave it as written (a 13th
+    /// month, a number too large to spell)
+    fn expand(self, caps: &Captures) -> Option<String> {
+        let group = |name: &str| caps.name(name).map(|m| m.as_str());
+
+        if let Some(date) = group("iso") {
+            let mut parts = date.split('-').map(|part| part.parse().ok());
+            let (year, month, day) = (parts.next()??, parts.next()??, parts.next()??);
+            return self.date(year, month, day);
+        }
+        if let Some(date) = group("slash") {
+            let parts: Vec<u64> = date.split('/').filter_map(|part| part.parse().ok()).collect();
+            return match self {
+                Locale::English => self.date(parts[2], parts[0], parts[1]),
+                Locale::Spanish => self.date(parts[2], parts[1], parts[0]),
+            };
+        }
+        if let Some(time) = group("time") {
+            let (hours, minutes) = time.split_once(':')?;
+            return self.time(hours.parse().ok()?, minutes.parse().ok()?);
+        }
+        if let (Some(symbol), Some(amount)) = (group("currency").or(group("currency_after")), group("amount").or(group("amount_before"))) {
+            let (whole, fraction) = self.parse_number(amount)?;
+            return self.money(symbol.chars().next()?, whole, fraction);
+        }
+        if let Some(percent) = group("percent") {
+            let (whole, fraction) = self.parse_number(percent)?;
+            let unit = match self {
+                Locale::English => "percent",
+                Locale::Spanish => "por ciento",
+            };
+            return Some(format!("{} {}", self.decimal(whole, fraction)?, unit));
+        }
+        if let (Some(ordinal), Some(suffix)) = (group("ordinal"), group("ordinal_suffix")) {
+            return self.ordinal(ordinal.parse().ok()?, suffix);
+        }
+        if let Some(number) = group("number") {
+            let (whole, fraction) = self.parse_number(number)?;
+            return self.decimal(whole, fraction);
+        }
+        group("symbol").and_then(|symbol| self.symbol(symbol))
+    }
+
+    /// Whole part and the digits after the decimal separator
+    fn parse_number(self, number: &str) -> Option<(u64, Option<&str>)> {
+        let (whole, fraction) = match self {
+            Locale::English => match number.split_once('.') {
+                Some((whole, fraction)) => (whole.replace(',', ""), Some(fraction)),
+                None => (number.replace(',', ""), None),
+            },
+            Locale::Spanish => match number.split_once(',') {
+                Some((whole, fraction)) => (whole.replace('.', ""), Some(fraction)),
+                // "3.500" is thousands; "3.5" is a decimal written the English way
+                None if number.split('.').skip(1).all(|group| group.len() == 3) => (number.replace('.', ""), None),
+                None => {
+                    let (whole, fraction) = number.split_once('.')?;
+                    (whole.to_string(), Some(fraction))
+                }
+            },
+        };
+        Some((whole.parse().ok()?, fraction))
+    }
+
+    fn cardinal(self, n: u64) -> Option<String> {
+        match self {
+            Locale::English => english_cardinal(n),
+            Locale::Spanish => spanish_cardinal(n, Gender::None),
+        }
+    }
+
+    /// "three point one four", "tres coma catorce"
+    fn decimal(self, whole: u64, fraction: Option<&str>) -> Option<String> {
+        let whole = self.cardinal(whole)?;
+        let Some(fraction) = fraction else {
+            return Some(whole);
+        };
+
+        let digits = || fraction.chars().filter_map(|c| c.to_digit(10)).filter_map(|d| self.cardinal(d as u64)).collect::<Vec<_>>().join(" ");
+        Some(match self {
+            Locale::English => format!("{} point {}", whole, digits()),
+            // Spanish reads a short fraction as a number unless it starts with zero
+            Locale::Spanish if fraction.len() <= 2 && !fraction.starts_with('0') => {
+                format!("{} coma {}", whole, self.cardinal(fraction.parse().ok()?)?)
+            }
+            Locale::Spanish => format!("{} coma {}", whole, digits()),
+        })
+    }
+
+    fn ordinal(self, n: u64, suffix: &str) -> Option<String> {
+        match (self, suffix) {
+            (Locale::English, "st" | "nd" | "rd" | "th") => english_ordinal(n),
+            (Locale::Spanish, "º" | "ª") => {
+                const ORDINALS: [&str; 10] = ["primero", "segundo", "tercero", "cuarto", "quinto", "sexto", "séptimo", "octavo", "noveno", "décimo"];
+                let masculine = ORDINALS.get((n as usize).checked_sub(1)?)?;
+                Some(if suffix == "ª" { format!("{}a", &masculine[..masculine.len() - 1]) } else { masculine.to_string() })
+            }
+            _ => None,
+        }
+    }
+
+    fn date(self, year: u64, month: u64, day: u64) -> Option<String> {
+        const ENGLISH_MONTHS: [&str; 12] = ["January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November", "December"];
+        const SPANISH_MONTHS: [&str; 12] = ["enero", "febrero", "marzo", "abril", "mayo", "junio", "julio", "agosto", "septiembre", "octubre", "noviembre", "diciembre"];
+        if !(1..=31).contains(&day) {
+            return None;
+        }
+        let month = (month as usize).checked_sub(1)?;
+
+        match self {
+            Locale::English => Some(format!("{} {}, {}", ENGLISH_MONTHS.get(month)?, english_ordinal(day)?, english_year(year)?)),
+            Locale::Spanish => Some(format!("{} de {} de {}", spanish_cardinal(day, Gender::None)?, SPANISH_MONTHS.get(month)?, spanish_cardinal(year, Gender::None)?)),
+        }
+    }
+
+    /// "three thirty", "three oh five", "las tres y media"
+    fn time(self, hours: u64, minutes: u64) -> Option<String> {
+        if hours > 23 || minutes > 59 {
+            return None;
+        }
+        let hours = if hours == 0 { 12 } else { hours };
+
+        match self {
+            Locale::English => {
+                let hour = english_cardinal(hours)?;
+                Some(match minutes {
+                    0 if hours <= 12 => format!("{} o'clock", hour),
+                    0 => format!("{} hundred", hour),
+                    1..=9 => format!("{} oh {}", hour, english_cardinal(minutes)?),
+                    _ => format!("{} {}", hour, english_cardinal(minutes)?),
+                })
+            }
+            Locale::Spanish => {
+                let hour = spanish_cardinal(hours, Gender::Feminine)?;
+                Some(match minutes {
+                    0 => format!("{} en punto", hour),
+                    15 => format!("{} y cuarto", hour),
+                    30 => format!("{} y media", hour),
+                    _ => format!("{} y {}", hour, spanish_cardinal(minutes, Gender::None)?),
+                })
+            }
+        }
+    }
+
+    /// "five dollars and fifty cents", "un euro con cincuenta céntimos"
+    fn money(self, symbol: char, whole: u64, fraction: Option<&str>) -> Option<String> {
+        // Singular and plural of the unit and of its hundredth
+        let (unit, units, cent, cents, gender) = match (self, symbol) {
+            (Locale::English, '$') => ("dollar", "dollars", "cent", "cents", Gender::None),
+            (Locale::English, '€') => ("euro", "euros", "cent", "cents", Gender::None),
+            (Locale::English, '£') => ("pound", "pounds", "penny", "pence", Gender::None),
+            (Locale::Spanish, '$') => ("dólar", "dólares", "centavo", "centavos", Gender::Masculine),
+            (Locale::Spanish, '€') => ("euro", "euros", "céntimo", "céntimos", Gender::Masculine),
+            (Locale::Spanish, '£') => ("libra", "libras", "penique", "peniques", Gender::Feminine),
+            _ => return None,
+        };
+        let count = |n: u64, singular: &str, plural: &str, gender: Gender| -> Option<String> {
+            let name = if n == 1 { singular } else { plural };
+            Some(match self {
+                Locale::English => format!("{} {}", english_cardinal(n)?, name),
+                // "un millón de dólares"
+                Locale::Spanish if n >= 1_000_000 && n.is_multiple_of(1_000_000) => format!("{} de {}", spanish_cardinal(n, gender)?, name),
+                Locale::Spanish => format!("{} {}", spanish_cardinal(n, gender)?, name),
+            })
+        };
+
+        let hundredths = match fraction {
+            None => 0,
+            Some(fraction) if fraction.len() <= 2 => format!("{:0<2}", fraction).parse().ok()?,
+            // More precision than cents: read it as a decimal amount
+            Some(_) => return Some(format!("{} {}", self.decimal(whole, fraction)?, units)),
+        };
+        let and = match self {
+            Locale::English => "and",
+            Locale::Spanish => "con",
+        };
+
+        Some(match (whole, hundredths) {
+            (0, 0) => count(0, unit, units, gender)?,
+            (0, hundredths) => count(hundredths, cent, cents, Gender::Masculine)?,
+            (whole, 0) => count(whole, unit, units, gender)?,
+            (whole, hundredths) => format!("{} {} {}", count(whole, unit, units, gender)?, and, count(hundredths, cent, cents, Gender::Masculine)?),
+        })
+    }
+
+    fn symbol(self, symbol: &str) -> Option<String> {
+        let spoken = match (self, symbol) {
+            (Locale::English, "&") => "and",
+            (Locale::English, "+") => "plus",
+            (Locale::English, "=") => "equals",
+            (Locale::English, "@") => "at",
+            (Locale::Spanish, "&") => "y",
+            (Locale::Spanish, "+") => "más",
+            (Locale::Spanish, "=") => "igual a",
+            (Locale::Spanish, "@") => "arroba",
+            _ => return None,
+        };
+        Some(spoken.to_string())
+    }
+
+    fn minus(self) -> &'static str {
+        match self {
+            Locale::English => "minus",
+            Locale::Spanish => "menos",
+        }
+    }
+}
+
+const ENGLISH_ONES: [&str; 20] = [
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten",
+    "eleven", "twelve", "thirteen", "fourteen", "fifteen", "sixteen", "seventeen", "eighteen", "nineteen",
+];
+const ENGLISH_TENS: [&str; 10] = ["", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety"];
+
+/// US style, without "and": "one hundred twenty-three"; up to trillions
+fn english_cardinal(n: u64) -> Option<String> {
+    const SCALES: [(u64, &str); 4] = [(1_000_000_000_000, "trillion"), (1_000_000_000, "billion"), (1_000_000, "million"), (1_000, "thousand")];
+
+    Some(match n {
+        0..=19 => ENGLISH_ONES[n as usize].to_string(),
+        20..=99 if n.is_multiple_of(10) => ENGLISH_TENS[n as usize / 10].to_string(),
+        20..=99 => format!("{}-{}", ENGLISH_TENS[n as usize / 10], ENGLISH_ONES[n as usize % 10]),
+        100..=999 => join(format!("{} hundred", ENGLISH_ONES[n as usize / 100]), n % 100, english_cardinal)?,
+        n if n >= 1_000 * SCALES[0].0 => return None,
+        n => {
+            let (scale, name) = SCALES.into_iter().find(|(scale, _)| n >= *scale)?;
+            join(format!("{} {}", english_cardinal(n / scale)?, name), n % scale, english_cardinal)?
+        }
+    })
+}
+
+/// `head`, then `rest` spelled out unless it's zero
+fn join(head: String, rest: u64, spell: fn(u64) -> Option<String>) -> Option<String> {
+    Some(if rest == 0 { head } else { format!("{} {}", head, spell(rest)?) })
+}
+
+fn english_ordinal(n: u64) -> Option<String> {
+    let cardinal = english_cardinal(n)?;
+    let split = cardinal.rfind([' ', '-']).map_or(0, |at| at + 1);
+    let (head, last) = cardinal.split_at(split);
+    let last = match last {
+        "one" => "first".to_string(),
+        "two" => "second".to_string(),
+        "three" => "third".to_string(),
+        "five" => "fifth".to_string(),
+        "eight" => "eighth".to_string(),
+        "nine" => "ninth".to_string(),
+        "twelve" => "twelfth".to_string(),
+        last if last.ends_with('y') => format!("{}ieth", &last[..last.len() - 1]),
+        last => format!("{}th", last),
+    };
+    Some(format!("{}{}", head, last))
+}
+
+/// "nineteen ninety-nine", "two thousand five", "twenty twenty-four"
+fn english_year(year: u64) -> Option<String> {
+    let (century, rest) = (year / 100, year % 100);
+    match year {
+        2000..=2009 => english_cardinal(year),
+        1000..=9999 if rest == 0 => Some(format!("{} hundred", english_cardinal(century)?)),
+        1000..=9999 if rest < 10 => Some(format!("{} oh {}", english_cardinal(century)?, english_cardinal(rest)?)),
+        1000..=9999 => Some(format!("{} {}", english_cardinal(century)?, english_cardinal(rest)?)),
+        _ => english_cardinal(year),
+    }
+}
+
+const SPANISH_UNITS: [&str; 30] = [
+    "cero", "uno", "dos", "tres", "cuatro", "cinco", "seis", "siete", "ocho", "nueve", "diez",
+    "once", "doce", "trece", "catorce", "quince", "dieciséis", "diecisiete", "dieciocho", "diecinueve",
+    "veinte", "veintiuno", "veintidós", "veintitrés", "veinticuatro", "veinticinco", "veintiséis", "veintisiete", "veintiocho", "veintinueve",
+];
+const SPANISH_TENS: [&str; 10] = ["", "", "veinte", "treinta", "cuarenta", "cincuenta", "sesenta", "setenta", "ochenta", "noventa"];
+const SPANISH_HUNDREDS: [&str; 10] = [
+    "", "ciento", "doscientos", "trescientos", "cuatrocientos", "quinientos", "seiscientos", "setecientos", "ochocientos", "novecientos",
+];
+
+/// Up to 999.999.999.999 ("novecientos noventa y nueve mil millones...")
+fn spanish_cardinal(n: u64, gender: Gender) -> Option<String> {
+    if n == 0 {
+        return Some("cero".to_string());
+    }
+    if n >= 1_000_000_000_000 {
+        return None;
+    }
+
+    let (millions, rest) = (n / 1_000_000, n % 1_000_000);
+    let mut words = Vec::new();
+    match millions {
+        0 => {}
+        1 => words.push("un millón".to_string()),
+        millions => words.push(format!("{} millones", spanish_below_million(millions, Gender::Masculine))),
+    }
+    if rest > 0 {
+        words.push(spanish_below_million(rest, gender));
+    }
+    Some(words.join(" "))
+}
+
+fn spanish_below_million(n: u64, gender: Gender) -> String {
+    let (thousands, rest) = (n / 1_000, n % 1_000);
+    let mut words = Vec::new();
+    match thousands {
+        0 => {}
+        1 => words.push("mil".to_string()),
+        // "veintiún mil", but "veintiuna mil libras"
+        thousands => {
+            let gender = if gender == Gender::Feminine { gender } else { Gender::Masculine };
+            words.push(format!("{} mil", spanish_below_thousand(thousands, gender)));
+        }
+    }
+    if rest > 0 {
+        words.push(spanish_below_thousand(rest, gender));
+    }
+    words.join(" ")
+}
+
+fn spanish_below_thousand(n: u64, gender: Gender) -> String {
+    let (hundreds, rest) = (n / 100, n % 100);
+    let mut words = Vec::new();
+    match hundreds {
+        0 => {}
+        1 if rest == 0 => words.push("cien".to_string()),
+        1 => words.push("ciento".to_string()),
+        hundreds if gender == Gender::Feminine => words.push(SPANISH_HUNDREDS[hundreds as usize].replace("tos", "tas")),
+        hundreds => words.push(SPANISH_HUNDREDS[hundreds as usize].to_string()),
+    }
+    if rest > 0 {
+        let below_hundred = match rest {
+            0..=29 => SPANISH_UNITS[rest as usize].to_string(),
+            _ if rest.is_multiple_of(10) => SPANISH_TENS[rest as usize / 10].to_string(),
+            _ => format!("{} y {}", SPANISH_TENS[rest as usize / 10], SPANISH_UNITS[rest as usize % 10]),
+        };
+        words.push(match gender {
+            Gender::None => below_hundred,
+            Gender::Masculine if below_hundred.ends_with("veintiuno") => below_hundred.replace("veintiuno", "veintiún"),
+            Gender::Masculine if below_hundred.ends_with("uno") => format!("{}n", &below_hundred[..below_hundred.len() - 2]),
+            Gender::Feminine if below_hundred.ends_with("uno") => format!("{}a", &below_hundred[..below_hundred.len() - 1]),
+            _ => below_hundred,
+        });
+    }
+    words.join(" ")
+}
+
+/// Whether `language` has spelling-out rules (English and Spanish)
+pub fn supports(language: &str) -> bool {
+    Locale::for_language(language).is_some()
+}
+
+/// Numbers, dates, times, amounts and symbols in `text` and how each is
+/// said; none for languages without rules
+pub fn expansions(text: &str, language: &str) -> Vec<Expansion> {
+    let Some(locale) = Locale::for_language(language) else {
+        return Vec::new();
+    };
+
+    stages::time(Stage::Normalization, || {
+        locale.pattern().captures_iter(text)
+            .filter_map(|caps| {
+                let matched = caps.get(0)?;
+                let mut expansion = Expansion { start: matched.start(), end: matched.end(), spoken: locale.expand(&caps)? };
+
+                // A leading "-" is a minus sign unless it joins two words ("2-3")
+                let signed = caps.name("number").or(caps.name("percent")).is_some();
+                let before = &text[..expansion.start];
+                if signed && before.ends_with('-') && before[..before.len() - 1].chars().next_back().is_none_or(char::is_whitespace) {
+                    expansion.start -= 1;
+                    expansion.spoken = format!("{} {}", locale.minus(), expansion.spoken);
+                }
+                Some(expansion)
+            })
+            .collect()
+    })
+}
+
+/// `text` with numbers, dates, times, amounts and symbols spelled out
+pub fn normalize(text: &str, language: &str) -> String {
+    apply(text, &expansions(text, language))
+}
+
+/// Replace each expansion's span with its spoken form, spaced from any
+/// word it touches ("R&D" → "R and D")
+pub fn apply(text: &str, expansions: &[Expansion]) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    let mut last = 0;
+    for expansion in expansions {
+        normalized.push_str(&text[last..expansion.start]);
+        if normalized.chars().next_back().is_some_and(char::is_alphanumeric) {
+            normalized.push(' ');
+        }
+        normalized.push_str(&expansion.spoken);
+        if text[expansion.end..].chars().next().is_some_and(char::is_alphanumeric) {
+            normalized.push(' ');
+        }
+        last = expansion.end;
+    }
+    normalized.push_str(&text[last..]);
+    normalized
+}
+
+/// `req` with its text spelled out if it asked for `normalize`
+///
+/// Timings and offsets are then for the spoken text, which the response
+/// returns as its `text`.
+pub fn alignment_request(req: &AlignmentRequest) -> Cow<'_, AlignmentRequest> {
+    if !req.normalize || !supports(&req.language) {
+        return Cow::Borrowed(req);
+    }
+    Cow::Owned(AlignmentRequest { text: normalize(&req.text, &req.language), ..req.clone() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_english() {
+        let cases = [
+            ("I have 42 cats", "I have forty-two cats"),
+            ("It costs $5.", "It costs five dollars."),
+            ("$1.50 or £0.05", "one dollar and fifty cents or five pence"),
+            ("Meet at 3:30, not 3:05 or 4:00", "Meet at three thirty, not three oh five or four o'clock"),
+            ("Born 1999-12-31", "Born December thirty-first, nineteen ninety-nine"),
+            ("On 3/15/2024 at 21:00", "On March fifteenth, twenty twenty-four at twenty-one hundred"),
+            ("The 2nd and 23rd", "The second and twenty-third"),
+            ("1,250,000 people, 50% off", "one million two hundred fifty thousand people, fifty percent off"),
+            ("It's -3.5 degrees", "It's minus three point five degrees"),
+            ("Pages 2-3", "Pages two-three"),
+            ("R&D", "R and D"),
+            ("Room 101", "Room one hundred one"),
+        ];
+        for (text, spoken) in cases {
+            assert_eq!(normalize(text, "en-US"), spoken, "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_spanish() {
+        let cases = [
+            ("Son 21", "Son veintiuno"),
+            ("Cuesta 21 €", "Cuesta veintiún euros"),
+            ("1.500,50 €", "mil quinientos euros con cincuenta céntimos"),
+            ("£200", "doscientas libras"),
+            ("$1000000", "un millón de dólares"),
+            ("A la 1:30 o a las 3:15", "A la una y media o a las tres y cuarto"),
+            ("El 15/03/2024", "El quince de marzo de dos mil veinticuatro"),
+            ("3,14 y 3,05", "tres coma catorce y tres coma cero cinco"),
+            ("la 1ª vez, el 3º", "la primera vez, el tercero"),
+            ("21.000 personas", "veintiún mil personas"),
+        ];
+        for (text, spoken) in cases {
+            assert_eq!(normalize(text, "es"), spoken, "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_left_as_written() {
+        assert_eq!(normalize("Version 13/45/2024 at 25:99", "en"), "Version 13/45/2024 at 25:99");
+        assert_eq!(normalize("42", "ja"), "42");
+        assert_eq!(expansions("Pay $5", "en"), vec![Expansion { start: 4, end: 6, spoken: "five dollars".to_string() }]);
+    }
+}
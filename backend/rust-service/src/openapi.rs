@@ -0,0 +1,102 @@
+use utoipa::OpenApi;
+
+use crate::models;
+
+/// OpenAPI document for every HTTP endpoint, served at `/api/docs`
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "dubdub", description = "Tokenization, word alignment and subtitle tooling for language learning and dubbing.\n\nTokenize, align and job status endpoints also accept and return MessagePack (`application/msgpack`) and CBOR (`application/cbor`), chosen by `Content-Type` and `Accept`; errors are always JSON."),
+    paths(
+        crate::health,
+        crate::deep_health,
+        crate::liveness,
+        crate::readiness,
+        crate::warmup,
+        crate::validate_request,
+        crate::tokenize,
+        crate::batch_tokenize,
+        crate::batch_align,
+        crate::align_words,
+        crate::align_file,
+        crate::align_file_stream,
+        crate::align_file_stats,
+        crate::score_alignment,
+        crate::upload_align,
+        crate::speech_rate,
+        crate::audio_snippets,
+        crate::score_pronunciation,
+        crate::practice_pack,
+        crate::parse_subtitles,
+        crate::generate_subtitles,
+        crate::resync_subtitles,
+        crate::validate_subtitles,
+        crate::merge_cues,
+        crate::split_cues,
+        crate::pair_subtitles,
+        crate::diff_transcript,
+        crate::fit_dub_script,
+        crate::predict_duration,
+        crate::cloze_exercises,
+        crate::extract_vocabulary,
+        crate::find_collocations,
+        crate::score_difficulty,
+        crate::put_known_words,
+        crate::get_known_words,
+        crate::delete_known_words,
+        crate::lookup,
+        crate::phonemize,
+        crate::phonemize_languages,
+        crate::syllabify,
+        crate::highlight_spans,
+        crate::normalize_text,
+        crate::batch_zip,
+        crate::submit_job,
+        crate::get_job,
+        crate::list_jobs,
+        crate::cancel_job,
+        crate::create_upload_url,
+        crate::get_usage,
+        crate::get_settings,
+        crate::update_settings,
+        crate::get_cache,
+        crate::flush_cache,
+        crate::reload_data,
+        crate::get_languages,
+        crate::evict_languages,
+    ),
+    components(schemas(
+        models::AlignmentMethod, models::GapKind, models::CueWarningKind,
+        models::CueAlignment, models::AlignmentProgress, models::AlignmentDone, models::StoredObject,
+        models::MultiAlignmentResponse, models::CueAlignmentResult, models::ResponseMeta,
+    )),
+    tags(
+        (name = "system"),
+        (name = "text", description = "Tokenization and normalization"),
+        (name = "alignment", description = "Word timing estimation and scoring"),
+        (name = "subtitles", description = "Subtitle file parsing, writing and QC"),
+        (name = "dubbing"),
+        (name = "learning", description = "Exercises and vocabulary for learners"),
+        (name = "batch"),
+        (name = "jobs", description = "Long-running work, polled by ID"),
+        (name = "storage", description = "Large files via object storage (S3_BUCKET)"),
+        (name = "admin", description = "Runtime settings, the alignment cache, data reloads and resident languages, with an admin key (ADMIN_KEYS)"),
+    )
+)]
+pub struct ApiDoc;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spec_lists_endpoints_and_schemas() {
+        let spec = ApiDoc::openapi();
+
+        assert!(spec.paths.paths.contains_key("/api/v1/align"));
+        assert!(spec.paths.paths.contains_key("/api/v1/subtitles/validate"));
+
+        let schemas = spec.components.unwrap().schemas;
+        assert!(schemas.contains_key("AlignmentRequest"));
+        assert!(schemas.contains_key("Cue"));
+    }
+}
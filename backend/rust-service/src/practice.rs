@@ -0,0 +1,230 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, Write};
+
+use sha2::{Digest, Sha256};
+use tempfile::NamedTempFile;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::audio::AudioBuffer;
+use crate::difficulty;
+use crate::error::{ApiError, ErrorCode};
+use crate::gloss::Glosser;
+use crate::models::{
+    Cue, CueWarning, CueWarningKind, FileAlignmentResponse, PackMedia, PracticeCue, PracticePack, PracticeWord,
+};
+use crate::snippets::{MAX_SNIPPETS, MAX_SNIPPET_SECONDS, MAX_TOTAL_SNIPPET_SECONDS};
+use crate::spool;
+use crate::vocabulary;
+
+/// `version` of the packs built here
+pub const PACK_VERSION: u32 = 1;
+
+/// Refuse to cut a clip of every cue when there are more cues, longer
+/// ones or more audio in all than `/audio-snippets` cuts, before any audio
+/// is decoded
+pub fn check_clips(alignment: &FileAlignmentResponse) -> Result<(), ApiError> {
+    let too_large = |message: String| ApiError::new(ErrorCode::PayloadTooLarge, message).with_field("subtitles");
+    if alignment.cues.len() > MAX_SNIPPETS {
+        return Err(too_large(format!("{} cues with audio, at most {} are cut into a pack", alignment.cues.len(), MAX_SNIPPETS)));
+    }
+    if let Some(long) = alignment.cues.iter().find(|cue| cue.end - cue.start > MAX_SNIPPET_SECONDS) {
+        return Err(too_large(format!("Cue {} is {:.1}s long, at most {}s are cut", long.index, long.end - long.start, MAX_SNIPPET_SECONDS)));
+    }
+    let total: f64 = alignment.cues.iter().map(|cue| cue.end - cue.start).sum();
+    if total > MAX_TOTAL_SNIPPET_SECONDS {
+        return Err(too_large(format!("Cues add up to {:.0}s of audio, at most {}s are cut into a pack", total, MAX_TOTAL_SNIPPET_SECONDS)));
+    }
+    Ok(())
+}
+
+/// Build a practice pack from an aligned file and, if there was audio, a
+/// clip of each cue, with the media files written one after another, in
+/// `media` order, to a temporary file
+///
+/// Cues whose clip is empty (they start after the audio ends) get no
+/// audio and a warning.
+///
+/// Blocking: call from `web::block`.
+pub fn assemble(alignment: FileAlignmentResponse, language: &str, clips: Option<Vec<AudioBuffer>>) -> Result<(PracticePack, NamedTempFile), ApiError> {
+    let cues: Vec<Cue> = alignment.cues.iter()
+        .map(|cue| Cue { index: cue.index, start: cue.start, end: cue.end, text: cue.text.clone(), ..Default::default() })
+        .collect();
+    let (score, per_cue) = difficulty::score_cues(&cues, language);
+    let (entries, _) = vocabulary::extract(&cues, language);
+
+    let mut warnings = alignment.warnings;
+    let mut media = Vec::new();
+    let mut files = spool::temp_file(|_| Ok(()))?;
+    let sample_rate = clips.as_ref().and_then(|clips| clips.first()).map(|clip| clip.sample_rate);
+    let mut clips = clips.map(Vec::into_iter);
+
+    let mut practice_cues = Vec::with_capacity(alignment.cues.len());
+    for (n, (cue, difficulty)) in alignment.cues.into_iter().zip(per_cue).enumerate() {
+        let clip = clips.as_mut().and_then(Iterator::next);
+        let audio = match clip {
+            Some(clip) if !clip.samples.is_empty() => {
+                let file = format!("audio/{:04}.wav", n + 1);
+                let bytes = clip.to_wav();
+                files.write_all(&bytes).map_err(|e| ApiError::internal(format!("Failed to write pack: {}", e)))?;
+                media.push(PackMedia {
+                    file: file.clone(),
+                    cue_index: cue.index,
+                    content_type: "audio/wav".to_string(),
+                    size: bytes.len(),
+                    sha256: hex::encode(Sha256::digest(&bytes)),
+                });
+                Some(file)
+            }
+            Some(_) => {
+                warnings.push(CueWarning {
+                    cue_index: cue.index,
+                    kind: CueWarningKind::BeyondAudio,
+                    message: format!("Cue starts at {} but the audio has ended; it has no clip", cue.start),
+                });
+                None
+            }
+            None => None,
+        };
+        practice_cues.push(PracticeCue {
+            index: cue.index,
+            start: cue.start,
+            end: cue.end,
+            text: cue.text,
+            timings: cue.timings,
+            difficulty: difficulty.score,
+            audio,
+        });
+    }
+
+    let pack = PracticePack {
+        version: PACK_VERSION,
+        language: language.to_string(),
+        gloss_language: None,
+        difficulty: score,
+        cues: practice_cues,
+        vocabulary: entries.into_iter().map(|entry| PracticeWord { entry, gloss: None }).collect(),
+        media,
+        sample_rate,
+        warnings,
+    };
+    Ok((pack, files))
+}
+
+/// Gloss every word of the pack's vocabulary in `target`
+pub async fn gloss(pack: &mut PracticePack, glosser: &Glosser, target: &str) {
+    let lemmas: Vec<String> = pack.vocabulary.iter().map(|word| word.entry.lemma.clone()).collect();
+    // Failed sources are logged; a pack has no place for request warnings
+    let (glosses, _) = glosser.gloss(&lemmas, &pack.language, target).await;
+    for (word, gloss) in pack.vocabulary.iter_mut().zip(glosses) {
+        word.gloss = gloss;
+    }
+    pack.gloss_language = Some(target.to_string());
+}
+
+/// Pack `pack.json` and the media files, read in turn from `files` at the
+/// sizes `media` gives, into `out`
+///
+/// Blocking: call from `web::block`.
+pub fn bundle_zip<W: Write + Seek>(pack: &PracticePack, files: &NamedTempFile, out: W) -> Result<W, ApiError> {
+    let write_error = |e: io::Error| ApiError::internal(format!("Failed to write pack: {}", e));
+    let mut writer = ZipWriter::new(out);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    let manifest = serde_json::to_vec(pack).map_err(|e| ApiError::internal(format!("Failed to serialize pack: {}", e)))?;
+    writer.start_file("pack.json", options)
+        .and_then(|_| writer.write_all(&manifest).map_err(Into::into))
+        .map_err(|e| ApiError::internal(format!("Failed to write pack: {}", e)))?;
+
+    let mut files = File::open(files.path()).map_err(write_error)?;
+    for media in &pack.media {
+        writer.start_file(media.file.as_str(), options)
+            .map_err(|e| ApiError::internal(format!("Failed to write pack: {}", e)))?;
+        let copied = io::copy(&mut Read::by_ref(&mut files).take(media.size as u64), &mut writer).map_err(write_error)?;
+        if copied != media.size as u64 {
+            return Err(ApiError::internal(format!("Failed to write pack: {} ended early", media.file)));
+        }
+    }
+
+    writer.finish().map_err(|e| ApiError::internal(format!("Failed to write pack: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::aligner::align_file;
+    use crate::fixtures::cue;
+    use crate::gloss::Dictionaries;
+    use crate::models::{FileAlignmentRequest, OverlapPolicy};
+
+    fn alignment() -> FileAlignmentResponse {
+        align_file(&FileAlignmentRequest {
+            language: "en".to_string(),
+            cues: vec![cue(1, 0.0, 1.0, "The cat sat."), cue(2, 1.0, 2.0, "The cat ran."), cue(3, 5.0, 6.0, "Gone.")],
+            overlap_policy: OverlapPolicy::Clamp,
+        }).unwrap()
+    }
+
+    #[test]
+    fn test_assemble_with_audio() {
+        let clips = vec![
+            AudioBuffer { samples: vec![0.1; 160], sample_rate: 16_000 },
+            AudioBuffer { samples: vec![0.2; 160], sample_rate: 16_000 },
+            AudioBuffer { samples: Vec::new(), sample_rate: 16_000 },
+        ];
+        let (pack, files) = assemble(alignment(), "en", Some(clips)).unwrap();
+
+        assert_eq!(pack.version, PACK_VERSION);
+        assert_eq!(pack.cues.len(), 3);
+        assert_eq!(pack.cues[1].timings.len(), 3);
+        assert_eq!(pack.cues[1].audio.as_deref(), Some("audio/0002.wav"));
+        assert_eq!(pack.cues[2].audio, None);
+        assert_eq!(pack.warnings.last().map(|warning| warning.kind), Some(CueWarningKind::BeyondAudio));
+        assert_eq!(pack.sample_rate, Some(16_000));
+        assert_eq!(pack.media.len(), 2);
+        assert!(pack.vocabulary.iter().any(|word| word.entry.lemma == "cat" && word.entry.count == 2));
+
+        let cursor = bundle_zip(&pack, &files, Cursor::new(Vec::new())).unwrap();
+        let mut archive = zip::ZipArchive::new(cursor).unwrap();
+        let mut names: Vec<&str> = archive.file_names().collect();
+        names.sort();
+        assert_eq!(names, vec!["audio/0001.wav", "audio/0002.wav", "pack.json"]);
+        for media in &pack.media {
+            let mut bytes = Vec::new();
+            archive.by_name(&media.file).unwrap().read_to_end(&mut bytes).unwrap();
+            assert_eq!(bytes.len(), media.size);
+            assert_eq!(hex::encode(Sha256::digest(&bytes)), media.sha256);
+        }
+    }
+
+    #[test]
+    fn test_check_clips() {
+        assert!(check_clips(&alignment()).is_ok());
+
+        let mut long = alignment();
+        long.cues[2].end = long.cues[2].start + MAX_SNIPPET_SECONDS + 1.0;
+        assert_eq!(check_clips(&long).unwrap_err().code, ErrorCode::PayloadTooLarge);
+
+        let many = align_file(&FileAlignmentRequest {
+            language: "en".to_string(),
+            cues: (0..=MAX_SNIPPETS).map(|n| cue(n + 1, n as f64 * 0.1, (n + 1) as f64 * 0.1, "Hi.")).collect(),
+            overlap_policy: OverlapPolicy::Clamp,
+        }).unwrap();
+        assert_eq!(check_clips(&many).unwrap_err().code, ErrorCode::PayloadTooLarge);
+    }
+
+    #[tokio::test]
+    async fn test_assemble_without_audio() {
+        let (mut pack, files) = assemble(alignment(), "en", None).unwrap();
+        assert!(files.as_file().metadata().unwrap().len() == 0 && pack.media.is_empty());
+        assert!(pack.cues.iter().all(|cue| cue.audio.is_none()));
+        assert_eq!(pack.sample_rate, None);
+
+        // No dictionaries are loaded in tests, so nothing is glossed
+        gloss(&mut pack, &Glosser::new(vec![Box::new(Dictionaries)]), "en").await;
+        assert_eq!(pack.gloss_language.as_deref(), Some("en"));
+        assert!(pack.vocabulary.iter().all(|word| word.gloss.is_none()));
+    }
+}
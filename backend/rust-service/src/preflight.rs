@@ -0,0 +1,108 @@
+use crate::models::{AlignmentMode, DryRun, DryRunRequest, RequestValidation};
+use crate::validation::{self, Validate};
+use crate::{aligner, audio, duration, usage};
+
+/// Check `dry_run`'s request the way its endpoint would, without doing the
+/// work, for `POST /api/validate-request`
+///
+/// # How it works:
+/// 1. Validate it as the endpoint does (language, text length, timings),
+///    without counting its text against the usage quota
+/// 2. For alignment against audio, check that forced alignment can run
+///    (or the request allows falling back) and that the audio answers
+/// 3. Warn about what would work but not as well as it could, e.g. a
+///    language with no duration model
+pub async fn check(dry_run: &DryRun) -> RequestValidation {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+
+    // Step 1: The endpoint's own validation
+    let validated = usage::unmetered(|| match &dry_run.request {
+        DryRunRequest::Tokenize(req) => req.validate(),
+        DryRunRequest::Align(req) => req.validate(),
+        DryRunRequest::AlignFile(req) => req.validate(),
+    });
+    errors.extend(validated.err());
+
+    // Step 2: Audio
+    let (language, audio_url) = match &dry_run.request {
+        DryRunRequest::Tokenize(_) => {
+            if dry_run.audio_url.is_some() {
+                warnings.push("audio_url was ignored: tokenizing doesn't use audio".to_string());
+            }
+            (None, None)
+        }
+        DryRunRequest::Align(req) => {
+            // TTS mode times the text as spoken by the voice, not the audio
+            let audio_url = req.audio_url.as_ref().filter(|_| req.mode == AlignmentMode::Subtitle);
+            if audio_url.is_some() {
+                let unavailable = aligner::forced_alignment_unavailable();
+                match req.allow_fallback {
+                    true => warnings.push(format!("{}, so timings will be estimated from the subtitle window", unavailable.message)),
+                    false => errors.push(unavailable),
+                }
+            }
+            (Some(&req.language), audio_url)
+        }
+        DryRunRequest::AlignFile(req) => (Some(&req.language), dry_run.audio_url.as_ref()),
+    };
+    if let Some(url) = audio_url
+        && let Err(e) = audio::probe(url).await
+    {
+        errors.push(e.with_field("audio_url"));
+    }
+
+    // Step 3: Data the language lacks
+    if let Some(language) = language.filter(|language| validation::is_known_language(language))
+        && !duration::models().contains(language)
+    {
+        warnings.push(format!("No duration model for '{}', so words will be weighted by their length", language));
+    }
+
+    RequestValidation { valid: errors.is_empty(), errors, warnings }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorCode;
+    use crate::models::{AlignmentRequest, TokenizeRequest};
+
+    fn dry_run(request: DryRunRequest) -> DryRun {
+        DryRun { request, audio_url: None }
+    }
+
+    #[tokio::test]
+    async fn test_valid_request() {
+        let tokenize = TokenizeRequest { text: "Hola".to_string(), language: "es".to_string(), known: None };
+        let checked = check(&dry_run(DryRunRequest::Tokenize(tokenize))).await;
+        assert!(checked.valid);
+        assert!(checked.errors.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reports_every_problem() {
+        let align = AlignmentRequest {
+            text: "Hello".to_string(),
+            language: "en".to_string(),
+            subtitle_start: 2.0,
+            subtitle_end: 1.0,
+            audio_url: Some("http://127.0.0.1:9/episode.mp3".to_string()),
+            ..Default::default()
+        };
+        let checked = check(&dry_run(DryRunRequest::Align(align.clone()))).await;
+        assert!(!checked.valid);
+        let codes: Vec<(ErrorCode, Option<&str>)> = checked.errors.iter().map(|e| (e.code, e.field.as_deref())).collect();
+        assert_eq!(codes, vec![
+            (ErrorCode::InvalidInput, Some("subtitle_end")),
+            (ErrorCode::Unsupported, Some("audio_url")),
+            // A private address, refused before it's requested
+            (ErrorCode::InvalidInput, Some("audio_url")),
+        ]);
+
+        // Falling back turns forced alignment being unavailable into a warning
+        let checked = check(&dry_run(DryRunRequest::Align(AlignmentRequest { allow_fallback: true, ..align }))).await;
+        assert_eq!(checked.errors.len(), 2);
+        assert!(checked.warnings.iter().any(|warning| warning.starts_with("Forced alignment not yet implemented")));
+    }
+}
@@ -0,0 +1,267 @@
+use crate::audio::{EnergyEnvelope, ENVELOPE_FRAME_SECONDS};
+use crate::error::ApiError;
+use crate::models::{PronouncedWord, PronunciationResponse, WordTiming};
+
+/// Silence (seconds) that counts as a pause, in the reference or the recording
+const MIN_PAUSE: f64 = 0.2;
+
+/// Furthest a recording's pause may sit from a reference pause, as a share
+/// of the speaking time, and still be taken for the same pause
+const MAX_PAUSE_SHIFT: f64 = 0.15;
+
+/// Least speech (seconds) a recording needs to be scored
+const MIN_SPEECH_SECONDS: f64 = 0.2;
+
+/// Share of a word's time with speech at which it counts as fully said;
+/// stops and the joins between words leave the rest quiet
+const FULL_VOICING: f64 = 0.6;
+
+/// Share of its score a word keeps with a hesitation in it
+const HESITATION_FACTOR: f64 = 0.5;
+
+/// Share of its score a word keeps when the learner runs into it through
+/// a pause the reference makes
+const MISSED_PAUSE_FACTOR: f64 = 0.75;
+
+/// Score a learner's recording of a cue against the cue's word timings
+///
+/// # How it works:
+/// 1. Find speech in the recording from its loudness (as `/speech-rate`
+///    does), trimming leading and trailing silence
+/// 2. Find pauses in both: gaps between reference words, and silences
+///    inside the learner's speech
+/// 3. Pair each reference pause with the learner pause at about the same
+///    point of the cue, and map reference time onto the recording through
+///    the paired pauses, so each word gets a window in the recording
+/// 4. Score each word by how much of its window has speech, less for
+///    unpaired learner pauses in or just before it (hesitations) and for
+///    reference pauses the learner ran through
+///
+/// Only timing and rhythm are judged: whether each sound was right needs an
+/// acoustic model, which a loudness envelope isn't.
+pub fn score(reference: &[WordTiming], envelope: &EnergyEnvelope) -> Result<PronunciationResponse, ApiError> {
+    let (Some(first), Some(last)) = (reference.first(), reference.last()) else {
+        return Err(ApiError::invalid_input("Reference timings are empty").with_field("timings"));
+    };
+    let (reference_start, reference_end) = (first.start, last.end.max(first.start));
+    if reference_end <= reference_start {
+        return Err(ApiError::invalid_input("Reference timings span no time").with_field("timings"));
+    }
+
+    // Step 1: Speech in the recording
+    let threshold = envelope.speech_threshold();
+    let voiced: Vec<bool> = envelope.levels.iter().map(|&level| level > threshold).collect();
+    let (Some(first_voiced), Some(last_voiced)) = (voiced.iter().position(|&v| v), voiced.iter().rposition(|&v| v)) else {
+        return Err(ApiError::invalid_input("No speech in the recording").with_field("audio"));
+    };
+    let speech_start = first_voiced as f64 * ENVELOPE_FRAME_SECONDS;
+    let speech_end = (last_voiced + 1) as f64 * ENVELOPE_FRAME_SECONDS;
+    if speech_end - speech_start < MIN_SPEECH_SECONDS {
+        return Err(ApiError::invalid_input("Too little speech in the recording to score").with_field("audio"));
+    }
+
+    // Step 2: Pauses, as (word after, start, end) in the reference
+    let reference_pauses: Vec<(usize, f64, f64)> = reference.windows(2)
+        .enumerate()
+        .filter(|(_, pair)| pair[1].start - pair[0].end >= MIN_PAUSE)
+        .map(|(at, pair)| (at + 1, pair[0].end, pair[1].start))
+        .collect();
+    let learner_pauses = silences(&voiced[first_voiced..=last_voiced], speech_start);
+
+    // Step 3: Paired pauses and the time map
+    let reference_position = |(from, to): (f64, f64)| ((from + to) / 2.0 - reference_start) / (reference_end - reference_start);
+    let learner_position = |(from, to): (f64, f64)| ((from + to) / 2.0 - speech_start) / (speech_end - speech_start);
+    let mut pairs: Vec<Option<usize>> = Vec::new();
+    let mut next = 0;
+    for &(_, from, to) in &reference_pauses {
+        let position = reference_position((from, to));
+        let pair = (next..learner_pauses.len())
+            .map(|at| (at, (learner_position(learner_pauses[at]) - position).abs()))
+            .filter(|(_, shift)| *shift <= MAX_PAUSE_SHIFT)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(at, _)| at);
+        if let Some(at) = pair {
+            next = at + 1;
+        }
+        pairs.push(pair);
+    }
+
+    let mut anchors = vec![(reference_start, speech_start)];
+    for (&(_, from, to), pair) in reference_pauses.iter().zip(&pairs) {
+        if let Some(&(learner_from, learner_to)) = pair.map(|at| &learner_pauses[at]) {
+            anchors.extend([(from, learner_from), (to, learner_to)]);
+        }
+    }
+    anchors.push((reference_end, speech_end));
+    let map = |time: f64| to_recording(&anchors, time);
+
+    // Step 4: Words
+    let windows: Vec<(f64, f64)> = reference.iter().map(|timing| (map(timing.start), map(timing.end))).collect();
+    let mut hesitations = vec![false; reference.len()];
+    let mut paused_before = vec![false; reference.len()];
+    for (at, &pause) in learner_pauses.iter().enumerate() {
+        let center = (pause.0 + pause.1) / 2.0;
+        let Some(word) = windows.iter().position(|&(_, end)| end > center) else { continue };
+        if center <= windows[word].0 {
+            paused_before[word] = true;
+        }
+        if !pairs.contains(&Some(at)) {
+            hesitations[word] = true;
+        }
+    }
+
+    let words: Vec<PronouncedWord> = reference.iter()
+        .zip(&windows)
+        .enumerate()
+        .map(|(at, (timing, &(start, end)))| {
+            let voiced_share = voiced_share(&voiced, start, end);
+            let pause_expected = reference_pauses.iter().any(|(word, _, _)| *word == at);
+            let paused = paused_before[at];
+            let mut score = (voiced_share / FULL_VOICING).min(1.0);
+            if hesitations[at] {
+                score *= HESITATION_FACTOR;
+            }
+            if pause_expected && !paused {
+                score *= MISSED_PAUSE_FACTOR;
+            }
+            PronouncedWord {
+                word: timing.word.clone(),
+                reference_start: timing.start,
+                reference_end: timing.end,
+                start,
+                end,
+                voiced: voiced_share,
+                pause_expected,
+                paused,
+                hesitation: hesitations[at],
+                score,
+                matched: score >= 0.5,
+            }
+        })
+        .collect();
+
+    Ok(PronunciationResponse {
+        tempo: (speech_end - speech_start) / (reference_end - reference_start),
+        score: words.iter().map(|word| word.score).sum::<f64>() / words.len() as f64,
+        n_matched: words.iter().filter(|word| word.matched).count(),
+        n_missed_pauses: words.iter().filter(|word| word.pause_expected && !word.paused).count(),
+        n_extra_pauses: learner_pauses.len() - pairs.iter().flatten().count(),
+        speech_start,
+        speech_end,
+        words,
+    })
+}
+
+/// Runs of at least `MIN_PAUSE` without speech, in seconds from `offset`
+fn silences(voiced: &[bool], offset: f64) -> Vec<(f64, f64)> {
+    let min_frames = (MIN_PAUSE / ENVELOPE_FRAME_SECONDS).round() as usize;
+    let to_seconds = |frame: usize| offset + frame as f64 * ENVELOPE_FRAME_SECONDS;
+    let mut silences = Vec::new();
+    let mut run_start = None;
+
+    for (frame, &is_voiced) in voiced.iter().chain([&true]).enumerate() {
+        match (is_voiced, run_start) {
+            (false, None) => run_start = Some(frame),
+            (true, Some(start)) => {
+                if frame - start >= min_frames {
+                    silences.push((to_seconds(start), to_seconds(frame)));
+                }
+                run_start = None;
+            }
+            _ => {}
+        }
+    }
+    silences
+}
+
+/// Reference time mapped linearly between the anchors around it
+fn to_recording(anchors: &[(f64, f64)], time: f64) -> f64 {
+    let after = anchors.iter().position(|&(reference, _)| reference > time).unwrap_or(anchors.len() - 1).max(1);
+    let ((reference_from, from), (reference_to, to)) = (anchors[after - 1], anchors[after]);
+    if reference_to <= reference_from {
+        return from;
+    }
+    let share = ((time - reference_from) / (reference_to - reference_from)).clamp(0.0, 1.0);
+    from + share * (to - from)
+}
+
+/// Share of the frames between `start` and `end` with speech
+fn voiced_share(voiced: &[bool], start: f64, end: f64) -> f64 {
+    let to_frame = |seconds: f64| ((seconds.max(0.0) / ENVELOPE_FRAME_SECONDS).round() as usize).min(voiced.len());
+    let frames = &voiced[to_frame(start)..to_frame(end).max(to_frame(start))];
+    match frames.len() {
+        0 => 0.0,
+        n => frames.iter().filter(|&&v| v).count() as f64 / n as f64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timing(word: &str, start: f64, end: f64) -> WordTiming {
+        WordTiming { word: word.to_string(), start, end, confidence: 1.0, char_start: 0, char_end: 0, flagged: false }
+    }
+
+    /// "one two" then a pause, then "three", starting 10 s into the file
+    fn reference() -> Vec<WordTiming> {
+        vec![timing("one", 10.0, 10.5), timing("two", 10.5, 11.0), timing("three", 11.5, 12.0)]
+    }
+
+    /// Speech in the given ranges, silence elsewhere, `duration` seconds long
+    fn envelope(speech: &[(f64, f64)], duration: f64) -> EnergyEnvelope {
+        let n_frames = (duration / ENVELOPE_FRAME_SECONDS).round() as usize;
+        let levels = (0..n_frames)
+            .map(|frame| {
+                let time = (frame as f64 + 0.5) * ENVELOPE_FRAME_SECONDS;
+                if speech.iter().any(|&(from, to)| time >= from && time < to) { -20.0 } else { -80.0 }
+            })
+            .collect();
+        EnergyEnvelope { levels }
+    }
+
+    #[test]
+    fn test_faithful_shadowing() {
+        // A little faster, pausing in the same place
+        let response = score(&reference(), &envelope(&[(0.2, 1.2), (1.5, 2.0)], 2.5)).unwrap();
+
+        assert!((response.tempo - 0.9).abs() < 1e-9);
+        assert_eq!((response.n_missed_pauses, response.n_extra_pauses), (0, 0));
+        assert_eq!(response.n_matched, 3);
+        assert!((response.score - 1.0).abs() < 1e-9);
+        let three = &response.words[2];
+        assert!(three.pause_expected && three.paused);
+        assert!((three.start - 1.5).abs() < 1e-9);
+        assert!((response.words[1].start - 0.7).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_hesitation_and_missed_pause() {
+        // Stops mid "two", then runs straight into "three"
+        let response = score(&reference(), &envelope(&[(0.0, 0.6), (0.9, 2.0)], 2.5)).unwrap();
+
+        assert_eq!((response.n_missed_pauses, response.n_extra_pauses), (1, 1));
+        let two = &response.words[1];
+        assert!(two.hesitation);
+        assert!(!two.matched);
+        let three = &response.words[2];
+        assert!(!three.paused && !three.hesitation);
+        assert!((three.score - MISSED_PAUSE_FACTOR).abs() < 1e-9);
+        assert!(response.words[0].matched);
+    }
+
+    #[test]
+    fn test_silence_is_rejected() {
+        assert!(score(&reference(), &envelope(&[], 2.0)).is_err());
+        assert!(score(&[], &envelope(&[(0.0, 1.0)], 2.0)).is_err());
+    }
+
+    #[test]
+    fn test_to_recording() {
+        let anchors = [(10.0, 0.0), (11.0, 2.0), (12.0, 2.5)];
+        assert_eq!(to_recording(&anchors, 10.5), 1.0);
+        assert_eq!(to_recording(&anchors, 11.5), 2.25);
+        assert_eq!(to_recording(&anchors, 9.0), 0.0);
+        assert_eq!(to_recording(&anchors, 13.0), 2.5);
+    }
+}
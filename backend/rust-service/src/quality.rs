@@ -0,0 +1,257 @@
+use crate::aligner::{align_linear, align_weighted};
+use crate::error::ApiError;
+use crate::models::{AlignmentRequest, QualityMetrics, ScoreRequest, ScoreResponse, Warning, WarningCode, WordTiming};
+
+/// Comfortable speaking rates (words per second) for subtitled dialogue
+const MIN_PLAUSIBLE_WPS: f64 = 1.0;
+const MAX_PLAUSIBLE_WPS: f64 = 5.0;
+
+/// Default score below which a cue is sent for manual review
+const DEFAULT_REVIEW_THRESHOLD: f64 = 0.6;
+
+/// Tolerance when comparing boundaries (floating point noise)
+const EPSILON: f64 = 1e-6;
+
+/// Score an existing alignment
+///
+/// # How it works:
+/// 1. Words per second → how plausible the speaking rate is
+/// 2. Boundary monotonicity → words ordered, no negative durations
+/// 3. Method agreement → distance from the heuristic aligners' output
+/// 4. Coverage → how much of the subtitle window the words occupy
+///
+/// The overall score is a weighted mean of the four metrics.
+#[tracing::instrument(skip_all, fields(language = %req.language, timings = req.timings.len()))]
+pub fn score_alignment(req: &ScoreRequest) -> Result<ScoreResponse, ApiError> {
+    let duration = req.subtitle_end - req.subtitle_start;
+
+    if duration <= 0.0 {
+        return Err(ApiError::invalid_input("Invalid subtitle timing: end must be after start").with_field("subtitle_end"));
+    }
+
+    if req.timings.is_empty() {
+        return Err(ApiError::invalid_input("No word timings to score").with_field("timings"));
+    }
+
+    let mut warnings = Vec::new();
+    if req.audio_url.is_some() {
+        log::warn!("Audio-based quality metrics not yet implemented, scoring text only");
+        warnings.push(Warning::new(WarningCode::AudioIgnored,
+            "audio_url was ignored: audio-based metrics aren't implemented, so only the timings were scored"));
+    }
+
+    let words_per_second = req.timings.len() as f64 / duration;
+
+    let metrics = QualityMetrics {
+        words_per_second,
+        rate_plausibility: rate_plausibility(words_per_second),
+        monotonicity: monotonicity(&req.timings),
+        method_agreement: method_agreement(req, duration)?,
+        coverage: coverage(&req.timings, req.subtitle_start, req.subtitle_end),
+    };
+
+    let score = 0.25 * metrics.rate_plausibility
+        + 0.30 * metrics.monotonicity
+        + 0.20 * metrics.method_agreement
+        + 0.25 * metrics.coverage;
+
+    let threshold = req.review_threshold.unwrap_or(DEFAULT_REVIEW_THRESHOLD);
+
+    Ok(ScoreResponse {
+        text: req.text.clone(),
+        language: req.language.clone(),
+        score,
+        needs_review: score < threshold,
+        metrics,
+        warnings,
+    })
+}
+
+/// 1.0 inside the plausible range, decaying proportionally outside it
+fn rate_plausibility(wps: f64) -> f64 {
+    if wps < MIN_PLAUSIBLE_WPS {
+        wps / MIN_PLAUSIBLE_WPS
+    } else if wps > MAX_PLAUSIBLE_WPS {
+        MAX_PLAUSIBLE_WPS / wps
+    } else {
+        1.0
+    }
+}
+
+/// Fraction of boundary checks that pass:
+/// every word has start <= end, and every word starts after the previous one ends
+fn monotonicity(timings: &[WordTiming]) -> f64 {
+    let mut checks = 0;
+    let mut passed = 0;
+
+    for timing in timings {
+        checks += 1;
+        if timing.start <= timing.end + EPSILON {
+            passed += 1;
+        }
+    }
+
+    for pair in timings.windows(2) {
+        checks += 1;
+        if pair[0].end <= pair[1].start + EPSILON {
+            passed += 1;
+        }
+    }
+
+    passed as f64 / checks as f64
+}
+
+/// Compare the given timings against the weighted and linear aligners
+///
+/// Boundary differences are measured in units of the average word duration,
+/// so a score of 0.5 means boundaries are off by half a word on average.
+fn method_agreement(req: &ScoreRequest, duration: f64) -> Result<f64, ApiError> {
+    let reference_req = AlignmentRequest {
+        text: req.text.clone(),
+        language: req.language.clone(),
+        subtitle_start: req.subtitle_start,
+        subtitle_end: req.subtitle_end,
+        audio_url: None,
+        ..Default::default()
+    };
+
+    let weighted = align_weighted(&reference_req)?;
+    let linear = align_linear(&reference_req)?;
+
+    let agreement = (boundary_agreement(&req.timings, &weighted.timings, duration)
+        + boundary_agreement(&req.timings, &linear.timings, duration))
+        / 2.0;
+
+    Ok(agreement)
+}
+
+fn boundary_agreement(timings: &[WordTiming], reference: &[WordTiming], duration: f64) -> f64 {
+    // Different word counts means the alignment doesn't match the text at all
+    if timings.len() != reference.len() {
+        return 0.0;
+    }
+
+    let average_word = duration / timings.len() as f64;
+
+    let total_diff: f64 = timings.iter()
+        .zip(reference)
+        .map(|(a, b)| ((a.start - b.start).abs() + (a.end - b.end).abs()) / 2.0)
+        .sum();
+
+    let mean_diff = total_diff / timings.len() as f64 / average_word;
+
+    (1.0 - mean_diff).max(0.0)
+}
+
+/// Fraction of the subtitle window covered by at least one word
+fn coverage(timings: &[WordTiming], window_start: f64, window_end: f64) -> f64 {
+    let mut intervals: Vec<(f64, f64)> = timings.iter()
+        .map(|t| (t.start.max(window_start), t.end.min(window_end)))
+        .filter(|(start, end)| end > start)
+        .collect();
+
+    intervals.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut covered = 0.0;
+    let mut current: Option<(f64, f64)> = None;
+
+    for (start, end) in intervals {
+        match current {
+            Some((cur_start, cur_end)) if start <= cur_end => {
+                current = Some((cur_start, cur_end.max(end)));
+            }
+            Some((cur_start, cur_end)) => {
+                covered += cur_end - cur_start;
+                current = Some((start, end));
+            }
+            None => current = Some((start, end)),
+        }
+    }
+
+    if let Some((cur_start, cur_end)) = current {
+        covered += cur_end - cur_start;
+    }
+
+    covered / (window_end - window_start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score_request(text: &str, timings: Vec<WordTiming>) -> ScoreRequest {
+        ScoreRequest {
+            text: text.to_string(),
+            language: "en".to_string(),
+            subtitle_start: 0.0,
+            subtitle_end: 2.0,
+            timings,
+            audio_url: None,
+            review_threshold: None,
+        }
+    }
+
+    fn timing(word: &str, start: f64, end: f64) -> WordTiming {
+        WordTiming {
+            word: word.to_string(),
+            start,
+            end,
+            confidence: 0.75,
+            char_start: 0,
+            char_end: word.len(),
+            flagged: false,
+        }
+    }
+
+    #[test]
+    fn test_weighted_alignment_scores_high() {
+        let align_req = AlignmentRequest {
+            text: "Hello world".to_string(),
+            language: "en".to_string(),
+            subtitle_start: 0.0,
+            subtitle_end: 2.0,
+            audio_url: None,
+            ..Default::default()
+        };
+        let aligned = align_weighted(&align_req).unwrap();
+
+        let result = score_alignment(&score_request("Hello world", aligned.timings.clone())).unwrap();
+
+        assert!(result.score > 0.9);
+        assert!(!result.needs_review);
+        assert!((result.metrics.coverage - 1.0).abs() < 0.01);
+        assert!((result.metrics.monotonicity - 1.0).abs() < 0.01);
+        assert!(result.warnings.is_empty());
+
+        let mut with_audio = score_request("Hello world", aligned.timings.clone());
+        with_audio.audio_url = Some("https://example.com/audio.wav".to_string());
+        let result = score_alignment(&with_audio).unwrap();
+        assert_eq!(result.warnings[0].code, WarningCode::AudioIgnored);
+    }
+
+    #[test]
+    fn test_out_of_order_timings_need_review() {
+        let timings = vec![
+            timing("Hello", 1.5, 1.6),
+            timing("world", 0.0, 0.1),
+        ];
+
+        let result = score_alignment(&score_request("Hello world", timings)).unwrap();
+
+        assert!(result.metrics.monotonicity < 1.0);
+        assert!(result.metrics.coverage < 0.2);
+        assert!(result.needs_review);
+    }
+
+    #[test]
+    fn test_rate_plausibility() {
+        assert_eq!(rate_plausibility(3.0), 1.0);
+        assert!((rate_plausibility(10.0) - 0.5).abs() < 0.01);
+        assert!((rate_plausibility(0.5) - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_empty_timings_rejected() {
+        assert!(score_alignment(&score_request("Hello world", vec![])).is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use futures::StreamExt;
+use lapin::message::Delivery;
+use lapin::options::{BasicAckOptions, BasicConsumeOptions, BasicNackOptions, BasicPublishOptions, BasicQosOptions, QueueDeclareOptions};
+use lapin::types::FieldTable;
+use lapin::{BasicProperties, Channel, Connection, ConnectionProperties, Consumer};
+use tokio::sync::Mutex;
+
+use super::{Broker, QueueOptions, Received};
+
+/// RabbitMQ (or any AMQP 0.9.1 broker)
+///
+/// Results go to the task's `reply_to` queue if it has one, otherwise to
+/// `--result-queue`, carrying the task's `correlation_id`. A task is acked
+/// only once its result is published; if publishing fails it goes back on
+/// the queue once, then to the queue's dead-letter exchange (if any).
+pub struct AmqpBroker {
+    // Closing the connection would close the channel
+    _connection: Connection,
+    channel: Channel,
+    consumer: Mutex<Consumer>,
+    results: String,
+}
+
+impl AmqpBroker {
+    /// Connect and subscribe, declaring both queues (durable) if missing
+    pub async fn connect(options: &QueueOptions) -> Result<Self, String> {
+        let connection = Connection::connect(&options.url, ConnectionProperties::default()).await
+            .map_err(|e| format!("Can't connect to the AMQP broker: {}", e))?;
+        let channel = connection.create_channel().await.map_err(amqp_error)?;
+
+        // Don't take more tasks than we work on at once, so others can
+        let prefetch = options.concurrency.clamp(1, u16::MAX as usize) as u16;
+        channel.basic_qos(prefetch, BasicQosOptions::default()).await.map_err(amqp_error)?;
+        for queue in [&options.tasks, &options.results] {
+            let durable = QueueDeclareOptions { durable: true, ..Default::default() };
+            channel.queue_declare(queue, durable, FieldTable::default()).await.map_err(amqp_error)?;
+        }
+
+        let consumer = channel.basic_consume(&options.tasks, "dubdub", BasicConsumeOptions::default(), FieldTable::default()).await
+            .map_err(amqp_error)?;
+        log::info!("Consuming AMQP queue '{}', results to '{}'", options.tasks, options.results);
+
+        Ok(AmqpBroker {
+            _connection: connection,
+            channel,
+            consumer: Mutex::new(consumer),
+            results: options.results.clone(),
+        })
+    }
+}
+
+impl Broker for AmqpBroker {
+    type Delivery = Delivery;
+
+    async fn receive(&self) -> Result<Option<Received<Delivery>>, String> {
+        let delivery = self.consumer.lock().await.next().await.transpose().map_err(amqp_error)?;
+        Ok(delivery.map(|mut delivery| Received {
+            body: std::mem::take(&mut delivery.data),
+            delivery,
+        }))
+    }
+
+    async fn reply(&self, delivery: Delivery, result: Vec<u8>) -> Result<(), String> {
+        let routing_key = delivery.properties.reply_to().as_ref().map(|queue| queue.as_str()).unwrap_or(&self.results);
+        let mut properties = BasicProperties::default().with_content_type("application/json".into());
+        if let Some(correlation_id) = delivery.properties.correlation_id() {
+            properties = properties.with_correlation_id(correlation_id.clone());
+        }
+
+        let published = async {
+            self.channel.basic_publish("", routing_key, BasicPublishOptions::default(), &result, properties).await?.await
+        }.await;
+        if let Err(e) = published {
+            let requeue = !delivery.redelivered;
+            if let Err(nack) = delivery.nack(BasicNackOptions { requeue, ..Default::default() }).await {
+                log::warn!("Can't return the task to the queue: {}", amqp_error(nack));
+            }
+            return Err(amqp_error(e));
+        }
+        delivery.ack(BasicAckOptions::default()).await.map_err(amqp_error)
+    }
+}
+
+fn amqp_error(e: lapin::Error) -> String {
+    format!("AMQP error: {}", e)
+}
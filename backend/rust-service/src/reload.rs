@@ -0,0 +1,210 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::Instant;
+
+use crate::models::{DataFile, DataReload, FailedDataFile, ReloadedData};
+use crate::{content, dictionary, frequency, resources};
+
+/// Held while reloading, so overlapping reloads (SIGHUP during an admin
+/// request) run one after the other instead of reading every file twice
+static RELOADING: Mutex<()> = Mutex::new(());
+
+/// Reloads finished since startup
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// How many times data files have been reloaded, so anything derived from
+/// them (ETags) can tell old data from new
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::Relaxed)
+}
+
+/// Data read from a directory at startup and swapped whole on reload
+///
+/// Readers take an `Arc` of the current data, so a request that started
+/// before a reload finishes with the data it started with, and the old data
+/// is freed once the last of them is done.
+pub struct Reloadable<T> {
+    dir: OnceLock<PathBuf>,
+    current: RwLock<Option<Arc<T>>>,
+}
+
+impl<T: Default> Reloadable<T> {
+    pub const fn new() -> Self {
+        Reloadable { dir: OnceLock::new(), current: RwLock::new(None) }
+    }
+
+    /// Load from `dir`, remembering it for reloads; false if already
+    /// initialised
+    pub fn init(&self, dir: &Path, load: impl FnOnce(&Path) -> T) -> bool {
+        if self.dir.set(dir.to_path_buf()).is_err() {
+            return false;
+        }
+        *self.current.write().unwrap() = Some(Arc::new(load(dir)));
+        true
+    }
+
+    /// Whether `init` has run
+    pub fn is_loaded(&self) -> bool {
+        self.current.read().unwrap().is_some()
+    }
+
+    /// The current data, or empty data if `init` was never called
+    pub fn get(&self) -> Arc<T> {
+        self.current.read().unwrap().clone().unwrap_or_default()
+    }
+
+    /// Load again from the `init` directory, given the current data, and
+    /// swap the result in, or `None` if there's no directory to load from
+    ///
+    /// Loading happens before the swap, so requests keep using the old data
+    /// until the new data is complete.
+    pub fn reload(&self, load: impl FnOnce(&Path, &T) -> T) -> Option<Arc<T>> {
+        let dir = self.dir.get()?;
+        let loaded = Arc::new(load(dir, &self.get()));
+        *self.current.write().unwrap() = Some(loaded.clone());
+        Some(loaded)
+    }
+
+    /// Swap in `change` applied to the current data, or `None` if `init`
+    /// never ran
+    ///
+    /// For trimming loaded data (evicting a language) without a trip to
+    /// disk; as with `reload`, readers keep the data they already hold.
+    pub fn update(&self, change: impl FnOnce(&T) -> T) -> Option<Arc<T>> {
+        let mut current = self.current.write().unwrap();
+        let changed = Arc::new(change(current.as_ref()?));
+        *current = Some(changed.clone());
+        Some(changed)
+    }
+}
+
+/// Languages with a file that failed to load
+pub fn failed_languages(failed: &[FailedDataFile]) -> BTreeSet<String> {
+    failed.iter().map(|file| file.language.clone()).collect()
+}
+
+/// Put `language`'s entry from `previous` back in `loaded`, or take it out
+/// if there was none
+pub fn restore<T>(loaded: &mut HashMap<String, Arc<T>>, previous: &HashMap<String, Arc<T>>, language: &str) {
+    match previous.get(language) {
+        Some(data) => loaded.insert(language.to_string(), data.clone()),
+        None => loaded.remove(language),
+    };
+}
+
+/// Replace `language`'s files in `loaded` with those in `previous`
+pub fn restore_files(loaded: &mut Vec<DataFile>, previous: &[DataFile], language: &str) {
+    loaded.retain(|file| file.language != language);
+    loaded.extend(previous.iter().filter(|file| file.language == language).cloned());
+    loaded.sort_by(|a, b| (&a.language, &a.file).cmp(&(&b.language, &b.file)));
+}
+
+impl<T: Default> Default for Reloadable<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Read dictionaries, frequency, stopword and abbreviation lists, and
+/// content lists from disk again, for `/admin/reload` and SIGHUP
+///
+/// Files that fail to load are logged and reported, and their languages
+/// keep the data they had, so a bad file doesn't take a language's good
+/// data with it. Evicted languages stay evicted until they're next used.
+/// Blocking: call from a blocking context.
+pub fn reload() -> DataReload {
+    let _reloading = RELOADING.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let started = Instant::now();
+    let wanted = |language: &str| !resources::is_evicted(language);
+
+    let mut reloaded = Vec::new();
+    if let Some(lists) = frequency::reload(wanted) {
+        reloaded.push(ReloadedData { name: "frequency_lists".to_string(), languages: lists.len(), files: lists.files().to_vec(), failed: lists.failed().to_vec() });
+    }
+    if let Some(dictionaries) = dictionary::reload(wanted) {
+        reloaded.push(ReloadedData { name: "dictionaries".to_string(), languages: dictionaries.len(), files: dictionaries.files().to_vec(), failed: dictionaries.failed().to_vec() });
+    }
+    if let Some(lists) = content::reload(wanted) {
+        reloaded.push(ReloadedData { name: "content_lists".to_string(), languages: lists.len(), files: lists.files().to_vec(), failed: lists.failed().to_vec() });
+    }
+
+    GENERATION.fetch_add(1, Ordering::Relaxed);
+    let elapsed_seconds = started.elapsed().as_secs_f64();
+    log::info!("Reloaded {} in {:.3}s",
+        reloaded.iter().map(|data| format!("{} ({} files, {} failed)", data.name, data.files.len(), data.failed.len())).collect::<Vec<_>>().join(", "),
+        elapsed_seconds);
+    DataReload { reloaded, elapsed_seconds }
+}
+
+/// Run `change` on loaded data with reloads held off, then retire ETags as
+/// a reload would, for changes short of a full reload (`resources::evict`)
+pub fn exclusively<R>(change: impl FnOnce() -> R) -> R {
+    let _reloading = RELOADING.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let changed = change();
+    GENERATION.fetch_add(1, Ordering::Relaxed);
+    changed
+}
+
+/// Run `change` on loaded data with reloads held off, leaving ETags alone,
+/// for changes that put back what's on disk (`resources::restore`)
+pub fn serialized<R>(change: impl FnOnce() -> R) -> R {
+    let _reloading = RELOADING.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    change()
+}
+
+/// Reload data files each time the process gets SIGHUP
+#[cfg(unix)]
+pub async fn reload_on_hangup() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            log::warn!("Can't listen for SIGHUP, data files only reload through /admin/reload: {}", e);
+            return;
+        }
+    };
+    while hangup.recv().await.is_some() {
+        log::info!("SIGHUP received, reloading data files");
+        if let Err(e) = tokio::task::spawn_blocking(reload).await {
+            log::error!("Reloading data files failed: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reload_swaps_data() {
+        let data: Reloadable<Vec<String>> = Reloadable::new();
+        assert!(!data.is_loaded());
+        assert!(data.get().is_empty());
+        assert!(data.reload(|_, _| vec!["never".to_string()]).is_none());
+
+        assert!(data.init(Path::new("/data"), |dir| vec![dir.display().to_string()]));
+        assert!(!data.init(Path::new("/other"), |_| Vec::new()));
+
+        // A reader holding the old data keeps it through the reload
+        let before = data.get();
+        data.reload(|dir, current| current.iter().cloned().chain([dir.display().to_string()]).collect()).unwrap();
+        assert_eq!(*before, vec!["/data"]);
+        assert_eq!(*data.get(), vec!["/data", "/data"]);
+    }
+
+    #[test]
+    fn test_update() {
+        let data: Reloadable<Vec<String>> = Reloadable::new();
+        assert!(data.update(|_| vec!["never".to_string()]).is_none());
+        assert!(!data.is_loaded());
+
+        data.init(Path::new("/data"), |_| vec!["en".to_string(), "es".to_string()]);
+        let before = data.get();
+        data.update(|languages| languages.iter().filter(|language| *language != "es").cloned().collect()).unwrap();
+        assert_eq!(*before, vec!["en", "es"]);
+        assert_eq!(*data.get(), vec!["en"]);
+    }
+}
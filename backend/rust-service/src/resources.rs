@@ -0,0 +1,283 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::mem::size_of;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::auth::Client;
+use crate::error::ApiError;
+use crate::models::{EvictedLanguages, LanguageEviction, LanguageResources, LanguageResourcesReport, ResidentData};
+use crate::{cache, content, dictionary, duration, frequency, reload};
+
+/// When use started being tracked (see `init`)
+static TRACKING_SINCE: LazyLock<u64> = LazyLock::new(now);
+
+/// Unix seconds of the latest request in each language, by lowercase
+/// primary code, so clients can't grow it with made-up regional variants
+///
+/// Each time is an atomic behind a read lock, so requests only contend for
+/// the write lock the first time their language is seen.
+static LAST_USED: LazyLock<RwLock<HashMap<String, AtomicU64>>> = LazyLock::new(Default::default);
+
+/// Languages whose data was evicted and hasn't been read back yet
+static EVICTED: LazyLock<RwLock<HashSet<String>>> = LazyLock::new(Default::default);
+
+/// Rough heap bytes owned by a value, for telling what a language costs
+///
+/// Counts what lengths and capacities say is allocated, not allocator
+/// overhead, so it's an estimate to compare languages by rather than an
+/// exact figure.
+pub trait HeapSize {
+    fn heap_size(&self) -> usize;
+}
+
+impl HeapSize for usize {
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+impl HeapSize for String {
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Option<T> {
+    fn heap_size(&self) -> usize {
+        self.as_ref().map_or(0, HeapSize::heap_size)
+    }
+}
+
+impl<T: HeapSize> HeapSize for Vec<T> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * size_of::<T>() + self.iter().map(HeapSize::heap_size).sum::<usize>()
+    }
+}
+
+impl<T: HeapSize> HeapSize for HashSet<T> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * (size_of::<T>() + 1) + self.iter().map(HeapSize::heap_size).sum::<usize>()
+    }
+}
+
+impl<K: HeapSize, V: HeapSize> HeapSize for HashMap<K, V> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * (size_of::<K>() + size_of::<V>() + 1)
+            + self.iter().map(|(key, value)| key.heap_size() + value.heap_size()).sum::<usize>()
+    }
+}
+
+/// Start tracking language use, so languages never requested count as idle
+/// from startup rather than forever
+pub fn init() {
+    LazyLock::force(&TRACKING_SINCE);
+}
+
+/// Note a request in `language`, for `last_used`
+pub fn touch(language: &str) {
+    let language = primary(language);
+    let now = now();
+    let seen = LAST_USED.read().unwrap().get(&language).map(|at| at.store(now, Ordering::Relaxed)).is_some();
+    if !seen {
+        LAST_USED.write().unwrap().entry(language).or_default().store(now, Ordering::Relaxed);
+    }
+}
+
+/// Latest request using `language`'s data: in the language itself or in one
+/// of its regional variants (`pt-BR` uses `pt`'s lists)
+///
+/// Use is tracked per primary code, so `pt-BR`'s data counts as used by
+/// any request in Portuguese.
+pub fn last_used(language: &str) -> Option<u64> {
+    LAST_USED.read().unwrap().get(&primary(language)).map(|at| at.load(Ordering::Relaxed))
+}
+
+/// Lowercase primary subtag of a language code
+fn primary(language: &str) -> String {
+    language.split(['-', '_']).next().unwrap_or_default().to_lowercase()
+}
+
+/// Whether `language`'s data was evicted and not read back since
+pub fn is_evicted(language: &str) -> bool {
+    EVICTED.read().unwrap().contains(language)
+}
+
+/// Read back the evicted data requests in `languages` use, on a blocking
+/// thread, before a handler reads it
+///
+/// Returns at once when nothing they use is evicted.
+pub async fn restore(languages: impl IntoIterator<Item = String>) {
+    let evicted: BTreeSet<String> = {
+        let evicted = EVICTED.read().unwrap();
+        if evicted.is_empty() {
+            return;
+        }
+        languages.into_iter()
+            .flat_map(|language| evicted.iter().filter(|code| uses(&language, code)).cloned().collect::<Vec<_>>())
+            .collect()
+    };
+    if evicted.is_empty() {
+        return;
+    }
+
+    if let Err(e) = tokio::task::spawn_blocking(move || restore_now(evicted)).await {
+        log::error!("Reading evicted data back failed: {}", e);
+    }
+}
+
+/// Read back the data of the `evicted` languages
+///
+/// Waits for any reload or eviction in progress, and requests that arrive
+/// meanwhile wait for this one, so none of them is served without the data.
+/// Blocking: call from a blocking context.
+fn restore_now(evicted: BTreeSet<String>) {
+    reload::serialized(|| {
+        for code in evicted {
+            // Another request may have read it back while this one waited
+            if !is_evicted(&code) {
+                continue;
+            }
+            dictionary::restore(&code);
+            frequency::restore(&code);
+            content::restore(&code);
+            EVICTED.write().unwrap().remove(&code);
+            log::info!("Read '{}' data back on its first use since it was evicted", code);
+        }
+    });
+}
+
+/// Whether a request in `code` reads `language`'s data
+fn uses(code: &str, language: &str) -> bool {
+    let code = code.to_lowercase();
+    code == language || code.split(['-', '_']).next() == Some(language)
+}
+
+/// Every language with data in memory, what it holds and when it was last used
+///
+/// Walks all loaded data to size it: call from a blocking context.
+pub fn report() -> LanguageResourcesReport {
+    let mut resident: BTreeMap<String, Vec<ResidentData>> = BTreeMap::new();
+    let loaded = dictionary::dictionaries().resident().into_iter()
+        .chain(frequency::lists().resident())
+        .chain(content::lists().resident());
+    for (language, data) in loaded {
+        resident.entry(language).or_default().push(data);
+    }
+
+    let cached = cache::languages();
+    let languages: Vec<LanguageResources> = resident.into_iter()
+        .map(|(language, data)| LanguageResources {
+            approx_bytes: data.iter().map(|data| data.approx_bytes).sum(),
+            duration_model: duration::models().contains(&language),
+            cached_alignments: cached.iter().filter(|(code, _)| uses(code, &language)).map(|(_, count)| count).sum(),
+            last_used: last_used(&language),
+            language,
+            data,
+        })
+        .collect();
+
+    LanguageResourcesReport {
+        approx_bytes: languages.iter().map(|language| language.approx_bytes).sum(),
+        languages,
+        cache: cache::stats(),
+        tracking_since: *TRACKING_SINCE,
+    }
+}
+
+/// Drop the data and cached alignments of the languages `eviction` picks,
+/// until a request uses them again (see `restore`)
+///
+/// Reloads (`/admin/reload` or SIGHUP) leave evicted languages out, so
+/// they only come back when they're wanted. Blocking: call from a blocking
+/// context.
+pub fn evict(eviction: &LanguageEviction, admin: &Client) -> Result<EvictedLanguages, ApiError> {
+    if eviction.languages.is_empty() && eviction.idle_seconds.is_none() {
+        return Err(ApiError::invalid_input("Give languages to evict, idle_seconds, or both").with_field("languages"));
+    }
+    let listed: Vec<String> = eviction.languages.iter().map(|language| language.trim().to_lowercase()).collect();
+    let now = now();
+
+    reload::exclusively(|| {
+        let picked: Vec<LanguageResources> = report().languages.into_iter()
+            .filter(|resources| listed.is_empty() || listed.contains(&resources.language))
+            .filter(|resources| eviction.idle_seconds.is_none_or(|idle| {
+                is_idle(resources.last_used.unwrap_or(*TRACKING_SINCE), idle, now)
+            }))
+            .collect();
+
+        let mut evicted = EvictedLanguages { evicted: Vec::new(), approx_bytes: 0, cached_alignments: 0 };
+        for resources in picked {
+            dictionary::evict(&resources.language);
+            frequency::evict(&resources.language);
+            content::evict(&resources.language);
+            evicted.cached_alignments += cache::forget(|code| uses(code, &resources.language));
+            evicted.approx_bytes += resources.approx_bytes;
+            EVICTED.write().unwrap().insert(resources.language.clone());
+            evicted.evicted.push(resources.language);
+        }
+
+        if !evicted.evicted.is_empty() {
+            log::warn!("{} evicted {} (about {} bytes, {} cached alignments) until they're next used",
+                admin, evicted.evicted.join(", "), evicted.approx_bytes, evicted.cached_alignments);
+        }
+        Ok(evicted)
+    })
+}
+
+/// Whether data last used at `last_used` has gone `idle_seconds` without a request
+fn is_idle(last_used: u64, idle_seconds: u64, now: u64) -> bool {
+    now.saturating_sub(last_used) >= idle_seconds
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heap_size() {
+        let words: Vec<String> = vec!["hola".to_string(), "adiós".to_string()];
+        assert_eq!(words.heap_size(), words.capacity() * size_of::<String>() + 4 + "adiós".len());
+
+        let mut ranks: HashMap<String, usize> = HashMap::new();
+        assert_eq!(ranks.heap_size(), 0);
+        ranks.insert("hola".to_string(), 1);
+        assert!(ranks.heap_size() > 4);
+        assert_eq!(None::<String>.heap_size(), 0);
+    }
+
+    #[test]
+    fn test_last_used() {
+        touch("xx-YY");
+        let used = last_used("xx").unwrap();
+        assert!(used >= *TRACKING_SINCE);
+        assert_eq!(last_used("xx-yy"), Some(used));
+        assert_eq!(last_used("x"), None);
+        assert_eq!(last_used("zz"), None);
+
+        // Made-up variants share their language's entry
+        touch("xx-a1");
+        touch("xx_A2");
+        assert!(LAST_USED.read().unwrap().keys().all(|code| !code.starts_with("xx-") && !code.starts_with("xx_")));
+    }
+
+    #[test]
+    fn test_is_idle() {
+        assert!(is_idle(100, 60, 160));
+        assert!(!is_idle(100, 60, 159));
+        assert!(is_idle(100, 0, 100));
+        // A clock that went backwards isn't idle
+        assert!(!is_idle(200, 60, 100));
+    }
+
+    #[test]
+    fn test_evict_needs_a_selection() {
+        let admin = Client { name: "admin".to_string(), tenant: None };
+        let error = evict(&LanguageEviction::default(), &admin).unwrap_err();
+        assert_eq!(error.field.as_deref(), Some("languages"));
+    }
+}
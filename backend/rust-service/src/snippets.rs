@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+use std::io::{Seek, Write};
+
+use futures::StreamExt;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::audio::AudioBuffer;
+use crate::error::{ApiError, ErrorCode};
+use crate::models::{AudioSnippet, AudioSnippetResponse, WordTiming};
+use crate::storage;
+use crate::tokenizer;
+
+/// Silence kept around each clip (seconds), so it doesn't start or end
+/// mid-sound
+pub const DEFAULT_PADDING: f64 = 0.1;
+
+/// Most clips cut from one request
+pub const MAX_SNIPPETS: usize = 1000;
+
+/// Longest clip cut (seconds, padding included): a word or phrase, not a scene
+pub const MAX_SNIPPET_SECONDS: f64 = 10.0;
+
+/// Most audio cut from one request, all clips together (seconds)
+pub const MAX_TOTAL_SNIPPET_SECONDS: f64 = 600.0;
+
+/// Clips uploaded to object storage at once
+const STORE_CONCURRENCY: usize = 8;
+
+/// Longest name part of a clip's file name
+const MAX_NAME_CHARS: usize = 40;
+
+/// Pick the span of audio to cut for each word, or for each phrase if any
+/// are given, and the phrases the timings don't contain
+///
+/// # How it works:
+/// 1. Compare words case-insensitively and without surrounding punctuation,
+///    so "Hello," in the timings matches "hello"
+/// 2. Without phrases, every distinct word is a snippet; with them, each
+///    phrase is looked for as a run of consecutive timed words
+/// 3. Where a word or phrase appears more than once, cut the occurrence
+///    aligned most confidently, widened by `padding` on each side
+pub fn select(timings: &[WordTiming], phrases: &[String], language: &str, padding: f64) -> (Vec<AudioSnippet>, Vec<String>) {
+    // Step 1: Comparable words
+    let words: Vec<String> = timings.iter().map(|timing| normalize(&timing.word)).collect();
+
+    // Step 2: Occurrences, as ranges of timings
+    let mut found: Vec<(String, Vec<(usize, usize)>)> = Vec::new();
+    let mut missing = Vec::new();
+    if phrases.is_empty() {
+        let mut seen: HashMap<&str, usize> = HashMap::new();
+        for (at, word) in words.iter().enumerate().filter(|(_, word)| !word.is_empty()) {
+            let slot = *seen.entry(word).or_insert_with(|| {
+                found.push((timings[at].word.clone(), Vec::new()));
+                found.len() - 1
+            });
+            found[slot].1.push((at, at + 1));
+        }
+    } else {
+        for phrase in phrases {
+            let wanted: Vec<String> = tokenizer::tokens(phrase, language).iter()
+                .map(|token| normalize(token.text))
+                .filter(|word| !word.is_empty())
+                .collect();
+            let occurrences: Vec<(usize, usize)> = match wanted.len() {
+                0 => Vec::new(),
+                n => words.windows(n)
+                    .enumerate()
+                    .filter(|(_, window)| *window == wanted.as_slice())
+                    .map(|(at, _)| (at, at + n))
+                    .collect(),
+            };
+            match occurrences.first() {
+                Some(&(from, to)) => {
+                    let text = timings[from..to].iter().map(|timing| timing.word.as_str()).collect::<Vec<_>>().join(" ");
+                    found.push((text, occurrences));
+                }
+                None => missing.push(phrase.clone()),
+            }
+        }
+    }
+
+    // Step 3: Best occurrence of each
+    let confidence = |(from, to): (usize, usize)| timings[from..to].iter().map(|timing| timing.confidence).sum::<f64>() / (to - from) as f64;
+    let snippets = found.into_iter()
+        .enumerate()
+        .map(|(n, (text, occurrences))| {
+            let best = occurrences.iter().copied()
+                .max_by(|a, b| confidence(*a).total_cmp(&confidence(*b)).then(b.0.cmp(&a.0)))
+                .unwrap_or_default();
+            AudioSnippet {
+                file: file_name(n + 1, &text),
+                start: (timings[best.0].start - padding).max(0.0),
+                end: timings[best.1 - 1].end + padding,
+                confidence: confidence(best),
+                occurrences: occurrences.len(),
+                text,
+                stored: None,
+            }
+        })
+        .collect();
+
+    (snippets, missing)
+}
+
+/// Refuse snippets longer than `MAX_SNIPPET_SECONDS`, or adding up to more
+/// than `MAX_TOTAL_SNIPPET_SECONDS`, before any audio is decoded
+pub fn check_lengths(snippets: &[AudioSnippet]) -> Result<(), ApiError> {
+    if let Some(long) = snippets.iter().find(|snippet| snippet.end - snippet.start > MAX_SNIPPET_SECONDS) {
+        return Err(ApiError::new(ErrorCode::PayloadTooLarge, format!("Snippet '{}' would be {:.1}s long, at most {}s are cut",
+            long.text, long.end - long.start, MAX_SNIPPET_SECONDS)).with_field("timings"));
+    }
+    let total: f64 = snippets.iter().map(|snippet| snippet.end - snippet.start).sum();
+    if total > MAX_TOTAL_SNIPPET_SECONDS {
+        return Err(ApiError::new(ErrorCode::PayloadTooLarge, format!("Snippets add up to {:.0}s of audio, at most {}s are cut at once; send phrases to choose",
+            total, MAX_TOTAL_SNIPPET_SECONDS)).with_field("timings"));
+    }
+    Ok(())
+}
+
+/// Pack each clip as the WAV file its snippet names, with the response as
+/// `snippets.json`, into `out`
+pub fn bundle_zip<W: Write + Seek>(response: &AudioSnippetResponse, clips: &[AudioBuffer], out: W) -> Result<W, ApiError> {
+    let mut writer = ZipWriter::new(out);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    let manifest = serde_json::to_vec_pretty(response).unwrap_or_default();
+    let files = response.snippets.iter().zip(clips)
+        .map(|(snippet, clip)| (snippet.file.as_str(), clip.to_wav()))
+        .chain([("snippets.json", manifest)]);
+    for (name, body) in files {
+        writer.start_file(name, options)
+            .and_then(|_| writer.write_all(&body).map_err(Into::into))
+            .map_err(|e| ApiError::internal(format!("Failed to write bundle: {}", e)))?;
+    }
+
+    writer.finish().map_err(|e| ApiError::internal(format!("Failed to write bundle: {}", e)))
+}
+
+/// Write each clip to object storage as a WAV file, linking it from its
+/// snippet
+pub async fn store(response: &mut AudioSnippetResponse, clips: Vec<AudioBuffer>) -> Result<(), ApiError> {
+    let stored: Vec<_> = futures::stream::iter(response.snippets.iter().zip(clips))
+        .map(|(snippet, clip)| storage::put(&snippet.file, "audio/wav", clip.to_wav()))
+        .buffered(STORE_CONCURRENCY)
+        .collect()
+        .await;
+    for (snippet, stored) in response.snippets.iter_mut().zip(stored) {
+        snippet.stored = Some(stored?);
+    }
+    Ok(())
+}
+
+/// Lowercased, with curly apostrophes straightened and punctuation trimmed
+fn normalize(word: &str) -> String {
+    word.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase().replace('’', "'")
+}
+
+/// "003-buenos_dias.wav": numbered so names stay unique and in order
+fn file_name(n: usize, text: &str) -> String {
+    let name: String = text.chars()
+        .filter_map(|c| match c {
+            c if c.is_alphanumeric() => Some(c.to_lowercase().next().unwrap_or(c)),
+            c if c.is_whitespace() || c == '-' => Some('_'),
+            _ => None,
+        })
+        .take(MAX_NAME_CHARS)
+        .collect();
+    format!("{:03}-{}.wav", n, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn timing(word: &str, start: f64, confidence: f64) -> WordTiming {
+        WordTiming {
+            word: word.to_string(),
+            start,
+            end: start + 0.5,
+            confidence,
+            char_start: 0,
+            char_end: 0,
+            flagged: false,
+        }
+    }
+
+    fn timings() -> Vec<WordTiming> {
+        vec![
+            timing("Buenos", 1.0, 0.6),
+            timing("días,", 1.5, 0.7),
+            timing("buenos", 3.0, 0.9),
+            timing("días", 3.5, 0.9),
+            timing("—", 4.0, 0.2),
+        ]
+    }
+
+    #[test]
+    fn test_every_word() {
+        let (snippets, missing) = select(&timings(), &[], "es", 0.1);
+        assert!(missing.is_empty());
+
+        let words: Vec<(&str, usize)> = snippets.iter().map(|snippet| (snippet.text.as_str(), snippet.occurrences)).collect();
+        assert_eq!(words, vec![("Buenos", 2), ("días,", 2)]);
+        // The second, more confident "buenos"
+        assert!((snippets[0].start - 2.9).abs() < 1e-9);
+        assert!((snippets[0].end - 3.6).abs() < 1e-9);
+        assert_eq!(snippets[1].file, "002-días.wav");
+    }
+
+    #[test]
+    fn test_phrases() {
+        let phrases = vec!["Buenos días".to_string(), "hasta luego".to_string()];
+        let (snippets, missing) = select(&timings(), &phrases, "es", 0.0);
+
+        assert_eq!(snippets.len(), 1);
+        assert_eq!(snippets[0].text, "Buenos días,");
+        assert_eq!(snippets[0].file, "001-buenos_días.wav");
+        assert_eq!((snippets[0].start, snippets[0].end), (3.0, 4.0));
+        assert!((snippets[0].confidence - 0.9).abs() < 1e-9);
+        assert_eq!(missing, vec!["hasta luego"]);
+    }
+
+    #[test]
+    fn test_bundle_zip() {
+        let (snippets, missing) = select(&timings(), &[], "es", 0.1);
+        let clips = vec![AudioBuffer { samples: vec![0.0; 160], sample_rate: 16_000 }; snippets.len()];
+        let response = AudioSnippetResponse { sample_rate: 16_000, snippets, missing };
+
+        let bytes = bundle_zip(&response, &clips, Cursor::new(Vec::new())).unwrap();
+        let archive = zip::ZipArchive::new(bytes).unwrap();
+        let names: Vec<&str> = archive.file_names().collect();
+        assert_eq!(archive.len(), 3);
+        assert!(names.contains(&"001-buenos.wav"));
+        assert!(names.contains(&"snippets.json"));
+    }
+
+    #[test]
+    fn test_length_caps() {
+        let (snippets, _) = select(&timings(), &[], "es", 0.1);
+        assert!(check_lengths(&snippets).is_ok());
+
+        let (long, _) = select(&timings(), &[], "es", MAX_SNIPPET_SECONDS);
+        assert_eq!(check_lengths(&long).unwrap_err().code, ErrorCode::PayloadTooLarge);
+
+        // A thousand distinct words of 0.7s each
+        let words: Vec<WordTiming> = (0..MAX_SNIPPETS).map(|i| timing(&format!("word{}", i), i as f64, 0.9)).collect();
+        let (many, _) = select(&words, &[], "es", 0.1);
+        assert_eq!(check_lengths(&many).unwrap_err().code, ErrorCode::PayloadTooLarge);
+    }
+}
@@ -0,0 +1,96 @@
+use crate::audio::EnergyEnvelope;
+use crate::models::{Cue, CueSpeechRate, SpeechRateResponse};
+use crate::syllables;
+use crate::tokenizer;
+
+/// Syllables per second learners are assumed comfortable with, unless
+/// they choose: a little under the 4-6 of everyday conversation
+pub const DEFAULT_COMFORTABLE_RATE: f64 = 4.0;
+
+/// Speech shorter than this (seconds) is too little to measure a rate on
+const MIN_SPEECH_SECONDS: f64 = 0.2;
+
+/// Measure how fast each cue is spoken in the audio
+///
+/// # How it works:
+/// 1. Count each cue's syllables (hyphenation patterns or vowel groups,
+///    as `/syllabify` does)
+/// 2. Time the speech in the cue's window from the audio's loudness:
+///    frames well above the file's noise floor count, pauses don't
+/// 3. Divide, and flag cues faster than the comfortable rate
+///
+/// The rate is per second of speech rather than of the cue, so a quick
+/// line followed by a long pause is still flagged.
+pub fn measure(cues: &[Cue], language: &str, envelope: &EnergyEnvelope, comfortable_rate: f64) -> SpeechRateResponse {
+    let threshold = envelope.speech_threshold();
+
+    let cues: Vec<CueSpeechRate> = cues.iter()
+        .map(|cue| {
+            // Step 1: Syllables
+            let syllables = count_syllables(&cue.text, language);
+
+            // Step 2: Speech time
+            let speech_seconds = envelope.speech_seconds(cue.start, cue.end, threshold);
+
+            // Step 3: Rate
+            let rate = (speech_seconds >= MIN_SPEECH_SECONDS && syllables > 0).then(|| syllables as f64 / speech_seconds);
+            let ratio = rate.map(|rate| rate / comfortable_rate);
+            CueSpeechRate {
+                index: cue.index,
+                start: cue.start,
+                end: cue.end,
+                syllables,
+                speech_seconds,
+                rate,
+                ratio,
+                too_fast: ratio.is_some_and(|ratio| ratio > 1.0),
+            }
+        })
+        .collect();
+
+    let rates: Vec<f64> = cues.iter().filter_map(|cue| cue.rate).collect();
+    SpeechRateResponse {
+        language: language.to_string(),
+        comfortable_rate,
+        mean_rate: (!rates.is_empty()).then(|| rates.iter().sum::<f64>() / rates.len() as f64),
+        n_too_fast: cues.iter().filter(|cue| cue.too_fast).count(),
+        cues,
+    }
+}
+
+/// Syllables in the words of `text`; numbers and symbols aren't counted
+pub fn count_syllables(text: &str, language: &str) -> usize {
+    tokenizer::tokens(text, language).into_iter()
+        .filter(|token| token.text.chars().any(char::is_alphabetic))
+        .map(|token| syllables::syllabify(token.text, language).len())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::cue;
+
+    #[test]
+    fn test_measure() {
+        // 1s of silence, then 2s of speech, then 1s of silence
+        let levels = [vec![-80.0; 50], vec![-20.0; 100], vec![-80.0; 50]].concat();
+        let envelope = EnergyEnvelope { levels };
+        let cues = vec![
+            cue(1, 1.0, 2.0, "banana banana"),
+            cue(2, 2.0, 4.0, "hi"),
+            cue(3, 3.0, 4.0, "quiet now"),
+        ];
+
+        let response = measure(&cues, "xx", &envelope, 4.0);
+        assert_eq!(response.cues[0].syllables, 6);
+        assert!((response.cues[0].rate.unwrap() - 6.0).abs() < 1e-9);
+        assert!(response.cues[0].too_fast);
+        // One syllable over a second of speech, though the cue lasts two
+        assert!((response.cues[1].rate.unwrap() - 1.0).abs() < 1e-9);
+        assert!(!response.cues[1].too_fast);
+        assert_eq!(response.cues[2].rate, None);
+        assert_eq!(response.n_too_fast, 1);
+        assert!((response.mean_rate.unwrap() - 3.5).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,191 @@
+use std::fs::File;
+use std::io;
+use std::sync::OnceLock;
+
+use actix_web::body::SizedStream;
+use actix_web::web::Bytes;
+use futures::Stream;
+use tempfile::NamedTempFile;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::error::{ApiError, ErrorCode};
+
+/// Bytes of one upload held in memory, unless configured
+pub const DEFAULT_UPLOAD_MEMORY_BUDGET: usize = 16 * 1024 * 1024;
+
+static BUDGET: OnceLock<usize> = OnceLock::new();
+
+/// Keep up to `budget` bytes of each large upload in memory
+/// (`UPLOAD_MEMORY_BUDGET`); the rest goes to a temporary file
+pub fn init(budget: usize) {
+    log::info!("Uploads over {} bytes are spooled to {}", budget, std::env::temp_dir().display());
+    if BUDGET.set(budget).is_err() {
+        log::warn!("Upload memory budget already initialised");
+    }
+}
+
+pub fn budget() -> usize {
+    *BUDGET.get_or_init(|| DEFAULT_UPLOAD_MEMORY_BUDGET)
+}
+
+/// A large body being received chunk by chunk
+///
+/// Held in memory until it outgrows the budget, then moved to a temporary
+/// file (deleted when dropped), so a whole movie's audio doesn't have to fit
+/// in a small container's memory. File writes go through `tokio::fs`, off
+/// the async workers.
+pub struct Spool {
+    budget: usize,
+    /// At most `limit` bytes are accepted
+    limit: usize,
+    len: usize,
+    memory: Vec<u8>,
+    /// The temporary file, and a handle to write to it without blocking
+    file: Option<(NamedTempFile, tokio::fs::File)>,
+}
+
+/// Where a finished spool's bytes ended up
+pub enum Spooled {
+    Memory(Vec<u8>),
+    File(NamedTempFile),
+}
+
+impl Spool {
+    /// An empty spool with the configured budget, refusing more than `limit` bytes
+    pub fn new(limit: usize) -> Self {
+        Spool::with_budget(budget(), limit)
+    }
+
+    pub fn with_budget(budget: usize, limit: usize) -> Self {
+        Spool { budget, limit, len: 0, memory: Vec::new(), file: None }
+    }
+
+    pub async fn write(&mut self, chunk: &[u8]) -> Result<(), ApiError> {
+        if self.len + chunk.len() > self.limit {
+            return Err(ApiError::new(ErrorCode::PayloadTooLarge, format!("Larger than {} bytes", self.limit)));
+        }
+        self.len += chunk.len();
+
+        if self.file.is_none() && self.memory.len() + chunk.len() > self.budget {
+            let (file, writer) = tokio::task::spawn_blocking(|| {
+                let file = NamedTempFile::with_prefix("dubdub-upload-")?;
+                let writer = file.as_file().try_clone()?;
+                Ok::<_, io::Error>((file, writer))
+            }).await.map_err(|e| spool_error(e.into()))?.map_err(spool_error)?;
+
+            let mut writer = tokio::fs::File::from_std(writer);
+            writer.write_all(&self.memory).await.map_err(spool_error)?;
+            log::debug!("Spooling upload past {} bytes to {}", self.budget, file.path().display());
+            self.memory = Vec::new();
+            self.file = Some((file, writer));
+        }
+
+        match &mut self.file {
+            Some((_, writer)) => writer.write_all(chunk).await.map_err(spool_error),
+            None => {
+                self.memory.extend_from_slice(chunk);
+                Ok(())
+            }
+        }
+    }
+
+    /// An empty spool with the same budget and limit, e.g. to start a
+    /// download over
+    pub fn fresh(&self) -> Spool {
+        Spool::with_budget(self.budget, self.limit)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub async fn finish(self) -> Result<Spooled, ApiError> {
+        match self.file {
+            Some((file, mut writer)) => {
+                writer.flush().await.map_err(spool_error)?;
+                Ok(Spooled::File(file))
+            }
+            None => Ok(Spooled::Memory(self.memory)),
+        }
+    }
+}
+
+/// Chunks a temporary file is sent in
+const CHUNK_BYTES: usize = 64 * 1024;
+
+/// Build a large response body (a ZIP of audio clips) in a temporary file
+/// rather than in memory
+///
+/// Blocking: call from `web::block`.
+pub fn temp_file(write: impl FnOnce(&mut File) -> Result<(), ApiError>) -> Result<NamedTempFile, ApiError> {
+    let mut file = NamedTempFile::with_prefix("dubdub-body-").map_err(spool_error)?;
+    write(file.as_file_mut())?;
+    Ok(file)
+}
+
+/// Send a `temp_file` as a response body, read a chunk at a time; the file
+/// is deleted once the body is dropped
+pub async fn body(file: NamedTempFile) -> Result<SizedStream<impl Stream<Item = Result<Bytes, io::Error>>>, ApiError> {
+    let reader = tokio::fs::File::open(file.path()).await.map_err(spool_error)?;
+    let size = reader.metadata().await.map_err(spool_error)?.len();
+
+    let chunks = futures::stream::unfold(Some((reader, file)), |state| async move {
+        let (mut reader, file) = state?;
+        let mut chunk = vec![0; CHUNK_BYTES];
+        match reader.read(&mut chunk).await {
+            Ok(0) => None,
+            Ok(read) => {
+                chunk.truncate(read);
+                Some((Ok(Bytes::from(chunk)), Some((reader, file))))
+            }
+            Err(e) => Some((Err(e), None)),
+        }
+    });
+    Ok(SizedStream::new(size, Box::pin(chunks)))
+}
+
+fn spool_error(e: io::Error) -> ApiError {
+    ApiError::internal(format!("Failed to spool upload to disk: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_spills_to_disk_past_budget() {
+        let mut small = Spool::with_budget(8, 100);
+        small.write(b"hello").await.unwrap();
+        assert!(matches!(small.finish().await.unwrap(), Spooled::Memory(bytes) if bytes == b"hello"));
+
+        let mut large = Spool::with_budget(8, 100);
+        large.write(b"hello").await.unwrap();
+        large.write(b" world").await.unwrap();
+        assert_eq!(large.len(), 11);
+        match large.finish().await.unwrap() {
+            Spooled::File(file) => assert_eq!(std::fs::read(file.path()).unwrap(), b"hello world"),
+            Spooled::Memory(_) => panic!("expected a file"),
+        }
+
+        let mut limited = Spool::with_budget(8, 10);
+        assert_eq!(limited.write(b"hello world").await.unwrap_err().code, ErrorCode::PayloadTooLarge);
+    }
+
+    #[actix_web::test]
+    async fn test_temp_file_body() {
+        use std::io::Write;
+
+        let content = "0123456789".repeat(CHUNK_BYTES / 4);
+        let file = temp_file(|file| file.write_all(content.as_bytes()).map_err(spool_error)).unwrap();
+        let path = file.path().to_path_buf();
+
+        let response = actix_web::HttpResponse::Ok().body(body(file).await.unwrap());
+        let bytes = actix_web::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(bytes, content.as_bytes());
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,106 @@
+use std::convert::Infallible;
+use std::ops::ControlFlow;
+use std::time::Instant;
+
+use actix_web::web::Bytes;
+use futures::Stream;
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+use crate::aligner::align_file_with_progress;
+use crate::error::ErrorResponse;
+use crate::models::{AlignmentDone, AlignmentProgress, FileAlignmentRequest};
+
+/// Events buffered ahead of a slow client before alignment waits for it
+const CHANNEL_CAPACITY: usize = 64;
+
+/// Format one Server-Sent Event
+pub fn event(name: &str, data: &impl Serialize) -> Bytes {
+    let json = serde_json::to_string(data).unwrap_or_else(|e| format!("{{\"error\":\"{}\"}}", e));
+    Bytes::from(format!("event: {}\ndata: {}\n\n", name, json))
+}
+
+/// Align a file on a worker thread, streaming events as cues complete
+///
+/// Events, in order:
+/// - `cue` (a `CueAlignment`) followed by `progress` for every cue
+/// - `done` with the warnings once everything is aligned, or `error`
+///   (an `ErrorResponse`)
+///
+/// If the client disconnects, alignment stops at the next cue.
+pub fn align_file_events(req: FileAlignmentRequest) -> impl Stream<Item = Result<Bytes, Infallible>> {
+    let (sender, mut receiver) = mpsc::channel::<Bytes>(CHANNEL_CAPACITY);
+
+    // Keep the request's span, so the alignment shows up in its trace
+    let span = tracing::Span::current();
+    tokio::task::spawn_blocking(move || {
+        let _entered = span.enter();
+        let started = Instant::now();
+
+        let result = align_file_with_progress(&req, |cue, processed, total| {
+            let elapsed = started.elapsed().as_secs_f64();
+            let progress = AlignmentProgress {
+                processed,
+                total,
+                elapsed_seconds: elapsed,
+                eta_seconds: elapsed / processed as f64 * (total - processed) as f64,
+            };
+            let sent = sender.blocking_send(event("cue", cue))
+                .and_then(|_| sender.blocking_send(event("progress", &progress)));
+
+            // The receiver is gone once the client disconnects
+            match sent {
+                Ok(()) => ControlFlow::Continue(()),
+                Err(_) => ControlFlow::Break(()),
+            }
+        });
+
+        let last = match result {
+            Ok(response) => event("done", &AlignmentDone {
+                language: response.language,
+                n_cues: response.cues.len(),
+                warnings: response.warnings,
+            }),
+            Err(error) => event("error", &ErrorResponse { error }),
+        };
+        let _ = sender.blocking_send(last);
+    });
+
+    futures::stream::poll_fn(move |cx| receiver.poll_recv(cx).map(|bytes| bytes.map(Ok)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Cue, OverlapPolicy};
+    use futures::StreamExt;
+
+    #[test]
+    fn test_event_format() {
+        let bytes = event("progress", &serde_json::json!({"processed": 1}));
+        assert_eq!(&bytes[..], b"event: progress\ndata: {\"processed\":1}\n\n");
+    }
+
+    #[tokio::test]
+    async fn test_streams_cues_then_done() {
+        let req = FileAlignmentRequest {
+            language: "en".to_string(),
+            cues: vec![
+                Cue { index: 1, start: 0.0, end: 1.0, text: "Hello".to_string(), ..Default::default() },
+                Cue { index: 2, start: 1.0, end: 2.0, text: "World".to_string(), ..Default::default() },
+            ],
+            overlap_policy: OverlapPolicy::Clamp,
+        };
+
+        let events: Vec<String> = align_file_events(req)
+            .map(|bytes| String::from_utf8(bytes.unwrap().to_vec()).unwrap())
+            .collect()
+            .await;
+
+        let names: Vec<&str> = events.iter()
+            .map(|e| e.lines().next().unwrap().trim_start_matches("event: "))
+            .collect();
+        assert_eq!(names, vec!["cue", "progress", "cue", "progress", "done"]);
+        assert!(events[3].contains("\"processed\":2,\"total\":2"));
+    }
+}
@@ -0,0 +1,221 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use crate::models::StageTimings;
+
+/// A part of handling a request that's timed on its own for `debug_timings`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    SubtitleParse,
+    Tokenization,
+    /// Spelling out numbers, dates and symbols
+    Normalization,
+    /// Looking up or deriving pronunciations
+    G2p,
+    Alignment,
+    /// Waiting on `TTS_ENGINE_URL`
+    TtsEngine,
+    /// Downloading `audio_url`
+    AudioFetch,
+    /// Probing and measuring audio
+    AudioDecode,
+}
+
+impl Stage {
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::SubtitleParse => "subtitle_parse",
+            Stage::Tokenization => "tokenization",
+            Stage::Normalization => "normalization",
+            Stage::G2p => "g2p",
+            Stage::Alignment => "alignment",
+            Stage::TtsEngine => "tts_engine",
+            Stage::AudioFetch => "audio_fetch",
+            Stage::AudioDecode => "audio_decode",
+        }
+    }
+}
+
+/// Time spent in each stage of one request
+///
+/// Made current with `in_scope` (sync code) or `wrap` (futures), like a
+/// tracing span; `time` and `time_async` then add to whichever recorder is
+/// current and cost nothing when there is none. Time in a nested stage
+/// (tokenizing while aligning) counts toward the nested stage only.
+pub struct Recorder {
+    started: Instant,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    totals: BTreeMap<Stage, Duration>,
+    /// For each stage in progress, time spent in stages nested in it so far
+    open: Vec<Duration>,
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<Recorder>>> = const { RefCell::new(None) };
+}
+
+/// Restores the previously current recorder when dropped, even on panic
+struct Entered(Option<Arc<Recorder>>);
+
+impl Drop for Entered {
+    fn drop(&mut self) {
+        CURRENT.with(|current| *current.borrow_mut() = self.0.take());
+    }
+}
+
+impl Recorder {
+    pub fn new() -> Arc<Recorder> {
+        Arc::new(Recorder { started: Instant::now(), state: Mutex::new(State::default()) })
+    }
+
+    /// Run `f` with this recorder current on this thread
+    pub fn in_scope<R>(self: &Arc<Self>, f: impl FnOnce() -> R) -> R {
+        let _entered = Entered(CURRENT.with(|current| current.replace(Some(self.clone()))));
+        f()
+    }
+
+    /// `future`, with this recorder current whenever it's polled
+    pub fn wrap<F: Future>(self: &Arc<Self>, future: F) -> Recorded<F> {
+        Recorded { recorder: self.clone(), future: Box::pin(future) }
+    }
+
+    /// Milliseconds per stage that ran, and since the recorder was created
+    pub fn timings(&self) -> StageTimings {
+        let state = self.state.lock().unwrap();
+        StageTimings {
+            total_ms: millis(self.started.elapsed()),
+            stages: state.totals.iter().map(|(stage, total)| (stage.name().to_string(), millis(*total))).collect(),
+        }
+    }
+
+    fn begin(&self) {
+        self.state.lock().unwrap().open.push(Duration::ZERO);
+    }
+
+    fn end(&self, stage: Stage, elapsed: Duration) {
+        let mut state = self.state.lock().unwrap();
+        let nested = state.open.pop().unwrap_or_default();
+        *state.totals.entry(stage).or_default() += elapsed.saturating_sub(nested);
+        if let Some(parent) = state.open.last_mut() {
+            *parent += elapsed;
+        }
+    }
+}
+
+/// A future polled with its request's recorder current
+pub struct Recorded<F> {
+    recorder: Arc<Recorder>,
+    future: Pin<Box<F>>,
+}
+
+impl<F: Future> Future for Recorded<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let recorder = self.recorder.clone();
+        recorder.in_scope(|| self.future.as_mut().poll(cx))
+    }
+}
+
+/// `f` with `recorder` current, if the request is timed
+pub fn scoped<R>(recorder: Option<&Arc<Recorder>>, f: impl FnOnce() -> R) -> R {
+    match recorder {
+        Some(recorder) => recorder.in_scope(f),
+        None => f(),
+    }
+}
+
+/// `future` with `recorder` current, if the request is timed
+pub async fn scoped_async<F: Future>(recorder: Option<&Arc<Recorder>>, future: F) -> F::Output {
+    match recorder {
+        Some(recorder) => recorder.wrap(future).await,
+        None => future.await,
+    }
+}
+
+/// The recorder of the request being handled on this thread, if it's timed
+///
+/// Capture it before handing work to another thread and enter it there.
+pub fn current() -> Option<Arc<Recorder>> {
+    CURRENT.with(|current| current.borrow().clone())
+}
+
+/// Run `f` as `stage` of the current request
+pub fn time<R>(stage: Stage, f: impl FnOnce() -> R) -> R {
+    let Some(recorder) = current() else {
+        return f();
+    };
+    recorder.begin();
+    let started = Instant::now();
+    let result = f();
+    recorder.end(stage, started.elapsed());
+    result
+}
+
+/// Await `future` as `stage` of the current request
+pub async fn time_async<F: Future>(stage: Stage, future: F) -> F::Output {
+    let Some(recorder) = current() else {
+        return future.await;
+    };
+    recorder.begin();
+    let started = Instant::now();
+    let result = future.await;
+    recorder.end(stage, started.elapsed());
+    result
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+
+    #[test]
+    fn test_nested_stages_are_exclusive() {
+        let recorder = Recorder::new();
+        recorder.in_scope(|| {
+            time(Stage::Alignment, || {
+                sleep(Duration::from_millis(20));
+                time(Stage::Tokenization, || sleep(Duration::from_millis(30)));
+            });
+        });
+
+        let timings = recorder.timings();
+        let (alignment, tokenization) = (timings.stages["alignment"], timings.stages["tokenization"]);
+        assert!(alignment >= 20.0 && tokenization >= 30.0);
+        // Were the tokenization counted toward alignment too, this would be ~80ms over ~50ms
+        assert!(timings.total_ms >= alignment + tokenization);
+        assert!(current().is_none());
+    }
+
+    #[test]
+    fn test_untimed_requests_record_nothing() {
+        assert_eq!(time(Stage::Alignment, || 1 + 1), 2);
+        assert!(current().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_recorded_future_keeps_recorder_across_awaits() {
+        let recorder = Recorder::new();
+        recorder.wrap(async {
+            time_async(Stage::TtsEngine, tokio::time::sleep(Duration::from_millis(10))).await;
+            time(Stage::Alignment, || ());
+        }).await;
+
+        let timings = recorder.timings();
+        assert!(timings.stages["tts_engine"] >= 10.0);
+        assert!(timings.stages.contains_key("alignment"));
+    }
+}
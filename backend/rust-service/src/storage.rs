@@ -0,0 +1,208 @@
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use futures::StreamExt;
+use object_store::aws::{AmazonS3, AmazonS3Builder};
+use object_store::path::Path;
+use object_store::signer::Signer;
+use object_store::{Attribute, Attributes, ObjectStore, PutOptions, PutPayload};
+use reqwest::Method;
+
+use crate::error::{ApiError, ErrorCode};
+use crate::models::{PresignedUpload, StoredObject};
+use crate::spool::Spool;
+
+pub const DEFAULT_PRESIGNED_URL_TTL_SECS: u64 = 3600;
+
+/// Longest file name kept in an upload key
+const MAX_FILENAME_LENGTH: usize = 100;
+
+/// S3-compatible bucket for large artifacts, configured at startup
+static STORAGE: OnceLock<Option<Storage>> = OnceLock::new();
+
+/// `S3_*` settings; credentials come from the usual `AWS_*` variables
+#[derive(Debug, Clone, PartialEq)]
+pub struct StorageConfig {
+    pub bucket: String,
+    /// For MinIO, R2 etc.; plain `http://` is allowed
+    pub endpoint: Option<String>,
+    pub region: Option<String>,
+    /// Prepended to every key the service writes
+    pub prefix: String,
+    /// How long presigned URLs stay valid
+    pub url_ttl: Duration,
+}
+
+struct Storage {
+    s3: AmazonS3,
+    config: StorageConfig,
+}
+
+/// Connect to the bucket (`S3_BUCKET`), if one is configured
+///
+/// Only builds the client; a wrong bucket or credentials show up on first use.
+pub fn init(config: Option<StorageConfig>) -> Result<(), String> {
+    let storage = config.map(|config| {
+        let mut builder = AmazonS3Builder::from_env().with_bucket_name(&config.bucket);
+        if let Some(endpoint) = &config.endpoint {
+            builder = builder.with_endpoint(endpoint).with_allow_http(endpoint.starts_with("http://"));
+        }
+        if let Some(region) = &config.region {
+            builder = builder.with_region(region);
+        }
+
+        let s3 = builder.build().map_err(|e| format!("Invalid object storage configuration: {}", e))?;
+        log::info!("Large results and uploads go to s3://{}/{}", config.bucket, config.prefix);
+        Ok::<_, String>(Storage { s3, config })
+    }).transpose()?;
+
+    if STORAGE.set(storage).is_err() {
+        log::warn!("Object storage already initialised");
+    }
+    Ok(())
+}
+
+/// The configured bucket, if any
+pub fn bucket() -> Option<&'static str> {
+    STORAGE.get().and_then(|storage| storage.as_ref()).map(|storage| storage.config.bucket.as_str())
+}
+
+fn storage() -> Result<&'static Storage, ApiError> {
+    STORAGE.get().and_then(|storage| storage.as_ref())
+        .ok_or_else(|| ApiError::unsupported("Object storage is not configured (S3_BUCKET)"))
+}
+
+/// Store a result under `results/` and return a link to download it
+pub async fn put(name: &str, content_type: &str, bytes: Vec<u8>) -> Result<StoredObject, ApiError> {
+    let storage = storage()?;
+    let key = format!("{}results/{}/{}", storage.config.prefix, uuid::Uuid::new_v4(), name);
+    let path = Path::from(key.as_str());
+    let size = bytes.len();
+
+    let options = PutOptions {
+        attributes: Attributes::from_iter([(Attribute::ContentType, content_type.to_string())]),
+        ..Default::default()
+    };
+    storage.s3.put_opts(&path, PutPayload::from(bytes), options).await.map_err(storage_error)?;
+    log::info!("Stored {} bytes at {}", size, key);
+
+    Ok(StoredObject {
+        url: sign(storage, Method::GET, &path).await?,
+        key,
+        content_type: content_type.to_string(),
+        size,
+        expires_at: expires_at(storage),
+    })
+}
+
+/// A presigned URL the client can PUT a file (e.g. hours of audio) to directly
+pub async fn upload_url(filename: &str) -> Result<PresignedUpload, ApiError> {
+    let storage = storage()?;
+    let key = format!("{}uploads/{}/{}", storage.config.prefix, uuid::Uuid::new_v4(), safe_filename(filename));
+    let path = Path::from(key.as_str());
+
+    Ok(PresignedUpload {
+        url: sign(storage, Method::PUT, &path).await?,
+        audio_url: format!("s3://{}/{}", storage.config.bucket, key),
+        key,
+        expires_at: expires_at(storage),
+    })
+}
+
+/// Read `s3://bucket/key` from the configured bucket into `spool`
+pub async fn get(url: &str, spool: &mut Spool) -> Result<(), ApiError> {
+    let storage = storage()?;
+    let mut chunks = storage.s3.get(&readable_key(&storage.config, url)?).await.map_err(storage_error)?.into_stream();
+    while let Some(chunk) = chunks.next().await {
+        spool.write(&chunk.map_err(storage_error)?).await?;
+    }
+    Ok(())
+}
+
+/// Check that the `s3://bucket/key` object `get` would read exists,
+/// without reading it
+pub async fn head(url: &str) -> Result<(), ApiError> {
+    let storage = storage()?;
+    storage.s3.head(&readable_key(&storage.config, url)?).await.map_err(storage_error)?;
+    Ok(())
+}
+
+/// The key of an `s3://bucket/key` URL, if it's one `upload_url` handed out
+///
+/// Results and anything else in the bucket stay out of reach: a caller can
+/// only have the service read what was uploaded for it to read.
+fn readable_key(config: &StorageConfig, url: &str) -> Result<Path, ApiError> {
+    let (bucket, key) = url.strip_prefix("s3://")
+        .and_then(|rest| rest.split_once('/'))
+        .ok_or_else(|| ApiError::invalid_input(format!("Expected s3://bucket/key, got '{}'", url)))?;
+    if bucket != config.bucket {
+        return Err(ApiError::invalid_input(format!("Only objects in bucket '{}' can be read", config.bucket)));
+    }
+
+    let uploads = format!("{}uploads/", config.prefix);
+    match Path::parse(key) {
+        Ok(path) if key.starts_with(&uploads) => Ok(path),
+        _ => Err(ApiError::invalid_input(format!("Only uploads (s3://{}/{}...) can be read", config.bucket, uploads))),
+    }
+}
+
+async fn sign(storage: &Storage, method: Method, path: &Path) -> Result<String, ApiError> {
+    storage.s3.signed_url(method, path, storage.config.url_ttl).await
+        .map(|url| url.to_string())
+        .map_err(storage_error)
+}
+
+fn expires_at(storage: &Storage) -> u64 {
+    (SystemTime::now() + storage.config.url_ttl).duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+/// Keep the name readable in the bucket but free of `/`, `..` and the like
+fn safe_filename(filename: &str) -> String {
+    let name: String = filename.rsplit(['/', '\\']).next().unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' })
+        .take(MAX_FILENAME_LENGTH)
+        .collect();
+
+    match name.trim_start_matches('.') {
+        "" => "file".to_string(),
+        name => name.to_string(),
+    }
+}
+
+fn storage_error(e: object_store::Error) -> ApiError {
+    match e {
+        object_store::Error::NotFound { path, .. } => ApiError::not_found(format!("No object {}", path)),
+        e => ApiError::new(ErrorCode::Upstream, format!("Object storage request failed: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_safe_filename() {
+        assert_eq!(safe_filename("episode 1.mp3"), "episode_1.mp3");
+        assert_eq!(safe_filename("../../etc/passwd"), "passwd");
+        assert_eq!(safe_filename("C:\\audio\\ep.wav"), "ep.wav");
+        assert_eq!(safe_filename("..."), "file");
+        assert_eq!(safe_filename(&"a".repeat(300)).len(), MAX_FILENAME_LENGTH);
+    }
+
+    #[test]
+    fn test_only_uploads_are_readable() {
+        let config = StorageConfig {
+            bucket: "media".to_string(),
+            endpoint: None,
+            region: None,
+            prefix: "dubdub/".to_string(),
+            url_ttl: Duration::from_secs(DEFAULT_PRESIGNED_URL_TTL_SECS),
+        };
+        assert_eq!(readable_key(&config, "s3://media/dubdub/uploads/1234/ep.mp3").unwrap().as_ref(), "dubdub/uploads/1234/ep.mp3");
+        for url in ["s3://other/dubdub/uploads/1234/ep.mp3", "s3://media/dubdub/results/1234/out.zip", "s3://media/private/keys.json",
+            "s3://media/dubdub/uploads/../results/1234/out.zip", "media/dubdub/uploads/1234/ep.mp3"] {
+            assert_eq!(readable_key(&config, url).unwrap_err().code, ErrorCode::InvalidInput, "{}", url);
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use super::{parse_timestamp, strip_tags};
+use crate::error::{ApiError, ErrorCode};
+use crate::models::{Cue, CueWarning, CueWarningKind};
+
+/// Event fields when a file has no `Format:` line (ASS v4+)
+pub(super) const DEFAULT_FORMAT: [&str; 10] = [
+    "layer", "start", "end", "style", "name",
+    "marginl", "marginr", "marginv", "effect", "text",
+];
+
+/// Parse ASS/SSA content (already BOM-stripped and LF-normalized)
+///
+/// # How it works:
+/// 1. Find the `[Events]` section and its `Format:` line
+/// 2. Split each `Dialogue:` line into fields (Text keeps any extra commas)
+/// 3. Strip override blocks (`{\an8}`, `{\k20}`) and convert `\N` line breaks
+///
+/// Comments, drawings (`{\p1}`) and events with no text left are skipped.
+/// Cues are numbered in file order; Style and Name become `style` / `actor`.
+pub fn parse(content: &str) -> Result<(Vec<Cue>, Vec<CueWarning>), ApiError> {
+    let mut cues = Vec::new();
+    let mut warnings = Vec::new();
+
+    let mut in_events = false;
+    let mut format: Vec<String> = DEFAULT_FORMAT.iter().map(|field| field.to_string()).collect();
+    let mut event_number = 0;
+
+    for line in content.lines() {
+        let line = line.trim();
+
+        if line.starts_with('[') {
+            in_events = line.eq_ignore_ascii_case("[events]");
+            continue;
+        }
+
+        if !in_events {
+            continue;
+        }
+
+        let Some((kind, value)) = line.split_once(':') else {
+            continue;
+        };
+
+        match kind.trim().to_lowercase().as_str() {
+            "format" => {
+                format = value.split(',')
+                    .map(|field| field.trim().to_lowercase())
+                    .collect();
+            }
+            "dialogue" => {
+                event_number += 1;
+
+                let fields: Vec<&str> = value.trim_start().splitn(format.len(), ',').collect();
+                let field = |name: &str| {
+                    format.iter()
+                        .position(|f| f == name)
+                        .and_then(|i| fields.get(i))
+                        .map(|value| value.trim())
+                };
+
+                let timing = field("start").and_then(parse_timestamp)
+                    .zip(field("end").and_then(parse_timestamp));
+                let Some((start, end)) = timing else {
+                    warnings.push(CueWarning {
+                        cue_index: event_number,
+                        kind: CueWarningKind::MalformedTimestamp,
+                        message: "Unreadable Dialogue start/end, event skipped".to_string(),
+                    });
+                    continue;
+                };
+
+                // Text is the last field and may itself contain commas
+                let raw_text = format.iter()
+                    .position(|f| f == "text")
+                    .and_then(|i| fields.get(i))
+                    .copied()
+                    .unwrap_or("");
+
+                if is_drawing(raw_text) {
+                    continue;
+                }
+
+                let text = clean_text(raw_text);
+                if text.is_empty() {
+                    continue;
+                }
+
+                cues.push(Cue {
+                    index: event_number,
+                    start,
+                    end,
+                    text,
+                    style: field("style").filter(|s| !s.is_empty()).map(str::to_string),
+                    actor: field("name").or_else(|| field("actor"))
+                        .filter(|s| !s.is_empty())
+                        .map(str::to_string),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    if cues.is_empty() {
+        return Err(ApiError::new(ErrorCode::InvalidSubtitles, "No Dialogue events found in ASS/SSA content"));
+    }
+
+    Ok((cues, warnings))
+}
+
+/// `{\p1}` switches the event into vector drawing mode; its "text" is shape commands
+fn is_drawing(text: &str) -> bool {
+    text.match_indices("\\p").any(|(i, _)| {
+        text[i + 2..].chars().next().is_some_and(|c| c.is_ascii_digit() && c != '0')
+    })
+}
+
+/// Convert ASS escapes to plain text and drop override tags
+fn clean_text(text: &str) -> String {
+    let text = text.replace("\\N", "\n")
+        .replace("\\n", "\n")
+        .replace("\\h", " ");
+
+    strip_tags(&text)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "[Script Info]
+Title: Sample
+ScriptType: v4.00+
+
+[V4+ Styles]
+Format: Name, Fontname, Fontsize
+Style: Default,Arial,20
+
+[Events]
+Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text
+Dialogue: 0,0:00:01.00,0:00:03.50,Default,Naruto,0,0,0,,{\\an8}Believe it, {\\k20}dattebayo!
+Comment: 0,0:00:04.00,0:00:05.00,Default,,0,0,0,,Translator note
+Dialogue: 0,0:00:04.00,0:00:05.00,Sign,,0,0,0,,{\\p1}m 0 0 l 100 0 100 100 0 100{\\p0}
+Dialogue: 0,0:00:06.00,0:00:08.00,Default,Sasuke,0,0,0,,Line one\\NLine two
+";
+
+    #[test]
+    fn test_parse_dialogue() {
+        let (cues, warnings) = parse(SAMPLE).unwrap();
+
+        assert!(warnings.is_empty());
+        assert_eq!(cues.len(), 2);
+
+        assert_eq!(cues[0].start, 1.0);
+        assert_eq!(cues[0].end, 3.5);
+        assert_eq!(cues[0].text, "Believe it, dattebayo!");
+        assert_eq!(cues[0].style.as_deref(), Some("Default"));
+        assert_eq!(cues[0].actor.as_deref(), Some("Naruto"));
+
+        assert_eq!(cues[1].text, "Line one\nLine two");
+        assert_eq!(cues[1].index, 3);
+    }
+
+    #[test]
+    fn test_ssa_format_line() {
+        let content = "[Events]
+Format: Marked, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text
+Dialogue: Marked=0,0:00:01.00,0:00:02.00,Main,,0000,0000,0000,,Hello
+";
+        let (cues, _) = parse(content).unwrap();
+
+        assert_eq!(cues[0].text, "Hello");
+        assert_eq!(cues[0].style.as_deref(), Some("Main"));
+        assert_eq!(cues[0].actor, None);
+    }
+
+    #[test]
+    fn test_bad_timestamp_reported() {
+        let content = "[Events]
+Dialogue: 0,nope,0:00:02.00,Default,,0,0,0,,Broken
+Dialogue: 0,0:00:03.00,0:00:04.00,Default,,0,0,0,,Fine
+";
+        let (cues, warnings) = parse(content).unwrap();
+
+        assert_eq!(cues.len(), 1);
+        assert_eq!(warnings[0].kind, CueWarningKind::MalformedTimestamp);
+        assert_eq!(warnings[0].cue_index, 1);
+    }
+}
@@ -0,0 +1,139 @@
+use crate::models::{Cue, CuePair};
+
+/// Pair the cues of two subtitle files for the same video
+///
+/// # How it works:
+/// 1. Link every source cue to every target cue it overlaps by at least
+///    `min_overlap` (fraction of the shorter cue's duration)
+/// 2. Group cues connected by links, so a sentence split over two cues in
+///    one language still pairs with the single cue carrying it in the other
+/// 3. Emit one pair per group, ordered by start time; cues with no
+///    counterpart get a pair with an empty other side
+pub fn pair_cues(source: &[Cue], target: &[Cue], min_overlap: f64) -> Vec<CuePair> {
+    let mut source: Vec<&Cue> = source.iter().collect();
+    let mut target: Vec<&Cue> = target.iter().collect();
+    source.sort_by(|a, b| a.start.total_cmp(&b.start));
+    target.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+    // Step 1: Link overlapping cues. Targets are indexed after sources.
+    let mut groups = DisjointSet::new(source.len() + target.len());
+
+    for (i, s) in source.iter().enumerate() {
+        for (j, t) in target.iter().enumerate() {
+            if t.start >= s.end {
+                break;
+            }
+
+            let overlap = s.end.min(t.end) - s.start.max(t.start);
+            let shorter = (s.end - s.start).min(t.end - t.start);
+            if shorter > 0.0 && overlap / shorter >= min_overlap {
+                groups.union(i, source.len() + j);
+            }
+        }
+    }
+
+    // Step 2: Collect group members (sorted input keeps each side in order)
+    let mut members: Vec<(Vec<&Cue>, Vec<&Cue>)> = vec![(Vec::new(), Vec::new()); source.len() + target.len()];
+    for (i, cue) in source.iter().enumerate() {
+        members[groups.find(i)].0.push(cue);
+    }
+    for (j, cue) in target.iter().enumerate() {
+        members[groups.find(source.len() + j)].1.push(cue);
+    }
+
+    // Step 3: One pair per group
+    let mut pairs: Vec<CuePair> = members.into_iter()
+        .filter(|(sources, targets)| !sources.is_empty() || !targets.is_empty())
+        .map(|(sources, targets)| {
+            let all = sources.iter().chain(&targets);
+            CuePair {
+                start: all.clone().map(|c| c.start).fold(f64::INFINITY, f64::min),
+                end: all.map(|c| c.end).fold(f64::NEG_INFINITY, f64::max),
+                source_indices: sources.iter().map(|c| c.index).collect(),
+                target_indices: targets.iter().map(|c| c.index).collect(),
+                source_text: join_text(&sources),
+                target_text: join_text(&targets),
+            }
+        })
+        .collect();
+
+    pairs.sort_by(|a, b| a.start.total_cmp(&b.start));
+    pairs
+}
+
+/// Cue texts as a single line each, joined by spaces
+fn join_text(cues: &[&Cue]) -> String {
+    cues.iter()
+        .flat_map(|cue| cue.text.split_whitespace())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Union-find over cue indices
+struct DisjointSet {
+    parent: Vec<usize>,
+}
+
+impl DisjointSet {
+    fn new(size: usize) -> Self {
+        DisjointSet { parent: (0..size).collect() }
+    }
+
+    fn find(&mut self, mut i: usize) -> usize {
+        while self.parent[i] != i {
+            self.parent[i] = self.parent[self.parent[i]];
+            i = self.parent[i];
+        }
+        i
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parent[b] = a;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::cue;
+
+    #[test]
+    fn test_one_to_one() {
+        let source = vec![cue(1, 0.0, 2.0, "Hello."), cue(2, 3.0, 5.0, "Goodbye.")];
+        let target = vec![cue(1, 0.1, 2.1, "Hola."), cue(2, 3.1, 4.9, "Adiós.")];
+        let pairs = pair_cues(&source, &target, 0.3);
+
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(pairs[0].source_text, "Hello.");
+        assert_eq!(pairs[0].target_text, "Hola.");
+        assert_eq!(pairs[1].target_indices, vec![2]);
+    }
+
+    #[test]
+    fn test_sentence_split_across_cues() {
+        let source = vec![cue(1, 0.0, 2.0, "I was going\nto say"), cue(2, 2.0, 4.0, "something.")];
+        let target = vec![cue(1, 0.0, 4.0, "Iba a decir algo.")];
+        let pairs = pair_cues(&source, &target, 0.3);
+
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].source_indices, vec![1, 2]);
+        assert_eq!(pairs[0].source_text, "I was going to say something.");
+        assert_eq!((pairs[0].start, pairs[0].end), (0.0, 4.0));
+    }
+
+    #[test]
+    fn test_unmatched_and_slight_overlap() {
+        let source = vec![cue(1, 0.0, 2.0, "Hello."), cue(2, 10.0, 12.0, "Untranslated sign")];
+        // Only touches the first cue by 0.1s, not enough to pair
+        let target = vec![cue(1, 1.9, 4.0, "Otra cosa.")];
+        let pairs = pair_cues(&source, &target, 0.3);
+
+        assert_eq!(pairs.len(), 3);
+        assert!(pairs[0].target_indices.is_empty());
+        assert!(pairs[1].source_indices.is_empty());
+        assert_eq!(pairs[2].source_text, "Untranslated sign");
+    }
+}
@@ -0,0 +1,278 @@
+use crate::cues::find_overlaps;
+use crate::error::ApiError;
+use crate::models::{ClozeRequest, CollocationRequest, Cue, CueWarning, DifficultyRequest, ParseSubtitlesResponse, SubtitleFormat, TranscriptDiffRequest, ValidateSubtitlesRequest, VocabularyRequest};
+use regex::Regex;
+use serde::de::{self, Deserializer, Unexpected, Visitor};
+use std::fmt;
+use std::sync::LazyLock;
+
+pub mod ass;
+pub mod bilingual;
+pub mod restructure;
+pub mod resync;
+pub mod srt;
+pub mod validate;
+pub mod vtt;
+pub mod writer;
+
+/// Formatting tags: HTML-style (`<i>`, `<font color=..>`) and ASS-style (`{\an8}`)
+static TAG_PATTERN: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]*>|\{[^}]*\}").unwrap());
+
+/// Parse a subtitle file into cues
+///
+/// The format is detected from the content unless `format` is given.
+/// Overlapping cues are reported as warnings but left untouched, so the
+/// result mirrors the file; alignment decides how to resolve them.
+#[tracing::instrument(skip(content), fields(bytes = content.len()))]
+pub fn parse(content: &str, format: Option<SubtitleFormat>) -> Result<ParseSubtitlesResponse, ApiError> {
+    let content = normalize(content);
+    let format = format.unwrap_or_else(|| detect_format(&content));
+
+    let (cues, mut warnings) = match format {
+        SubtitleFormat::Srt => srt::parse(&content)?,
+        SubtitleFormat::Vtt => vtt::parse(&content)?,
+        SubtitleFormat::Ass => ass::parse(&content)?,
+    };
+    warnings.extend(find_overlaps(&cues));
+
+    Ok(ParseSubtitlesResponse {
+        format,
+        cues,
+        warnings,
+    })
+}
+
+/// Requests taking either a raw subtitle file (`content`, in `format` or
+/// detected) or already-parsed `cues`
+pub trait CueSource {
+    fn cue_source(&self) -> (Option<&[Cue]>, Option<&str>, Option<SubtitleFormat>);
+}
+
+impl CueSource for ValidateSubtitlesRequest {
+    fn cue_source(&self) -> (Option<&[Cue]>, Option<&str>, Option<SubtitleFormat>) {
+        (self.cues.as_deref(), self.content.as_deref(), self.format)
+    }
+}
+
+impl CueSource for ClozeRequest {
+    fn cue_source(&self) -> (Option<&[Cue]>, Option<&str>, Option<SubtitleFormat>) {
+        (self.cues.as_deref(), self.content.as_deref(), self.format)
+    }
+}
+
+impl CueSource for VocabularyRequest {
+    fn cue_source(&self) -> (Option<&[Cue]>, Option<&str>, Option<SubtitleFormat>) {
+        (self.cues.as_deref(), self.content.as_deref(), self.format)
+    }
+}
+
+impl CueSource for CollocationRequest {
+    fn cue_source(&self) -> (Option<&[Cue]>, Option<&str>, Option<SubtitleFormat>) {
+        (self.cues.as_deref(), self.content.as_deref(), self.format)
+    }
+}
+
+impl CueSource for DifficultyRequest {
+    fn cue_source(&self) -> (Option<&[Cue]>, Option<&str>, Option<SubtitleFormat>) {
+        (self.cues.as_deref(), self.content.as_deref(), self.format)
+    }
+}
+
+impl CueSource for TranscriptDiffRequest {
+    fn cue_source(&self) -> (Option<&[Cue]>, Option<&str>, Option<SubtitleFormat>) {
+        (self.cues.as_deref(), self.content.as_deref(), self.format)
+    }
+}
+
+/// A `CueSource` request's cues, preferring `cues` over `content`, with any
+/// warnings from parsing `content`
+pub fn cues_or_content(req: &impl CueSource) -> Result<(Vec<Cue>, Vec<CueWarning>), ApiError> {
+    match req.cue_source() {
+        (Some(cues), _, _) => Ok((cues.to_vec(), Vec::new())),
+        (None, Some(content), format) => parse(content, format).map(|parsed| (parsed.cues, parsed.warnings)),
+        (None, None, _) => Err(ApiError::invalid_input("Provide either 'content' or 'cues'").with_field("content")),
+    }
+}
+
+/// Guess the format from the WEBVTT header or ASS section headers / event lines
+pub fn detect_format(content: &str) -> SubtitleFormat {
+    if content.trim_start().starts_with("WEBVTT") {
+        return SubtitleFormat::Vtt;
+    }
+
+    let is_ass = content.lines()
+        .map(str::trim)
+        .any(|line| {
+            line.eq_ignore_ascii_case("[script info]")
+                || line.eq_ignore_ascii_case("[events]")
+                || line.starts_with("Dialogue:")
+        });
+
+    if is_ass { SubtitleFormat::Ass } else { SubtitleFormat::Srt }
+}
+
+/// Format implied by a file name's extension, if it's a subtitle extension
+pub fn format_from_filename(filename: &str) -> Option<SubtitleFormat> {
+    let (_, extension) = filename.rsplit_once('.')?;
+
+    match extension.to_lowercase().as_str() {
+        "srt" => Some(SubtitleFormat::Srt),
+        "vtt" => Some(SubtitleFormat::Vtt),
+        "ass" | "ssa" => Some(SubtitleFormat::Ass),
+        _ => None,
+    }
+}
+
+/// Strip a leading BOM and normalize CRLF / CR line endings to LF
+fn normalize(content: &str) -> String {
+    content.trim_start_matches('\u{feff}')
+        .replace("\r\n", "\n")
+        .replace('\r', "\n")
+}
+
+/// Remove formatting and positioning tags from cue text
+pub fn strip_tags(text: &str) -> String {
+    TAG_PATTERN.replace_all(text, "").trim().to_string()
+}
+
+/// Parse a subtitle timestamp into seconds
+///
+/// Accepts "HH:MM:SS,mmm" (SRT), "HH:MM:SS.mmm" (VTT), "H:MM:SS.cc" (ASS)
+/// and "MM:SS.mmm", with any number of fractional digits.
+pub fn parse_timestamp(value: &str) -> Option<f64> {
+    let value = value.trim().replace(',', ".");
+
+    let (clock, fraction) = match value.split_once('.') {
+        Some((clock, fraction)) => (clock, fraction),
+        None => (value.as_str(), ""),
+    };
+
+    let parts: Vec<&str> = clock.split(':').collect();
+    if !(2..=3).contains(&parts.len()) {
+        return None;
+    }
+
+    let mut seconds = 0.0;
+    for part in &parts {
+        if part.is_empty() || !part.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+    }
+
+    if !fraction.is_empty() {
+        if !fraction.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        seconds += format!("0.{}", fraction).parse::<f64>().ok()?;
+    }
+
+    Some(seconds)
+}
+
+/// Deserialize seconds given either as a number or as a timestamp string
+/// `parse_timestamp` accepts, for request fields callers copy straight out
+/// of subtitle files (`#[serde(deserialize_with = "...")]`)
+pub fn deserialize_seconds<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    struct Seconds;
+
+    impl Visitor<'_> for Seconds {
+        type Value = f64;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("seconds, or a timestamp such as \"00:01:02,500\"")
+        }
+
+        fn visit_f64<E: de::Error>(self, value: f64) -> Result<f64, E> {
+            Ok(value)
+        }
+
+        fn visit_i64<E: de::Error>(self, value: i64) -> Result<f64, E> {
+            Ok(value as f64)
+        }
+
+        fn visit_u64<E: de::Error>(self, value: u64) -> Result<f64, E> {
+            Ok(value as f64)
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<f64, E> {
+            parse_timestamp(value).ok_or_else(|| E::invalid_value(Unexpected::Str(value), &self))
+        }
+    }
+
+    deserializer.deserialize_any(Seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_from_filename() {
+        assert_eq!(format_from_filename("episode01.SRT"), Some(SubtitleFormat::Srt));
+        assert_eq!(format_from_filename("fansub.ass"), Some(SubtitleFormat::Ass));
+        assert_eq!(format_from_filename("notes.txt"), None);
+    }
+
+    #[test]
+    fn test_parse_timestamp_formats() {
+        assert_eq!(parse_timestamp("00:01:02,500"), Some(62.5));
+        assert_eq!(parse_timestamp("00:01:02.500"), Some(62.5));
+        assert_eq!(parse_timestamp("0:01:02.50"), Some(62.5));
+        assert_eq!(parse_timestamp("01:02.5"), Some(62.5));
+        assert_eq!(parse_timestamp("1:00:00,000"), Some(3600.0));
+    }
+
+    #[test]
+    fn test_parse_timestamp_rejects_garbage() {
+        assert_eq!(parse_timestamp("abc"), None);
+        assert_eq!(parse_timestamp("12"), None);
+        assert_eq!(parse_timestamp("00:-1:02,500"), None);
+        assert_eq!(parse_timestamp("1:2:3:4"), None);
+    }
+
+    #[test]
+    fn test_deserialize_seconds() {
+        #[derive(serde::Deserialize)]
+        struct Window {
+            #[serde(deserialize_with = "deserialize_seconds")]
+            start: f64,
+            #[serde(deserialize_with = "deserialize_seconds")]
+            end: f64,
+        }
+
+        let window: Window = serde_json::from_str(r#"{"start": "00:01:02,500", "end": 63}"#).unwrap();
+        assert_eq!((window.start, window.end), (62.5, 63.0));
+        let window: Window = serde_json::from_str(r#"{"start": 1.5, "end": "00:00:02.250"}"#).unwrap();
+        assert_eq!((window.start, window.end), (1.5, 2.25));
+
+        let error = serde_json::from_str::<Window>(r#"{"start": "soon", "end": 2}"#).err().unwrap();
+        assert!(error.to_string().contains("00:01:02,500"));
+        assert!(serde_json::from_str::<Window>(r#"{"start": true, "end": 2}"#).is_err());
+    }
+
+    #[test]
+    fn test_strip_tags() {
+        assert_eq!(strip_tags("{\\an8}<i>Hello</i> <font color=\"red\">there</font>"), "Hello there");
+    }
+
+    #[test]
+    fn test_parse_reports_overlaps() {
+        let content = "\u{feff}1\r\n00:00:01,000 --> 00:00:03,000\r\nHello\r\n\r\n2\r\n00:00:02,000 --> 00:00:04,000\r\nWorld\r\n";
+        let parsed = parse(content, None).unwrap();
+
+        assert_eq!(parsed.format, SubtitleFormat::Srt);
+        assert_eq!(parsed.cues.len(), 2);
+        assert_eq!(parsed.warnings.len(), 1);
+        assert_eq!(parsed.warnings[0].cue_index, 2);
+    }
+
+    #[test]
+    fn test_detects_ass() {
+        let content = "[Script Info]\r\nTitle: x\r\n\r\n[Events]\r\nDialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,Hi\r\n";
+        let parsed = parse(content, None).unwrap();
+
+        assert_eq!(parsed.format, SubtitleFormat::Ass);
+        assert_eq!(parsed.cues[0].text, "Hi");
+    }
+}
@@ -0,0 +1,209 @@
+use crate::aligner::align_weighted;
+use crate::models::{AlignmentRequest, Cue};
+use crate::tokenizer::{self, clause_boundaries, sentence_boundaries};
+
+use super::writer::reading_chars;
+
+/// Thresholds for merging and splitting cues
+#[derive(Debug, Clone, Copy)]
+pub struct RestructureOptions {
+    /// Cues shorter than this (seconds) are merged with a neighbour
+    pub min_duration: f64,
+    /// Cues longer than this (seconds) are split
+    pub max_duration: f64,
+    /// Cues with more characters than this are split; merges never exceed it
+    pub max_chars: usize,
+    /// Only merge cues separated by at most this much silence (seconds)
+    pub max_gap: f64,
+}
+
+/// Merge runs of short consecutive cues
+///
+/// A cue is merged into the previous one when either of them is shorter than
+/// `min_duration`, they are at most `max_gap` apart, and the result stays
+/// within `max_chars` and `max_duration`. Cues are renumbered from 1.
+pub fn merge_short(cues: &[Cue], options: &RestructureOptions) -> Vec<Cue> {
+    let mut merged: Vec<Cue> = Vec::with_capacity(cues.len());
+
+    for cue in cues {
+        if let Some(previous) = merged.last_mut() {
+            let is_short = |c: &Cue| c.end - c.start < options.min_duration;
+            let gap = cue.start - previous.end;
+            let text = format!("{} {}", previous.text, cue.text);
+
+            if (is_short(previous) || is_short(cue))
+                && (0.0..=options.max_gap).contains(&gap)
+                && reading_chars(&text) <= options.max_chars
+                && cue.end - previous.start <= options.max_duration
+            {
+                previous.end = cue.end;
+                previous.text = text;
+                continue;
+            }
+        }
+
+        merged.push(cue.clone());
+    }
+
+    renumber(merged)
+}
+
+/// Split cues that are too long to read comfortably
+///
+/// # How it works:
+/// 1. Pick a break point closest to the middle: a sentence end if there is
+///    one, else a clause break (comma, dash, ...), else any word boundary
+/// 2. Align the cue's words (weighted aligner) and cut the timing where the
+///    second part's first word starts, so each half keeps its spoken time
+/// 3. Repeat on both halves until they fit or are single words
+///
+/// Cues are renumbered from 1.
+pub fn split_long(cues: &[Cue], language: &str, options: &RestructureOptions) -> Vec<Cue> {
+    let mut result = Vec::with_capacity(cues.len());
+
+    for cue in cues {
+        split_recursive(cue.clone(), language, options, &mut result);
+    }
+
+    renumber(result)
+}
+
+fn split_recursive(cue: Cue, language: &str, options: &RestructureOptions, out: &mut Vec<Cue>) {
+    let too_long = cue.end - cue.start > options.max_duration
+        || reading_chars(&cue.text) > options.max_chars;
+
+    let Some(split_at) = too_long.then(|| find_split(&cue.text, language)).flatten() else {
+        out.push(cue);
+        return;
+    };
+
+    let split_time = split_time(&cue, language, split_at);
+
+    let first = Cue {
+        end: split_time,
+        text: cue.text[..split_at].trim().to_string(),
+        ..cue.clone()
+    };
+    let second = Cue {
+        start: split_time,
+        text: cue.text[split_at..].trim().to_string(),
+        ..cue
+    };
+
+    split_recursive(first, language, options, out);
+    split_recursive(second, language, options, out);
+}
+
+/// Byte offset to split at, or None if the text is a single word
+fn find_split(text: &str, language: &str) -> Option<usize> {
+    let middle = text.len() / 2;
+    let closest = |candidates: Vec<usize>| candidates.into_iter().min_by_key(|offset| offset.abs_diff(middle));
+
+    closest(sentence_boundaries(text))
+        .or_else(|| closest(clause_boundaries(text)))
+        .or_else(|| {
+            closest(tokenizer::tokens(text, language).iter().skip(1).map(|token| token.start).collect())
+        })
+}
+
+/// When the word starting at byte `split_at` is spoken
+///
+/// Falls back to splitting the cue's duration by character share when the
+/// text can't be aligned.
+fn split_time(cue: &Cue, language: &str, split_at: usize) -> f64 {
+    let request = AlignmentRequest {
+        text: cue.text.clone(),
+        language: language.to_string(),
+        subtitle_start: cue.start,
+        subtitle_end: cue.end,
+        ..Default::default()
+    };
+
+    let aligned = align_weighted(&request).ok().and_then(|response| {
+        let next = response.timings.iter().position(|t| t.char_start >= split_at)?;
+        let previous_end = next.checked_sub(1)
+            .map(|i| response.timings[i].end)
+            .unwrap_or(cue.start);
+        // Middle of any pause between the two halves
+        Some((previous_end + response.timings[next].start) / 2.0)
+    });
+
+    aligned.unwrap_or_else(|| {
+        let share = cue.text[..split_at].chars().count() as f64 / cue.text.chars().count().max(1) as f64;
+        cue.start + (cue.end - cue.start) * share
+    })
+}
+
+fn renumber(mut cues: Vec<Cue>) -> Vec<Cue> {
+    for (i, cue) in cues.iter_mut().enumerate() {
+        cue.index = i + 1;
+    }
+    cues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::cue;
+
+    const OPTIONS: RestructureOptions = RestructureOptions {
+        min_duration: 1.0,
+        max_duration: 7.0,
+        max_chars: 40,
+        max_gap: 0.5,
+    };
+
+    #[test]
+    fn test_merge_short_cues() {
+        let cues = vec![
+            cue(1, 0.0, 0.5, "Yes."),
+            cue(2, 0.6, 1.2, "No."),
+            cue(3, 1.3, 3.0, "Maybe we should talk about it."),
+            cue(4, 10.0, 10.4, "Hm."),
+        ];
+        let merged = merge_short(&cues, &OPTIONS);
+
+        let texts: Vec<&str> = merged.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, vec!["Yes. No.", "Maybe we should talk about it.", "Hm."]);
+        assert_eq!(merged[0].end, 1.2);
+        assert_eq!(merged[2].index, 3);
+    }
+
+    #[test]
+    fn test_merge_respects_max_chars() {
+        let cues = vec![
+            cue(1, 0.0, 0.5, "Short."),
+            cue(2, 0.6, 3.0, "This one is already pretty long on its own"),
+        ];
+        assert_eq!(merge_short(&cues, &OPTIONS).len(), 2);
+    }
+
+    #[test]
+    fn test_split_at_sentence_boundary() {
+        let cues = vec![cue(1, 0.0, 4.0, "I never said that. You must have misheard me.")];
+        let split = split_long(&cues, "en", &OPTIONS);
+
+        assert_eq!(split.len(), 2);
+        assert_eq!(split[0].text, "I never said that.");
+        assert_eq!(split[1].text, "You must have misheard me.");
+        assert_eq!(split[0].end, split[1].start);
+        assert!(split[0].end > 1.0 && split[0].end < 2.5);
+        assert_eq!((split[0].index, split[1].index), (1, 2));
+    }
+
+    #[test]
+    fn test_split_long_duration_without_punctuation() {
+        let cues = vec![cue(1, 0.0, 10.0, "words without any punctuation at all")];
+        let split = split_long(&cues, "en", &OPTIONS);
+
+        assert!(split.len() >= 2);
+        assert!(split.iter().all(|c| c.end - c.start <= 7.0));
+        assert_eq!(split.last().unwrap().end, 10.0);
+    }
+
+    #[test]
+    fn test_single_word_not_split() {
+        let cues = vec![cue(1, 0.0, 10.0, "Aaaaaaaah")];
+        assert_eq!(split_long(&cues, "en", &OPTIONS).len(), 1);
+    }
+}
@@ -0,0 +1,352 @@
+use std::ops::Range;
+
+use super::ass::DEFAULT_FORMAT;
+use super::parse_timestamp;
+use super::writer::{format_ass_timestamp, format_timestamp};
+use crate::error::ApiError;
+use crate::models::{Cue, CueWarning, CueWarningKind, SubtitleFormat, SyncPoint};
+
+/// Linear time mapping: new = old * scale + offset
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeTransform {
+    pub scale: f64,
+    pub offset: f64,
+}
+
+impl TimeTransform {
+    pub fn new(scale: f64, offset: f64) -> Result<Self, ApiError> {
+        if !scale.is_finite() || scale <= 0.0 {
+            return Err(ApiError::invalid_input(format!("Scale must be positive, got {}", scale)).with_field("scale"));
+        }
+        if !offset.is_finite() {
+            return Err(ApiError::invalid_input("Offset must be a finite number").with_field("offset"));
+        }
+        Ok(TimeTransform { scale, offset })
+    }
+
+    /// Two-point sync: the transform that moves both subtitle times onto their video times
+    pub fn from_sync_points(points: &[SyncPoint]) -> Result<Self, ApiError> {
+        let [first, second] = points else {
+            return Err(ApiError::invalid_input(format!("Two-point sync needs exactly 2 sync points, got {}", points.len())).with_field("sync_points"));
+        };
+
+        let subtitle_span = second.subtitle_time - first.subtitle_time;
+        if subtitle_span.abs() < 1e-9 {
+            return Err(ApiError::invalid_input("Sync points must be at different subtitle times").with_field("sync_points"));
+        }
+
+        let scale = (second.video_time - first.video_time) / subtitle_span;
+        let offset = first.video_time - first.subtitle_time * scale;
+
+        TimeTransform::new(scale, offset)
+    }
+
+    pub fn apply(&self, seconds: f64) -> f64 {
+        seconds * self.scale + self.offset
+    }
+}
+
+/// Move every cue through `transform`
+///
+/// Cues shifted to before 0:00 are clamped to start at zero; cues that end
+/// up entirely before zero are dropped and reported.
+pub fn resync(cues: &[Cue], transform: &TimeTransform) -> (Vec<Cue>, Vec<CueWarning>) {
+    let mut warnings = Vec::new();
+    let mut shifted = Vec::with_capacity(cues.len());
+
+    for cue in cues {
+        let start = transform.apply(cue.start);
+        let end = transform.apply(cue.end);
+
+        if end <= 0.0 {
+            warnings.push(CueWarning {
+                cue_index: cue.index,
+                kind: CueWarningKind::InvalidTiming,
+                message: format!("Cue moved to before the start of the video ({} → {}), dropped", cue.end, end),
+            });
+            continue;
+        }
+
+        shifted.push(Cue {
+            start: start.max(0.0),
+            end,
+            ..cue.clone()
+        });
+    }
+
+    (shifted, warnings)
+}
+
+/// Move every cue of a subtitle file through `transform`, rewriting nothing
+/// but its timestamps
+///
+/// Cue numbers, text, tags, VTT cue settings, ASS fields and line endings
+/// are kept byte for byte. As in `resync`, starts before 0:00 are clamped
+/// and cues that end up entirely before zero are removed (without
+/// renumbering the rest). Returns the file and how many cues it kept.
+pub fn retime(content: &str, format: SubtitleFormat, transform: &TimeTransform) -> (String, usize, Vec<CueWarning>) {
+    match format {
+        SubtitleFormat::Srt => retime_blocks(content, |seconds| format_timestamp(seconds, ','), transform),
+        SubtitleFormat::Vtt => retime_blocks(content, |seconds| format_timestamp(seconds, '.'), transform),
+        SubtitleFormat::Ass => retime_events(content, transform),
+    }
+}
+
+/// What became of one cue's timing line
+enum Retimed {
+    Moved(String),
+    /// Ends before the start of the video: `(old end, new end)`
+    Dropped(f64, f64),
+    Unreadable,
+}
+
+/// SRT and WebVTT: blank-line separated blocks with a "start --> end" line
+fn retime_blocks(content: &str, timestamp: fn(f64) -> String, transform: &TimeTransform) -> (String, usize, Vec<CueWarning>) {
+    let mut out = String::with_capacity(content.len());
+    let mut warnings = Vec::new();
+    let (mut number, mut kept) = (0, 0);
+    let is_blank = |line: &&str| line.trim().is_empty();
+
+    let mut lines = content.split_inclusive('\n').peekable();
+    while let Some(line) = lines.next() {
+        if is_blank(&line) {
+            out.push_str(line);
+            continue;
+        }
+        let mut block = vec![line];
+        while let Some(line) = lines.next_if(|line| !is_blank(line)) {
+            block.push(line);
+        }
+
+        let Some(timing) = block.iter().take(2).position(|line| line.contains("-->")) else {
+            block.iter().for_each(|line| out.push_str(line));
+            continue;
+        };
+        number += 1;
+
+        let line = block[timing];
+        let retimed = match timing_ranges(line) {
+            Some((start, end)) => splice(line, start, end, timestamp, transform),
+            None => Retimed::Unreadable,
+        };
+        match retimed {
+            Retimed::Moved(moved) => {
+                kept += 1;
+                block[..timing].iter().for_each(|line| out.push_str(line));
+                out.push_str(&moved);
+                block[timing + 1..].iter().for_each(|line| out.push_str(line));
+            }
+            Retimed::Dropped(end, moved) => {
+                warnings.push(dropped(number, end, moved));
+                while lines.next_if(is_blank).is_some() {}
+            }
+            Retimed::Unreadable => {
+                warnings.push(unreadable(number, line));
+                block.iter().for_each(|line| out.push_str(line));
+            }
+        }
+    }
+
+    (out, kept, warnings)
+}
+
+/// Byte ranges of the start and end timestamps in "start --> end settings"
+fn timing_ranges(line: &str) -> Option<(Range<usize>, Range<usize>)> {
+    let arrow = line.find("-->")?;
+    let start = trimmed(line, 0..arrow);
+    let after = arrow + "-->".len();
+    let end_offset = after + line[after..].find(|c: char| !c.is_whitespace())?;
+    let end_length = line[end_offset..].find(char::is_whitespace).unwrap_or(line.len() - end_offset);
+    Some((start, end_offset..end_offset + end_length))
+}
+
+/// ASS/SSA: the Start and End fields of every `Dialogue:` and `Comment:` event
+fn retime_events(content: &str, transform: &TimeTransform) -> (String, usize, Vec<CueWarning>) {
+    let mut out = String::with_capacity(content.len());
+    let mut warnings = Vec::new();
+    let (mut number, mut kept) = (0, 0);
+    let mut in_events = false;
+    let mut format: Vec<String> = DEFAULT_FORMAT.iter().map(|field| field.to_string()).collect();
+
+    for line in content.split_inclusive('\n') {
+        let trimmed_line = line.trim();
+        if trimmed_line.starts_with('[') {
+            in_events = trimmed_line.eq_ignore_ascii_case("[events]");
+        }
+        let kind = trimmed_line.split_once(':').map(|(kind, _)| kind.trim().to_lowercase());
+        if !in_events || !matches!(kind.as_deref(), Some("dialogue" | "comment")) {
+            if in_events && kind.as_deref() == Some("format") {
+                let (_, fields) = trimmed_line.split_once(':').unwrap_or_default();
+                format = fields.split(',').map(|field| field.trim().to_lowercase()).collect();
+            }
+            out.push_str(line);
+            continue;
+        }
+        if kind.as_deref() == Some("dialogue") {
+            number += 1;
+        }
+
+        let retimed = match event_ranges(line, &format) {
+            Some((start, end)) => splice(line, start, end, format_ass_timestamp, transform),
+            None => Retimed::Unreadable,
+        };
+        match retimed {
+            Retimed::Moved(moved) => {
+                kept += usize::from(kind.as_deref() == Some("dialogue"));
+                out.push_str(&moved);
+            }
+            Retimed::Dropped(end, moved) => warnings.push(dropped(number, end, moved)),
+            Retimed::Unreadable => {
+                warnings.push(unreadable(number, line));
+                out.push_str(line);
+            }
+        }
+    }
+
+    (out, kept, warnings)
+}
+
+/// Byte ranges of an event's Start and End fields, by the section's `Format:`
+/// line (or the ASS v4+ default)
+fn event_ranges(line: &str, format: &[String]) -> Option<(Range<usize>, Range<usize>)> {
+    let position = |name: &str| format.iter().position(|field| field == name);
+    let (start, end) = (position("start")?, position("end")?);
+
+    let values = line.find(':')? + 1;
+    let mut fields = Vec::new();
+    let mut from = values;
+    for (comma, _) in line[values..].match_indices(',') {
+        fields.push(from..values + comma);
+        from = values + comma + 1;
+    }
+    fields.push(from..line.len());
+
+    Some((trimmed(line, fields.get(start)?.clone()), trimmed(line, fields.get(end)?.clone())))
+}
+
+/// `range` of `line` without surrounding whitespace
+fn trimmed(line: &str, range: Range<usize>) -> Range<usize> {
+    let value = &line[range.clone()];
+    let start = range.start + (value.len() - value.trim_start().len());
+    start..start + value.trim().len()
+}
+
+/// `line` with the timestamps at `start` and `end` moved through `transform`
+fn splice(line: &str, start: Range<usize>, end: Range<usize>, timestamp: fn(f64) -> String, transform: &TimeTransform) -> Retimed {
+    let (Some(old_start), Some(old_end)) = (parse_timestamp(&line[start.clone()]), parse_timestamp(&line[end.clone()])) else {
+        return Retimed::Unreadable;
+    };
+    let (new_start, new_end) = (transform.apply(old_start), transform.apply(old_end));
+    if new_end <= 0.0 {
+        return Retimed::Dropped(old_end, new_end);
+    }
+
+    let mut spans = [(start, timestamp(new_start.max(0.0))), (end, timestamp(new_end))];
+    spans.sort_by_key(|(range, _)| std::cmp::Reverse(range.start));
+    let mut moved = line.to_string();
+    for (range, value) in spans {
+        moved.replace_range(range, &value);
+    }
+    Retimed::Moved(moved)
+}
+
+fn dropped(cue_index: usize, end: f64, moved: f64) -> CueWarning {
+    CueWarning {
+        cue_index,
+        kind: CueWarningKind::InvalidTiming,
+        message: format!("Cue moved to before the start of the video ({} → {}), dropped", end, moved),
+    }
+}
+
+fn unreadable(cue_index: usize, line: &str) -> CueWarning {
+    CueWarning {
+        cue_index,
+        kind: CueWarningKind::MalformedTimestamp,
+        message: format!("Unreadable timing line {:?}, left as is", line.trim_end()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::cue;
+
+    #[test]
+    fn test_offset_only() {
+        let transform = TimeTransform::new(1.0, 2.5).unwrap();
+        let (cues, warnings) = resync(&[cue(1, 1.0, 2.0, "x")], &transform);
+
+        assert!(warnings.is_empty());
+        assert_eq!((cues[0].start, cues[0].end), (3.5, 4.5));
+    }
+
+    #[test]
+    fn test_two_point_sync() {
+        // 25 fps subtitles played at 23.976: drift grows linearly
+        let points = [
+            SyncPoint { subtitle_time: 10.0, video_time: 12.0 },
+            SyncPoint { subtitle_time: 110.0, video_time: 116.0 },
+        ];
+        let transform = TimeTransform::from_sync_points(&points).unwrap();
+
+        assert!((transform.scale - 1.04).abs() < 1e-9);
+        assert!((transform.apply(10.0) - 12.0).abs() < 1e-9);
+        assert!((transform.apply(110.0) - 116.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_negative_shift_clamps_and_drops() {
+        let transform = TimeTransform::new(1.0, -3.0).unwrap();
+        let (cues, warnings) = resync(&[cue(1, 1.0, 2.0, "x"), cue(2, 2.5, 4.0, "x")], &transform);
+
+        assert_eq!(cues.len(), 1);
+        assert_eq!(cues[0].index, 2);
+        assert_eq!(cues[0].start, 0.0);
+        assert_eq!(warnings[0].cue_index, 1);
+    }
+
+    #[test]
+    fn test_invalid_transforms() {
+        assert!(TimeTransform::new(0.0, 0.0).is_err());
+        assert!(TimeTransform::from_sync_points(&[SyncPoint { subtitle_time: 1.0, video_time: 1.0 }]).is_err());
+
+        let same_time = [
+            SyncPoint { subtitle_time: 5.0, video_time: 1.0 },
+            SyncPoint { subtitle_time: 5.0, video_time: 2.0 },
+        ];
+        assert!(TimeTransform::from_sync_points(&same_time).is_err());
+    }
+
+    #[test]
+    fn test_retime_keeps_everything_but_timestamps() {
+        let srt = "7\r\n00:00:01,000 --> 00:00:02,000 X1:10\r\n<i>Hello</i>,  world\r\n\r\n9\r\n00:00:05,000 --> 00:00:06,500\r\n{\\an8}Bye\r\n";
+        let transform = TimeTransform::new(1.0, 2.5).unwrap();
+        let (content, kept, warnings) = retime(srt, SubtitleFormat::Srt, &transform);
+
+        assert_eq!(content, "7\r\n00:00:03,500 --> 00:00:04,500 X1:10\r\n<i>Hello</i>,  world\r\n\r\n9\r\n00:00:07,500 --> 00:00:09,000\r\n{\\an8}Bye\r\n");
+        assert_eq!(kept, 2);
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_retime_drops_cues_before_zero() {
+        let vtt = "WEBVTT\n\nintro\n00:01.000 --> 00:02.000 align:start\n<v Ann>Hi\n\n00:04.000 --> 00:05.000\nThere\n";
+        let transform = TimeTransform::new(1.0, -3.0).unwrap();
+        let (content, kept, warnings) = retime(vtt, SubtitleFormat::Vtt, &transform);
+
+        assert_eq!(content, "WEBVTT\n\n00:00:01.000 --> 00:00:02.000\nThere\n");
+        assert_eq!(kept, 1);
+        assert_eq!(warnings[0].cue_index, 1);
+    }
+
+    #[test]
+    fn test_retime_ass_events() {
+        let ass = "[Script Info]\nTitle: x\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 0,0:00:01.00,0:00:02.50,Default,,0,0,0,,{\\i1}Hi,\\Nthere\n";
+        let transform = TimeTransform::new(2.0, 0.0).unwrap();
+        let (content, kept, _) = retime(ass, SubtitleFormat::Ass, &transform);
+
+        assert!(content.ends_with("Dialogue: 0,0:00:02.00,0:00:05.00,Default,,0,0,0,,{\\i1}Hi,\\Nthere\n"));
+        assert!(content.starts_with("[Script Info]\nTitle: x\n"));
+        assert_eq!(kept, 1);
+    }
+}
@@ -0,0 +1,161 @@
+use super::{parse_timestamp, strip_tags};
+use crate::error::{ApiError, ErrorCode};
+use crate::models::{Cue, CueWarning, CueWarningKind};
+
+/// Parse SRT content (already BOM-stripped and LF-normalized)
+///
+/// # How it works:
+/// 1. Split into blocks on blank lines
+/// 2. Each block: optional index line, a "start --> end" line, then text
+/// 3. Blocks without a timing line are treated as a blank line inside the
+///    previous cue's text rather than dropped
+///
+/// Missing, non-numeric or duplicate indices are replaced with the next
+/// sequential number and reported as warnings.
+pub fn parse(content: &str) -> Result<(Vec<Cue>, Vec<CueWarning>), ApiError> {
+    let mut cues: Vec<Cue> = Vec::new();
+    let mut warnings = Vec::new();
+
+    for block in content.split("\n\n") {
+        let lines: Vec<&str> = block.lines()
+            .map(|line| line.trim_end())
+            .skip_while(|line| line.trim().is_empty())
+            .collect();
+
+        if lines.is_empty() {
+            continue;
+        }
+
+        let next_index = cues.last().map(|cue| cue.index + 1).unwrap_or(1);
+
+        // Step 2: Find the timing line (first or second line of the block)
+        let Some(timing_line) = lines.iter().take(2).position(|line| line.contains("-->")) else {
+            match cues.last_mut() {
+                Some(previous) => {
+                    previous.text = format!("{}\n\n{}", previous.text, strip_tags(&lines.join("\n")));
+                }
+                None => warnings.push(CueWarning {
+                    cue_index: next_index,
+                    kind: CueWarningKind::MalformedTimestamp,
+                    message: format!("Block without timing line skipped: {:?}", lines[0]),
+                }),
+            }
+            continue;
+        };
+
+        let index = match (timing_line, lines[0].trim().parse::<usize>()) {
+            (1, Ok(index)) if cues.last().is_none_or(|cue| index > cue.index) => index,
+            (1, Ok(index)) => {
+                warnings.push(CueWarning {
+                    cue_index: next_index,
+                    kind: CueWarningKind::MalformedIndex,
+                    message: format!("Index {} out of sequence, renumbered to {}", index, next_index),
+                });
+                next_index
+            }
+            _ => {
+                let found = if timing_line == 1 { lines[0].trim() } else { "" };
+                warnings.push(CueWarning {
+                    cue_index: next_index,
+                    kind: CueWarningKind::MalformedIndex,
+                    message: format!("Missing or invalid index {:?}, numbered {}", found, next_index),
+                });
+                next_index
+            }
+        };
+
+        let Some((start, end)) = parse_timing_line(lines[timing_line]) else {
+            warnings.push(CueWarning {
+                cue_index: index,
+                kind: CueWarningKind::MalformedTimestamp,
+                message: format!("Unreadable timing line {:?}, cue skipped", lines[timing_line]),
+            });
+            continue;
+        };
+
+        cues.push(Cue {
+            index,
+            start,
+            end,
+            text: strip_tags(&lines[timing_line + 1..].join("\n")),
+            style: None,
+            actor: None,
+        });
+    }
+
+    if cues.is_empty() {
+        return Err(ApiError::new(ErrorCode::InvalidSubtitles, "No cues found in SRT content"));
+    }
+
+    Ok((cues, warnings))
+}
+
+/// "00:00:01,000 --> 00:00:02,500 X1:100 X2:200 Y1:10 Y2:50" → (1.0, 2.5)
+///
+/// Trailing position coordinates are ignored.
+fn parse_timing_line(line: &str) -> Option<(f64, f64)> {
+    let (start, rest) = line.split_once("-->")?;
+    let end = rest.split_whitespace().next()?;
+
+    Some((parse_timestamp(start)?, parse_timestamp(end)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_basic() {
+        let content = "1\n00:00:01,000 --> 00:00:02,500\nHello there\n\n2\n00:00:03,000 --> 00:00:04,000\n<i>General</i>\nKenobi\n";
+        let (cues, warnings) = parse(content).unwrap();
+
+        assert!(warnings.is_empty());
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].start, 1.0);
+        assert_eq!(cues[0].end, 2.5);
+        assert_eq!(cues[1].text, "General\nKenobi");
+    }
+
+    #[test]
+    fn test_position_coordinates_ignored() {
+        let content = "1\n00:00:01,000 --> 00:00:02,000 X1:100 X2:200 Y1:10 Y2:50\n{\\an8}Top\n";
+        let (cues, _) = parse(content).unwrap();
+
+        assert_eq!(cues[0].end, 2.0);
+        assert_eq!(cues[0].text, "Top");
+    }
+
+    #[test]
+    fn test_malformed_indices_renumbered() {
+        let content = "00:00:01,000 --> 00:00:02,000\nNo index\n\nabc\n00:00:03,000 --> 00:00:04,000\nBad index\n\n1\n00:00:05,000 --> 00:00:06,000\nDuplicate\n";
+        let (cues, warnings) = parse(content).unwrap();
+
+        let indices: Vec<usize> = cues.iter().map(|cue| cue.index).collect();
+        assert_eq!(indices, vec![1, 2, 3]);
+        assert_eq!(warnings.len(), 3);
+        assert!(warnings.iter().all(|w| w.kind == CueWarningKind::MalformedIndex));
+    }
+
+    #[test]
+    fn test_blank_line_inside_cue_text() {
+        let content = "1\n00:00:01,000 --> 00:00:02,000\nFirst line\n\nafter blank\n\n2\n00:00:03,000 --> 00:00:04,000\nNext\n";
+        let (cues, _) = parse(content).unwrap();
+
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].text, "First line\n\nafter blank");
+    }
+
+    #[test]
+    fn test_bad_timestamp_skipped() {
+        let content = "1\n00:00:xx,000 --> 00:00:02,000\nBroken\n\n2\n00:00:03,000 --> 00:00:04,000\nFine\n";
+        let (cues, warnings) = parse(content).unwrap();
+
+        assert_eq!(cues.len(), 1);
+        assert_eq!(warnings[0].kind, CueWarningKind::MalformedTimestamp);
+    }
+
+    #[test]
+    fn test_empty_content_rejected() {
+        assert!(parse("\n\n").is_err());
+    }
+}
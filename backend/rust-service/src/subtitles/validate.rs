@@ -0,0 +1,185 @@
+use super::writer::reading_chars;
+use crate::models::{
+    Cue, CueWarning, CueWarningKind, Severity, ValidationCode, ValidationIssue, ValidationReport,
+};
+
+/// Thresholds a file is checked against
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub max_cps: f64,
+    pub max_lines: usize,
+    pub max_line_length: usize,
+    pub min_gap: f64,
+}
+
+impl Default for Limits {
+    /// Common broadcast guidelines (Netflix-style adult limits)
+    fn default() -> Self {
+        Limits { max_cps: 17.0, max_lines: 2, max_line_length: 42, min_gap: 0.083 }
+    }
+}
+
+/// Reading speed this far over the limit is unreadable, not just fast
+const CPS_ERROR_FACTOR: f64 = 1.5;
+
+/// Run standard subtitle QC checks over parsed cues
+///
+/// Checks, in file order:
+/// - zero or negative durations (error)
+/// - cues overlapping an earlier cue (error)
+/// - gaps between consecutive cues shorter than `min_gap` (warning)
+/// - reading speed over `max_cps` (warning, error past 1.5× the limit)
+/// - more than `max_lines` lines, or lines over `max_line_length` (warning)
+///
+/// `parse_warnings` from reading the file are included too: renumbered
+/// indices as info, anything else (e.g. skipped cues) as warnings.
+pub fn validate(cues: &[Cue], parse_warnings: &[CueWarning], limits: &Limits) -> ValidationReport {
+    let mut issues: Vec<ValidationIssue> = parse_warnings.iter()
+        .filter(|warning| warning.kind != CueWarningKind::Overlap)
+        .map(|warning| ValidationIssue {
+            cue_index: warning.cue_index,
+            severity: match warning.kind {
+                CueWarningKind::MalformedIndex => Severity::Info,
+                _ => Severity::Warning,
+            },
+            code: ValidationCode::ParseProblem,
+            message: warning.message.clone(),
+        })
+        .collect();
+
+    let mut latest: Option<&Cue> = None;
+
+    for cue in cues {
+        let duration = cue.end - cue.start;
+        let mut issue = |severity, code, message| {
+            issues.push(ValidationIssue { cue_index: cue.index, severity, code, message });
+        };
+
+        if duration <= 0.0 {
+            issue(Severity::Error, ValidationCode::NegativeDuration,
+                format!("Cue ends at {} but starts at {}", cue.end, cue.start));
+        }
+
+        if let Some(previous) = latest {
+            let gap = cue.start - previous.end;
+            if gap < -1e-6 {
+                issue(Severity::Error, ValidationCode::Overlap,
+                    format!("Overlaps cue {} by {:.3}s", previous.index, -gap));
+            } else if gap < limits.min_gap {
+                issue(Severity::Warning, ValidationCode::GapTooShort,
+                    format!("Only {:.3}s after cue {}, minimum is {}s", gap.max(0.0), previous.index, limits.min_gap));
+            }
+        }
+
+        if duration > 0.0 {
+            let cps = reading_chars(&cue.text) as f64 / duration;
+            if cps > limits.max_cps {
+                let severity = if cps > limits.max_cps * CPS_ERROR_FACTOR { Severity::Error } else { Severity::Warning };
+                issue(severity, ValidationCode::CpsExceeded,
+                    format!("Reads at {:.1} characters/second, limit is {}", cps, limits.max_cps));
+            }
+        }
+
+        let lines: Vec<&str> = cue.text.lines().collect();
+        if lines.len() > limits.max_lines {
+            issue(Severity::Warning, ValidationCode::TooManyLines,
+                format!("{} lines, limit is {}", lines.len(), limits.max_lines));
+        }
+
+        if let Some(longest) = lines.iter().map(|line| line.chars().count()).max()
+            && longest > limits.max_line_length
+        {
+            issue(Severity::Warning, ValidationCode::LineTooLong,
+                format!("Line of {} characters, limit is {}", longest, limits.max_line_length));
+        }
+
+        if latest.is_none_or(|previous| cue.end > previous.end) {
+            latest = Some(cue);
+        }
+    }
+
+    let error_count = issues.iter().filter(|i| i.severity == Severity::Error).count();
+    let warning_count = issues.iter().filter(|i| i.severity == Severity::Warning).count();
+
+    ValidationReport {
+        valid: error_count == 0,
+        cue_count: cues.len(),
+        error_count,
+        warning_count,
+        issues,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::cue;
+
+    const LIMITS: Limits = Limits { max_cps: 17.0, max_lines: 2, max_line_length: 42, min_gap: 0.083 };
+
+    fn codes(report: &ValidationReport) -> Vec<(usize, ValidationCode)> {
+        report.issues.iter().map(|i| (i.cue_index, i.code)).collect()
+    }
+
+    #[test]
+    fn test_clean_file_is_valid() {
+        let cues = vec![cue(1, 0.0, 2.0, "Hello there"), cue(2, 2.5, 4.0, "General Kenobi")];
+        let report = validate(&cues, &[], &LIMITS);
+
+        assert!(report.valid);
+        assert!(report.issues.is_empty());
+        assert_eq!(report.cue_count, 2);
+    }
+
+    #[test]
+    fn test_timing_problems() {
+        let cues = vec![
+            cue(1, 0.0, 2.0, "a"),
+            cue(2, 1.5, 3.0, "b"),
+            cue(3, 3.02, 4.0, "c"),
+            cue(4, 5.0, 5.0, "d"),
+        ];
+        let report = validate(&cues, &[], &LIMITS);
+
+        assert!(!report.valid);
+        assert_eq!(codes(&report), vec![
+            (2, ValidationCode::Overlap),
+            (3, ValidationCode::GapTooShort),
+            (4, ValidationCode::NegativeDuration),
+        ]);
+        assert_eq!(report.error_count, 2);
+        assert_eq!(report.warning_count, 1);
+    }
+
+    #[test]
+    fn test_layout_problems() {
+        let long_line = "This single line is definitely longer than forty-two characters";
+        let cues = vec![
+            cue(1, 0.0, 10.0, long_line),
+            cue(2, 11.0, 20.0, "one\ntwo\nthree"),
+            cue(3, 21.0, 21.5, "Far too much text for half a second"),
+        ];
+        let report = validate(&cues, &[], &LIMITS);
+
+        assert_eq!(codes(&report), vec![
+            (1, ValidationCode::LineTooLong),
+            (2, ValidationCode::TooManyLines),
+            (3, ValidationCode::CpsExceeded),
+        ]);
+        assert_eq!(report.issues[2].severity, Severity::Error);
+    }
+
+    #[test]
+    fn test_parse_warnings_included() {
+        let parse_warnings = vec![CueWarning {
+            cue_index: 1,
+            kind: CueWarningKind::MalformedIndex,
+            message: "renumbered".to_string(),
+        }];
+        let report = validate(&[cue(1, 0.0, 2.0, "a")], &parse_warnings, &LIMITS);
+
+        assert!(report.valid);
+        assert_eq!(report.issues[0].severity, Severity::Info);
+        assert_eq!(report.warning_count, 0);
+    }
+}
@@ -0,0 +1,109 @@
+use super::{parse_timestamp, strip_tags};
+use crate::error::{ApiError, ErrorCode};
+use crate::models::{Cue, CueWarning, CueWarningKind};
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// `<v Speaker>` / `<v.loud Speaker>` voice span
+static VOICE_PATTERN: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<v(?:\.[^ >]*)?\s+([^>]+)>").unwrap());
+
+/// Parse WebVTT content (already BOM-stripped and LF-normalized)
+///
+/// Header, NOTE, STYLE and REGION blocks are skipped. Cue identifiers are
+/// free-form in VTT, so cues are numbered in file order instead. The first
+/// `<v Speaker>` voice tag of a cue becomes its `actor`.
+pub fn parse(content: &str) -> Result<(Vec<Cue>, Vec<CueWarning>), ApiError> {
+    let mut cues = Vec::new();
+    let mut warnings = Vec::new();
+
+    for (block_number, block) in content.split("\n\n").enumerate() {
+        let lines: Vec<&str> = block.lines()
+            .map(str::trim_end)
+            .skip_while(|line| line.trim().is_empty())
+            .collect();
+
+        let Some(first) = lines.first() else {
+            continue;
+        };
+
+        if (block_number == 0 && first.starts_with("WEBVTT"))
+            || ["NOTE", "STYLE", "REGION"].iter().any(|kind| first.starts_with(kind))
+        {
+            continue;
+        }
+
+        let index = cues.len() + 1;
+
+        let Some(timing_line) = lines.iter().take(2).position(|line| line.contains("-->")) else {
+            warnings.push(CueWarning {
+                cue_index: index,
+                kind: CueWarningKind::MalformedTimestamp,
+                message: format!("Block without timing line skipped: {:?}", first),
+            });
+            continue;
+        };
+
+        // "00:01.000 --> 00:04.000 align:start line:0" → settings after the end time are ignored
+        let timing = lines[timing_line].split_once("-->").and_then(|(start, rest)| {
+            let end = rest.split_whitespace().next()?;
+            Some((parse_timestamp(start)?, parse_timestamp(end)?))
+        });
+
+        let Some((start, end)) = timing else {
+            warnings.push(CueWarning {
+                cue_index: index,
+                kind: CueWarningKind::MalformedTimestamp,
+                message: format!("Unreadable timing line {:?}, cue skipped", lines[timing_line]),
+            });
+            continue;
+        };
+
+        let raw_text = lines[timing_line + 1..].join("\n");
+        let actor = VOICE_PATTERN.captures(&raw_text)
+            .map(|captures| captures[1].trim().to_string());
+
+        cues.push(Cue {
+            index,
+            start,
+            end,
+            text: decode_entities(&strip_tags(&raw_text)),
+            style: None,
+            actor,
+        });
+    }
+
+    if cues.is_empty() {
+        return Err(ApiError::new(ErrorCode::InvalidSubtitles, "No cues found in WebVTT content"));
+    }
+
+    Ok((cues, warnings))
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .replace("&lrm;", "")
+        .replace("&rlm;", "")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_vtt() {
+        let content = "WEBVTT - Episode 1\n\nNOTE translated by volunteers\n\nintro\n00:01.000 --> 00:04.000 align:start\n<v Roger Bingham>We are in New York City\n\n00:00:05.000 --> 00:00:06.500\nFish &amp; chips\n";
+        let (cues, warnings) = parse(content).unwrap();
+
+        assert!(warnings.is_empty());
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].start, 1.0);
+        assert_eq!(cues[0].end, 4.0);
+        assert_eq!(cues[0].text, "We are in New York City");
+        assert_eq!(cues[0].actor.as_deref(), Some("Roger Bingham"));
+        assert_eq!(cues[1].index, 2);
+        assert_eq!(cues[1].text, "Fish & chips");
+    }
+}
@@ -0,0 +1,294 @@
+use crate::models::{Cue, CueWarning, CueWarningKind, SubtitleFormat};
+
+/// Layout rules applied when writing cues
+#[derive(Debug, Clone, Copy)]
+pub struct WriteOptions {
+    /// `None` keeps the cue text exactly as given
+    pub max_line_length: Option<usize>,
+    pub max_lines: usize,
+    /// Characters per second; `None` disables the reading-speed check
+    pub max_cps: Option<f64>,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        WriteOptions {
+            max_line_length: Some(42),
+            max_lines: 2,
+            max_cps: Some(17.0),
+        }
+    }
+}
+
+/// Render cues as SRT, WebVTT or ASS
+///
+/// # How it works:
+/// 1. Re-wrap every cue's text to `max_line_length` (balanced two-line breaks),
+///    unless it is `None`
+/// 2. Cues read faster than `max_cps` are extended into the silence before
+///    the next cue, never overlapping it
+/// 3. Serialize in the requested format
+///
+/// Cues still too fast, or needing more than `max_lines`, are reported as warnings.
+pub fn write(cues: &[Cue], format: SubtitleFormat, options: &WriteOptions) -> (String, Vec<CueWarning>) {
+    let mut warnings = Vec::new();
+    let mut laid_out: Vec<Cue> = Vec::with_capacity(cues.len());
+
+    for (i, cue) in cues.iter().enumerate() {
+        let mut cue = cue.clone();
+
+        if let Some(max_line_length) = options.max_line_length {
+            let lines = wrap_text(&cue.text, max_line_length);
+
+            if lines.len() > options.max_lines {
+                warnings.push(CueWarning {
+                    cue_index: cue.index,
+                    kind: CueWarningKind::TooManyLines,
+                    message: format!("Needs {} lines at {} characters, limit is {}",
+                        lines.len(), max_line_length, options.max_lines),
+                });
+            }
+            cue.text = lines.join("\n");
+        }
+
+        if let Some(max_cps) = options.max_cps {
+            let chars = reading_chars(&cue.text) as f64;
+            let needed = chars / max_cps;
+
+            if cue.end - cue.start < needed {
+                let limit = cues.get(i + 1).map(|next| next.start).unwrap_or(f64::INFINITY);
+                cue.end = (cue.start + needed).min(limit.max(cue.end));
+
+                let cps = chars / (cue.end - cue.start);
+                if cps > max_cps + 1e-9 {
+                    warnings.push(CueWarning {
+                        cue_index: cue.index,
+                        kind: CueWarningKind::CpsExceeded,
+                        message: format!("Reads at {:.1} characters/second, limit is {}", cps, max_cps),
+                    });
+                }
+            }
+        }
+
+        laid_out.push(cue);
+    }
+
+    let content = match format {
+        SubtitleFormat::Srt => write_srt(&laid_out),
+        SubtitleFormat::Vtt => write_vtt(&laid_out),
+        SubtitleFormat::Ass => write_ass(&laid_out),
+    };
+
+    (content, warnings)
+}
+
+impl SubtitleFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            SubtitleFormat::Srt => "application/x-subrip; charset=utf-8",
+            SubtitleFormat::Vtt => "text/vtt; charset=utf-8",
+            SubtitleFormat::Ass => "text/x-ssa; charset=utf-8",
+        }
+    }
+}
+
+/// Characters a viewer reads: everything except line breaks
+pub fn reading_chars(text: &str) -> usize {
+    text.chars().filter(|c| *c != '\n').count()
+}
+
+/// Wrap text into lines of at most `max_len` characters
+///
+/// Dialogue lines ("- Hi" / "- Hello") keep their breaks; anything else is
+/// re-flowed. Text that fits on two lines is split where both lines are
+/// closest in length, which reads better than a greedy break.
+pub fn wrap_text(text: &str, max_len: usize) -> Vec<String> {
+    let original: Vec<&str> = text.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+
+    if original.len() > 1 && original.iter().all(|line| line.starts_with('-')) {
+        return original.iter().flat_map(|line| wrap_paragraph(line, max_len)).collect();
+    }
+
+    wrap_paragraph(&original.join(" "), max_len)
+}
+
+fn wrap_paragraph(text: &str, max_len: usize) -> Vec<String> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let len = |words: &[&str]| words.iter().map(|w| w.chars().count()).sum::<usize>() + words.len().saturating_sub(1);
+
+    if len(&words) <= max_len {
+        return vec![words.join(" ")];
+    }
+
+    // Balanced two-line split
+    let best_split = (1..words.len())
+        .filter(|&i| len(&words[..i]) <= max_len && len(&words[i..]) <= max_len)
+        .min_by_key(|&i| len(&words[..i]).abs_diff(len(&words[i..])));
+
+    if let Some(i) = best_split {
+        return vec![words[..i].join(" "), words[i..].join(" ")];
+    }
+
+    // Greedy fallback for longer text (or words longer than a line)
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+
+    for word in words {
+        if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > max_len {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+
+    lines
+}
+
+/// Seconds → "HH:MM:SS{sep}mmm"
+pub fn format_timestamp(seconds: f64, separator: char) -> String {
+    let total_ms = (seconds.max(0.0) * 1000.0).round() as u64;
+    let (hours, rest) = (total_ms / 3_600_000, total_ms % 3_600_000);
+    let (minutes, rest) = (rest / 60_000, rest % 60_000);
+    let (secs, ms) = (rest / 1000, rest % 1000);
+
+    format!("{:02}:{:02}:{:02}{}{:03}", hours, minutes, secs, separator, ms)
+}
+
+fn write_srt(cues: &[Cue]) -> String {
+    cues.iter()
+        .enumerate()
+        .map(|(i, cue)| format!("{}\n{} --> {}\n{}\n",
+            i + 1,
+            format_timestamp(cue.start, ','),
+            format_timestamp(cue.end, ','),
+            cue.text))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn write_vtt(cues: &[Cue]) -> String {
+    let mut out = String::from("WEBVTT\n");
+
+    for cue in cues {
+        let text = cue.text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+        let text = match &cue.actor {
+            Some(actor) => format!("<v {}>{}", actor, text),
+            None => text,
+        };
+
+        out.push_str(&format!("\n{} --> {}\n{}\n",
+            format_timestamp(cue.start, '.'),
+            format_timestamp(cue.end, '.'),
+            text));
+    }
+
+    out
+}
+
+/// ASS timestamps: "H:MM:SS.cc"
+pub fn format_ass_timestamp(seconds: f64) -> String {
+    let total_cs = (seconds.max(0.0) * 100.0).round() as u64;
+    format!("{}:{:02}:{:02}.{:02}",
+        total_cs / 360_000, (total_cs / 6000) % 60, (total_cs / 100) % 60, total_cs % 100)
+}
+
+fn write_ass(cues: &[Cue]) -> String {
+    let mut out = String::from(
+        "[Script Info]\nScriptType: v4.00+\n\n\
+         [V4+ Styles]\n\
+         Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n\
+         Style: Default,Arial,20,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,2,2,2,10,10,10,1\n\n\
+         [Events]\n\
+         Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n",
+    );
+
+    for cue in cues {
+        out.push_str(&format!("Dialogue: 0,{},{},{},{},0,0,0,,{}\n",
+            format_ass_timestamp(cue.start),
+            format_ass_timestamp(cue.end),
+            cue.style.as_deref().unwrap_or("Default"),
+            cue.actor.as_deref().unwrap_or(""),
+            cue.text.replace('\n', "\\N")));
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::cue;
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(3723.5, ','), "01:02:03,500");
+        assert_eq!(format_timestamp(0.0015, '.'), "00:00:00.002");
+        assert_eq!(format_ass_timestamp(62.5), "0:01:02.50");
+    }
+
+    #[test]
+    fn test_wrap_balanced() {
+        let lines = wrap_text("I don't think that's what he meant at all, to be honest", 42);
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|l| l.chars().count() <= 42));
+        assert!(lines[0].len().abs_diff(lines[1].len()) < 10);
+    }
+
+    #[test]
+    fn test_wrap_keeps_dialogue_lines() {
+        let lines = wrap_text("- Hi\n- Hello", 42);
+        assert_eq!(lines, vec!["- Hi", "- Hello"]);
+    }
+
+    #[test]
+    fn test_write_srt_roundtrip() {
+        let cues = vec![cue(7, 1.0, 3.0, "Hello there"), cue(9, 4.0, 6.0, "General Kenobi")];
+        let options = WriteOptions { max_cps: None, ..Default::default() };
+
+        let (content, warnings) = write(&cues, SubtitleFormat::Srt, &options);
+        let (parsed, _) = crate::subtitles::srt::parse(&content).unwrap();
+
+        assert!(warnings.is_empty());
+        assert!(content.starts_with("1\n00:00:01,000 --> 00:00:03,000\nHello there\n\n2\n"));
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[1].text, "General Kenobi");
+    }
+
+    #[test]
+    fn test_cps_extends_into_gap() {
+        // 33 characters in 1s, next cue 3s later
+        let cues = vec![cue(1, 0.0, 1.0, "This line is far too fast to read"), cue(2, 4.0, 5.0, "Ok")];
+        let (content, warnings) = write(&cues, SubtitleFormat::Vtt, &WriteOptions::default());
+
+        assert!(warnings.is_empty());
+        assert!(content.contains("00:00:00.000 --> 00:00:01.941"));
+    }
+
+    #[test]
+    fn test_cps_warns_when_no_room() {
+        let cues = vec![cue(1, 0.0, 1.0, "This line is far too fast to read"), cue(2, 1.2, 2.0, "Ok")];
+        let (_, warnings) = write(&cues, SubtitleFormat::Srt, &WriteOptions::default());
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].kind, CueWarningKind::CpsExceeded);
+    }
+
+    #[test]
+    fn test_write_ass_parses_back() {
+        let mut spoken = cue(1, 1.0, 2.5, "Line one\nLine two");
+        spoken.actor = Some("Naruto".to_string());
+        let options = WriteOptions { max_line_length: Some(10), max_cps: None, ..Default::default() };
+
+        let (content, _) = write(&[spoken], SubtitleFormat::Ass, &options);
+        let (parsed, _) = crate::subtitles::ass::parse(&content).unwrap();
+
+        assert_eq!(parsed[0].text, "Line one\nLine two");
+        assert_eq!(parsed[0].actor.as_deref(), Some("Naruto"));
+        assert_eq!(parsed[0].end, 2.5);
+    }
+}
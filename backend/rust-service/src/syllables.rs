@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::models::{DataFile, Syllable, SyllabificationMethod};
+use crate::tokenizer::is_cjk_language;
+
+/// Patterns loaded at startup, shared by every request
+static PATTERNS: OnceLock<HyphenationPatterns> = OnceLock::new();
+
+/// Letters that can start a syllable after another consonant ("pr", "bl")
+const LIQUIDS: &[char] = &['l', 'r'];
+const OBSTRUENTS: &[char] = &['b', 'c', 'd', 'f', 'g', 'k', 'p', 't', 'v'];
+
+/// Liang's hyphenation patterns for one language, as used by TeX
+///
+/// On disk (`data/hyphenation/en.pat`) this is the body of a TeX pattern
+/// file: whitespace-separated patterns like `.ach4` or `4b1b`, with `%`
+/// comments. The `\patterns{...}` wrapper is optional; words listed in
+/// `\hyphenation{...}` (`ta-ble`) override the patterns.
+#[derive(Debug, Clone, Default)]
+pub struct Patterns {
+    /// Letters of a pattern → the digit before each letter and after the last
+    patterns: HashMap<String, Vec<u8>>,
+    exceptions: HashMap<String, Vec<usize>>,
+    longest: usize,
+}
+
+impl Patterns {
+    pub fn parse(content: &str) -> Self {
+        let mut parsed = Patterns::default();
+        let mut in_exceptions = false;
+
+        for line in content.lines() {
+            let line = line.split('%').next().unwrap_or_default();
+            for item in line.split_whitespace() {
+                let item = if let Some(rest) = item.strip_prefix("\\patterns{") {
+                    in_exceptions = false;
+                    rest
+                } else if let Some(rest) = item.strip_prefix("\\hyphenation{") {
+                    in_exceptions = true;
+                    rest
+                } else {
+                    item
+                };
+                let item = item.trim_end_matches('}');
+                if item.is_empty() {
+                    continue;
+                }
+
+                if in_exceptions {
+                    let word = item.to_lowercase();
+                    let breaks = word.split('-')
+                        .scan(0, |at, part| {
+                            *at += part.chars().count();
+                            Some(*at)
+                        })
+                        .collect::<Vec<_>>();
+                    parsed.exceptions.insert(word.replace('-', ""), breaks[..breaks.len() - 1].to_vec());
+                } else {
+                    parsed.insert(item);
+                }
+            }
+        }
+
+        parsed
+    }
+
+    fn insert(&mut self, pattern: &str) {
+        let mut letters = String::new();
+        let mut values = vec![0];
+        for c in pattern.chars() {
+            match c.to_digit(10) {
+                Some(digit) => *values.last_mut().unwrap() = digit as u8,
+                None => {
+                    letters.extend(c.to_lowercase());
+                    values.push(0);
+                }
+            }
+        }
+        self.longest = self.longest.max(letters.chars().count());
+        self.patterns.insert(letters, values);
+    }
+
+    /// Char indices where `word` may be split, in order
+    pub fn breaks(&self, word: &str) -> Vec<usize> {
+        let (lower, origin) = lowercase(word);
+        if let Some(breaks) = self.exceptions.get(&lower.iter().collect::<String>()) {
+            return original_breaks(breaks.iter().copied(), &origin);
+        }
+
+        let chars: Vec<char> = std::iter::once('.').chain(lower).chain(std::iter::once('.')).collect();
+        let mut points = vec![0u8; chars.len() + 1];
+        for start in 0..chars.len() {
+            for end in start + 1..=chars.len().min(start + self.longest) {
+                let key: String = chars[start..end].iter().collect();
+                if let Some(values) = self.patterns.get(&key) {
+                    for (offset, value) in values.iter().enumerate() {
+                        points[start + offset] = points[start + offset].max(*value);
+                    }
+                }
+            }
+        }
+
+        // Before word char `i` is `points[i + 1]`, past the leading dot
+        let letters = chars.len() - 2;
+        original_breaks((1..letters).filter(|i| points[i + 1] % 2 == 1), &origin)
+    }
+
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+}
+
+/// All loaded pattern sets, keyed by lowercase language code
+#[derive(Debug, Default)]
+pub struct HyphenationPatterns {
+    languages: HashMap<String, Patterns>,
+    /// Files loaded, by language
+    files: Vec<DataFile>,
+}
+
+impl HyphenationPatterns {
+    /// Load every `<language>.pat` file in `dir`
+    pub fn load_dir(dir: &Path) -> Self {
+        let mut languages = HashMap::new();
+        let mut files = Vec::new();
+
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::info!("No hyphenation patterns loaded from {}: {}", dir.display(), e);
+                return HyphenationPatterns::default();
+            }
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("pat") {
+                continue;
+            }
+            let Some(language) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+
+            match fs::read_to_string(&path) {
+                Ok(content) => {
+                    let patterns = Patterns::parse(&content);
+                    log::info!("Loaded {} hyphenation patterns for '{}'", patterns.len(), language);
+                    files.push(DataFile::new(language, &path, &content, patterns.len()));
+                    languages.insert(language.to_lowercase(), patterns);
+                }
+                Err(e) => log::warn!("Skipping hyphenation patterns {}: {}", path.display(), e),
+            }
+        }
+
+        files.sort_by(|a, b| a.language.cmp(&b.language));
+        HyphenationPatterns { languages, files }
+    }
+
+    /// Number of languages with patterns
+    pub fn len(&self) -> usize {
+        self.languages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.languages.is_empty()
+    }
+
+    /// The files the patterns came from
+    pub fn files(&self) -> &[DataFile] {
+        &self.files
+    }
+
+    /// Patterns for a language (`pt-BR` falls back to `pt`), if loaded
+    pub fn get(&self, language: &str) -> Option<&Patterns> {
+        let language = language.to_lowercase();
+        self.languages.get(&language)
+            .or_else(|| self.languages.get(language.split(['-', '_']).next().unwrap_or_default()))
+    }
+}
+
+/// Load patterns once at startup
+pub fn init(dir: &Path) {
+    let loaded = HyphenationPatterns::load_dir(dir);
+    if PATTERNS.set(loaded).is_err() {
+        log::warn!("Hyphenation patterns already initialised");
+    }
+}
+
+/// Patterns loaded by `init`, or none if it was never called
+pub fn patterns() -> &'static HyphenationPatterns {
+    PATTERNS.get_or_init(HyphenationPatterns::default)
+}
+
+/// How words of `language` are split
+pub fn method(language: &str) -> SyllabificationMethod {
+    if is_cjk_language(language) {
+        SyllabificationMethod::Characters
+    } else if patterns().get(language).is_some() {
+        SyllabificationMethod::Patterns
+    } else {
+        SyllabificationMethod::Vowels
+    }
+}
+
+/// Split one word into syllables, with byte offsets into the word
+pub fn syllabify(word: &str, language: &str) -> Vec<Syllable> {
+    let breaks = match method(language) {
+        SyllabificationMethod::Characters => (1..word.chars().count()).collect(),
+        SyllabificationMethod::Patterns => patterns().get(language).map(|patterns| patterns.breaks(word)).unwrap_or_default(),
+        SyllabificationMethod::Vowels => vowel_breaks(word),
+    };
+
+    let offsets: Vec<usize> = word.char_indices().map(|(offset, _)| offset).chain(std::iter::once(word.len())).collect();
+    let bounds: Vec<usize> = std::iter::once(0).chain(breaks).chain(std::iter::once(offsets.len() - 1)).collect();
+    bounds.windows(2)
+        .map(|pair| {
+            let (start, end) = (offsets[pair[0]], offsets[pair[1]]);
+            Syllable { text: word[start..end].to_string(), start, end }
+        })
+        .collect()
+}
+
+/// Char indices to split at, one syllable per vowel group
+///
+/// For languages without patterns. Consonants between two vowel groups go
+/// to the later syllable if they can start one together (a single
+/// consonant, or an obstruent followed by l or r as in "pro"), otherwise
+/// only the last one does.
+fn vowel_breaks(word: &str) -> Vec<usize> {
+    let (chars, origin) = lowercase(word);
+    let nuclei: Vec<usize> = (0..chars.len())
+        .filter(|&i| is_vowel(chars[i]) && (i == 0 || !is_vowel(chars[i - 1])))
+        .collect();
+
+    let breaks = nuclei.windows(2)
+        .map(|pair| {
+            let vowels_end = (pair[0]..pair[1]).find(|&i| !is_vowel(chars[i])).unwrap_or(pair[1]);
+            let consonants = &chars[vowels_end..pair[1]];
+            let onset = match consonants {
+                [] | [_] => consonants.len(),
+                [.., first, second] if OBSTRUENTS.contains(first) && LIQUIDS.contains(second) => 2,
+                _ => 1,
+            };
+            pair[1] - onset
+        });
+    original_breaks(breaks, &origin)
+}
+
+/// `word` lowercased char by char, with the index of the char in `word`
+/// each lowercase char came from, then the number of chars in `word`
+///
+/// A char can lowercase to several ('İ' to "i̇"), so indices into the
+/// lowercase chars don't line up with the word's own.
+fn lowercase(word: &str) -> (Vec<char>, Vec<usize>) {
+    let mut lower = Vec::new();
+    let mut origin = Vec::new();
+    let mut count = 0;
+    for (index, c) in word.chars().enumerate() {
+        for lowered in c.to_lowercase() {
+            lower.push(lowered);
+            origin.push(index);
+        }
+        count = index + 1;
+    }
+    origin.push(count);
+    (lower, origin)
+}
+
+/// Breaks between lowercase chars (see `lowercase`) as breaks between the
+/// word's chars, leaving out any within one char's lowercase form
+fn original_breaks(breaks: impl IntoIterator<Item = usize>, origin: &[usize]) -> Vec<usize> {
+    breaks.into_iter()
+        .filter(|&at| at > 0 && at < origin.len() && origin[at] != origin[at - 1])
+        .map(|at| origin[at])
+        .collect()
+}
+
+fn is_vowel(c: char) -> bool {
+    "aeiouyàáâãäåæèéêëìíîïòóôõöøùúûüýÿœ".contains(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(syllables: &[Syllable]) -> Vec<&str> {
+        syllables.iter().map(|syllable| syllable.text.as_str()).collect()
+    }
+
+    #[test]
+    fn test_liang_patterns() {
+        // Enough of the TeX English patterns for "hyphenation"
+        let patterns = Patterns::parse("% test\n\\patterns{\nhy3ph he2n hena4 hen5at 1na n2at 1tio 2io o2n\n}\n\\hyphenation{ta-ble}\n");
+        assert_eq!(patterns.breaks("hyphenation"), vec![2, 6]);
+        assert_eq!(patterns.breaks("Table"), vec![2]);
+    }
+
+    #[test]
+    fn test_vowel_groups() {
+        let split = |word: &str| texts(&syllabify(word, "xx")).join("-");
+        assert_eq!(split("palabra"), "pa-la-bra");
+        assert_eq!(split("Instante"), "Ins-tan-te");
+        assert_eq!(split("cuaderno"), "cua-der-no");
+        assert_eq!(split("a"), "a");
+        assert_eq!(split("brr"), "brr");
+    }
+
+    #[test]
+    fn test_offsets_are_bytes() {
+        let syllables = syllabify("canción", "es");
+        assert_eq!(texts(&syllables), vec!["can", "ción"]);
+        assert_eq!((syllables[1].start, syllables[1].end), (3, 8));
+
+        assert_eq!(texts(&syllabify("日本語", "ja")), vec!["日", "本", "語"]);
+    }
+
+    #[test]
+    fn test_chars_that_lowercase_to_several() {
+        // 'İ' lowercases to two chars, which used to shift every index after it
+        assert_eq!(texts(&syllabify("İİİa", "tr")), vec!["İİİa"]);
+        assert_eq!(texts(&syllabify("İstanbul", "tr")), vec!["İs", "tan", "bul"]);
+
+        let patterns = Patterns::parse("hy3ph he2n hena4 hen5at 1na n2at 1tio 2io o2n\n\\hyphenation{İs-tan-bul}");
+        assert_eq!(patterns.breaks("İstanbul"), vec![2, 5]);
+        assert_eq!(patterns.breaks("İhyphenation"), vec![3, 7]);
+    }
+}
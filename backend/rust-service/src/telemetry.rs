@@ -0,0 +1,185 @@
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+use actix_web::http::header::HeaderMap;
+use opentelemetry::global;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+/// Service name reported with every span, unless configured
+pub const DEFAULT_SERVICE_NAME: &str = "dubdub";
+
+/// Headers left out of verbose request logs
+const REDACTED_HEADERS: &[&str] = &["authorization", "proxy-authorization", "x-api-key", "cookie", "set-cookie"];
+
+/// Swaps the log filter at runtime (see `set_log_filter`)
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// Log every request's and response's headers
+static VERBOSE_REQUESTS: AtomicBool = AtomicBool::new(false);
+
+/// Set up logging, and span export when an OTLP endpoint is configured
+///
+/// # How it works:
+/// 1. Log lines (including `log` macros) go to stderr, filtered by `RUST_LOG`
+///    (default `info`)
+/// 2. With an endpoint, spans are batched and sent over OTLP/HTTP to
+///    `{endpoint}/v1/traces`
+/// 3. W3C `traceparent` headers are picked up from incoming requests, so our
+///    spans join the caller's trace
+///
+/// Returns the tracer provider to flush on shutdown, if exporting.
+pub fn init(otlp_endpoint: Option<&str>, service_name: &str) -> Option<SdkTracerProvider> {
+    // Step 1: Filter and log formatting
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let (filter, handle) = reload::Layer::new(filter);
+    let _ = FILTER.set(handle);
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer()
+            .with_writer(std::io::stderr)
+            .with_ansi(std::io::stderr().is_terminal()));
+
+    // Step 2: OTLP export
+    let provider = otlp_endpoint.map(|endpoint| tracer_provider(endpoint, service_name)).transpose();
+
+    match &provider {
+        Ok(Some(provider)) => registry
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer(DEFAULT_SERVICE_NAME)))
+            .init(),
+        _ => registry.init(),
+    }
+    let provider = provider.unwrap_or_else(|e| {
+        log::error!("Tracing export disabled, can't set up OTLP exporter: {}", e);
+        None
+    });
+
+    // Step 3: Trace context propagation
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    if let (Some(provider), Some(endpoint)) = (&provider, otlp_endpoint) {
+        global::set_tracer_provider(provider.clone());
+        log::info!("Exporting traces to {} as '{}'", endpoint, service_name);
+    }
+    provider
+}
+
+fn tracer_provider(endpoint: &str, service_name: &str) -> Result<SdkTracerProvider, String> {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(traces_url(endpoint))
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name(service_name.to_string()).build())
+        .build())
+}
+
+/// `OTEL_EXPORTER_OTLP_ENDPOINT` names the collector; traces go to its `/v1/traces`
+fn traces_url(endpoint: &str) -> String {
+    format!("{}/v1/traces", endpoint.trim_end_matches('/'))
+}
+
+/// The log filter in effect, e.g. `info` or `info,dubdub::tts=debug`
+pub fn log_filter() -> Option<String> {
+    FILTER.get()?.with_current(|filter| filter.to_string()).ok()
+}
+
+/// Replace the log filter (`RUST_LOG` syntax) without restarting
+pub fn set_log_filter(directives: &str) -> Result<(), String> {
+    let filter = EnvFilter::try_new(directives)
+        .map_err(|e| format!("Invalid log filter '{}': {}", directives, e))?;
+    let handle = FILTER.get().ok_or("Logging isn't initialised")?;
+
+    // `log` macros are dropped before reaching the filter above their max level
+    log::set_max_level(log_level(filter.max_level_hint()));
+    handle.reload(filter).map_err(|e| format!("Can't change log filter: {}", e))
+}
+
+fn log_level(level: Option<LevelFilter>) -> log::LevelFilter {
+    match level.unwrap_or(LevelFilter::TRACE).into_level() {
+        None => log::LevelFilter::Off,
+        Some(tracing::Level::ERROR) => log::LevelFilter::Error,
+        Some(tracing::Level::WARN) => log::LevelFilter::Warn,
+        Some(tracing::Level::INFO) => log::LevelFilter::Info,
+        Some(tracing::Level::DEBUG) => log::LevelFilter::Debug,
+        Some(tracing::Level::TRACE) => log::LevelFilter::Trace,
+    }
+}
+
+pub fn verbose_requests() -> bool {
+    VERBOSE_REQUESTS.load(Ordering::Relaxed)
+}
+
+pub fn set_verbose_requests(verbose: bool) {
+    VERBOSE_REQUESTS.store(verbose, Ordering::Relaxed);
+}
+
+/// Headers to append to the access log line: nothing unless verbose
+/// request logging is on
+pub fn verbose_headers(label: &str, headers: &HeaderMap) -> String {
+    if !verbose_requests() {
+        return String::new();
+    }
+    format!(" {} headers [{}]", label, describe_headers(headers))
+}
+
+/// Headers as `name: value` pairs, with credentials hidden
+fn describe_headers(headers: &HeaderMap) -> String {
+    let mut pairs: Vec<String> = headers.iter()
+        .map(|(name, value)| if REDACTED_HEADERS.contains(&name.as_str()) {
+            format!("{}: <redacted>", name)
+        } else {
+            format!("{}: {}", name, String::from_utf8_lossy(value.as_bytes()))
+        })
+        .collect();
+    pairs.sort();
+    pairs.join(", ")
+}
+
+/// Send spans still waiting in the batch
+pub fn shutdown(provider: Option<SdkTracerProvider>) {
+    if let Some(provider) = provider
+        && let Err(e) = provider.shutdown()
+    {
+        log::warn!("Failed to flush traces: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traces_url() {
+        assert_eq!(traces_url("http://collector:4318"), "http://collector:4318/v1/traces");
+        assert_eq!(traces_url("http://collector:4318/"), "http://collector:4318/v1/traces");
+    }
+
+    #[test]
+    fn test_describe_headers_redacts_credentials() {
+        let req = actix_web::test::TestRequest::default()
+            .insert_header(("x-api-key", "secret"))
+            .insert_header(("accept", "application/json"))
+            .to_http_request();
+
+        assert_eq!(describe_headers(req.headers()), "accept: application/json, x-api-key: <redacted>");
+    }
+
+    #[test]
+    fn test_log_level() {
+        assert_eq!(log_level(Some(LevelFilter::DEBUG)), log::LevelFilter::Debug);
+        assert_eq!(log_level(Some(LevelFilter::OFF)), log::LevelFilter::Off);
+        assert_eq!(log_level(None), log::LevelFilter::Trace);
+    }
+}
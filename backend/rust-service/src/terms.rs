@@ -0,0 +1,146 @@
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder, MatchKind};
+
+use crate::resources::HeapSize;
+use crate::tokenizer::is_cjk_language;
+
+/// A list of terms to find in cue text (protected names, multi-word
+/// expressions), compiled once when the list is loaded
+///
+/// Matching runs a single Aho-Corasick automaton over the text, so a cue is
+/// scanned once however many thousands of terms the list has. Overlapping
+/// terms resolve to the longest at each position ("New York City" over "New
+/// York"), and case is ignored: terms and text are both lowercased before
+/// matching, with offsets mapped back to the text as given.
+pub struct TermList {
+    terms: Vec<String>,
+    automaton: AhoCorasick,
+    /// Matches must start and end on word boundaries (languages with spaces)
+    whole_words: bool,
+}
+
+/// Where one term was found, as byte offsets into the text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TermMatch {
+    /// Index into the list's terms
+    pub term: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl TermList {
+    /// Compile `terms` for text in `language`; blank terms are skipped
+    pub fn new<I, S>(terms: I, language: &str) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let terms: Vec<String> = terms.into_iter()
+            .map(Into::into)
+            .map(|term| term.trim().to_string())
+            .filter(|term| !term.is_empty())
+            .collect();
+        let automaton = AhoCorasickBuilder::new()
+            .match_kind(MatchKind::LeftmostLongest)
+            .build(terms.iter().map(|term| lowercase(term).0))
+            .map_err(|e| format!("Can't compile term list: {}", e))?;
+
+        Ok(TermList { terms, automaton, whole_words: !is_cjk_language(language) })
+    }
+
+    pub fn len(&self) -> usize {
+        self.terms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    pub fn term(&self, index: usize) -> &str {
+        &self.terms[index]
+    }
+
+    /// Non-overlapping matches in `text`, left to right
+    pub fn find(&self, text: &str) -> Vec<TermMatch> {
+        let (lower, origin) = lowercase(text);
+        self.automaton.find_iter(&lower)
+            // Part of a char that lowercases to several ('İ' to "i̇") isn't a match
+            .filter(|m| starts_char(&origin, m.start()) && starts_char(&origin, m.end()))
+            .map(|m| TermMatch { term: m.pattern().as_usize(), start: origin[m.start()], end: origin[m.end()] })
+            .filter(|m| !self.whole_words || on_word_boundaries(text, m.start, m.end))
+            .collect()
+    }
+}
+
+impl HeapSize for TermList {
+    fn heap_size(&self) -> usize {
+        self.terms.heap_size() + self.automaton.memory_usage()
+    }
+}
+
+/// `text` lowercased char by char, with the byte offset in `text` of the
+/// char each byte of the lowercase text came from, then `text.len()`
+///
+/// Lowercasing can change a char's length ('K' for the Kelvin sign is
+/// three bytes, 'k' one), so offsets into the lowercase text don't line up
+/// with the original.
+fn lowercase(text: &str) -> (String, Vec<usize>) {
+    let mut lower = String::with_capacity(text.len());
+    let mut origin = Vec::with_capacity(text.len() + 1);
+    for (offset, c) in text.char_indices() {
+        for lowered in c.to_lowercase() {
+            lower.push(lowered);
+            origin.extend(std::iter::repeat_n(offset, lowered.len_utf8()));
+        }
+    }
+    origin.push(text.len());
+    (lower, origin)
+}
+
+/// Whether byte `at` of the lowercase text begins a char of the original
+/// (or is its end)
+fn starts_char(origin: &[usize], at: usize) -> bool {
+    at == 0 || at == origin.len() - 1 || origin[at] != origin[at - 1]
+}
+
+/// Neither side of `text[start..end]` continues a word ("cat" in "concatenate")
+fn on_word_boundaries(text: &str, start: usize, end: usize) -> bool {
+    let before = text[..start].chars().next_back();
+    let after = text[end..].chars().next();
+    !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_longest_whole_word_matches() {
+        let list = TermList::new(["New York", "New York City", "cat", " "], "en").unwrap();
+        assert_eq!(list.len(), 3);
+
+        let text = "Welcome to new york city, said the cat, concatenating";
+        let found: Vec<&str> = list.find(text).iter().map(|m| &text[m.start..m.end]).collect();
+        assert_eq!(found, vec!["new york city", "cat"]);
+        assert_eq!(list.term(list.find(text)[0].term), "New York City");
+    }
+
+    #[test]
+    fn test_case_beyond_ascii() {
+        let list = TermList::new(["Ärzte ohne Grenzen", "i", "Straße"], "de").unwrap();
+        let text = "ÄRZTE OHNE GRENZEN in der STRAßE, İ";
+        let found: Vec<&str> = list.find(text).iter().map(|m| &text[m.start..m.end]).collect();
+        assert_eq!(found, vec!["ÄRZTE OHNE GRENZEN", "STRAßE"]);
+
+        // The Kelvin sign lowercases to a shorter 'k': offsets stay on the text
+        let list = TermList::new(["kilo"], "en").unwrap();
+        let text = "\u{212a}ILO and kilo";
+        assert_eq!(list.find(text), vec![TermMatch { term: 0, start: 0, end: 6 }, TermMatch { term: 0, start: 11, end: 15 }]);
+    }
+
+    #[test]
+    fn test_cjk_matches_inside_words() {
+        let list = TermList::new(["東京タワー"], "ja").unwrap();
+        let text = "あれは東京タワーです";
+        assert_eq!(list.find(text), vec![TermMatch { term: 0, start: 9, end: 24 }]);
+    }
+}
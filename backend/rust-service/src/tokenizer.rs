@@ -1,86 +1,209 @@
-use crate::models::{TokenizeResponse, TokenPosition};
+use crate::error::ApiError;
+use crate::models::{TokenizeResponse, TokenPosition, Warning, WarningCode};
+use crate::stages::{self, Stage};
+use crate::validation;
 use regex::Regex;
+use std::sync::LazyLock;
 use unicode_segmentation::UnicodeSegmentation;
 
+/// How a language is split into tokens
+///
+/// Patterns are statics compiled on first use and shared by every request;
+/// per-language patterns belong here rather than in the tokenizing functions.
+#[derive(Debug, Clone, Copy)]
+enum Segmenter {
+    /// One token per grapheme, for scripts without spaces between words
+    Graphemes,
+    /// Matches of a word pattern
+    Words(&'static LazyLock<Regex>),
+}
+
+/// Words for space-separated languages; the Unicode classes make this slow
+/// to compile, so it's compiled once, for `Segmenter::Words`
+static WORD_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"[\p{L}\p{M}]+(?:['\-][\p{L}\p{M}]+)*").unwrap() //NOte this handles apostrophes and hyphens need to check for other variations if possible
+});
+
+impl Segmenter {
+    fn for_language(language: &str) -> Segmenter {
+        if is_cjk_language(language) {
+            Segmenter::Graphemes
+        } else {
+            Segmenter::Words(&WORD_PATTERN)
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Segmenter::Graphemes => "graphemes",
+            Segmenter::Words(_) => "words",
+        }
+    }
+}
+
+/// A token borrowed from the text it was found in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Token<'a> {
+    pub text: &'a str,
+    /// Byte offsets into the text
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Split text into tokens without copying it
+///
+/// The aligners work on these and only copy each word once, into its
+/// `WordTiming`; use `tokenize_text` when owned tokens are the result.
+pub fn tokens<'a>(text: &'a str, language: &str) -> Vec<Token<'a>> {
+    stages::time(Stage::Tokenization, || match Segmenter::for_language(language) {
+        Segmenter::Graphemes => tokenize_cjk(text),
+        Segmenter::Words(pattern) => tokenize_standard(text, pattern),
+    })
+}
+
 /// Tokenize text based on language
-pub fn tokenize_text(text: &str, language: &str) -> Result<TokenizeResponse, String> {
-    let language_lower = language.to_lowercase();
-    
-    let (tokens, positions) = match language_lower.as_str() {
-        lang if is_cjk_language(lang) => tokenize_cjk(text),
-        _ => tokenize_standard(text),
-    };
+#[tracing::instrument(level = "debug", skip_all, fields(language = %language, bytes = text.len()))]
+pub fn tokenize_text(text: &str, language: &str) -> Result<TokenizeResponse, ApiError> {
+    let tokens = tokens(text, language);
     
     Ok(TokenizeResponse {
         text: text.to_string(),
         language: language.to_string(),
-        tokens,
-        positions,
+        tokens: tokens.iter().map(|token| token.text.to_string()).collect(),
+        positions: tokens.iter().map(|token| TokenPosition { start: token.start, end: token.end }).collect(),
+        warnings: warning(language).into_iter().collect(),
+        content_flags: None,
+        known: None,
+        glosses: None,
+        morphology: None,
     })
 }
 
-/// Check if language uses CJK characters (Chinese, Japanese, Korean)
-fn is_cjk_language(lang: &str) -> bool {
-    matches!(
-        lang,
-        "chinese" | "zh" | "zh-hans" | "zh-hant" | 
-        "japanese" | "ja" | 
-        "korean" | "ko"
-    )
+/// Why `language`'s tokens are only a best guess, if they are
+///
+/// Scripts written without spaces that aren't CJK (Thai, Khmer, ...) have
+/// no segmenter here, so the word pattern returns whole phrases as tokens.
+pub fn warning(language: &str) -> Option<Warning> {
+    let primary = language.trim().to_lowercase();
+    let primary = primary.split(['-', '_']).next().unwrap_or_default();
+    if !validation::is_known_language(language) {
+        Some(Warning::new(WarningCode::DefaultTokenizer,
+            format!("'{}' isn't a language the tokenizer knows, so the text was split on spaces and punctuation", language)))
+    } else if UNSEGMENTED_LANGUAGES.contains(&primary) {
+        Some(Warning::new(WarningCode::DefaultTokenizer,
+            format!("'{}' is written without spaces between words and has no word segmenter yet, so tokens may span several words", language)))
+    } else {
+        None
+    }
 }
 
+/// Languages written without spaces between words, besides CJK: Thai, Lao,
+/// Khmer, Burmese, Tibetan and Dzongkha
+const UNSEGMENTED_LANGUAGES: &[&str] = &["th", "lo", "km", "my", "bo", "dz"];
 
-fn tokenize_cjk(text: &str) -> (Vec<String>, Vec<TokenPosition>) {
-    let mut tokens = Vec::new();
-    let mut positions = Vec::new();
-    let mut current_pos = 0;
-    
-    for grapheme in text.graphemes(true) {
-        let grapheme_str = grapheme.to_string();
-        let grapheme_len = grapheme_str.len();
-        
-
-        if grapheme.trim().is_empty() {
-            current_pos += grapheme_len;
-            continue;
-        }
-        
-        tokens.push(grapheme_str);
-        positions.push(TokenPosition {
-            start: current_pos,
-            end: current_pos + grapheme_len,
-        });
-        
-        current_pos += grapheme_len;
+/// Build the segmenter `language` needs now rather than on its first request;
+/// returns which one it is
+pub fn warm_up(language: &str) -> &'static str {
+    let segmenter = Segmenter::for_language(language);
+    if let Segmenter::Words(pattern) = segmenter {
+        LazyLock::force(pattern);
     }
-    
-    (tokens, positions)
+    segmenter.name()
 }
 
+/// Languages written without spaces between words, as codes or names
+const CJK_LANGUAGES: &[&str] = &[
+    "chinese", "zh", "zh-hans", "zh-hant",
+    "japanese", "ja",
+    "korean", "ko",
+];
+
+/// Check if language uses CJK characters (Chinese, Japanese, Korean), in any case
+pub(crate) fn is_cjk_language(lang: &str) -> bool {
+    CJK_LANGUAGES.iter().any(|cjk| cjk.eq_ignore_ascii_case(lang))
+}
 
-fn tokenize_standard(text: &str) -> (Vec<String>, Vec<TokenPosition>) {
-    let mut tokens = Vec::new();
-    let mut positions = Vec::new();
-    
 
-    let word_pattern = Regex::new(r"[\p{L}\p{M}]+(?:['\-][\p{L}\p{M}]+)*").unwrap(); //NOte this handles apostrophes and hyphens need to check for other variations if possible
+fn tokenize_cjk(text: &str) -> Vec<Token<'_>> {
+    text.grapheme_indices(true)
+        .filter(|(_, grapheme)| !grapheme.trim().is_empty())
+        .map(|(start, grapheme)| Token { text: grapheme, start, end: start + grapheme.len() })
+        .collect()
+}
+
+
+fn tokenize_standard<'a>(text: &'a str, pattern: &Regex) -> Vec<Token<'a>> {
+    pattern.find_iter(text)
+        .map(|mat| Token { text: mat.as_str(), start: mat.start(), end: mat.end() })
+        .collect()
+}
+
+/// Punctuation that ends a sentence
+const SENTENCE_MARKS: &[char] = &['.', '!', '?', '…', '。', '！', '？'];
+
+/// Punctuation that ends a clause (sentence marks count too)
+const CLAUSE_MARKS: &[char] = &[',', ';', ':', '—', '–', '，', '、', '；', '：', '.', '!', '?', '…', '。', '！', '？'];
+
+/// Full-width marks don't need a following space to end a sentence
+const FULL_WIDTH_MARKS: &[char] = &['。', '！', '？', '，', '、', '；', '：'];
+
+/// Byte offsets just after each sentence-ending mark (and any closing quotes),
+/// excluding the end of the text itself
+pub fn sentence_boundaries(text: &str) -> Vec<usize> {
+    boundaries(text, SENTENCE_MARKS)
+}
+
+/// Like `sentence_boundaries`, but also breaking at commas, semicolons, dashes etc.
+pub fn clause_boundaries(text: &str) -> Vec<usize> {
+    boundaries(text, CLAUSE_MARKS)
+}
+
+fn boundaries(text: &str, marks: &[char]) -> Vec<usize> {
+    let mut result = Vec::new();
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let content_end = text.trim_end().len();
+    let mut i = 0;
     
-    for mat in word_pattern.find_iter(text) {
-        let word = mat.as_str().to_string();
-        tokens.push(word);
-        positions.push(TokenPosition {
-            start: mat.start(),
-            end: mat.end(),
-        });
+    while i < chars.len() {
+        let (_, c) = chars[i];
+        if !marks.contains(&c) {
+            i += 1;
+            continue;
+        }
+        
+        let full_width = FULL_WIDTH_MARKS.contains(&c);
+        
+        // Swallow runs like "?!" or "..." and closing quotes/brackets
+        let mut j = i + 1;
+        while j < chars.len() && (marks.contains(&chars[j].1) || "\"'”’»)]」』".contains(chars[j].1)) {
+            j += 1;
+        }
+        
+        let offset = chars.get(j).map(|(offset, _)| *offset).unwrap_or(text.len());
+        let followed_by_space = chars.get(j).is_none_or(|(_, next)| next.is_whitespace());
+        
+        if (followed_by_space || full_width) && offset < content_end {
+            result.push(offset);
+        }
+        i = j;
     }
     
-    (tokens, positions)
+    result
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     
+    #[test]
+    fn test_languages_share_compiled_patterns() {
+        let (Segmenter::Words(en), Segmenter::Words(de)) = (Segmenter::for_language("en"), Segmenter::for_language("de")) else {
+            panic!("expected word segmenters");
+        };
+        assert!(std::ptr::eq(en, de));
+        assert!(matches!(Segmenter::for_language("ja"), Segmenter::Graphemes));
+    }
+    
     #[test]
     fn test_tokenize_english() {
         let result = tokenize_text("Hello, world! How are you?", "en").unwrap();
@@ -94,6 +217,17 @@ mod tests {
         assert_eq!(result.tokens, vec!["I", "don't", "know", "what", "you're", "doing"]);
     }
     
+    #[test]
+    fn test_fallback_warnings() {
+        assert!(tokenize_text("Hello there", "en").unwrap().warnings.is_empty());
+        assert!(tokenize_text("我爱学习中文", "zh").unwrap().warnings.is_empty());
+
+        let thai = tokenize_text("สวัสดีครับ", "th-TH").unwrap();
+        assert_eq!(thai.tokens.len(), 1);
+        assert_eq!(thai.warnings[0].code, WarningCode::DefaultTokenizer);
+        assert!(warning("klingon").unwrap().message.starts_with("'klingon' isn't a language"));
+    }
+
     #[test]
     fn test_tokenize_french() {
         let result = tokenize_text("C'est très bien!", "fr").unwrap();
@@ -130,4 +264,43 @@ mod tests {
             assert_eq!(token, extracted);
         }
     }
+    
+    #[test]
+    fn test_segmenter_per_language() {
+        assert_eq!(warm_up("ZH-Hant"), "graphemes");
+        assert_eq!(warm_up("pt-BR"), "words");
+        assert_eq!(tokenize_text("日本語", "JA").unwrap().tokens.len(), 3);
+    }
+    
+    #[test]
+    fn test_tokens_borrow_from_text() {
+        let text = "Hi, wonderful";
+        let tokens = tokens(text, "en");
+        assert_eq!(tokens[1], Token { text: "wonderful", start: 4, end: 13 });
+        assert!(std::ptr::eq(tokens[1].text, &text[4..13]));
+    }
+    
+    #[test]
+    fn test_sentence_boundaries() {
+        let text = "Hello there! How are you? I'm fine...  Thanks.";
+        assert_eq!(sentence_boundaries(text), vec![12, 25, 37]);
+    }
+    
+    #[test]
+    fn test_sentence_boundaries_ignore_inner_dots() {
+        assert!(sentence_boundaries("It costs 3.50 dollars.").is_empty());
+        assert_eq!(sentence_boundaries("\"Run!\" he said."), vec![6]);
+    }
+    
+    #[test]
+    fn test_cjk_sentence_boundaries() {
+        // Full-width marks end a sentence without a following space
+        assert_eq!(sentence_boundaries("我很好。你呢？"), vec!["我很好。".len()]);
+    }
+    
+    #[test]
+    fn test_clause_boundaries() {
+        let text = "Well, I think so; maybe.";
+        assert_eq!(clause_boundaries(text), vec![5, 17]);
+    }
 }
@@ -0,0 +1,417 @@
+use crate::aligner::{apply_confidence_threshold, find_gaps, language_warnings, mean_confidence, single_cue};
+use crate::calibration;
+use crate::duration::{self, DurationModel};
+use crate::error::{ApiError, ErrorCode};
+use crate::features::Feature;
+use crate::g2p;
+use crate::models::{AlignmentMethod, AlignmentRequest, AlignmentResponse, DurationPredictionRequest, DurationPredictionResponse, PredictedWord, Warning, WarningCode, WordTiming};
+use crate::stages::{self, Stage};
+use crate::tokenizer;
+use crate::upstream::{self, Breaker, Failure, RetryPolicy, DEFAULT_BREAKER_COOLDOWN_SECS, DEFAULT_BREAKER_THRESHOLD};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// External TTS engine that can report word timings, configured at startup
+static ENGINE_URL: OnceLock<Option<String>> = OnceLock::new();
+
+/// Stops asking an engine that keeps failing; requests predict meanwhile
+static ENGINE_BREAKER: OnceLock<Breaker> = OnceLock::new();
+
+/// Client for engine calls, with the timeouts below
+static ENGINE_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+/// Longest an engine call may take, synthesis included; the request then
+/// falls back to prediction
+const ENGINE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Longest to wait for a connection to the engine
+const ENGINE_CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// One retry: a failed engine call falls back to prediction anyway, so
+/// waiting longer only slows the answer down
+const ENGINE_RETRY: RetryPolicy = RetryPolicy { retries: 1, initial_backoff: Duration::from_millis(250) };
+
+/// Body sent to the TTS engine
+#[derive(Debug, Serialize)]
+struct EngineRequest<'a> {
+    text: &'a str,
+    language: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    voice: Option<&'a str>,
+}
+
+/// Expected engine reply: word boundaries in seconds from the start of the utterance
+#[derive(Debug, Deserialize)]
+struct EngineResponse {
+    words: Vec<EngineWord>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EngineWord {
+    start: f64,
+    end: f64,
+}
+
+/// Configure the optional TTS engine (`TTS_ENGINE_URL`)
+pub fn init(engine_url: Option<String>) {
+    if let Some(url) = &engine_url {
+        log::info!("TTS timing engine: {}", url);
+    }
+    if ENGINE_URL.set(engine_url).is_err() {
+        log::warn!("TTS engine already initialised");
+    }
+}
+
+pub fn engine_url() -> Option<&'static str> {
+    ENGINE_URL.get().and_then(|url| url.as_deref())
+}
+
+fn engine_client() -> &'static reqwest::Client {
+    ENGINE_CLIENT.get_or_init(|| reqwest::Client::builder()
+        .timeout(ENGINE_TIMEOUT)
+        .read_timeout(ENGINE_TIMEOUT)
+        .connect_timeout(ENGINE_CONNECT_TIMEOUT)
+        .build()
+        .unwrap_or_default())
+}
+
+fn engine_breaker() -> &'static Breaker {
+    ENGINE_BREAKER.get_or_init(|| Breaker::new("The TTS engine", DEFAULT_BREAKER_THRESHOLD, Duration::from_secs(DEFAULT_BREAKER_COOLDOWN_SECS)))
+}
+
+/// Align text as a TTS voice would speak it
+///
+/// Asks the configured TTS engine for word timings first; if no engine is
+/// configured (or switched off with the `tts_engine` feature flag), or its
+/// answer can't be matched to our tokens, falls back to predicting durations
+/// from the language's duration model.
+pub async fn align_tts(req: &AlignmentRequest) -> Result<AlignmentResponse, ApiError> {
+    single_cue(req)?;
+    let mut response = match engine_url().filter(|_| Feature::TtsEngine.is_enabled()) {
+        Some(url) => match align_with_engine(url, req).await {
+            Ok(response) => response,
+            Err(e) => {
+                log::warn!("TTS engine timing failed, predicting instead: {}", e);
+                let mut response = predict_alignment(req, duration::models().get(&req.language))?;
+                response.warnings.insert(0, Warning::new(WarningCode::TtsEngineFallback, "The TTS engine failed, so timings were predicted from the duration model"));
+                response
+            }
+        },
+        None => predict_alignment(req, duration::models().get(&req.language))?,
+    };
+
+    if let Some(min_confidence) = req.min_confidence {
+        apply_confidence_threshold(&mut response, min_confidence, req.exclude_flagged);
+    }
+
+    Ok(response)
+}
+
+/// Predict word durations from the duration model's speaking rate
+///
+/// # How it works:
+/// 1. Weigh every word and punctuation pause (same weights as the weighted aligner)
+/// 2. Convert weights to seconds using the speaking rate (the request's,
+///    else the voice's profile, else the language's)
+/// 3. Lay the words out from `subtitle_start`; `subtitle_end` is ignored
+pub fn predict_alignment(req: &AlignmentRequest, model: &DurationModel) -> Result<AlignmentResponse, ApiError> {
+    stages::time(Stage::Alignment, || predict(req, model))
+}
+
+fn predict(req: &AlignmentRequest, model: &DurationModel) -> Result<AlignmentResponse, ApiError> {
+    single_cue(req)?;
+
+    let tokens = tokenizer::tokens(&req.text, &req.language);
+
+    if tokens.is_empty() {
+        return Err(ApiError::new(ErrorCode::NoWords, "No words found to align").with_field("text"));
+    }
+
+    let speaking_rate = req.speaking_rate
+        .or_else(|| req.voice.as_deref().and_then(|voice| model.voice_rate(voice)))
+        .unwrap_or_else(|| model.speaking_rate());
+    if speaking_rate <= 0.0 {
+        return Err(ApiError::invalid_input("Speaking rate must be positive").with_field("speaking_rate"));
+    }
+
+    let confidence = calibration::get().confidence(&AlignmentMethod::TtsPrediction, &req.language, tokens.len());
+    let mut timings = Vec::with_capacity(tokens.len());
+    let mut current_time = req.subtitle_start;
+
+    for (i, token) in tokens.iter().enumerate() {
+        let word_duration = model.word_weight(token.text) / speaking_rate;
+
+        timings.push(WordTiming {
+            word: token.text.to_string(),
+            start: current_time,
+            end: current_time + word_duration,
+            confidence,
+            char_start: token.start,
+            char_end: token.end,
+            flagged: false,
+        });
+        current_time += word_duration;
+
+        if let Some(next) = tokens.get(i + 1) {
+            let separator = &req.text[token.end..next.start];
+            current_time += model.pause_weight(separator) / speaking_rate;
+        }
+    }
+
+    let end = current_time;
+
+    Ok(AlignmentResponse {
+        text: req.text.clone(),
+        language: req.language.clone(),
+        duration: end - req.subtitle_start,
+        mean_confidence: mean_confidence(&timings),
+        gaps: find_gaps(&req.text, &timings, req.subtitle_start, end),
+        timings,
+        method: AlignmentMethod::TtsPrediction,
+        n_flagged: 0,
+        warnings: language_warnings(&req.language, true),
+        debug_timings: None,
+    })
+}
+
+/// Predict how long a text takes to say, before anyone records it
+///
+/// # How it works:
+/// 1. Pick the speaking rate: the request's, else the voice's profile,
+///    else the language's
+/// 2. Weigh each word as `predict_alignment` does, scaled by its sounds per
+///    letter when G2P knows its pronunciation ("though" has six letters
+///    but three sounds)
+/// 3. Add the pauses at punctuation and convert to seconds
+///
+/// G2P may run espeak-ng: call from a blocking context.
+pub fn predict_duration(req: &DurationPredictionRequest, model: &DurationModel) -> Result<DurationPredictionResponse, ApiError> {
+    let tokens = tokenizer::tokens(&req.text, &req.language);
+    if tokens.is_empty() {
+        return Err(ApiError::new(ErrorCode::NoWords, "No words found to predict").with_field("text"));
+    }
+
+    // Step 1: Speaking rate
+    let profile_rate = req.voice.as_deref().and_then(|voice| model.voice_rate(voice));
+    let speaking_rate = req.speaking_rate.or(profile_rate).unwrap_or_else(|| model.speaking_rate());
+    if speaking_rate <= 0.0 {
+        return Err(ApiError::invalid_input("Speaking rate must be positive").with_field("speaking_rate"));
+    }
+
+    let mut warnings = Vec::new();
+    let mut pronunciations = if g2p::supports(&req.language) {
+        g2p::phonemize_all(&tokens.iter().map(|token| token.text).collect::<Vec<_>>(), &req.language)
+    } else {
+        warnings.push(Warning::new(WarningCode::NoG2pData,
+            format!("No pronunciations for '{}', so words were weighed by their spelling", req.language)));
+        vec![None; tokens.len()]
+    };
+    let mut words = Vec::with_capacity(tokens.len());
+    let mut pauses = 0.0;
+
+    for (i, token) in tokens.iter().enumerate() {
+        // Step 2: Word weight
+        let ipa = pronunciations[i].take().map(|(ipa, _)| ipa);
+        let mut weight = model.word_weight(token.text);
+        let letters = token.text.chars().filter(|c| c.is_alphabetic()).count();
+        if let Some(ipa) = &ipa && letters > 0 {
+            weight *= n_phonemes(ipa) as f64 / letters as f64;
+        }
+        words.push(PredictedWord {
+            word: token.text.to_string(),
+            char_start: token.start,
+            char_end: token.end,
+            ipa,
+            duration: weight / speaking_rate,
+        });
+
+        // Step 3: Pauses
+        if let Some(next) = tokens.get(i + 1) {
+            pauses += model.pause_weight(&req.text[token.end..next.start]) / speaking_rate;
+        }
+    }
+
+    Ok(DurationPredictionResponse {
+        language: req.language.clone(),
+        voice: req.voice.clone(),
+        speaking_rate,
+        voice_profile: req.speaking_rate.is_none() && profile_rate.is_some(),
+        duration: words.iter().map(|word| word.duration).sum::<f64>() + pauses,
+        pauses,
+        n_phonemized: words.iter().filter(|word| word.ipa.is_some()).count(),
+        words,
+        warnings,
+    })
+}
+
+/// Sounds in an IPA transcription; stress and length marks, diacritics
+/// and syllable breaks don't count
+fn n_phonemes(ipa: &str) -> usize {
+    ipa.chars().filter(|c| c.is_alphabetic() && !matches!(c, '\u{2b0}'..='\u{2ff}')).count()
+}
+
+/// Ask the TTS engine for real word boundaries
+///
+/// The engine's words are matched to our tokens by position, so both
+/// must split the text into the same number of words.
+#[tracing::instrument(skip(req), fields(language = %req.language))]
+async fn align_with_engine(url: &str, req: &AlignmentRequest) -> Result<AlignmentResponse, ApiError> {
+    let tokens = tokenizer::tokens(&req.text, &req.language);
+
+    let body = EngineRequest {
+        text: &req.text,
+        language: &req.language,
+        voice: req.voice.as_deref(),
+    };
+
+    let (client, body) = (engine_client(), &body);
+    let engine: EngineResponse = stages::time_async(Stage::TtsEngine, upstream::call(engine_breaker(), ENGINE_RETRY, || async move {
+        client.post(url)
+            .json(body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| Failure::from_reqwest(e, "TTS engine request failed"))?
+            .json()
+            .await
+            .map_err(|e| Failure::Final(ApiError::new(ErrorCode::Upstream, format!("Invalid TTS engine response: {}", e))))
+    })).await?;
+
+    if engine.words.len() != tokens.len() {
+        return Err(ApiError::new(ErrorCode::Upstream, format!("TTS engine returned {} words, expected {}",
+            engine.words.len(), tokens.len())));
+    }
+
+    let confidence = calibration::get().confidence(&AlignmentMethod::TtsEngine, &req.language, tokens.len());
+    let timings: Vec<WordTiming> = tokens.iter()
+        .zip(&engine.words)
+        .map(|(token, engine_word)| WordTiming {
+            word: token.text.to_string(),
+            start: req.subtitle_start + engine_word.start,
+            end: req.subtitle_start + engine_word.end,
+            confidence,
+            char_start: token.start,
+            char_end: token.end,
+            flagged: false,
+        })
+        .collect();
+
+    let end = timings.last().map(|t| t.end).unwrap_or(req.subtitle_start);
+
+    Ok(AlignmentResponse {
+        text: req.text.clone(),
+        language: req.language.clone(),
+        duration: end - req.subtitle_start,
+        mean_confidence: mean_confidence(&timings),
+        gaps: find_gaps(&req.text, &timings, req.subtitle_start, end),
+        timings,
+        method: AlignmentMethod::TtsEngine,
+        n_flagged: 0,
+        warnings: language_warnings(&req.language, false),
+        debug_timings: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AlignmentMode;
+
+    #[test]
+    fn test_prediction_ignores_subtitle_window() {
+        let req = AlignmentRequest {
+            text: "Hello, world".to_string(),
+            language: "en".to_string(),
+            subtitle_start: 10.0,
+            subtitle_end: 11.0,
+            mode: AlignmentMode::Tts,
+            speaking_rate: Some(10.0),
+            ..Default::default()
+        };
+
+        let result = predict_alignment(&req, &DurationModel::default()).unwrap();
+
+        // 5 chars + comma pause (2) + 5 chars at 10 units/sec
+        assert!((result.duration - 1.2).abs() < 0.01);
+        assert!((result.timings[0].end - 10.5).abs() < 0.01);
+        assert!((result.timings[1].start - 10.7).abs() < 0.01);
+        assert_eq!(result.gaps.len(), 1);
+    }
+
+    #[test]
+    fn test_prediction_uses_model_rate() {
+        let req = AlignmentRequest {
+            text: "abcd".to_string(),
+            language: "xx".to_string(),
+            mode: AlignmentMode::Tts,
+            ..Default::default()
+        };
+
+        let model = DurationModel::default().with_speaking_rate(2.0);
+        let result = predict_alignment(&req, &model).unwrap();
+
+        assert!((result.duration - 2.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_prediction_uses_voice_profile() {
+        let model = DurationModel::default().with_voices(std::collections::HashMap::from([("fast".to_string(), 20.0)]));
+        let req = AlignmentRequest {
+            text: "abcd".to_string(),
+            language: "xx".to_string(),
+            voice: Some("Fast".to_string()),
+            ..Default::default()
+        };
+
+        assert!((predict_alignment(&req, &model).unwrap().duration - 0.2).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_predict_duration() {
+        let req = DurationPredictionRequest {
+            text: "Hola, amigo".to_string(),
+            language: "xx".to_string(),
+            speaking_rate: Some(10.0),
+            ..Default::default()
+        };
+        let response = predict_duration(&req, &DurationModel::default()).unwrap();
+
+        // 4 chars + comma pause (2) + 5 chars at 10 units/sec
+        assert!((response.duration - 1.1).abs() < 0.01);
+        assert!((response.pauses - 0.2).abs() < 0.01);
+        assert_eq!(response.n_phonemized, 0);
+        assert!(!response.voice_profile);
+        assert_eq!(response.warnings.iter().map(|warning| warning.code).collect::<Vec<_>>(), vec![WarningCode::NoG2pData]);
+    }
+
+    #[test]
+    fn test_predict_duration_weighs_sounds() {
+        let req = DurationPredictionRequest {
+            text: "queso".to_string(),
+            language: "es".to_string(),
+            speaking_rate: Some(10.0),
+            ..Default::default()
+        };
+        let response = predict_duration(&req, &DurationModel::default()).unwrap();
+
+        // Five letters, four sounds
+        assert_eq!(response.words[0].ipa.as_deref(), Some("keso"));
+        assert!((response.duration - 0.4).abs() < 0.01);
+        assert!(response.warnings.is_empty());
+        assert_eq!(n_phonemes("ˈθɪŋk"), 4);
+        assert_eq!(n_phonemes("kʰæːt"), 3);
+    }
+
+    #[test]
+    fn test_invalid_speaking_rate() {
+        let req = AlignmentRequest {
+            text: "abcd".to_string(),
+            language: "en".to_string(),
+            speaking_rate: Some(0.0),
+            ..Default::default()
+        };
+
+        assert!(predict_alignment(&req, &DurationModel::default()).is_err());
+    }
+}
@@ -0,0 +1,500 @@
+use std::collections::HashMap;
+
+use actix_multipart::Multipart;
+use actix_web::web;
+use futures::StreamExt;
+use tempfile::NamedTempFile;
+
+use crate::aligner::align_file;
+use crate::audio::{self, AudioBuffer, AudioSource, SpooledSource};
+use crate::error::{ApiError, ErrorCode};
+use crate::models::{
+    AudioSnippetResponse, Cue, CueWarning, CueWarningKind, FileAlignmentRequest, FileAlignmentResponse, OverlapPolicy,
+    PracticePack, PronunciationResponse, SpeechRateResponse, WordTiming,
+};
+use crate::practice;
+use crate::pronunciation;
+use crate::resources;
+use crate::snippets;
+use crate::speech_rate;
+use crate::spool::Spool;
+use crate::stages::{self, Stage};
+use crate::subtitles;
+use crate::usage;
+use crate::validation::Validator;
+
+/// Largest subtitle file accepted in an upload
+const MAX_SUBTITLE_BYTES: usize = 5 * 1024 * 1024;
+
+/// Largest audio file accepted in an upload
+const MAX_AUDIO_BYTES: usize = 200 * 1024 * 1024;
+
+/// Largest plain form field (language, options)
+const MAX_FIELD_BYTES: usize = 1024;
+
+/// Largest `text` or `phrases` field
+const MAX_TEXT_BYTES: usize = 64 * 1024;
+
+/// Largest `timings` field: JSON word timings for a feature-length film
+const MAX_TIMINGS_BYTES: usize = 5 * 1024 * 1024;
+
+/// Longest `audio_url`; presigned URLs carry long signatures
+const MAX_URL_BYTES: usize = 8 * 1024;
+
+pub struct UploadedFile {
+    pub filename: Option<String>,
+    pub bytes: Vec<u8>,
+}
+
+/// Everything posted to the file alignment upload endpoint
+///
+/// Audio past `UPLOAD_MEMORY_BUDGET` is spooled to a temporary file as it
+/// arrives, and only its length is ever measured, so a feature-length file
+/// costs no more memory than the budget.
+///
+/// Form fields:
+/// - `subtitles` (file, required): SRT, WebVTT or ASS/SSA
+/// - `audio` (file, optional): any format the audio module decodes
+/// - `audio_url` (text, optional): instead of `audio`, e.g. an `s3://` URL
+///   from `POST /api/storage/uploads`, so large audio skips the upload
+/// - `language` (text, required)
+/// - `overlap_policy` (text, optional): clamp | merge | keep
+pub struct AlignUpload {
+    pub subtitles: UploadedFile,
+    pub audio: Option<SpooledSource>,
+    pub language: String,
+    pub overlap_policy: OverlapPolicy,
+}
+
+/// A multipart/form-data body: the `subtitles` file, audio uploaded as
+/// `audio` or fetched from `audio_url`, and short text fields
+struct Form {
+    subtitles: Option<UploadedFile>,
+    audio: Option<SpooledSource>,
+    fields: HashMap<String, String>,
+}
+
+impl Form {
+    fn take(&mut self, name: &str) -> Option<String> {
+        self.fields.remove(name).filter(|value| !value.is_empty())
+    }
+}
+
+/// Read a multipart/form-data body, keeping the text fields in `known`
+async fn read_form(mut payload: Multipart, known: &[&str]) -> Result<Form, ApiError> {
+    let mut subtitles = None;
+    let mut audio = None;
+    let mut audio_url = None;
+    let mut fields = HashMap::new();
+
+    while let Some(field) = payload.next().await {
+        let mut field = field.map_err(|e| ApiError::invalid_input(format!("Invalid multipart body: {}", e)))?;
+
+        let name = field.name().unwrap_or_default().to_string();
+        let filename = field.content_disposition()
+            .and_then(|disposition| disposition.get_filename())
+            .map(str::to_string);
+
+        if name == "audio" {
+            let mut spool = Spool::new(MAX_AUDIO_BYTES);
+            while let Some(chunk) = field.next().await {
+                let chunk = chunk.map_err(|e| ApiError::invalid_input(format!("Failed to read field '{}': {}", name, e)).with_field(&name))?;
+                spool.write(&chunk).await.map_err(|e| e.context("Field 'audio'").with_field(&name))?;
+            }
+            audio = Some(SpooledSource {
+                content: spool.finish().await?,
+                format_hint: filename.as_deref()
+                    .and_then(|name| name.rsplit_once('.'))
+                    .map(|(_, ext)| ext.to_lowercase()),
+            });
+            continue;
+        }
+
+        let limit = match name.as_str() {
+            "subtitles" => MAX_SUBTITLE_BYTES,
+            "audio_url" => MAX_URL_BYTES,
+            "text" | "phrases" => MAX_TEXT_BYTES,
+            "timings" => MAX_TIMINGS_BYTES,
+            _ => MAX_FIELD_BYTES,
+        };
+
+        let mut bytes = Vec::new();
+        while let Some(chunk) = field.next().await {
+            let chunk = chunk.map_err(|e| ApiError::invalid_input(format!("Failed to read field '{}': {}", name, e)).with_field(&name))?;
+            if bytes.len() + chunk.len() > limit {
+                return Err(ApiError::new(ErrorCode::PayloadTooLarge, format!("Field '{}' exceeds {} bytes", name, limit))
+                    .with_field(name));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+
+        match name.as_str() {
+            "subtitles" => subtitles = Some(UploadedFile { filename, bytes }),
+            "audio_url" => audio_url = Some(String::from_utf8_lossy(&bytes).trim().to_string()),
+            other if known.contains(&other) => {
+                fields.insert(name, String::from_utf8_lossy(&bytes).trim().to_string());
+            }
+            other => log::debug!("Ignoring unknown upload field '{}'", other),
+        }
+    }
+
+    let audio = match (audio, audio_url.filter(|url| !url.is_empty())) {
+        (Some(_), Some(_)) => return Err(ApiError::invalid_input("Send either 'audio' or 'audio_url', not both")
+            .with_field("audio_url")),
+        (None, Some(url)) => {
+            log::info!("Fetching audio from {}", url.split('?').next().unwrap_or_default());
+            let fetched = stages::time_async(Stage::AudioFetch, audio::fetch(&url, MAX_AUDIO_BYTES)).await;
+            Some(fetched.map_err(|e| e.with_field("audio_url"))?)
+        }
+        (audio, None) => audio,
+    };
+    let audio = match audio {
+        Some(audio) if usage::is_metering() => Some(meter_audio(audio).await?),
+        audio => audio,
+    };
+
+    Ok(Form { subtitles, audio, fields })
+}
+
+/// Count the audio's length against the request's usage
+///
+/// Undecodable audio counts as none; the handler reports why.
+async fn meter_audio(audio: SpooledSource) -> Result<SpooledSource, ApiError> {
+    let (audio, seconds) = web::block(move || {
+        let seconds = audio.duration();
+        (audio, seconds)
+    }).await
+        .map_err(|e| ApiError::internal(format!("Worker error: {}", e)))?;
+
+    usage::audio_seconds(seconds.unwrap_or(0.0));
+    Ok(audio)
+}
+
+/// Read a multipart/form-data body into an `AlignUpload`
+pub async fn read_align_upload(payload: Multipart) -> Result<AlignUpload, ApiError> {
+    let mut form = read_form(payload, &["language", "overlap_policy"]).await?;
+
+    let subtitles = form.subtitles.take().ok_or_else(|| ApiError::invalid_input("Missing 'subtitles' file").with_field("subtitles"))?;
+    let language = form.take("language")
+        .ok_or_else(|| ApiError::invalid_input("Missing 'language' field").with_field("language"))?;
+    let overlap_policy = match form.take("overlap_policy").as_deref() {
+        None => OverlapPolicy::default(),
+        Some("clamp") => OverlapPolicy::Clamp,
+        Some("merge") => OverlapPolicy::Merge,
+        Some("keep") => OverlapPolicy::Keep,
+        Some(other) => return Err(ApiError::invalid_input(format!("Unknown overlap_policy '{}'", other))
+            .with_field("overlap_policy")),
+    };
+
+    Ok(AlignUpload { subtitles, audio: form.audio, language, overlap_policy })
+}
+
+/// Everything posted to the speech rate endpoint
+///
+/// Form fields:
+/// - `audio` (file) or `audio_url` (text): required
+/// - `subtitles` (file): cues to measure, or
+/// - `text` (text): the words of the whole audio, measured as one cue
+/// - `language` (text, required)
+/// - `comfortable_rate` (text, optional): syllables per second
+pub struct SpeechRateUpload {
+    pub audio: SpooledSource,
+    pub subtitles: Option<UploadedFile>,
+    pub text: Option<String>,
+    pub language: String,
+    pub comfortable_rate: f64,
+}
+
+/// Read a multipart/form-data body into a `SpeechRateUpload`
+pub async fn read_speech_rate_upload(payload: Multipart) -> Result<SpeechRateUpload, ApiError> {
+    let mut form = read_form(payload, &["language", "text", "comfortable_rate"]).await?;
+
+    let audio = form.audio.take().ok_or_else(|| ApiError::invalid_input("Missing 'audio' file or 'audio_url'").with_field("audio"))?;
+    let text = form.take("text");
+    match (&form.subtitles, &text) {
+        (Some(_), Some(_)) => return Err(ApiError::invalid_input("Send either 'subtitles' or 'text', not both").with_field("text")),
+        (None, None) => return Err(ApiError::invalid_input("Missing 'subtitles' file or 'text'").with_field("subtitles")),
+        _ => {}
+    }
+    let language = form.take("language")
+        .ok_or_else(|| ApiError::invalid_input("Missing 'language' field").with_field("language"))?;
+    let comfortable_rate = match form.take("comfortable_rate") {
+        None => speech_rate::DEFAULT_COMFORTABLE_RATE,
+        Some(rate) => rate.parse::<f64>().ok()
+            .filter(|rate| rate.is_finite() && *rate > 0.0)
+            .ok_or_else(|| ApiError::invalid_input("comfortable_rate must be a positive number").with_field("comfortable_rate"))?,
+    };
+
+    Ok(SpeechRateUpload { audio, subtitles: form.subtitles, text, language, comfortable_rate })
+}
+
+/// Measure an upload's speech rate per cue
+///
+/// CPU-bound: call from a blocking context.
+pub fn speech_rate(upload: SpeechRateUpload) -> Result<SpeechRateResponse, ApiError> {
+    let envelope = stages::time(Stage::AudioDecode, || upload.audio.envelope())?;
+
+    let cues = match (upload.subtitles, upload.text) {
+        (Some(subtitles), _) => {
+            let content = String::from_utf8_lossy(&subtitles.bytes);
+            let format = subtitles.filename.as_deref().and_then(subtitles::format_from_filename);
+            stages::time(Stage::SubtitleParse, || subtitles::parse(&content, format))?.cues
+        }
+        (None, text) => vec![Cue { index: 1, start: 0.0, end: envelope.duration(), text: text.unwrap_or_default(), ..Default::default() }],
+    };
+
+    Ok(speech_rate::measure(&cues, &upload.language, &envelope, upload.comfortable_rate))
+}
+
+/// Everything posted to the audio snippet endpoint
+///
+/// Form fields:
+/// - `audio` (file) or `audio_url` (text): required
+/// - `timings` (text, required): JSON word timings, either the `timings`
+///   array of an `/align` response or the whole response
+/// - `language` (text, required): for splitting `phrases` into words
+/// - `phrases` (text, optional): one per line; without them every distinct
+///   word gets a clip
+/// - `padding_ms` (text, optional): kept around each clip
+pub struct SnippetUpload {
+    pub audio: SpooledSource,
+    pub timings: Vec<WordTiming>,
+    pub language: String,
+    pub phrases: Vec<String>,
+    pub padding: f64,
+}
+
+/// Read a multipart/form-data body into a `SnippetUpload`
+pub async fn read_snippet_upload(payload: Multipart) -> Result<SnippetUpload, ApiError> {
+    let mut form = read_form(payload, &["timings", "language", "phrases", "padding_ms"]).await?;
+
+    let audio = form.audio.take().ok_or_else(|| ApiError::invalid_input("Missing 'audio' file or 'audio_url'").with_field("audio"))?;
+    let timings = take_timings(&mut form)?;
+    let language = form.take("language")
+        .ok_or_else(|| ApiError::invalid_input("Missing 'language' field").with_field("language"))?;
+    let phrases = form.take("phrases").unwrap_or_default()
+        .lines()
+        .map(str::trim)
+        .filter(|phrase| !phrase.is_empty())
+        .map(str::to_string)
+        .collect();
+    let padding = match form.take("padding_ms") {
+        None => snippets::DEFAULT_PADDING,
+        Some(padding) => padding.parse::<f64>().ok()
+            .filter(|padding| padding.is_finite() && *padding >= 0.0 && *padding <= 5000.0)
+            .ok_or_else(|| ApiError::invalid_input("padding_ms must be a number from 0 to 5000").with_field("padding_ms"))?
+            / 1000.0,
+    };
+
+    Ok(SnippetUpload { audio, timings, language, phrases, padding })
+}
+
+/// Everything posted to the pronunciation scoring endpoint
+///
+/// Form fields:
+/// - `audio` (file) or `audio_url` (text): the learner's recording of one
+///   cue, required
+/// - `timings` (text, required): the cue's reference word timings, as for
+///   `SnippetUpload`
+pub struct PronunciationUpload {
+    pub audio: SpooledSource,
+    pub timings: Vec<WordTiming>,
+}
+
+/// Read a multipart/form-data body into a `PronunciationUpload`
+pub async fn read_pronunciation_upload(payload: Multipart) -> Result<PronunciationUpload, ApiError> {
+    let mut form = read_form(payload, &["timings"]).await?;
+
+    let audio = form.audio.take().ok_or_else(|| ApiError::invalid_input("Missing 'audio' file or 'audio_url'").with_field("audio"))?;
+    let timings = take_timings(&mut form)?;
+
+    Ok(PronunciationUpload { audio, timings })
+}
+
+/// Score a learner's recording against the reference timings
+///
+/// CPU-bound: call from a blocking context.
+pub fn pronunciation(upload: PronunciationUpload) -> Result<PronunciationResponse, ApiError> {
+    let envelope = stages::time(Stage::AudioDecode, || upload.audio.envelope())?;
+    pronunciation::score(&upload.timings, &envelope)
+}
+
+/// Everything posted to the practice pack endpoint
+///
+/// Form fields:
+/// - `subtitles` (file, required): SRT, WebVTT or ASS/SSA
+/// - `audio` (file) or `audio_url` (text), optional: cut into a clip per cue
+/// - `language` (text, required)
+/// - `gloss` (text, optional): language to gloss the vocabulary in
+pub struct PracticePackUpload {
+    pub subtitles: UploadedFile,
+    pub audio: Option<SpooledSource>,
+    pub language: String,
+    pub gloss: Option<String>,
+}
+
+/// Read a multipart/form-data body into a `PracticePackUpload`
+pub async fn read_practice_pack_upload(payload: Multipart) -> Result<PracticePackUpload, ApiError> {
+    let mut form = read_form(payload, &["language", "gloss"]).await?;
+
+    let subtitles = form.subtitles.take().ok_or_else(|| ApiError::invalid_input("Missing 'subtitles' file").with_field("subtitles"))?;
+    let language = form.take("language")
+        .ok_or_else(|| ApiError::invalid_input("Missing 'language' field").with_field("language"))?;
+    let gloss = form.take("gloss");
+
+    let mut validator = Validator::default();
+    validator.language("language", &language);
+    if let Some(gloss) = &gloss {
+        validator.language("gloss", gloss);
+    }
+    validator.finish()?;
+    resources::restore([language.clone()]).await;
+
+    Ok(PracticePackUpload { subtitles, audio: form.audio, language, gloss })
+}
+
+/// Align an upload's cues, cut a clip of each from its audio, and build
+/// the pack without glosses, returning its media files in `media` order
+///
+/// CPU-bound: call from a blocking context.
+pub fn practice_pack(upload: PracticePackUpload) -> Result<(PracticePack, NamedTempFile), ApiError> {
+    let alignment = align_upload(AlignUpload {
+        subtitles: upload.subtitles,
+        audio: None,
+        language: upload.language.clone(),
+        overlap_policy: OverlapPolicy::default(),
+    })?;
+
+    if upload.audio.is_some() {
+        practice::check_clips(&alignment)?;
+    }
+    let clips = upload.audio
+        .map(|audio| {
+            let ranges: Vec<(f64, f64)> = alignment.cues.iter().map(|cue| (cue.start, cue.end)).collect();
+            stages::time(Stage::AudioDecode, || audio.clips(&ranges))
+        })
+        .transpose()?;
+
+    practice::assemble(alignment, &upload.language, clips)
+}
+
+/// The required `timings` field
+fn take_timings(form: &mut Form) -> Result<Vec<WordTiming>, ApiError> {
+    let timings = form.take("timings")
+        .ok_or_else(|| ApiError::invalid_input("Missing 'timings' field").with_field("timings"))?;
+    // Untagged, so serde's error says nothing about which field was wrong
+    parse_timings(&timings)
+        .map_err(|_| ApiError::invalid_input("Invalid timings: expected the JSON timings array of an /align response, or the whole response")
+            .with_field("timings"))
+}
+
+/// A bare timings array, or the object (an `/align` response) holding one
+fn parse_timings(json: &str) -> Result<Vec<WordTiming>, serde_json::Error> {
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum Timings {
+        Bare(Vec<WordTiming>),
+        Alignment { timings: Vec<WordTiming> },
+    }
+
+    serde_json::from_str(json).map(|timings| match timings {
+        Timings::Bare(timings) | Timings::Alignment { timings } => timings,
+    })
+}
+
+/// Cut a clip of each word or phrase in an upload, with the snippets that
+/// describe them
+///
+/// CPU-bound: call from a blocking context.
+pub fn audio_snippets(upload: SnippetUpload) -> Result<(AudioSnippetResponse, Vec<AudioBuffer>), ApiError> {
+    let (snippets, missing) = snippets::select(&upload.timings, &upload.phrases, &upload.language, upload.padding);
+    if snippets.len() > snippets::MAX_SNIPPETS {
+        return Err(ApiError::new(ErrorCode::PayloadTooLarge, format!("{} snippets requested, at most {} are cut at once; send phrases to choose",
+            snippets.len(), snippets::MAX_SNIPPETS)).with_field("timings"));
+    }
+    snippets::check_lengths(&snippets)?;
+
+    let ranges: Vec<(f64, f64)> = snippets.iter().map(|snippet| (snippet.start, snippet.end)).collect();
+    let clips = stages::time(Stage::AudioDecode, || upload.audio.clips(&ranges))?;
+    let sample_rate = clips.first().map_or(audio::TARGET_SAMPLE_RATE, |clip| clip.sample_rate);
+
+    Ok((AudioSnippetResponse { sample_rate, snippets, missing }, clips))
+}
+
+/// Parse, align and (when audio was uploaded) sanity-check an upload
+///
+/// CPU-bound: call from a blocking context.
+pub fn align_upload(upload: AlignUpload) -> Result<FileAlignmentResponse, ApiError> {
+    // The raw file is dropped once parsed; only the cues are kept
+    let parsed = {
+        let content = String::from_utf8_lossy(&upload.subtitles.bytes);
+        let format = upload.subtitles.filename.as_deref().and_then(subtitles::format_from_filename);
+        stages::time(Stage::SubtitleParse, || subtitles::parse(&content, format))?
+    };
+    drop(upload.subtitles);
+
+    let request = FileAlignmentRequest {
+        language: upload.language,
+        cues: parsed.cues,
+        overlap_policy: upload.overlap_policy,
+    };
+
+    let mut response = align_file(&request)?;
+
+    // Parse-time overlap warnings are superseded by how alignment resolved them
+    let parse_warnings = parsed.warnings.into_iter()
+        .filter(|warning| warning.kind != CueWarningKind::Overlap);
+    response.warnings.splice(0..0, parse_warnings);
+
+    if let Some(audio) = upload.audio {
+        let audio_duration = stages::time(Stage::AudioDecode, || audio.duration())?;
+
+        log::info!("Uploaded audio: {:.1}s (word timings remain text-based)", audio_duration);
+
+        for cue in &response.cues {
+            if cue.end > audio_duration {
+                response.warnings.push(CueWarning {
+                    cue_index: cue.index,
+                    kind: CueWarningKind::BeyondAudio,
+                    message: format!("Cue ends at {} but audio is only {:.3}s long", cue.end, audio_duration),
+                });
+            }
+        }
+    }
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_timings() {
+        let timing = r#"{"word": "hola", "start": 0.5, "end": 0.9, "confidence": 0.8, "char_start": 0, "char_end": 4}"#;
+        assert_eq!(parse_timings(&format!("[{}]", timing)).unwrap()[0].word, "hola");
+
+        let alignment = format!(r#"{{"text": "hola", "language": "es", "timings": [{}], "method": "forced_aligner"}}"#, timing);
+        assert_eq!(parse_timings(&alignment).unwrap().len(), 1);
+        assert!(parse_timings(r#"{"words": []}"#).is_err());
+    }
+
+    #[test]
+    fn test_align_upload_without_audio() {
+        let upload = AlignUpload {
+            subtitles: UploadedFile {
+                filename: Some("ep.srt".to_string()),
+                bytes: b"1\n00:00:01,000 --> 00:00:02,000\nHello there\n\nx\n00:00:03,000 --> 00:00:04,000\nGeneral Kenobi\n".to_vec(),
+            },
+            audio: None,
+            language: "en".to_string(),
+            overlap_policy: OverlapPolicy::Clamp,
+        };
+
+        let response = align_upload(upload).unwrap();
+
+        assert_eq!(response.cues.len(), 2);
+        assert_eq!(response.cues[1].timings.len(), 2);
+        assert_eq!(response.warnings[0].kind, CueWarningKind::MalformedIndex);
+    }
+}
@@ -0,0 +1,235 @@
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::error::{ApiError, ErrorCode};
+
+/// Tries after the first failed one, unless configured
+pub const DEFAULT_RETRIES: u32 = 2;
+
+/// Failed calls in a row that open a breaker, unless configured
+pub const DEFAULT_BREAKER_THRESHOLD: u32 = 5;
+
+/// Seconds an open breaker turns calls away, unless configured
+pub const DEFAULT_BREAKER_COOLDOWN_SECS: u64 = 30;
+
+/// Wait before the first retry; each later one waits twice as long
+const INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+
+/// How a failed call to a service we depend on is retried
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    pub initial_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy { retries: DEFAULT_RETRIES, initial_backoff: INITIAL_BACKOFF }
+    }
+}
+
+impl RetryPolicy {
+    /// Wait before retry `retry` (1-based): 250ms, 500ms, 1s, ...
+    fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff * 2u32.saturating_pow(retry.saturating_sub(1))
+    }
+}
+
+/// Why one try failed
+pub enum Failure {
+    /// Worth trying again: the network, a 5xx, a 408 or a 429
+    Transient(ApiError),
+    /// Trying again won't help, e.g. a 404 or a file over the size limit
+    Final(ApiError),
+}
+
+impl From<ApiError> for Failure {
+    fn from(error: ApiError) -> Self {
+        Failure::Final(error)
+    }
+}
+
+impl Failure {
+    /// An `upstream` error saying `what` failed, transient unless the
+    /// service answered with a client error other than 408 or 429
+    pub fn from_reqwest(e: reqwest::Error, what: &str) -> Self {
+        let error = ApiError::new(ErrorCode::Upstream, format!("{}: {}", what, e));
+        match e.status() {
+            Some(status) if status.is_client_error() && !matches!(status.as_u16(), 408 | 429) => Failure::Final(error),
+            _ => Failure::Transient(error),
+        }
+    }
+}
+
+/// Stops calling a service that keeps failing
+///
+/// After `threshold` failed calls in a row the breaker opens, and for
+/// `cooldown` every call fails at once with `upstream_unavailable` instead
+/// of tying up a request (and its retries) on a service that's down. Then
+/// calls go through again; one more failure reopens it, a success closes it.
+pub struct Breaker {
+    name: String,
+    threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Default)]
+struct BreakerState {
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl Breaker {
+    /// A closed breaker for the service called `name` (in errors and logs);
+    /// a `threshold` of 0 never opens
+    pub fn new(name: impl Into<String>, threshold: u32, cooldown: Duration) -> Self {
+        Breaker { name: name.into(), threshold, cooldown, state: Mutex::new(BreakerState::default()) }
+    }
+
+    /// Err while the breaker is open
+    pub fn check(&self) -> Result<(), ApiError> {
+        let state = self.state.lock().unwrap();
+        match state.open_until.map(|until| until.saturating_duration_since(Instant::now())) {
+            Some(left) if !left.is_zero() => Err(ApiError::new(ErrorCode::UpstreamUnavailable,
+                format!("{} is failing; not calling it for another {}s", self.name, left.as_secs() + 1))
+                .with_details(serde_json::json!({ "retry_after": left.as_secs() + 1 }))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Count a call's outcome, opening or closing the breaker
+    pub fn record(&self, success: bool) {
+        let mut state = self.state.lock().unwrap();
+        if success {
+            if state.failures >= self.threshold && self.threshold > 0 {
+                log::info!("{} is answering again", self.name);
+            }
+            *state = BreakerState::default();
+            return;
+        }
+
+        state.failures = state.failures.saturating_add(1);
+        if self.threshold > 0 && state.failures >= self.threshold {
+            log::warn!("{} failed {} times in a row; not calling it for {}s", self.name, state.failures, self.cooldown.as_secs());
+            state.open_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+
+    /// Whether the last call failed, so the breaker is worth keeping
+    pub fn is_failing(&self) -> bool {
+        self.state.lock().unwrap().failures > 0
+    }
+}
+
+/// Call a service through `breaker`, retrying transient failures
+///
+/// `attempt` makes one try. Only the call's final outcome counts towards
+/// the breaker, and only transient failures: a 404 says nothing about
+/// whether the service is up.
+pub async fn call<T, F, Fut>(breaker: &Breaker, policy: RetryPolicy, mut attempt: F) -> Result<T, ApiError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Failure>>,
+{
+    breaker.check()?;
+
+    let mut retry = 0;
+    loop {
+        match attempt().await {
+            Ok(value) => {
+                breaker.record(true);
+                return Ok(value);
+            }
+            Err(Failure::Final(error)) => return Err(error),
+            Err(Failure::Transient(error)) if retry < policy.retries => {
+                retry += 1;
+                log::warn!("{} (try {} of {}), retrying", error, retry, policy.retries + 1);
+                tokio::time::sleep(policy.backoff(retry)).await;
+            }
+            Err(Failure::Transient(error)) => {
+                breaker.record(false);
+                return Err(error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn policy(retries: u32) -> RetryPolicy {
+        RetryPolicy { retries, initial_backoff: Duration::from_millis(1) }
+    }
+
+    fn down() -> Failure {
+        Failure::Transient(ApiError::new(ErrorCode::Upstream, "down"))
+    }
+
+    #[test]
+    fn test_backoff_doubles() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.backoff(1), Duration::from_millis(250));
+        assert_eq!(policy.backoff(3), Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_retries_transient_failures() {
+        let breaker = Breaker::new("CDN", 1, Duration::from_secs(60));
+        let tries = AtomicU32::new(0);
+        let result = call(&breaker, policy(2), || async {
+            match tries.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => Err(down()),
+                _ => Ok("audio"),
+            }
+        }).await;
+        assert_eq!(result.unwrap(), "audio");
+        assert_eq!(tries.load(Ordering::SeqCst), 3);
+        assert!(!breaker.is_failing());
+
+        // Final failures are neither retried nor counted
+        tries.store(0, Ordering::SeqCst);
+        let result: Result<(), _> = call(&breaker, policy(2), || async {
+            tries.fetch_add(1, Ordering::SeqCst);
+            Err(Failure::Final(ApiError::not_found("gone")))
+        }).await;
+        assert_eq!(result.unwrap_err().code, ErrorCode::NotFound);
+        assert_eq!(tries.load(Ordering::SeqCst), 1);
+        assert!(breaker.check().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_breaker_opens_and_recovers() {
+        let breaker = Breaker::new("CDN", 2, Duration::from_millis(50));
+        for _ in 0..2 {
+            let result: Result<(), _> = call(&breaker, policy(0), || async { Err(down()) }).await;
+            assert_eq!(result.unwrap_err().code, ErrorCode::Upstream);
+        }
+
+        let tries = AtomicU32::new(0);
+        let result = call(&breaker, policy(0), || async {
+            tries.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }).await;
+        let error = result.unwrap_err();
+        assert_eq!(error.code, ErrorCode::UpstreamUnavailable);
+        assert!(error.details.is_some());
+        assert_eq!(tries.load(Ordering::SeqCst), 0);
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert!(call(&breaker, policy(0), || async { Ok::<_, Failure>(()) }).await.is_ok());
+        assert!(!breaker.is_failing());
+    }
+
+    #[test]
+    fn test_zero_threshold_never_opens() {
+        let breaker = Breaker::new("CDN", 0, Duration::from_secs(60));
+        for _ in 0..10 {
+            breaker.record(false);
+        }
+        assert!(breaker.check().is_ok());
+    }
+}
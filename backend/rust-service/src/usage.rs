@@ -0,0 +1,708 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use actix_web::dev::ServiceResponse;
+use actix_web::http::header;
+use actix_web::HttpResponse;
+use sqlx::any::AnyPoolOptions;
+use sqlx::{AnyPool, Row};
+
+use crate::auth::Client;
+use crate::error::{ApiError, ErrorCode, ErrorResponse};
+use crate::models::{UsageMetric, UsageReport};
+use crate::versioning;
+
+/// `/api` paths that are neither counted nor refused, so clients over
+/// quota can still check health and see their usage
+const EXEMPT_PATHS: &[&str] = &["health", "health/deep", "usage"];
+
+/// Quota entry that applies to every account without its own
+const DEFAULT_ACCOUNT: &str = "*";
+
+/// How often counts are written to the database and other instances'
+/// counts read back
+const SYNC_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Plain SQL that SQLite and Postgres both accept
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS usage_counts (
+    account TEXT NOT NULL,
+    period TEXT NOT NULL,
+    requests BIGINT NOT NULL,
+    characters BIGINT NOT NULL,
+    audio_seconds DOUBLE PRECISION NOT NULL,
+    PRIMARY KEY (account, period)
+)";
+
+static LEDGER: OnceLock<Ledger> = OnceLock::new();
+
+tokio::task_local! {
+    static METER: Meter;
+}
+
+/// What an account has used
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Usage {
+    pub requests: u64,
+    /// Characters of text submitted
+    pub characters: u64,
+    /// Length of the audio uploaded or fetched
+    pub audio_seconds: f64,
+}
+
+impl Usage {
+    fn plus(self, other: Usage) -> Usage {
+        Usage {
+            requests: self.requests + other.requests,
+            characters: self.characters + other.characters,
+            audio_seconds: self.audio_seconds + other.audio_seconds,
+        }
+    }
+
+    fn minus(self, other: Usage) -> Usage {
+        Usage {
+            requests: self.requests.saturating_sub(other.requests),
+            characters: self.characters.saturating_sub(other.characters),
+            audio_seconds: (self.audio_seconds - other.audio_seconds).max(0.0),
+        }
+    }
+}
+
+/// The account a request is counted against and what it has used so far
+///
+/// Shared, so work moved to a blocking thread can carry it along (see
+/// `item`).
+#[derive(Debug, Clone)]
+pub struct Meter {
+    account: String,
+    used: Arc<Mutex<Usage>>,
+}
+
+impl Meter {
+    fn new(account: String) -> Self {
+        Meter { account, used: Arc::default() }
+    }
+
+    fn add(&self, used: Usage) {
+        let mut usage = self.used.lock().unwrap();
+        *usage = usage.plus(used);
+    }
+
+    /// What to record: the request, and what it used if it succeeded
+    fn finish(&self, succeeded: bool) -> Usage {
+        let metered = std::mem::take(&mut *self.used.lock().unwrap());
+        match succeeded {
+            true => Usage { requests: 1, ..metered },
+            false => Usage { requests: 1, ..Usage::default() },
+        }
+    }
+}
+
+/// Monthly limits for one account; `None` (or 0 when configured) is unlimited
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Quota {
+    pub requests: Option<u64>,
+    pub characters: Option<u64>,
+    pub audio_seconds: Option<u64>,
+}
+
+impl Quota {
+    fn set(&mut self, metric: &str, limit: u64) -> Result<(), String> {
+        let slot = match metric {
+            "requests" => &mut self.requests,
+            "characters" => &mut self.characters,
+            "audio_seconds" => &mut self.audio_seconds,
+            other => return Err(format!("Unknown quota metric '{}', expected requests, characters or audio_seconds", other)),
+        };
+        *slot = Some(limit);
+        Ok(())
+    }
+
+    /// This quota's limits, falling back to `default` for the ones it doesn't set
+    fn or(self, default: Quota) -> Quota {
+        Quota {
+            requests: self.requests.or(default.requests),
+            characters: self.characters.or(default.characters),
+            audio_seconds: self.audio_seconds.or(default.audio_seconds),
+        }
+    }
+}
+
+/// Quotas by account, with `*` for everyone else
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Quotas {
+    default: Quota,
+    accounts: HashMap<String, Quota>,
+}
+
+impl Quotas {
+    /// Parse `USAGE_QUOTAS`, e.g. `*.requests=100000,partner-a.audio_seconds=36000`
+    ///
+    /// Each entry is `account.metric=limit`, the account being a tenant or
+    /// API key name. A limit of 0 lifts the default for that account.
+    pub fn parse(text: Option<&str>) -> Result<Self, String> {
+        let mut quotas = Quotas::default();
+
+        let entries = text.unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty());
+        for entry in entries {
+            let (key, limit) = entry.split_once('=')
+                .ok_or_else(|| format!("Invalid quota '{}', expected account.metric=limit", entry))?;
+            let (account, metric) = key.trim().rsplit_once('.')
+                .filter(|(account, _)| !account.is_empty())
+                .ok_or_else(|| format!("Invalid quota '{}', expected account.metric=limit", entry))?;
+            let limit: u64 = limit.trim().parse()
+                .map_err(|_| format!("Invalid quota limit for '{}'", key.trim()))?;
+
+            let quota = match account {
+                DEFAULT_ACCOUNT => &mut quotas.default,
+                account => quotas.accounts.entry(account.to_string()).or_default(),
+            };
+            quota.set(metric, limit)?;
+        }
+
+        Ok(quotas)
+    }
+
+    /// The limits `account` is held to
+    pub fn quota(&self, account: &str) -> Quota {
+        let quota = self.accounts.get(account).copied().unwrap_or_default().or(self.default);
+        let limit = |limit: Option<u64>| limit.filter(|limit| *limit > 0);
+        Quota {
+            requests: limit(quota.requests),
+            characters: limit(quota.characters),
+            audio_seconds: limit(quota.audio_seconds),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.default == Quota::default() && self.accounts.is_empty()
+    }
+}
+
+/// A calendar month, UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Month {
+    pub year: i64,
+    /// 1 to 12
+    pub month: u32,
+}
+
+impl Month {
+    /// The month containing `secs` (Unix seconds)
+    pub fn at(secs: u64) -> Month {
+        // Days to civil date, after Howard Hinnant's `civil_from_days`
+        let days = (secs / 86_400) as i64 + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+        Month { year, month }
+    }
+
+    /// Unix seconds at the start of the month
+    pub fn start(&self) -> u64 {
+        // Civil date to days, after Howard Hinnant's `days_from_civil`
+        let year = self.year - i64::from(self.month <= 2);
+        let era = year.div_euclid(400);
+        let year_of_era = year.rem_euclid(400);
+        let mp = (self.month as i64 + 9) % 12;
+        let day_of_year = (153 * mp + 2) / 5;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146_097 + day_of_era - 719_468;
+        days.max(0) as u64 * 86_400
+    }
+
+    pub fn next(&self) -> Month {
+        match self.month {
+            12 => Month { year: self.year + 1, month: 1 },
+            month => Month { year: self.year, month: month + 1 },
+        }
+    }
+}
+
+impl fmt::Display for Month {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{:02}", self.year, self.month)
+    }
+}
+
+/// An account's counts for one month
+#[derive(Debug)]
+struct Counted {
+    month: Month,
+    /// Every instance's usage, as of the last sync
+    shared: Usage,
+    /// Recorded here, being written to the database
+    syncing: Usage,
+    /// Recorded here since
+    unsynced: Usage,
+}
+
+impl Counted {
+    fn new(month: Month) -> Self {
+        Counted { month, shared: Usage::default(), syncing: Usage::default(), unsynced: Usage::default() }
+    }
+
+    fn total(&self) -> Usage {
+        self.shared.plus(self.syncing).plus(self.unsynced)
+    }
+}
+
+/// Each account's usage this month, held to its quota
+///
+/// With a database, counts are shared with the other instances and kept
+/// across restarts, synced every `SYNC_INTERVAL`.
+#[derive(Debug, Default)]
+pub struct Ledger {
+    quotas: Quotas,
+    accounts: Mutex<HashMap<String, Counted>>,
+    db: Option<AnyPool>,
+}
+
+impl Ledger {
+    pub fn new(quotas: Quotas) -> Self {
+        Ledger { quotas, accounts: Mutex::default(), db: None }
+    }
+
+    /// Keep counts in the database at `url`, creating its table if missing
+    pub async fn connect(quotas: Quotas, url: &str) -> Result<Self, String> {
+        sqlx::any::install_default_drivers();
+        let pool = AnyPoolOptions::new()
+            .max_connections(2)
+            .connect(url)
+            .await
+            .map_err(|e| format!("Can't open usage database: {}", e))?;
+        sqlx::query(SCHEMA).execute(&pool).await
+            .map_err(|e| format!("Can't create the usage_counts table: {}", e))?;
+        Ok(Ledger { quotas, accounts: Mutex::default(), db: Some(pool) })
+    }
+
+    /// `account`'s usage in the month of `now`, starting over in a new month
+    fn usage(&self, account: &str, now: u64) -> Usage {
+        let month = Month::at(now);
+        match self.accounts.lock().unwrap().get(account) {
+            Some(counted) if counted.month == month => counted.total(),
+            _ => Usage::default(),
+        }
+    }
+
+    /// Refuse a request from `account` if it has used up any of its quota
+    ///
+    /// Checked as requests arrive, against counts up to a sync old, so
+    /// requests in flight together or on other instances can take an
+    /// account a little over its limits.
+    pub fn admit(&self, account: &str, now: u64) -> Result<(), ApiError> {
+        let quota = self.quotas.quota(account);
+        let usage = self.usage(account, now);
+
+        let exceeded = [
+            ("requests", quota.requests, usage.requests as f64),
+            ("characters", quota.characters, usage.characters as f64),
+            ("audio_seconds", quota.audio_seconds, usage.audio_seconds),
+        ];
+        match exceeded.into_iter().find(|(_, limit, used)| limit.is_some_and(|limit| *used >= limit as f64)) {
+            Some((metric, Some(limit), used)) => Err(ApiError::new(ErrorCode::QuotaExceeded, format!("Monthly {} quota of {} used up", metric.replace('_', " "), limit))
+                .with_field(metric)
+                .with_details(serde_json::json!({
+                    "metric": metric,
+                    "limit": limit,
+                    "used": used.ceil() as u64,
+                    "resets_at": Month::at(now).next().start(),
+                }))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Add a request's usage to `account`
+    pub fn record(&self, account: &str, used: Usage, now: u64) {
+        let month = Month::at(now);
+        let mut accounts = self.accounts.lock().unwrap();
+        let counted = accounts.entry(account.to_string()).or_insert_with(|| Counted::new(month));
+        if counted.month != month {
+            *counted = Counted::new(month);
+        }
+        counted.unsynced = counted.unsynced.plus(used);
+    }
+
+    /// Add what was recorded here since the last sync to the database, then
+    /// read back every account's count for the month of `now`
+    pub async fn sync(&self, now: u64) -> Result<(), String> {
+        let Some(pool) = &self.db else {
+            return Ok(());
+        };
+
+        let pending: Vec<(String, Month, Usage)> = self.accounts.lock().unwrap().iter_mut()
+            .filter(|(_, counted)| counted.unsynced != Usage::default())
+            .map(|(account, counted)| {
+                let taken = std::mem::take(&mut counted.unsynced);
+                counted.syncing = counted.syncing.plus(taken);
+                (account.clone(), counted.month, taken)
+            })
+            .collect();
+
+        for (account, month, used) in pending {
+            let written = sqlx::query("INSERT INTO usage_counts (account, period, requests, characters, audio_seconds)
+                VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT (account, period) DO UPDATE SET
+                    requests = usage_counts.requests + excluded.requests,
+                    characters = usage_counts.characters + excluded.characters,
+                    audio_seconds = usage_counts.audio_seconds + excluded.audio_seconds")
+                .bind(&account)
+                .bind(month.to_string())
+                .bind(used.requests as i64)
+                .bind(used.characters as i64)
+                .bind(used.audio_seconds)
+                .execute(pool)
+                .await;
+            let mut accounts = self.accounts.lock().unwrap();
+            let Some(counted) = accounts.get_mut(&account).filter(|counted| counted.month == month) else {
+                continue;
+            };
+            counted.syncing = counted.syncing.minus(used);
+            match written {
+                // In `shared` until it's read back below
+                Ok(_) => counted.shared = counted.shared.plus(used),
+                // Tried again next time
+                Err(e) => {
+                    log::error!("Usage of '{}' not written: {}", account, e);
+                    counted.unsynced = counted.unsynced.plus(used);
+                }
+            }
+        }
+
+        let month = Month::at(now);
+        let rows = sqlx::query("SELECT account, requests, characters, audio_seconds FROM usage_counts WHERE period = $1")
+            .bind(month.to_string())
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Can't read usage: {}", e))?;
+        let mut accounts = self.accounts.lock().unwrap();
+        for row in rows {
+            let account: String = row.try_get("account").map_err(|e| e.to_string())?;
+            let shared = Usage {
+                requests: row.try_get::<i64, _>("requests").map_err(|e| e.to_string())? as u64,
+                characters: row.try_get::<i64, _>("characters").map_err(|e| e.to_string())? as u64,
+                audio_seconds: row.try_get("audio_seconds").map_err(|e| e.to_string())?,
+            };
+            let counted = accounts.entry(account).or_insert_with(|| Counted::new(month));
+            if counted.month != month {
+                *counted = Counted::new(month);
+            }
+            counted.shared = shared;
+        }
+        Ok(())
+    }
+
+    pub fn report(&self, account: &str, now: u64) -> UsageReport {
+        let quota = self.quotas.quota(account);
+        let usage = self.usage(account, now);
+        let month = Month::at(now);
+
+        UsageReport {
+            account: account.to_string(),
+            period: month.to_string(),
+            resets_at: month.next().start(),
+            requests: UsageMetric { used: usage.requests, limit: quota.requests },
+            characters: UsageMetric { used: usage.characters, limit: quota.characters },
+            audio_seconds: UsageMetric { used: usage.audio_seconds.ceil() as u64, limit: quota.audio_seconds },
+        }
+    }
+}
+
+/// Configure the quotas (`USAGE_QUOTAS`), keeping counts in the database
+/// at `database_url` if given (`JOB_DATABASE_URL`)
+pub async fn init(quotas: Quotas, database_url: Option<&str>) -> Result<(), String> {
+    if quotas.is_empty() {
+        log::info!("No USAGE_QUOTAS configured, usage is counted but not limited");
+    } else {
+        log::info!("Usage quotas: {} default, {} per-account", if quotas.default == Quota::default() { "no" } else { "a" }, quotas.accounts.len());
+    }
+
+    let counts = match database_url {
+        Some(url) => {
+            let counts = Ledger::connect(quotas, url).await?;
+            counts.sync(now()).await?;
+            counts
+        }
+        None => Ledger::new(quotas),
+    };
+    let shared = counts.db.is_some();
+    if LEDGER.set(counts).is_err() {
+        log::warn!("Usage quotas already initialised");
+        return Ok(());
+    }
+
+    if shared {
+        tokio::spawn(async {
+            let mut interval = tokio::time::interval(SYNC_INTERVAL);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                if let Err(e) = ledger().sync(now()).await {
+                    log::error!("Usage sync failed: {}", e);
+                }
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Write what this instance has counted since the last sync, before it stops
+pub async fn flush() {
+    if let Err(e) = ledger().sync(now()).await {
+        log::error!("Usage sync failed: {}", e);
+    }
+}
+
+fn ledger() -> &'static Ledger {
+    LEDGER.get_or_init(Ledger::default)
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Who a client's usage is counted against: its tenant, or its API key
+pub fn account(client: &Client) -> String {
+    client.tenant.clone().unwrap_or_else(|| client.name.clone())
+}
+
+/// The account a request to `path` is counted against, or why it's refused
+///
+/// Requests without an authenticated client (authentication off, public
+/// paths) aren't counted.
+pub fn admit(client: Option<&Client>, path: &str) -> Result<Option<String>, ApiError> {
+    let Some(client) = client else {
+        return Ok(None);
+    };
+    if versioning::api_path(path).is_none_or(|route| EXEMPT_PATHS.contains(&route)) {
+        return Ok(None);
+    }
+
+    let account = account(client);
+    ledger().admit(&account, now())?;
+    Ok(Some(account))
+}
+
+/// 429 with `Retry-After` until the quota resets
+pub fn exceeded_response(error: ApiError) -> HttpResponse {
+    log::warn!("{:?}: {}", error.code, error);
+    let now = now();
+    let retry_after = Month::at(now).next().start().saturating_sub(now);
+    HttpResponse::build(error.code.status())
+        .insert_header((header::RETRY_AFTER, retry_after.to_string()))
+        .json(ErrorResponse { error })
+}
+
+/// Run a request for `account`, counting it along with the characters and
+/// audio it turns out to use
+///
+/// Characters and audio only count when the request succeeds.
+pub async fn track<F, B>(account: Option<String>, fut: F) -> Result<ServiceResponse<B>, actix_web::Error>
+where
+    F: Future<Output = Result<ServiceResponse<B>, actix_web::Error>>,
+{
+    let Some(account) = account else {
+        return fut.await;
+    };
+
+    let meter = Meter::new(account);
+    let result = METER.scope(meter.clone(), fut).await;
+
+    let succeeded = result.as_ref().is_ok_and(|res| res.status().is_success());
+    ledger().record(&meter.account, meter.finish(succeeded), now());
+    result
+}
+
+/// The current request's meter, for work it hands on: batch items and the
+/// messages of a WebSocket session
+pub fn current() -> Option<Meter> {
+    METER.try_with(Meter::clone).ok()
+}
+
+/// Run one item of a batch, or one WebSocket message, as a request of its
+/// own against `meter`'s account: refused once a quota is used up, and
+/// counted with what it uses
+///
+/// Works on any thread; without a meter, `f` just runs.
+pub fn item<R>(meter: Option<&Meter>, f: impl FnOnce() -> Result<R, ApiError>) -> Result<R, ApiError> {
+    let Some(meter) = meter else {
+        return f();
+    };
+    ledger().admit(&meter.account, now())?;
+
+    let item = Meter::new(meter.account.clone());
+    let result = METER.sync_scope(item.clone(), f);
+    ledger().record(&item.account, item.finish(result.is_ok()), now());
+    result
+}
+
+/// `item`, for an item that awaits
+pub async fn item_async<R>(meter: Option<&Meter>, fut: impl Future<Output = Result<R, ApiError>>) -> Result<R, ApiError> {
+    let Some(meter) = meter else {
+        return fut.await;
+    };
+    ledger().admit(&meter.account, now())?;
+
+    let item = Meter::new(meter.account.clone());
+    let result = METER.scope(item.clone(), fut).await;
+    ledger().record(&item.account, item.finish(result.is_ok()), now());
+    result
+}
+
+/// Whether the current request's usage is being counted
+pub fn is_metering() -> bool {
+    METER.try_with(|_| ()).is_ok()
+}
+
+/// Run `f` without counting the text or audio it meters, e.g. to
+/// validate a request without doing its work
+pub fn unmetered<R>(f: impl FnOnce() -> R) -> R {
+    match current() {
+        Some(meter) => METER.sync_scope(Meter::new(meter.account), f),
+        None => f(),
+    }
+}
+
+/// Count `count` characters of text against the current request
+pub fn characters(count: usize) {
+    let _ = METER.try_with(|meter| meter.add(Usage { characters: count as u64, ..Usage::default() }));
+}
+
+/// Count `seconds` of audio against the current request
+pub fn audio_seconds(seconds: f64) {
+    let _ = METER.try_with(|meter| meter.add(Usage { audio_seconds: seconds, ..Usage::default() }));
+}
+
+/// `client`'s usage this month, for `GET /api/usage`
+pub fn report(client: &Client) -> UsageReport {
+    ledger().report(&account(client), now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2026-10-16T12:00:00Z
+    const NOW: u64 = 1_792_152_000;
+
+    #[test]
+    fn test_parse_quotas() {
+        let quotas = Quotas::parse(Some("*.requests=1000, *.characters=50000, partner.a.audio_seconds=3600, partner.a.requests=0")).unwrap();
+
+        assert_eq!(quotas.quota("node"), Quota { requests: Some(1000), characters: Some(50000), audio_seconds: None });
+        assert_eq!(quotas.quota("partner.a"), Quota { requests: None, characters: Some(50000), audio_seconds: Some(3600) });
+        assert!(Quotas::parse(None).unwrap().is_empty());
+
+        assert!(Quotas::parse(Some("requests=10")).is_err());
+        assert!(Quotas::parse(Some("*.minutes=10")).is_err());
+        assert!(Quotas::parse(Some("*.requests=lots")).is_err());
+    }
+
+    #[test]
+    fn test_months() {
+        let month = Month::at(NOW);
+        assert_eq!(month, Month { year: 2026, month: 10 });
+        assert_eq!(month.to_string(), "2026-10");
+        assert_eq!(month.start(), 1_790_812_800);
+        assert_eq!(Month::at(month.start()), month);
+        assert_eq!(Month::at(month.start() - 1), Month { year: 2026, month: 9 });
+        assert_eq!(Month { year: 2026, month: 12 }.next(), Month { year: 2027, month: 1 });
+        assert_eq!(Month { year: 2024, month: 3 }.start() - Month { year: 2024, month: 2 }.start(), 29 * 86_400);
+    }
+
+    #[test]
+    fn test_ledger_enforces_and_resets() {
+        let ledger = Ledger::new(Quotas::parse(Some("*.requests=2,*.characters=100")).unwrap());
+
+        ledger.record("node", Usage { requests: 1, characters: 40, audio_seconds: 1.5 }, NOW);
+        assert!(ledger.admit("node", NOW).is_ok());
+        ledger.record("node", Usage { requests: 1, characters: 40, audio_seconds: 0.0 }, NOW);
+
+        let error = ledger.admit("node", NOW).unwrap_err();
+        assert_eq!(error.code, ErrorCode::QuotaExceeded);
+        assert_eq!(error.field.as_deref(), Some("requests"));
+        assert!(ledger.admit("partner", NOW).is_ok());
+
+        let report = ledger.report("node", NOW);
+        assert_eq!(report.period, "2026-10");
+        assert_eq!((report.requests.used, report.requests.limit), (2, Some(2)));
+        assert_eq!((report.characters.used, report.audio_seconds.used), (80, 2));
+
+        let next_month = Month::at(NOW).next().start();
+        assert!(ledger.admit("node", next_month).is_ok());
+        assert_eq!(ledger.report("node", next_month).requests.used, 0);
+    }
+
+    #[tokio::test]
+    async fn test_metering_outside_a_request() {
+        assert!(!is_metering());
+        characters(10);
+
+        let meter = Meter::new("node".to_string());
+        METER.scope(meter.clone(), async {
+            assert!(is_metering());
+            characters(10);
+            audio_seconds(2.5);
+        }).await;
+        assert_eq!(meter.finish(true), Usage { requests: 1, characters: 10, audio_seconds: 2.5 });
+
+        METER.scope(meter.clone(), async {
+            characters(10);
+            unmetered(|| characters(1000));
+        }).await;
+        assert_eq!(meter.finish(true).characters, 10);
+        assert_eq!(meter.finish(false), Usage { requests: 1, ..Usage::default() });
+    }
+
+    #[test]
+    fn test_items_are_metered_on_any_thread() {
+        let meter = Meter::new("ws-client".to_string());
+        let used = std::thread::spawn(move || {
+            item(Some(&meter), || {
+                characters(42);
+                Ok(())
+            }).unwrap();
+            ledger().usage("ws-client", now())
+        }).join().unwrap();
+        assert_eq!((used.requests, used.characters), (1, 42));
+
+        // Without a meter, nothing is counted
+        item(None, || {
+            characters(42);
+            Ok(())
+        }).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_counts_shared_through_the_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = format!("sqlite://{}?mode=rwc", dir.path().join("usage.db").display());
+        let quotas = || Quotas::parse(Some("*.requests=3")).unwrap();
+        let first = Ledger::connect(quotas(), &url).await.unwrap();
+        let second = Ledger::connect(quotas(), &url).await.unwrap();
+
+        first.record("node", Usage { requests: 2, characters: 40, audio_seconds: 1.5 }, NOW);
+        first.sync(NOW).await.unwrap();
+        second.record("node", Usage { requests: 1, characters: 10, audio_seconds: 0.0 }, NOW);
+        assert!(second.admit("node", NOW).is_ok());
+        second.sync(NOW).await.unwrap();
+        assert_eq!(second.admit("node", NOW).unwrap_err().code, ErrorCode::QuotaExceeded);
+        first.sync(NOW).await.unwrap();
+        assert_eq!(first.usage("node", NOW), Usage { requests: 3, characters: 50, audio_seconds: 1.5 });
+
+        // A restarted instance starts from the shared counts
+        let restarted = Ledger::connect(quotas(), &url).await.unwrap();
+        restarted.sync(NOW).await.unwrap();
+        assert_eq!(restarted.report("node", NOW).requests.used, 3);
+        assert_eq!(restarted.report("node", Month::at(NOW).next().start()).requests.used, 0);
+    }
+}
@@ -0,0 +1,670 @@
+use std::sync::OnceLock;
+
+use serde::Serialize;
+
+use crate::audit;
+use crate::resources;
+use crate::usage;
+use crate::error::{ApiError, ErrorCode};
+use crate::i18n::{self, Message};
+use crate::known::MAX_KNOWN_WORDS;
+use crate::models::{AlignmentRequest, ClozeRequest, CollocationRequest, Cue, DifficultyRequest, DubFitRequest, DurationPredictionRequest, FileAlignmentRequest, HighlightRequest, JobRequest, KnownWords, KnownWordsList, LookupQuery, PhonemizeRequest, SyllabifyRequest, NormalizeRequest, RestructureRequest, ScoreRequest, TokenizeRequest, VocabularyRequest, WarmupRequest,
+    GenerateSubtitlesRequest, PairSubtitlesRequest, ResyncRequest, TranscriptDiffRequest, ValidateSubtitlesRequest};
+
+/// Longest text (in characters) accepted for one subtitle, unless configured
+pub const DEFAULT_MAX_TEXT_LENGTH: usize = 5000;
+
+/// Most items (batch-tokenize entries, cues) in one request, unless configured
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 2000;
+
+/// Stop collecting after this many problems, so a broken file doesn't
+/// produce a megabyte of errors
+const MAX_FIELD_ERRORS: usize = 50;
+
+/// ISO 639-1 codes
+const LANGUAGE_CODES: &[&str] = &[
+    "aa", "ab", "ae", "af", "ak", "am", "an", "ar", "as", "av", "ay", "az",
+    "ba", "be", "bg", "bh", "bi", "bm", "bn", "bo", "br", "bs",
+    "ca", "ce", "ch", "co", "cr", "cs", "cu", "cv", "cy",
+    "da", "de", "dv", "dz", "ee", "el", "en", "eo", "es", "et", "eu",
+    "fa", "ff", "fi", "fj", "fo", "fr", "fy", "ga", "gd", "gl", "gn", "gu", "gv",
+    "ha", "he", "hi", "ho", "hr", "ht", "hu", "hy", "hz",
+    "ia", "id", "ie", "ig", "ii", "ik", "io", "is", "it", "iu",
+    "ja", "jv", "ka", "kg", "ki", "kj", "kk", "kl", "km", "kn", "ko", "kr", "ks", "ku", "kv", "kw", "ky",
+    "la", "lb", "lg", "li", "ln", "lo", "lt", "lu", "lv",
+    "mg", "mh", "mi", "mk", "ml", "mn", "mr", "ms", "mt", "my",
+    "na", "nb", "nd", "ne", "ng", "nl", "nn", "no", "nr", "nv", "ny",
+    "oc", "oj", "om", "or", "os", "pa", "pi", "pl", "ps", "pt", "qu",
+    "rm", "rn", "ro", "ru", "rw",
+    "sa", "sc", "sd", "se", "sg", "si", "sk", "sl", "sm", "sn", "so", "sq", "sr", "ss", "st", "su", "sv", "sw",
+    "ta", "te", "tg", "th", "ti", "tk", "tl", "tn", "to", "tr", "ts", "tt", "tw", "ty",
+    "ug", "uk", "ur", "uz", "ve", "vi", "vo", "wa", "wo", "xh", "yi", "yo", "za", "zh", "zu",
+];
+
+/// Language names the tokenizer understands besides codes
+const LANGUAGE_NAMES: &[&str] = &["chinese", "japanese", "korean"];
+
+/// Size limits on request contents
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub max_text_length: usize,
+    pub max_batch_size: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits { max_text_length: DEFAULT_MAX_TEXT_LENGTH, max_batch_size: DEFAULT_MAX_BATCH_SIZE }
+    }
+}
+
+static LIMITS: OnceLock<Limits> = OnceLock::new();
+
+/// Configure the limits (`MAX_TEXT_LENGTH`, `MAX_BATCH_SIZE`)
+pub fn init(limits: Limits) {
+    log::info!("Accepting texts up to {} characters and batches of up to {} items", limits.max_text_length, limits.max_batch_size);
+
+    if LIMITS.set(limits).is_err() {
+        log::warn!("Validation limits already initialised");
+    }
+}
+
+fn limits() -> Limits {
+    LIMITS.get().copied().unwrap_or_default()
+}
+
+fn max_text_length() -> usize {
+    limits().max_text_length
+}
+
+/// Whether `language` is an ISO 639-1 code (optionally with a region or
+/// script, e.g. `pt-BR`, `zh_Hant`) or a language name we recognise
+pub fn is_known_language(language: &str) -> bool {
+    let language = language.trim().to_lowercase();
+    let primary = language.split(['-', '_']).next().unwrap_or_default();
+
+    LANGUAGE_NAMES.contains(&language.as_str()) || LANGUAGE_CODES.contains(&primary)
+}
+
+/// One invalid field, listed in the error's `details.fields`
+///
+/// `message` is in the request's `Accept-Language` where supported;
+/// `code` and `params` let clients word it themselves.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub code: &'static str,
+    #[serde(skip_serializing_if = "serde_json::Map::is_empty")]
+    pub params: serde_json::Map<String, serde_json::Value>,
+    pub message: String,
+}
+
+/// Collects every problem with a request, so clients can fix them all at once
+#[derive(Debug, Default)]
+pub struct Validator {
+    errors: Vec<FieldError>,
+    /// Too many items: reported as 413 instead of the field errors
+    too_large: Option<ApiError>,
+    /// Languages of valid requests, whose evicted data `admit` reads back
+    languages: Vec<String>,
+}
+
+impl Validator {
+    pub fn error(&mut self, field: impl Into<String>, message: Message) {
+        if self.errors.len() < MAX_FIELD_ERRORS {
+            self.errors.push(FieldError {
+                field: field.into(),
+                code: message.code(),
+                params: message.params(),
+                message: message.render(i18n::current()),
+            });
+        }
+    }
+
+    /// At most the configured batch size; returns whether the items are
+    /// worth checking one by one
+    pub fn batch(&mut self, field: &str, len: usize) -> bool {
+        let max = limits().max_batch_size;
+        if len > max && self.too_large.is_none() {
+            self.too_large = Some(ApiError::new(ErrorCode::PayloadTooLarge, format!("At most {} items per request, got {}", max, len))
+                .with_field(field)
+                .with_details(serde_json::json!({ "limit": max, "count": len })));
+        }
+        len <= max
+    }
+
+    /// Non-blank and within the configured length
+    pub fn text(&mut self, field: &str, text: &str) {
+        let length = text.chars().count();
+        if text.trim().is_empty() {
+            self.error(field, Message::Empty);
+        } else if length > max_text_length() {
+            self.error(field, Message::TooLong { max: max_text_length() });
+        }
+        usage::characters(length);
+    }
+
+    pub fn language(&mut self, field: &str, language: &str) {
+        if language.trim().is_empty() {
+            self.error(field, Message::Empty);
+        } else if !is_known_language(language) {
+            self.error(field, Message::UnknownLanguage { language: language.to_string() });
+        } else if field == "language" {
+            audit::language(language);
+            resources::touch(language);
+            self.languages.push(language.to_string());
+        }
+    }
+
+    /// A finite, non-negative number of seconds
+    pub fn time(&mut self, field: &str, seconds: f64) -> bool {
+        if !seconds.is_finite() {
+            self.error(field, Message::NotFinite);
+            false
+        } else if seconds < 0.0 {
+            self.error(field, Message::Negative);
+            false
+        } else {
+            true
+        }
+    }
+
+    /// Two valid times with `end` after `start`
+    pub fn span(&mut self, (start_field, start): (&str, f64), (end_field, end): (&str, f64)) {
+        let valid = self.time(start_field, start) & self.time(end_field, end);
+        if valid && end <= start {
+            self.error(end_field, Message::NotAfter { other: start_field.to_string() });
+        }
+    }
+
+    /// Cue times and texts
+    ///
+    /// Cues ending before they start are left to the aligner, which skips
+    /// them with a warning rather than failing the whole file.
+    pub fn cues(&mut self, field: &str, cues: &[Cue]) {
+        if cues.is_empty() {
+            self.error(field, Message::Empty);
+        }
+        if !self.batch(field, cues.len()) {
+            return;
+        }
+        for (i, cue) in cues.iter().enumerate() {
+            self.time(&format!("{}[{}].start", field, i), cue.start);
+            self.time(&format!("{}[{}].end", field, i), cue.end);
+            let length = cue.text.chars().count();
+            if length > max_text_length() {
+                self.error(format!("{}[{}].text", field, i), Message::TooLong { max: max_text_length() });
+            }
+            usage::characters(length);
+        }
+    }
+
+    /// Within `min..=max`, when given
+    pub fn range(&mut self, field: &str, value: Option<f64>, min: f64, max: f64) {
+        if let Some(value) = value
+            && !(min..=max).contains(&value)
+        {
+            self.error(field, Message::OutOfRange { min, max });
+        }
+    }
+
+    /// At most `MAX_KNOWN_WORDS` known words
+    pub fn known(&mut self, field: &str, known: &[String]) {
+        if known.len() > MAX_KNOWN_WORDS {
+            self.error(field, Message::TooManyWords { max: MAX_KNOWN_WORDS, count: known.len() });
+        }
+    }
+
+    /// Any finite number, negative included
+    pub fn finite(&mut self, field: &str, value: f64) {
+        if !value.is_finite() {
+            self.error(field, Message::NotFinite);
+        }
+    }
+
+    pub fn at_least(&mut self, field: &str, value: usize, min: usize) {
+        if value < min {
+            self.error(field, Message::AtLeast { min });
+        }
+    }
+
+    pub fn positive(&mut self, field: &str, value: Option<f64>) {
+        if let Some(value) = value
+            && !(value.is_finite() && value > 0.0)
+        {
+            self.error(field, Message::NotPositive);
+        }
+    }
+
+    /// 400 with every field error in `details.fields`, or `Ok` if there were none
+    ///
+    /// The message and `field` repeat the first error for clients that
+    /// only show one. An oversized batch is a 413 instead.
+    pub fn finish(self) -> Result<(), ApiError> {
+        if let Some(error) = self.too_large {
+            return Err(error);
+        }
+        let Some(first) = self.errors.first() else {
+            return Ok(());
+        };
+
+        let message = match self.errors.len() {
+            1 => first.message.clone(),
+            n => Message::AndMore { message: first.message.clone(), count: n - 1 }.render(i18n::current()),
+        };
+        Err(ApiError::invalid_input(message)
+            .with_field(first.field.clone())
+            .with_details(serde_json::json!({ "fields": self.errors })))
+    }
+}
+
+/// Requests checked before any work is done on them
+pub trait Validate {
+    fn check(&self, v: &mut Validator);
+
+    fn validate(&self) -> Result<(), ApiError> {
+        let mut validator = Validator::default();
+        self.check(&mut validator);
+        validator.finish()
+    }
+
+    /// `validate`, then read back any evicted data the request's language
+    /// uses (see `resources::restore`), for handlers about to read it
+    fn admit(&self) -> impl Future<Output = Result<(), ApiError>> {
+        let mut validator = Validator::default();
+        self.check(&mut validator);
+        let languages = std::mem::take(&mut validator.languages);
+        let validated = validator.finish();
+        async move {
+            validated?;
+            resources::restore(languages).await;
+            Ok(())
+        }
+    }
+}
+
+impl Validate for TokenizeRequest {
+    fn check(&self, v: &mut Validator) {
+        v.text("text", &self.text);
+        v.language("language", &self.language);
+        if let Some(known) = &self.known {
+            known.check(v);
+        }
+    }
+}
+
+impl Validate for HighlightRequest {
+    /// Timings must point into the text in order, without overlapping
+    fn check(&self, v: &mut Validator) {
+        v.text("text", &self.text);
+        let mut end = 0;
+        for (i, timing) in self.timings.iter().enumerate() {
+            v.time(&format!("timings[{}].start", i), timing.start);
+            v.time(&format!("timings[{}].end", i), timing.end);
+            let in_text = |offset: usize| self.text.is_char_boundary(offset);
+            if timing.char_start < end || timing.char_end < timing.char_start || !in_text(timing.char_start) || !in_text(timing.char_end) {
+                v.error(format!("timings[{}]", i), Message::OutOfOrder);
+            } else {
+                end = timing.char_end;
+            }
+        }
+    }
+}
+
+impl Validate for DifficultyRequest {
+    fn check(&self, v: &mut Validator) {
+        v.language("language", &self.language);
+        if let Some(text) = &self.text {
+            v.text("text", text);
+        }
+        if let Some(cues) = &self.cues {
+            v.cues("cues", cues);
+        }
+    }
+}
+
+impl Validate for CollocationRequest {
+    fn check(&self, v: &mut Validator) {
+        v.language("language", &self.language);
+        if let Some(cues) = &self.cues {
+            v.cues("cues", cues);
+        }
+        if !(2..=4).contains(&self.max_words) {
+            v.error("max_words", Message::OutOfRange { min: 2.0, max: 4.0 });
+        }
+        v.at_least("min_count", self.min_count, 1);
+        v.at_least("limit", self.limit, 1);
+    }
+}
+
+impl Validate for KnownWords {
+    fn check(&self, v: &mut Validator) {
+        match self {
+            KnownWords::Words(words) => v.known("known", words),
+            KnownWords::List(id) if id.trim().is_empty() => v.error("known", Message::EmptyListId),
+            KnownWords::List(_) => {}
+        }
+    }
+}
+
+impl Validate for KnownWordsList {
+    fn check(&self, v: &mut Validator) {
+        v.language("language", &self.language);
+        v.known("words", &self.words);
+    }
+}
+
+impl Validate for VocabularyRequest {
+    fn check(&self, v: &mut Validator) {
+        match &self.known {
+            Some(known) => known.check(v),
+            None if self.only_new => v.error("only_new", Message::NeedsKnownWords),
+            None => {}
+        }
+    }
+}
+
+impl Validate for PhonemizeRequest {
+    fn check(&self, v: &mut Validator) {
+        v.text("text", &self.text);
+        v.language("language", &self.language);
+    }
+}
+
+impl Validate for NormalizeRequest {
+    fn check(&self, v: &mut Validator) {
+        v.text("text", &self.text);
+        v.language("language", &self.language);
+    }
+}
+
+impl Validate for SyllabifyRequest {
+    fn check(&self, v: &mut Validator) {
+        v.text("text", &self.text);
+        v.language("language", &self.language);
+    }
+}
+
+impl Validate for LookupQuery {
+    fn check(&self, v: &mut Validator) {
+        v.text("word", &self.word);
+        v.language("language", &self.language);
+    }
+}
+
+impl Validate for AlignmentRequest {
+    fn check(&self, v: &mut Validator) {
+        if self.cues.is_empty() {
+            v.text("text", &self.text);
+            v.language("language", &self.language);
+            v.span(("subtitle_start", self.subtitle_start), ("subtitle_end", self.subtitle_end));
+        } else {
+            if !self.text.is_empty() {
+                v.error("text", Message::Excludes { other: "cues".to_string() });
+            }
+            v.language("language", &self.language);
+            if v.batch("cues", self.cues.len()) {
+                for (i, cue) in self.cues.iter().enumerate() {
+                    v.text(&format!("cues[{}].text", i), &cue.text);
+                    v.span((&format!("cues[{}].subtitle_start", i), cue.subtitle_start), (&format!("cues[{}].subtitle_end", i), cue.subtitle_end));
+                }
+            }
+        }
+        v.range("min_confidence", self.min_confidence, 0.0, 1.0);
+        v.positive("speaking_rate", self.speaking_rate);
+    }
+}
+
+impl Validate for ScoreRequest {
+    fn check(&self, v: &mut Validator) {
+        v.text("text", &self.text);
+        v.language("language", &self.language);
+        v.span(("subtitle_start", self.subtitle_start), ("subtitle_end", self.subtitle_end));
+        v.range("review_threshold", self.review_threshold, 0.0, 1.0);
+        for (i, timing) in self.timings.iter().enumerate() {
+            v.time(&format!("timings[{}].start", i), timing.start);
+            v.time(&format!("timings[{}].end", i), timing.end);
+        }
+    }
+}
+
+impl Validate for FileAlignmentRequest {
+    fn check(&self, v: &mut Validator) {
+        v.language("language", &self.language);
+        v.cues("cues", &self.cues);
+    }
+}
+
+impl Validate for DurationPredictionRequest {
+    fn check(&self, v: &mut Validator) {
+        v.text("text", &self.text);
+        v.language("language", &self.language);
+        v.positive("speaking_rate", self.speaking_rate);
+    }
+}
+
+impl Validate for DubFitRequest {
+    fn check(&self, v: &mut Validator) {
+        v.language("language", &self.language);
+        v.cues("cues", &self.cues);
+        v.positive("speaking_rate", self.speaking_rate);
+        v.positive("max_stretch", Some(self.max_stretch));
+        v.range("min_fill", Some(self.min_fill), 0.0, 1.0);
+    }
+}
+
+impl Validate for RestructureRequest {
+    fn check(&self, v: &mut Validator) {
+        v.language("language", &self.language);
+        v.cues("cues", &self.cues);
+        v.span(("min_duration", self.min_duration), ("max_duration", self.max_duration));
+        v.at_least("max_chars", self.max_chars, 1);
+        v.time("max_gap", self.max_gap);
+    }
+}
+
+impl Validate for GenerateSubtitlesRequest {
+    fn check(&self, v: &mut Validator) {
+        v.cues("cues", &self.cues);
+        if let Some(max_line_length) = self.max_line_length {
+            v.at_least("max_line_length", max_line_length, 1);
+        }
+        v.at_least("max_lines", self.max_lines, 1);
+        v.positive("max_cps", self.max_cps);
+    }
+}
+
+impl Validate for ResyncRequest {
+    fn check(&self, v: &mut Validator) {
+        match &self.sync_points {
+            Some(points) => for (i, point) in points.iter().enumerate() {
+                v.finite(&format!("sync_points[{}].subtitle_time", i), point.subtitle_time);
+                v.finite(&format!("sync_points[{}].video_time", i), point.video_time);
+            },
+            None => {
+                v.positive("scale", Some(self.scale));
+                v.finite("offset", self.offset);
+            }
+        }
+    }
+}
+
+impl Validate for ValidateSubtitlesRequest {
+    fn check(&self, v: &mut Validator) {
+        if let Some(cues) = &self.cues {
+            v.cues("cues", cues);
+        }
+        v.positive("max_cps", Some(self.max_cps));
+        v.at_least("max_lines", self.max_lines, 1);
+        v.at_least("max_line_length", self.max_line_length, 1);
+        v.time("min_gap", self.min_gap);
+    }
+}
+
+impl Validate for PairSubtitlesRequest {
+    fn check(&self, v: &mut Validator) {
+        v.range("min_overlap", Some(self.min_overlap), 0.0, 1.0);
+    }
+}
+
+impl Validate for ClozeRequest {
+    fn check(&self, v: &mut Validator) {
+        v.language("language", &self.language);
+        if let Some(cues) = &self.cues {
+            v.cues("cues", cues);
+        }
+        v.at_least("gaps_per_cue", self.gaps_per_cue, 1);
+    }
+}
+
+impl Validate for TranscriptDiffRequest {
+    fn check(&self, v: &mut Validator) {
+        v.language("language", &self.language);
+        if let Some(cues) = &self.cues {
+            v.cues("cues", cues);
+        }
+        if v.batch("transcript", self.transcript.len()) {
+            for (i, word) in self.transcript.iter().enumerate() {
+                v.time(&format!("transcript[{}].start", i), word.start);
+                v.time(&format!("transcript[{}].end", i), word.end);
+            }
+        }
+    }
+}
+
+impl Validate for WarmupRequest {
+    fn check(&self, v: &mut Validator) {
+        if self.languages.is_empty() {
+            v.error("languages", Message::Empty);
+        }
+        if v.batch("languages", self.languages.len()) {
+            for (i, language) in self.languages.iter().enumerate() {
+                v.language(&format!("languages[{}]", i), language);
+            }
+        }
+    }
+}
+
+impl Validate for JobRequest {
+    fn check(&self, v: &mut Validator) {
+        match self {
+            JobRequest::AlignFile(req) => req.check(v),
+            JobRequest::Align(req) => req.check(v),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::ResponseError;
+
+    fn alignment(text: &str, language: &str, start: f64, end: f64) -> AlignmentRequest {
+        AlignmentRequest {
+            text: text.to_string(),
+            language: language.to_string(),
+            subtitle_start: start,
+            subtitle_end: end,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_known_languages() {
+        assert!(is_known_language("en"));
+        assert!(is_known_language("pt-BR"));
+        assert!(is_known_language("zh_Hant"));
+        assert!(is_known_language("Japanese"));
+        assert!(!is_known_language("klingon"));
+        assert!(!is_known_language("xx"));
+    }
+
+    #[test]
+    fn test_valid_request_passes() {
+        assert!(alignment("Hello world", "en", 1.0, 2.5).validate().is_ok());
+    }
+
+    #[test]
+    fn test_reports_every_field() {
+        let error = alignment("  ", "xx", f64::NAN, 2.0).validate().unwrap_err();
+
+        assert_eq!(error.status_code(), actix_web::http::StatusCode::BAD_REQUEST);
+        assert_eq!(error.field.as_deref(), Some("text"));
+        let fields: Vec<&str> = error.details.as_ref().unwrap()["fields"].as_array().unwrap().iter()
+            .map(|f| f["field"].as_str().unwrap())
+            .collect();
+        assert_eq!(fields, vec!["text", "language", "subtitle_start"]);
+        let language = &error.details.as_ref().unwrap()["fields"][1];
+        assert_eq!(language["code"], "unknown_language");
+        assert_eq!(language["params"]["language"], "xx");
+    }
+
+    #[tokio::test]
+    async fn test_messages_follow_the_locale() {
+        let error = i18n::scope(i18n::Locale::De, async { alignment("", "en", 1.0, 0.5).validate().unwrap_err() }).await;
+        assert_eq!(error.message, "darf nicht leer sein (und 1 weitere ungültige Felder)");
+        assert_eq!(error.details.unwrap()["fields"][1]["message"], "muss nach subtitle_start liegen");
+    }
+
+    #[test]
+    fn test_end_must_follow_start() {
+        let error = alignment("Hello", "en", 2.0, 2.0).validate().unwrap_err();
+        assert_eq!(error.field.as_deref(), Some("subtitle_end"));
+
+        let error = alignment("Hello", "en", -1.0, 2.0).validate().unwrap_err();
+        assert_eq!(error.field.as_deref(), Some("subtitle_start"));
+    }
+
+    #[test]
+    fn test_batch_size_limit() {
+        let cue = Cue { start: 0.0, end: 1.0, text: "Hi".to_string(), ..Default::default() };
+        let request = FileAlignmentRequest {
+            language: "en".to_string(),
+            cues: vec![cue; DEFAULT_MAX_BATCH_SIZE + 1],
+            overlap_policy: Default::default(),
+        };
+        let error = request.validate().unwrap_err();
+
+        assert_eq!(error.status_code(), actix_web::http::StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(error.field.as_deref(), Some("cues"));
+    }
+
+    #[test]
+    fn test_text_length_limit() {
+        let long = "a".repeat(DEFAULT_MAX_TEXT_LENGTH + 1);
+        assert!(alignment(&long, "en", 0.0, 1.0).validate().is_err());
+    }
+
+    #[test]
+    fn test_cue_fields_are_indexed() {
+        let req = FileAlignmentRequest {
+            language: "en".to_string(),
+            cues: vec![
+                Cue { index: 1, start: 0.0, end: 1.0, text: "Fine".to_string(), style: None, actor: None },
+                Cue { index: 2, start: -3.0, end: 1.0, text: "Broken".to_string(), style: None, actor: None },
+            ],
+            overlap_policy: Default::default(),
+        };
+        assert_eq!(req.validate().unwrap_err().field.as_deref(), Some("cues[1].start"));
+    }
+
+    #[test]
+    fn test_subtitle_limits_must_be_usable() {
+        let request: GenerateSubtitlesRequest = serde_json::from_value(serde_json::json!({
+            "format": "srt",
+            "cues": [{ "index": 1, "start": 0.0, "end": 1.0, "text": "Hi" }],
+            "max_cps": 0.0,
+            "max_lines": 0,
+        })).unwrap();
+        let error = request.validate().unwrap_err();
+        let fields: Vec<&str> = error.details.as_ref().unwrap()["fields"].as_array().unwrap().iter()
+            .map(|f| f["field"].as_str().unwrap())
+            .collect();
+        assert_eq!(fields, vec!["max_lines", "max_cps"]);
+
+        let resync: ResyncRequest = serde_json::from_value(serde_json::json!({ "content": "", "scale": -1.0 })).unwrap();
+        assert_eq!(resync.validate().unwrap_err().field.as_deref(), Some("scale"));
+
+        let pair: PairSubtitlesRequest = serde_json::from_value(serde_json::json!({ "source": "", "target": "", "min_overlap": 2.0 })).unwrap();
+        assert_eq!(pair.validate().unwrap_err().field.as_deref(), Some("min_overlap"));
+    }
+}
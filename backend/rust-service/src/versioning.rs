@@ -0,0 +1,155 @@
+use actix_web::body::{self, BoxBody, MessageBody};
+use actix_web::dev::ServiceResponse;
+use actix_web::http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use actix_web::web::Bytes;
+
+use crate::error::{ApiError, ErrorCode};
+
+/// Version served at the unversioned `/api/...` aliases
+pub const CURRENT: &str = "v1";
+/// Request and response header naming the API version
+pub const VERSION_HEADER: &str = "api-version";
+
+const SUPPORTED: &[&str] = &["v1"];
+/// `Accept: application/vnd.dubdub.v1+json`
+const MEDIA_TYPE_PREFIX: &str = "application/vnd.dubdub.";
+
+/// The API version a request explicitly asks for, if any
+///
+/// # How it works:
+/// 1. A `/api/v1/...` path wins
+/// 2. Otherwise the `API-Version` header (`1` or `v1`)
+/// 3. Otherwise a vendor media type in `Accept`
+///
+/// Requests naming none get the unversioned aliases, which serve `CURRENT`.
+pub fn requested(path: &str, headers: &HeaderMap) -> Result<Option<&'static str>, ApiError> {
+    // Step 1: Path prefix
+    let segment = path.strip_prefix("/api/").and_then(|rest| rest.split('/').next());
+    if let Some(version) = segment.filter(|segment| is_version(segment)) {
+        return supported(version).map(Some);
+    }
+
+    // Step 2: Header
+    if let Some(value) = headers.get(VERSION_HEADER) {
+        let value = value.to_str().unwrap_or_default().trim();
+        return supported(&format!("v{}", value.trim_start_matches('v'))).map(Some);
+    }
+
+    // Step 3: Media type
+    let media_type = headers.get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .and_then(|accept| accept.split(',').find_map(|item| item.trim().strip_prefix(MEDIA_TYPE_PREFIX)))
+        .and_then(|rest| rest.split(['+', ';']).next());
+    media_type.map(supported).transpose()
+}
+
+/// The path below `/api` or `/api/v{n}`, e.g. `align/file` for both
+/// `/api/align/file` and `/api/v1/align/file`
+pub fn api_path(path: &str) -> Option<&str> {
+    let rest = path.strip_prefix("/api/")?;
+    match rest.split_once('/') {
+        Some((segment, rest)) if is_version(segment) => Some(rest),
+        _ => Some(rest),
+    }
+}
+
+fn is_version(segment: &str) -> bool {
+    segment.strip_prefix('v').is_some_and(|digits| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()))
+}
+
+fn supported(version: &str) -> Result<&'static str, ApiError> {
+    SUPPORTED.iter().copied().find(|supported| *supported == version).ok_or_else(|| {
+        ApiError::new(ErrorCode::UnsupportedVersion, format!("API version '{}' isn't supported", version))
+            .with_field(VERSION_HEADER)
+            .with_details(serde_json::json!({ "supported": SUPPORTED }))
+    })
+}
+
+/// Label a response with the version that produced it
+///
+/// Every response gets the `API-Version` header. When the client asked for
+/// a version, JSON object bodies also get an `api_version` field; the
+/// unversioned aliases keep their original bodies.
+pub async fn tag<B: MessageBody + 'static>(
+    res: ServiceResponse<B>,
+    requested: Option<&'static str>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let mut res = res.map_into_boxed_body();
+    res.headers_mut().insert(HeaderName::from_static(VERSION_HEADER), HeaderValue::from_static(requested.unwrap_or(CURRENT)));
+
+    let is_json = res.headers().get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("application/json"));
+    let Some(version) = requested.filter(|_| is_json) else {
+        return Ok(res);
+    };
+
+    let (req, res) = res.into_parts();
+    let (res, body) = res.into_parts();
+    let body = body::to_bytes(body).await.map_err(actix_web::error::ErrorInternalServerError)?;
+    let body = with_version_field(&body, version).unwrap_or(body);
+    Ok(ServiceResponse::new(req, res.set_body(BoxBody::new(body))))
+}
+
+/// The `API-Version` header for errors rendered outside the handlers
+/// (e.g. timeouts)
+pub fn tag_error(mut err: actix_web::Error, requested: Option<&'static str>) -> actix_web::Error {
+    err.add_response_mapper(move |mut res| {
+        res.headers_mut().insert(HeaderName::from_static(VERSION_HEADER), HeaderValue::from_static(requested.unwrap_or(CURRENT)));
+        res
+    });
+    err
+}
+
+/// Splice `"api_version"` in as the first key, keeping the rest byte-for-byte
+fn with_version_field(body: &[u8], version: &str) -> Option<Bytes> {
+    let rest = body.trim_ascii_start().strip_prefix(b"{")?;
+    let separator = if rest.trim_ascii_start().starts_with(b"}") { "" } else { "," };
+
+    let mut tagged = format!("{{\"api_version\":\"{}\"{}", version, separator).into_bytes();
+    tagged.extend_from_slice(rest);
+    Some(Bytes::from(tagged))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    fn headers(name: &'static str, value: &'static str) -> HeaderMap {
+        TestRequest::default().insert_header((name, value)).to_http_request().headers().clone()
+    }
+
+    #[test]
+    fn test_requested_from_path() {
+        assert_eq!(requested("/api/v1/tokenize", &HeaderMap::new()).unwrap(), Some("v1"));
+        assert_eq!(requested("/api/tokenize", &HeaderMap::new()).unwrap(), None);
+        assert_eq!(requested("/api/vocabulary", &HeaderMap::new()).unwrap(), None);
+
+        let error = requested("/api/v2/tokenize", &HeaderMap::new()).unwrap_err();
+        assert_eq!(error.code, ErrorCode::UnsupportedVersion);
+    }
+
+    #[test]
+    fn test_requested_from_headers() {
+        assert_eq!(requested("/api/align", &headers(VERSION_HEADER, "1")).unwrap(), Some("v1"));
+        assert_eq!(requested("/api/align", &headers("accept", "application/vnd.dubdub.v1+json")).unwrap(), Some("v1"));
+        assert_eq!(requested("/api/align", &headers("accept", "application/json")).unwrap(), None);
+        assert!(requested("/api/align", &headers(VERSION_HEADER, "3")).is_err());
+    }
+
+    #[test]
+    fn test_api_path() {
+        assert_eq!(api_path("/api/v1/align/file"), Some("align/file"));
+        assert_eq!(api_path("/api/align/file"), Some("align/file"));
+        assert_eq!(api_path("/api/vocabulary"), Some("vocabulary"));
+        assert_eq!(api_path("/healthz"), None);
+    }
+
+    #[test]
+    fn test_version_field() {
+        assert_eq!(with_version_field(br#"{"tokens":[]}"#, "v1").unwrap(), Bytes::from(r#"{"api_version":"v1","tokens":[]}"#));
+        assert_eq!(with_version_field(b"{}", "v1").unwrap(), Bytes::from(r#"{"api_version":"v1"}"#));
+        assert!(with_version_field(b"[1,2]", "v1").is_none());
+    }
+}
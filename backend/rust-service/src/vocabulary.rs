@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+
+use crate::aligner::align_weighted;
+use crate::frequency;
+use crate::models::{AlignmentRequest, Cue, FrequencyBand, VocabEntry};
+
+/// Days before a new card's first review, unless it's worth waiting longer
+const BASE_INTERVAL: u32 = 1;
+
+/// Cues a word must appear in for the file itself to count as reviewing it
+const REVIEWING_CONTEXTS: usize = 3;
+
+/// Every distinct word of a subtitle file, in order of first appearance
+///
+/// # How it works:
+/// 1. Align each cue so every word gets a timestamp
+/// 2. Group words by lemma, collecting surface forms and counting uses
+/// 3. Look up each lemma's rank in the language's frequency list
+/// 4. Suggest each word's first review interval
+///
+/// There is no lemmatizer yet, so the lemma is the lowercased word:
+/// "Run" and "run" group together, "ran" does not.
+pub fn extract(cues: &[Cue], language: &str) -> (Vec<VocabEntry>, usize) {
+    let lists = frequency::lists();
+    let list = lists.get(language);
+    let mut entries: Vec<VocabEntry> = Vec::new();
+    let mut by_lemma: HashMap<String, usize> = HashMap::new();
+    // Position in `cues` each entry was last seen at
+    let mut last_seen: Vec<usize> = Vec::new();
+    let mut n_tokens = 0;
+
+    for (at, cue) in cues.iter().enumerate() {
+        // Step 1: Timestamps for every word
+        let request = AlignmentRequest {
+            text: cue.text.clone(),
+            language: language.to_string(),
+            subtitle_start: cue.start,
+            subtitle_end: cue.end,
+            ..Default::default()
+        };
+        let timings = match align_weighted(&request) {
+            Ok(response) => response.timings,
+            Err(e) => {
+                log::debug!("Skipping cue {} for vocabulary: {}", cue.index, e);
+                continue;
+            }
+        };
+
+        // Step 2: Group by lemma
+        for timing in timings.iter().filter(|t| is_word(&t.word)) {
+            n_tokens += 1;
+            let lemma = lemma(&timing.word);
+
+            match by_lemma.get(&lemma) {
+                Some(&i) => {
+                    let entry = &mut entries[i];
+                    entry.count += 1;
+                    if !entry.forms.contains(&timing.word) {
+                        entry.forms.push(timing.word.clone());
+                    }
+                    entry.last_start = timing.start;
+                    entry.last_cue_index = cue.index;
+                    if last_seen[i] != at {
+                        last_seen[i] = at;
+                        entry.n_contexts += 1;
+                    }
+                }
+                None => {
+                    by_lemma.insert(lemma.clone(), entries.len());
+                    last_seen.push(at);
+                    // Step 3: Global frequency
+                    let rank = list.rank(&lemma);
+                    entries.push(VocabEntry {
+                        rank,
+                        band: FrequencyBand::of(rank),
+                        lemma,
+                        forms: vec![timing.word.clone()],
+                        count: 1,
+                        first_start: timing.start,
+                        first_cue_index: cue.index,
+                        last_start: timing.start,
+                        last_cue_index: cue.index,
+                        n_contexts: 1,
+                        initial_interval: BASE_INTERVAL,
+                        context: cue.text.split_whitespace().collect::<Vec<_>>().join(" "),
+                        known: None,
+                    });
+                }
+            }
+        }
+    }
+
+    // Step 4: Review intervals
+    for entry in &mut entries {
+        entry.initial_interval = initial_interval(entry.band, entry.n_contexts);
+    }
+
+    (entries, n_tokens)
+}
+
+/// Days until a new card's first review
+///
+/// One day, doubled for a word the file itself repeats in enough cues
+/// that watching reviews it, and doubled again for one of the 1000 most
+/// frequent words, met everywhere else too.
+fn initial_interval(band: FrequencyBand, n_contexts: usize) -> u32 {
+    let mut interval = BASE_INTERVAL;
+    if n_contexts >= REVIEWING_CONTEXTS {
+        interval *= 2;
+    }
+    if band == FrequencyBand::K1 {
+        interval *= 2;
+    }
+    interval
+}
+
+fn lemma(word: &str) -> String {
+    word.to_lowercase().replace('’', "'")
+}
+
+/// Skip numbers and stray symbols
+fn is_word(token: &str) -> bool {
+    token.chars().any(char::is_alphabetic)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::cue;
+
+    #[test]
+    fn test_groups_surface_forms() {
+        let cues = vec![
+            cue(1, 0.0, 2.0, "Run, Forrest!"),
+            cue(2, 5.0, 6.0, "run run 42"),
+        ];
+        let (entries, n_tokens) = extract(&cues, "xx");
+
+        assert_eq!(n_tokens, 4);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].lemma, "run");
+        assert_eq!(entries[0].forms, vec!["Run", "run"]);
+        assert_eq!(entries[0].count, 3);
+        assert_eq!(entries[0].first_start, 0.0);
+        assert_eq!((entries[0].last_cue_index, entries[0].n_contexts), (2, 2));
+        assert!(entries[0].last_start > 5.0);
+        assert_eq!(entries[0].band, FrequencyBand::Unlisted);
+        assert_eq!(entries[1].lemma, "forrest");
+        assert_eq!(entries[1].first_cue_index, 1);
+        assert!(entries[1].first_start > 0.5);
+        assert_eq!(entries[1].context, "Run, Forrest!");
+    }
+
+    #[test]
+    fn test_initial_interval() {
+        assert_eq!(initial_interval(FrequencyBand::Rare, 1), 1);
+        assert_eq!(initial_interval(FrequencyBand::Rare, 3), 2);
+        assert_eq!(initial_interval(FrequencyBand::K1, 1), 2);
+        assert_eq!(initial_interval(FrequencyBand::K1, 5), 4);
+    }
+
+    #[test]
+    fn test_unknown_language_has_no_ranks() {
+        let (entries, _) = extract(&[cue(1, 0.0, 1.0, "hello")], "xx");
+        assert_eq!(entries[0].rank, None);
+    }
+}
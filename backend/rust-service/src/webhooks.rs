@@ -0,0 +1,170 @@
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::egress;
+use crate::error::ApiError;
+use crate::models::JobStatus;
+
+/// Header carrying `sha256=<hex HMAC of "{timestamp}.{body}">`
+pub const SIGNATURE_HEADER: &str = "X-Dubdub-Signature";
+
+/// Header carrying the Unix time the payload was signed at
+pub const TIMESTAMP_HEADER: &str = "X-Dubdub-Timestamp";
+
+const MAX_ATTEMPTS: u32 = 5;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Shared secret for signing callbacks, configured at startup
+static SECRET: OnceLock<Option<String>> = OnceLock::new();
+
+/// Where callbacks may be delivered, configured at startup
+static EGRESS: OnceLock<egress::Policy> = OnceLock::new();
+
+/// Configure the signing secret (`WEBHOOK_SECRET`) and which private hosts
+/// callbacks may reach (`EGRESS_ALLOWED_HOSTS`)
+pub fn init(secret: Option<String>, egress: egress::Policy) {
+    if secret.is_none() {
+        log::warn!("WEBHOOK_SECRET not set, job callbacks will be unsigned");
+    }
+    if SECRET.set(secret).is_err() || EGRESS.set(egress).is_err() {
+        log::warn!("Webhook settings already initialised");
+    }
+}
+
+fn secret() -> Option<&'static str> {
+    SECRET.get().and_then(|secret| secret.as_deref())
+}
+
+fn egress() -> &'static egress::Policy {
+    EGRESS.get_or_init(egress::Policy::default)
+}
+
+/// Check a callback URL when the job is submitted rather than when it
+/// finishes: http(s), to a host resolving only to public addresses
+pub async fn validate_url(url: &str) -> Result<(), ApiError> {
+    let parsed = reqwest::Url::parse(url)
+        .map_err(|e| ApiError::invalid_input(format!("Invalid URL: {}", e)).with_field("callback_url"))?;
+
+    egress().check_resolved(&parsed).await
+        .map_err(|message| ApiError::invalid_input(message).with_field("callback_url"))
+}
+
+/// `sha256=<hex>` signature over `"{timestamp}.{body}"`
+///
+/// Including the timestamp lets receivers reject replayed deliveries.
+pub fn sign(secret: &str, timestamp: u64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Wait before retry `attempt` (1-based): 1s, 2s, 4s, ...
+fn backoff(attempt: u32) -> Duration {
+    INITIAL_BACKOFF * 2u32.pow(attempt.saturating_sub(1))
+}
+
+/// POST a finished job's status (including its result) to `url`
+///
+/// # How it works:
+/// 1. Serialize the status once and sign it with the configured secret
+/// 2. Retry network errors, 5xx, 408 and 429 with exponential backoff,
+///    up to `MAX_ATTEMPTS`
+/// 3. Give up on other 4xx answers, since retrying won't change them
+///
+/// Failures are logged; the job itself stays available for polling either way.
+pub async fn deliver(url: &str, status: &JobStatus) {
+    // Step 1: Serialize and sign
+    let body = match serde_json::to_vec(status) {
+        Ok(body) => body,
+        Err(e) => {
+            log::error!("Callback for job {} not sent: {}", status.id, e);
+            return;
+        }
+    };
+    // The address is checked again as it's connected to, since the name
+    // may point elsewhere by now; redirects aren't followed at all
+    let builder = egress().apply(reqwest::Client::builder().timeout(REQUEST_TIMEOUT))
+        .redirect(reqwest::redirect::Policy::none());
+    let client = match builder.build() {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Callback for job {} not sent: {}", status.id, e);
+            return;
+        }
+    };
+
+    // Step 2: Deliver with retries
+    for attempt in 1..=MAX_ATTEMPTS {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+
+        let mut request = client.post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(TIMESTAMP_HEADER, timestamp)
+            .body(body.clone());
+        if let Some(secret) = secret() {
+            request = request.header(SIGNATURE_HEADER, sign(secret, timestamp, &body));
+        }
+
+        let error = match request.send().await {
+            Ok(response) if response.status().is_success() => {
+                log::info!("Callback for job {} delivered to {}", status.id, url);
+                return;
+            }
+            // Step 3: Client errors are final
+            Ok(response) if response.status().is_client_error()
+                && !matches!(response.status().as_u16(), 408 | 429) =>
+            {
+                log::error!("Callback for job {} rejected by {}: {}", status.id, url, response.status());
+                return;
+            }
+            Ok(response) => response.status().to_string(),
+            Err(e) => e.to_string(),
+        };
+
+        if attempt < MAX_ATTEMPTS {
+            log::warn!("Callback for job {} failed (attempt {}): {}, retrying", status.id, attempt, error);
+            tokio::time::sleep(backoff(attempt)).await;
+        } else {
+            log::error!("Callback for job {} failed after {} attempts: {}", status.id, attempt, error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_is_stable_and_keyed() {
+        let signature = sign("secret", 1700000000, b"{\"id\":\"1\"}");
+
+        assert!(signature.starts_with("sha256="));
+        assert_eq!(signature.len(), "sha256=".len() + 64);
+        assert_eq!(signature, sign("secret", 1700000000, b"{\"id\":\"1\"}"));
+        assert_ne!(signature, sign("other", 1700000000, b"{\"id\":\"1\"}"));
+        assert_ne!(signature, sign("secret", 1700000001, b"{\"id\":\"1\"}"));
+    }
+
+    #[tokio::test]
+    async fn test_validate_url() {
+        assert!(validate_url("https://93.184.216.34/hooks/dubdub").await.is_ok());
+        assert!(validate_url("ftp://example.com").await.is_err());
+        assert!(validate_url("not a url").await.is_err());
+        assert!(validate_url("http://169.254.169.254/latest/meta-data").await.is_err());
+        assert!(validate_url("http://localhost:3000/hooks").await.is_err());
+    }
+
+    #[test]
+    fn test_backoff_doubles() {
+        assert_eq!(backoff(1), Duration::from_secs(1));
+        assert_eq!(backoff(4), Duration::from_secs(8));
+    }
+}
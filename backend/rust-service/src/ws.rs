@@ -0,0 +1,156 @@
+use actix_ws::{AggregatedMessage, Session};
+use futures::StreamExt;
+
+use crate::aligner::align_smart;
+use crate::cache;
+use crate::error::ApiError;
+use crate::models::{AlignmentRequest, TokenizeRequest, WsClientMessage, WsServerMessage};
+use crate::tokenizer::tokenize_text;
+use crate::usage::{self, Meter};
+use crate::validation::Validate;
+
+/// Largest message a client may send (a long cue with room to spare)
+const MAX_MESSAGE_BYTES: usize = 64 * 1024;
+
+/// Per-connection state
+#[derive(Debug, Default)]
+pub struct SessionState {
+    /// Used when a message doesn't name its language
+    language: Option<String>,
+    /// The account the session's tokenize and align messages are each
+    /// counted against, as requests of their own
+    meter: Option<Meter>,
+}
+
+/// Drive one WebSocket connection until the client goes away
+///
+/// Every text frame is a JSON `WsClientMessage` and gets exactly one
+/// `WsServerMessage` back, so clients can pipeline cues without waiting.
+pub async fn run(mut session: Session, stream: actix_ws::MessageStream, meter: Option<Meter>) {
+    let mut stream = stream
+        .max_frame_size(MAX_MESSAGE_BYTES)
+        .aggregate_continuations()
+        .max_continuation_size(MAX_MESSAGE_BYTES);
+    let mut state = SessionState { meter, ..Default::default() };
+
+    while let Some(message) = stream.next().await {
+        let reply = match message {
+            Ok(AggregatedMessage::Text(text)) => handle_message(&mut state, &text),
+            Ok(AggregatedMessage::Binary(_)) => error_message(None, ApiError::unsupported("Binary messages are not supported, send JSON text")),
+            Ok(AggregatedMessage::Ping(bytes)) => {
+                if session.pong(&bytes).await.is_err() {
+                    return;
+                }
+                continue;
+            }
+            Ok(AggregatedMessage::Pong(_)) => continue,
+            Ok(AggregatedMessage::Close(reason)) => {
+                let _ = session.close(reason).await;
+                return;
+            }
+            Err(e) => {
+                log::warn!("WebSocket protocol error: {}", e);
+                break;
+            }
+        };
+
+        let Ok(json) = serde_json::to_string(&reply) else {
+            continue;
+        };
+        if session.text(json).await.is_err() {
+            return;
+        }
+    }
+
+    let _ = session.close(None).await;
+}
+
+/// Answer one client message
+pub fn handle_message(state: &mut SessionState, text: &str) -> WsServerMessage {
+    let message: WsClientMessage = match serde_json::from_str(text) {
+        Ok(message) => message,
+        Err(e) => return error_message(None, ApiError::invalid_input(format!("Invalid message: {}", e))),
+    };
+
+    let language = |requested: Option<String>, state: &SessionState| {
+        requested.or_else(|| state.language.clone())
+            .ok_or_else(|| ApiError::invalid_input("No language given and no session language set").with_field("language"))
+    };
+
+    match message {
+        WsClientMessage::Start { language } => {
+            state.language = Some(language.clone());
+            WsServerMessage::Ready { language }
+        }
+        WsClientMessage::Tokenize { id, text, language: requested } => {
+            let result = language(requested, state).and_then(|language| usage::item(state.meter.as_ref(), || {
+                let req = TokenizeRequest { text, language, known: None };
+                req.validate()?;
+                tokenize_text(&req.text, &req.language)
+            }));
+            match result {
+                Ok(response) => WsServerMessage::Tokens { id, response },
+                Err(e) => error_message(Some(id), e),
+            }
+        }
+        WsClientMessage::Align { id, text, language: requested, start, end } => {
+            let result = language(requested, state).and_then(|language| usage::item(state.meter.as_ref(), || {
+                let req = AlignmentRequest {
+                    text,
+                    language,
+                    subtitle_start: start,
+                    subtitle_end: end,
+                    ..Default::default()
+                };
+                req.validate()?;
+                cache::get_or_align(&req, align_smart)
+            }));
+            match result {
+                Ok(response) => WsServerMessage::Alignment { id, response },
+                Err(e) => error_message(Some(id), e),
+            }
+        }
+    }
+}
+
+fn error_message(id: Option<String>, error: ApiError) -> WsServerMessage {
+    WsServerMessage::Error { id, code: error.code, message: error.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_language() {
+        let mut state = SessionState::default();
+
+        let reply = handle_message(&mut state, r#"{"type": "tokenize", "id": "1", "text": "Hola"}"#);
+        assert!(matches!(reply, WsServerMessage::Error { id: Some(_), .. }));
+
+        let reply = handle_message(&mut state, r#"{"type": "start", "language": "es"}"#);
+        assert!(matches!(reply, WsServerMessage::Ready { .. }));
+
+        let reply = handle_message(&mut state, r#"{"type": "tokenize", "id": "2", "text": "Hola amigo"}"#);
+        let WsServerMessage::Tokens { id, response } = reply else { panic!("expected tokens") };
+        assert_eq!(id, "2");
+        assert_eq!(response.tokens, vec!["Hola", "amigo"]);
+    }
+
+    #[test]
+    fn test_align_cue() {
+        let mut state = SessionState::default();
+        let reply = handle_message(&mut state,
+            r#"{"type": "align", "id": "c1", "text": "Hello world", "language": "en", "start": 1.0, "end": 2.0}"#);
+
+        let WsServerMessage::Alignment { response, .. } = reply else { panic!("expected alignment") };
+        assert_eq!(response.timings.len(), 2);
+        assert_eq!(response.timings[1].end, 2.0);
+    }
+
+    #[test]
+    fn test_invalid_message() {
+        let reply = handle_message(&mut SessionState::default(), "not json");
+        assert!(matches!(reply, WsServerMessage::Error { id: None, .. }));
+    }
+}